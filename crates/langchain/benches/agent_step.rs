@@ -0,0 +1,209 @@
+//! 长对话下每个 super-step 的状态开销
+//!
+//! `MessagesState` 使用持久化的 `im::Vector<Arc<Message>>`，克隆只复制树的根节点，
+//! 消息本身在各个快照间共享。`state_clone/vec_deep` 是逐条深拷贝消息的对照组。
+//! `label_intern` 衡量路由每步驻留标签的开销。
+//!
+//! `graph_run/checkpointed_*` 在每步保存检查点。`checkpoint_save` 对比保存时取得父检查点 id
+//! 的两种方式：`reload_parent` 每次从 checkpointer 读取并反序列化线程最新的检查点，
+//! `tracked_parent` 由运行器记录本次运行上一次保存的检查点 id。
+//!
+//! ```sh
+//! cargo bench -p langchain --bench agent_step
+//! ```
+
+use std::hint::black_box;
+
+use async_trait::async_trait;
+use criterion::{BatchSize, Criterion, criterion_group, criterion_main};
+use langchain::{AgentError, ReactAgentSpec};
+use langchain_core::{
+    message::Message,
+    state::{ChatStreamEvent, MessagesState},
+};
+use langgraph::{
+    checkpoint::{Checkpoint, Configuration, MemorySaver},
+    label::GraphLabel,
+    node::{EventSink, Node, NodeContext},
+    state_graph::{RunStrategy, StateGraph, StateGraphRunner},
+};
+
+const HISTORY: usize = 200;
+const STEPS: usize = 10;
+
+fn history() -> Vec<Message> {
+    (0..HISTORY)
+        .map(|i| {
+            let content = format!("message {i}: {}", "lorem ipsum ".repeat(40));
+            if i % 2 == 0 {
+                Message::user(content)
+            } else {
+                Message::assistant(content)
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, GraphLabel)]
+enum BenchLabel {
+    Reply,
+}
+
+/// 每步追加一条回复，直到达到步数上限
+struct ReplyNode;
+
+#[async_trait]
+impl Node<MessagesState, MessagesState, AgentError, ChatStreamEvent> for ReplyNode {
+    async fn run_sync(
+        &self,
+        _input: &MessagesState,
+        _context: NodeContext<'_>,
+    ) -> Result<MessagesState, AgentError> {
+        let mut delta = MessagesState::default();
+        delta.push_message_owned(Message::assistant("ok"));
+        Ok(delta)
+    }
+
+    async fn run_stream(
+        &self,
+        input: &MessagesState,
+        _sink: &dyn EventSink<ChatStreamEvent>,
+        context: NodeContext<'_>,
+    ) -> Result<MessagesState, AgentError> {
+        self.run_sync(input, context).await
+    }
+}
+
+fn state_clone(c: &mut Criterion) {
+    let messages = history();
+    let state = MessagesState::new(messages.clone());
+
+    let mut group = c.benchmark_group("state_clone");
+    group.bench_function("persistent", |b| b.iter(|| black_box(&state).clone()));
+    group.bench_function("vec_deep", |b| b.iter(|| black_box(&messages).clone()));
+    group.finish();
+}
+
+/// 每步追加一条回复的单节点循环图
+fn reply_graph() -> StateGraph<ReactAgentSpec> {
+    let mut graph: StateGraph<ReactAgentSpec> = StateGraph::new(
+        BenchLabel::Reply,
+        |old: &mut MessagesState, update: MessagesState| old.append_messages(update.messages),
+    );
+    graph.add_node(BenchLabel::Reply, ReplyNode);
+    graph.add_edge(BenchLabel::Reply, BenchLabel::Reply);
+    graph
+}
+
+fn graph_run(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+
+    let graph = reply_graph();
+    let state = MessagesState::new(history());
+    let config = Configuration::default();
+
+    c.bench_function("graph_run/200_messages_10_steps", |b| {
+        b.to_async(&runtime).iter(|| async {
+            graph
+                .run(
+                    state.clone(),
+                    &config,
+                    STEPS,
+                    RunStrategy::StopAtNonLinear,
+                    None,
+                )
+                .await
+                .unwrap()
+        })
+    });
+
+    // 每次迭代使用新的 checkpointer，已保存的检查点不会越积越多
+    let config = Configuration {
+        thread_id: Some("bench".to_owned()),
+        ..Configuration::default()
+    };
+    c.bench_function("graph_run/checkpointed_200_messages_10_steps", |b| {
+        b.to_async(&runtime).iter_batched(
+            || reply_graph().with_checkpointer(MemorySaver::new()),
+            |graph| {
+                let (state, config) = (state.clone(), &config);
+                async move {
+                    graph
+                        .run(state, config, STEPS, RunStrategy::StopAtNonLinear, None)
+                        .await
+                        .unwrap()
+                }
+            },
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn checkpoint_save(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let state = MessagesState::new(history());
+    let config = Configuration {
+        thread_id: Some("bench".to_owned()),
+        ..Configuration::default()
+    };
+    let checkpointed = || reply_graph().with_checkpointer(MemorySaver::new());
+
+    let mut group = c.benchmark_group("checkpoint_save/200_messages_10_steps");
+    group.bench_function("reload_parent", |b| {
+        b.to_async(&runtime).iter_batched(
+            checkpointed,
+            |graph| {
+                let state = &state;
+                async move {
+                    let checkpointer = graph.checkpointer.as_ref().unwrap();
+                    for step in 0..STEPS {
+                        let parent = checkpointer
+                            .get("bench")
+                            .await
+                            .unwrap()
+                            .map(|checkpoint| checkpoint.metadata.id);
+                        let checkpoint =
+                            Checkpoint::new_auto(state.clone(), "bench".to_owned(), step, parent);
+                        checkpointer.put(&checkpoint).await.unwrap();
+                    }
+                }
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function("tracked_parent", |b| {
+        b.to_async(&runtime).iter_batched(
+            checkpointed,
+            |graph| {
+                let (state, config) = (state.clone(), &config);
+                async move {
+                    let mut runner = StateGraphRunner::new(&graph, state);
+                    for step in 0..STEPS {
+                        runner.save_checkpoint(config, step).await;
+                    }
+                }
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+fn label_intern(c: &mut Criterion) {
+    c.bench_function("label_intern/unit_variant", |b| {
+        b.iter(|| black_box(BenchLabel::Reply).intern())
+    });
+}
+
+criterion_group!(
+    benches,
+    state_clone,
+    graph_run,
+    checkpoint_save,
+    label_intern
+);
+criterion_main!(benches);
@@ -71,7 +71,7 @@ async fn main() {
         .with_tool_middleware(Arc::new(Box::new(
             |state: &MessagesState, _ctx: &NodeContext, name, args, handler| {
                 let state = state.clone();
-                let name = name.to_string();
+                let name = name.to_owned();
                 Box::pin(async move {
                     tracing::info!("wrap_tool before: {} {:?}", name, args);
                     tracing::info!("Current state msg count: {}", state.messages.len());
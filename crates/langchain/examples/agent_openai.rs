@@ -1,5 +1,4 @@
-use langchain::ReactAgent;
-use langchain_core::{message::Message, tool};
+use langchain::prelude::*;
 use langchain_openai::ChatOpenAIBuilder;
 use std::env;
 
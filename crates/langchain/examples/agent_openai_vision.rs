@@ -0,0 +1,39 @@
+use langchain::prelude::*;
+use langchain_openai::ChatOpenAIBuilder;
+use std::env;
+
+const BASE_URL: &str = "https://api.siliconflow.cn/v1";
+const MODEL: &str = "Qwen/Qwen2.5-VL-72B-Instruct";
+const IMAGE_URL: &str = "https://upload.wikimedia.org/wikipedia/commons/thumb/4/47/PNG_transparency_demonstration_1.png/640px-PNG_transparency_demonstration_1.png";
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        .init();
+
+    let api_key = env::var("OPENAI_API_KEY").expect("OPENAI_API_KEY must be set");
+    // 没有单独指定精度的图片按 low 处理，速度更快、消耗的 token 更少
+    let model = ChatOpenAIBuilder::from_base(MODEL, BASE_URL, api_key.as_str())
+        .image_detail(ImageDetail::Low)
+        .build();
+
+    let agent = ReactAgent::builder(model)
+        .with_system_prompt("你是一个视觉助手，请根据图片内容回答用户的问题。")
+        .build();
+
+    let state = agent
+        .invoke(
+            Message::user_with_content_blocks(vec![
+                ContentBlock::text("图片里有什么？它们是什么颜色的？"),
+                ContentBlock::image(IMAGE_URL),
+            ]),
+            None,
+        )
+        .await
+        .unwrap();
+
+    if let Some(msg) = state.messages.last() {
+        println!("{}", msg.to_pretty());
+    }
+}
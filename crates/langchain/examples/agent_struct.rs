@@ -38,5 +38,9 @@ async fn main() {
         .await
         .unwrap();
 
+    // 回复无法解析时运行仍然成功，原始回复和解析错误保留在结果中
+    if let Some(error) = &result.parse_error {
+        eprintln!("无法解析模型回复 {:?}: {error}", result.raw);
+    }
     println!("{:?}", result.struct_output);
 }
@@ -0,0 +1,60 @@
+use langchain::prelude::*;
+use langchain_openai::ChatOpenAIBuilder;
+use std::{env, sync::Arc};
+
+const BASE_URL: &str = "https://api.siliconflow.cn/v1";
+const MODEL: &str = "deepseek-ai/DeepSeek-V3.2";
+
+#[tool(
+    description = "look up the population of a city",
+    args(city = "city name")
+)]
+async fn population(city: String) -> String {
+    match city.to_lowercase().as_str() {
+        "paris" => "2102650".to_owned(),
+        "berlin" => "3878100".to_owned(),
+        _ => "unknown".to_owned(),
+    }
+}
+
+#[tool(
+    description = "add two numbers",
+    args(a = "first number", b = "second number")
+)]
+async fn add(a: i64, b: i64) -> i64 {
+    a + b
+}
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::DEBUG)
+        .init();
+
+    let api_key = env::var("OPENAI_API_KEY").expect("OPENAI_API_KEY must be set");
+    let model = Arc::new(ChatOpenAIBuilder::from_base(MODEL, BASE_URL, api_key.as_str()).build());
+
+    // 两个工作者各自是完整的 ReactAgent，只能使用自己的工具
+    let researcher = ReactAgent::builder(model.clone())
+        .with_tools([population_tool()])
+        .with_system_prompt("你负责查询城市人口，只回答查到的数字。")
+        .build();
+    let mathematician = ReactAgent::builder(model.clone())
+        .with_tools([add_tool()])
+        .with_system_prompt("你负责计算，使用工具完成加法。")
+        .build();
+
+    let supervisor = Supervisor::builder(model)
+        .with_worker("researcher", "查询城市人口", researcher)
+        .with_worker("mathematician", "进行数学计算", mathematician)
+        .build();
+
+    let state = supervisor
+        .invoke(Message::user("巴黎和柏林的人口加起来是多少？"))
+        .await
+        .unwrap();
+
+    for msg in state.messages.iter() {
+        println!("{}", msg.to_pretty());
+    }
+}
@@ -0,0 +1,42 @@
+//! 使用 Open-Meteo 天气工具的 Agent
+//!
+//! 天气查询不需要 API key，只需要模型的 `OPENAI_API_KEY`：
+//!
+//! ```text
+//! OPENAI_API_KEY=... cargo run -p langchain --example agent_weather
+//! ```
+
+use langchain::prelude::*;
+use langchain_openai::ChatOpenAIBuilder;
+use langchain_tools::weather::OpenMeteo;
+use std::env;
+
+const BASE_URL: &str = "https://api.siliconflow.cn/v1";
+const MODEL: &str = "deepseek-ai/DeepSeek-V3.2";
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        .init();
+
+    let api_key = env::var("OPENAI_API_KEY").expect("OPENAI_API_KEY must be set");
+    let model = ChatOpenAIBuilder::from_base(MODEL, BASE_URL, api_key.as_str()).build();
+
+    let agent = ReactAgent::builder(model)
+        .with_tools([OpenMeteo::new().with_forecast_days(3).tool()])
+        .with_system_prompt("你是一个天气助手，使用 get_weather 工具查询天气后再回答用户的问题。")
+        .build();
+
+    let state = agent
+        .invoke(
+            Message::user("巴黎（法国）现在天气怎么样？未来两天需要带伞吗？"),
+            None,
+        )
+        .await
+        .unwrap();
+
+    if let Some(msg) = state.messages.last() {
+        println!("{}", msg.to_pretty());
+    }
+}
@@ -1,13 +1,38 @@
+pub mod middleware;
 pub mod node;
-
-use std::{collections::HashMap, error::Error, marker::PhantomData, sync::Arc};
+#[cfg(feature = "otel")]
+pub mod otel;
+pub mod plan_execute;
+pub mod prelude;
+pub mod spans;
+pub mod stepper;
+pub mod summarizing;
+pub mod supervisor;
+pub mod testing;
+pub mod tool_selection;
+
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    marker::PhantomData,
+    sync::Arc,
+    time::Duration,
+};
 
 use futures::{Stream, StreamExt};
-use langchain_core::{ModelError, ToolError};
+use langchain_core::{
+    ModelError, ToolError,
+    error::{ErrorCategory, LangChainError, RetryConfig},
+};
 use langchain_core::{
     message::Message,
-    request::{FormatType, ResponseFormat, ToolSpec},
-    state::{AgentState, ChatModel, ChatStreamEvent, MessagesState, RegisteredTool, ToolFn},
+    parsers::{JsonParser, OutputParser, ParseError},
+    prompt::{PromptError, PromptTemplate},
+    request::{FormatType, ResponseFormat, ToolChoice, ToolSpec},
+    state::{
+        AgentState, ChatModel, ChatStreamEvent, JumpTo, MessagesReducer, MessagesState,
+        RegisteredTool, ToolFn, ToolRegistry,
+    },
     store::BaseStore,
 };
 use langgraph::label::InternedGraphLabel;
@@ -26,10 +51,60 @@ use smallvec::{SmallVec, smallvec};
 use thiserror::Error;
 use tracing::debug;
 
+pub use langgraph::id::{IdGenerator, SequentialIdGenerator, UuidGenerator};
 use node::llm::LlmNode;
-pub use node::tool::{ToolMiddleware, ToolNode};
+pub use node::tool::{
+    OutputValidation, RepetitionAction, RepetitionGuard, ToolErrorFormatter, ToolMiddleware,
+    ToolNode, ToolRetry,
+};
+pub use stepper::{AgentStepper, StepEvent};
+pub use summarizing::SummarizingCheckpointer;
+pub use tokio_util::sync::CancellationToken;
+pub use tool_selection::ToolSelection;
+use tool_selection::ToolSelector;
+
+use crate::middleware::MemoryMiddleware;
+use crate::node::middleware::{AgentHook, AgentMiddleware, AgentMiddlewareNode, RunErrorHandler};
+
+/// An item of [`ReactAgent::invoke_structured_stream`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum StructuredStreamEvent<S> {
+    /// The reply so far, completed with [`JsonParser::parse_partial`]. Fields
+    /// fill in as tokens arrive; the value is not validated against `S` yet.
+    Partial(serde_json::Value),
+    /// The complete reply, validated as `S`. Always the last item.
+    Final(S),
+}
+
+/// How [`ReactAgent::invoke_structured`] turns the run's final reply into the
+/// output type. Set with [`ReactAgentBuilder::with_structured_output`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StructuredOutputStrategy {
+    /// Parses the final assistant message and nothing else: a reply that does
+    /// not parse is reported in [`AgentState::parse_error`], without another
+    /// model call.
+    #[default]
+    ParseFinal,
+    /// Parses the final assistant message first, so a model that already
+    /// answered in the expected format costs no extra call. When parsing fails,
+    /// the model is asked again, up to `max_retries` times, with the parse
+    /// error and the parser's format instructions, and its new reply is parsed.
+    ///
+    /// The follow-up messages become part of the conversation, and of the
+    /// thread when a checkpointer is configured.
+    Reprompt { max_retries: usize },
+}
 
-use crate::node::middleware::{AgentHook, AgentMiddleware, AgentMiddlewareNode};
+/// One conversation of [`ReactAgent::invoke_batch`].
+#[derive(Debug)]
+pub struct BatchRun {
+    /// The thread the run was saved under, to continue it with
+    /// [`ReactAgent::invoke`] or inspect its checkpoints. `None` when the agent
+    /// has no checkpointer.
+    pub thread_id: Option<String>,
+    /// The final state, or why the run failed.
+    pub result: Result<MessagesState, AgentError>,
+}
 
 /// Specification for the React Agent Graph
 pub struct ReactAgentSpec;
@@ -41,12 +116,36 @@ impl GraphSpec for ReactAgentSpec {
     type Event = ChatStreamEvent;
 }
 
+/// Nodes of the React Agent graph.
+///
+/// Together with [`BaseGraphLabel::End`] these are the labels a custom route
+/// (see [`ReactAgentBuilder::with_route`]) can return.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, GraphLabel)]
-enum ReactAgentLabel {
+pub enum ReactAgentLabel {
+    /// Calls the model.
     Llm,
+    /// Executes the tool calls of the last assistant message.
     Tool,
 }
 
+/// Decides where the agent goes after the model replies (and after the
+/// `after_model` hooks have run).
+///
+/// Return [`ReactAgentLabel::Tool`] to execute tools and then call the model
+/// again, or [`BaseGraphLabel::End`] to finish the run (through the
+/// `after_agent` hooks). Any other label is treated as `End`.
+pub type RouteFn = fn(&MessagesState) -> InternedGraphLabel;
+
+/// The default route: executes tools when the last message has tool calls,
+/// otherwise finishes the run.
+pub fn default_route(state: &MessagesState) -> InternedGraphLabel {
+    if state.last_tool_calls().is_some() {
+        ReactAgentLabel::Tool.intern()
+    } else {
+        BaseGraphLabel::End.intern()
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum AgentError {
     #[error("model error: {0}")]
@@ -57,28 +156,158 @@ pub enum AgentError {
     Graph(String),
     #[error("agent error: {0}")]
     Agent(String),
+    /// 图中的节点执行失败，`node` 为失败的节点，`step` 为失败时的 super-step 序号（从 0 开始），
+    /// `error` 为节点返回的错误，可以用 [`root_cause`](Self::root_cause) 取出
+    #[error("node {node:?} failed at step {step}: {error}")]
+    Node {
+        node: InternedGraphLabel,
+        step: usize,
+        #[source]
+        error: Box<AgentError>,
+    },
     #[error("structured output error: {0}")]
     StructuredOutput(String),
+    #[error("system prompt error: {0}")]
+    Prompt(#[from] PromptError),
+    #[error("token budget exceeded: used {used} of {budget} tokens")]
+    TokenBudgetExceeded { used: u32, budget: u32 },
+    #[error("duplicate tool call id: {0}")]
+    DuplicateToolCallId(String),
+    #[error("model requested unknown tool `{tool}` {attempts} times in a row")]
+    UnknownToolLimitExceeded { tool: String, attempts: usize },
+    #[error("model called tool `{tool}` with the same arguments {calls} times")]
+    RepeatedToolCall { tool: String, calls: usize },
+    /// 运行在步数上限内没有结束，`state` 为停止时的状态
+    #[error("agent did not finish within {steps} steps")]
+    MaxStepsExceeded {
+        steps: usize,
+        state: Box<MessagesState>,
+    },
+    /// 运行超过了截止时间，`state` 为最后一个完成的 super-step 之后的状态
+    #[error("agent did not finish within {elapsed:?}")]
+    Timeout {
+        elapsed: Duration,
+        state: Box<MessagesState>,
+    },
+    /// 运行被调用方取消，`state` 为最后一个完成的 super-step 之后的状态；
+    /// 其中请求了工具但工具尚未执行的模型回复已撤回
+    #[error("agent run was cancelled")]
+    Cancelled { state: Box<MessagesState> },
+    /// 运行累计的 token 用量超过了上限，`state` 为超出上限的那次模型调用之后的状态
+    #[error("agent used {used} tokens, over the limit of {limit}")]
+    TokenLimitExceeded {
+        used: u32,
+        limit: u32,
+        state: Box<MessagesState>,
+    },
+}
+
+impl AgentError {
+    /// The state the run stopped at, for errors that carry one
+    /// ([`MaxStepsExceeded`](Self::MaxStepsExceeded), [`Timeout`](Self::Timeout),
+    /// [`Cancelled`](Self::Cancelled) and
+    /// [`TokenLimitExceeded`](Self::TokenLimitExceeded)).
+    pub fn state(&self) -> Option<&MessagesState> {
+        match self {
+            Self::MaxStepsExceeded { state, .. }
+            | Self::Timeout { state, .. }
+            | Self::Cancelled { state }
+            | Self::TokenLimitExceeded { state, .. } => Some(state),
+            Self::Node { error, .. } => error.state(),
+            _ => None,
+        }
+    }
+
+    /// The error a failing node returned, without the [`Node`](Self::Node)
+    /// context around it. Other errors are returned as they are.
+    pub fn root_cause(&self) -> &AgentError {
+        match self {
+            Self::Node { error, .. } => error.root_cause(),
+            _ => self,
+        }
+    }
+}
+
+impl LangChainError for AgentError {
+    fn category(&self) -> ErrorCategory {
+        match self {
+            Self::Model(e) => e.category(),
+            Self::Node { error, .. } => error.category(),
+            Self::Timeout { .. } => ErrorCategory::Transient,
+            Self::Tool(_) => ErrorCategory::External,
+            Self::Graph(_) | Self::Agent(_) => ErrorCategory::Internal,
+            Self::StructuredOutput(_)
+            | Self::Prompt(_)
+            | Self::TokenBudgetExceeded { .. }
+            | Self::DuplicateToolCallId(_)
+            | Self::UnknownToolLimitExceeded { .. }
+            | Self::RepeatedToolCall { .. }
+            | Self::MaxStepsExceeded { .. }
+            | Self::TokenLimitExceeded { .. } => ErrorCategory::Validation,
+            Self::Cancelled { .. } => ErrorCategory::Cancelled,
+        }
+    }
+
+    fn retry_delay_ms(&self) -> Option<u64> {
+        match self {
+            Self::Model(e) => e.retry_delay_ms(),
+            Self::Node { error, .. } => error.retry_delay_ms(),
+            _ => None,
+        }
+    }
 }
 
 impl From<GraphError<AgentError>> for AgentError {
     fn from(value: GraphError<AgentError>) -> Self {
         match value {
-            GraphError::NodeRunError(e) => e,
+            GraphError::NodeRunError { node, step, error } => Self::Node {
+                node,
+                step,
+                error: Box::new(error),
+            },
             _ => Self::Graph(value.to_string()),
         }
     }
 }
 
+/// Maximum number of graph super-steps in a single agent run.
+pub const MAX_STEPS: usize = 25;
+
 /// Unified React Agent Builder
 pub struct ReactAgentBuilder<M> {
     model: M,
     tools: Vec<RegisteredTool<ToolError>>,
     system_prompt: Option<String>,
+    system_prompt_template: Option<(String, HashMap<String, String>)>,
+    examples: Vec<(String, String)>,
     store: Option<Arc<dyn BaseStore>>,
     checkpointer: Option<Arc<dyn Checkpointer<MessagesState>>>,
     middlewares: SmallVec<[AgentMiddleware<MessagesState>; 4]>,
+    memory: Option<MemoryMiddleware>,
     tool_middleware: Option<Arc<ToolMiddleware<ToolError>>>,
+    model_retry: Option<RetryConfig>,
+    tool_retry: Option<RetryConfig>,
+    max_tool_concurrency: Option<usize>,
+    max_tool_output_chars: Option<usize>,
+    deadline: Option<Duration>,
+    token_limit: Option<u32>,
+    tool_choice: Option<ToolChoice>,
+    unknown_tool_limit: Option<usize>,
+    repetition_guard: Option<RepetitionGuard>,
+    tool_error_formatter: Option<ToolErrorFormatter<ToolError>>,
+    tool_selection: ToolSelection,
+    normalize_messages: bool,
+    hide_reasoning: bool,
+    trace_content: bool,
+    validate_tool_arguments: bool,
+    output_validation: Option<OutputValidation>,
+    coerce_tool_arguments: bool,
+    structured_output: StructuredOutputStrategy,
+    id_generator: Option<Arc<dyn IdGenerator>>,
+    route: RouteFn,
+    reducer: MessagesReducer,
+    interrupt_before: Vec<InternedGraphLabel>,
+    interrupt_after: Vec<InternedGraphLabel>,
 }
 
 impl<M> ReactAgentBuilder<M>
@@ -90,10 +319,36 @@ where
             model,
             tools: Vec::new(),
             system_prompt: None,
+            system_prompt_template: None,
+            examples: Vec::new(),
             store: None,
             checkpointer: None,
             middlewares: SmallVec::new(),
+            memory: None,
             tool_middleware: None,
+            model_retry: None,
+            tool_retry: None,
+            max_tool_concurrency: None,
+            max_tool_output_chars: None,
+            deadline: None,
+            token_limit: None,
+            tool_choice: None,
+            unknown_tool_limit: None,
+            repetition_guard: None,
+            tool_error_formatter: None,
+            tool_selection: ToolSelection::All,
+            normalize_messages: false,
+            hide_reasoning: false,
+            trace_content: false,
+            validate_tool_arguments: false,
+            output_validation: None,
+            coerce_tool_arguments: false,
+            structured_output: StructuredOutputStrategy::ParseFinal,
+            id_generator: None,
+            route: default_route,
+            reducer: MessagesReducer::Append,
+            interrupt_before: Vec::new(),
+            interrupt_after: Vec::new(),
         }
     }
 
@@ -102,8 +357,298 @@ where
         self
     }
 
+    /// Retries the model node with backoff on transient or rate-limit errors
+    /// before failing the whole run.
+    pub fn with_model_retry(mut self, config: RetryConfig) -> Self {
+        self.model_retry = Some(config);
+        self
+    }
+
+    /// Retries tool calls that fail with a transient or rate-limit error before
+    /// reporting the failure to the model. Validation errors such as bad
+    /// arguments are reported immediately.
+    pub fn with_tool_retry(mut self, config: RetryConfig) -> Self {
+        self.tool_retry = Some(config);
+        self
+    }
+
+    /// Runs at most `limit` tool calls of a single model reply at once (values
+    /// below 1 are treated as 1). By default all calls of a reply run
+    /// concurrently. Results are still returned in the order the model issued them.
+    pub fn with_max_tool_concurrency(mut self, limit: usize) -> Self {
+        self.max_tool_concurrency = Some(limit);
+        self
+    }
+
+    /// Truncates tool results longer than `limit` characters before they are
+    /// sent back to the model, ending them with `[truncated N chars]`, so one
+    /// huge result cannot overflow the context window. Tools can override the
+    /// limit with [`RegisteredTool::with_max_output_chars`]. Unlimited by default.
+    pub fn with_max_tool_output_chars(mut self, limit: usize) -> Self {
+        self.max_tool_output_chars = Some(limit);
+        self
+    }
+
+    /// Bounds the wall-clock time of every run. When the deadline passes, the
+    /// in-flight model and tool calls are cancelled and the run fails with
+    /// [`AgentError::Timeout`], carrying the state after the last completed step.
+    /// Streams ([`ReactAgent::stream`], [`ReactAgent::invoke_structured_stream`])
+    /// end early instead, see [`ReactAgent::stream`].
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Caps the tokens a run may use, whatever its step count. Usage reported by
+    /// the model is summed over the run and checked after each model call; once
+    /// it exceeds `limit` the run fails with [`AgentError::TokenLimitExceeded`],
+    /// carrying the state including the reply that went over.
+    ///
+    /// Unlike [`TokenBudgetMiddleware`](middleware::TokenBudgetMiddleware),
+    /// which runs as a hook before model calls, this is enforced by the
+    /// [`AgentStepper`] and cannot be skipped by other hooks. Streaming runs are
+    /// not covered; use the middleware there.
+    pub fn with_token_limit(mut self, limit: u32) -> Self {
+        self.token_limit = Some(limit);
+        self
+    }
+
+    /// Forces or forbids tool use on the model's first call after each user
+    /// message, e.g. [`ToolChoice::function`] with a [terminal](RegisteredTool::terminal)
+    /// tool for structured output. Calls that follow tool results are left to the
+    /// model so the run can finish. [`ToolChoice::None`] is enforced by not sending
+    /// the tools at all, so it works with every provider; the other choices are
+    /// ignored by providers that do not support them.
+    pub fn with_tool_choice(mut self, tool_choice: ToolChoice) -> Self {
+        self.tool_choice = Some(tool_choice);
+        self
+    }
+
+    /// Ends the run with [`AgentError::UnknownToolLimitExceeded`] once the model
+    /// has requested unregistered tools `limit` times in a row, instead of looping
+    /// until the step limit. Unknown tools are otherwise answered with an error
+    /// message so the model can correct itself.
+    pub fn with_unknown_tool_limit(mut self, limit: usize) -> Self {
+        self.unknown_tool_limit = Some(limit);
+        self
+    }
+
+    /// Catches a model stuck calling the same tool with the same arguments.
+    ///
+    /// Once a call repeats `guard.threshold` times within the last
+    /// `guard.window` tool calls of the current turn, it is not executed: with
+    /// [`RepetitionAction::Nudge`] the model gets an error result asking it to
+    /// try something else, with [`RepetitionAction::Stop`] the run ends with
+    /// [`AgentError::RepeatedToolCall`]. This catches loops long before
+    /// [`MAX_STEPS`] does.
+    pub fn with_repetition_guard(mut self, guard: RepetitionGuard) -> Self {
+        self.repetition_guard = Some(guard);
+        self
+    }
+
+    /// Controls what the model sees when a tool returns an error. Defaults to
+    /// [`ToolErrorFormatter::concise`], which sends only the first line of the
+    /// error; the full error is always logged.
+    pub fn with_tool_error_formatter(mut self, formatter: ToolErrorFormatter<ToolError>) -> Self {
+        self.tool_error_formatter = Some(formatter);
+        self
+    }
+
+    /// Chooses the tool definitions sent on each model call, for toolsets too
+    /// large for the model's function-definition limits: compact every schema,
+    /// or send only the tools relevant to the latest user message. See
+    /// [`ToolSelection`].
+    ///
+    /// Only what the model sees changes; any registered tool it calls still runs.
+    pub fn with_tool_selection(mut self, selection: ToolSelection) -> Self {
+        self.tool_selection = selection;
+        self
+    }
+
+    /// Normalizes the history with [`MessagesState::normalize`] before every model
+    /// call, for providers that reject consecutive same-role messages or system
+    /// messages after the first turn. The stored state is left as is.
+    pub fn with_message_normalization(mut self, enabled: bool) -> Self {
+        self.normalize_messages = enabled;
+        self
+    }
+
+    /// Hides the chain-of-thought of reasoning models: no
+    /// [`ChatStreamEvent::Reasoning`] events are streamed and the assistant
+    /// messages saved to the state carry no `reasoning_content`. Tool calls
+    /// are unaffected, since routing never looks at reasoning content.
+    pub fn with_reasoning_hidden(mut self, hidden: bool) -> Self {
+        self.hide_reasoning = hidden;
+        self
+    }
+
+    /// Records the messages sent to and received from the model, and each tool
+    /// call's arguments and result, on the `llm.call` and `tool.call` spans
+    /// (see [`spans`]). Off by default, since every subscriber — including
+    /// plain log formatters — would then see the conversation. Turn it on when
+    /// exporting to an observability backend such as the `otel` feature's
+    /// OpenInference layer.
+    pub fn with_content_tracing(mut self, enabled: bool) -> Self {
+        self.trace_content = enabled;
+        self
+    }
+
+    /// Validates tool call arguments against each tool's parameter schema before
+    /// running it. Invalid calls are not executed; the model gets a tool message
+    /// naming the offending field so it can retry with corrected arguments.
+    pub fn with_argument_validation(mut self, enabled: bool) -> Self {
+        self.validate_tool_arguments = enabled;
+        self
+    }
+
+    /// Validates tool results against the output schema tools declare (see
+    /// [`RegisteredTool::with_output_schema`]); tools without one are not
+    /// checked. A mismatch is logged, and with [`OutputValidation::Reject`] the
+    /// model gets an error naming the offending field instead of the result.
+    pub fn with_output_validation(mut self, validation: OutputValidation) -> Self {
+        self.output_validation = Some(validation);
+        self
+    }
+
+    /// Converts loosely typed tool arguments to the types the tool's parameter
+    /// schema expects before running it, e.g. `"5"` to `5` or `"true"` to `true`.
+    /// Helps with weaker models; every conversion is logged. Runs before
+    /// [`with_argument_validation`](Self::with_argument_validation) when both are enabled.
+    pub fn with_argument_coercion(mut self, enabled: bool) -> Self {
+        self.coerce_tool_arguments = enabled;
+        self
+    }
+
+    /// Chooses what [`ReactAgent::invoke_structured`] does when the final reply
+    /// does not parse (default [`StructuredOutputStrategy::ParseFinal`]: report
+    /// the parse error). See [`StructuredOutputStrategy`].
+    pub fn with_structured_output(mut self, strategy: StructuredOutputStrategy) -> Self {
+        self.structured_output = strategy;
+        self
+    }
+
+    /// Mints the ids the agent creates itself with `ids` instead of random
+    /// UUID v4s: thread ids of [`ReactAgent::invoke_batch`] and ids of tool
+    /// calls the model returned without one, in both the final state and the
+    /// streamed [`ChatStreamEvent::ToolCallDelta`]s. Use e.g.
+    /// [`SequentialIdGenerator`] for reproducible transcripts in tests.
+    ///
+    /// Checkpoint ids are not affected: checkpointers find the latest
+    /// checkpoint of a thread by its time-ordered UUID v7 id.
+    ///
+    /// Ids must be unique: a reused tool call id fails the run with
+    /// [`AgentError::DuplicateToolCallId`]. See [`IdGenerator`].
+    pub fn with_id_generator(mut self, ids: Arc<dyn IdGenerator>) -> Self {
+        self.id_generator = Some(ids);
+        self
+    }
+
+    /// Overrides where the agent goes after each model reply (default
+    /// [`default_route`]), e.g. to finish as soon as a given tool has been called
+    /// or when the reply contains a keyword. See [`RouteFn`] for the labels the
+    /// function may return.
+    ///
+    /// ```rust,ignore
+    /// fn route(state: &MessagesState) -> InternedGraphLabel {
+    ///     match state.last_message() {
+    ///         Some(m) if m.content().contains("FINAL ANSWER") => BaseGraphLabel::End.intern(),
+    ///         _ => default_route(state),
+    ///     }
+    /// }
+    /// let agent = ReactAgent::builder(model).with_route(route).build();
+    /// ```
+    pub fn with_route(mut self, route: RouteFn) -> Self {
+        self.route = route;
+        self
+    }
+
+    /// Sets how node updates are merged into the conversation (default
+    /// [`MessagesReducer::Append`]). [`MessagesReducer::Window`] bounds the
+    /// history kept between model calls; [`MessagesReducer::ReplaceLast`] drops
+    /// each previous message and is rarely what a tool-calling agent wants.
+    pub fn with_reducer(mut self, reducer: MessagesReducer) -> Self {
+        self.reducer = reducer;
+        self
+    }
+
+    /// Pauses runs before any of `nodes` executes, e.g. before
+    /// [`ReactAgentLabel::Tool`] so a person can approve the proposed tool
+    /// calls. A paused [`invoke`](ReactAgent::invoke) returns the state so far
+    /// and, with a checkpointer, records the pending nodes on the thread:
+    /// check them with [`ReactAgent::pending_nodes`] and continue with
+    /// [`ReactAgent::resume`]. An [`AgentStepper`] reports the pause as
+    /// [`StepEvent::Interrupted`].
+    pub fn with_interrupt_before<I, L>(mut self, nodes: I) -> Self
+    where
+        I: IntoIterator<Item = L>,
+        L: GraphLabel,
+    {
+        self.interrupt_before = nodes.into_iter().map(|node| node.intern()).collect();
+        self
+    }
+
+    /// Pauses runs after any of `nodes` completes, once its output is merged
+    /// and saved, e.g. after [`ReactAgentLabel::Llm`] to review the model's
+    /// reply before anything acts on it. Runs do not pause when only the end of
+    /// the graph is left. Resumes like
+    /// [`with_interrupt_before`](Self::with_interrupt_before).
+    pub fn with_interrupt_after<I, L>(mut self, nodes: I) -> Self
+    where
+        I: IntoIterator<Item = L>,
+        L: GraphLabel,
+    {
+        self.interrupt_after = nodes.into_iter().map(|node| node.intern()).collect();
+        self
+    }
+
     pub fn with_system_prompt<Str: Into<String>>(mut self, system_prompt: Str) -> Self {
         self.system_prompt = Some(system_prompt.into());
+        self.system_prompt_template = None;
+        self
+    }
+
+    /// Uses a system prompt rendered from a [`PromptTemplate`] when a run starts,
+    /// replacing any [`with_system_prompt`](Self::with_system_prompt).
+    ///
+    /// `{name}` placeholders are filled from, in order of precedence, the
+    /// variables passed to [`ReactAgent::invoke_with_vars`] (or the run's
+    /// [`Configuration::vars`]), `vars`, and the
+    /// built-in `{tools}` (comma-separated names of the tools visible to the
+    /// run). A missing variable fails the run with [`AgentError::Prompt`]; a
+    /// malformed template fails [`try_build`](Self::try_build).
+    pub fn with_system_prompt_template<Str, I, K, V>(mut self, template: Str, vars: I) -> Self
+    where
+        Str: Into<String>,
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let vars = vars
+            .into_iter()
+            .map(|(k, v)| (k.into(), v.into()))
+            .collect();
+        self.system_prompt_template = Some((template.into(), vars));
+        self.system_prompt = None;
+        self
+    }
+
+    /// Adds few-shot example turns, `(user, assistant)` pairs, to every model
+    /// call: they are sent after the system prompt and before the conversation,
+    /// but never stored in the state, so they don't reach the checkpointer.
+    ///
+    /// Examples are re-sent on every model call, including each call of the tool
+    /// loop, so their tokens are paid `llm_calls` times per run. Keep them short
+    /// and few.
+    pub fn with_examples<I, U, A>(mut self, examples: I) -> Self
+    where
+        I: IntoIterator<Item = (U, A)>,
+        U: Into<String>,
+        A: Into<String>,
+    {
+        self.examples = examples
+            .into_iter()
+            .map(|(user, assistant)| (user.into(), assistant.into()))
+            .collect();
         self
     }
 
@@ -115,6 +660,13 @@ where
         self
     }
 
+    /// Uses every tool of `registry`, replacing tools set with
+    /// [`with_tools`](Self::with_tools). Names in a registry are unique.
+    pub fn with_registry(mut self, registry: ToolRegistry<ToolError>) -> Self {
+        self.tools = registry.into_iter().collect();
+        self
+    }
+
     pub fn bind_tool(mut self, tool: RegisteredTool<ToolError>) -> Self {
         self.tools.push(tool);
         self
@@ -125,6 +677,21 @@ where
         self
     }
 
+    /// Gives the agent long-term memory backed by `store`.
+    ///
+    /// Adds a [`MemoryMiddleware`] with its defaults: memories are namespaced by
+    /// `thread_id` (runs without one neither read nor write memories), each user
+    /// message that ends a turn is remembered, and before every model call the
+    /// most relevant memories not already in the conversation are shown to the
+    /// model as a system message. Pass a configured [`MemoryMiddleware`] to
+    /// [`with_middlewares`](Self::with_middlewares) to change the namespace
+    /// (e.g. to share memories across a user's threads), extraction or number
+    /// of memories.
+    pub fn with_memory_store(mut self, store: Arc<dyn BaseStore>) -> Self {
+        self.memory = Some(MemoryMiddleware::new(store));
+        self
+    }
+
     pub fn with_checkpointer(mut self, checkpointer: Arc<dyn Checkpointer<MessagesState>>) -> Self {
         self.checkpointer = Some(checkpointer);
         self
@@ -138,18 +705,62 @@ where
         self
     }
 
-    /// Transforms this builder into a structured agent builder
+    /// Builds the agent, panicking if the resulting graph is misconfigured.
+    ///
+    /// Use [`ReactAgentBuilder::try_build`] to handle the error instead.
     pub fn build(self) -> ReactAgent {
+        self.try_build().expect("invalid agent graph")
+    }
+
+    /// Builds the agent and validates its graph (see [`StateGraph::validate`]),
+    /// so misconfigured middleware routes fail here rather than mid-run.
+    pub fn try_build(self) -> Result<ReactAgent, AgentError> {
+        let system_prompt_template = self
+            .system_prompt_template
+            .map(|(template, vars)| Ok::<_, AgentError>((PromptTemplate::new(&template)?, vars)))
+            .transpose()?;
+        let terminal_tools: HashSet<String> = self
+            .tools
+            .iter()
+            .filter(|tool| tool.terminal)
+            .map(|tool| tool.function.name.clone())
+            .collect();
+        let output_limits = self
+            .tools
+            .iter()
+            .filter_map(|tool| Some((tool.function.name.clone(), tool.max_output_chars?)))
+            .collect();
+        let stream_tools = self
+            .tools
+            .iter()
+            .filter_map(|tool| Some((tool.function.name.clone(), tool.stream_handler.clone()?)))
+            .collect();
         let (tool_specs, tools) = parse_tool(self.tools);
+        let tool_names = tools.keys().cloned().collect();
+        let schemas = || -> HashMap<String, serde_json::Value> {
+            tool_specs
+                .iter()
+                .map(|ToolSpec::Function { function }| {
+                    (function.name.clone(), function.parameters.clone())
+                })
+                .collect()
+        };
+        let argument_schemas = self.validate_tool_arguments.then(schemas);
+        let coercion_schemas = self.coerce_tool_arguments.then(schemas);
+        let output_validation = self.output_validation.map(|validation| {
+            let schemas: HashMap<String, serde_json::Value> = tool_specs
+                .iter()
+                .filter_map(|ToolSpec::Function { function }| {
+                    Some((function.name.clone(), function.output_schema.clone()?))
+                })
+                .collect();
+            (schemas, validation)
+        });
 
+        let reducer = self.reducer;
         let mut graph: StateGraph<ReactAgentSpec> = StateGraph::new(
             BaseGraphLabel::Start,
-            |old: &mut MessagesState, update: MessagesState| {
-                if !update.messages.is_empty() {
-                    old.append_messages(update.messages);
-                }
-                old.llm_calls += update.llm_calls;
-            },
+            move |old: &mut MessagesState, update: MessagesState| reducer.reduce(old, update),
         );
 
         if let Some(store) = self.store {
@@ -159,6 +770,8 @@ where
         if let Some(checkpointer) = self.checkpointer {
             graph = graph.with_shared_checkpointer(checkpointer);
         }
+        graph.interrupt_before = self.interrupt_before;
+        graph.interrupt_after = self.interrupt_after;
 
         let mut before_agent_nodes: SmallVec<[_; 4]> = smallvec![];
         let mut before_model_nodes: SmallVec<[_; 4]> = smallvec![];
@@ -179,7 +792,13 @@ where
             }
         };
 
-        self.middlewares.into_iter().for_each(|middleware| {
+        let mut middlewares = self.middlewares;
+        middlewares.extend(self.memory.map(AgentMiddleware::from));
+        let run_error_handlers: Vec<_> = middlewares
+            .iter()
+            .filter_map(|middleware| middleware.on_run_error.clone())
+            .collect();
+        middlewares.into_iter().for_each(|middleware| {
             add_node(
                 &mut before_agent_nodes,
                 middleware.before_agent,
@@ -214,10 +833,42 @@ where
                 _marker: PhantomData,
             },
         );
-        graph.add_node(ReactAgentLabel::Llm, LlmNode::new(self.model, tool_specs));
+        let mut llm_node = LlmNode::new(self.model, tool_specs);
+        llm_node.normalize_messages = self.normalize_messages;
+        llm_node.hide_reasoning = self.hide_reasoning;
+        llm_node.trace_content = self.trace_content;
+        llm_node.tool_choice = self.tool_choice;
+        llm_node.examples = self.examples;
+        llm_node.tool_selector = ToolSelector::new(self.tool_selection);
+        if let Some(ids) = &self.id_generator {
+            llm_node.id_generator = ids.clone();
+        }
+        match self.model_retry {
+            Some(config) => graph.add_node_with_retry(ReactAgentLabel::Llm, llm_node, config),
+            None => graph.add_node(ReactAgentLabel::Llm, llm_node),
+        }
 
-        let mut tool_node = ToolNode::new(tools);
+        let mut tool_node = ToolNode::new(tools).with_streaming_tools(stream_tools);
         tool_node.middleware = self.tool_middleware;
+        tool_node.unknown_tool_limit = self.unknown_tool_limit;
+        tool_node.repetition_guard = self.repetition_guard;
+        tool_node.error_formatter = self.tool_error_formatter.map(Arc::new);
+        tool_node.argument_schemas = argument_schemas;
+        tool_node.coercion_schemas = coercion_schemas;
+        if let Some((schemas, validation)) = output_validation {
+            tool_node = tool_node.with_output_validation(schemas, validation);
+        }
+        if let Some(config) = self.tool_retry {
+            tool_node = tool_node.with_retry(config);
+        }
+        if let Some(limit) = self.max_tool_concurrency {
+            tool_node = tool_node.with_max_concurrency(limit);
+        }
+        tool_node.max_output_chars = self.max_tool_output_chars;
+        tool_node.output_limits = output_limits;
+        tool_node.trace_content = self.trace_content;
+        let ends_on_terminal_tool = !terminal_tools.is_empty();
+        tool_node.terminal_tools = terminal_tools;
         graph.add_node(ReactAgentLabel::Tool, tool_node);
 
         let after_agent_entry = apply_middleware_chain(
@@ -225,46 +876,66 @@ where
             &after_agent_nodes,
             BaseGraphLabel::End.intern(),
             true,
-            false,
+            None,
+            JumpTargets {
+                end: BaseGraphLabel::End.intern(),
+                after_model: None,
+            },
         );
 
+        let route = self.route;
         let after_model_entry = apply_middleware_chain(
             &mut graph,
             &after_model_nodes,
             after_agent_entry,
             true,
-            true,
+            Some(route),
+            JumpTargets {
+                end: after_agent_entry,
+                after_model: None,
+            },
         );
 
-        if !after_model_nodes.is_empty() {
-            graph.add_edge(ReactAgentLabel::Llm, after_model_entry);
+        // 模型给出回复（或钩子跳过模型）之后的去向
+        let after_model = if !after_model_nodes.is_empty() {
+            AfterModelRoute::Hooks(after_model_entry)
         } else {
-            let mut branches = HashMap::new();
-            branches.insert(after_agent_entry, after_agent_entry);
-            branches.insert(
-                ReactAgentLabel::Tool.intern(),
-                ReactAgentLabel::Tool.intern(),
-            );
+            AfterModelRoute::ToolsOr {
+                end: after_agent_entry,
+                route,
+            }
+        };
 
-            graph.add_condition_edge(
-                ReactAgentLabel::Llm,
-                branches,
-                move |state: &MessagesState| {
-                    if state.last_tool_calls().is_some() {
-                        smallvec![ReactAgentLabel::Tool.intern()]
-                    } else {
-                        smallvec![after_agent_entry]
-                    }
-                },
-            );
+        match after_model {
+            AfterModelRoute::Hooks(entry) => graph.add_edge(ReactAgentLabel::Llm, entry),
+            AfterModelRoute::ToolsOr { end, .. } => {
+                let mut branches = HashMap::new();
+                branches.insert(end, end);
+                branches.insert(
+                    ReactAgentLabel::Tool.intern(),
+                    ReactAgentLabel::Tool.intern(),
+                );
+
+                graph.add_condition_edge(
+                    ReactAgentLabel::Llm,
+                    branches,
+                    move |state: &MessagesState| smallvec![after_model.resolve(state)],
+                );
+            }
         }
 
+        let before_jumps = JumpTargets {
+            end: after_agent_entry,
+            after_model: Some(after_model),
+        };
+
         let before_model_entry = apply_middleware_chain(
             &mut graph,
             &before_model_nodes,
             ReactAgentLabel::Llm.intern(),
             false,
-            false,
+            None,
+            before_jumps,
         );
 
         let before_agent_entry = apply_middleware_chain(
@@ -272,16 +943,41 @@ where
             &before_agent_nodes,
             before_model_entry,
             false,
-            false,
+            None,
+            before_jumps,
         );
 
         graph.add_edge(BaseGraphLabel::Start, before_agent_entry);
-        graph.add_edge(ReactAgentLabel::Tool, before_model_entry);
+        if ends_on_terminal_tool {
+            // 终止工具调用成功后跳过模型，直接结束
+            graph.add_condition_edge(
+                ReactAgentLabel::Tool,
+                HashMap::from([
+                    (before_model_entry, before_model_entry),
+                    (after_agent_entry, after_agent_entry),
+                ]),
+                move |state: &MessagesState| match state.jump_to {
+                    Some(JumpTo::End) => smallvec![after_agent_entry],
+                    _ => smallvec![before_model_entry],
+                },
+            );
+        } else {
+            graph.add_edge(ReactAgentLabel::Tool, before_model_entry);
+        }
 
-        ReactAgent {
+        graph.validate()?;
+
+        Ok(ReactAgent {
             graph,
             system_prompt: self.system_prompt,
-        }
+            system_prompt_template,
+            tool_names,
+            deadline: self.deadline,
+            token_limit: self.token_limit,
+            structured_output: self.structured_output,
+            id_generator: self.id_generator,
+            run_error_handlers,
+        })
     }
 }
 
@@ -291,12 +987,67 @@ struct AgentMiddlewareEdge {
     branches: Vec<InternedGraphLabel>,
 }
 
+/// 模型调用之后的去向
+#[derive(Clone, Copy)]
+enum AfterModelRoute {
+    /// 进入 after_model 钩子链
+    Hooks(InternedGraphLabel),
+    /// 没有 after_model 钩子：由路由函数决定执行工具还是进入 `end`
+    ToolsOr {
+        end: InternedGraphLabel,
+        route: RouteFn,
+    },
+}
+
+impl AfterModelRoute {
+    fn resolve(self, state: &MessagesState) -> InternedGraphLabel {
+        match self {
+            Self::Hooks(entry) => entry,
+            Self::ToolsOr { end, route } => resolve_route(route, state, end),
+        }
+    }
+
+    fn targets(self) -> impl Iterator<Item = InternedGraphLabel> {
+        let (first, tool) = match self {
+            Self::Hooks(entry) => (entry, None),
+            Self::ToolsOr { end, .. } => (end, Some(ReactAgentLabel::Tool.intern())),
+        };
+        std::iter::once(first).chain(tool)
+    }
+}
+
+/// 把路由函数返回的标签映射到图中的节点：`Tool` 执行工具，其余进入 `end`
+fn resolve_route(
+    route: RouteFn,
+    state: &MessagesState,
+    end: InternedGraphLabel,
+) -> InternedGraphLabel {
+    let label = route(state);
+    if label == ReactAgentLabel::Tool.intern() {
+        return label;
+    }
+    if label != BaseGraphLabel::End.intern() {
+        tracing::warn!("route returned unknown label {label:?}, ending the run");
+    }
+    end
+}
+
+/// 钩子通过 [`JumpTo`] 请求跳转时的目标
+#[derive(Clone, Copy)]
+struct JumpTargets {
+    /// [`JumpTo::End`] 的目标
+    end: InternedGraphLabel,
+    /// [`JumpTo::SkipModel`] 的目标，只对模型调用前的钩子有效
+    after_model: Option<AfterModelRoute>,
+}
+
 fn apply_middleware_chain(
     graph: &mut StateGraph<ReactAgentSpec>,
     nodes: &[AgentMiddlewareEdge],
     next_label: InternedGraphLabel,
     reverse: bool,
-    check_tool_calls: bool,
+    route: Option<RouteFn>,
+    jumps: JumpTargets,
 ) -> InternedGraphLabel {
     if nodes.is_empty() {
         return next_label;
@@ -325,18 +1076,33 @@ fn apply_middleware_chain(
             .map(|&l| (l, l))
             .collect::<HashMap<_, _>>();
         branches.insert(next, next);
-        if check_tool_calls && is_last {
+        if route.is_some() && is_last {
             branches.insert(
                 ReactAgentLabel::Tool.intern(),
                 ReactAgentLabel::Tool.intern(),
             );
         }
+        branches.insert(jumps.end, jumps.end);
+        for label in jumps
+            .after_model
+            .into_iter()
+            .flat_map(AfterModelRoute::targets)
+        {
+            branches.insert(label, label);
+        }
 
         graph.add_condition_edge(current_label, branches, move |state: &MessagesState| {
+            match (state.jump_to, jumps.after_model) {
+                (Some(JumpTo::End), _) => return smallvec![jumps.end],
+                (Some(JumpTo::SkipModel), Some(route)) => return smallvec![route.resolve(state)],
+                _ => {}
+            }
             if let Some(target) = target {
                 smallvec![target]
-            } else if check_tool_calls && is_last && state.last_tool_calls().is_some() {
-                smallvec![ReactAgentLabel::Tool.intern()]
+            } else if let Some(route) = route
+                && is_last
+            {
+                smallvec![resolve_route(route, state, next)]
             } else {
                 smallvec![next]
             }
@@ -349,6 +1115,14 @@ fn apply_middleware_chain(
 pub struct ReactAgent {
     pub graph: StateGraph<ReactAgentSpec>,
     pub system_prompt: Option<String>,
+    system_prompt_template: Option<(PromptTemplate, HashMap<String, String>)>,
+    tool_names: HashSet<String>,
+    deadline: Option<Duration>,
+    token_limit: Option<u32>,
+    structured_output: StructuredOutputStrategy,
+    id_generator: Option<Arc<dyn IdGenerator>>,
+    /// 中间件的 `on_run_error` 回调，按注册顺序
+    run_error_handlers: Vec<RunErrorHandler>,
 }
 
 impl ReactAgent {
@@ -366,181 +1140,603 @@ impl ReactAgent {
         Self::builder(model).with_tools(tools).build()
     }
 
+    /// The generator for the ids this agent mints, [`UuidGenerator`] unless
+    /// one was set with [`ReactAgentBuilder::with_id_generator`]. Use it for
+    /// ids minted on the agent's behalf, e.g. new thread ids.
+    pub fn id_generator(&self) -> &dyn IdGenerator {
+        self.id_generator.as_deref().unwrap_or(&UuidGenerator)
+    }
+
     pub async fn invoke(
         &self,
         message: Message,
         thread_id: Option<&str>,
     ) -> Result<MessagesState, AgentError> {
-        let config = thread_id.map_or(
-            Configuration {
-                thread_id: None,
-                response_format: None,
-            },
-            |thread_id| Configuration {
-                thread_id: Some(thread_id.to_owned()),
-                response_format: None,
-            },
-        );
-
-        let (mut state, resume_from) = self.get_state(&config).await;
-        state.push_message_owned(message.clone());
-        let max_steps = 25;
-
-        let (state, _) = self
-            .graph
-            .run(
-                state,
-                &config,
-                max_steps,
-                RunStrategy::StopAtNonLinear,
-                resume_from,
-            )
-            .await?;
+        let config = Configuration {
+            thread_id: thread_id.map(ToOwned::to_owned),
+            ..Configuration::default()
+        };
 
-        Ok(state)
+        self.run_with_config(message, config).await
     }
 
-    pub async fn invoke_structured<S>(
+    /// Like [`ReactAgent::invoke`], but fills the system prompt template (see
+    /// [`ReactAgentBuilder::with_system_prompt_template`]) with `vars` as well,
+    /// e.g. the current date or the user's name. The prompt is only rendered
+    /// when the run starts a new conversation.
+    pub async fn invoke_with_vars<I, K, V>(
         &self,
         message: Message,
         thread_id: Option<&str>,
-    ) -> Result<AgentState<MessagesState, S>, AgentError>
+        vars: I,
+    ) -> Result<MessagesState, AgentError>
     where
-        S: DeserializeOwned + JsonSchema,
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
     {
-        let mode = FormatType::JsonObject;
-
-        let response_format = match mode {
-            FormatType::JsonSchema => {
-                let schema = serde_json::to_string(&schemars::schema_for!(S)).map_err(|e| {
-                    AgentError::StructuredOutput(format!("Failed to serialize schema: {}", e))
-                })?;
-                Some(ResponseFormat {
-                    format_type: FormatType::JsonSchema,
-                    json_schema: Some(schema),
-                })
-            }
-            FormatType::JsonObject => Some(ResponseFormat {
-                format_type: FormatType::JsonObject,
-                json_schema: None,
-            }),
-            _ => None,
+        let config = Configuration {
+            thread_id: thread_id.map(ToOwned::to_owned),
+            vars: collect_vars(vars),
+            ..Configuration::default()
         };
-
-        let config = thread_id.map_or(
-            Configuration {
-                thread_id: None,
-                response_format: response_format.clone(),
-            },
-            |thread_id| Configuration {
-                thread_id: Some(thread_id.to_owned()),
-                response_format,
-            },
-        );
-
-        let (mut state, resume_from) = self.get_state(&config).await;
-        state.push_message_owned(message.clone());
-        let max_steps = 25;
-
-        let (state, _) = self
-            .graph
-            .run(
-                state,
-                &config,
-                max_steps,
-                RunStrategy::StopAtNonLinear,
-                resume_from,
-            )
-            .await?;
-
-        let content = state
-            .last_assistant()
-            .ok_or_else(|| AgentError::Agent("No assistant message in state".to_owned()))?
-            .content();
-
-        let output: S = serde_json::from_str(content).map_err(|e| {
-            AgentError::StructuredOutput(format!("Failed to parse structured output: {}", e))
-        })?;
-
-        Ok(AgentState {
-            state,
-            struct_output: Some(output),
-        })
+        self.run_with_config(message, config).await
     }
 
-    pub async fn stream<'a>(
-        &'a self,
+    /// Like [`ReactAgent::invoke`], but only exposes the named tools for this run.
+    ///
+    /// The model sees only these tools and calls to any other tool are not
+    /// executed, so one agent can serve callers with different permissions
+    /// without rebuilding its graph. Every name must be a tool the agent was
+    /// built with.
+    pub async fn invoke_with_tools<I, S>(
+        &self,
         message: Message,
         thread_id: Option<&str>,
-    ) -> Result<impl Stream<Item = ChatStreamEvent> + 'a, AgentError> {
-        let graph = &self.graph;
-
-        let config = thread_id.map_or(
-            Configuration {
-                thread_id: None,
-                response_format: None,
-            },
-            |thread_id| Configuration {
-                thread_id: Some(thread_id.to_owned()),
-                response_format: None,
-            },
-        );
-
-        let (mut state, resume_from) = self.get_state(&config).await;
-
-        state.push_message_owned(message.clone());
-        let max_steps = 25;
-
-        let stream = async_stream::stream! {
-            let mut inner_stream = graph.stream(
-                state,
-                &config,
-                max_steps,
-                RunStrategy::StopAtNonLinear,
-                resume_from,
-            );
+        tools: I,
+    ) -> Result<MessagesState, AgentError>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let tools: Vec<String> = tools.into_iter().map(Into::into).collect();
+        if let Some(unknown) = tools.iter().find(|name| !self.tool_names.contains(*name)) {
+            return Err(AgentError::Agent(format!("unknown tool: {unknown}")));
+        }
 
-            while let Some(item) = inner_stream.next().await {
-                yield item;
-            }
+        let config = Configuration {
+            thread_id: thread_id.map(ToOwned::to_owned),
+            tools: Some(tools),
+            ..Configuration::default()
         };
+        self.run_with_config(message, config).await
+    }
 
-        Ok(stream)
+    /// Like [`ReactAgent::invoke`], but stops as soon as `cancel` is
+    /// cancelled, e.g. when the user navigates away.
+    ///
+    /// The in-flight model or tool calls are dropped and the run fails with
+    /// [`AgentError::Cancelled`], carrying the state after the last completed
+    /// step (which the checkpointer, if any, has already saved). A model reply
+    /// whose tool calls were cancelled before they ran is rolled back, so the
+    /// next message on the thread starts a clean turn.
+    pub async fn invoke_with_cancel(
+        &self,
+        message: Message,
+        thread_id: Option<&str>,
+        cancel: CancellationToken,
+    ) -> Result<MessagesState, AgentError> {
+        let config = Configuration {
+            thread_id: thread_id.map(ToOwned::to_owned),
+            ..Configuration::default()
+        };
+        let (mut state, resume_from) = self.get_state(&config).await?;
+        state.push_message_owned(message);
+        AgentStepper::new(self, state, &config, resume_from)
+            .with_cancellation(cancel)
+            .run_to_end()
+            .await
     }
 
-    async fn get_state(
+    async fn run_with_config(
         &self,
-        config: &Configuration,
-    ) -> (MessagesState, Option<SmallVec<[String; 4]>>) {
+        message: Message,
+        config: Configuration,
+    ) -> Result<MessagesState, AgentError> {
+        let (mut state, resume_from) = self.get_state(&config).await?;
+        state.push_message_owned(message);
+        let state = self.run_graph(state, &config, resume_from).await?;
+
+        Ok(state)
+    }
+
+    /// Runs the graph to completion, failing with [`AgentError::MaxStepsExceeded`]
+    /// when nodes are still pending after [`MAX_STEPS`] super-steps, with
+    /// [`AgentError::TokenLimitExceeded`] when the run uses too many tokens, or
+    /// with [`AgentError::Timeout`] when the run's deadline passes first.
+    async fn run_graph(
+        &self,
+        state: MessagesState,
+        config: &Configuration,
+        resume_from: Option<SmallVec<[String; 4]>>,
+    ) -> Result<MessagesState, AgentError> {
+        AgentStepper::new(self, state, config, resume_from)
+            .run_to_end()
+            .await
+    }
+
+    /// Starts a run from `initial` that the caller advances one step at a
+    /// time, e.g. to confirm tool calls before they execute.
+    ///
+    /// `initial` is used as is: add the system prompt and the user message
+    /// yourself. With a `thread_id`, every step is saved to the agent's
+    /// checkpointer under it (the last one as the final checkpoint), so a later
+    /// [`invoke`](Self::invoke) on the thread continues the conversation; the
+    /// thread id is also what middlewares and traces see. See [`AgentStepper`]
+    /// for what can change between steps.
+    pub fn stepper(&self, initial: MessagesState, thread_id: Option<&str>) -> AgentStepper<'_> {
+        let config = Configuration {
+            thread_id: thread_id.map(ToOwned::to_owned),
+            ..Configuration::default()
+        };
+        AgentStepper::new(self, initial, &config, None)
+    }
+
+    /// Continues the conversation stored under `thread_id`.
+    ///
+    /// Loads the thread's history from the checkpointer, appends `message`, runs
+    /// the agent and saves the updated state back. The first message on a new
+    /// thread starts from the system prompt (if any). Returns an error when the
+    /// agent was built without a checkpointer, since history could not persist.
+    pub async fn invoke_with_thread(
+        &self,
+        message: Message,
+        thread_id: &str,
+    ) -> Result<MessagesState, AgentError> {
+        if self.graph.checkpointer.is_none() {
+            return Err(AgentError::Agent(
+                "invoke_with_thread requires a checkpointer".to_owned(),
+            ));
+        }
+        self.invoke(message, Some(thread_id)).await
+    }
+
+    /// The nodes a paused run on `thread_id` will execute when resumed (see
+    /// [`ReactAgentBuilder::with_interrupt_before`]), in the order they run.
+    /// Empty when the thread's last run finished or the thread is unknown.
+    pub async fn pending_nodes(&self, thread_id: &str) -> Result<Vec<String>, AgentError> {
+        let Some(checkpointer) = &self.graph.checkpointer else {
+            return Ok(Vec::new());
+        };
+        let checkpoint = checkpointer
+            .get(thread_id)
+            .await
+            .map_err(|e| AgentError::Agent(format!("failed to load checkpoint: {e}")))?;
+        Ok(checkpoint
+            .map(|checkpoint| checkpoint.next_nodes.into_vec())
+            .unwrap_or_default())
+    }
+
+    /// Continues a run on `thread_id` that paused at an interrupt, without
+    /// adding a message. Edit the thread's state first to change what the
+    /// pending nodes see, e.g. to drop tool calls that were not approved.
+    /// Returns the stored state unchanged when nothing is pending. Requires a
+    /// checkpointer.
+    pub async fn resume(&self, thread_id: &str) -> Result<MessagesState, AgentError> {
+        let Some(checkpointer) = &self.graph.checkpointer else {
+            return Err(AgentError::Agent(
+                "resume requires a checkpointer".to_owned(),
+            ));
+        };
+        let checkpoint = checkpointer
+            .get(thread_id)
+            .await
+            .map_err(|e| AgentError::Agent(format!("failed to load checkpoint: {e}")))?
+            .ok_or_else(|| AgentError::Agent(format!("unknown thread: {thread_id}")))?;
+        if checkpoint.next_nodes.is_empty() {
+            return Ok(checkpoint.state);
+        }
+        let config = Configuration {
+            thread_id: Some(thread_id.to_owned()),
+            ..Configuration::default()
+        };
+        AgentStepper::new(self, checkpoint.state, &config, Some(checkpoint.next_nodes))
+            .resuming_interrupt()
+            .run_to_end()
+            .await
+    }
+
+    /// Runs independent single-turn conversations concurrently.
+    ///
+    /// At most `concurrency` runs are in flight at once (values below 1 are
+    /// treated as 1). Each run starts from a fresh state; when a checkpointer is
+    /// configured every run gets its own `thread_id` from the agent's
+    /// [`id_generator`](Self::id_generator) so runs never share history; the
+    /// id is returned with the run's result. Results are returned in input
+    /// order.
+    pub async fn invoke_batch(&self, messages: Vec<Message>, concurrency: usize) -> Vec<BatchRun> {
+        let has_checkpointer = self.graph.checkpointer.is_some();
+        futures::stream::iter(messages)
+            .map(|message| async move {
+                let thread_id = has_checkpointer.then(|| self.id_generator().next_id());
+                let result = self.invoke(message, thread_id.as_deref()).await;
+                BatchRun { thread_id, result }
+            })
+            .buffered(concurrency.max(1))
+            .collect()
+            .await
+    }
+
+    /// Runs the agent and parses the final assistant message as JSON into `S`.
+    ///
+    /// A reply that fails to parse is not an error: the returned [`AgentState`]
+    /// keeps the final state, the raw reply text and the [`ParseError`], so
+    /// callers can see what the model actually produced. Use
+    /// [`AgentState::into_output`] to get `S` or the parse error. Whether the
+    /// model is asked again first depends on the agent's
+    /// [`StructuredOutputStrategy`].
+    ///
+    /// This is a change from earlier versions, which failed with
+    /// [`AgentError::StructuredOutput`] when the reply did not parse. Callers
+    /// that relied on `?` to catch bad replies should check
+    /// [`AgentState::parse_error`] or call [`AgentState::into_output`]; see
+    /// `CHANGELOG.md`. This method now only returns
+    /// [`AgentError::StructuredOutput`] when `S` has no usable JSON schema.
+    ///
+    /// [`ParseError`]: langchain_core::parsers::ParseError
+    pub async fn invoke_structured<S>(
+        &self,
+        message: Message,
+        thread_id: Option<&str>,
+    ) -> Result<AgentState<MessagesState, S>, AgentError>
+    where
+        S: DeserializeOwned + JsonSchema + Send + Sync,
+    {
+        let config = Configuration {
+            thread_id: thread_id.map(ToOwned::to_owned),
+            response_format: structured_response_format::<S>()?,
+            ..Configuration::default()
+        };
+        self.run_structured(message, config, &JsonParser::<S>::strict())
+            .await
+    }
+
+    /// Like [`ReactAgent::invoke_structured`], but fills the system prompt
+    /// template with `vars` as well, see [`ReactAgent::invoke_with_vars`].
+    pub async fn invoke_structured_with_vars<S, I, K, V>(
+        &self,
+        message: Message,
+        thread_id: Option<&str>,
+        vars: I,
+    ) -> Result<AgentState<MessagesState, S>, AgentError>
+    where
+        S: DeserializeOwned + JsonSchema + Send + Sync,
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let config = Configuration {
+            thread_id: thread_id.map(ToOwned::to_owned),
+            response_format: structured_response_format::<S>()?,
+            vars: collect_vars(vars),
+            ..Configuration::default()
+        };
+        self.run_structured(message, config, &JsonParser::<S>::strict())
+            .await
+    }
+
+    /// Like [`ReactAgent::invoke_structured`], but parses the final assistant
+    /// message with `parser`, e.g. a lenient [`JsonParser::new`] or a
+    /// [`ListParser`](langchain_core::parsers::ListParser). No `response_format`
+    /// is sent, since the expected format is up to the parser; tell the model
+    /// about it in the prompt, e.g. with
+    /// [`OutputParser::get_format_instructions`].
+    pub async fn invoke_structured_with<S>(
+        &self,
+        message: Message,
+        thread_id: Option<&str>,
+        parser: &dyn OutputParser<S>,
+    ) -> Result<AgentState<MessagesState, S>, AgentError> {
+        let config = Configuration {
+            thread_id: thread_id.map(ToOwned::to_owned),
+            ..Configuration::default()
+        };
+        self.run_structured(message, config, parser).await
+    }
+
+    async fn run_structured<S>(
+        &self,
+        message: Message,
+        config: Configuration,
+        parser: &dyn OutputParser<S>,
+    ) -> Result<AgentState<MessagesState, S>, AgentError> {
+        let (mut state, resume_from) = self.get_state(&config).await?;
+        state.push_message_owned(message);
+        // 重新询问在同一次运行中继续，共用截止时间和步数上限
+        let mut stepper = AgentStepper::new(self, state, &config, resume_from);
+        stepper.run_until_paused().await?;
+
+        let mut retries = match self.structured_output {
+            StructuredOutputStrategy::ParseFinal => 0,
+            StructuredOutputStrategy::Reprompt { max_retries } => max_retries,
+        };
+        loop {
+            let state = std::mem::take(stepper.state_mut());
+            let raw = state
+                .last_assistant()
+                .map(|m| m.content().to_owned())
+                .unwrap_or_default();
+            let output = AgentState::parse(state, raw, |raw| parser.parse(raw));
+            let Some(error) = output.parse_error.as_ref().filter(|_| retries > 0) else {
+                return Ok(output);
+            };
+            retries -= 1;
+            debug!("最终回复解析失败，重新询问模型: {error}");
+            let reprompt = Message::user(format!(
+                "Your previous reply could not be parsed: {error}\n{}\nReply again with only the final answer in this format.",
+                parser.get_format_instructions()
+            ));
+            *stepper.state_mut() = output.state;
+            stepper.continue_with(reprompt);
+            stepper.run_until_paused().await?;
+        }
+    }
+
+    /// Streams a structured reply: every content token yields a
+    /// [`StructuredStreamEvent::Partial`] with the JSON parsed so far, for
+    /// live-updating UIs, and the run ends with a [`StructuredStreamEvent::Final`]
+    /// holding the reply validated as `S`.
+    ///
+    /// Only the final reply is validated. If it is empty or not valid JSON for
+    /// `S` when the run ends, the last item is the [`ParseError`] instead.
+    /// Content of model calls that request tools is not part of the reply.
+    /// The agent's deadline applies as in [`ReactAgent::stream`]: a run cut
+    /// short ends with whatever reply was streamed so far, usually a
+    /// [`ParseError`].
+    pub async fn invoke_structured_stream<'a, S>(
+        &'a self,
+        message: Message,
+        thread_id: Option<&str>,
+    ) -> Result<impl Stream<Item = Result<StructuredStreamEvent<S>, ParseError>> + 'a, AgentError>
+    where
+        S: DeserializeOwned + JsonSchema + Send + Sync + 'a,
+    {
+        let config = Configuration {
+            thread_id: thread_id.map(ToOwned::to_owned),
+            response_format: structured_response_format::<S>()?,
+            ..Configuration::default()
+        };
+        let events = self.stream_with_config(message, config).await?;
+
+        Ok(async_stream::stream! {
+            let parser = JsonParser::<S>::new();
+            let mut events = std::pin::pin!(events);
+            // 当前模型调用已输出的内容；以最后一次不调用工具的模型回复作为最终结果
+            let mut reply = String::new();
+            let mut calls_tools = false;
+            let mut final_reply = String::new();
+            let mut last_partial = None;
+            while let Some(event) = events.next().await {
+                match event {
+                    ChatStreamEvent::Content(text) => {
+                        reply.push_str(&text);
+                        if let Some(value) = parser.parse_partial(&reply)
+                            && last_partial.as_ref() != Some(&value)
+                        {
+                            last_partial = Some(value.clone());
+                            yield Ok(StructuredStreamEvent::Partial(value));
+                        }
+                    }
+                    ChatStreamEvent::ToolCallDelta { .. } => calls_tools = true,
+                    ChatStreamEvent::Done { .. } => {
+                        if calls_tools {
+                            reply.clear();
+                        } else {
+                            final_reply = std::mem::take(&mut reply);
+                        }
+                        calls_tools = false;
+                        last_partial = None;
+                    }
+                    _ => {}
+                }
+            }
+            if !calls_tools && !reply.is_empty() {
+                final_reply = reply;
+            }
+
+            yield if final_reply.trim().is_empty() {
+                Err(ParseError::EmptyOutput)
+            } else {
+                parser.parse(&final_reply).map(StructuredStreamEvent::Final)
+            };
+        })
+    }
+
+    /// Streams the run's events as they happen.
+    ///
+    /// The agent's deadline (see [`ReactAgentBuilder::with_deadline`]) applies:
+    /// when it passes, the in-flight step is dropped and the stream ends with a
+    /// [`ChatStreamEvent::Done`] whose `finish_reason` is `"timeout"`. Steps
+    /// completed before that are saved by the checkpointer as usual. Middleware
+    /// `on_run_error` handlers see an [`AgentError::Timeout`] carrying the state
+    /// the run started from, since the stream does not collect the state.
+    pub async fn stream<'a>(
+        &'a self,
+        message: Message,
+        thread_id: Option<&str>,
+    ) -> Result<impl Stream<Item = ChatStreamEvent> + 'a, AgentError> {
+        let config = Configuration {
+            thread_id: thread_id.map(ToOwned::to_owned),
+            ..Configuration::default()
+        };
+
+        self.stream_with_config(message, config).await
+    }
+
+    /// Like [`ReactAgent::stream`], but fills the system prompt template with
+    /// `vars` as well, see [`ReactAgent::invoke_with_vars`].
+    pub async fn stream_with_vars<'a, I, K, V>(
+        &'a self,
+        message: Message,
+        thread_id: Option<&str>,
+        vars: I,
+    ) -> Result<impl Stream<Item = ChatStreamEvent> + 'a, AgentError>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let config = Configuration {
+            thread_id: thread_id.map(ToOwned::to_owned),
+            vars: collect_vars(vars),
+            ..Configuration::default()
+        };
+
+        self.stream_with_config(message, config).await
+    }
+
+    async fn stream_with_config(
+        &self,
+        message: Message,
+        mut config: Configuration,
+    ) -> Result<impl Stream<Item = ChatStreamEvent> + '_, AgentError> {
+        let graph = &self.graph;
+        let started = tokio::time::Instant::now();
+        if config.deadline.is_none() {
+            config.deadline = self.deadline.map(|deadline| started + deadline);
+        }
+
+        let (mut state, resume_from) = self.get_state(&config).await?;
+
+        state.push_message_owned(message);
+
+        Ok(async_stream::stream! {
+            // 超时时交给 on_run_error 的状态
+            let mut initial = config.deadline.is_some().then(|| state.clone());
+            let mut inner_stream = graph.stream(
+                state,
+                &config,
+                MAX_STEPS,
+                RunStrategy::StopAtNonLinear,
+                resume_from,
+            );
+
+            loop {
+                let next = match config.deadline {
+                    Some(deadline) => {
+                        match tokio::time::timeout_at(deadline, inner_stream.next()).await {
+                            Ok(next) => next,
+                            Err(_) => {
+                                let error = AgentError::Timeout {
+                                    elapsed: started.elapsed(),
+                                    state: Box::new(initial.take().unwrap_or_default()),
+                                };
+                                tracing::warn!("streamed run stopped: {error}");
+                                for handler in &self.run_error_handlers {
+                                    handler(&config, &error);
+                                }
+                                yield ChatStreamEvent::Done {
+                                    finish_reason: Some("timeout".to_owned()),
+                                    usage: None,
+                                };
+                                break;
+                            }
+                        }
+                    }
+                    None => inner_stream.next().await,
+                };
+                let Some(item) = next else {
+                    break;
+                };
+                yield item;
+            }
+        })
+    }
+
+    /// 新对话使用的系统提示词；有模板时用 `config.vars`、构建时的变量和 `{tools}` 渲染
+    fn render_system_prompt(&self, config: &Configuration) -> Result<Option<String>, AgentError> {
+        let Some((template, defaults)) = &self.system_prompt_template else {
+            return Ok(self.system_prompt.clone());
+        };
+        let mut tools: Vec<&str> = match &config.tools {
+            Some(tools) => tools.iter().map(String::as_str).collect(),
+            None => self.tool_names.iter().map(String::as_str).collect(),
+        };
+        tools.sort_unstable();
+        let mut all = HashMap::from([("tools".to_owned(), tools.join(", "))]);
+        all.extend(defaults.iter().map(|(k, v)| (k.clone(), v.clone())));
+        all.extend(config.vars.iter().map(|(k, v)| (k.clone(), v.clone())));
+        Ok(Some(template.render(&all)?))
+    }
+
+    async fn get_state(
+        &self,
+        config: &Configuration,
+    ) -> Result<(MessagesState, Option<SmallVec<[String; 4]>>), AgentError> {
         if let Some(checkpointer) = &self.graph.checkpointer
             && let Some(thread_id) = &config.thread_id
         {
             debug!("有checkpointer，尝试从checkpointer获取状态");
             if let Ok(Some(checkpoint)) = checkpointer.get(thread_id).await {
                 debug!("从checkpointer获取状态成功");
-                (checkpoint.state, Some(checkpoint.next_nodes))
+                Ok((checkpoint.state, Some(checkpoint.next_nodes)))
             } else {
                 debug!("从checkpointer获取状态失败，初始化新状态");
                 let mut state = MessagesState::default();
-                if let Some(system_prompt) = &self.system_prompt {
-                    state.push_message_owned(Message::system(system_prompt.clone()));
+                if let Some(system_prompt) = self.render_system_prompt(config)? {
+                    state.push_message_owned(Message::system(system_prompt));
                 }
                 let checkpoint = Checkpoint::new_auto(state.clone(), thread_id.clone(), 0, None);
                 if let Err(e) = checkpointer.put(&checkpoint).await {
                     tracing::error!("Failed to save checkpoint: {:?}", e);
                 }
-                (state, None)
+                Ok((state, None))
             }
         } else {
             let mut state = MessagesState::default();
-            if let Some(system_prompt) = &self.system_prompt {
-                state.push_message_owned(Message::system(system_prompt.clone()));
+            if let Some(system_prompt) = self.render_system_prompt(config)? {
+                state.push_message_owned(Message::system(system_prompt));
             }
-            (state, None)
+            Ok((state, None))
         }
     }
 }
 
+/// 把调用方传入的模板变量收集成 [`Configuration::vars`]
+fn collect_vars<I, K, V>(vars: I) -> HashMap<String, String>
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: Into<String>,
+    V: Into<String>,
+{
+    vars.into_iter()
+        .map(|(k, v)| (k.into(), v.into()))
+        .collect()
+}
+
+/// 结构化输出请求使用的 `response_format`
+fn structured_response_format<S: JsonSchema>() -> Result<Option<ResponseFormat>, AgentError> {
+    let mode = FormatType::JsonObject;
+
+    let response_format = match mode {
+        FormatType::JsonSchema => {
+            let schema = serde_json::to_string(&schemars::schema_for!(S)).map_err(|e| {
+                AgentError::StructuredOutput(format!("Failed to serialize schema: {}", e))
+            })?;
+            Some(ResponseFormat {
+                format_type: FormatType::JsonSchema,
+                json_schema: Some(schema),
+            })
+        }
+        FormatType::JsonObject => Some(ResponseFormat {
+            format_type: FormatType::JsonObject,
+            json_schema: None,
+        }),
+        _ => None,
+    };
+    Ok(response_format)
+}
+
 fn parse_tool<E>(tools: Vec<RegisteredTool<E>>) -> (Vec<ToolSpec>, HashMap<String, Arc<ToolFn<E>>>)
 where
     E: Error + Send + Sync + 'static,
@@ -562,6 +1758,7 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testing::MockModel;
     use async_trait::async_trait;
     use langchain_core::state::{ChatCompletion, ChatStreamEvent};
     use langchain_core::tool;
@@ -605,6 +1802,7 @@ mod tests {
                 content: "assistant".to_owned(),
                 tool_calls,
                 name: None,
+                metadata: Default::default(),
             };
 
             let usage = Usage::default();
@@ -623,7 +1821,7 @@ mod tests {
             use async_stream::try_stream;
 
             let stream = try_stream! {
-                yield ChatStreamEvent::ReasoningContent("assistant".to_owned());
+                yield ChatStreamEvent::Reasoning("assistant".to_owned());
                 yield ChatStreamEvent::Content("assistant".to_owned());
                 yield ChatStreamEvent::ToolCallDelta {
                     index: 0,
@@ -658,7 +1856,19 @@ mod tests {
             .with_tools(vec![tool])
             .build();
 
-        let _final_state = agent.invoke(Message::user("hello"), None).await.unwrap();
+        // TestModel 每次都请求工具，运行会在步数上限处停止并带回中间状态
+        let result = agent.invoke(Message::user("hello"), None).await;
+        let Err(AgentError::MaxStepsExceeded { steps, state }) = result else {
+            panic!("expected MaxStepsExceeded, got {result:?}");
+        };
+        assert_eq!(steps, MAX_STEPS);
+        assert!(state.llm_calls > 1);
+        assert!(
+            state
+                .messages
+                .iter()
+                .any(|m| m.content() == "\"tool_result\"")
+        );
     }
 
     #[tokio::test]
@@ -702,6 +1912,1245 @@ mod tests {
         assert_eq!(state3.messages.len(), 2);
     }
 
+    #[tool(description = "another test tool")]
+    async fn other_tool() -> Result<String, TestError> {
+        Ok("other_result".to_owned())
+    }
+
+    #[tokio::test]
+    async fn invoke_with_tools_limits_visible_tools() {
+        let model = Arc::new(MockModel::from_fn(|_| Ok(Message::assistant("done"))));
+        let agent = ReactAgent::builder(model.clone())
+            .with_tools(vec![test_tool_tool(), other_tool_tool()])
+            .build();
+
+        agent
+            .invoke_with_tools(Message::user("hi"), None, ["other_tool"])
+            .await
+            .unwrap();
+        agent
+            .invoke_with_tools(Message::user("hi"), None, Vec::<String>::new())
+            .await
+            .unwrap();
+        // 每次调用时模型看到的工具
+        let seen: Vec<Vec<String>> = model
+            .requests()
+            .iter()
+            .map(|request| {
+                request
+                    .tools
+                    .iter()
+                    .map(|tool| tool.function_name().to_owned())
+                    .collect()
+            })
+            .collect();
+        assert_eq!(seen, vec![vec!["other_tool".to_owned()], Vec::new()]);
+
+        let result = agent
+            .invoke_with_tools(Message::user("hi"), None, ["missing_tool"])
+            .await;
+        assert!(matches!(result, Err(AgentError::Agent(_))));
+    }
+
+    fn before_model_middleware(
+        label: node::middleware::MiddlewareLabel,
+        handler: impl Fn(&MessagesState) -> MessagesState + Send + Sync + 'static,
+    ) -> AgentMiddleware<MessagesState> {
+        AgentMiddleware::from_label(label).with_before_model(AgentHook {
+            handler: Arc::new(move |state: &MessagesState, _ctx: &_| {
+                let update = handler(state);
+                Box::pin(async move { Ok(update) })
+            }),
+            target: None,
+            branches: vec![],
+        })
+    }
+
+    #[tokio::test]
+    async fn message_normalization_merges_injected_messages() {
+        for normalize in [false, true] {
+            let model = Arc::new(MockModel::from_fn(|_| Ok(Message::assistant("ok"))));
+            let agent = ReactAgent::builder(model.clone())
+                .with_system_prompt("be brief")
+                .with_message_normalization(normalize)
+                .with_middlewares([before_model_middleware(
+                    define_middleware_label!(Reminder),
+                    |_| {
+                        let mut update = MessagesState::default();
+                        update.push_message_owned(Message::user("remember to be polite"));
+                        update
+                    },
+                )])
+                .build();
+
+            let state = agent.invoke(Message::user("hi"), None).await.unwrap();
+
+            // 保存的状态不受影响，只有发送给模型的消息被整理
+            assert_eq!(state.messages.len(), 4);
+            let expected = if normalize { 2 } else { 3 };
+            assert_eq!(model.requests()[0].messages.len(), expected);
+            assert_eq!(model.call_count(), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn reasoning_is_streamed_unless_hidden_and_never_routes_to_tools() {
+        use futures::StreamExt;
+
+        let reply = || Message::Assistant {
+            content: "It is sunny.".to_owned(),
+            reasoning_content: Some("I could call get_weather(city=Paris).".to_owned()),
+            tool_calls: None,
+            name: None,
+            metadata: Default::default(),
+        };
+        for hidden in [false, true] {
+            let agent = ReactAgent::builder(MockModel::from_fn(move |_| Ok(reply())))
+                .with_tools(vec![test_tool_tool()])
+                .with_reasoning_hidden(hidden)
+                .build();
+
+            let events: Vec<_> = agent
+                .stream(Message::user("weather?"), None)
+                .await
+                .unwrap()
+                .collect()
+                .await;
+            let streamed = events
+                .iter()
+                .any(|event| matches!(event, ChatStreamEvent::Reasoning(_)));
+            assert_eq!(streamed, !hidden);
+
+            let state = agent.invoke(Message::user("weather?"), None).await.unwrap();
+            assert_eq!(state.llm_calls, 1);
+            let Message::Assistant {
+                reasoning_content, ..
+            } = state.last_assistant().unwrap().as_ref()
+            else {
+                unreachable!()
+            };
+            assert_eq!(reasoning_content.is_some(), !hidden);
+        }
+    }
+
+    #[tokio::test]
+    async fn before_model_hook_can_short_circuit() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let from_model = || Arc::new(MockModel::from_fn(|_| Ok(Message::assistant("from model"))));
+
+        // 拒绝：结束运行，后注册的中间件不会执行
+        let model = from_model();
+        let later_ran = Arc::new(AtomicUsize::new(0));
+        let later = later_ran.clone();
+        let agent = ReactAgent::builder(model.clone())
+            .with_middlewares([
+                before_model_middleware(define_middleware_label!(Refuse), |_| {
+                    let mut update = MessagesState::default();
+                    update.push_message_owned(Message::assistant("refused"));
+                    update.jump(JumpTo::End);
+                    update
+                }),
+                before_model_middleware(define_middleware_label!(Later), move |_| {
+                    later.fetch_add(1, Ordering::SeqCst);
+                    MessagesState::default()
+                }),
+            ])
+            .build();
+        let state = agent.invoke(Message::user("hi"), None).await.unwrap();
+        assert_eq!(state.last_message().unwrap().content(), "refused");
+        assert_eq!(model.call_count(), 0);
+        assert_eq!(later_ran.load(Ordering::SeqCst), 0);
+
+        // 缓存命中：给出带工具调用的回复，工具照常执行，之后才真正调用模型
+        let model = from_model();
+        let agent = ReactAgent::builder(model.clone())
+            .with_tools(vec![test_tool_tool()])
+            .with_middlewares([before_model_middleware(
+                define_middleware_label!(Cache),
+                |state| {
+                    let mut update = MessagesState::default();
+                    if state.messages.len() == 1 {
+                        update.push_message_owned(Message::Assistant {
+                            content: String::new(),
+                            reasoning_content: None,
+                            tool_calls: Some(vec![ToolCall {
+                                id: "cached".to_owned(),
+                                type_name: "function".to_owned(),
+                                function: FunctionCall {
+                                    name: "test_tool".to_owned(),
+                                    arguments: serde_json::json!({}),
+                                },
+                            }]),
+                            name: None,
+                            metadata: Default::default(),
+                        });
+                        update.jump(JumpTo::SkipModel);
+                    }
+                    update
+                },
+            )])
+            .build();
+        let state = agent.invoke(Message::user("hi"), None).await.unwrap();
+        assert_eq!(state.messages[2].content(), "\"tool_result\"");
+        assert_eq!(state.last_message().unwrap().content(), "from model");
+        assert_eq!(model.call_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn invoke_with_thread_accumulates_history() {
+        use langgraph::checkpoint::MemorySaver;
+
+        let agent = ReactAgent::builder(TestModel)
+            .with_system_prompt("be brief")
+            .with_checkpointer(Arc::new(MemorySaver::new()))
+            .build();
+
+        let first = agent
+            .invoke_with_thread(Message::user("hello"), "chat")
+            .await
+            .unwrap();
+        // system + user + assistant
+        assert_eq!(first.messages.len(), 3);
+        assert!(matches!(first.messages[0].as_ref(), Message::System { .. }));
+
+        let second = agent
+            .invoke_with_thread(Message::user("again"), "chat")
+            .await
+            .unwrap();
+        assert_eq!(second.messages.len(), 5);
+        assert_eq!(second.messages[3].content(), "again");
+
+        let other = agent
+            .invoke_with_thread(Message::user("hi"), "other")
+            .await
+            .unwrap();
+        assert_eq!(other.messages.len(), 3);
+
+        let without_checkpointer = ReactAgent::builder(TestModel).build();
+        assert!(matches!(
+            without_checkpointer
+                .invoke_with_thread(Message::user("hello"), "chat")
+                .await,
+            Err(AgentError::Agent(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn tool_outputs_are_capped_per_agent_and_per_tool() {
+        let blob = |name: &str| {
+            RegisteredTool::<ToolError>::new(
+                name.to_owned(),
+                String::new(),
+                serde_json::json!({"type": "object"}),
+                Arc::new(|_| Box::pin(async { Ok(serde_json::json!("x".repeat(1000))) })),
+            )
+        };
+        let model = MockModel::new([
+            MockModel::tool_calls([
+                ("read_file", serde_json::json!({})),
+                ("read_page", serde_json::json!({})),
+            ]),
+            Message::assistant("done"),
+        ]);
+        let agent = ReactAgent::builder(model)
+            .with_tools([
+                blob("read_file"),
+                blob("read_page").with_max_output_chars(10),
+            ])
+            .with_max_tool_output_chars(100)
+            .build();
+
+        let state = agent.invoke(Message::user("go"), None).await.unwrap();
+        let outputs: Vec<_> = state
+            .messages
+            .iter()
+            .filter(|m| matches!(m.as_ref(), Message::Tool { .. }))
+            .map(|m| m.content().to_owned())
+            .collect();
+        assert!(outputs[0].ends_with("\n[truncated 902 chars]"));
+        assert!(outputs[1].ends_with("\n[truncated 992 chars]"));
+    }
+
+    #[tokio::test]
+    async fn terminal_tool_ends_the_run_after_its_turn() {
+        let echo = |name: &str| {
+            RegisteredTool::<ToolError>::new(
+                name.to_owned(),
+                String::new(),
+                serde_json::json!({"type": "object"}),
+                Arc::new(|args| {
+                    Box::pin(async move {
+                        match args["fail"].as_bool() {
+                            Some(true) => Err(ToolError::ExecutionFailed("bad answer".to_owned())),
+                            _ => Ok(args),
+                        }
+                    })
+                }),
+            )
+        };
+        let model = Arc::new(MockModel::new([
+            // 失败的终止工具调用不结束运行
+            MockModel::tool_call("submit", serde_json::json!({"fail": true})),
+            MockModel::tool_calls([
+                ("note", serde_json::json!({"text": "saved"})),
+                ("submit", serde_json::json!({"answer": 42})),
+            ]),
+        ]));
+        let agent = ReactAgent::builder(model.clone())
+            .with_tools([echo("note"), echo("submit").terminal()])
+            .build();
+
+        let state = agent.invoke(Message::user("go"), None).await.unwrap();
+
+        assert_eq!(model.call_count(), 2);
+        // 同一轮的其他工具照常执行，终止工具的结果是最后一条消息
+        let tail: Vec<_> = state.messages.iter().skip(3).map(|m| m.content()).collect();
+        assert_eq!(tail, ["", "{\"text\":\"saved\"}", "{\"answer\":42}"]);
+    }
+
+    #[tokio::test]
+    async fn custom_route_can_end_before_running_tools() {
+        // 模型请求 finish 时直接结束，不执行工具
+        fn route(state: &MessagesState) -> InternedGraphLabel {
+            match state.last_tool_calls() {
+                Some(calls) if calls.iter().any(|c| c.function_name() == "finish") => {
+                    BaseGraphLabel::End.intern()
+                }
+                _ => default_route(state),
+            }
+        }
+
+        let echo = |name: &str| {
+            RegisteredTool::<ToolError>::new(
+                name.to_owned(),
+                String::new(),
+                serde_json::json!({"type": "object"}),
+                Arc::new(|args| Box::pin(async move { Ok(args) })),
+            )
+        };
+        let model = Arc::new(MockModel::new([
+            MockModel::tool_call("lookup", serde_json::json!({"q": "rust"})),
+            MockModel::tool_call("finish", serde_json::json!({"answer": 42})),
+        ]));
+        let agent = ReactAgent::builder(model.clone())
+            .with_tools([echo("lookup"), echo("finish")])
+            .with_route(route)
+            .build();
+
+        let state = agent.invoke(Message::user("go"), None).await.unwrap();
+
+        assert_eq!(model.call_count(), 2);
+        // user、lookup 调用、lookup 结果、finish 调用
+        assert_eq!(state.messages.len(), 4);
+        assert_eq!(
+            state.last_tool_calls().unwrap()[0].function_name(),
+            "finish"
+        );
+    }
+
+    #[tokio::test]
+    async fn tool_choice_applies_to_the_first_call_of_a_turn() {
+        let lookup = || {
+            RegisteredTool::<ToolError>::new(
+                "lookup".to_owned(),
+                String::new(),
+                serde_json::json!({"type": "object"}),
+                Arc::new(|args| Box::pin(async move { Ok(args) })),
+            )
+        };
+        let model = Arc::new(MockModel::new([
+            MockModel::tool_call("lookup", serde_json::json!({"q": "rust"})),
+            Message::assistant("done"),
+        ]));
+        let agent = ReactAgent::builder(model.clone())
+            .with_tools([lookup()])
+            .with_tool_choice(ToolChoice::function("lookup"))
+            .build();
+        agent.invoke(Message::user("go"), None).await.unwrap();
+
+        let requests = model.requests();
+        assert_eq!(
+            requests[0].tool_choice,
+            Some(ToolChoice::function("lookup"))
+        );
+        // 拿到工具结果后由模型自行决定
+        assert_eq!(requests[1].tool_choice, None);
+        assert_eq!(requests[1].tools.len(), 1);
+
+        // None 通过不发送工具实现
+        let model = Arc::new(MockModel::new([Message::assistant("hi")]));
+        let agent = ReactAgent::builder(model.clone())
+            .with_tools([lookup()])
+            .with_tool_choice(ToolChoice::None)
+            .build();
+        agent.invoke(Message::user("go"), None).await.unwrap();
+        let request = model.last_request().unwrap();
+        assert!(request.tools.is_empty());
+        assert_eq!(request.tool_choice, None);
+    }
+
+    #[tokio::test]
+    async fn tool_examples_are_appended_to_the_system_prompt() {
+        let lookup = RegisteredTool::<ToolError>::new(
+            "lookup".to_owned(),
+            String::new(),
+            serde_json::json!({"type": "object"}),
+            Arc::new(|args| Box::pin(async move { Ok(args) })),
+        )
+        .with_example(
+            serde_json::json!({"q": "rust"}),
+            serde_json::json!(["rust-lang.org"]),
+        );
+        let model = Arc::new(MockModel::new([Message::assistant("hi")]));
+        let agent = ReactAgent::builder(model.clone())
+            .with_system_prompt("Be brief.")
+            .with_tools([lookup])
+            .build();
+        let state = agent.invoke(Message::user("go"), None).await.unwrap();
+
+        let request = model.last_request().unwrap();
+        assert_eq!(
+            request.messages[0].content(),
+            "Be brief.\n\nExamples of correct tool calls and their results:\n\
+             - lookup({\"q\":\"rust\"}) -> [\"rust-lang.org\"]"
+        );
+        // 示例只出现在请求中，不写入状态
+        assert_eq!(state.messages[0].content(), "Be brief.");
+    }
+
+    #[tokio::test]
+    async fn tool_results_are_checked_against_the_output_schema() {
+        // 声明返回 `{"price": number}`，实际返回了字符串
+        let get_price = || {
+            RegisteredTool::<ToolError>::new(
+                "get_price".to_owned(),
+                "looks up a price".to_owned(),
+                serde_json::json!({"type": "object"}),
+                Arc::new(|_| Box::pin(async { Ok(serde_json::json!("five dollars")) })),
+            )
+            .with_output_schema(serde_json::json!({
+                "type": "object",
+                "properties": {"price": {"type": "number"}},
+                "required": ["price"]
+            }))
+        };
+        let run = |validation| async move {
+            let model = MockModel::new([
+                MockModel::tool_call("get_price", serde_json::json!({})),
+                Message::assistant("done"),
+            ]);
+            let agent = ReactAgent::builder(model)
+                .with_tools([get_price()])
+                .with_output_validation(validation)
+                .build();
+            let state = agent.invoke(Message::user("price?"), None).await.unwrap();
+            state.last_turn_tool_steps().remove(0)
+        };
+
+        let step = run(OutputValidation::Warn).await;
+        assert!(!step.failed);
+        assert_eq!(step.result.as_deref(), Some("\"five dollars\""));
+
+        let step = run(OutputValidation::Reject).await;
+        assert!(step.failed);
+        assert!(
+            step.result
+                .unwrap()
+                .starts_with("Error: tool `get_price` returned an unexpected result at `$`")
+        );
+    }
+
+    #[tokio::test]
+    async fn partially_failed_tool_turns_are_reported() {
+        let tool = |name: &str, result: Result<&'static str, &'static str>| {
+            RegisteredTool::<ToolError>::new(
+                name.to_owned(),
+                name.to_owned(),
+                serde_json::json!({"type": "object"}),
+                Arc::new(move |_| {
+                    Box::pin(async move {
+                        result
+                            .map(|text| serde_json::json!(text))
+                            .map_err(|e| ToolError::ExecutionFailed(e.to_owned()))
+                    })
+                }),
+            )
+        };
+        let model = MockModel::new([
+            MockModel::tool_calls([
+                ("get_price", serde_json::json!({})),
+                ("get_stock", serde_json::json!({})),
+            ]),
+            Message::assistant("It costs 5, stock unknown."),
+        ]);
+        let agent = ReactAgent::builder(model)
+            .with_tools([
+                tool("get_price", Ok("5")),
+                tool("get_stock", Err("inventory service down")),
+            ])
+            .build();
+
+        let state = agent
+            .invoke(Message::user("price and stock?"), None)
+            .await
+            .unwrap();
+        let steps = state.last_turn_tool_steps();
+        let failed: Vec<_> = steps
+            .iter()
+            .map(|step| (step.name.as_str(), step.failed))
+            .collect();
+        assert_eq!(failed, [("get_price", false), ("get_stock", true)]);
+        // 模型看到的仍是普通的错误文本
+        assert_eq!(
+            steps[1].result.as_deref(),
+            Some("Error: tool `get_stock` failed: Tool execution failed: inventory service down")
+        );
+    }
+
+    #[tokio::test]
+    async fn repetition_guard_breaks_a_stuck_search_loop() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let searches = Arc::new(AtomicUsize::new(0));
+        let search_web = || {
+            let searches = searches.clone();
+            RegisteredTool::<ToolError>::new(
+                "search_web".to_owned(),
+                "searches the web".to_owned(),
+                serde_json::json!({"type": "object"}),
+                Arc::new(move |_| {
+                    searches.fetch_add(1, Ordering::SeqCst);
+                    Box::pin(async { Ok(serde_json::json!("no results")) })
+                }),
+            )
+        };
+        let stuck = || MockModel::tool_call("search_web", serde_json::json!({"query": "rust"}));
+
+        // 第三次相同的搜索不执行，提醒模型换一种做法
+        let model = Arc::new(MockModel::new([
+            stuck(),
+            stuck(),
+            stuck(),
+            Message::assistant("I could not find anything."),
+        ]));
+        let agent = ReactAgent::builder(model.clone())
+            .with_tools([search_web()])
+            .with_repetition_guard(RepetitionGuard::new(10, 3))
+            .build();
+        let state = agent.invoke(Message::user("find it"), None).await.unwrap();
+        assert_eq!(searches.load(Ordering::SeqCst), 2);
+        let nudge = model
+            .last_request()
+            .unwrap()
+            .messages
+            .last()
+            .unwrap()
+            .clone();
+        assert!(nudge.content().starts_with(
+            "Error: `search_web` was already called 2 times with the same arguments."
+        ));
+        assert_eq!(
+            state.last_message().unwrap().content(),
+            "I could not find anything."
+        );
+
+        let model = MockModel::new([stuck(), stuck(), stuck(), stuck()]);
+        let agent = ReactAgent::builder(model)
+            .with_tools([search_web()])
+            .with_repetition_guard(RepetitionGuard::new(10, 3).with_action(RepetitionAction::Stop))
+            .build();
+        let err = agent
+            .invoke(Message::user("find it"), None)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.root_cause(),
+            AgentError::RepeatedToolCall { tool, calls: 3 } if tool == "search_web"
+        ));
+        assert_eq!(searches.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn few_shot_examples_are_sent_but_not_persisted() {
+        use langgraph::checkpoint::MemorySaver;
+
+        let model = Arc::new(MockModel::new([
+            Message::assistant("POSITIVE"),
+            Message::assistant("NEGATIVE"),
+        ]));
+        let checkpointer = Arc::new(MemorySaver::new());
+        let agent = ReactAgent::builder(model.clone())
+            .with_system_prompt("Classify the sentiment.")
+            .with_examples([("I love it", "POSITIVE"), ("Awful", "NEGATIVE")])
+            .with_checkpointer(checkpointer.clone())
+            .build();
+
+        agent
+            .invoke_with_thread(Message::user("Great!"), "chat")
+            .await
+            .unwrap();
+        let state = agent
+            .invoke_with_thread(Message::user("Meh"), "chat")
+            .await
+            .unwrap();
+
+        let contents: Vec<_> = model
+            .last_request()
+            .unwrap()
+            .messages
+            .iter()
+            .map(|m| m.content().to_owned())
+            .collect();
+        assert_eq!(
+            contents,
+            [
+                "Classify the sentiment.",
+                "I love it",
+                "POSITIVE",
+                "Awful",
+                "NEGATIVE",
+                "Great!",
+                "POSITIVE",
+                "Meh",
+            ]
+        );
+        // 示例不写入状态，也不写入 checkpoint
+        assert_eq!(state.messages.len(), 5);
+        let saved: Checkpoint<MessagesState> = checkpointer.get("chat").await.unwrap().unwrap();
+        assert_eq!(saved.state.messages.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn system_prompt_template_is_rendered_per_run() {
+        let lookup = RegisteredTool::<ToolError>::new(
+            "lookup".to_owned(),
+            String::new(),
+            serde_json::json!({"type": "object"}),
+            Arc::new(|args| Box::pin(async move { Ok(args) })),
+        );
+        let model = Arc::new(MockModel::new([
+            Message::assistant("hi"),
+            Message::assistant("hello"),
+        ]));
+        let agent = ReactAgent::builder(model.clone())
+            .with_system_prompt_template(
+                "Help {user} on {date}. Tools: {tools}.",
+                [("user", "a guest")],
+            )
+            .with_tools([lookup])
+            .build();
+
+        let state = agent
+            .invoke_with_vars(Message::user("go"), None, [("date", "2026-10-16")])
+            .await
+            .unwrap();
+        assert_eq!(
+            state.messages[0].content(),
+            "Help a guest on 2026-10-16. Tools: lookup."
+        );
+
+        let state = agent
+            .invoke_with_vars(
+                Message::user("go"),
+                None,
+                [("user", "Ann"), ("date", "2026-10-17")],
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            state.messages[0].content(),
+            "Help Ann on 2026-10-17. Tools: lookup."
+        );
+
+        // 缺少变量时不调用模型
+        let err = agent.invoke(Message::user("go"), None).await.unwrap_err();
+        assert!(matches!(
+            err,
+            AgentError::Prompt(PromptError::MissingVariable(ref name)) if name == "date"
+        ));
+        assert_eq!(model.call_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn system_prompt_template_vars_reach_stream_and_structured_runs() {
+        #[derive(Debug, serde::Deserialize, JsonSchema)]
+        struct Reply {
+            ok: bool,
+        }
+
+        let model = Arc::new(MockModel::new([
+            Message::assistant("hi"),
+            Message::assistant(r#"{"ok": true}"#),
+        ]));
+        let agent = ReactAgent::builder(model.clone())
+            .with_system_prompt_template("Today is {date}.", [] as [(&str, &str); 0])
+            .build();
+
+        let _: Vec<_> = agent
+            .stream_with_vars(Message::user("go"), None, [("date", "2026-10-16")])
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(
+            model.last_request().unwrap().messages[0].content(),
+            "Today is 2026-10-16."
+        );
+
+        let output = agent
+            .invoke_structured_with_vars::<Reply, _, _, _>(
+                Message::user("go"),
+                None,
+                [("date", "2026-10-17")],
+            )
+            .await
+            .unwrap();
+        assert_eq!(output.state.messages[0].content(), "Today is 2026-10-17.");
+        assert!(output.into_output().unwrap().ok);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn deadline_cancels_the_run_and_keeps_partial_state() {
+        // 每次调用耗时一秒，并一直请求工具
+        let model =
+            MockModel::from_fn(|_| Ok(MockModel::tool_call("test_tool", serde_json::json!({}))))
+                .with_latency(Duration::from_secs(1));
+        let agent = ReactAgent::builder(model)
+            .with_tools([test_tool_tool()])
+            .with_deadline(Duration::from_millis(2500))
+            .build();
+
+        let result = agent.invoke(Message::user("hello"), None).await;
+        let Err(AgentError::Timeout { elapsed, state }) = result else {
+            panic!("expected Timeout, got {result:?}");
+        };
+        // 第三次模型调用在截止时间被取消，前两轮的结果保留
+        assert_eq!(elapsed, Duration::from_millis(2500));
+        assert_eq!(state.llm_calls, 2);
+        assert_eq!(state.messages.len(), 5);
+        assert!(AgentError::Timeout { elapsed, state }.is_retryable());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn streams_stop_at_the_deadline() {
+        use std::sync::Mutex;
+
+        // 每次工具调用耗时一秒，模型一直请求工具
+        let slow_tool = RegisteredTool::<ToolError>::new(
+            "slow_tool".to_owned(),
+            "takes a second".to_owned(),
+            serde_json::json!({"type": "object"}),
+            Arc::new(|_| {
+                Box::pin(async {
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    Ok(serde_json::json!("done"))
+                })
+            }),
+        );
+        let model =
+            MockModel::from_fn(|_| Ok(MockModel::tool_call("slow_tool", serde_json::json!({}))));
+        let failures = Arc::new(Mutex::new(Vec::new()));
+        let seen = failures.clone();
+        let observer = AgentMiddleware::from_label(crate::define_middleware_label!(
+            StreamDeadlineObserverLabel
+        ))
+        .with_on_run_error(Arc::new(move |_, error: &AgentError| {
+            seen.lock().unwrap().push(error.to_string());
+        }));
+        let agent = ReactAgent::builder(model)
+            .with_tools([slow_tool])
+            .with_middlewares([observer])
+            .with_deadline(Duration::from_millis(2500))
+            .build();
+
+        let started = tokio::time::Instant::now();
+        let events: Vec<_> = agent
+            .stream(Message::user("hello"), None)
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(started.elapsed(), Duration::from_millis(2500));
+        assert!(matches!(
+            events.last(),
+            Some(ChatStreamEvent::Done { finish_reason: Some(reason), .. }) if reason == "timeout"
+        ));
+        // 前两轮工具调用在截止时间前完成
+        let rounds = events
+            .iter()
+            .filter(|event| matches!(event, ChatStreamEvent::ToolResults { .. }))
+            .count();
+        assert_eq!(rounds, 2);
+        assert_eq!(failures.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancelling_a_run_drops_the_in_flight_tool_call() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        struct SetOnDrop(Arc<AtomicBool>);
+        impl Drop for SetOnDrop {
+            fn drop(&mut self) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let dropped = Arc::new(AtomicBool::new(false));
+        let flag = dropped.clone();
+        let slow_report = RegisteredTool::<ToolError>::new(
+            "slow_report".to_owned(),
+            "builds a report".to_owned(),
+            serde_json::json!({"type": "object"}),
+            Arc::new(move |_| {
+                let guard = SetOnDrop(flag.clone());
+                Box::pin(async move {
+                    let _guard = guard;
+                    tokio::time::sleep(Duration::from_secs(3600)).await;
+                    Ok(serde_json::json!("report"))
+                })
+            }),
+        );
+        let model = MockModel::new([
+            MockModel::tool_call("slow_report", serde_json::json!({})),
+            Message::assistant("Here is the report."),
+        ]);
+        let agent = ReactAgent::builder(model)
+            .with_tools([slow_report])
+            .with_checkpointer(Arc::new(langgraph::checkpoint::MemorySaver::new()))
+            .build();
+
+        let cancel = CancellationToken::new();
+        let trigger = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            trigger.cancel();
+        });
+        let result = agent
+            .invoke_with_cancel(Message::user("report please"), Some("t"), cancel)
+            .await;
+
+        let Err(error @ AgentError::Cancelled { .. }) = result else {
+            panic!("expected Cancelled, got {result:?}");
+        };
+        assert_eq!(error.category(), ErrorCategory::Cancelled);
+        assert!(!error.is_retryable());
+        // 工具调用在执行中被取消，请求工具的模型回复被撤回
+        let state = error.state().unwrap();
+        assert!(dropped.load(Ordering::SeqCst));
+        assert_eq!(state.llm_calls, 1);
+        assert_eq!(state.messages.len(), 1);
+        assert!(state.last_tool_calls().is_none());
+
+        // 线程的下一条消息从完整的历史开始
+        let state = agent
+            .invoke(Message::user("just summarize"), Some("t"))
+            .await
+            .unwrap();
+        let contents: Vec<_> = state.messages.iter().map(|m| m.content()).collect();
+        assert_eq!(
+            contents,
+            ["report please", "just summarize", "Here is the report."]
+        );
+    }
+
+    #[tokio::test]
+    async fn windowed_reducer_bounds_thread_history() {
+        use langgraph::checkpoint::MemorySaver;
+
+        let agent = ReactAgent::builder(TestModel)
+            .with_system_prompt("be brief")
+            .with_checkpointer(Arc::new(MemorySaver::new()))
+            .with_reducer(MessagesReducer::Window(2))
+            .build();
+
+        agent
+            .invoke_with_thread(Message::user("hello"), "chat")
+            .await
+            .unwrap();
+        let second = agent
+            .invoke_with_thread(Message::user("again"), "chat")
+            .await
+            .unwrap();
+
+        // 系统提示 + 最近一轮的用户消息和回复
+        assert_eq!(second.messages.len(), 3);
+        assert!(matches!(
+            second.messages[0].as_ref(),
+            Message::System { .. }
+        ));
+        assert_eq!(second.messages[1].content(), "again");
+    }
+
+    #[tokio::test]
+    async fn invoke_batch_isolates_runs_and_preserves_order() {
+        use langgraph::checkpoint::MemorySaver;
+
+        let agent = ReactAgent::builder(TestModel)
+            .with_checkpointer(Arc::new(MemorySaver::new()))
+            .build();
+
+        let inputs = ["a", "b", "c"].map(Message::user).to_vec();
+        let results = agent.invoke_batch(inputs, 2).await;
+
+        assert_eq!(results.len(), 3);
+        let mut threads = Vec::new();
+        for (run, expected) in results.into_iter().zip(["a", "b", "c"]) {
+            let state = run.result.unwrap();
+            // 每个运行都有独立的 thread，不会带上其他输入的历史
+            assert_eq!(state.messages.len(), 2);
+            assert_eq!(state.messages[0].content(), expected);
+            threads.push(run.thread_id.unwrap());
+        }
+        let mut distinct = threads.clone();
+        distinct.sort();
+        distinct.dedup();
+        assert_eq!(distinct.len(), 3);
+
+        // 返回的 thread_id 可以继续对应的对话
+        let state = agent
+            .invoke(Message::user("more"), Some(&threads[1]))
+            .await
+            .unwrap();
+        assert_eq!(state.messages[0].content(), "b");
+        assert_eq!(state.messages.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn injected_id_generator_mints_thread_and_tool_call_ids() {
+        use langchain_core::message::ToolCall;
+        use langgraph::checkpoint::MemorySaver;
+
+        let lookup = RegisteredTool::<ToolError>::new(
+            "lookup".to_owned(),
+            String::new(),
+            serde_json::json!({"type": "object"}),
+            Arc::new(|_| Box::pin(async { Ok(serde_json::json!("found")) })),
+        );
+        // 模型返回的工具调用没有 id
+        let model = MockModel::new([
+            Message::assistant_with_tool_calls(
+                "",
+                vec![ToolCall::new("", "lookup", serde_json::json!({}))],
+            ),
+            Message::assistant("done"),
+        ]);
+        let checkpointer = Arc::new(MemorySaver::new());
+        let agent = ReactAgent::builder(model)
+            .with_tools([lookup])
+            .with_checkpointer(checkpointer.clone())
+            .with_id_generator(Arc::new(SequentialIdGenerator::new("id-")))
+            .build();
+
+        let run = agent
+            .invoke_batch(vec![Message::user("go")], 1)
+            .await
+            .remove(0);
+        assert_eq!(run.thread_id.as_deref(), Some("id-1"));
+        let state = run.result.unwrap();
+
+        let Message::Assistant {
+            tool_calls: Some(calls),
+            ..
+        } = state.messages[1].as_ref()
+        else {
+            panic!("expected a tool call, got {:?}", state.messages[1]);
+        };
+        let call_id = calls[0].id().to_owned();
+        assert!(call_id.starts_with("id-"));
+        assert!(matches!(
+            state.messages[2].as_ref(),
+            Message::Tool { tool_call_id, .. } if *tool_call_id == call_id
+        ));
+        // 第一个 id 分配给了线程，检查点 id 仍由检查点存储生成
+        let checkpoint: Checkpoint<MessagesState> =
+            checkpointer.get("id-1").await.unwrap().unwrap();
+        assert!(!checkpoint.metadata.id.starts_with("id-"));
+    }
+
+    #[tokio::test]
+    async fn streamed_tool_call_deltas_carry_generated_ids() {
+        use futures::StreamExt;
+        use langchain_core::message::ToolCall;
+
+        let lookup = RegisteredTool::<ToolError>::new(
+            "lookup".to_owned(),
+            String::new(),
+            serde_json::json!({"type": "object"}),
+            Arc::new(|_| Box::pin(async { Ok(serde_json::json!("found")) })),
+        );
+        let model = MockModel::new([
+            Message::assistant_with_tool_calls(
+                "",
+                vec![ToolCall::new("", "lookup", serde_json::json!({}))],
+            ),
+            Message::assistant("done"),
+        ]);
+        let agent = ReactAgent::builder(model)
+            .with_tools([lookup])
+            .with_id_generator(Arc::new(SequentialIdGenerator::new("id-")))
+            .build();
+
+        let events: Vec<_> = agent
+            .stream(Message::user("go"), None)
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        let streamed: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                ChatStreamEvent::ToolCallDelta { id, .. } => id.clone(),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(streamed, ["id-1"]);
+        assert!(events.iter().any(|event| matches!(
+            event,
+            ChatStreamEvent::ToolResults { succeeded, .. } if succeeded == &["id-1"]
+        )));
+    }
+
+    #[tokio::test]
+    async fn sqlite_resume_loads_latest_of_many_checkpoints() {
+        use langchain_core::message::ToolCall;
+        use langgraph::checkpoint::{SqliteSaver, SqliteSaverConfig};
+
+        let lookup = RegisteredTool::<ToolError>::new(
+            "lookup".to_owned(),
+            String::new(),
+            serde_json::json!({"type": "object"}),
+            Arc::new(|_| Box::pin(async { Ok(serde_json::json!("found")) })),
+        );
+        // 6 轮工具调用 + 最终回复：一次运行保存十几个检查点
+        let mut replies: Vec<Message> = (0..6)
+            .map(|_| {
+                Message::assistant_with_tool_calls(
+                    "",
+                    vec![ToolCall::new("", "lookup", serde_json::json!({}))],
+                )
+            })
+            .collect();
+        replies.push(Message::assistant("first done"));
+        replies.push(Message::assistant("second done"));
+
+        let dir = std::env::temp_dir().join(format!("resume-{}", uuid::Uuid::new_v4()));
+        let checkpointer = Arc::new(
+            SqliteSaver::new(SqliteSaverConfig::file(dir.join("checkpoints.db")))
+                .await
+                .unwrap(),
+        );
+        let agent = ReactAgent::builder(MockModel::new(replies))
+            .with_tools([lookup])
+            .with_checkpointer(checkpointer.clone())
+            .with_id_generator(Arc::new(SequentialIdGenerator::new("id-")))
+            .build();
+
+        let first = agent.invoke(Message::user("go"), Some("t")).await.unwrap();
+        assert_eq!(first.messages.len(), 14);
+        let stats = Checkpointer::<MessagesState>::stats(checkpointer.as_ref(), Some("t"))
+            .await
+            .unwrap();
+        assert!(stats.total_count > 10);
+
+        let second = agent
+            .invoke(Message::user("again"), Some("t"))
+            .await
+            .unwrap();
+        assert_eq!(second.messages.len(), 16);
+        assert_eq!(second.messages[13].content(), "first done");
+        assert_eq!(second.messages[15].content(), "second done");
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn invoke_structured_keeps_raw_output_on_parse_failure() {
+        #[derive(Debug, serde::Deserialize, JsonSchema)]
+        struct Person {
+            name: String,
+        }
+
+        let agent = ReactAgent::builder(crate::testing::MockModel::echo()).build();
+
+        let ok = agent
+            .invoke_structured::<Person>(Message::user(r#"{"name": "Ann"}"#), None)
+            .await
+            .unwrap();
+        assert!(ok.parse_error.is_none());
+        assert_eq!(ok.into_output().unwrap().name, "Ann");
+
+        let bad = agent
+            .invoke_structured::<Person>(Message::user("my name is Ann"), None)
+            .await
+            .unwrap();
+        assert!(bad.struct_output.is_none());
+        assert_eq!(bad.raw, "my name is Ann");
+        assert_eq!(bad.state.messages.len(), 2);
+        assert!(matches!(
+            bad.parse_error,
+            Some(langchain_core::parsers::ParseError::Json(_))
+        ));
+        assert!(bad.into_output().is_err());
+    }
+
+    #[tokio::test]
+    async fn structured_output_reprompts_only_when_the_final_reply_does_not_parse() {
+        #[derive(Debug, serde::Deserialize, JsonSchema)]
+        struct Person {
+            name: String,
+        }
+
+        // 已经给出结构化回复时不再调用模型
+        let model = Arc::new(MockModel::new([Message::assistant(r#"{"name": "Ann"}"#)]));
+        let agent = ReactAgent::builder(model.clone())
+            .with_structured_output(StructuredOutputStrategy::Reprompt { max_retries: 2 })
+            .build();
+        let output = agent
+            .invoke_structured::<Person>(Message::user("who?"), None)
+            .await
+            .unwrap();
+        assert_eq!(output.into_output().unwrap().name, "Ann");
+        assert_eq!(model.call_count(), 1);
+
+        // 解析失败时带上错误和格式说明重新询问
+        let model = Arc::new(MockModel::new([
+            Message::assistant("It is Ann."),
+            Message::assistant(r#"{"name": "Ann"}"#),
+        ]));
+        let agent = ReactAgent::builder(model.clone())
+            .with_structured_output(StructuredOutputStrategy::Reprompt { max_retries: 2 })
+            .build();
+        let output = agent
+            .invoke_structured::<Person>(Message::user("who?"), None)
+            .await
+            .unwrap();
+        assert!(output.parse_error.is_none());
+        assert_eq!(output.state.messages.len(), 4);
+        let reprompt = model.requests()[1]
+            .messages
+            .last()
+            .unwrap()
+            .content()
+            .to_owned();
+        assert!(reprompt.contains("could not be parsed"));
+        assert!(reprompt.contains("Output only valid JSON"));
+        assert_eq!(output.into_output().unwrap().name, "Ann");
+
+        // 重试用完后返回最后一次的解析错误
+        let model = Arc::new(MockModel::from_fn(|_| Ok(Message::assistant("Ann"))));
+        let agent = ReactAgent::builder(model.clone())
+            .with_structured_output(StructuredOutputStrategy::Reprompt { max_retries: 1 })
+            .build();
+        let output = agent
+            .invoke_structured::<Person>(Message::user("who?"), None)
+            .await
+            .unwrap();
+        assert_eq!(output.raw, "Ann");
+        assert!(output.parse_error.is_some());
+        assert_eq!(model.call_count(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn structured_output_reprompts_share_the_runs_deadline() {
+        // 每次调用耗时一秒且从不给出结构化回复
+        let model = Arc::new(
+            MockModel::from_fn(|_| Ok(Message::assistant("Ann")))
+                .with_latency(Duration::from_secs(1)),
+        );
+        let agent = ReactAgent::builder(model.clone())
+            .with_structured_output(StructuredOutputStrategy::Reprompt { max_retries: 5 })
+            .with_deadline(Duration::from_millis(2500))
+            .build();
+
+        let result = agent
+            .invoke_structured::<serde_json::Value>(Message::user("who?"), None)
+            .await;
+        let Err(AgentError::Timeout { elapsed, state }) = result else {
+            panic!("expected Timeout, got {result:?}");
+        };
+        // 重新询问不会重置截止时间：第三次调用在 2.5 秒时被取消
+        assert_eq!(elapsed, Duration::from_millis(2500));
+        assert_eq!(state.llm_calls, 2);
+        assert_eq!(model.call_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn invoke_structured_with_uses_the_given_parser() {
+        use langchain_core::parsers::ListParser;
+
+        let model = Arc::new(MockModel::new([Message::assistant("red, green, blue")]));
+        let agent = ReactAgent::builder(model.clone()).build();
+        let output = agent
+            .invoke_structured_with(
+                Message::user("colors?"),
+                None,
+                &ListParser::comma_separated(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(output.into_output().unwrap(), ["red", "green", "blue"]);
+        assert_eq!(model.call_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn invoke_structured_stream_yields_partials_then_the_final_value() {
+        #[derive(Debug, PartialEq, serde::Deserialize, JsonSchema)]
+        struct Person {
+            name: String,
+            age: u32,
+        }
+
+        // 把用户消息按每 8 个字符一段流式输出
+        let agent =
+            ReactAgent::builder(crate::testing::MockModel::echo().with_chunk_size(8)).build();
+
+        let items: Vec<_> = agent
+            .invoke_structured_stream::<Person>(
+                Message::user(r#"{"name": "Ann Lee", "age": 41}"#),
+                None,
+            )
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        let (last, partials) = items.split_last().unwrap();
+        assert_eq!(
+            last.as_ref().unwrap(),
+            &StructuredStreamEvent::Final(Person {
+                name: "Ann Lee".to_owned(),
+                age: 41,
+            })
+        );
+        let partials: Vec<_> = partials
+            .iter()
+            .map(|item| match item {
+                Ok(StructuredStreamEvent::Partial(value)) => value.clone(),
+                other => panic!("unexpected item: {other:?}"),
+            })
+            .collect();
+        assert_eq!(
+            partials,
+            [
+                serde_json::json!({}),
+                serde_json::json!({ "name": "Ann Le" }),
+                serde_json::json!({ "name": "Ann Lee" }),
+                serde_json::json!({ "name": "Ann Lee", "age": 41 }),
+            ]
+        );
+
+        // 流结束时仍不是合法的 JSON：最后一项为解析错误
+        let items: Vec<_> = agent
+            .invoke_structured_stream::<Person>(Message::user(r#"{"name": "Ann"#), None)
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert!(matches!(
+            items.last(),
+            Some(Err(ParseError::PatternNotFound(_)))
+        ));
+    }
+
     #[tokio::test]
     async fn test_react_agent_without_checkpointer() {
         // let tool = test_tool_tool();
@@ -726,6 +3175,99 @@ mod tests {
         assert_eq!(state2.messages.len(), 2);
     }
 
+    #[tokio::test]
+    async fn model_retry_recovers_from_transient_errors() {
+        let model = MockModel::new([]);
+        model.push_error(ModelError::ServerError(503, "unavailable".to_owned()));
+        model.push_response(Message::assistant("assistant"));
+        let agent = ReactAgent::builder(model)
+            .with_model_retry(RetryConfig {
+                initial_delay_ms: 1,
+                ..Default::default()
+            })
+            .build();
+
+        let state = agent.invoke(Message::user("hello"), None).await.unwrap();
+        assert_eq!(state.messages.len(), 2);
+    }
+
+    #[test]
+    fn react_agent_graph_renders_as_mermaid() {
+        let agent = ReactAgent::builder(TestModel)
+            .with_tools(vec![test_tool_tool()])
+            .build();
+
+        let mermaid = agent.graph.to_mermaid();
+        for name in ["Start", "Llm", "Tool", "End"] {
+            assert!(mermaid.contains(&format!("\"{name}\"")), "{mermaid}");
+        }
+        assert!(mermaid.contains("-.->|Tool|"));
+    }
+
+    #[test]
+    fn try_build_rejects_middleware_route_to_missing_node() {
+        use crate::node::middleware::MiddlewareLabel;
+
+        #[derive(Debug, Clone, PartialEq, Eq, Hash, GraphLabel)]
+        enum Guard {
+            BeforeAgent,
+            BeforeModel,
+            AfterModel,
+            AfterAgent,
+            Missing,
+        }
+
+        let label = MiddlewareLabel {
+            before_agent: Guard::BeforeAgent.intern(),
+            before_model: Guard::BeforeModel.intern(),
+            after_model: Guard::AfterModel.intern(),
+            after_agent: Guard::AfterAgent.intern(),
+        };
+        let hook = AgentHook {
+            handler: Arc::new(|state: &MessagesState, _ctx: &_| {
+                let state = state.clone();
+                Box::pin(async move { Ok(state) })
+            }),
+            target: None,
+            branches: vec![Guard::Missing.intern()],
+        };
+        let middleware = AgentMiddleware::from_label(label).with_before_model(hook);
+
+        let result = ReactAgent::builder(TestModel)
+            .with_middlewares([middleware])
+            .try_build();
+
+        assert!(matches!(result, Err(AgentError::Graph(_))));
+    }
+
+    #[tokio::test]
+    async fn node_errors_carry_the_failing_node_and_step() {
+        let lookup = RegisteredTool::<ToolError>::new(
+            "lookup".to_owned(),
+            "looks something up".to_owned(),
+            serde_json::json!({"type": "object"}),
+            Arc::new(|_| Box::pin(async { Ok(serde_json::json!("found")) })),
+        );
+        let model = MockModel::new([MockModel::tool_call("lookup", serde_json::json!({}))]);
+        model.push_error(ModelError::InvalidApiKey);
+        let agent = ReactAgent::builder(model).with_tools([lookup]).build();
+
+        let err = agent.invoke(Message::user("go"), None).await.unwrap_err();
+
+        // 第 0 步为入口，第 1 步调用模型，第 2 步执行工具，第 3 步再次调用模型时失败
+        let AgentError::Node { node, step, .. } = err else {
+            panic!("expected a node error, got {err:?}");
+        };
+        assert_eq!(node, ReactAgentLabel::Llm.intern());
+        assert_eq!(step, 3);
+        assert!(matches!(
+            err.root_cause(),
+            AgentError::Model(ModelError::InvalidApiKey)
+        ));
+        assert_eq!(err.category(), ErrorCategory::Authentication);
+        assert!(err.to_string().starts_with("node Llm failed at step 3: "));
+    }
+
     #[tokio::test]
     async fn test_react_agent_system_prompt() {
         let agent = ReactAgent::builder(TestModel)
@@ -0,0 +1,364 @@
+//! 模型响应缓存中间件
+//!
+//! 以消息历史和工具定义为键，把模型回复保存到 [`BaseStore`]。相同的输入再次出现时直接
+//! 返回缓存的回复并跳过模型调用，适合测试、演示等会反复发送相同提示的场景。
+
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use langchain_core::{
+    message::Message,
+    request::ToolSpec,
+    state::{JumpTo, MessagesState},
+    store::{BaseStore, Namespace},
+};
+use langgraph::node::NodeContext;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    AgentError, define_middleware_label,
+    node::middleware::{AgentHook, AgentMiddleware},
+};
+
+/// 自定义缓存键：参数为发送给模型的消息和工具定义
+pub type CacheKeyFn = dyn Fn(&[Arc<Message>], &[ToolSpec]) -> String + Send + Sync;
+
+/// 存储中的缓存条目
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    /// 写入时间（Unix 时间戳，秒）
+    created_at: u64,
+    message: Message,
+}
+
+#[derive(Clone)]
+struct Inner {
+    store: Arc<dyn BaseStore>,
+    namespace: Namespace,
+    ttl: Option<Duration>,
+    tools: Vec<ToolSpec>,
+    key_fn: Option<Arc<CacheKeyFn>>,
+}
+
+/// 模型响应缓存中间件
+///
+/// - `before_model`：计算缓存键，命中且未过期时把缓存的回复加入状态，并通过
+///   [`JumpTo::SkipModel`] 跳过本次模型调用
+/// - `after_model`：未命中时把模型的回复写入存储
+///
+/// 默认的键是消息历史、工具定义和本次调用允许的工具（[`Configuration::tools`]）的 SHA-256。
+/// 中间件看不到 Agent 注册的工具，创建时必须传入与 Agent 一致的工具定义，
+/// 工具不同的 Agent 共用一个存储时才不会命中彼此的条目。
+///
+/// 缓存命中时不会产生流式事件，也不计入 [`MessagesState::usage`]。
+///
+/// [`Configuration::tools`]: langgraph::checkpoint::Configuration::tools
+///
+/// ```rust,ignore
+/// let specs = tools
+///     .iter()
+///     .map(|tool| ToolSpec::Function { function: tool.function.clone() })
+///     .collect();
+/// let cache = CacheMiddleware::new(Arc::new(InMemoryStore::new()), specs)
+///     .with_ttl(Duration::from_secs(3600));
+/// let agent = ReactAgent::builder(model)
+///     .with_middlewares([cache.into()])
+///     .build();
+/// ```
+#[derive(Clone)]
+pub struct CacheMiddleware {
+    inner: Arc<Inner>,
+}
+
+impl std::fmt::Debug for CacheMiddleware {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CacheMiddleware")
+            .field("namespace", &self.inner.namespace)
+            .field("ttl", &self.inner.ttl)
+            .field("custom_key", &self.inner.key_fn.is_some())
+            .finish()
+    }
+}
+
+impl CacheMiddleware {
+    /// 使用默认命名空间 `llm_cache` 创建缓存中间件，条目永不过期
+    ///
+    /// `tools` 是参与缓存键计算的工具定义，应与传给 Agent 的工具一致；Agent 没有工具时传入空列表
+    pub fn new(store: Arc<dyn BaseStore>, tools: Vec<ToolSpec>) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                store,
+                namespace: Namespace::new(vec!["llm_cache".to_owned()]),
+                ttl: None,
+                tools,
+                key_fn: None,
+            }),
+        }
+    }
+
+    /// 配置已被克隆共享时复制一份，不影响其他副本
+    fn inner_mut(&mut self) -> &mut Inner {
+        Arc::make_mut(&mut self.inner)
+    }
+
+    pub fn with_namespace(mut self, namespace: Namespace) -> Self {
+        self.inner_mut().namespace = namespace;
+        self
+    }
+
+    /// 条目写入后超过 `ttl` 视为未命中，并在下次读取时删除
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.inner_mut().ttl = Some(ttl);
+        self
+    }
+
+    /// 替换默认的键函数，例如只按最后一条用户消息缓存
+    pub fn with_key_fn<F>(mut self, key_fn: F) -> Self
+    where
+        F: Fn(&[Arc<Message>], &[ToolSpec]) -> String + Send + Sync + 'static,
+    {
+        self.inner_mut().key_fn = Some(Arc::new(key_fn));
+        self
+    }
+
+    /// 计算给定消息对应的缓存键
+    pub fn key(&self, messages: &[Arc<Message>], allowed_tools: Option<&[String]>) -> String {
+        if let Some(key_fn) = &self.inner.key_fn {
+            return key_fn(messages, &self.inner.tools);
+        }
+        let mut hasher = Sha256::new();
+        for message in messages {
+            hasher.update(serde_json::to_vec(message.as_ref()).unwrap_or_default());
+            hasher.update([0]);
+        }
+        hasher.update(serde_json::to_vec(&self.inner.tools).unwrap_or_default());
+        if let Some(allowed) = allowed_tools {
+            hasher.update(serde_json::to_vec(allowed).unwrap_or_default());
+        }
+        format!("{:x}", hasher.finalize())
+    }
+
+    /// 读取未过期的缓存回复，过期条目会被删除
+    async fn lookup(&self, key: &str) -> Option<Message> {
+        let inner = &self.inner;
+        let bytes = match inner.store.get(&inner.namespace, key).await {
+            Ok(bytes) => bytes?,
+            Err(e) => {
+                tracing::warn!("cache lookup failed: {e}");
+                return None;
+            }
+        };
+        let entry: CacheEntry = match serde_json::from_slice(&bytes) {
+            Ok(entry) => entry,
+            Err(e) => {
+                tracing::warn!("discarding malformed cache entry {key}: {e}");
+                return None;
+            }
+        };
+        if let Some(ttl) = inner.ttl
+            && now_secs().saturating_sub(entry.created_at) >= ttl.as_secs()
+        {
+            let _ = inner.store.delete(&inner.namespace, key).await;
+            return None;
+        }
+        Some(entry.message)
+    }
+
+    async fn save(&self, key: &str, message: Message) {
+        let entry = CacheEntry {
+            created_at: now_secs(),
+            message,
+        };
+        let bytes = match serde_json::to_vec(&entry) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                tracing::warn!("failed to serialize cache entry: {e}");
+                return;
+            }
+        };
+        if let Err(e) = self
+            .inner
+            .store
+            .put(&self.inner.namespace, key, bytes)
+            .await
+        {
+            tracing::warn!("failed to write cache entry: {e}");
+        }
+    }
+
+    fn before_model(
+        &self,
+        state: &MessagesState,
+        context: &NodeContext,
+    ) -> impl Future<Output = Result<MessagesState, AgentError>> + Send + 'static {
+        let messages: Vec<_> = state.messages.iter().cloned().collect();
+        let key = self.key(&messages, context.config.tools.as_deref());
+        let this = self.clone();
+        async move {
+            let mut update = MessagesState::default();
+            if let Some(message) = this.lookup(&key).await {
+                tracing::debug!("model cache hit: {key}");
+                update.push_message_owned(message);
+                update.jump(JumpTo::SkipModel);
+            }
+            Ok(update)
+        }
+    }
+
+    fn after_model(
+        &self,
+        state: &MessagesState,
+        context: &NodeContext,
+    ) -> impl Future<Output = Result<MessagesState, AgentError>> + Send + 'static {
+        // 最后一条是模型（或缓存）的回复，之前的消息就是 before_model 时的输入
+        let reply = state
+            .last_message()
+            .filter(|m| matches!(m.as_ref(), Message::Assistant { .. }))
+            .map(|m| m.as_ref().clone());
+        let messages: Vec<_> = state.messages.iter().cloned().collect();
+        let input = &messages[..messages.len().saturating_sub(1)];
+        let key = self.key(input, context.config.tools.as_deref());
+        let this = self.clone();
+        async move {
+            // 命中缓存时不重写条目，以免每次命中都刷新过期时间
+            if let Some(reply) = reply
+                && this.lookup(&key).await.is_none()
+            {
+                this.save(&key, reply).await;
+            }
+            Ok(MessagesState::default())
+        }
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+impl From<CacheMiddleware> for AgentMiddleware<MessagesState> {
+    fn from(cache: CacheMiddleware) -> Self {
+        let label = define_middleware_label!(CacheLabel);
+        let before = cache.clone();
+        AgentMiddleware::from_label(label)
+            .with_before_model(AgentHook {
+                handler: Arc::new(move |state: &MessagesState, context: &NodeContext| {
+                    Box::pin(before.before_model(state, context))
+                }),
+                target: None,
+                branches: vec![],
+            })
+            .with_after_model(AgentHook {
+                handler: Arc::new(move |state: &MessagesState, context: &NodeContext| {
+                    Box::pin(cache.after_model(state, context))
+                }),
+                target: None,
+                branches: vec![],
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ReactAgent, testing::MockModel};
+    use langchain_core::store::InMemoryStore;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// 回复中带有调用序号和用户消息，便于区分缓存的回复
+    fn agent(cache: CacheMiddleware) -> (ReactAgent, Arc<MockModel>) {
+        let replies = AtomicUsize::new(0);
+        let model = Arc::new(MockModel::from_fn(move |request| {
+            let n = replies.fetch_add(1, Ordering::SeqCst);
+            let last = request.messages.last().unwrap().content();
+            Ok(Message::assistant(format!("reply {n} to {last}")))
+        }));
+        let agent = ReactAgent::builder(model.clone())
+            .with_middlewares([cache.into()])
+            .build();
+        (agent, model)
+    }
+
+    #[tokio::test]
+    async fn repeated_prompt_is_served_from_cache() {
+        let (agent, model) = agent(CacheMiddleware::new(
+            Arc::new(InMemoryStore::new()),
+            Vec::new(),
+        ));
+
+        let first = agent.invoke(Message::user("hi"), None).await.unwrap();
+        let second = agent.invoke(Message::user("hi"), None).await.unwrap();
+        let other = agent.invoke(Message::user("bye"), None).await.unwrap();
+
+        assert_eq!(first.last_message().unwrap().content(), "reply 0 to hi");
+        assert_eq!(second.last_message().unwrap().content(), "reply 0 to hi");
+        assert_eq!(other.last_message().unwrap().content(), "reply 1 to bye");
+        assert_eq!(model.call_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn expired_entries_are_ignored() {
+        let store = Arc::new(InMemoryStore::new());
+        let cache = CacheMiddleware::new(store.clone(), Vec::new()).with_ttl(Duration::ZERO);
+        let (agent, model) = agent(cache);
+
+        agent.invoke(Message::user("hi"), None).await.unwrap();
+        let second = agent.invoke(Message::user("hi"), None).await.unwrap();
+
+        assert_eq!(second.last_message().unwrap().content(), "reply 1 to hi");
+        assert_eq!(model.call_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn custom_key_fn_controls_hits() {
+        let cache = CacheMiddleware::new(Arc::new(InMemoryStore::new()), Vec::new())
+            .with_key_fn(|_, _| "constant".to_owned());
+        let (agent, model) = agent(cache);
+
+        agent.invoke(Message::user("hi"), None).await.unwrap();
+        let second = agent.invoke(Message::user("bye"), None).await.unwrap();
+
+        assert_eq!(second.last_message().unwrap().content(), "reply 0 to hi");
+        assert_eq!(model.call_count(), 1);
+    }
+
+    #[test]
+    fn default_key_depends_on_tools() {
+        let store: Arc<dyn BaseStore> = Arc::new(InMemoryStore::new());
+        let messages = vec![Arc::new(Message::user("hi"))];
+        let cache = CacheMiddleware::new(store.clone(), Vec::new());
+
+        let all = cache.key(&messages, None);
+        let restricted = cache.key(&messages, Some(&["search".to_owned()]));
+
+        assert_ne!(all, restricted);
+        assert_eq!(all, cache.key(&messages, None));
+
+        // 工具不同的 Agent 不共用条目
+        let search = ToolSpec::Function {
+            function: langchain_core::request::ToolFunction {
+                name: "search".to_owned(),
+                description: String::new(),
+                parameters: serde_json::json!({"type": "object"}),
+                examples: Vec::new(),
+                output_schema: None,
+            },
+        };
+        let with_tools = CacheMiddleware::new(store, vec![search]);
+        assert_ne!(all, with_tools.key(&messages, None));
+    }
+
+    #[test]
+    fn configuring_a_clone_leaves_the_original_unchanged() {
+        let cache = CacheMiddleware::new(Arc::new(InMemoryStore::new()), Vec::new());
+        let configured = cache.clone().with_ttl(Duration::from_secs(60));
+
+        assert_eq!(cache.inner.ttl, None);
+        assert_eq!(configured.inner.ttl, Some(Duration::from_secs(60)));
+    }
+}
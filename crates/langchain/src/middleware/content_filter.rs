@@ -0,0 +1,299 @@
+//! 内容过滤中间件
+//!
+//! 按黑名单或正则规则检查用户输入和模型输出：命中黑名单时拦截，命中正则时替换匹配的内容。
+
+use std::sync::Arc;
+
+use langchain_core::{
+    message::Message,
+    state::{JumpTo, MessagesState},
+};
+use langgraph::node::NodeContext;
+use regex::Regex;
+
+use crate::{
+    define_middleware_label,
+    node::middleware::{AgentHook, AgentMiddleware},
+};
+
+/// 默认的拒绝回复
+const DEFAULT_REFUSAL: &str = "Sorry, I can't help with that request.";
+
+/// 过滤规则
+#[derive(Debug, Clone)]
+pub enum FilterRule {
+    /// 文本包含其中任意一个词（不区分大小写）时拦截
+    Blacklist(Vec<String>),
+    /// 把匹配的内容替换为 `replacement`，支持 `$1` 等捕获组引用
+    Regex { pattern: Regex, replacement: String },
+}
+
+impl FilterRule {
+    pub fn blacklist<I, S>(words: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self::Blacklist(words.into_iter().map(Into::into).collect())
+    }
+
+    pub fn regex(pattern: &str, replacement: impl Into<String>) -> Result<Self, regex::Error> {
+        Ok(Self::Regex {
+            pattern: Regex::new(pattern)?,
+            replacement: replacement.into(),
+        })
+    }
+
+    /// 文本命中黑名单时返回命中的词
+    fn blocked_word(&self, text: &str) -> Option<&str> {
+        let Self::Blacklist(words) = self else {
+            return None;
+        };
+        let text = text.to_lowercase();
+        words
+            .iter()
+            .find(|word| !word.is_empty() && text.contains(&word.to_lowercase()))
+            .map(String::as_str)
+    }
+}
+
+/// 依次应用所有正则规则，没有改动时返回 `None`
+fn redact(rules: &[FilterRule], text: &str) -> Option<String> {
+    let mut result: Option<String> = None;
+    for rule in rules {
+        if let FilterRule::Regex {
+            pattern,
+            replacement,
+        } = rule
+        {
+            let current = result.as_deref().unwrap_or(text);
+            if pattern.is_match(current) {
+                result = Some(
+                    pattern
+                        .replace_all(current, replacement.as_str())
+                        .into_owned(),
+                );
+            }
+        }
+    }
+    result
+}
+
+/// 第一个命中的黑名单词
+fn blocked_word<'a>(rules: &'a [FilterRule], message: &Message) -> Option<&'a str> {
+    let mut texts = Vec::new();
+    // 借助 map_text 遍历所有文本（包括工具调用参数），不修改消息
+    message.map_text(|text| {
+        texts.push(text.to_owned());
+        None
+    });
+    rules
+        .iter()
+        .find_map(|rule| texts.iter().find_map(|text| rule.blocked_word(text)))
+}
+
+/// 内容过滤中间件
+///
+/// - 输入规则在 `before_model` 中检查本轮新增的用户消息（最后一条助手消息之后的用户消息）：
+///   命中黑名单时追加拒绝回复并通过 [`JumpTo::End`] 结束运行，不调用模型；
+///   正则规则直接替换消息中匹配的内容，模型和 checkpoint 只会看到替换后的文本
+/// - 输出规则在 `after_model` 中检查模型回复：命中黑名单时把回复（连同其中的工具调用）
+///   替换为拒绝回复；正则规则替换回复正文和工具调用参数中匹配的内容
+///
+/// 黑名单先于正则规则检查，与规则的添加顺序无关。
+///
+/// ```rust,ignore
+/// let filter = ContentFilterMiddleware::new()
+///     .with_input_rule(FilterRule::blacklist(["ignore previous instructions"]))
+///     .with_output_rule(FilterRule::regex(r"sk-[A-Za-z0-9]{20,}", "[API_KEY]")?);
+/// let agent = ReactAgent::builder(model)
+///     .with_middlewares([filter.into()])
+///     .build();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ContentFilterMiddleware {
+    input_rules: Vec<FilterRule>,
+    output_rules: Vec<FilterRule>,
+    refusal: String,
+}
+
+impl Default for ContentFilterMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ContentFilterMiddleware {
+    /// 没有任何规则的过滤器，通过 `with_*_rule` 添加规则
+    pub fn new() -> Self {
+        Self {
+            input_rules: Vec::new(),
+            output_rules: Vec::new(),
+            refusal: DEFAULT_REFUSAL.to_owned(),
+        }
+    }
+
+    pub fn with_input_rule(mut self, rule: FilterRule) -> Self {
+        self.input_rules.push(rule);
+        self
+    }
+
+    pub fn with_output_rule(mut self, rule: FilterRule) -> Self {
+        self.output_rules.push(rule);
+        self
+    }
+
+    /// 拦截时的回复内容
+    pub fn with_refusal(mut self, refusal: impl Into<String>) -> Self {
+        self.refusal = refusal.into();
+        self
+    }
+
+    fn filter_input(&self, state: &MessagesState) -> MessagesState {
+        let mut update = MessagesState::default();
+        let start = state
+            .messages
+            .iter()
+            .rposition(|m| matches!(m.as_ref(), Message::Assistant { .. }))
+            .map_or(0, |index| index + 1);
+
+        for (index, message) in state.messages.iter().enumerate().skip(start) {
+            if !matches!(message.as_ref(), Message::User { .. }) {
+                continue;
+            }
+            if let Some(word) = blocked_word(&self.input_rules, message) {
+                tracing::warn!("blocked input containing {word:?}");
+                let mut blocked = MessagesState::default();
+                blocked.push_message_owned(Message::assistant(self.refusal.clone()));
+                blocked.jump(JumpTo::End);
+                return blocked;
+            }
+            if let Some(redacted) = message.map_text(|text| redact(&self.input_rules, text)) {
+                update.replace_message(index, redacted);
+            }
+        }
+        update
+    }
+
+    fn filter_output(&self, state: &MessagesState) -> MessagesState {
+        let mut update = MessagesState::default();
+        let Some(reply) = state
+            .last_message()
+            .filter(|m| matches!(m.as_ref(), Message::Assistant { .. }))
+        else {
+            return update;
+        };
+        let index = state.messages.len() - 1;
+
+        if let Some(word) = blocked_word(&self.output_rules, reply) {
+            tracing::warn!("blocked model output containing {word:?}");
+            update.replace_message(index, Message::assistant(self.refusal.clone()));
+        } else if let Some(redacted) = reply.map_text(|text| redact(&self.output_rules, text)) {
+            update.replace_message(index, redacted);
+        }
+        update
+    }
+}
+
+impl From<ContentFilterMiddleware> for AgentMiddleware<MessagesState> {
+    fn from(filter: ContentFilterMiddleware) -> Self {
+        let label = define_middleware_label!(ContentFilterLabel);
+        let mut middleware = AgentMiddleware::from_label(label);
+
+        if !filter.input_rules.is_empty() {
+            let input = filter.clone();
+            middleware = middleware.with_before_model(AgentHook {
+                handler: Arc::new(move |state: &MessagesState, _: &NodeContext| {
+                    let update = input.filter_input(state);
+                    Box::pin(async move { Ok(update) })
+                }),
+                target: None,
+                branches: vec![],
+            });
+        }
+        if !filter.output_rules.is_empty() {
+            middleware = middleware.with_after_model(AgentHook {
+                handler: Arc::new(move |state: &MessagesState, _: &NodeContext| {
+                    let update = filter.filter_output(state);
+                    Box::pin(async move { Ok(update) })
+                }),
+                target: None,
+                branches: vec![],
+            });
+        }
+        middleware
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ReactAgent, testing::MockModel};
+
+    /// 模型复述最后一条消息
+    fn agent(filter: ContentFilterMiddleware) -> (ReactAgent, Arc<MockModel>) {
+        let model = Arc::new(MockModel::from_fn(|request| {
+            let last = request.messages.last().unwrap().content();
+            Ok(Message::assistant(format!("you said: {last}")))
+        }));
+        let agent = ReactAgent::builder(model.clone())
+            .with_middlewares([filter.into()])
+            .build();
+        (agent, model)
+    }
+
+    #[tokio::test]
+    async fn blacklisted_input_is_refused_without_calling_the_model() {
+        let filter = ContentFilterMiddleware::new()
+            .with_input_rule(FilterRule::blacklist(["Forbidden"]))
+            .with_refusal("no");
+        let (agent, model) = agent(filter);
+
+        let blocked = agent
+            .invoke(Message::user("this is FORBIDDEN stuff"), None)
+            .await
+            .unwrap();
+        let allowed = agent.invoke(Message::user("hello"), None).await.unwrap();
+
+        assert_eq!(blocked.last_message().unwrap().content(), "no");
+        assert_eq!(allowed.last_message().unwrap().content(), "you said: hello");
+        assert_eq!(model.call_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn regex_rules_redact_input_and_assistant_output() {
+        let filter = ContentFilterMiddleware::new()
+            .with_input_rule(FilterRule::regex(r"\d{4}-\d{4}", "[CARD]").unwrap())
+            .with_output_rule(FilterRule::regex(r"(?i)you said", "echo").unwrap())
+            .with_output_rule(FilterRule::regex(r"secret-(\w+)", "secret-***").unwrap());
+        let (agent, _) = agent(filter);
+
+        let state = agent
+            .invoke(Message::user("card 1234-5678 and secret-abc"), None)
+            .await
+            .unwrap();
+
+        // 输入在发送给模型前已经替换
+        assert_eq!(state.messages[0].content(), "card [CARD] and secret-abc");
+        assert_eq!(
+            state.last_message().unwrap().content(),
+            "echo: card [CARD] and secret-***"
+        );
+    }
+
+    #[tokio::test]
+    async fn blacklisted_output_is_replaced_with_refusal() {
+        let filter =
+            ContentFilterMiddleware::new().with_output_rule(FilterRule::blacklist(["password"]));
+        let (agent, _) = agent(filter);
+
+        let state = agent
+            .invoke(Message::user("my password is hunter2"), None)
+            .await
+            .unwrap();
+
+        assert_eq!(state.last_message().unwrap().content(), DEFAULT_REFUSAL);
+        // 输入没有输入规则，不受影响
+        assert_eq!(state.messages[0].content(), "my password is hunter2");
+    }
+}
@@ -0,0 +1,292 @@
+//! 费用统计中间件
+//!
+//! 按每个模型的千 token 单价，把模型调用的 token 用量换算为费用并累计。[`CostTracker`] 通过
+//! `Arc` 在多个 Agent 之间共享，即可统计使用不同模型的 Agent 的总费用。
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
+
+use langchain_core::{response::Usage, state::MessagesState};
+use langgraph::{checkpoint::Configuration, node::NodeContext};
+
+use super::runs::RunRegistry;
+use crate::{
+    AgentError, define_middleware_label,
+    node::middleware::{AgentHook, AgentMiddleware},
+};
+
+/// 单个模型的累计用量与费用
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ModelCost {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cost: f64,
+}
+
+#[derive(Debug, Default)]
+struct Totals {
+    models: HashMap<String, ModelCost>,
+    /// 已经警告过没有单价的模型，避免重复输出
+    unpriced: HashSet<String>,
+}
+
+/// 费用累计器
+///
+/// 单价为每 1000 个 token 的价格 `(输入, 输出)`，货币单位由调用方决定。没有配置单价的模型
+/// 仍会累计 token 数，但费用按 0 计算。
+///
+/// ```rust,ignore
+/// let tracker = Arc::new(
+///     CostTracker::new()
+///         .with_price("gpt-4o", 0.0025, 0.01)
+///         .with_price("gpt-4o-mini", 0.00015, 0.0006),
+/// );
+/// let agent = ReactAgent::builder(model)
+///     .with_middlewares([CostTrackingMiddleware::new(tracker.clone(), "gpt-4o").into()])
+///     .build();
+/// agent.invoke(Message::user("hi"), None).await?;
+/// println!("${:.4}", tracker.total_cost());
+/// ```
+#[derive(Debug, Default)]
+pub struct CostTracker {
+    prices: HashMap<String, (f64, f64)>,
+    totals: Mutex<Totals>,
+}
+
+impl CostTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 使用给定的单价表：模型名 -> `(输入单价, 输出单价)`，单位为每 1000 token
+    pub fn with_prices(mut self, prices: HashMap<String, (f64, f64)>) -> Self {
+        self.prices = prices;
+        self
+    }
+
+    /// 设置单个模型每 1000 token 的输入、输出单价
+    pub fn with_price(mut self, model: impl Into<String>, input: f64, output: f64) -> Self {
+        self.prices.insert(model.into(), (input, output));
+        self
+    }
+
+    /// 累计一次调用的用量，返回这次调用的费用
+    pub fn record(&self, model: &str, usage: &Usage) -> f64 {
+        let cost = match self.prices.get(model) {
+            Some((input, output)) => {
+                (f64::from(usage.prompt_tokens) * input
+                    + f64::from(usage.completion_tokens) * output)
+                    / 1000.0
+            }
+            None => 0.0,
+        };
+
+        let mut totals = self.totals.lock().unwrap();
+        if !self.prices.contains_key(model) && totals.unpriced.insert(model.to_owned()) {
+            tracing::warn!("no price configured for model {model}, counting its cost as 0");
+        }
+        let entry = totals.models.entry(model.to_owned()).or_default();
+        entry.prompt_tokens += u64::from(usage.prompt_tokens);
+        entry.completion_tokens += u64::from(usage.completion_tokens);
+        entry.cost += cost;
+        cost
+    }
+
+    /// 所有模型的累计费用
+    pub fn total_cost(&self) -> f64 {
+        self.totals
+            .lock()
+            .unwrap()
+            .models
+            .values()
+            .map(|m| m.cost)
+            .sum()
+    }
+
+    /// 按模型分列的累计用量与费用
+    pub fn costs_by_model(&self) -> HashMap<String, ModelCost> {
+        self.totals.lock().unwrap().models.clone()
+    }
+
+    /// 清空累计值，单价保持不变
+    pub fn reset(&self) {
+        self.totals.lock().unwrap().models.clear();
+    }
+}
+
+/// 费用统计中间件
+///
+/// 在 `before_model` 记下本次运行的累计用量（[`MessagesState::usage`]），在 `after_model`
+/// 取差值得到这次调用的用量，按 `model` 的单价计入 [`CostTracker`]。缓存命中等跳过模型的
+/// 调用没有用量，不产生费用。
+#[derive(Clone)]
+pub struct CostTrackingMiddleware {
+    tracker: Arc<CostTracker>,
+    model: Arc<str>,
+    /// 进行中的模型调用开始前的累计用量
+    pending: Arc<RunRegistry<Usage>>,
+}
+
+impl std::fmt::Debug for CostTrackingMiddleware {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CostTrackingMiddleware")
+            .field("model", &self.model)
+            .field("tracker", &self.tracker)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CostTrackingMiddleware {
+    /// `model` 为 Agent 使用的模型名，用于在 `tracker` 中查找单价
+    pub fn new(tracker: Arc<CostTracker>, model: impl Into<String>) -> Self {
+        Self {
+            tracker,
+            model: Arc::from(model.into()),
+            pending: Arc::default(),
+        }
+    }
+
+    pub fn tracker(&self) -> &Arc<CostTracker> {
+        &self.tracker
+    }
+
+    /// 共享的 [`CostTracker`] 中所有模型的累计费用
+    pub fn total_cost(&self) -> f64 {
+        self.tracker.total_cost()
+    }
+
+    fn before_model(&self, state: &MessagesState, context: &NodeContext) {
+        self.pending.start(context.config, state.usage.clone());
+    }
+
+    fn after_model(&self, state: &MessagesState, context: &NodeContext) {
+        let before = self.pending.finish(context.config).unwrap_or_default();
+        let usage = Usage {
+            prompt_tokens: state
+                .usage
+                .prompt_tokens
+                .saturating_sub(before.prompt_tokens),
+            completion_tokens: state
+                .usage
+                .completion_tokens
+                .saturating_sub(before.completion_tokens),
+            total_tokens: state.usage.total_tokens.saturating_sub(before.total_tokens),
+            completion_tokens_details: None,
+        };
+        if usage.total_tokens > 0 || usage.prompt_tokens > 0 || usage.completion_tokens > 0 {
+            self.tracker.record(&self.model, &usage);
+        }
+    }
+}
+
+impl From<CostTrackingMiddleware> for AgentMiddleware<MessagesState> {
+    fn from(cost: CostTrackingMiddleware) -> Self {
+        let label = define_middleware_label!(CostTrackingLabel);
+        let before = cost.clone();
+        let pending = cost.pending.clone();
+        AgentMiddleware::from_label(label)
+            .with_before_model(AgentHook {
+                handler: Arc::new(move |state: &MessagesState, context: &NodeContext| {
+                    before.before_model(state, context);
+                    Box::pin(async { Ok::<_, AgentError>(MessagesState::default()) })
+                }),
+                target: None,
+                branches: vec![],
+            })
+            .with_after_model(AgentHook {
+                handler: Arc::new(move |state: &MessagesState, context: &NodeContext| {
+                    cost.after_model(state, context);
+                    Box::pin(async { Ok::<_, AgentError>(MessagesState::default()) })
+                }),
+                target: None,
+                branches: vec![],
+            })
+            .with_on_run_error(Arc::new(move |config: &Configuration, _: &AgentError| {
+                pending.finish(config);
+            }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ReactAgent, testing::MockModel};
+    use langchain_core::{error::ToolError, message::Message, tool};
+
+    /// 第一次调用工具，之后直接回答；每次调用输入 1000、输出 500 token
+    fn tool_then_answer() -> MockModel {
+        MockModel::from_fn(|request| {
+            let last = request.messages.last().unwrap();
+            Ok(if matches!(last.as_ref(), Message::Tool { .. }) {
+                Message::assistant("done")
+            } else {
+                MockModel::tool_call("noop", serde_json::json!({}))
+            })
+        })
+        .with_usage(Usage {
+            prompt_tokens: 1000,
+            completion_tokens: 500,
+            total_tokens: 1500,
+            completion_tokens_details: None,
+        })
+    }
+
+    #[tool(description = "noop")]
+    async fn noop() -> Result<String, ToolError> {
+        Ok("ok".to_owned())
+    }
+
+    fn agent(tracker: &Arc<CostTracker>, model: &str) -> ReactAgent {
+        ReactAgent::builder(tool_then_answer())
+            .with_tools([noop_tool()])
+            .with_middlewares([CostTrackingMiddleware::new(tracker.clone(), model).into()])
+            .build()
+    }
+
+    #[tokio::test]
+    async fn accumulates_cost_per_call_and_per_model() {
+        let tracker = Arc::new(
+            CostTracker::new()
+                .with_price("big", 0.01, 0.03)
+                .with_price("small", 0.001, 0.002),
+        );
+
+        agent(&tracker, "big")
+            .invoke(Message::user("go"), None)
+            .await
+            .unwrap();
+        agent(&tracker, "small")
+            .invoke(Message::user("go"), None)
+            .await
+            .unwrap();
+
+        let costs = tracker.costs_by_model();
+        // 每次调用 1000 * 0.01 / 1000 + 500 * 0.03 / 1000 = 0.025，共两次
+        assert!((costs["big"].cost - 0.05).abs() < 1e-9);
+        assert_eq!(costs["big"].prompt_tokens, 2000);
+        assert_eq!(costs["small"].completion_tokens, 1000);
+        assert!((costs["small"].cost - 0.004).abs() < 1e-9);
+        assert!((tracker.total_cost() - 0.054).abs() < 1e-9);
+    }
+
+    #[test]
+    fn unpriced_models_count_tokens_but_cost_nothing() {
+        let tracker =
+            CostTracker::new().with_prices(HashMap::from([("known".to_owned(), (1.0, 1.0))]));
+        let usage = Usage {
+            prompt_tokens: 10,
+            completion_tokens: 10,
+            total_tokens: 20,
+            completion_tokens_details: None,
+        };
+
+        assert_eq!(tracker.record("unknown", &usage), 0.0);
+        assert!((tracker.record("known", &usage) - 0.02).abs() < 1e-9);
+        assert_eq!(tracker.costs_by_model()["unknown"].prompt_tokens, 10);
+
+        tracker.reset();
+        assert_eq!(tracker.total_cost(), 0.0);
+    }
+}
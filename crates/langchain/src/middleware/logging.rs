@@ -0,0 +1,407 @@
+//! 日志中间件
+//!
+//! 记录 Agent 运行、每次模型调用和每次工具调用的开始、结束与耗时。同一次运行的日志带有相同的
+//! request id，便于在并发请求交错的日志中筛选。
+
+use std::{fmt::Display, sync::Arc, time::Instant};
+
+use langchain_core::{
+    message::Message,
+    state::{MessagesState, ToolFuture},
+};
+use langgraph::{checkpoint::Configuration, node::NodeContext};
+use serde_json::Value;
+use tracing::Level;
+
+use super::runs::RunRegistry;
+use crate::{
+    AgentError, define_middleware_label,
+    node::{
+        middleware::{AgentHook, AgentMiddleware},
+        tool::{ToolHandler, ToolMiddleware},
+    },
+};
+
+/// 单次运行的日志上下文
+struct RunLog {
+    request_id: String,
+    started_at: Instant,
+    model_started_at: Option<Instant>,
+}
+
+impl RunLog {
+    fn new(config: &Configuration) -> Self {
+        Self {
+            request_id: new_request_id(config),
+            started_at: Instant::now(),
+            model_started_at: None,
+        }
+    }
+}
+
+fn new_request_id(config: &Configuration) -> String {
+    config.thread_id.clone().unwrap_or_else(|| {
+        let mut id = uuid::Uuid::new_v4().simple().to_string();
+        id.truncate(8);
+        id
+    })
+}
+
+/// 日志中间件
+///
+/// 通过 `tracing` 输出以下事件，每条都以 `[request_id]` 开头：
+///
+/// - Agent 开始与结束，结束时附带总耗时、模型调用次数和 token 用量；以错误结束时附带错误
+/// - 每次模型调用的开始与结束，结束时附带耗时和工具调用数量
+/// - 每次工具调用的结果和耗时（需要通过 [`tool_middleware`](Self::tool_middleware) 注册）
+///
+/// request id 优先使用 [`Configuration::thread_id`]，未设置时为每次运行生成一个随机 id。
+/// 默认不记录消息内容，开启 [`with_message_contents`](Self::with_message_contents) 后记录
+/// 模型的输入输出和工具的参数与结果，注意其中可能包含敏感信息。
+///
+/// [`Configuration::thread_id`]: langgraph::checkpoint::Configuration::thread_id
+///
+/// ```rust,ignore
+/// let logging = LoggingMiddleware::new().with_level(Level::DEBUG);
+/// let agent = ReactAgent::builder(model)
+///     .with_tool_middleware(logging.tool_middleware())
+///     .with_middlewares([logging.into()])
+///     .build();
+/// ```
+#[derive(Clone)]
+pub struct LoggingMiddleware {
+    level: Level,
+    log_contents: bool,
+    max_content_len: usize,
+    runs: Arc<RunRegistry<RunLog>>,
+}
+
+impl std::fmt::Debug for LoggingMiddleware {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LoggingMiddleware")
+            .field("level", &self.level)
+            .field("log_contents", &self.log_contents)
+            .field("max_content_len", &self.max_content_len)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for LoggingMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LoggingMiddleware {
+    /// 以 `INFO` 级别记录，不记录消息内容
+    pub fn new() -> Self {
+        Self {
+            level: Level::INFO,
+            log_contents: false,
+            max_content_len: 500,
+            runs: Arc::default(),
+        }
+    }
+
+    /// 日志级别，默认 `INFO`
+    pub fn with_level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// 是否记录消息内容、工具参数与结果，默认不记录
+    pub fn with_message_contents(mut self, enabled: bool) -> Self {
+        self.log_contents = enabled;
+        self
+    }
+
+    /// 记录内容时每段文本最多保留的字符数，默认 500
+    pub fn with_max_content_len(mut self, max_len: usize) -> Self {
+        self.max_content_len = max_len;
+        self
+    }
+
+    /// 记录工具调用的包装器，传给 [`ReactAgentBuilder::with_tool_middleware`]
+    ///
+    /// [`ReactAgentBuilder::with_tool_middleware`]: crate::ReactAgentBuilder::with_tool_middleware
+    pub fn tool_middleware<E>(&self) -> Arc<ToolMiddleware<E>>
+    where
+        E: Display + Send + 'static,
+    {
+        let this = self.clone();
+        Arc::new(Box::new(
+            move |_: &MessagesState,
+                  context: &NodeContext,
+                  name: &str,
+                  args: Value,
+                  handler: ToolHandler<E>|
+                  -> ToolFuture<E> {
+                let request_id = this.request_id(context);
+                let name = name.to_owned();
+                if this.log_contents {
+                    this.emit(format!(
+                        "[{request_id}] tool {name} called with {}",
+                        this.truncate(&args.to_string())
+                    ));
+                }
+                let this = this.clone();
+                Box::pin(async move {
+                    let started = Instant::now();
+                    let result = handler(args).await;
+                    let elapsed = started.elapsed().as_millis();
+                    match &result {
+                        Ok(value) if this.log_contents => this.emit(format!(
+                            "[{request_id}] tool {name} ok in {elapsed}ms: {}",
+                            this.truncate(&value.to_string())
+                        )),
+                        Ok(_) => this.emit(format!("[{request_id}] tool {name} ok in {elapsed}ms")),
+                        Err(e) => this.emit(format!(
+                            "[{request_id}] tool {name} failed in {elapsed}ms: {e}"
+                        )),
+                    }
+                    result
+                })
+            },
+        ))
+    }
+
+    fn emit(&self, line: String) {
+        match self.level {
+            Level::TRACE => tracing::trace!("{line}"),
+            Level::DEBUG => tracing::debug!("{line}"),
+            Level::INFO => tracing::info!("{line}"),
+            Level::WARN => tracing::warn!("{line}"),
+            Level::ERROR => tracing::error!("{line}"),
+        }
+    }
+
+    fn truncate(&self, text: &str) -> String {
+        let max_len = self.max_content_len;
+        let total = text.chars().count();
+        if total <= max_len {
+            return text.to_owned();
+        }
+        let kept: String = text.chars().take(max_len).collect();
+        format!("{kept}… ({} more chars)", total - max_len)
+    }
+
+    /// 当前运行的 request id；从中断处恢复的运行不会经过 `before_agent`，此时补建记录
+    fn request_id(&self, context: &NodeContext) -> String {
+        self.runs.with(
+            context.config,
+            || RunLog::new(context.config),
+            |run| run.request_id.clone(),
+        )
+    }
+
+    fn before_agent(&self, state: &MessagesState, context: &NodeContext) {
+        let run = RunLog::new(context.config);
+        let request_id = run.request_id.clone();
+        self.runs.start(context.config, run);
+        self.emit(format!(
+            "[{request_id}] agent started with {} messages",
+            state.messages.len()
+        ));
+    }
+
+    fn before_model(&self, state: &MessagesState, context: &NodeContext) {
+        let request_id = self.runs.with(
+            context.config,
+            || RunLog::new(context.config),
+            |run| {
+                run.model_started_at = Some(Instant::now());
+                run.request_id.clone()
+            },
+        );
+        let mut line = format!(
+            "[{request_id}] model call #{} with {} messages",
+            state.llm_calls + 1,
+            state.messages.len()
+        );
+        if self.log_contents
+            && let Some(last) = state.last_message()
+        {
+            line.push_str(&format!("\n{}", last.render(Some(self.max_content_len))));
+        }
+        self.emit(line);
+    }
+
+    fn after_model(&self, state: &MessagesState, context: &NodeContext) {
+        let (request_id, model_started_at) = self.runs.with(
+            context.config,
+            || RunLog::new(context.config),
+            |run| (run.request_id.clone(), run.model_started_at.take()),
+        );
+        let elapsed = model_started_at
+            .map(|started| format!("{}ms", started.elapsed().as_millis()))
+            .unwrap_or_else(|| "?".to_owned());
+        let reply = state
+            .last_message()
+            .filter(|m| matches!(m.as_ref(), Message::Assistant { .. }));
+        let tool_calls = match reply.map(|m| m.as_ref()) {
+            Some(Message::Assistant {
+                tool_calls: Some(calls),
+                ..
+            }) => calls.len(),
+            _ => 0,
+        };
+        let mut line =
+            format!("[{request_id}] model replied in {elapsed} with {tool_calls} tool calls");
+        if self.log_contents
+            && let Some(reply) = reply
+        {
+            line.push_str(&format!("\n{}", reply.render(Some(self.max_content_len))));
+        }
+        self.emit(line);
+    }
+
+    /// 取出并结束当前运行，返回 request id 和运行耗时
+    fn finish(&self, config: &Configuration) -> (String, String) {
+        match self.runs.finish(config) {
+            Some(run) => (
+                run.request_id,
+                format!("{}ms", run.started_at.elapsed().as_millis()),
+            ),
+            None => (new_request_id(config), "?".to_owned()),
+        }
+    }
+
+    fn after_agent(&self, state: &MessagesState, context: &NodeContext) {
+        let (request_id, elapsed) = self.finish(context.config);
+        self.emit(format!(
+            "[{request_id}] agent finished in {elapsed}: {} model calls, {} tokens",
+            state.llm_calls, state.usage.total_tokens
+        ));
+    }
+
+    fn on_run_error(&self, config: &Configuration, error: &AgentError) {
+        let (request_id, elapsed) = self.finish(config);
+        self.emit(format!("[{request_id}] agent failed in {elapsed}: {error}"));
+    }
+}
+
+/// 把只记录日志、不修改状态的方法包装为钩子
+fn hook(
+    logging: &LoggingMiddleware,
+    log: fn(&LoggingMiddleware, &MessagesState, &NodeContext),
+) -> AgentHook<MessagesState> {
+    let logging = logging.clone();
+    AgentHook {
+        handler: Arc::new(move |state: &MessagesState, context: &NodeContext| {
+            log(&logging, state, context);
+            Box::pin(async { Ok::<_, AgentError>(MessagesState::default()) })
+        }),
+        target: None,
+        branches: vec![],
+    }
+}
+
+impl From<LoggingMiddleware> for AgentMiddleware<MessagesState> {
+    fn from(logging: LoggingMiddleware) -> Self {
+        let label = define_middleware_label!(LoggingLabel);
+        AgentMiddleware::from_label(label)
+            .with_before_agent(hook(&logging, LoggingMiddleware::before_agent))
+            .with_before_model(hook(&logging, LoggingMiddleware::before_model))
+            .with_after_model(hook(&logging, LoggingMiddleware::after_model))
+            .with_after_agent(hook(&logging, LoggingMiddleware::after_agent))
+            .with_on_run_error(Arc::new(
+                move |config: &Configuration, error: &AgentError| {
+                    logging.on_run_error(config, error);
+                },
+            ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ReactAgent, testing::MockModel};
+    use langchain_core::{error::ToolError, tool};
+    use std::sync::Mutex;
+
+    /// 第一次调用工具，之后直接回答
+    fn tool_then_answer() -> MockModel {
+        MockModel::from_fn(|request| {
+            let last = request.messages.last().unwrap();
+            Ok(if matches!(last.as_ref(), Message::Tool { .. }) {
+                Message::assistant("the answer is 3")
+            } else {
+                MockModel::tool_call("add", serde_json::json!({"a": 1, "b": 2}))
+            })
+        })
+    }
+
+    #[tool(description = "add two numbers", args(a = "a", b = "b"))]
+    async fn add(a: i32, b: i32) -> Result<i32, ToolError> {
+        Ok(a + b)
+    }
+
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    async fn run(logging: LoggingMiddleware, thread_id: Option<&str>) -> String {
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(Level::TRACE)
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let agent = ReactAgent::builder(tool_then_answer())
+            .with_tools([add_tool()])
+            .with_tool_middleware(logging.tool_middleware())
+            .with_middlewares([logging.into()])
+            .build();
+        agent
+            .invoke(Message::user("1 + 2?"), thread_id)
+            .await
+            .unwrap();
+
+        let bytes = captured.0.lock().unwrap().clone();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn logs_model_and_tool_calls_with_request_id() {
+        let logs = run(LoggingMiddleware::new(), Some("thread-7")).await;
+
+        let lines: Vec<_> = logs.lines().filter(|l| l.contains("[thread-7]")).collect();
+        assert!(lines[0].contains("agent started with 1 messages"));
+        assert!(lines.iter().any(|l| l.contains("model call #2")));
+        assert!(lines.iter().any(|l| l.contains("tool add ok in")));
+        assert!(lines.last().unwrap().contains("agent finished in"));
+        assert!(lines.last().unwrap().contains("2 model calls"));
+        // 默认不记录内容
+        assert!(!logs.contains("1 + 2?"));
+    }
+
+    #[tokio::test]
+    async fn contents_and_level_are_configurable() {
+        let logging = LoggingMiddleware::new()
+            .with_level(Level::DEBUG)
+            .with_message_contents(true)
+            .with_max_content_len(6);
+        let logs = run(logging.clone(), None).await;
+
+        assert!(logs.contains("DEBUG"));
+        assert!(!logs.contains(" INFO "));
+        assert!(logs.contains("tool add called with {\"a\":1… (7 more chars)"));
+        assert!(logs.contains("User: 1 + 2?"));
+        assert!(logs.contains("Assistant: the answer is 3"));
+        // 运行结束后不保留记录
+        assert_eq!(logging.runs.len(), 0);
+    }
+}
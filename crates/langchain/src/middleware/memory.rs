@@ -0,0 +1,456 @@
+//! 长期记忆中间件
+//!
+//! 把每轮对话中值得记住的内容保存到 [`BaseStore`]，之后在同一命名空间（默认按线程）的
+//! 对话中，挑选与当前问题最相关的几条记忆，以一条系统消息的形式提供给模型。
+
+use std::{
+    collections::HashSet,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use langchain_core::{
+    message::Message,
+    state::MessagesState,
+    store::{BaseStore, Namespace, StoreFilter},
+};
+use langgraph::{checkpoint::Configuration, node::NodeContext};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    AgentError, define_middleware_label,
+    node::middleware::{AgentHook, AgentMiddleware},
+};
+
+/// 每次提供给模型的默认记忆条数
+pub const DEFAULT_MAX_MEMORIES: usize = 5;
+
+/// 标记记忆系统消息的 metadata 键
+pub const MEMORY_METADATA_KEY: &str = "memory";
+
+/// 默认命名空间的第一段，第二段为 `thread_id`
+pub const DEFAULT_MEMORY_NAMESPACE: &str = "memories";
+
+/// 根据运行配置决定记忆的命名空间，返回 `None` 时本次运行不读写记忆
+pub type MemoryNamespaceFn = dyn Fn(&Configuration) -> Option<Namespace> + Send + Sync;
+
+/// 从一轮结束时的状态中提取需要保存的记忆
+pub type MemoryExtractor = dyn Fn(&MessagesState) -> Vec<String> + Send + Sync;
+
+/// 存储中的一条记忆
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryRecord {
+    pub content: String,
+    /// 写入时间（Unix 时间戳，秒）
+    pub created_at: u64,
+}
+
+#[derive(Clone)]
+struct Inner {
+    store: Arc<dyn BaseStore>,
+    max_memories: usize,
+    namespace_fn: Arc<MemoryNamespaceFn>,
+    extractor: Arc<MemoryExtractor>,
+}
+
+/// 长期记忆中间件
+///
+/// - `before_model`：读取命名空间中的记忆，按与最后一条用户消息的词语重合度（相同时按时间
+///   由新到旧）选出至多 `max_memories` 条，作为一条带 [`MEMORY_METADATA_KEY`] 标记的系统消息
+///   加入历史；历史中已有这条消息时原位替换，不会重复追加。内容已经在对话历史中的记忆
+///   （例如本线程之前保存的）不再提供，检查点已经保留了它们
+/// - `after_model`：模型给出不含工具调用的最终回复后，用提取函数得到新的记忆并保存，
+///   内容相同的记忆只保存一份
+///
+/// 默认命名空间为 `memories:<thread_id>`，线程之间互不可见，没有 `thread_id` 的运行不读写记忆；
+/// 默认提取函数保存最后一条用户消息。需要跨线程共享记忆（例如按用户）时使用
+/// [`with_namespace_fn`](Self::with_namespace_fn)，需要由模型总结事实时使用
+/// [`with_extractor`](Self::with_extractor)。
+///
+/// ```rust,ignore
+/// let memory = MemoryMiddleware::new(Arc::new(InMemoryStore::new()))
+///     .with_namespace_fn(|config| {
+///         let user = config.thread_id.as_deref()?.split(':').next()?;
+///         Some(Namespace::new(vec!["memories".to_owned(), user.to_owned()]))
+///     });
+/// let agent = ReactAgent::builder(model)
+///     .with_middlewares([memory.into()])
+///     .build();
+/// ```
+#[derive(Clone)]
+pub struct MemoryMiddleware {
+    inner: Arc<Inner>,
+}
+
+impl std::fmt::Debug for MemoryMiddleware {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MemoryMiddleware")
+            .field("max_memories", &self.inner.max_memories)
+            .finish()
+    }
+}
+
+impl MemoryMiddleware {
+    pub fn new(store: Arc<dyn BaseStore>) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                store,
+                max_memories: DEFAULT_MAX_MEMORIES,
+                namespace_fn: Arc::new(|config: &Configuration| {
+                    let thread_id = config.thread_id.clone()?;
+                    Some(Namespace::new(vec![
+                        DEFAULT_MEMORY_NAMESPACE.to_owned(),
+                        thread_id,
+                    ]))
+                }),
+                extractor: Arc::new(|state: &MessagesState| {
+                    last_user_message(state)
+                        .filter(|content| !content.trim().is_empty())
+                        .map(|content| vec![content.to_owned()])
+                        .unwrap_or_default()
+                }),
+            }),
+        }
+    }
+
+    /// 配置已被克隆共享时复制一份，不影响其他副本
+    fn inner_mut(&mut self) -> &mut Inner {
+        Arc::make_mut(&mut self.inner)
+    }
+
+    /// 每次提供给模型的最大记忆条数
+    pub fn with_max_memories(mut self, max_memories: usize) -> Self {
+        self.inner_mut().max_memories = max_memories;
+        self
+    }
+
+    pub fn with_namespace_fn<F>(mut self, namespace_fn: F) -> Self
+    where
+        F: Fn(&Configuration) -> Option<Namespace> + Send + Sync + 'static,
+    {
+        self.inner_mut().namespace_fn = Arc::new(namespace_fn);
+        self
+    }
+
+    pub fn with_extractor<F>(mut self, extractor: F) -> Self
+    where
+        F: Fn(&MessagesState) -> Vec<String> + Send + Sync + 'static,
+    {
+        self.inner_mut().extractor = Arc::new(extractor);
+        self
+    }
+
+    /// 命名空间中的全部记忆，按写入时间由旧到新排列
+    pub async fn memories(&self, namespace: &Namespace) -> Vec<MemoryRecord> {
+        let entries = match self
+            .inner
+            .store
+            .list(namespace, &StoreFilter::Prefix(String::new()), None)
+            .await
+        {
+            Ok(entries) => entries,
+            Err(e) => {
+                tracing::warn!("failed to load memories from {namespace}: {e}");
+                return Vec::new();
+            }
+        };
+        let mut records: Vec<MemoryRecord> = entries
+            .into_iter()
+            .filter_map(|(key, bytes)| match serde_json::from_slice(&bytes) {
+                Ok(record) => Some(record),
+                Err(e) => {
+                    tracing::warn!("discarding malformed memory {key}: {e}");
+                    None
+                }
+            })
+            .collect();
+        records.sort_by_key(|record| record.created_at);
+        records
+    }
+
+    /// 保存一条记忆，内容相同的记忆只保留最早的一份
+    pub async fn remember(&self, namespace: &Namespace, content: &str) {
+        let key = format!("{:x}", Sha256::digest(content));
+        let store = &self.inner.store;
+        if store.exists(namespace, &key).await.unwrap_or(false) {
+            return;
+        }
+        let record = MemoryRecord {
+            content: content.to_owned(),
+            created_at: now_secs(),
+        };
+        let bytes = match serde_json::to_vec(&record) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                tracing::warn!("failed to serialize memory: {e}");
+                return;
+            }
+        };
+        if let Err(e) = store.put(namespace, &key, bytes).await {
+            tracing::warn!("failed to write memory: {e}");
+        }
+    }
+
+    /// 与 `query` 最相关的至多 `max_memories` 条记忆，按写入时间由旧到新排列
+    fn select(&self, records: Vec<MemoryRecord>, query: &str) -> Vec<MemoryRecord> {
+        let query = terms(query);
+        let mut scored: Vec<_> = records
+            .into_iter()
+            .map(|record| (terms(&record.content).intersection(&query).count(), record))
+            .collect();
+        scored.sort_by(|(a_score, a), (b_score, b)| {
+            b_score.cmp(a_score).then(b.created_at.cmp(&a.created_at))
+        });
+        let mut selected: Vec<_> = scored
+            .into_iter()
+            .take(self.inner.max_memories)
+            .map(|(_, record)| record)
+            .collect();
+        selected.sort_by_key(|record| record.created_at);
+        selected
+    }
+
+    fn before_model(
+        &self,
+        state: &MessagesState,
+        context: &NodeContext,
+    ) -> impl Future<Output = Result<MessagesState, AgentError>> + Send + 'static {
+        let namespace = (self.inner.namespace_fn)(context.config);
+        let query = last_user_message(state).unwrap_or_default().to_owned();
+        let seen: HashSet<String> = state
+            .messages
+            .iter()
+            .map(|m| m.content().to_owned())
+            .collect();
+        let existing = state
+            .messages
+            .iter()
+            .position(|m| m.metadata().contains_key(MEMORY_METADATA_KEY));
+        let this = self.clone();
+        async move {
+            let mut update = MessagesState::default();
+            let Some(namespace) = namespace else {
+                return Ok(update);
+            };
+            let records = this
+                .memories(&namespace)
+                .await
+                .into_iter()
+                .filter(|record| !seen.contains(&record.content))
+                .collect();
+            let selected = this.select(records, &query);
+            if selected.is_empty() {
+                return Ok(update);
+            }
+
+            let mut content = "Relevant memories from earlier conversations:".to_owned();
+            for record in &selected {
+                content.push_str("\n- ");
+                content.push_str(&record.content);
+            }
+            let message = Message::system(content).with_metadata(MEMORY_METADATA_KEY, true.into());
+            match existing {
+                Some(index) => update.replace_message(index, message),
+                None => update.push_message_owned(message),
+            }
+            Ok(update)
+        }
+    }
+
+    fn after_model(
+        &self,
+        state: &MessagesState,
+        context: &NodeContext,
+    ) -> impl Future<Output = Result<MessagesState, AgentError>> + Send + 'static {
+        // 还要调用工具时本轮尚未结束
+        let finished = state
+            .last_message()
+            .is_some_and(|m| matches!(m.as_ref(), Message::Assistant { .. }))
+            && state.last_tool_calls().is_none();
+        let namespace = (self.inner.namespace_fn)(context.config).filter(|_| finished);
+        let memories = match &namespace {
+            Some(_) => (self.inner.extractor)(state),
+            None => Vec::new(),
+        };
+        let this = self.clone();
+        async move {
+            if let Some(namespace) = namespace {
+                for memory in memories {
+                    this.remember(&namespace, &memory).await;
+                }
+            }
+            Ok(MessagesState::default())
+        }
+    }
+}
+
+fn last_user_message(state: &MessagesState) -> Option<&str> {
+    state
+        .messages
+        .iter()
+        .rev()
+        .find(|m| matches!(m.as_ref(), Message::User { .. }))
+        .map(|m| m.content())
+}
+
+/// 用于计算相关度的词语：按非字母数字字符切分并转为小写
+fn terms(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+impl From<MemoryMiddleware> for AgentMiddleware<MessagesState> {
+    fn from(memory: MemoryMiddleware) -> Self {
+        let label = define_middleware_label!(MemoryLabel);
+        let before = memory.clone();
+        AgentMiddleware::from_label(label)
+            .with_before_model(AgentHook {
+                handler: Arc::new(move |state: &MessagesState, context: &NodeContext| {
+                    Box::pin(before.before_model(state, context))
+                }),
+                target: None,
+                branches: vec![],
+            })
+            .with_after_model(AgentHook {
+                handler: Arc::new(move |state: &MessagesState, context: &NodeContext| {
+                    Box::pin(memory.after_model(state, context))
+                }),
+                target: None,
+                branches: vec![],
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ReactAgent, testing::MockModel};
+    use langchain_core::store::InMemoryStore;
+    use langgraph::checkpoint::MemorySaver;
+
+    #[tokio::test]
+    async fn threads_cannot_see_each_others_memories() {
+        let store: Arc<dyn BaseStore> = Arc::new(InMemoryStore::new());
+        let model = Arc::new(MockModel::new([
+            Message::assistant("Nice to meet you, Ada."),
+            Message::assistant("You like tea."),
+            Message::assistant("I don't know."),
+            Message::assistant("I don't know."),
+        ]));
+        let agent = ReactAgent::builder(model.clone())
+            .with_memory_store(store.clone())
+            .build();
+
+        agent
+            .invoke(Message::user("My name is Ada and I like tea"), Some("t1"))
+            .await
+            .unwrap();
+        let state = agent
+            .invoke(Message::user("What do I like?"), Some("t1"))
+            .await
+            .unwrap();
+        agent
+            .invoke(Message::user("What do I like?"), Some("t2"))
+            .await
+            .unwrap();
+        // 没有线程 id 的运行不读写记忆
+        agent
+            .invoke(Message::user("What do I like?"), None)
+            .await
+            .unwrap();
+
+        let requests = model.requests();
+        let memory = |index: usize| {
+            requests[index]
+                .messages
+                .iter()
+                .find(|m| m.metadata().contains_key(MEMORY_METADATA_KEY))
+                .map(|m| m.content().to_owned())
+        };
+        assert_eq!(memory(0), None);
+        assert_eq!(
+            memory(1).as_deref(),
+            Some("Relevant memories from earlier conversations:\n- My name is Ada and I like tea")
+        );
+        assert_eq!(memory(2), None);
+        assert_eq!(memory(3), None);
+        assert_eq!(state.last_message().unwrap().content(), "You like tea.");
+
+        let memory = MemoryMiddleware::new(store);
+        let namespace = |thread: &str| {
+            Namespace::new(vec![DEFAULT_MEMORY_NAMESPACE.to_owned(), thread.to_owned()])
+        };
+        assert_eq!(memory.memories(&namespace("t1")).await.len(), 2);
+        assert_eq!(memory.memories(&namespace("t2")).await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn memories_already_in_the_history_are_not_repeated() {
+        let model = Arc::new(MockModel::new([
+            Message::assistant("Nice to meet you, Ada."),
+            Message::assistant("You like tea."),
+        ]));
+        let agent = ReactAgent::builder(model.clone())
+            .with_memory_store(Arc::new(InMemoryStore::new()))
+            .with_checkpointer(Arc::new(MemorySaver::new()))
+            .build();
+
+        agent
+            .invoke(Message::user("My name is Ada and I like tea"), Some("t1"))
+            .await
+            .unwrap();
+        agent
+            .invoke(Message::user("What do I like?"), Some("t1"))
+            .await
+            .unwrap();
+
+        // 检查点保留了这条消息，不再作为记忆提供
+        assert!(
+            model.requests()[1]
+                .messages
+                .iter()
+                .all(|m| !m.metadata().contains_key(MEMORY_METADATA_KEY))
+        );
+    }
+
+    #[test]
+    fn configuring_a_clone_leaves_the_original_unchanged() {
+        let memory = MemoryMiddleware::new(Arc::new(InMemoryStore::new()));
+        let configured = memory.clone().with_max_memories(1);
+
+        assert_eq!(memory.inner.max_memories, DEFAULT_MAX_MEMORIES);
+        assert_eq!(configured.inner.max_memories, 1);
+    }
+
+    #[test]
+    fn most_relevant_memories_are_selected() {
+        let memory = MemoryMiddleware::new(Arc::new(InMemoryStore::new())).with_max_memories(2);
+        let record = |content: &str, created_at| MemoryRecord {
+            content: content.to_owned(),
+            created_at,
+        };
+
+        let selected = memory.select(
+            vec![
+                record("lives in Paris", 1),
+                record("likes green tea", 2),
+                record("has a cat", 3),
+                record("drinks tea every morning", 4),
+            ],
+            "what tea do I like?",
+        );
+
+        let contents: Vec<_> = selected.iter().map(|r| r.content.as_str()).collect();
+        assert_eq!(contents, ["likes green tea", "drinks tea every morning"]);
+    }
+}
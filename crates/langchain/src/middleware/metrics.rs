@@ -0,0 +1,465 @@
+//! 性能指标中间件
+//!
+//! 记录 Agent 运行、模型调用和工具调用的耗时与错误次数，通过 [`MetricsCollector`] 输出到
+//! 任意监控系统。内置 [`InMemoryMetrics`]，启用 `prometheus` feature 后还可以使用
+//! [`PrometheusMetrics`]。
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use langchain_core::state::{MessagesState, ToolFuture};
+use langgraph::{checkpoint::Configuration, node::NodeContext};
+use serde_json::Value;
+
+use super::runs::{RunKey, RunRegistry};
+use crate::{
+    AgentError, define_middleware_label,
+    node::{
+        middleware::{AgentHook, AgentMiddleware},
+        tool::{ToolHandler, ToolMiddleware},
+    },
+};
+
+/// 整个 Agent 运行的指标名
+pub const AGENT_NODE: &str = "agent";
+/// 模型调用的指标名
+pub const MODEL_NODE: &str = "model";
+
+/// 工具调用的指标名：`tool:<工具名>`
+pub fn tool_node(name: &str) -> String {
+    format!("tool:{name}")
+}
+
+/// 指标收集器
+///
+/// `node` 为 [`AGENT_NODE`]、[`MODEL_NODE`] 或 [`tool_node`] 返回的名称。
+/// 方法在钩子中同步调用，实现应当尽快返回。
+pub trait MetricsCollector: Send + Sync {
+    /// 记录一次执行的耗时（无论成功与否）
+    fn record_duration(&self, node: &str, duration: Duration);
+
+    /// 记录一次失败
+    fn record_error(&self, node: &str);
+}
+
+/// 单个节点的统计
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NodeMetrics {
+    /// 执行次数
+    pub count: u64,
+    /// 失败次数
+    pub errors: u64,
+    /// 累计耗时
+    pub total: Duration,
+    pub min: Duration,
+    pub max: Duration,
+}
+
+impl NodeMetrics {
+    /// 平均耗时，没有记录时为零
+    pub fn mean(&self) -> Duration {
+        match u32::try_from(self.count) {
+            Ok(0) => Duration::ZERO,
+            Ok(count) => self.total / count,
+            Err(_) => Duration::from_secs_f64(self.total.as_secs_f64() / self.count as f64),
+        }
+    }
+}
+
+/// 保存在内存中的指标，适合测试和简单的服务
+#[derive(Debug, Default)]
+pub struct InMemoryMetrics {
+    nodes: Mutex<HashMap<String, NodeMetrics>>,
+}
+
+impl InMemoryMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 当前所有节点统计的快照
+    pub fn snapshot(&self) -> HashMap<String, NodeMetrics> {
+        self.nodes.lock().unwrap().clone()
+    }
+
+    /// 单个节点统计的快照
+    pub fn get(&self, node: &str) -> Option<NodeMetrics> {
+        self.nodes.lock().unwrap().get(node).copied()
+    }
+
+    /// 清空所有统计
+    pub fn reset(&self) {
+        self.nodes.lock().unwrap().clear();
+    }
+}
+
+impl MetricsCollector for InMemoryMetrics {
+    fn record_duration(&self, node: &str, duration: Duration) {
+        let mut nodes = self.nodes.lock().unwrap();
+        let metrics = nodes.entry(node.to_owned()).or_default();
+        metrics.min = if metrics.count == 0 {
+            duration
+        } else {
+            metrics.min.min(duration)
+        };
+        metrics.max = metrics.max.max(duration);
+        metrics.count += 1;
+        metrics.total += duration;
+    }
+
+    fn record_error(&self, node: &str) {
+        let mut nodes = self.nodes.lock().unwrap();
+        nodes.entry(node.to_owned()).or_default().errors += 1;
+    }
+}
+
+/// 输出到 Prometheus 的指标
+///
+/// - `langchain_node_duration_seconds{node}`：耗时直方图
+/// - `langchain_node_errors_total{node}`：失败次数
+#[cfg(feature = "prometheus")]
+#[derive(Debug, Clone)]
+pub struct PrometheusMetrics {
+    durations: prometheus::HistogramVec,
+    errors: prometheus::IntCounterVec,
+}
+
+#[cfg(feature = "prometheus")]
+impl PrometheusMetrics {
+    /// 创建指标并注册到 `registry`
+    pub fn new(registry: &prometheus::Registry) -> prometheus::Result<Self> {
+        let durations = prometheus::HistogramVec::new(
+            prometheus::HistogramOpts::new(
+                "langchain_node_duration_seconds",
+                "Duration of agent runs, model calls and tool calls",
+            )
+            .buckets(vec![
+                0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0,
+            ]),
+            &["node"],
+        )?;
+        let errors = prometheus::IntCounterVec::new(
+            prometheus::Opts::new(
+                "langchain_node_errors_total",
+                "Failed agent runs, model calls and tool calls",
+            ),
+            &["node"],
+        )?;
+        registry.register(Box::new(durations.clone()))?;
+        registry.register(Box::new(errors.clone()))?;
+        Ok(Self { durations, errors })
+    }
+}
+
+#[cfg(feature = "prometheus")]
+impl MetricsCollector for PrometheusMetrics {
+    fn record_duration(&self, node: &str, duration: Duration) {
+        self.durations
+            .with_label_values(&[node])
+            .observe(duration.as_secs_f64());
+    }
+
+    fn record_error(&self, node: &str) {
+        self.errors.with_label_values(&[node]).inc();
+    }
+}
+
+/// 单次运行的计时
+struct RunTimer {
+    started_at: Instant,
+    model_started_at: Option<Instant>,
+}
+
+impl RunTimer {
+    fn new() -> Self {
+        Self {
+            started_at: Instant::now(),
+            model_started_at: None,
+        }
+    }
+}
+
+/// 性能指标中间件
+///
+/// - Agent 运行：`before_agent` 到 `after_agent`
+/// - 模型调用：本中间件的 `before_model` 到 `after_model`。`before_model` 按注册顺序执行、
+///   `after_model` 逆序执行，因此把本中间件注册在最后，计时就只包含模型调用本身
+/// - 工具调用：通过 [`tool_middleware`](Self::tool_middleware) 包裹每次调用，工具返回错误时计入失败
+///
+/// 以错误结束的运行在 `on_run_error` 中计入 Agent 的耗时和失败次数；如果失败时模型调用
+/// 尚未返回（例如模型请求失败或超时），这次调用也计入模型的耗时和失败次数。
+///
+/// ```rust,ignore
+/// let metrics = Arc::new(InMemoryMetrics::new());
+/// let middleware = MetricsMiddleware::new(metrics.clone());
+/// let agent = ReactAgent::builder(model)
+///     .with_tool_middleware(middleware.tool_middleware())
+///     .with_middlewares([middleware.into()])
+///     .build();
+/// agent.invoke(Message::user("hi"), None).await?;
+/// println!("{:?}", metrics.snapshot());
+/// ```
+#[derive(Clone)]
+pub struct MetricsMiddleware {
+    collector: Arc<dyn MetricsCollector>,
+    runs: Arc<RunRegistry<RunTimer>>,
+}
+
+impl std::fmt::Debug for MetricsMiddleware {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MetricsMiddleware").finish_non_exhaustive()
+    }
+}
+
+impl MetricsMiddleware {
+    pub fn new(collector: Arc<dyn MetricsCollector>) -> Self {
+        Self {
+            collector,
+            runs: Arc::default(),
+        }
+    }
+
+    pub fn collector(&self) -> &Arc<dyn MetricsCollector> {
+        &self.collector
+    }
+
+    /// 记录工具调用的包装器，传给 [`ReactAgentBuilder::with_tool_middleware`]
+    ///
+    /// [`ReactAgentBuilder::with_tool_middleware`]: crate::ReactAgentBuilder::with_tool_middleware
+    pub fn tool_middleware<E>(&self) -> Arc<ToolMiddleware<E>>
+    where
+        E: Display + Send + 'static,
+    {
+        let collector = self.collector.clone();
+        Arc::new(Box::new(
+            move |_: &MessagesState,
+                  _: &NodeContext,
+                  name: &str,
+                  args: Value,
+                  handler: ToolHandler<E>|
+                  -> ToolFuture<E> {
+                let node = tool_node(name);
+                let collector = collector.clone();
+                Box::pin(async move {
+                    let started = Instant::now();
+                    let result = handler(args).await;
+                    collector.record_duration(&node, started.elapsed());
+                    if result.is_err() {
+                        collector.record_error(&node);
+                    }
+                    result
+                })
+            },
+        ))
+    }
+
+    fn before_agent(&self, _: &MessagesState, context: &NodeContext) {
+        self.runs.start(context.config, RunTimer::new());
+    }
+
+    fn before_model(&self, _: &MessagesState, context: &NodeContext) {
+        // 从中断处恢复的运行不会经过 before_agent，此时只统计模型调用
+        self.runs.with(context.config, RunTimer::new, |run| {
+            run.model_started_at = Some(Instant::now());
+        });
+    }
+
+    fn after_model(&self, _: &MessagesState, context: &NodeContext) {
+        let started = self
+            .runs
+            .update(RunKey::of(context.config), |run| {
+                run.model_started_at.take()
+            })
+            .flatten();
+        if let Some(started) = started {
+            self.collector
+                .record_duration(MODEL_NODE, started.elapsed());
+        }
+    }
+
+    fn after_agent(&self, _: &MessagesState, context: &NodeContext) {
+        if let Some(run) = self.runs.finish(context.config) {
+            self.collector
+                .record_duration(AGENT_NODE, run.started_at.elapsed());
+        }
+    }
+
+    fn on_run_error(&self, config: &Configuration, _: &AgentError) {
+        let Some(run) = self.runs.finish(config) else {
+            return;
+        };
+        if let Some(started) = run.model_started_at {
+            self.collector
+                .record_duration(MODEL_NODE, started.elapsed());
+            self.collector.record_error(MODEL_NODE);
+        }
+        self.collector
+            .record_duration(AGENT_NODE, run.started_at.elapsed());
+        self.collector.record_error(AGENT_NODE);
+    }
+}
+
+/// 把只记录指标、不修改状态的方法包装为钩子
+fn hook(
+    metrics: &MetricsMiddleware,
+    record: fn(&MetricsMiddleware, &MessagesState, &NodeContext),
+) -> AgentHook<MessagesState> {
+    let metrics = metrics.clone();
+    AgentHook {
+        handler: Arc::new(move |state: &MessagesState, context: &NodeContext| {
+            record(&metrics, state, context);
+            Box::pin(async { Ok::<_, AgentError>(MessagesState::default()) })
+        }),
+        target: None,
+        branches: vec![],
+    }
+}
+
+impl From<MetricsMiddleware> for AgentMiddleware<MessagesState> {
+    fn from(metrics: MetricsMiddleware) -> Self {
+        let label = define_middleware_label!(MetricsLabel);
+        AgentMiddleware::from_label(label)
+            .with_before_agent(hook(&metrics, MetricsMiddleware::before_agent))
+            .with_before_model(hook(&metrics, MetricsMiddleware::before_model))
+            .with_after_model(hook(&metrics, MetricsMiddleware::after_model))
+            .with_after_agent(hook(&metrics, MetricsMiddleware::after_agent))
+            .with_on_run_error(Arc::new(
+                move |config: &Configuration, error: &AgentError| {
+                    metrics.on_run_error(config, error);
+                },
+            ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ReactAgent, testing::MockModel};
+    use langchain_core::{
+        error::{ModelError, ToolError},
+        message::Message,
+        tool,
+    };
+
+    /// 第一次并行调用两个工具，之后直接回答；每次调用耗时 20ms
+    fn tools_then_answer() -> MockModel {
+        MockModel::from_fn(|request| {
+            let last = request.messages.last().unwrap();
+            Ok(if matches!(last.as_ref(), Message::Tool { .. }) {
+                Message::assistant("done")
+            } else {
+                MockModel::tool_calls([
+                    ("slow", serde_json::json!({})),
+                    ("broken", serde_json::json!({})),
+                ])
+            })
+        })
+        .with_latency(Duration::from_millis(20))
+    }
+
+    #[tool(description = "slow")]
+    async fn slow() -> Result<String, ToolError> {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        Ok("ok".to_owned())
+    }
+
+    #[tool(description = "broken")]
+    async fn broken() -> Result<String, ToolError> {
+        Err(ToolError::ExecutionFailed("boom".to_owned()))
+    }
+
+    #[tokio::test]
+    async fn records_model_tool_and_agent_metrics() {
+        let metrics = Arc::new(InMemoryMetrics::new());
+        let middleware = MetricsMiddleware::new(metrics.clone());
+        let agent = ReactAgent::builder(tools_then_answer())
+            .with_tools([slow_tool(), broken_tool()])
+            .with_tool_middleware(middleware.tool_middleware())
+            .with_middlewares([middleware.into()])
+            .build();
+
+        agent.invoke(Message::user("go"), None).await.unwrap();
+
+        let snapshot = metrics.snapshot();
+        let model = snapshot[MODEL_NODE];
+        assert_eq!(model.count, 2);
+        assert!(model.min >= Duration::from_millis(20));
+        assert!(model.mean() >= model.min && model.mean() <= model.max);
+
+        let slow = snapshot[&tool_node("slow")];
+        assert_eq!((slow.count, slow.errors), (1, 0));
+        assert!(slow.total >= Duration::from_millis(10));
+        let broken = metrics.get(&tool_node("broken")).unwrap();
+        assert_eq!((broken.count, broken.errors), (1, 1));
+
+        let run = snapshot[AGENT_NODE];
+        assert_eq!(run.count, 1);
+        assert!(run.total >= model.total);
+    }
+
+    #[tokio::test]
+    async fn failed_model_calls_and_runs_are_counted() {
+        let metrics = Arc::new(InMemoryMetrics::new());
+        let middleware = MetricsMiddleware::new(metrics.clone());
+        let model = MockModel::new([Message::assistant("hi")]);
+        model.push_error(ModelError::ResponseError("overloaded".to_owned()));
+        let agent = ReactAgent::builder(model)
+            .with_middlewares([middleware.into()])
+            .build();
+
+        agent.invoke(Message::user("hi"), None).await.unwrap();
+        agent
+            .invoke(Message::user("again"), None)
+            .await
+            .unwrap_err();
+
+        let model = metrics.get(MODEL_NODE).unwrap();
+        assert_eq!((model.count, model.errors), (2, 1));
+        let run = metrics.get(AGENT_NODE).unwrap();
+        assert_eq!((run.count, run.errors), (2, 1));
+    }
+
+    #[test]
+    fn in_memory_collector_aggregates_and_resets() {
+        let metrics = InMemoryMetrics::new();
+        metrics.record_duration("model", Duration::from_millis(30));
+        metrics.record_duration("model", Duration::from_millis(10));
+        metrics.record_error("model");
+
+        assert_eq!(
+            metrics.get("model"),
+            Some(NodeMetrics {
+                count: 2,
+                errors: 1,
+                total: Duration::from_millis(40),
+                min: Duration::from_millis(10),
+                max: Duration::from_millis(30),
+            })
+        );
+        assert_eq!(
+            metrics.get("model").unwrap().mean(),
+            Duration::from_millis(20)
+        );
+
+        metrics.reset();
+        assert!(metrics.snapshot().is_empty());
+    }
+
+    #[cfg(feature = "prometheus")]
+    #[test]
+    fn prometheus_collector_exports_metrics() {
+        let registry = prometheus::Registry::new();
+        let metrics = PrometheusMetrics::new(&registry).unwrap();
+        metrics.record_duration("model", Duration::from_millis(30));
+        metrics.record_error("tool:search");
+
+        let families = registry.gather();
+        let names: Vec<_> = families.iter().map(|f| f.name()).collect();
+        assert!(names.contains(&"langchain_node_duration_seconds"));
+        assert!(names.contains(&"langchain_node_errors_total"));
+    }
+}
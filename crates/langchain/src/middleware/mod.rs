@@ -0,0 +1,35 @@
+//! 内置的 Agent 中间件
+//!
+//! 每个中间件都可以通过 `From` 转换为 [`AgentMiddleware`](crate::node::middleware::AgentMiddleware)，
+//! 直接传给 [`ReactAgentBuilder::with_middlewares`](crate::ReactAgentBuilder::with_middlewares)。
+//! 同一种中间件在一个 Agent 中只能注册一次，因为它们的图节点标签是固定的。
+
+pub mod cache;
+pub mod content_filter;
+pub mod cost;
+pub mod logging;
+pub mod memory;
+pub mod metrics;
+pub mod pii;
+pub mod rate_limit;
+mod runs;
+pub mod token_budget;
+pub mod trace;
+
+pub use cache::{CacheKeyFn, CacheMiddleware};
+pub use content_filter::{ContentFilterMiddleware, FilterRule};
+pub use cost::{CostTracker, CostTrackingMiddleware, ModelCost};
+pub use logging::LoggingMiddleware;
+pub use memory::{MemoryMiddleware, MemoryRecord};
+#[cfg(feature = "prometheus")]
+pub use metrics::PrometheusMetrics;
+pub use metrics::{InMemoryMetrics, MetricsCollector, MetricsMiddleware, NodeMetrics};
+pub use pii::{PiiPattern, PiiRedactionMiddleware, PiiVault, Redaction, RedactionAudit};
+pub use rate_limit::{
+    RateLimitMiddleware, RateLimitStats, RateLimiter, TokenEstimator, estimate_tokens,
+};
+pub use token_budget::{BudgetAction, TokenBudgetMiddleware};
+pub use trace::{
+    HttpTraceSink, HttpTraceSinkConfig, ModelCallTrace, RunTrace, ToolCallTrace, TraceAuth,
+    TraceFormat, TraceMiddleware, TraceSink,
+};
@@ -0,0 +1,513 @@
+//! PII 脱敏中间件
+//!
+//! 在消息发送给第三方模型之前，把邮箱、电话号码、银行卡号等敏感信息替换为
+//! `[EMAIL_1]` 这样的占位符，并记录每一次脱敏以便审计。
+
+use std::{collections::HashMap, sync::Arc};
+
+use langchain_core::state::MessagesState;
+use langgraph::{checkpoint::Configuration, node::NodeContext};
+use regex::Regex;
+
+use super::runs::RunRegistry;
+use crate::{
+    AgentError, define_middleware_label,
+    node::middleware::{AgentHook, AgentMiddleware},
+};
+
+/// 一类需要脱敏的信息
+#[derive(Debug, Clone)]
+pub struct PiiPattern {
+    /// 类别名称，用于生成占位符，例如 `email` 生成 `[EMAIL_1]`
+    pub kind: String,
+    pub regex: Regex,
+}
+
+impl PiiPattern {
+    pub fn new(kind: impl Into<String>, pattern: &str) -> Result<Self, regex::Error> {
+        Ok(Self {
+            kind: kind.into(),
+            regex: Regex::new(pattern)?,
+        })
+    }
+
+    pub fn email() -> Self {
+        Self::new("email", r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}").unwrap()
+    }
+
+    /// 13 到 19 位数字，允许以空格或 `-` 分组
+    pub fn credit_card() -> Self {
+        Self::new("credit_card", r"\b(?:\d[ -]?){12,18}\d\b").unwrap()
+    }
+
+    /// 常见的 3-3-4 / 3-4-4 格式电话号码，可带国际区号
+    pub fn phone() -> Self {
+        Self::new(
+            "phone",
+            r"(?:\+\d{1,3}[ -]?)?(?:\(\d{3}\)|\b\d{3})[ -]?\d{3,4}[ -]?\d{4}\b",
+        )
+        .unwrap()
+    }
+}
+
+/// 一条脱敏记录
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redaction {
+    pub kind: String,
+    pub placeholder: String,
+    pub original: String,
+}
+
+/// 一次运行中原文与占位符的对应关系
+///
+/// 原文只保存在内存中，运行结束时随之丢弃，不会进入 checkpoint，也不会带到其他运行。
+#[derive(Debug, Default)]
+pub struct PiiVault {
+    /// 原文 -> 占位符，同一个值在一次运行中使用相同的占位符
+    placeholders: HashMap<String, String>,
+    counters: HashMap<String, usize>,
+    redactions: Vec<Redaction>,
+}
+
+impl PiiVault {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 本次运行的脱敏记录，每个不同的原文只记录一次
+    pub fn redactions(&self) -> &[Redaction] {
+        &self.redactions
+    }
+
+    /// 从已有的消息中找出占位符并跳过它们的编号：同一线程的历史在之前的运行中已经脱敏，
+    /// 新的原文不能复用这些占位符
+    fn skip_placeholders_in(&mut self, state: &MessagesState, patterns: &[PiiPattern]) {
+        for pattern in patterns {
+            let prefix = format!("[{}_", pattern.kind.to_uppercase());
+            let used = state
+                .messages
+                .iter()
+                .flat_map(|message| {
+                    let text = message.content();
+                    text.match_indices(&prefix)
+                        .filter_map(|(start, _)| {
+                            let rest = &text[start + prefix.len()..];
+                            rest[..rest.find(']')?].parse::<usize>().ok()
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .max();
+            if let Some(used) = used {
+                let counter = self.counters.entry(pattern.kind.clone()).or_default();
+                *counter = (*counter).max(used);
+            }
+        }
+    }
+
+    fn placeholder_for(&mut self, kind: &str, original: &str) -> String {
+        if let Some(placeholder) = self.placeholders.get(original) {
+            return placeholder.clone();
+        }
+        let counter = self.counters.entry(kind.to_owned()).or_default();
+        *counter += 1;
+        let placeholder = format!("[{}_{}]", kind.to_uppercase(), counter);
+        tracing::debug!("redacted {kind} as {placeholder}");
+        self.placeholders
+            .insert(original.to_owned(), placeholder.clone());
+        self.redactions.push(Redaction {
+            kind: kind.to_owned(),
+            placeholder: placeholder.clone(),
+            original: original.to_owned(),
+        });
+        placeholder
+    }
+}
+
+/// 运行结束时接收该次运行的脱敏记录，见 [`PiiRedactionMiddleware::with_audit`]
+pub type RedactionAudit = Arc<dyn Fn(&Configuration, &[Redaction]) + Send + Sync>;
+
+/// PII 脱敏中间件
+///
+/// - `before_model`：扫描所有消息（包括工具调用参数中的字符串），把匹配到的内容替换为
+///   占位符后写回状态，因此模型和 checkpoint 都只会看到占位符；
+/// - `after_model`：开启 [`restore_placeholders`](Self::restore_placeholders) 后，
+///   把模型回复及其工具调用参数中的占位符还原为原文，工具拿到的仍是真实数据。
+///
+/// 每次运行使用独立的 [`PiiVault`]，运行结束（包括以错误结束）时丢弃，原文不会在并发的运行
+/// 或不同的会话之间共享。同一线程的后续运行只能看到历史中的占位符，无法再还原它们。
+/// 模式按添加顺序依次应用，默认顺序为邮箱、银行卡号、电话号码。
+///
+/// ```rust,ignore
+/// let pii = PiiRedactionMiddleware::new()
+///     .restore_placeholders(true)
+///     .with_audit(Arc::new(|config, redactions| {
+///         tracing::info!(thread_id = ?config.thread_id, "redacted {} values", redactions.len());
+///     }));
+/// let agent = ReactAgent::builder(model)
+///     .with_middlewares([pii.into()])
+///     .build();
+/// agent.invoke(Message::user("mail me at alice@example.com"), None).await?;
+/// ```
+#[derive(Clone)]
+pub struct PiiRedactionMiddleware {
+    patterns: Arc<Vec<PiiPattern>>,
+    restore: bool,
+    audit: Option<RedactionAudit>,
+    vaults: Arc<RunRegistry<PiiVault>>,
+}
+
+impl std::fmt::Debug for PiiRedactionMiddleware {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PiiRedactionMiddleware")
+            .field("patterns", &self.patterns)
+            .field("restore", &self.restore)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for PiiRedactionMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PiiRedactionMiddleware {
+    /// 使用内置的邮箱、银行卡号、电话号码模式
+    pub fn new() -> Self {
+        Self::with_patterns(vec![
+            PiiPattern::email(),
+            PiiPattern::credit_card(),
+            PiiPattern::phone(),
+        ])
+    }
+
+    /// 只使用给定的模式
+    pub fn with_patterns(patterns: Vec<PiiPattern>) -> Self {
+        Self {
+            patterns: Arc::new(patterns),
+            restore: false,
+            audit: None,
+            vaults: Arc::default(),
+        }
+    }
+
+    /// 追加一个模式
+    pub fn with_pattern(mut self, pattern: PiiPattern) -> Self {
+        Arc::make_mut(&mut self.patterns).push(pattern);
+        self
+    }
+
+    /// 是否在模型回复中还原占位符，默认不还原
+    pub fn restore_placeholders(mut self, restore: bool) -> Self {
+        self.restore = restore;
+        self
+    }
+
+    /// 每次运行结束时把该次运行的脱敏记录交给 `audit`，没有脱敏时不调用
+    pub fn with_audit(mut self, audit: RedactionAudit) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// 脱敏一段文本，新的原文记入 `vault`，没有匹配时返回 `None`
+    pub fn redact(&self, vault: &mut PiiVault, text: &str) -> Option<String> {
+        let mut result: Option<String> = None;
+
+        for pattern in self.patterns.iter() {
+            let current = result.as_deref().unwrap_or(text);
+            if !pattern.regex.is_match(current) {
+                continue;
+            }
+            let replaced = pattern
+                .regex
+                .replace_all(current, |caps: &regex::Captures<'_>| {
+                    vault.placeholder_for(&pattern.kind, &caps[0])
+                })
+                .into_owned();
+            result = Some(replaced);
+        }
+
+        result
+    }
+
+    /// 把文本中 `vault` 记录过的占位符还原为原文，没有占位符时返回 `None`
+    pub fn restore(&self, vault: &PiiVault, text: &str) -> Option<String> {
+        let mut result: Option<String> = None;
+        for redaction in &vault.redactions {
+            let current = result.as_deref().unwrap_or(text);
+            if current.contains(&redaction.placeholder) {
+                result = Some(current.replace(&redaction.placeholder, &redaction.original));
+            }
+        }
+        result
+    }
+
+    fn new_vault(&self, state: &MessagesState) -> PiiVault {
+        let mut vault = PiiVault::new();
+        vault.skip_placeholders_in(state, &self.patterns);
+        vault
+    }
+
+    fn redact_state(&self, state: &MessagesState, context: &NodeContext) -> MessagesState {
+        self.vaults.with(
+            context.config,
+            || self.new_vault(state),
+            |vault| {
+                let mut update = MessagesState::default();
+                for (index, message) in state.messages.iter().enumerate() {
+                    if let Some(redacted) = message.map_text(|text| self.redact(vault, text)) {
+                        update.replace_message(index, redacted);
+                    }
+                }
+                update
+            },
+        )
+    }
+
+    fn restore_last_message(&self, state: &MessagesState, context: &NodeContext) -> MessagesState {
+        let mut update = MessagesState::default();
+        if let Some(message) = state.last_message() {
+            let restored = self.vaults.with(
+                context.config,
+                || self.new_vault(state),
+                |vault| message.map_text(|text| self.restore(vault, text)),
+            );
+            if let Some(restored) = restored {
+                update.replace_message(state.messages.len() - 1, restored);
+            }
+        }
+        update
+    }
+
+    /// 运行结束：交出脱敏记录并丢弃原文
+    fn finish(&self, config: &Configuration) {
+        if let Some(vault) = self.vaults.finish(config)
+            && let Some(audit) = &self.audit
+            && !vault.redactions.is_empty()
+        {
+            audit(config, &vault.redactions);
+        }
+    }
+}
+
+impl From<PiiRedactionMiddleware> for AgentMiddleware<MessagesState> {
+    fn from(pii: PiiRedactionMiddleware) -> Self {
+        let label = define_middleware_label!(PiiRedactionLabel);
+
+        let redactor = pii.clone();
+        let finisher = pii.clone();
+        let mut middleware = AgentMiddleware::from_label(label)
+            .with_before_model(AgentHook {
+                handler: Arc::new(move |state: &MessagesState, context: &NodeContext| {
+                    let update = redactor.redact_state(state, context);
+                    Box::pin(async move { Ok(update) })
+                }),
+                target: None,
+                branches: vec![],
+            })
+            .with_after_agent(AgentHook {
+                handler: Arc::new(move |_: &MessagesState, context: &NodeContext| {
+                    finisher.finish(context.config);
+                    Box::pin(async { Ok::<_, AgentError>(MessagesState::default()) })
+                }),
+                target: None,
+                branches: vec![],
+            });
+
+        if pii.restore {
+            let restorer = pii.clone();
+            middleware = middleware.with_after_model(AgentHook {
+                handler: Arc::new(move |state: &MessagesState, context: &NodeContext| {
+                    let update = restorer.restore_last_message(state, context);
+                    Box::pin(async move { Ok(update) })
+                }),
+                target: None,
+                branches: vec![],
+            });
+        }
+        middleware.with_on_run_error(Arc::new(move |config: &Configuration, _: &AgentError| {
+            pii.finish(config);
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    use crate::{ReactAgent, testing::MockModel};
+    use langchain_core::message::{FunctionCall, Message, ToolCall};
+
+    #[test]
+    fn redacts_builtin_patterns_with_stable_placeholders() {
+        let pii = PiiRedactionMiddleware::new();
+        let mut vault = PiiVault::new();
+
+        let text = "mail alice@example.com or bob@example.org, card 4111 1111 1111 1111, \
+                    call 555-123-4567 before 2024-01-01";
+        let redacted = pii.redact(&mut vault, text).unwrap();
+
+        assert_eq!(
+            redacted,
+            "mail [EMAIL_1] or [EMAIL_2], card [CREDIT_CARD_1], \
+             call [PHONE_1] before 2024-01-01"
+        );
+        // 同一个值复用占位符
+        assert_eq!(
+            pii.redact(&mut vault, "again alice@example.com").unwrap(),
+            "again [EMAIL_1]"
+        );
+        assert_eq!(vault.redactions().len(), 4);
+        assert_eq!(pii.restore(&vault, &redacted).unwrap(), text);
+        assert!(pii.redact(&mut vault, "nothing to see").is_none());
+    }
+
+    #[test]
+    fn tool_call_arguments_stay_valid_json() {
+        let pii = PiiRedactionMiddleware::with_patterns(vec![
+            PiiPattern::new("quote", "\"").unwrap(),
+            PiiPattern::email(),
+        ]);
+        let message = Message::Assistant {
+            content: String::new(),
+            reasoning_content: None,
+            tool_calls: Some(vec![ToolCall {
+                id: "call_1".to_owned(),
+                type_name: "function".to_owned(),
+                function: FunctionCall {
+                    name: "send_mail".to_owned(),
+                    // 参数为 JSON 文本
+                    arguments: serde_json::Value::String(
+                        r#"{"to":"alice@example.com","cc":["bob@example.org"]}"#.to_owned(),
+                    ),
+                },
+            }]),
+            name: None,
+            metadata: Default::default(),
+        };
+
+        let mut vault = PiiVault::new();
+        let redacted = message
+            .map_text(|text| pii.redact(&mut vault, text))
+            .unwrap();
+        let Message::Assistant {
+            tool_calls: Some(calls),
+            ..
+        } = &redacted
+        else {
+            unreachable!()
+        };
+        assert_eq!(
+            calls[0].arguments().unwrap(),
+            serde_json::json!({ "to": "[EMAIL_2]", "cc": ["[EMAIL_1]"] })
+        );
+    }
+
+    /// 回显模型看到的最后一条用户消息
+    fn echo_model() -> Arc<MockModel> {
+        Arc::new(MockModel::from_fn(|request| {
+            let last = request.messages.last().unwrap().content();
+            Ok(Message::assistant(format!("echo: {last}")))
+        }))
+    }
+
+    /// 模型每次调用看到的最后一条消息
+    fn seen(model: &MockModel) -> Vec<String> {
+        model
+            .requests()
+            .iter()
+            .map(|request| request.messages.last().unwrap().content().to_owned())
+            .collect()
+    }
+
+    type Audited = Arc<Mutex<Vec<(Option<String>, Vec<Redaction>)>>>;
+
+    fn audited(pii: PiiRedactionMiddleware) -> (PiiRedactionMiddleware, Audited) {
+        let audited: Audited = Arc::default();
+        let sink = audited.clone();
+        let pii = pii.with_audit(Arc::new(move |config, redactions| {
+            sink.lock()
+                .unwrap()
+                .push((config.thread_id.clone(), redactions.to_vec()));
+        }));
+        (pii, audited)
+    }
+
+    #[tokio::test]
+    async fn model_sees_placeholders_and_reply_is_restored() {
+        let model = echo_model();
+        let (pii, audited) = audited(PiiRedactionMiddleware::new().restore_placeholders(true));
+        let agent = ReactAgent::builder(model.clone())
+            .with_middlewares([pii.clone().into()])
+            .build();
+
+        let state = agent
+            .invoke(Message::user("contact alice@example.com"), None)
+            .await
+            .unwrap();
+
+        assert_eq!(seen(&model), ["contact [EMAIL_1]"]);
+        // 状态中的用户消息保持脱敏，模型回复已还原
+        assert_eq!(state.messages[0].content(), "contact [EMAIL_1]");
+        assert_eq!(
+            state.last_message().unwrap().content(),
+            "echo: contact alice@example.com"
+        );
+        assert_eq!(
+            *audited.lock().unwrap(),
+            vec![(
+                None,
+                vec![Redaction {
+                    kind: "email".to_owned(),
+                    placeholder: "[EMAIL_1]".to_owned(),
+                    original: "alice@example.com".to_owned(),
+                }]
+            )]
+        );
+        // 运行结束后原文已丢弃
+        assert_eq!(pii.vaults.len(), 0);
+    }
+
+    #[tokio::test]
+    async fn runs_do_not_share_originals() {
+        let model = echo_model();
+        let (pii, audited) = audited(PiiRedactionMiddleware::new().restore_placeholders(true));
+        let agent = ReactAgent::builder(model.clone())
+            .with_middlewares([pii.clone().into()])
+            .with_checkpointer(Arc::new(langgraph::checkpoint::MemorySaver::new()))
+            .build();
+
+        agent
+            .invoke(Message::user("contact alice@example.com"), Some("a"))
+            .await
+            .unwrap();
+        // 另一个会话不能通过占位符取回第一个会话的原文
+        let other = agent
+            .invoke(Message::user("who is [EMAIL_1]?"), Some("b"))
+            .await
+            .unwrap();
+        assert_eq!(
+            other.last_message().unwrap().content(),
+            "echo: who is [EMAIL_1]?"
+        );
+
+        // 同一线程的新运行跳过历史中已用过的编号；上一轮已还原的回复在本次运行中重新脱敏为 [EMAIL_2]
+        let resumed = agent
+            .invoke(Message::user("and carol@example.com"), Some("a"))
+            .await
+            .unwrap();
+        assert_eq!(resumed.messages[1].content(), "echo: contact [EMAIL_2]");
+        assert_eq!(seen(&model).last().unwrap(), "and [EMAIL_3]");
+        assert_eq!(
+            resumed.last_message().unwrap().content(),
+            "echo: and carol@example.com"
+        );
+
+        let audited = audited.lock().unwrap();
+        let threads: Vec<_> = audited.iter().map(|(thread, _)| thread.clone()).collect();
+        assert_eq!(threads, vec![Some("a".to_owned()), Some("a".to_owned())]);
+        assert_eq!(audited[1].1[1].original, "carol@example.com");
+        assert_eq!(pii.vaults.len(), 0);
+    }
+}
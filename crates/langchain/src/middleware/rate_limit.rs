@@ -0,0 +1,327 @@
+//! 限流中间件
+//!
+//! 使用令牌桶限制每分钟的模型请求数和 token 数。[`RateLimiter`] 通过 `Arc` 在多个 Agent 之间
+//! 共享，即可让它们共同遵守同一份配额。
+
+use std::{
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use langchain_core::{
+    message::Message,
+    state::MessagesState,
+    tokenizer::{HeuristicTokenizer, Tokenizer},
+};
+use langgraph::node::NodeContext;
+use tokio::time::Instant;
+
+use crate::{
+    AgentError, define_middleware_label,
+    node::middleware::{AgentHook, AgentMiddleware},
+};
+
+/// 估算一次模型调用消耗的 token 数
+pub type TokenEstimator = dyn Fn(&[Arc<Message>]) -> u32 + Send + Sync;
+
+/// 令牌桶：容量为每分钟的配额，按秒匀速补充
+#[derive(Debug)]
+struct Bucket {
+    capacity: f64,
+    available: f64,
+    refill_per_sec: f64,
+}
+
+impl Bucket {
+    fn per_minute(limit: u32) -> Self {
+        let capacity = f64::from(limit.max(1));
+        Self {
+            capacity,
+            available: capacity,
+            refill_per_sec: capacity / 60.0,
+        }
+    }
+
+    fn refill(&mut self, elapsed: Duration) {
+        self.available =
+            (self.available + elapsed.as_secs_f64() * self.refill_per_sec).min(self.capacity);
+    }
+
+    /// 取出 `amount` 还需等待的时间；超过容量的请求按容量计算，否则永远等不到
+    fn wait_for(&self, amount: f64) -> Duration {
+        let missing = amount.min(self.capacity) - self.available;
+        if missing <= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(missing / self.refill_per_sec)
+        }
+    }
+
+    fn take(&mut self, amount: f64) {
+        self.available -= amount.min(self.capacity);
+    }
+}
+
+#[derive(Debug)]
+struct Buckets {
+    requests: Option<Bucket>,
+    tokens: Option<Bucket>,
+    updated_at: Instant,
+}
+
+/// 限流器的累计统计
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RateLimitStats {
+    /// 放行的调用次数
+    pub acquired: u64,
+    /// 其中需要等待的次数
+    pub blocked: u64,
+    /// 累计等待时间
+    pub waited: Duration,
+}
+
+/// 按分钟配额限流的令牌桶
+///
+/// 未设置的维度不做限制。桶的初始状态是满的，因此启动时允许一次性用完整分钟的配额。
+///
+/// ```rust,ignore
+/// let limiter = Arc::new(
+///     RateLimiter::new()
+///         .with_requests_per_minute(60)
+///         .with_tokens_per_minute(90_000),
+/// );
+/// let a = ReactAgent::builder(model_a)
+///     .with_middlewares([RateLimitMiddleware::new(limiter.clone()).into()])
+///     .build();
+/// let b = ReactAgent::builder(model_b)
+///     .with_middlewares([RateLimitMiddleware::new(limiter).into()])
+///     .build();
+/// ```
+#[derive(Debug)]
+pub struct RateLimiter {
+    buckets: Mutex<Buckets>,
+    acquired: AtomicU64,
+    blocked: AtomicU64,
+    waited_us: AtomicU64,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RateLimiter {
+    /// 不做任何限制的限流器，通过 `with_*` 设置配额
+    pub fn new() -> Self {
+        Self {
+            buckets: Mutex::new(Buckets {
+                requests: None,
+                tokens: None,
+                updated_at: Instant::now(),
+            }),
+            acquired: AtomicU64::new(0),
+            blocked: AtomicU64::new(0),
+            waited_us: AtomicU64::new(0),
+        }
+    }
+
+    pub fn with_requests_per_minute(self, limit: u32) -> Self {
+        self.buckets.lock().unwrap().requests = Some(Bucket::per_minute(limit));
+        self
+    }
+
+    pub fn with_tokens_per_minute(self, limit: u32) -> Self {
+        self.buckets.lock().unwrap().tokens = Some(Bucket::per_minute(limit));
+        self
+    }
+
+    /// 等待直到有足够的配额发起一次消耗 `tokens` 个 token 的请求
+    pub async fn acquire(&self, tokens: u32) {
+        let started = Instant::now();
+        let mut blocked = false;
+        loop {
+            let wait = {
+                let mut buckets = self.buckets.lock().unwrap();
+                let now = Instant::now();
+                let elapsed = now.duration_since(buckets.updated_at);
+                buckets.updated_at = now;
+
+                let tokens = f64::from(tokens);
+                let mut wait = Duration::ZERO;
+                if let Some(bucket) = &mut buckets.requests {
+                    bucket.refill(elapsed);
+                    wait = wait.max(bucket.wait_for(1.0));
+                }
+                if let Some(bucket) = &mut buckets.tokens {
+                    bucket.refill(elapsed);
+                    wait = wait.max(bucket.wait_for(tokens));
+                }
+                if wait.is_zero() {
+                    if let Some(bucket) = &mut buckets.requests {
+                        bucket.take(1.0);
+                    }
+                    if let Some(bucket) = &mut buckets.tokens {
+                        bucket.take(tokens);
+                    }
+                }
+                wait
+            };
+            if wait.is_zero() {
+                break;
+            }
+            blocked = true;
+            tokio::time::sleep(wait).await;
+        }
+
+        self.acquired.fetch_add(1, Ordering::Relaxed);
+        if blocked {
+            let waited = u64::try_from(started.elapsed().as_micros()).unwrap_or(u64::MAX);
+            self.blocked.fetch_add(1, Ordering::Relaxed);
+            self.waited_us.fetch_add(waited, Ordering::Relaxed);
+        }
+    }
+
+    pub fn stats(&self) -> RateLimitStats {
+        RateLimitStats {
+            acquired: self.acquired.load(Ordering::Relaxed),
+            blocked: self.blocked.load(Ordering::Relaxed),
+            waited: Duration::from_micros(self.waited_us.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// 使用 [`HeuristicTokenizer`] 粗略估算 token
+pub fn estimate_tokens(messages: &[Arc<Message>]) -> u32 {
+    u32::try_from(HeuristicTokenizer.count_messages(messages)).unwrap_or(u32::MAX)
+}
+
+/// 限流中间件
+///
+/// 在 `before_model` 中等待 [`RateLimiter`] 放行。实际用量要在调用之后才知道，
+/// 所以 token 配额按发送给模型的消息估算，默认使用 [`estimate_tokens`]。
+#[derive(Clone)]
+pub struct RateLimitMiddleware {
+    limiter: Arc<RateLimiter>,
+    estimator: Arc<TokenEstimator>,
+}
+
+impl std::fmt::Debug for RateLimitMiddleware {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RateLimitMiddleware")
+            .field("limiter", &self.limiter)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RateLimitMiddleware {
+    pub fn new(limiter: Arc<RateLimiter>) -> Self {
+        Self {
+            limiter,
+            estimator: Arc::new(estimate_tokens),
+        }
+    }
+
+    /// 替换默认的 token 估算方式，例如使用模型的 [`ChatModel::tokenizer`]
+    ///
+    /// [`ChatModel::tokenizer`]: langchain_core::state::ChatModel::tokenizer
+    pub fn with_token_estimator<F>(mut self, estimator: F) -> Self
+    where
+        F: Fn(&[Arc<Message>]) -> u32 + Send + Sync + 'static,
+    {
+        self.estimator = Arc::new(estimator);
+        self
+    }
+
+    pub fn limiter(&self) -> &Arc<RateLimiter> {
+        &self.limiter
+    }
+}
+
+impl From<RateLimitMiddleware> for AgentMiddleware<MessagesState> {
+    fn from(rate_limit: RateLimitMiddleware) -> Self {
+        let label = define_middleware_label!(RateLimitLabel);
+        AgentMiddleware::from_label(label).with_before_model(AgentHook {
+            handler: Arc::new(move |state: &MessagesState, _: &NodeContext| {
+                let messages: Vec<_> = state.messages.iter().cloned().collect();
+                let tokens = (rate_limit.estimator)(&messages);
+                let limiter = rate_limit.limiter.clone();
+                Box::pin(async move {
+                    limiter.acquire(tokens).await;
+                    Ok::<_, AgentError>(MessagesState::default())
+                })
+            }),
+            target: None,
+            branches: vec![],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ReactAgent, testing::MockModel};
+
+    #[tokio::test(start_paused = true)]
+    async fn requests_beyond_quota_wait_for_refill() {
+        let limiter = Arc::new(RateLimiter::new().with_requests_per_minute(2));
+
+        limiter.acquire(0).await;
+        limiter.acquire(0).await;
+        assert_eq!(limiter.stats().blocked, 0);
+
+        let started = Instant::now();
+        limiter.acquire(0).await;
+        // 每 30 秒补充一个请求
+        assert!(started.elapsed() >= Duration::from_secs(29));
+        let stats = limiter.stats();
+        assert_eq!(stats.acquired, 3);
+        assert_eq!(stats.blocked, 1);
+    }
+
+    #[tokio::test]
+    async fn token_quota_and_oversized_requests() {
+        let limiter = RateLimiter::new().with_tokens_per_minute(100);
+
+        // 超过容量的请求按容量计，不会永远阻塞
+        limiter.acquire(1000).await;
+        assert_eq!(limiter.stats().blocked, 0);
+        let wait = limiter
+            .buckets
+            .lock()
+            .unwrap()
+            .tokens
+            .as_ref()
+            .unwrap()
+            .wait_for(30.0);
+        assert!(wait > Duration::from_secs(17) && wait <= Duration::from_secs(18));
+    }
+
+    #[tokio::test]
+    async fn shared_limiter_counts_calls_from_all_agents() {
+        let limiter = Arc::new(RateLimiter::new().with_requests_per_minute(100));
+        let agents: Vec<_> = (0..2)
+            .map(|_| {
+                ReactAgent::builder(MockModel::from_fn(|_| Ok(Message::assistant("ok"))))
+                    .with_middlewares([RateLimitMiddleware::new(limiter.clone()).into()])
+                    .build()
+            })
+            .collect();
+
+        for agent in &agents {
+            agent.invoke(Message::user("hi"), None).await.unwrap();
+        }
+
+        assert_eq!(limiter.stats().acquired, 2);
+    }
+
+    #[test]
+    fn estimate_rounds_up() {
+        let messages = vec![Arc::new(Message::user("hello"))];
+        // 消息固定开销 4 + "hello" 向上取整 2
+        assert_eq!(estimate_tokens(&messages), 6);
+    }
+}
@@ -0,0 +1,90 @@
+//! 按运行区分的中间件数据
+//!
+//! 中间件实例会被同一个 Agent 的所有并发运行共享。同一次运行的所有节点共享同一个
+//! [`Configuration`]，因此以它的地址区分运行，记录计时、日志 id 等只属于这次运行的数据。
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use langgraph::checkpoint::Configuration;
+
+/// 超过该时间仍未结束的运行视为已被丢弃（例如调用方取消了 future），清理其数据
+const STALE_AFTER: Duration = Duration::from_secs(3600);
+
+/// 一次运行的标识：运行配置的地址
+///
+/// 工具调用等在钩子返回后才结束的异步操作拿不到运行配置的引用，先取出标识，结束时按它更新。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct RunKey(usize);
+
+impl RunKey {
+    pub(crate) fn of(config: &Configuration) -> Self {
+        Self(std::ptr::from_ref(config) as usize)
+    }
+}
+
+/// 每次运行一份数据 `T`
+///
+/// 运行在 `before_agent` 中 [`start`](Self::start)，在 `after_agent` 或 `on_run_error` 中
+/// [`finish`](Self::finish)。从中断处恢复的运行不会经过 `before_agent`，需要时通过
+/// [`with`](Self::with) 补建。
+pub(crate) struct RunRegistry<T> {
+    runs: Mutex<HashMap<RunKey, (Instant, T)>>,
+}
+
+impl<T> Default for RunRegistry<T> {
+    fn default() -> Self {
+        Self {
+            runs: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<T> RunRegistry<T> {
+    /// 开始一次运行，替换该运行已有的数据，并清理过期的运行
+    pub(crate) fn start(&self, config: &Configuration, value: T) {
+        let mut runs = self.runs.lock().unwrap();
+        runs.retain(|_, (started, _)| started.elapsed() < STALE_AFTER);
+        runs.insert(RunKey::of(config), (Instant::now(), value));
+    }
+
+    /// 在当前运行的数据上执行 `f`，没有数据时先用 `init` 补建
+    pub(crate) fn with<R>(
+        &self,
+        config: &Configuration,
+        init: impl FnOnce() -> T,
+        f: impl FnOnce(&mut T) -> R,
+    ) -> R {
+        let mut runs = self.runs.lock().unwrap();
+        let (_, value) = runs
+            .entry(RunKey::of(config))
+            .or_insert_with(|| (Instant::now(), init()));
+        f(value)
+    }
+
+    /// 在运行 `key` 的数据上执行 `f`，没有数据时返回 `None`
+    pub(crate) fn update<R>(&self, key: RunKey, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        self.runs
+            .lock()
+            .unwrap()
+            .get_mut(&key)
+            .map(|(_, value)| f(value))
+    }
+
+    /// 结束运行并取出它的数据
+    pub(crate) fn finish(&self, config: &Configuration) -> Option<T> {
+        self.runs
+            .lock()
+            .unwrap()
+            .remove(&RunKey::of(config))
+            .map(|(_, value)| value)
+    }
+
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.runs.lock().unwrap().len()
+    }
+}
@@ -0,0 +1,214 @@
+//! Token 预算中间件
+//!
+//! 按 [`MessagesState::usage`] 统计本次运行累计的 token 用量，超出预算后阻止后续的模型调用，
+//! 避免 Agent 在循环中无限消耗费用。
+
+use std::sync::Arc;
+
+use langchain_core::{message::Message, state::MessagesState};
+use langgraph::{checkpoint::Configuration, node::NodeContext};
+
+use crate::{
+    AgentError, define_middleware_label,
+    node::middleware::{AgentHook, AgentMiddleware},
+};
+
+/// 超出预算后的处理方式
+#[derive(Debug, Clone, Default)]
+pub enum BudgetAction {
+    /// 直接以 [`AgentError::TokenBudgetExceeded`] 结束运行
+    #[default]
+    Abort,
+    /// 追加一条用户消息要求模型总结并给出最终回答，再允许一次模型调用；
+    /// 之后如果模型仍要继续（例如再次调用工具），按 `Abort` 处理
+    Summarize(String),
+}
+
+/// Token 预算中间件
+///
+/// 在每次模型调用前检查累计用量，因此超出预算的那次调用会正常完成，只阻止下一次。
+/// 用量只统计本次运行：从 checkpoint 恢复的历史不计入。
+///
+/// 以 [`AgentError::TokenBudgetExceeded`] 结束的运行与其他失败的运行一样不会执行
+/// `after_agent`，而是触发所有中间件的 `on_run_error`，例如让
+/// [`TraceMiddleware`](super::TraceMiddleware) 上报这次失败。
+///
+/// ```rust,ignore
+/// let agent = ReactAgent::builder(model)
+///     .with_middlewares([TokenBudgetMiddleware::new(20_000).into()])
+///     .build();
+/// ```
+#[derive(Debug, Clone)]
+pub struct TokenBudgetMiddleware {
+    budget: u32,
+    action: BudgetAction,
+}
+
+impl TokenBudgetMiddleware {
+    /// `budget` 为允许的 `total_tokens` 上限
+    pub fn new(budget: u32) -> Self {
+        Self {
+            budget,
+            action: BudgetAction::Abort,
+        }
+    }
+
+    pub fn with_action(mut self, action: BudgetAction) -> Self {
+        self.action = action;
+        self
+    }
+
+    fn check(&self, state: &MessagesState) -> Result<MessagesState, AgentError> {
+        let used = state.usage.total_tokens;
+        if used <= self.budget {
+            return Ok(MessagesState::default());
+        }
+
+        let exceeded = AgentError::TokenBudgetExceeded {
+            used,
+            budget: self.budget,
+        };
+        match &self.action {
+            BudgetAction::Abort => Err(exceeded),
+            BudgetAction::Summarize(prompt) => {
+                let already_asked = state
+                    .messages
+                    .iter()
+                    .any(|m| matches!(m.as_ref(), Message::User { .. }) && m.content() == prompt);
+                if already_asked {
+                    return Err(exceeded);
+                }
+                tracing::warn!(
+                    "token budget exceeded ({used} > {}), asking model to summarize",
+                    self.budget
+                );
+                let mut update = MessagesState::default();
+                update.push_message_owned(Message::user(prompt.clone()));
+                Ok(update)
+            }
+        }
+    }
+}
+
+impl From<TokenBudgetMiddleware> for AgentMiddleware<MessagesState> {
+    fn from(budget: TokenBudgetMiddleware) -> Self {
+        let label = define_middleware_label!(TokenBudgetLabel);
+        AgentMiddleware::from_label(label)
+            .with_before_model(AgentHook {
+                handler: Arc::new(move |state: &MessagesState, _: &NodeContext| {
+                    let result = budget.check(state);
+                    Box::pin(async move { result })
+                }),
+                target: None,
+                branches: vec![],
+            })
+            .with_on_run_error(Arc::new(|config: &Configuration, error: &AgentError| {
+                if let AgentError::TokenBudgetExceeded { used, budget } = error.root_cause() {
+                    tracing::warn!(
+                        thread_id = config.thread_id.as_deref(),
+                        "run aborted after using {used} of {budget} budgeted tokens"
+                    );
+                }
+            }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ReactAgent, testing::MockModel};
+    use langchain_core::{
+        error::ToolError, message::ToolCall, response::Usage, state::RegisteredTool, tool,
+    };
+
+    const SUMMARIZE: &str = "Budget exhausted, summarize.";
+
+    /// 每次调用消耗 600 token，并一直调用工具，直到被要求总结
+    fn expensive_model() -> Arc<MockModel> {
+        let model = MockModel::from_fn(|request| {
+            Ok(if request.messages.last().unwrap().content() == SUMMARIZE {
+                Message::assistant("summary")
+            } else {
+                Message::assistant_with_tool_calls(
+                    "",
+                    vec![ToolCall::new(
+                        format!("call_{}", request.messages.len()),
+                        "search",
+                        serde_json::json!({}),
+                    )],
+                )
+            })
+        });
+        Arc::new(model.with_usage(Usage {
+            total_tokens: 600,
+            ..Default::default()
+        }))
+    }
+
+    #[tool(description = "search")]
+    async fn search() -> Result<String, ToolError> {
+        Ok("nothing found".to_owned())
+    }
+
+    fn agent(action: BudgetAction) -> (ReactAgent, Arc<MockModel>) {
+        let model = expensive_model();
+        let tools: Vec<RegisteredTool<ToolError>> = vec![search_tool()];
+        let agent = ReactAgent::builder(model.clone())
+            .with_tools(tools)
+            .with_middlewares([TokenBudgetMiddleware::new(1000).with_action(action).into()])
+            .build();
+        (agent, model)
+    }
+
+    #[tokio::test]
+    async fn aborts_before_the_call_after_budget_is_exceeded() {
+        let (agent, model) = agent(BudgetAction::Abort);
+
+        let error = agent.invoke(Message::user("go"), None).await.unwrap_err();
+
+        assert!(matches!(
+            error.root_cause(),
+            AgentError::TokenBudgetExceeded {
+                used: 1200,
+                budget: 1000
+            }
+        ));
+        // 第二次调用越过预算，但仍然完成；第三次被阻止
+        assert_eq!(model.call_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn exceeding_the_budget_fires_on_run_error() {
+        let errors = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = errors.clone();
+        let observer = AgentMiddleware::from_label(define_middleware_label!(ObserverLabel))
+            .with_on_run_error(Arc::new(
+                move |config: &Configuration, error: &AgentError| {
+                    seen.lock()
+                        .unwrap()
+                        .push((config.thread_id.clone(), error.to_string()));
+                },
+            ));
+        let tools: Vec<RegisteredTool<ToolError>> = vec![search_tool()];
+        let agent = ReactAgent::builder(expensive_model())
+            .with_tools(tools)
+            .with_middlewares([TokenBudgetMiddleware::new(1000).into(), observer])
+            .build();
+
+        let result = agent.invoke(Message::user("go"), Some("t1")).await;
+
+        let error = result.unwrap_err().to_string();
+        assert_eq!(*errors.lock().unwrap(), [(Some("t1".to_owned()), error)]);
+    }
+
+    #[tokio::test]
+    async fn summarize_allows_one_final_call() {
+        let (agent, model) = agent(BudgetAction::Summarize(SUMMARIZE.to_owned()));
+
+        let state = agent.invoke(Message::user("go"), None).await.unwrap();
+
+        assert_eq!(state.last_message().unwrap().content(), "summary");
+        assert_eq!(state.usage.total_tokens, 1800);
+        assert_eq!(model.call_count(), 3);
+    }
+}
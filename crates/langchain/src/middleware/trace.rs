@@ -0,0 +1,881 @@
+//! 运行追踪中间件
+//!
+//! 把每次 Agent 运行整理为一条 [`RunTrace`]（输入输出、模型调用、工具调用、token 用量和耗时），
+//! 在运行结束时交给 [`TraceSink`]，用于接入 Langfuse 等外部追踪后端做评估和监控。
+//! 内置的 [`HttpTraceSink`] 在后台批量发送，不阻塞请求，发送失败也不影响运行。
+
+use std::{
+    fmt::Display,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use langchain_core::{
+    response::Usage,
+    state::{MessagesState, ToolFuture},
+};
+use langgraph::{checkpoint::Configuration, node::NodeContext};
+use serde::Serialize;
+use serde_json::{Value, json};
+use tokio::sync::{mpsc, oneshot};
+
+use super::runs::{RunKey, RunRegistry};
+use crate::{
+    AgentError, define_middleware_label,
+    node::{
+        middleware::{AgentHook, AgentMiddleware},
+        tool::{ToolHandler, ToolMiddleware},
+    },
+};
+
+/// 一次 Agent 运行的追踪记录
+///
+/// 时间均为 Unix 毫秒时间戳，消息为 OpenAI 格式。关闭
+/// [`TraceMiddleware::with_message_contents`] 时 `input`、`output`、工具参数和结果为空。
+#[derive(Debug, Clone, Serialize)]
+pub struct RunTrace {
+    /// 运行 id，每次运行随机生成
+    pub id: String,
+    pub thread_id: Option<String>,
+    pub started_at: u64,
+    pub duration_ms: u64,
+    /// 运行开始时状态中的消息
+    pub input: Vec<Value>,
+    /// 本次运行新增的消息；运行失败且错误中没有状态时为空
+    pub output: Vec<Value>,
+    pub model_calls: Vec<ModelCallTrace>,
+    pub tool_calls: Vec<ToolCallTrace>,
+    /// 本次运行的 token 用量合计
+    pub usage: Usage,
+    /// 运行以错误结束时的错误信息
+    pub error: Option<String>,
+}
+
+/// 一次模型调用
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelCallTrace {
+    pub started_at: u64,
+    pub duration_ms: u64,
+    pub usage: Usage,
+    /// 运行在这次调用中失败时的错误信息
+    pub error: Option<String>,
+}
+
+/// 一次工具调用
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolCallTrace {
+    pub name: String,
+    pub started_at: u64,
+    pub duration_ms: u64,
+    pub arguments: Option<Value>,
+    pub output: Option<Value>,
+    /// 工具失败时的错误信息，不受 `with_message_contents` 影响
+    pub error: Option<String>,
+}
+
+/// 追踪记录的去处
+///
+/// [`submit`](Self::submit) 在 `after_agent` 钩子（运行失败时为 `on_run_error`）中同步调用，
+/// 实现应当尽快返回（例如放入队列），并自行处理发送失败，不能让追踪拖慢或中断运行。
+#[async_trait]
+pub trait TraceSink: Send + Sync {
+    /// 提交一条结束的运行
+    fn submit(&self, trace: RunTrace);
+
+    /// 等待已提交的记录发送完毕，用于关闭服务前；默认直接返回
+    async fn flush(&self) {}
+}
+
+/// [`HttpTraceSink`] 的认证方式
+#[derive(Clone)]
+pub enum TraceAuth {
+    /// `Authorization: Bearer <token>`
+    Bearer(String),
+    /// HTTP Basic 认证，例如 Langfuse 的 public key / secret key
+    Basic { username: String, password: String },
+}
+
+impl std::fmt::Debug for TraceAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Bearer(_) => f.write_str("Bearer(..)"),
+            Self::Basic { username, .. } => f
+                .debug_struct("Basic")
+                .field("username", username)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+/// [`HttpTraceSink`] 发送的请求体格式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// `{"traces": [...]}`，数组元素为序列化的 [`RunTrace`]，由接收方自行转换
+    #[default]
+    Json,
+    /// Langfuse 的 `/api/public/ingestion` 批量接口：每条运行对应一个 trace，
+    /// 模型调用为 generation，工具调用为 span
+    Langfuse,
+}
+
+/// [`HttpTraceSink`] 的配置
+#[derive(Debug, Clone)]
+pub struct HttpTraceSinkConfig {
+    /// 接收追踪记录的地址，每批按 [`format`](Self::format) 组成 JSON 请求体 POST
+    pub endpoint: String,
+    pub format: TraceFormat,
+    pub auth: Option<TraceAuth>,
+    /// 附加的请求头
+    pub headers: Vec<(String, String)>,
+    /// 发送请求使用的客户端，默认为 [`shared_client`](langchain_core::http::shared_client)
+    pub client: reqwest::Client,
+    /// 攒够该数量的记录立即发送，默认 20
+    pub batch_size: usize,
+    /// 未攒够一批时最长等待该时间后发送，默认 5 秒
+    pub flush_interval: Duration,
+    /// 等待发送的记录数上限，队列满时丢弃新记录，默认 1000
+    pub queue_capacity: usize,
+    /// 单次请求的超时时间，默认 10 秒
+    pub timeout: Duration,
+}
+
+impl HttpTraceSinkConfig {
+    pub fn new(endpoint: impl Into<String>) -> Self {
+        Self {
+            endpoint: endpoint.into(),
+            format: TraceFormat::Json,
+            auth: None,
+            headers: Vec::new(),
+            client: langchain_core::http::shared_client(),
+            batch_size: 20,
+            flush_interval: Duration::from_secs(5),
+            queue_capacity: 1000,
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// 发送到 Langfuse：`host` 例如 `https://cloud.langfuse.com`，使用项目的 public key /
+    /// secret key 认证
+    pub fn langfuse(
+        host: &str,
+        public_key: impl Into<String>,
+        secret_key: impl Into<String>,
+    ) -> Self {
+        let mut config = Self::new(format!(
+            "{}/api/public/ingestion",
+            host.trim_end_matches('/')
+        ))
+        .with_basic_auth(public_key, secret_key);
+        config.format = TraceFormat::Langfuse;
+        config
+    }
+
+    pub fn with_format(mut self, format: TraceFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn with_bearer_token(mut self, token: impl Into<String>) -> Self {
+        self.auth = Some(TraceAuth::Bearer(token.into()));
+        self
+    }
+
+    pub fn with_basic_auth(
+        mut self,
+        username: impl Into<String>,
+        password: impl Into<String>,
+    ) -> Self {
+        self.auth = Some(TraceAuth::Basic {
+            username: username.into(),
+            password: password.into(),
+        });
+        self
+    }
+
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// 使用自己的客户端，例如需要代理或自定义 TLS 时
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    pub fn with_flush_interval(mut self, interval: Duration) -> Self {
+        self.flush_interval = interval;
+        self
+    }
+
+    pub fn with_queue_capacity(mut self, capacity: usize) -> Self {
+        self.queue_capacity = capacity.max(1);
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+enum Command {
+    Trace(Box<RunTrace>),
+    Flush(oneshot::Sender<()>),
+}
+
+/// 通过 HTTP 批量发送追踪记录
+///
+/// 记录先放入有界队列，由后台任务按 [`HttpTraceSinkConfig::batch_size`] 和
+/// [`HttpTraceSinkConfig::flush_interval`] 攒批发送。队列已满、请求失败或返回非 2xx 时只记录
+/// 警告日志并丢弃这批记录，不会重试，也不会影响 Agent 运行。
+///
+/// ```rust,ignore
+/// let sink = HttpTraceSink::new(HttpTraceSinkConfig::langfuse(
+///     "https://cloud.langfuse.com",
+///     public_key,
+///     secret_key,
+/// ));
+/// let tracing = TraceMiddleware::new(Arc::new(sink));
+/// let agent = ReactAgent::builder(model)
+///     .with_tool_middleware(tracing.tool_middleware())
+///     .with_middlewares([tracing.into()])
+///     .build();
+/// ```
+#[derive(Debug, Clone)]
+pub struct HttpTraceSink {
+    sender: mpsc::Sender<Command>,
+}
+
+impl HttpTraceSink {
+    /// 创建发送器并启动后台任务，需要在 Tokio 运行时中调用
+    pub fn new(config: HttpTraceSinkConfig) -> Self {
+        let (sender, receiver) = mpsc::channel(config.queue_capacity.max(1));
+        tokio::spawn(export_loop(config, receiver));
+        Self { sender }
+    }
+}
+
+#[async_trait]
+impl TraceSink for HttpTraceSink {
+    fn submit(&self, trace: RunTrace) {
+        if let Err(e) = self.sender.try_send(Command::Trace(Box::new(trace))) {
+            tracing::warn!("Dropped a run trace: {e}");
+        }
+    }
+
+    async fn flush(&self) {
+        let (done, flushed) = oneshot::channel();
+        if self.sender.send(Command::Flush(done)).await.is_ok() {
+            let _ = flushed.await;
+        }
+    }
+}
+
+/// 后台任务：攒批并发送，所有发送器都被丢弃后发送剩余记录并退出
+async fn export_loop(config: HttpTraceSinkConfig, mut receiver: mpsc::Receiver<Command>) {
+    let mut batch = Vec::new();
+    let mut ticker = tokio::time::interval(config.flush_interval.max(Duration::from_millis(1)));
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            command = receiver.recv() => match command {
+                Some(Command::Trace(trace)) => {
+                    batch.push(*trace);
+                    if batch.len() >= config.batch_size {
+                        export(&config, &mut batch).await;
+                    }
+                }
+                Some(Command::Flush(done)) => {
+                    export(&config, &mut batch).await;
+                    let _ = done.send(());
+                }
+                None => {
+                    export(&config, &mut batch).await;
+                    return;
+                }
+            },
+            _ = ticker.tick() => export(&config, &mut batch).await,
+        }
+    }
+}
+
+async fn export(config: &HttpTraceSinkConfig, batch: &mut Vec<RunTrace>) {
+    if batch.is_empty() {
+        return;
+    }
+    let traces = std::mem::take(batch);
+    let body = match config.format {
+        TraceFormat::Json => json!({ "traces": traces }),
+        TraceFormat::Langfuse => langfuse_batch(&traces),
+    };
+    let mut request = config
+        .client
+        .post(&config.endpoint)
+        .timeout(config.timeout)
+        .json(&body);
+    match &config.auth {
+        Some(TraceAuth::Bearer(token)) => request = request.bearer_auth(token),
+        Some(TraceAuth::Basic { username, password }) => {
+            request = request.basic_auth(username, Some(password));
+        }
+        None => {}
+    }
+    for (name, value) in &config.headers {
+        request = request.header(name, value);
+    }
+    match request.send().await.and_then(|r| r.error_for_status()) {
+        Ok(_) => tracing::debug!("Exported {} run traces", traces.len()),
+        Err(e) => tracing::warn!("Failed to export {} run traces: {e}", traces.len()),
+    }
+}
+
+/// Langfuse ingestion 接口的请求体：`{"batch": [事件...]}`
+fn langfuse_batch(traces: &[RunTrace]) -> Value {
+    let mut events = Vec::new();
+    let mut event = |kind: &str, timestamp: u64, body: Value| {
+        events.push(json!({
+            "id": uuid::Uuid::new_v4().to_string(),
+            "type": kind,
+            "timestamp": iso8601(timestamp),
+            "body": body,
+        }));
+    };
+    for trace in traces {
+        event(
+            "trace-create",
+            trace.started_at,
+            json!({
+                "id": trace.id,
+                "name": "agent",
+                "timestamp": iso8601(trace.started_at),
+                "sessionId": trace.thread_id,
+                "input": trace.input,
+                "output": trace.output,
+                "metadata": {
+                    "duration_ms": trace.duration_ms,
+                    "usage": trace.usage,
+                    "error": trace.error,
+                },
+            }),
+        );
+        for (index, call) in trace.model_calls.iter().enumerate() {
+            event(
+                "generation-create",
+                call.started_at,
+                json!({
+                    "id": format!("{}-model-{index}", trace.id),
+                    "traceId": trace.id,
+                    "name": "model",
+                    "startTime": iso8601(call.started_at),
+                    "endTime": iso8601(call.started_at + call.duration_ms),
+                    "usage": {
+                        "input": call.usage.prompt_tokens,
+                        "output": call.usage.completion_tokens,
+                        "total": call.usage.total_tokens,
+                        "unit": "TOKENS",
+                    },
+                    "level": if call.error.is_some() { "ERROR" } else { "DEFAULT" },
+                    "statusMessage": call.error,
+                }),
+            );
+        }
+        for (index, call) in trace.tool_calls.iter().enumerate() {
+            event(
+                "span-create",
+                call.started_at,
+                json!({
+                    "id": format!("{}-tool-{index}", trace.id),
+                    "traceId": trace.id,
+                    "name": call.name,
+                    "startTime": iso8601(call.started_at),
+                    "endTime": iso8601(call.started_at + call.duration_ms),
+                    "input": call.arguments,
+                    "output": call.output,
+                    "level": if call.error.is_some() { "ERROR" } else { "DEFAULT" },
+                    "statusMessage": call.error,
+                }),
+            );
+        }
+    }
+    json!({ "batch": events })
+}
+
+/// Unix 毫秒时间戳转为 ISO 8601（UTC）
+fn iso8601(millis: u64) -> String {
+    chrono::DateTime::from_timestamp_millis(i64::try_from(millis).unwrap_or(i64::MAX))
+        .unwrap_or_default()
+        .to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+}
+
+fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+fn usage_since(now: &Usage, before: &Usage) -> Usage {
+    Usage {
+        prompt_tokens: now.prompt_tokens.saturating_sub(before.prompt_tokens),
+        completion_tokens: now
+            .completion_tokens
+            .saturating_sub(before.completion_tokens),
+        total_tokens: now.total_tokens.saturating_sub(before.total_tokens),
+        completion_tokens_details: None,
+    }
+}
+
+/// 单次运行尚未结束的记录
+struct RunState {
+    trace: RunTrace,
+    started: Instant,
+    /// 运行开始时的消息数和用量，结束时据此计算新增的消息和用量
+    input_len: usize,
+    input_usage: Usage,
+    /// 进行中的模型调用：开始时间和开始时的用量
+    model_call: Option<(Instant, SystemTime, Usage)>,
+}
+
+/// 运行追踪中间件
+///
+/// - `before_agent` 到 `after_agent` 为一次运行，结束时把 [`RunTrace`] 提交给 [`TraceSink`]
+/// - 以错误结束的运行在 `on_run_error` 中提交，[`RunTrace::error`] 为错误信息；错误带有状态
+///   （例如 [`AgentError::Timeout`]）时同样记录输出和用量
+/// - 模型调用：本中间件的 `before_model` 到 `after_model`，把本中间件注册在最后，
+///   计时就只包含模型调用本身，见 [`MetricsMiddleware`](super::MetricsMiddleware)
+/// - 工具调用：通过 [`tool_middleware`](Self::tool_middleware) 记录
+#[derive(Clone)]
+pub struct TraceMiddleware {
+    sink: Arc<dyn TraceSink>,
+    trace_contents: bool,
+    runs: Arc<RunRegistry<RunState>>,
+}
+
+impl std::fmt::Debug for TraceMiddleware {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TraceMiddleware")
+            .field("trace_contents", &self.trace_contents)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TraceMiddleware {
+    /// 记录消息内容、工具参数与结果
+    pub fn new(sink: Arc<dyn TraceSink>) -> Self {
+        Self {
+            sink,
+            trace_contents: true,
+            runs: Arc::default(),
+        }
+    }
+
+    pub fn sink(&self) -> &Arc<dyn TraceSink> {
+        &self.sink
+    }
+
+    /// 是否记录消息内容、工具参数与结果，默认记录；关闭后只上报结构、用量和耗时
+    pub fn with_message_contents(mut self, enabled: bool) -> Self {
+        self.trace_contents = enabled;
+        self
+    }
+
+    /// 记录工具调用的包装器，传给 [`ReactAgentBuilder::with_tool_middleware`]
+    ///
+    /// [`ReactAgentBuilder::with_tool_middleware`]: crate::ReactAgentBuilder::with_tool_middleware
+    pub fn tool_middleware<E>(&self) -> Arc<ToolMiddleware<E>>
+    where
+        E: Display + Send + 'static,
+    {
+        let this = self.clone();
+        Arc::new(Box::new(
+            move |state: &MessagesState,
+                  context: &NodeContext,
+                  name: &str,
+                  args: Value,
+                  handler: ToolHandler<E>|
+                  -> ToolFuture<E> {
+                let key = RunKey::of(context.config);
+                // 从中断处恢复的运行不会经过 before_agent，此时补建记录
+                this.with_run(state, context, |_| {});
+                let this = this.clone();
+                let name = name.to_owned();
+                Box::pin(async move {
+                    let started_at = SystemTime::now();
+                    let started = Instant::now();
+                    let arguments = this.trace_contents.then(|| args.clone());
+                    let result = handler(args).await;
+                    let (output, error) = match &result {
+                        Ok(value) => (this.trace_contents.then(|| value.clone()), None),
+                        Err(e) => (None, Some(e.to_string())),
+                    };
+                    let call = ToolCallTrace {
+                        name,
+                        started_at: unix_millis(started_at),
+                        duration_ms: started.elapsed().as_millis() as u64,
+                        arguments,
+                        output,
+                        error,
+                    };
+                    this.runs.update(key, |run| run.trace.tool_calls.push(call));
+                    result
+                })
+            },
+        ))
+    }
+
+    fn new_run(&self, state: &MessagesState, context: &NodeContext) -> RunState {
+        let started_at = SystemTime::now();
+        RunState {
+            trace: RunTrace {
+                id: uuid::Uuid::new_v4().to_string(),
+                thread_id: context.config.thread_id.clone(),
+                started_at: unix_millis(started_at),
+                duration_ms: 0,
+                input: if self.trace_contents {
+                    state.messages.iter().map(|m| m.to_openai()).collect()
+                } else {
+                    Vec::new()
+                },
+                output: Vec::new(),
+                model_calls: Vec::new(),
+                tool_calls: Vec::new(),
+                usage: Usage::default(),
+                error: None,
+            },
+            started: Instant::now(),
+            input_len: state.messages.len(),
+            input_usage: state.usage.clone(),
+            model_call: None,
+        }
+    }
+
+    /// 在当前运行的记录上执行 `f`，没有记录时先补建
+    fn with_run(
+        &self,
+        state: &MessagesState,
+        context: &NodeContext,
+        f: impl FnOnce(&mut RunState),
+    ) {
+        self.runs
+            .with(context.config, || self.new_run(state, context), f);
+    }
+
+    fn before_agent(&self, state: &MessagesState, context: &NodeContext) {
+        self.runs
+            .start(context.config, self.new_run(state, context));
+    }
+
+    fn before_model(&self, state: &MessagesState, context: &NodeContext) {
+        self.with_run(state, context, |run| {
+            run.model_call = Some((Instant::now(), SystemTime::now(), state.usage.clone()));
+        });
+    }
+
+    fn after_model(&self, state: &MessagesState, context: &NodeContext) {
+        self.with_run(state, context, |run| {
+            if let Some((started, started_at, usage)) = run.model_call.take() {
+                run.trace.model_calls.push(ModelCallTrace {
+                    started_at: unix_millis(started_at),
+                    duration_ms: started.elapsed().as_millis() as u64,
+                    usage: usage_since(&state.usage, &usage),
+                    error: None,
+                });
+            }
+        });
+    }
+
+    /// 以运行结束时的状态补全输出和用量
+    fn complete(&self, run: &mut RunState, state: &MessagesState) {
+        run.trace.usage = usage_since(&state.usage, &run.input_usage);
+        if self.trace_contents {
+            run.trace.output = state
+                .messages
+                .iter()
+                .skip(run.input_len)
+                .map(|m| m.to_openai())
+                .collect();
+        }
+    }
+
+    fn after_agent(&self, state: &MessagesState, context: &NodeContext) {
+        let Some(mut run) = self.runs.finish(context.config) else {
+            return;
+        };
+        run.trace.duration_ms = run.started.elapsed().as_millis() as u64;
+        self.complete(&mut run, state);
+        self.sink.submit(run.trace);
+    }
+
+    fn on_run_error(&self, config: &Configuration, error: &AgentError) {
+        let Some(mut run) = self.runs.finish(config) else {
+            return;
+        };
+        let message = error.to_string();
+        run.trace.duration_ms = run.started.elapsed().as_millis() as u64;
+        // 失败的模型调用没有经过 after_model
+        if let Some((started, started_at, _)) = run.model_call.take() {
+            run.trace.model_calls.push(ModelCallTrace {
+                started_at: unix_millis(started_at),
+                duration_ms: started.elapsed().as_millis() as u64,
+                usage: Usage::default(),
+                error: Some(message.clone()),
+            });
+        }
+        match error.state() {
+            Some(state) => self.complete(&mut run, state),
+            None => {
+                for call in &run.trace.model_calls {
+                    run.trace.usage += &call.usage;
+                }
+            }
+        }
+        run.trace.error = Some(message);
+        self.sink.submit(run.trace);
+    }
+}
+
+/// 把只记录、不修改状态的方法包装为钩子
+fn hook(
+    tracer: &TraceMiddleware,
+    record: fn(&TraceMiddleware, &MessagesState, &NodeContext),
+) -> AgentHook<MessagesState> {
+    let tracer = tracer.clone();
+    AgentHook {
+        handler: Arc::new(move |state: &MessagesState, context: &NodeContext| {
+            record(&tracer, state, context);
+            Box::pin(async { Ok::<_, AgentError>(MessagesState::default()) })
+        }),
+        target: None,
+        branches: vec![],
+    }
+}
+
+impl From<TraceMiddleware> for AgentMiddleware<MessagesState> {
+    fn from(tracer: TraceMiddleware) -> Self {
+        let label = define_middleware_label!(TraceLabel);
+        AgentMiddleware::from_label(label)
+            .with_before_agent(hook(&tracer, TraceMiddleware::before_agent))
+            .with_before_model(hook(&tracer, TraceMiddleware::before_model))
+            .with_after_model(hook(&tracer, TraceMiddleware::after_model))
+            .with_after_agent(hook(&tracer, TraceMiddleware::after_agent))
+            .with_on_run_error(Arc::new(
+                move |config: &Configuration, error: &AgentError| {
+                    tracer.on_run_error(config, error);
+                },
+            ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use langchain_core::{
+        ToolError,
+        message::Message,
+        state::RegisteredTool,
+        testing::{MockHttpServer, response},
+    };
+
+    use super::*;
+    use crate::{ReactAgent, testing::MockModel};
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Collected(Mutex<Vec<RunTrace>>);
+
+    impl TraceSink for Collected {
+        fn submit(&self, trace: RunTrace) {
+            self.0.lock().unwrap().push(trace);
+        }
+    }
+
+    fn lookup_tool() -> RegisteredTool<ToolError> {
+        RegisteredTool::new(
+            "lookup".to_owned(),
+            String::new(),
+            serde_json::json!({"type": "object"}),
+            Arc::new(|_| Box::pin(async { Ok(serde_json::json!("found")) })),
+        )
+    }
+
+    fn agent(tracer: TraceMiddleware) -> ReactAgent {
+        let model = MockModel::new([
+            MockModel::tool_call("lookup", serde_json::json!({"q": "rust"})),
+            Message::assistant("done"),
+        ])
+        .with_usage(Usage {
+            prompt_tokens: 10,
+            completion_tokens: 5,
+            total_tokens: 15,
+            completion_tokens_details: None,
+        });
+        ReactAgent::builder(model)
+            .with_tools([lookup_tool()])
+            .with_tool_middleware(tracer.tool_middleware())
+            .with_middlewares([tracer.into()])
+            .build()
+    }
+
+    #[tokio::test]
+    async fn submits_one_trace_per_run_with_model_and_tool_calls() {
+        let sink = Arc::new(Collected::default());
+        agent(TraceMiddleware::new(sink.clone()))
+            .invoke(Message::user("go"), Some("thread-1"))
+            .await
+            .unwrap();
+
+        let traces = sink.0.lock().unwrap();
+        assert_eq!(traces.len(), 1);
+        let trace = &traces[0];
+        assert_eq!(trace.thread_id.as_deref(), Some("thread-1"));
+        assert_eq!(trace.input.len(), 1);
+        // 工具调用、工具结果和最终回答
+        assert_eq!(trace.output.len(), 3);
+        assert_eq!(trace.output[2]["content"], "done");
+        assert_eq!(trace.model_calls.len(), 2);
+        assert_eq!(trace.model_calls[0].usage.total_tokens, 15);
+        assert_eq!(trace.usage.total_tokens, 30);
+        let call = &trace.tool_calls[0];
+        assert_eq!(call.name, "lookup");
+        assert_eq!(call.arguments, Some(serde_json::json!({"q": "rust"})));
+        assert_eq!(call.output, Some(serde_json::json!("found")));
+    }
+
+    #[tokio::test]
+    async fn contents_can_be_left_out() {
+        let sink = Arc::new(Collected::default());
+        agent(TraceMiddleware::new(sink.clone()).with_message_contents(false))
+            .invoke(Message::user("go"), None)
+            .await
+            .unwrap();
+
+        let traces = sink.0.lock().unwrap();
+        assert!(traces[0].input.is_empty() && traces[0].output.is_empty());
+        assert_eq!(traces[0].tool_calls[0].arguments, None);
+        assert_eq!(traces[0].model_calls.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn failed_runs_are_submitted_with_the_error() {
+        let sink = Arc::new(Collected::default());
+        let tracer = TraceMiddleware::new(sink.clone());
+        let model = MockModel::new([MockModel::tool_call("lookup", serde_json::json!({}))]);
+        model.push_error(langchain_core::ModelError::ResponseError(
+            "overloaded".to_owned(),
+        ));
+        let agent = ReactAgent::builder(model)
+            .with_tools([lookup_tool()])
+            .with_tool_middleware(tracer.tool_middleware())
+            .with_middlewares([tracer.clone().into()])
+            .build();
+
+        let error = agent
+            .invoke(Message::user("go"), Some("thread-2"))
+            .await
+            .unwrap_err();
+
+        let traces = sink.0.lock().unwrap();
+        assert_eq!(traces.len(), 1);
+        let trace = &traces[0];
+        assert_eq!(trace.error.as_deref(), Some(error.to_string().as_str()));
+        assert_eq!(trace.tool_calls.len(), 1);
+        assert_eq!(trace.model_calls.len(), 2);
+        assert!(trace.model_calls[0].error.is_none());
+        assert!(trace.model_calls[1].error.is_some());
+        assert_eq!(tracer.runs.len(), 0);
+    }
+
+    #[test]
+    fn langfuse_batches_map_runs_to_traces_generations_and_spans() {
+        let trace = RunTrace {
+            id: "run-1".to_owned(),
+            thread_id: Some("thread-1".to_owned()),
+            started_at: 1_700_000_000_000,
+            duration_ms: 30,
+            input: vec![serde_json::json!({"role": "user", "content": "go"})],
+            output: Vec::new(),
+            model_calls: vec![ModelCallTrace {
+                started_at: 1_700_000_000_000,
+                duration_ms: 10,
+                usage: Usage {
+                    prompt_tokens: 10,
+                    completion_tokens: 5,
+                    total_tokens: 15,
+                    completion_tokens_details: None,
+                },
+                error: None,
+            }],
+            tool_calls: vec![ToolCallTrace {
+                name: "lookup".to_owned(),
+                started_at: 1_700_000_000_010,
+                duration_ms: 5,
+                arguments: None,
+                output: None,
+                error: Some("boom".to_owned()),
+            }],
+            usage: Usage::default(),
+            error: None,
+        };
+
+        let body = langfuse_batch(&[trace]);
+        let events = body["batch"].as_array().unwrap();
+        let kinds: Vec<_> = events.iter().map(|e| e["type"].as_str().unwrap()).collect();
+        assert_eq!(kinds, ["trace-create", "generation-create", "span-create"]);
+        assert_eq!(events[0]["body"]["id"], "run-1");
+        assert_eq!(events[0]["body"]["sessionId"], "thread-1");
+        assert_eq!(events[0]["timestamp"], "2023-11-14T22:13:20.000Z");
+        assert_eq!(events[1]["body"]["traceId"], "run-1");
+        assert_eq!(events[1]["body"]["usage"]["total"], 15);
+        assert_eq!(events[1]["body"]["endTime"], "2023-11-14T22:13:20.010Z");
+        assert_eq!(events[2]["body"]["name"], "lookup");
+        assert_eq!(events[2]["body"]["level"], "ERROR");
+    }
+
+    #[tokio::test]
+    async fn http_sink_posts_batches_with_auth() {
+        let server = MockHttpServer::start(vec![response("200 OK", "application/json", "")]).await;
+
+        let sink = Arc::new(HttpTraceSink::new(
+            HttpTraceSinkConfig::new(format!("{}/runs", server.url()))
+                .with_bearer_token("secret")
+                .with_header("x-project", "demo")
+                .with_batch_size(2)
+                .with_flush_interval(Duration::from_secs(60)),
+        ));
+        for input in ["a", "b"] {
+            agent(TraceMiddleware::new(sink.clone()))
+                .invoke(Message::user(input), None)
+                .await
+                .unwrap();
+        }
+
+        let request = server.request(0).await;
+        assert!(request.starts_with("POST /runs"));
+        assert!(request.contains("authorization: Bearer secret"));
+        assert!(request.contains("x-project: demo"));
+        let body: Value = serde_json::from_str(request.split("\r\n\r\n").nth(1).unwrap()).unwrap();
+        assert_eq!(body["traces"].as_array().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn export_failures_do_not_fail_the_run() {
+        // 绑定后立即释放端口，使请求被拒绝
+        let addr = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let sink = Arc::new(HttpTraceSink::new(HttpTraceSinkConfig::new(format!(
+            "http://{addr}"
+        ))));
+        agent(TraceMiddleware::new(sink.clone()))
+            .invoke(Message::user("go"), None)
+            .await
+            .unwrap();
+        sink.flush().await;
+    }
+}
@@ -1,15 +1,20 @@
-use std::mem;
+use std::{borrow::Cow, mem, sync::Arc, time::Instant};
 
 use async_trait::async_trait;
 use futures::StreamExt;
 use langchain_core::{
     message::{FunctionCall, Message, ToolCall},
-    request::ToolSpec,
+    request::{ToolChoice, ToolSpec, tool_examples_prompt},
+    response::Usage,
     state::{ChatCompletion, ChatModel, ChatStreamEvent, InvokeOptions, MessagesState},
 };
-use langgraph::node::{EventSink, Node, NodeContext};
+use langgraph::{
+    id::{IdGenerator, UuidGenerator},
+    node::{EventSink, Node, NodeContext},
+};
+use tracing::{Instrument, Span, field};
 
-use crate::AgentError;
+use crate::{AgentError, spans::LLM_CALL_SPAN, tool_selection::ToolSelector};
 
 pub struct LlmNode<M>
 where
@@ -19,6 +24,20 @@ where
     pub tools: Vec<ToolSpec>,
     pub temperature: Option<f32>,
     pub max_tokens: Option<u32>,
+    /// 工具选择，见 [`LlmNode::with_tool_choice`]
+    pub tool_choice: Option<ToolChoice>,
+    /// 发送前调用 [`MessagesState::normalize`] 整理消息，不影响图中保存的状态
+    pub normalize_messages: bool,
+    /// 隐藏思考内容：不推送 [`ChatStreamEvent::Reasoning`]，也不写入助手消息
+    pub hide_reasoning: bool,
+    /// 在 `llm.call` span 中记录请求和回复的消息，见 [`crate::spans`]
+    pub trace_content: bool,
+    /// 少样本示例，见 [`LlmNode::with_examples`]
+    pub examples: Vec<(String, String)>,
+    /// 每次调用前选择发送的工具，见 [`ToolSelection`](crate::tool_selection::ToolSelection)
+    pub(crate) tool_selector: ToolSelector,
+    /// 为模型没有给出 id 的工具调用生成 id
+    pub id_generator: Arc<dyn IdGenerator>,
 }
 
 impl<M> LlmNode<M>
@@ -31,7 +50,80 @@ where
             tools,
             temperature: None,
             max_tokens: None,
+            tool_choice: None,
+            normalize_messages: false,
+            hide_reasoning: false,
+            trace_content: false,
+            examples: Vec::new(),
+            tool_selector: ToolSelector::default(),
+            id_generator: Arc::new(UuidGenerator),
+        }
+    }
+
+    /// 本次调用发送给模型的消息
+    fn request_messages(&self, input: &MessagesState) -> Vec<Arc<Message>> {
+        if self.normalize_messages {
+            let mut normalized = input.clone();
+            normalized.normalize();
+            normalized.messages.into_iter().collect()
+        } else {
+            input.messages.iter().cloned().collect()
+        }
+    }
+
+    /// 本次发送的工具带有调用示例时，把示例附加到系统提示末尾（没有系统提示时新建一条），
+    /// 只影响本次请求，不写入状态
+    fn with_tool_examples(messages: &mut Vec<Arc<Message>>, tools: Option<&[ToolSpec]>) {
+        let Some(examples) = tools.and_then(tool_examples_prompt) else {
+            return;
+        };
+        match messages.first().map(|m| m.as_ref()) {
+            Some(Message::System { content, .. }) => {
+                messages[0] = Arc::new(Message::system(format!("{content}\n\n{examples}")));
+            }
+            _ => messages.insert(0, Arc::new(Message::system(examples))),
+        }
+    }
+
+    /// 把少样本示例作为 user/assistant 对话插入到开头的系统消息之后，只影响本次请求，不写入状态
+    fn with_few_shot_examples(&self, messages: &mut Vec<Arc<Message>>) {
+        if self.examples.is_empty() {
+            return;
         }
+        let at = messages
+            .iter()
+            .take_while(|m| matches!(m.as_ref(), Message::System { .. }))
+            .count();
+        let examples = self.examples.iter().flat_map(|(user, assistant)| {
+            [
+                Arc::new(Message::user(user.clone())),
+                Arc::new(Message::assistant(assistant.clone())),
+            ]
+        });
+        messages.splice(at..at, examples);
+    }
+
+    /// 按运行配置筛选本次调用可见的工具，再按工具选择策略选出本次发送的工具
+    async fn effective_tools(
+        &self,
+        input: &MessagesState,
+        context: &NodeContext<'_>,
+    ) -> Vec<ToolSpec> {
+        let tools = match &context.config.tools {
+            Some(allowed) => Cow::Owned(
+                self.tools
+                    .iter()
+                    .filter(|spec| allowed.iter().any(|name| name == spec.function_name()))
+                    .cloned()
+                    .collect(),
+            ),
+            None => Cow::Borrowed(self.tools.as_slice()),
+        };
+        let forced = match &self.tool_choice {
+            Some(ToolChoice::Function(name)) => Some(name.as_str()),
+            _ => None,
+        };
+        self.tool_selector.select(input, &tools, forced).await
     }
 
     pub fn with_temperature(mut self, temperature: f32) -> Self {
@@ -43,6 +135,135 @@ where
         self.max_tokens = Some(max_tokens);
         self
     }
+
+    /// 每次调用模型时在系统提示之后、对话之前插入的示例对话 `(user, assistant)`
+    pub fn with_examples(mut self, examples: Vec<(String, String)>) -> Self {
+        self.examples = examples;
+        self
+    }
+
+    /// 强制或禁止模型调用工具
+    ///
+    /// 只作用于用户消息之后的第一次调用，模型拿到工具结果后的调用由模型自行选择，
+    /// 避免强制调用工具时无法结束。[`ToolChoice::None`] 通过不发送工具列表实现，
+    /// 对不支持 `tool_choice` 的模型同样有效；其余选项由模型实现决定是否支持。
+    pub fn with_tool_choice(mut self, tool_choice: ToolChoice) -> Self {
+        self.tool_choice = Some(tool_choice);
+        self
+    }
+
+    /// 本次调用的工具列表与工具选择
+    fn tool_options<'a>(
+        &self,
+        input: &MessagesState,
+        tools: &'a [ToolSpec],
+    ) -> (Option<&'a [ToolSpec]>, Option<ToolChoice>) {
+        let after_tool_result = matches!(
+            input.messages.last().map(|m| m.as_ref()),
+            Some(Message::Tool { .. })
+        );
+        let choice = match &self.tool_choice {
+            Some(_) if after_tool_result => None,
+            Some(ToolChoice::Function(name))
+                if !tools.iter().any(|spec| spec.function_name() == name) =>
+            {
+                tracing::warn!("tool_choice names unavailable tool `{name}`, ignoring it");
+                None
+            }
+            choice => choice.clone(),
+        };
+        match choice {
+            // 没有可用工具时工具选择没有意义
+            Some(ToolChoice::None) => (None, None),
+            _ if tools.is_empty() => (None, None),
+            choice => (Some(tools), choice),
+        }
+    }
+}
+
+/// 一次模型请求的 span，见 [`crate::spans`]
+fn llm_span(context: &NodeContext<'_>, stream: bool, messages: usize, tools: usize) -> Span {
+    tracing::info_span!(
+        LLM_CALL_SPAN,
+        thread_id = context.config.thread_id.as_deref(),
+        stream,
+        messages,
+        tools,
+        prompt_tokens = field::Empty,
+        completion_tokens = field::Empty,
+        total_tokens = field::Empty,
+        duration_ms = field::Empty,
+        input = field::Empty,
+        output = field::Empty,
+    )
+}
+
+/// 把请求或回复的消息以 JSON 记录到 span 的 `input` / `output` 字段
+fn record_messages<'a>(
+    span: &Span,
+    field: &'static str,
+    messages: impl IntoIterator<Item = &'a Arc<Message>>,
+) {
+    let messages = messages.into_iter().map(|m| m.to_openai()).collect();
+    span.record(field, serde_json::Value::Array(messages).to_string());
+}
+
+/// 请求结束时记录用量和耗时
+fn record_llm_call(span: &Span, usage: &Usage, started: Instant) {
+    span.record("prompt_tokens", usage.prompt_tokens);
+    span.record("completion_tokens", usage.completion_tokens);
+    span.record("total_tokens", usage.total_tokens);
+    span.record("duration_ms", started.elapsed().as_millis() as u64);
+}
+
+/// 去掉助手消息中的思考内容
+fn without_reasoning(message: Arc<Message>) -> Arc<Message> {
+    match message.as_ref() {
+        Message::Assistant {
+            reasoning_content: Some(_),
+            ..
+        } => {
+            let mut message = Arc::unwrap_or_clone(message);
+            if let Message::Assistant {
+                reasoning_content, ..
+            } = &mut message
+            {
+                *reasoning_content = None;
+            }
+            Arc::new(message)
+        }
+        _ => message,
+    }
+}
+
+/// 为没有 id 的工具调用补上 id：部分模型提供方不返回 id，而工具结果需要按 id 对应到调用
+fn fill_tool_call_ids(tool_calls: &mut [ToolCall], ids: &dyn IdGenerator) {
+    for call in tool_calls.iter_mut().filter(|call| call.id.is_empty()) {
+        call.id = ids.next_id();
+    }
+}
+
+/// 对助手消息调用 [`fill_tool_call_ids`]，所有调用都有 id 时不复制消息
+fn with_tool_call_ids(message: Arc<Message>, ids: &dyn IdGenerator) -> Arc<Message> {
+    let missing = match message.as_ref() {
+        Message::Assistant {
+            tool_calls: Some(calls),
+            ..
+        } => calls.iter().any(|call| call.id.is_empty()),
+        _ => false,
+    };
+    if !missing {
+        return message;
+    }
+    let mut message = Arc::unwrap_or_clone(message);
+    if let Message::Assistant {
+        tool_calls: Some(tool_calls),
+        ..
+    } = &mut message
+    {
+        fill_tool_call_ids(tool_calls, ids);
+    }
+    Arc::new(message)
 }
 
 #[async_trait]
@@ -55,27 +276,49 @@ where
         input: &MessagesState,
         context: NodeContext<'_>,
     ) -> Result<MessagesState, AgentError> {
-        let messages: Vec<_> = input.messages.iter().cloned().collect();
+        let mut messages = self.request_messages(input);
+        let tools = self.effective_tools(input, &context).await;
+        let (tools, tool_choice) = self.tool_options(input, &tools);
+        self.with_few_shot_examples(&mut messages);
+        Self::with_tool_examples(&mut messages, tools);
         let options = InvokeOptions {
-            tools: if self.tools.is_empty() {
-                None
-            } else {
-                Some(&self.tools)
-            },
+            tools,
+            tool_choice,
             temperature: self.temperature,
             max_tokens: self.max_tokens,
             response_format: context.config.response_format.as_ref(),
             ..Default::default()
         };
+        let span = llm_span(&context, false, messages.len(), tools.map_or(0, <[_]>::len));
+        if self.trace_content {
+            record_messages(&span, "input", &messages);
+        }
+        let started = Instant::now();
         let completion: ChatCompletion = self
             .model
             .invoke(&messages, &options)
+            .instrument(span.clone())
             .await
             .map_err(AgentError::Model)?;
-        tracing::debug!("LLM completion: {:?}", completion);
+        record_llm_call(&span, &completion.usage, started);
+        tracing::debug!(parent: &span, "LLM completion: {:?}", completion);
 
+        let messages = completion
+            .messages
+            .into_iter()
+            .map(|message| with_tool_call_ids(message, self.id_generator.as_ref()));
+        let messages: Vec<_> = if self.hide_reasoning {
+            messages.map(without_reasoning).collect()
+        } else {
+            messages.collect()
+        };
+        // 记录处理后的回复：隐藏的思考内容不写入 span
+        if self.trace_content {
+            record_messages(&span, "output", &messages);
+        }
         let mut delta = MessagesState::default();
-        delta.append_messages(completion.messages.into());
+        delta.append_messages(messages.into());
+        delta.usage = completion.usage;
         delta.increment_llm_calls();
         Ok(delta)
     }
@@ -84,110 +327,149 @@ where
         &self,
         input: &MessagesState,
         sink: &dyn EventSink<ChatStreamEvent>,
-        _context: NodeContext<'_>,
+        context: NodeContext<'_>,
     ) -> Result<MessagesState, AgentError> {
-        let messages: Vec<_> = input.messages.iter().cloned().collect();
+        let mut messages = self.request_messages(input);
+        let tools = self.effective_tools(input, &context).await;
+        let (tools, tool_choice) = self.tool_options(input, &tools);
+        self.with_few_shot_examples(&mut messages);
+        Self::with_tool_examples(&mut messages, tools);
 
         let options = InvokeOptions {
-            tools: if self.tools.is_empty() {
-                None
-            } else {
-                Some(&self.tools)
-            },
+            tools,
+            tool_choice,
             temperature: self.temperature,
             max_tokens: self.max_tokens,
+            response_format: context.config.response_format.as_ref(),
             ..Default::default()
         };
 
-        let mut completion_stream = self
-            .model
-            .stream(&messages, &options)
-            .await
-            .map_err(AgentError::Model)?;
-
-        let mut content = String::new();
-        let mut reasoning_content = String::new();
-        let mut tool_calls: Vec<ToolCall> = Vec::new();
+        let span = llm_span(&context, true, messages.len(), tools.map_or(0, <[_]>::len));
+        if self.trace_content {
+            record_messages(&span, "input", &messages);
+        }
+        let started = Instant::now();
+        let delta = async {
+            let mut completion_stream = self
+                .model
+                .stream(&messages, &options)
+                .await
+                .map_err(AgentError::Model)?;
 
-        let mut raw_args = String::new();
+            let mut content = String::new();
+            let mut reasoning_content = String::new();
+            let mut tool_calls: Vec<ToolCall> = Vec::new();
 
-        while let Some(event) = completion_stream.next().await {
-            let event = event.map_err(AgentError::Model)?;
-            sink.emit(event.clone()).await;
+            let mut raw_args = String::new();
+            let mut stream_usage = Usage::default();
 
-            match event {
-                ChatStreamEvent::Content(chunk) => {
-                    content.push_str(&chunk);
+            while let Some(event) = completion_stream.next().await {
+                let mut event = event.map_err(AgentError::Model)?;
+                if self.hide_reasoning && matches!(event, ChatStreamEvent::Reasoning(_)) {
+                    continue;
                 }
-                ChatStreamEvent::ReasoningContent(chunk) => {
-                    reasoning_content.push_str(&chunk);
+                // 新工具调用的第一个增量没有 id 时在转发前补上，流式输出与最终状态使用同一个 id
+                if let ChatStreamEvent::ToolCallDelta { index, id, .. } = &mut event
+                    && *index >= tool_calls.len()
+                    && id.as_deref().is_none_or(str::is_empty)
+                {
+                    *id = Some(self.id_generator.next_id());
                 }
-                ChatStreamEvent::ToolCallDelta {
-                    index,
-                    id,
-                    type_name,
-                    name,
-                    arguments,
-                } => {
-                    if tool_calls.len() <= index {
-                        tool_calls.resize_with(index + 1, || ToolCall {
-                            id: String::new(),
-                            type_name: String::new(),
-                            function: FunctionCall {
-                                name: String::new(),
-                                arguments: serde_json::Value::Null,
-                            },
-                        });
-
-                        if index > 0 {
-                            let call = &mut tool_calls[index - 1];
-                            call.function.arguments =
-                                serde_json::Value::String(mem::take(&mut raw_args));
-                        }
-                    }
-
-                    let call = &mut tool_calls[index];
+                sink.emit(event.clone()).await;
 
-                    if let Some(id) = id {
-                        call.id = id;
+                match event {
+                    ChatStreamEvent::Content(chunk) => {
+                        content.push_str(&chunk);
                     }
-                    if let Some(tn) = type_name {
-                        call.type_name = tn;
+                    ChatStreamEvent::Reasoning(chunk) => {
+                        reasoning_content.push_str(&chunk);
                     }
-                    if let Some(name) = name {
-                        call.function.name = name;
+                    ChatStreamEvent::ToolCallDelta {
+                        index,
+                        id,
+                        type_name,
+                        name,
+                        arguments,
+                    } => {
+                        if tool_calls.len() <= index {
+                            tool_calls.resize_with(index + 1, || ToolCall {
+                                id: String::new(),
+                                type_name: String::new(),
+                                function: FunctionCall {
+                                    name: String::new(),
+                                    arguments: serde_json::Value::Null,
+                                },
+                            });
+
+                            if index > 0 {
+                                let call = &mut tool_calls[index - 1];
+                                call.function.arguments =
+                                    serde_json::Value::String(mem::take(&mut raw_args));
+                            }
+                        }
+
+                        let call = &mut tool_calls[index];
+
+                        if let Some(id) = id {
+                            call.id = id;
+                        }
+                        if let Some(tn) = type_name {
+                            call.type_name = tn;
+                        }
+                        if let Some(name) = name {
+                            call.function.name = name;
+                        }
+                        if let Some(args) = arguments {
+                            raw_args.push_str(&args);
+                        }
                     }
-                    if let Some(args) = arguments {
-                        raw_args.push_str(&args);
+                    ChatStreamEvent::Done { usage, .. } => {
+                        if let Some(usage) = usage {
+                            stream_usage = usage;
+                        }
                     }
+                    // 模型不会产生工具输出
+                    ChatStreamEvent::ToolChunk { .. } | ChatStreamEvent::ToolResults { .. } => {}
                 }
-                ChatStreamEvent::Done { .. } => {}
             }
-        }
 
-        let mut delta = MessagesState::default();
-
-        if !content.is_empty() || !tool_calls.is_empty() {
-            let assistant = Message::Assistant {
-                content,
-                reasoning_content: if reasoning_content.is_empty() {
-                    None
-                } else {
-                    Some(reasoning_content)
-                },
-                tool_calls: if tool_calls.is_empty() {
-                    None
-                } else {
-                    let len = tool_calls.len();
-                    tool_calls[len - 1].function.arguments = serde_json::Value::String(raw_args);
-                    Some(tool_calls)
-                },
-                name: None,
+            let mut delta = MessagesState {
+                usage: stream_usage,
+                ..Default::default()
             };
-            delta.push_message_owned(assistant);
-        }
 
-        delta.increment_llm_calls();
+            if !content.is_empty() || !tool_calls.is_empty() {
+                let assistant = Message::Assistant {
+                    content,
+                    reasoning_content: if reasoning_content.is_empty() {
+                        None
+                    } else {
+                        Some(reasoning_content)
+                    },
+                    tool_calls: if tool_calls.is_empty() {
+                        None
+                    } else {
+                        let len = tool_calls.len();
+                        tool_calls[len - 1].function.arguments =
+                            serde_json::Value::String(raw_args);
+                        fill_tool_call_ids(&mut tool_calls, self.id_generator.as_ref());
+                        Some(tool_calls)
+                    },
+                    name: None,
+                    metadata: Default::default(),
+                };
+                delta.push_message_owned(assistant);
+            }
+
+            delta.increment_llm_calls();
+            Ok::<_, AgentError>(delta)
+        }
+        .instrument(span.clone())
+        .await?;
+        record_llm_call(&span, &delta.usage, started);
+        if self.trace_content {
+            record_messages(&span, "output", &delta.messages);
+        }
         Ok(delta)
     }
 }
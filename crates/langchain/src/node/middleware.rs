@@ -3,6 +3,7 @@ use std::sync::Arc;
 use futures::future::BoxFuture;
 use langchain_core::state::{ChatStreamEvent, MessagesState};
 use langgraph::{
+    checkpoint::Configuration,
     label::InternedGraphLabel,
     node::{Node, NodeContext},
 };
@@ -12,6 +13,10 @@ use crate::AgentError;
 pub type MiddlewareHandler<S> =
     Arc<dyn Fn(&S, &NodeContext) -> BoxFuture<'static, Result<S, AgentError>> + Send + Sync>;
 
+/// 运行以错误结束时的回调，参数为该次运行的配置（与钩子收到的 [`NodeContext::config`]
+/// 是同一个引用，可以据此找到运行中记录的数据）和结束运行的错误
+pub type RunErrorHandler = Arc<dyn Fn(&Configuration, &AgentError) + Send + Sync>;
+
 #[derive(Clone)]
 pub struct AgentMiddleware<S: Default> {
     /// 中间件标签
@@ -24,6 +29,8 @@ pub struct AgentMiddleware<S: Default> {
     pub after_model: Option<AgentHook<S>>,
     /// 每次代理完成（每个调用一次）
     pub after_agent: Option<AgentHook<S>>,
+    /// 运行以错误结束时调用（此时不会执行 `after_agent`），用于清理运行中记录的数据或上报失败
+    pub on_run_error: Option<RunErrorHandler>,
 }
 
 impl<S: Default> AgentMiddleware<S> {
@@ -34,6 +41,7 @@ impl<S: Default> AgentMiddleware<S> {
             before_model: None,
             after_model: None,
             after_agent: None,
+            on_run_error: None,
         }
     }
 
@@ -56,6 +64,11 @@ impl<S: Default> AgentMiddleware<S> {
         self.after_agent = Some(handler);
         self
     }
+
+    pub fn with_on_run_error(mut self, handler: RunErrorHandler) -> Self {
+        self.on_run_error = Some(handler);
+        self
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -66,6 +79,18 @@ pub struct MiddlewareLabel {
     pub after_agent: InternedGraphLabel,
 }
 
+/// 中间件钩子
+///
+/// `handler` 返回的更新会合并到状态中。除了静态的 `target`，钩子还可以在更新中调用
+/// [`MessagesState::jump`](langchain_core::state::MessagesState::jump) 短路后续流程，
+/// 例如缓存命中时追加缓存的回复并跳过模型（[`JumpTo::SkipModel`]），
+/// 或者追加一条拒绝消息后结束运行（[`JumpTo::End`]）。
+///
+/// 钩子按中间件的注册顺序执行（`after_*` 钩子为逆序），第一个请求跳转的钩子生效，
+/// 同一阶段中排在它之后的钩子不会执行；跳转优先于 `target`。
+///
+/// [`JumpTo::SkipModel`]: langchain_core::state::JumpTo::SkipModel
+/// [`JumpTo::End`]: langchain_core::state::JumpTo::End
 #[derive(Clone)]
 pub struct AgentHook<S: Default> {
     pub handler: MiddlewareHandler<S>,
@@ -76,8 +101,8 @@ pub struct AgentHook<S: Default> {
 #[macro_export]
 macro_rules! define_middleware_label {
     ($name:ident) => {{
-        use langchain::node::middleware::MiddlewareLabel;
         use langgraph::label::GraphLabel;
+        use $crate::node::middleware::MiddlewareLabel;
 
         #[derive(Debug, Clone, PartialEq, Eq, Hash, GraphLabel)]
         pub enum $name {
@@ -1,18 +1,30 @@
 use std::error::Error;
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::pin::Pin;
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Instant,
+};
 
 use async_trait::async_trait;
-use futures::Future;
-use futures::future::join_all;
+use futures::channel::mpsc::{UnboundedReceiver, UnboundedSender, unbounded};
+use futures::future::{Either, select};
+use futures::{Future, StreamExt};
 use langchain_core::{
-    message::Message,
-    state::{ChatStreamEvent, MessagesState, ToolFn, ToolFuture},
+    error::{ErrorCategory, LangChainError, RetryConfig, retry_with_backoff_hint},
+    message::{Message, ToolCall},
+    schema,
+    state::{
+        ChatStreamEvent, JumpTo, MessagesState, TOOL_FAILED_METADATA, ToolChunkFn, ToolFn,
+        ToolFuture, ToolStreamFn,
+    },
 };
 use langgraph::node::{EventSink, Node, NodeContext};
 use serde_json::Value;
+use tracing::{Instrument, field};
 
-use crate::AgentError;
+use crate::{AgentError, spans::TOOL_CALL_SPAN};
 
 pub type ToolHandler<E> = Box<dyn FnOnce(Value) -> ToolFuture<E> + Send + 'static>;
 
@@ -22,12 +34,244 @@ pub type ToolMiddleware<E> = Box<
         + Sync,
 >;
 
+/// 工具调用的结果：(调用 id, 结果内容，失败时为返回给模型的错误, 是否为成功的终止工具调用)
+type ToolOutcome = (String, Result<String, String>, bool);
+
+/// 工具调用失败时的重试策略，见 [`ToolNode::with_retry`]
+pub struct ToolRetry<E> {
+    pub config: RetryConfig,
+    category: fn(&E) -> ErrorCategory,
+    retry_delay_ms: fn(&E) -> Option<u64>,
+}
+
+/// 工具执行失败时返回给模型的内容，见 [`ToolNode::with_error_formatter`]
+///
+/// 默认的 [`concise`](Self::concise) 只保留错误信息的第一行，避免把调用栈等内部细节发给模型。
+pub struct ToolErrorFormatter<E> {
+    format: Box<ToolErrorFormatFn<E>>,
+    retry_hint: Option<String>,
+}
+
+/// 由工具名和错误生成返回给模型的内容
+pub type ToolErrorFormatFn<E> = dyn Fn(&str, &E) -> String + Send + Sync;
+
+/// [`ToolErrorFormatter::with_retry_hint`] 的默认提示
+pub const DEFAULT_RETRY_HINT: &str =
+    "Check the arguments and try again, or use a different approach.";
+
+/// [`ToolErrorFormatter::concise`] 保留的最大字符数
+const CONCISE_ERROR_MAX_CHARS: usize = 500;
+
+impl<E: Error> Default for ToolErrorFormatter<E> {
+    fn default() -> Self {
+        Self::concise()
+    }
+}
+
+impl<E> ToolErrorFormatter<E> {
+    /// `Error: tool `{name}` failed: {error}`，错误只取第一行，最多 500 个字符
+    pub fn concise() -> Self
+    where
+        E: Error,
+    {
+        Self::custom(|name, error: &E| {
+            let message = error.to_string();
+            let line = message.lines().next().unwrap_or_default().trim();
+            let line: String = match line.char_indices().nth(CONCISE_ERROR_MAX_CHARS) {
+                Some((end, _)) => format!("{}...", &line[..end]),
+                None => line.to_owned(),
+            };
+            format!("Error: tool `{name}` failed: {line}")
+        })
+    }
+
+    /// 包含错误的 `Debug` 输出，便于调试，但可能暴露内部细节
+    pub fn debug() -> Self
+    where
+        E: Error,
+    {
+        Self::custom(|name, error: &E| format!("Error: tool `{name}` failed: {error:?}"))
+    }
+
+    /// 由 `format(工具名, 错误)` 生成返回给模型的内容
+    pub fn custom<F>(format: F) -> Self
+    where
+        F: Fn(&str, &E) -> String + Send + Sync + 'static,
+    {
+        Self {
+            format: Box::new(format),
+            retry_hint: None,
+        }
+    }
+
+    /// 在错误后另起一行附加提示，引导模型调整参数重试，见 [`DEFAULT_RETRY_HINT`]
+    pub fn with_retry_hint<S: Into<String>>(mut self, hint: S) -> Self {
+        self.retry_hint = Some(hint.into());
+        self
+    }
+
+    pub fn format(&self, name: &str, error: &E) -> String {
+        let message = (self.format)(name, error);
+        match &self.retry_hint {
+            Some(hint) => format!("{message}\n{hint}"),
+            None => message,
+        }
+    }
+}
+
+/// 工具结果不满足输出 schema 时的处理方式，见 [`ToolNode::with_output_validation`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputValidation {
+    /// 记录警告，结果照常返回给模型
+    #[default]
+    Warn,
+    /// 按工具执行失败处理，把不匹配的字段作为错误返回给模型
+    Reject,
+}
+
+/// 未知工具错误消息的前缀，用于在历史中识别连续的未知工具调用
+const UNKNOWN_TOOL_ERROR: &str = "Error: unknown tool";
+
+/// 重复工具调用检测，见 [`ToolNode::with_repetition_guard`]
+///
+/// 与步数上限不同，模型反复用同样的参数调用同一个工具时，不必等步数用完就能发现。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RepetitionGuard {
+    /// 检查最近多少次工具调用，只统计最后一条用户消息之后的调用
+    pub window: usize,
+    /// 窗口内同一工具、同样参数的调用达到该次数（包括本次）时触发
+    pub threshold: usize,
+    pub action: RepetitionAction,
+}
+
+/// 检测到重复调用时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RepetitionAction {
+    /// 不执行这次调用，以工具结果的形式提醒模型换一种做法
+    #[default]
+    Nudge,
+    /// 以 [`AgentError::RepeatedToolCall`] 结束运行
+    Stop,
+}
+
+impl Default for RepetitionGuard {
+    fn default() -> Self {
+        Self::new(10, 3)
+    }
+}
+
+impl RepetitionGuard {
+    /// 检查最近 `window` 次调用，第 `threshold` 次相同的调用时提醒模型（小于 2 的值按 2 处理）
+    pub fn new(window: usize, threshold: usize) -> Self {
+        Self {
+            window,
+            threshold: threshold.max(2),
+            action: RepetitionAction::Nudge,
+        }
+    }
+
+    pub fn with_action(mut self, action: RepetitionAction) -> Self {
+        self.action = action;
+        self
+    }
+}
+
+/// 工具名与参数的哈希；对象按键排序，与模型输出的键顺序无关
+fn call_key(call: &ToolCall) -> u64 {
+    fn hash_value(value: &Value, hasher: &mut DefaultHasher) {
+        match value {
+            Value::Object(map) => {
+                let mut entries: Vec<_> = map.iter().collect();
+                entries.sort_unstable_by_key(|(key, _)| *key);
+                entries.len().hash(hasher);
+                for (key, value) in entries {
+                    key.hash(hasher);
+                    hash_value(value, hasher);
+                }
+            }
+            Value::Array(items) => {
+                items.len().hash(hasher);
+                items.iter().for_each(|item| hash_value(item, hasher));
+            }
+            // 标量的 JSON 文本已经是规范形式，并带有类型信息
+            scalar => scalar.to_string().hash(hasher),
+        }
+    }
+
+    let mut hasher = DefaultHasher::new();
+    call.function_name().hash(&mut hasher);
+    match call.arguments() {
+        Ok(args) => hash_value(&args, &mut hasher),
+        Err(_) => hash_value(&call.function.arguments, &mut hasher),
+    }
+    hasher.finish()
+}
+
+/// 最后一条用户消息之后、本轮之前最近 `window` 次工具调用的 [`call_key`]
+fn recent_call_keys(state: &MessagesState, window: usize) -> Vec<u64> {
+    let mut keys = Vec::new();
+    let mut assistants = 0;
+    for message in state.messages.iter().rev() {
+        match message.as_ref() {
+            Message::User { .. } => break,
+            Message::Assistant { tool_calls, .. } => {
+                assistants += 1;
+                // 最后一条助手消息是本轮要执行的调用
+                if assistants > 1 {
+                    keys.extend(tool_calls.iter().flatten().rev().map(call_key));
+                }
+                if keys.len() >= window {
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+    keys.truncate(window);
+    keys
+}
+
 pub struct ToolNode<E>
 where
     E: Send + Sync + 'static,
 {
     pub middleware: Option<Arc<ToolMiddleware<E>>>,
     pub tools: HashMap<String, Arc<ToolFn<E>>>,
+    /// 支持增量输出的工具；流式运行时代替 `tools` 中的同名函数执行，
+    /// 输出片段作为 [`ChatStreamEvent::ToolChunk`] 发出
+    pub stream_tools: HashMap<String, Arc<ToolStreamFn<E>>>,
+    /// 连续请求未知工具达到该次数后以 [`AgentError::UnknownToolLimitExceeded`] 结束运行，
+    /// `None` 表示不限制
+    pub unknown_tool_limit: Option<usize>,
+    /// 各工具的参数 JSON Schema；设置后调用前先校验参数，不满足时不执行工具，
+    /// 把出错的字段作为工具结果返回给模型。`None` 表示不校验
+    pub argument_schemas: Option<HashMap<String, Value>>,
+    /// 各工具的参数 JSON Schema；设置后调用前先按 schema 转换类型宽松的参数
+    /// （如 `"5"` → `5`、`"true"` → `true`），再进行校验。`None` 表示不转换
+    pub coercion_schemas: Option<HashMap<String, Value>>,
+    /// 各工具结果的 JSON Schema；设置后校验工具返回的结果，不满足时按
+    /// `output_validation` 处理。`None` 表示不校验
+    pub output_schemas: Option<HashMap<String, Value>>,
+    pub output_validation: OutputValidation,
+    /// 终止工具的名称：本轮中任一终止工具调用成功时，更新中请求 [`JumpTo::End`]，
+    /// 同一轮的其他工具调用照常执行并记录结果
+    pub terminal_tools: HashSet<String>,
+    /// 工具返回可重试的错误（按 [`ErrorCategory`] 判断）时先按退避重试，
+    /// 仍失败才把错误作为工具结果返回给模型。`None` 表示不重试
+    pub retry: Option<Arc<ToolRetry<E>>>,
+    /// 同一轮中同时执行的工具调用数上限，`None` 表示全部同时执行
+    pub max_concurrency: Option<usize>,
+    /// 重复工具调用检测，`None` 表示不检测
+    pub repetition_guard: Option<RepetitionGuard>,
+    /// 工具执行失败时返回给模型的内容，`None` 时使用 [`ToolErrorFormatter::concise`]
+    pub error_formatter: Option<Arc<ToolErrorFormatter<E>>>,
+    /// 返回给模型的工具结果的最大字符数，超出部分被截断并标注 `[truncated N chars]`，
+    /// `None` 表示不限制
+    pub max_output_chars: Option<usize>,
+    /// 各工具的结果字符数上限，优先于 `max_output_chars`
+    pub output_limits: HashMap<String, usize>,
+    /// 在 `tool.call` span 中记录工具参数和结果，见 [`crate::spans`]
+    pub trace_content: bool,
 }
 
 impl<E> ToolNode<E>
@@ -37,7 +281,21 @@ where
     pub fn new(tools: HashMap<String, Arc<ToolFn<E>>>) -> Self {
         Self {
             tools,
+            stream_tools: HashMap::new(),
             middleware: None,
+            unknown_tool_limit: None,
+            argument_schemas: None,
+            coercion_schemas: None,
+            output_schemas: None,
+            output_validation: OutputValidation::Warn,
+            terminal_tools: HashSet::new(),
+            retry: None,
+            max_concurrency: None,
+            repetition_guard: None,
+            error_formatter: None,
+            max_output_chars: None,
+            output_limits: HashMap::new(),
+            trace_content: false,
         }
     }
 
@@ -51,31 +309,347 @@ where
         self.middleware = Some(Arc::new(Box::new(f)));
         self
     }
+
+    /// 见 [`ToolNode::stream_tools`]
+    pub fn with_streaming_tools(mut self, tools: HashMap<String, Arc<ToolStreamFn<E>>>) -> Self {
+        self.stream_tools = tools;
+        self
+    }
+
+    pub fn with_unknown_tool_limit(mut self, limit: usize) -> Self {
+        self.unknown_tool_limit = Some(limit);
+        self
+    }
+
+    /// 按工具名对应的 schema 校验参数，没有 schema 的工具不校验
+    pub fn with_argument_validation(mut self, schemas: HashMap<String, Value>) -> Self {
+        self.argument_schemas = Some(schemas);
+        self
+    }
+
+    /// 按工具名对应的 schema 转换参数类型，没有 schema 的工具不转换
+    pub fn with_argument_coercion(mut self, schemas: HashMap<String, Value>) -> Self {
+        self.coercion_schemas = Some(schemas);
+        self
+    }
+
+    /// 按工具名对应的 schema 校验工具结果，没有 schema 的工具不校验
+    pub fn with_output_validation(
+        mut self,
+        schemas: HashMap<String, Value>,
+        validation: OutputValidation,
+    ) -> Self {
+        self.output_schemas = Some(schemas);
+        self.output_validation = validation;
+        self
+    }
+
+    /// 这些工具调用成功后结束运行，见 [`ToolNode::terminal_tools`]
+    pub fn with_terminal_tools<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.terminal_tools = names.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// 见 [`ToolNode::max_concurrency`]，小于 1 的值按 1 处理
+    pub fn with_max_concurrency(mut self, limit: usize) -> Self {
+        self.max_concurrency = Some(limit.max(1));
+        self
+    }
+
+    /// 见 [`RepetitionGuard`]
+    pub fn with_repetition_guard(mut self, guard: RepetitionGuard) -> Self {
+        self.repetition_guard = Some(guard);
+        self
+    }
+
+    /// 见 [`ToolErrorFormatter`]
+    pub fn with_error_formatter(mut self, formatter: ToolErrorFormatter<E>) -> Self {
+        self.error_formatter = Some(Arc::new(formatter));
+        self
+    }
+
+    /// 见 [`ToolNode::max_output_chars`]
+    pub fn with_max_output_chars(mut self, limit: usize) -> Self {
+        self.max_output_chars = Some(limit);
+        self
+    }
+
+    /// 单独设置某个工具的结果字符数上限，见 [`ToolNode::output_limits`]
+    pub fn with_output_limit<S: Into<String>>(mut self, name: S, limit: usize) -> Self {
+        self.output_limits.insert(name.into(), limit);
+        self
+    }
+
+    /// 临时性错误和速率限制按 `config` 重试，参数校验等错误直接返回给模型
+    pub fn with_retry(mut self, config: RetryConfig) -> Self
+    where
+        E: LangChainError,
+    {
+        self.retry = Some(Arc::new(ToolRetry {
+            config,
+            category: E::category,
+            retry_delay_ms: E::retry_delay_ms,
+        }));
+        self
+    }
+
+    /// 按工具 schema 就地转换参数类型，并记录被转换的字段
+    fn coerce_arguments(&self, name: &str, args: &mut Value) {
+        let Some(schema) = self.coercion_schemas.as_ref().and_then(|s| s.get(name)) else {
+            return;
+        };
+        let coerced = schema::coerce(schema, args);
+        if !coerced.is_empty() {
+            tracing::info!("Coerced arguments of tool `{name}` at {coerced:?}");
+        }
+    }
+
+    /// 按工具的字符数上限截断结果，只在字符边界处截断
+    fn limit_output(&self, name: &str, content: String) -> String {
+        let Some(limit) = self
+            .output_limits
+            .get(name)
+            .copied()
+            .or(self.max_output_chars)
+        else {
+            return content;
+        };
+        let Some((end, _)) = content.char_indices().nth(limit) else {
+            return content;
+        };
+        let truncated = content[end..].chars().count();
+        tracing::warn!("Output of tool `{name}` truncated by {truncated} chars");
+        format!("{}\n[truncated {truncated} chars]", &content[..end])
+    }
+
+    /// 参数不满足工具 schema 时返回给模型的错误消息
+    fn invalid_arguments(&self, name: &str, args: &Value) -> Option<String> {
+        let schema = self.argument_schemas.as_ref()?.get(name)?;
+        let violation = schema::validate(schema, args).err()?;
+        Some(format!(
+            "Error: invalid arguments for tool `{name}` at `{}`: {}",
+            violation.path, violation.message
+        ))
+    }
 }
 
-#[async_trait]
-impl<E> Node<MessagesState, MessagesState, AgentError, ChatStreamEvent> for ToolNode<E>
+/// 在 `tool.call` span 中执行一次工具调用，结束时记录是否成功和耗时，
+/// `trace_content` 时还记录参数和结果，见 [`crate::spans`]
+fn traced(
+    fut: Pin<Box<dyn Future<Output = ToolOutcome> + Send>>,
+    call: &ToolCall,
+    thread_id: Option<&str>,
+    trace_content: bool,
+) -> Pin<Box<dyn Future<Output = ToolOutcome> + Send>> {
+    let span = tracing::info_span!(
+        TOOL_CALL_SPAN,
+        thread_id,
+        tool_name = call.function_name(),
+        tool_call_id = call.id(),
+        success = field::Empty,
+        duration_ms = field::Empty,
+        input = field::Empty,
+        output = field::Empty,
+    );
+    if trace_content {
+        match &call.function.arguments {
+            Value::String(raw) => span.record("input", raw.as_str()),
+            arguments => span.record("input", arguments.to_string()),
+        };
+    }
+    Box::pin(async move {
+        let started = Instant::now();
+        let outcome = fut.instrument(span.clone()).await;
+        span.record("success", outcome.1.is_ok());
+        span.record("duration_ms", started.elapsed().as_millis() as u64);
+        if trace_content {
+            let (Ok(output) | Err(output)) = &outcome.1;
+            span.record("output", output.as_str());
+        }
+        outcome
+    })
+}
+
+/// 包装工具函数：每次调用都在 `retry` 的策略下重试
+fn with_retry<E>(handler: Arc<ToolFn<E>>, retry: Arc<ToolRetry<E>>) -> Arc<ToolFn<E>>
 where
     E: Error + Send + Sync + 'static,
 {
-    async fn run_sync(
+    Arc::new(move |args: Value| {
+        let handler = handler.clone();
+        let retry = retry.clone();
+        Box::pin(async move {
+            retry_with_backoff_hint(
+                || (handler)(args.clone()),
+                retry.category,
+                retry.retry_delay_ms,
+                &retry.config,
+            )
+            .await
+        })
+    })
+}
+
+/// 包装增量输出的工具函数：片段作为 [`ChatStreamEvent::ToolChunk`] 发送到 `events`
+fn forward_chunks<E>(
+    handler: Arc<ToolStreamFn<E>>,
+    events: UnboundedSender<ChatStreamEvent>,
+    id: String,
+    name: String,
+) -> Arc<ToolFn<E>>
+where
+    E: 'static,
+{
+    let on_chunk: Arc<ToolChunkFn> = Arc::new(move |chunk| {
+        let _ = events.unbounded_send(ChatStreamEvent::ToolChunk {
+            id: id.clone(),
+            name: name.clone(),
+            chunk,
+        });
+    });
+    Arc::new(move |args| (handler)(args, on_chunk.clone()))
+}
+
+/// 等待 `work` 完成，期间把收到的事件转发到 `sink`
+async fn emit_until<T>(
+    mut work: impl Future<Output = T> + Unpin,
+    mut events: UnboundedReceiver<ChatStreamEvent>,
+    sink: &dyn EventSink<ChatStreamEvent>,
+) -> T {
+    let output = loop {
+        match select(&mut work, events.next()).await {
+            Either::Left((output, _)) => break output,
+            Either::Right((Some(event), _)) => sink.emit(event).await,
+            Either::Right((None, work)) => break work.await,
+        }
+    };
+    // 工具完成前推送、尚未转发的片段
+    while let Ok(Some(event)) = events.try_next() {
+        sink.emit(event).await;
+    }
+    output
+}
+
+/// 从历史末尾往前数，连续的未知工具错误有多少条；遇到用户消息或其他工具结果即停止
+fn trailing_unknown_tool_errors(state: &MessagesState) -> usize {
+    let mut count = 0;
+    for message in state.messages.iter().rev() {
+        match message.as_ref() {
+            Message::Tool { content, .. } if content.starts_with(UNKNOWN_TOOL_ERROR) => count += 1,
+            Message::Tool { .. } | Message::User { .. } => break,
+            _ => {}
+        }
+    }
+    count
+}
+
+impl<E> ToolNode<E>
+where
+    E: Error + Send + Sync + 'static,
+{
+    /// 执行最后一条助手消息中的工具调用；提供 `sink` 时使用增量输出的工具函数并转发输出片段
+    async fn execute(
         &self,
         input: &MessagesState,
         context: NodeContext<'_>,
+        sink: Option<&dyn EventSink<ChatStreamEvent>>,
     ) -> Result<MessagesState, AgentError> {
         let mut delta = MessagesState::default();
         if let Some(calls) = input.last_tool_calls() {
-            let mut futures = Vec::new();
-            let mut ids = Vec::new();
+            // 结果按 id 回填，重复的 id 会让结果无法与调用一一对应
+            let mut seen = HashSet::new();
+            if let Some(call) = calls.iter().find(|call| !seen.insert(call.id())) {
+                return Err(AgentError::DuplicateToolCallId(call.id().to_owned()));
+            }
+
+            let mut futures: Vec<Pin<Box<dyn Future<Output = ToolOutcome> + Send>>> = Vec::new();
+            let (chunk_tx, chunk_rx) = unbounded();
+            let mut unknown = trailing_unknown_tool_errors(input);
+            let mut recent = self
+                .repetition_guard
+                .map(|guard| recent_call_keys(input, guard.window))
+                .unwrap_or_default();
             tracing::debug!("Tool calls count: {}", calls.len());
             for call in calls {
-                if let Some(handler) = self.tools.get(call.function_name()) {
-                    ids.push(call.id().to_owned());
+                if let Some(guard) = &self.repetition_guard {
+                    let key = call_key(call);
+                    let repeats = 1 + recent.iter().filter(|k| **k == key).count();
+                    // 同一轮中相同的调用也计入
+                    recent.push(key);
+                    if repeats >= guard.threshold {
+                        let name = call.function_name();
+                        tracing::warn!(
+                            "Tool `{name}` called {repeats} times with the same arguments"
+                        );
+                        if guard.action == RepetitionAction::Stop {
+                            return Err(AgentError::RepeatedToolCall {
+                                tool: name.to_owned(),
+                                calls: repeats,
+                            });
+                        }
+                        let id = call.id().to_owned();
+                        let msg = format!(
+                            "Error: `{name}` was already called {} times with the same arguments. \
+                             Do not repeat it; use the earlier results or try a different approach.",
+                            repeats - 1
+                        );
+                        futures.push(Box::pin(async move { (id, Err(msg), false) }));
+                        continue;
+                    }
+                }
+                // 本次运行未开放的工具与未注册的工具同样处理
+                let allowed =
+                    context.config.tools.as_ref().is_none_or(|allowed| {
+                        allowed.iter().any(|name| name == call.function_name())
+                    });
+                if let Some(handler) = self.tools.get(call.function_name()).filter(|_| allowed) {
+                    let id = call.id().to_owned();
+                    let terminal = self.terminal_tools.contains(call.function_name());
+                    let name = call.function_name().to_owned();
+                    let formatter = self.error_formatter.clone();
+                    let output_schema = self
+                        .output_schemas
+                        .as_ref()
+                        .and_then(|s| s.get(call.function_name()))
+                        .cloned();
+                    let output_validation = self.output_validation;
                     tracing::debug!("Tool call: {:?}", call.function);
 
-                    let fut: Pin<Box<dyn Future<Output = String> + Send>> = match call.arguments() {
+                    let fut: Pin<Box<dyn Future<Output = ToolOutcome> + Send>> = match call
+                        .arguments()
+                        .map_err(|e| format!("Error: Failed to parse arguments: {}", e))
+                        .map(|mut args| {
+                            self.coerce_arguments(call.function_name(), &mut args);
+                            args
+                        })
+                        .and_then(|args| {
+                            match self.invalid_arguments(call.function_name(), &args) {
+                                Some(msg) => Err(msg),
+                                None => Ok(args),
+                            }
+                        }) {
                         Ok(args) => {
-                            let handler = handler.clone();
+                            let handler = match self
+                                .stream_tools
+                                .get(call.function_name())
+                                .filter(|_| sink.is_some())
+                            {
+                                Some(stream) => forward_chunks(
+                                    stream.clone(),
+                                    chunk_tx.clone(),
+                                    id.clone(),
+                                    call.function_name().to_owned(),
+                                ),
+                                None => handler.clone(),
+                            };
+                            let handler = match &self.retry {
+                                Some(retry) => with_retry(handler, retry.clone()),
+                                None => handler,
+                            };
                             let fut = if let Some(middleware) = &self.middleware {
                                 let handler: ToolHandler<E> = Box::new(move |args| (handler)(args));
                                 (middleware)(input, &context, call.function_name(), args, handler)
@@ -87,39 +661,554 @@ where
                                 match fut.await {
                                     Ok(value) => {
                                         tracing::debug!("Tool call result: {}", value);
-                                        value.to_string()
+                                        if let Some(schema) = &output_schema
+                                            && let Err(violation) = schema::validate(schema, &value)
+                                        {
+                                            let msg = format!(
+                                                "tool `{name}` returned an unexpected result at `{}`: {}",
+                                                violation.path, violation.message
+                                            );
+                                            tracing::warn!("{msg}");
+                                            if output_validation == OutputValidation::Reject {
+                                                return (id, Err(format!("Error: {msg}")), false);
+                                            }
+                                        }
+                                        (id, Ok(value.to_string()), terminal)
                                     }
                                     Err(e) => {
-                                        tracing::error!("Tool call failed: {}", e);
-                                        format!("Error: {}", e)
+                                        // 完整的错误只记录在日志中
+                                        tracing::error!("Tool `{name}` failed: {e:?}");
+                                        let msg = match formatter {
+                                            Some(formatter) => formatter.format(&name, &e),
+                                            None => ToolErrorFormatter::concise().format(&name, &e),
+                                        };
+                                        (id, Err(msg), false)
                                     }
                                 }
                             })
                         }
-                        Err(e) => {
-                            let msg = format!("Error: Failed to parse arguments: {}", e);
+                        Err(msg) => {
                             tracing::error!("{}", msg);
-                            Box::pin(async move { msg })
+                            Box::pin(async move { (id, Err(msg), false) })
                         }
                     };
 
-                    futures.push(fut);
+                    futures.push(traced(
+                        fut,
+                        call,
+                        context.config.thread_id.as_deref(),
+                        self.trace_content,
+                    ));
+                } else {
+                    let name = call.function_name();
+                    unknown += 1;
+                    if let Some(limit) = self.unknown_tool_limit
+                        && unknown >= limit
+                    {
+                        return Err(AgentError::UnknownToolLimitExceeded {
+                            tool: name.to_owned(),
+                            attempts: unknown,
+                        });
+                    }
+                    tracing::warn!("Tool not found: {}", name);
+                    let id = call.id().to_owned();
+                    let msg = format!("{UNKNOWN_TOOL_ERROR} `{name}`");
+                    futures.push(Box::pin(async move { (id, Err(msg), false) }));
                 }
             }
-            let results = join_all(futures).await;
-            for (id, content) in ids.into_iter().zip(results.into_iter()) {
-                delta.push_message_owned(Message::tool(content, id));
+            drop(chunk_tx);
+            // 上面只构造了尚未开始的 future，这里最多同时推进 `limit` 个，
+            // 结果按完成顺序收集，再按 id 回填
+            let limit = self.max_concurrency.unwrap_or(futures.len()).max(1);
+            let outcomes = futures::stream::iter(futures)
+                .buffer_unordered(limit)
+                .collect::<Vec<ToolOutcome>>();
+            let outcomes = match sink {
+                Some(sink) => emit_until(outcomes, chunk_rx, sink).await,
+                None => outcomes.await,
+            };
+            let mut finished = false;
+            let mut results: HashMap<String, Result<String, String>> = outcomes
+                .into_iter()
+                .map(|(id, content, terminal)| {
+                    finished |= terminal;
+                    (id, content)
+                })
+                .collect();
+            let (mut succeeded, mut failed) = (Vec::new(), Vec::new());
+            // 按模型给出的调用顺序输出，与各个工具完成的先后无关
+            for call in calls {
+                let id = call.id().to_owned();
+                match results.remove(call.id()) {
+                    Some(Ok(content)) => {
+                        let content = self.limit_output(call.function_name(), content);
+                        delta.push_message_owned(Message::tool(content, id.clone()));
+                        succeeded.push(id);
+                    }
+                    // 模型看到的内容不变，失败只记录在附加信息中
+                    Some(Err(msg)) => {
+                        let msg = self.limit_output(call.function_name(), msg);
+                        delta.push_message_owned(
+                            Message::tool(msg, id.clone())
+                                .with_metadata(TOOL_FAILED_METADATA, Value::Bool(true)),
+                        );
+                        failed.push(id);
+                    }
+                    None => {}
+                }
+            }
+            if !failed.is_empty() {
+                tracing::warn!(
+                    "{} of {} tool calls failed: {failed:?}",
+                    failed.len(),
+                    calls.len()
+                );
+            }
+            if let Some(sink) = sink {
+                sink.emit(ChatStreamEvent::ToolResults { succeeded, failed })
+                    .await;
+            }
+            if finished {
+                tracing::debug!("terminal tool succeeded, ending the run");
+                delta.jump(JumpTo::End);
             }
         }
         Ok(delta)
     }
+}
+
+#[async_trait]
+impl<E> Node<MessagesState, MessagesState, AgentError, ChatStreamEvent> for ToolNode<E>
+where
+    E: Error + Send + Sync + 'static,
+{
+    async fn run_sync(
+        &self,
+        input: &MessagesState,
+        context: NodeContext<'_>,
+    ) -> Result<MessagesState, AgentError> {
+        self.execute(input, context, None).await
+    }
 
     async fn run_stream(
         &self,
         input: &MessagesState,
-        _sink: &dyn EventSink<ChatStreamEvent>,
+        sink: &dyn EventSink<ChatStreamEvent>,
         context: NodeContext<'_>,
     ) -> Result<MessagesState, AgentError> {
-        self.run_sync(input, context).await
+        self.execute(input, context, Some(sink)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use langchain_core::message::{FunctionCall, ToolCall};
+    use langgraph::checkpoint::Configuration;
+    use std::time::Duration;
+
+    /// 按参数中的 `delay` 毫秒延迟后原样返回 `text`，用来打乱完成顺序
+    fn echo_node() -> ToolNode<std::io::Error> {
+        let echo: Arc<ToolFn<std::io::Error>> = Arc::new(|args: Value| {
+            Box::pin(async move {
+                let delay = args["delay"].as_u64().unwrap_or_default();
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+                Ok(args["text"].clone())
+            })
+        });
+        ToolNode::new(HashMap::from([("echo".to_owned(), echo)]))
+    }
+
+    fn call(id: &str, text: &str, delay: u64) -> ToolCall {
+        ToolCall {
+            id: id.to_owned(),
+            type_name: "function".to_owned(),
+            function: FunctionCall {
+                name: "echo".to_owned(),
+                arguments: serde_json::json!({ "text": text, "delay": delay }),
+            },
+        }
+    }
+
+    fn state_with_calls(calls: Vec<ToolCall>) -> MessagesState {
+        MessagesState::new(vec![Message::Assistant {
+            content: String::new(),
+            reasoning_content: None,
+            tool_calls: Some(calls),
+            name: None,
+            metadata: Default::default(),
+        }])
+    }
+
+    #[tokio::test]
+    async fn results_are_matched_by_call_id() {
+        let state = state_with_calls(vec![call("call_a", "a", 30), call("call_b", "b", 0)]);
+        let config = Configuration::default();
+
+        let delta = echo_node()
+            .run_sync(&state, NodeContext::from_config(&config))
+            .await
+            .unwrap();
+
+        let results: Vec<_> = delta
+            .messages
+            .iter()
+            .map(|m| match m.as_ref() {
+                Message::Tool {
+                    tool_call_id,
+                    content,
+                    ..
+                } => (tool_call_id.as_str(), content.as_str()),
+                other => panic!("unexpected message: {other:?}"),
+            })
+            .collect();
+        assert_eq!(results, [("call_a", "\"a\""), ("call_b", "\"b\"")]);
+    }
+
+    #[tokio::test]
+    async fn duplicate_call_ids_are_rejected() {
+        let state = state_with_calls(vec![call("call_a", "a", 0), call("call_a", "b", 0)]);
+        let config = Configuration::default();
+
+        let result = echo_node()
+            .run_sync(&state, NodeContext::from_config(&config))
+            .await;
+
+        assert!(matches!(result, Err(AgentError::DuplicateToolCallId(id)) if id == "call_a"));
+    }
+
+    #[tokio::test]
+    async fn repeated_unknown_tools_hit_the_limit() {
+        let mut missing = call("call_1", "a", 0);
+        missing.function.name = "missing".to_owned();
+        let config = Configuration::default();
+        let node = echo_node().with_unknown_tool_limit(2);
+
+        // 第一次：返回错误消息，让模型有机会纠正
+        let mut state = state_with_calls(vec![missing.clone()]);
+        let delta = node
+            .run_sync(&state, NodeContext::from_config(&config))
+            .await
+            .unwrap();
+        assert_eq!(
+            delta.last_message().unwrap().content(),
+            "Error: unknown tool `missing`"
+        );
+
+        // 第二次连续请求同一个不存在的工具：结束运行
+        state.append_messages(delta.messages);
+        missing.id = "call_2".to_owned();
+        state.push_message_owned(Message::Assistant {
+            content: String::new(),
+            reasoning_content: None,
+            tool_calls: Some(vec![missing]),
+            name: None,
+            metadata: Default::default(),
+        });
+        let result = node
+            .run_sync(&state, NodeContext::from_config(&config))
+            .await;
+        assert!(matches!(
+            result,
+            Err(AgentError::UnknownToolLimitExceeded { attempts: 2, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn concurrency_limit_bounds_in_flight_calls() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let tracked: Arc<ToolFn<std::io::Error>> = {
+            let (running, peak) = (running.clone(), peak.clone());
+            Arc::new(move |args: Value| {
+                let (running, peak) = (running.clone(), peak.clone());
+                Box::pin(async move {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    // 让后面的调用先完成，打乱完成顺序
+                    let delay = 50 - args["index"].as_u64().unwrap_or_default();
+                    tokio::time::sleep(Duration::from_millis(delay)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    Ok(args["index"].clone())
+                })
+            })
+        };
+        let node =
+            ToolNode::new(HashMap::from([("echo".to_owned(), tracked)])).with_max_concurrency(4);
+
+        let mut calls: Vec<_> = (0..50)
+            .map(|i| {
+                let mut call = call(&format!("call_{i}"), "", 0);
+                call.function.arguments = serde_json::json!({ "index": i });
+                call
+            })
+            .collect();
+        calls[7].function.name = "missing".to_owned();
+        let state = state_with_calls(calls);
+        let config = Configuration::default();
+
+        let delta = node
+            .run_sync(&state, NodeContext::from_config(&config))
+            .await
+            .unwrap();
+
+        assert!(peak.load(Ordering::SeqCst) <= 4);
+        assert_eq!(delta.messages.len(), 50);
+        for (i, message) in delta.messages.iter().enumerate() {
+            let Message::Tool {
+                tool_call_id,
+                content,
+                ..
+            } = message.as_ref()
+            else {
+                panic!("unexpected message: {message:?}");
+            };
+            assert_eq!(tool_call_id, &format!("call_{i}"));
+            if i == 7 {
+                assert_eq!(content, "Error: unknown tool `missing`");
+            } else {
+                assert_eq!(content, &i.to_string());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn transient_tool_errors_are_retried_and_validation_errors_are_not() {
+        use langchain_core::error::ToolError;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let counter = attempts.clone();
+        let flaky: Arc<ToolFn<ToolError>> = Arc::new(move |args: Value| {
+            let attempt = counter.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async move {
+                match args["text"].as_str() {
+                    Some("bad") => Err(ToolError::InvalidArguments("text".to_owned())),
+                    _ if attempt < 2 => Err(ToolError::Timeout("echo".to_owned())),
+                    _ => Ok(args["text"].clone()),
+                }
+            })
+        });
+        let node =
+            ToolNode::new(HashMap::from([("echo".to_owned(), flaky)])).with_retry(RetryConfig {
+                max_retries: 3,
+                initial_delay_ms: 1,
+                ..Default::default()
+            });
+        let config = Configuration::default();
+
+        // 超时属于临时性错误：重试两次后成功
+        let state = state_with_calls(vec![call("call_a", "a", 0)]);
+        let delta = node
+            .run_sync(&state, NodeContext::from_config(&config))
+            .await
+            .unwrap();
+        assert_eq!(delta.messages[0].content(), "\"a\"");
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+        // 参数错误不重试，直接返回给模型
+        let state = state_with_calls(vec![call("call_b", "bad", 0)]);
+        let delta = node
+            .run_sync(&state, NodeContext::from_config(&config))
+            .await
+            .unwrap();
+        assert!(delta.messages[0].content().starts_with("Error: "));
+        assert_eq!(attempts.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn tool_errors_are_formatted_for_the_model() {
+        use langchain_core::error::ToolError;
+
+        let failing: Arc<ToolFn<ToolError>> = Arc::new(|_| {
+            Box::pin(async {
+                Err(ToolError::ExecutionFailed(
+                    "disk full\n  at write_file (fs.rs:10)".to_owned(),
+                ))
+            })
+        });
+        let tools = HashMap::from([("echo".to_owned(), failing)]);
+        let state = state_with_calls(vec![call("call_a", "a", 0)]);
+        let config = Configuration::default();
+
+        // 默认只保留第一行，不带出调用栈
+        let delta = ToolNode::new(tools.clone())
+            .run_sync(&state, NodeContext::from_config(&config))
+            .await
+            .unwrap();
+        assert_eq!(
+            delta.messages[0].content(),
+            "Error: tool `echo` failed: Tool execution failed: disk full"
+        );
+
+        let node = ToolNode::new(tools.clone()).with_error_formatter(
+            ToolErrorFormatter::concise().with_retry_hint(DEFAULT_RETRY_HINT),
+        );
+        let delta = node
+            .run_sync(&state, NodeContext::from_config(&config))
+            .await
+            .unwrap();
+        assert!(
+            delta.messages[0]
+                .content()
+                .ends_with(&format!("disk full\n{DEFAULT_RETRY_HINT}"))
+        );
+
+        let node = ToolNode::new(tools).with_error_formatter(ToolErrorFormatter::custom(
+            |name, _: &ToolError| format!("{name} is unavailable"),
+        ));
+        let delta = node
+            .run_sync(&state, NodeContext::from_config(&config))
+            .await
+            .unwrap();
+        assert_eq!(delta.messages[0].content(), "echo is unavailable");
+    }
+
+    #[tokio::test]
+    async fn long_outputs_are_truncated_on_char_boundaries() {
+        let state = state_with_calls(vec![call("call_a", "héllo wörld", 0)]);
+        let config = Configuration::default();
+
+        let delta = echo_node()
+            .with_max_output_chars(4)
+            .run_sync(&state, NodeContext::from_config(&config))
+            .await
+            .unwrap();
+        assert_eq!(delta.messages[0].content(), "\"hél\n[truncated 9 chars]");
+
+        // 单个工具的上限优先于全局上限
+        let delta = echo_node()
+            .with_max_output_chars(4)
+            .with_output_limit("echo", 100)
+            .run_sync(&state, NodeContext::from_config(&config))
+            .await
+            .unwrap();
+        assert_eq!(delta.messages[0].content(), "\"héllo wörld\"");
+    }
+
+    #[tokio::test]
+    async fn coerced_arguments_pass_validation() {
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": {"text": {"type": "string"}, "delay": {"type": "integer"}},
+            "required": ["text"]
+        });
+        let schemas = HashMap::from([("echo".to_owned(), schema)]);
+        let mut loose = call("call_a", "a", 0);
+        loose.function.arguments = serde_json::json!({ "text": "a", "delay": "5" });
+        let state = state_with_calls(vec![loose]);
+        let config = Configuration::default();
+
+        // 只校验时字符串形式的整数被拒绝
+        let strict = echo_node().with_argument_validation(schemas.clone());
+        let delta = strict
+            .run_sync(&state, NodeContext::from_config(&config))
+            .await
+            .unwrap();
+        assert!(
+            delta.messages[0]
+                .content()
+                .starts_with("Error: invalid arguments")
+        );
+
+        let lenient = echo_node()
+            .with_argument_coercion(schemas.clone())
+            .with_argument_validation(schemas);
+        let delta = lenient
+            .run_sync(&state, NodeContext::from_config(&config))
+            .await
+            .unwrap();
+        assert_eq!(delta.messages[0].content(), "\"a\"");
+    }
+
+    #[tokio::test]
+    async fn invalid_arguments_are_reported_without_running_the_tool() {
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": {"text": {"type": "string"}, "delay": {"type": "integer"}},
+            "required": ["text"]
+        });
+        let node =
+            echo_node().with_argument_validation(HashMap::from([("echo".to_owned(), schema)]));
+        let mut bad = call("call_a", "a", 0);
+        bad.function.arguments = serde_json::json!({ "delay": "soon" });
+        let state = state_with_calls(vec![bad, call("call_b", "b", 0)]);
+        let config = Configuration::default();
+
+        let delta = node
+            .run_sync(&state, NodeContext::from_config(&config))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            delta.messages[0].content(),
+            "Error: invalid arguments for tool `echo` at `$.text`: required property is missing"
+        );
+        assert_eq!(delta.messages[1].content(), "\"b\"");
+    }
+
+    #[tokio::test]
+    async fn streaming_tools_forward_chunks_in_stream_mode() {
+        use std::sync::Mutex;
+
+        #[derive(Default)]
+        struct Collect(Mutex<Vec<ChatStreamEvent>>);
+
+        #[async_trait]
+        impl EventSink<ChatStreamEvent> for Collect {
+            async fn emit(&self, event: ChatStreamEvent) {
+                self.0.lock().unwrap().push(event);
+            }
+        }
+
+        // 逐词推送 `text`，最后返回完整内容
+        let words: Arc<ToolStreamFn<std::io::Error>> = Arc::new(|args: Value, on_chunk| {
+            Box::pin(async move {
+                let text = args["text"].as_str().unwrap_or_default().to_owned();
+                for word in text.split(' ') {
+                    on_chunk(word.to_owned());
+                    tokio::task::yield_now().await;
+                }
+                Ok(Value::String(text))
+            })
+        });
+        let node = echo_node().with_streaming_tools(HashMap::from([("echo".to_owned(), words)]));
+        let state = state_with_calls(vec![call("call_a", "one two three", 0)]);
+        let config = Configuration::default();
+
+        let sink = Collect::default();
+        let delta = node
+            .run_stream(&state, &sink, NodeContext::from_config(&config))
+            .await
+            .unwrap();
+        assert_eq!(delta.messages[0].content(), "\"one two three\"");
+        let chunks: Vec<_> = sink
+            .0
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .filter_map(|event| match event {
+                ChatStreamEvent::ToolChunk { id, name, chunk } => {
+                    assert_eq!((id.as_str(), name.as_str()), ("call_a", "echo"));
+                    Some(chunk)
+                }
+                ChatStreamEvent::ToolResults { succeeded, failed } => {
+                    assert_eq!(succeeded, ["call_a"]);
+                    assert!(failed.is_empty());
+                    None
+                }
+                other => panic!("unexpected event: {other:?}"),
+            })
+            .collect();
+        assert_eq!(chunks, ["one", "two", "three"]);
+
+        // 非流式运行使用普通的工具函数
+        let delta = node
+            .run_sync(&state, NodeContext::from_config(&config))
+            .await
+            .unwrap();
+        assert_eq!(delta.messages[0].content(), "\"one two three\"");
     }
 }
@@ -0,0 +1,334 @@
+//! Export agent runs as OpenTelemetry spans following the [OpenInference]
+//! semantic conventions, so they show up in Phoenix, Langfuse and other LLM
+//! observability tools. Requires the `otel` feature.
+//!
+//! [`OpenInferenceLayer`] is a `tracing_subscriber` layer that turns the spans
+//! listed in [`crate::spans`] into OpenTelemetry spans on the given tracer:
+//!
+//! | `tracing` span | `openinference.span.kind` | attributes |
+//! |---|---|---|
+//! | `agent.step`, `graph.node` | `CHAIN` | `graph.node.id` |
+//! | `llm.call` | `LLM` | `llm.token_count.*`, `llm.input_messages.*`, `llm.output_messages.*` |
+//! | `tool.call` | `TOOL` | `tool.name`, `tool_call.id` |
+//!
+//! Every span also carries `session.id` (the thread id). The message and
+//! `input.value` / `output.value` attributes of model and tool calls need
+//! [`ReactAgentBuilder::with_content_tracing`](crate::ReactAgentBuilder::with_content_tracing).
+//! A failed tool call sets the span status to error. Other `tracing` spans are ignored, so the layer can
+//! sit next to a formatter in the same subscriber:
+//!
+//! ```ignore
+//! use opentelemetry::trace::TracerProvider;
+//! use tracing_subscriber::prelude::*;
+//!
+//! let tracer = provider.tracer("my-agent");
+//! tracing_subscriber::registry()
+//!     .with(OpenInferenceLayer::new(tracer))
+//!     .with(tracing_subscriber::fmt::layer())
+//!     .init();
+//! ```
+//!
+//! [OpenInference]: https://github.com/Arize-ai/openinference/blob/main/spec/semantic_conventions.md
+
+use opentelemetry::{
+    Context as OtelContext, KeyValue, Value as OtelValue,
+    trace::{Status, TraceContextExt, Tracer},
+};
+use serde_json::Value;
+use tracing::{
+    Subscriber,
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+};
+use tracing_subscriber::{Layer, layer::Context, registry::LookupSpan};
+
+use crate::spans::{AGENT_STEP_SPAN, LLM_CALL_SPAN, NODE_SPAN, TOOL_CALL_SPAN};
+
+/// `openinference.span.kind` of a span we export, `None` for spans we ignore.
+fn span_kind(name: &str) -> Option<&'static str> {
+    match name {
+        LLM_CALL_SPAN => Some("LLM"),
+        TOOL_CALL_SPAN => Some("TOOL"),
+        AGENT_STEP_SPAN | NODE_SPAN => Some("CHAIN"),
+        _ => None,
+    }
+}
+
+/// A `tracing_subscriber` layer exporting agent spans to an OpenTelemetry
+/// tracer with OpenInference attributes. See the [module docs](self).
+pub struct OpenInferenceLayer<T> {
+    tracer: T,
+}
+
+impl<T> OpenInferenceLayer<T>
+where
+    T: Tracer + 'static,
+    T::Span: Send + Sync + 'static,
+{
+    pub fn new(tracer: T) -> Self {
+        Self { tracer }
+    }
+}
+
+/// OpenTelemetry context of an exported span, kept in the span's extensions.
+struct Exported(OtelContext);
+
+/// Turns recorded `tracing` fields into OpenInference attributes.
+struct OpenInferenceFields<'a> {
+    kind: &'static str,
+    attributes: &'a mut Vec<KeyValue>,
+    failed: &'a mut bool,
+}
+
+impl OpenInferenceFields<'_> {
+    fn push(&mut self, key: impl Into<opentelemetry::Key>, value: impl Into<OtelValue>) {
+        self.attributes.push(KeyValue::new(key, value));
+    }
+
+    /// `input` / `output` hold a JSON array of messages for model calls and
+    /// plain text for tool calls.
+    fn push_content(&mut self, direction: &'static str, value: &str) {
+        self.push(format!("{direction}.value"), value.to_owned());
+        if self.kind != "LLM" {
+            return;
+        }
+        self.push(format!("{direction}.mime_type"), "application/json");
+        let Ok(Value::Array(messages)) = serde_json::from_str::<Value>(value) else {
+            return;
+        };
+        for (index, message) in messages.iter().enumerate() {
+            let prefix = format!("llm.{direction}_messages.{index}.message");
+            if let Some(role) = message.get("role").and_then(Value::as_str) {
+                self.push(format!("{prefix}.role"), role.to_owned());
+            }
+            match message.get("content") {
+                Some(Value::String(content)) => {
+                    self.push(format!("{prefix}.content"), content.clone());
+                }
+                Some(Value::Null) | None => {}
+                Some(content) => self.push(format!("{prefix}.content"), content.to_string()),
+            }
+            let tool_calls = message.get("tool_calls").and_then(Value::as_array);
+            for (call_index, call) in tool_calls.into_iter().flatten().enumerate() {
+                let call_prefix = format!("{prefix}.tool_calls.{call_index}.tool_call");
+                if let Some(id) = call.get("id").and_then(Value::as_str) {
+                    self.push(format!("{call_prefix}.id"), id.to_owned());
+                }
+                let function = call.get("function");
+                if let Some(name) = function.and_then(|f| f.get("name")).and_then(Value::as_str) {
+                    self.push(format!("{call_prefix}.function.name"), name.to_owned());
+                }
+                if let Some(arguments) = function.and_then(|f| f.get("arguments")) {
+                    let arguments = match arguments {
+                        Value::String(raw) => raw.clone(),
+                        other => other.to_string(),
+                    };
+                    self.push(format!("{call_prefix}.function.arguments"), arguments);
+                }
+            }
+        }
+    }
+}
+
+impl Visit for OpenInferenceFields<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "thread_id" => self.push("session.id", value.to_owned()),
+            "node" => self.push("graph.node.id", value.to_owned()),
+            "tool_name" => self.push("tool.name", value.to_owned()),
+            "tool_call_id" => self.push("tool_call.id", value.to_owned()),
+            "input" => self.push_content("input", value),
+            "output" => self.push_content("output", value),
+            _ => {}
+        }
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        let value = i64::try_from(value).unwrap_or(i64::MAX);
+        match field.name() {
+            "prompt_tokens" => self.push("llm.token_count.prompt", value),
+            "completion_tokens" => self.push("llm.token_count.completion", value),
+            "total_tokens" => self.push("llm.token_count.total", value),
+            "step" => self.push("graph.step", value),
+            _ => {}
+        }
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        if let Ok(value) = u64::try_from(value) {
+            self.record_u64(field, value);
+        }
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        if field.name() == "success" && !value {
+            *self.failed = true;
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn std::fmt::Debug) {}
+}
+
+/// Collects the OpenInference attributes of a set of recorded fields.
+fn collect(kind: &'static str, record: impl FnOnce(&mut dyn Visit)) -> (Vec<KeyValue>, bool) {
+    let mut attributes = Vec::new();
+    let mut failed = false;
+    record(&mut OpenInferenceFields {
+        kind,
+        attributes: &mut attributes,
+        failed: &mut failed,
+    });
+    (attributes, failed)
+}
+
+fn set_failed(cx: &OtelContext) {
+    cx.span().set_status(Status::error("tool call failed"));
+}
+
+impl<S, T> Layer<S> for OpenInferenceLayer<T>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    T: Tracer + 'static,
+    T::Span: Send + Sync + 'static,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(kind) = span_kind(attrs.metadata().name()) else {
+            return;
+        };
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let parent_cx = span
+            .scope()
+            .skip(1)
+            .find_map(|ancestor| {
+                ancestor
+                    .extensions()
+                    .get::<Exported>()
+                    .map(|exported| exported.0.clone())
+            })
+            .unwrap_or_else(OtelContext::current);
+
+        let (mut attributes, failed) = collect(kind, |visitor| attrs.record(visitor));
+        attributes.push(KeyValue::new("openinference.span.kind", kind));
+        let builder = self
+            .tracer
+            .span_builder(attrs.metadata().name())
+            .with_attributes(attributes);
+        let otel_span = self.tracer.build_with_context(builder, &parent_cx);
+        let cx = parent_cx.with_span(otel_span);
+        if failed {
+            set_failed(&cx);
+        }
+        span.extensions_mut().insert(Exported(cx));
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let Some(kind) = span_kind(span.name()) else {
+            return;
+        };
+        let extensions = span.extensions();
+        let Some(Exported(cx)) = extensions.get::<Exported>() else {
+            return;
+        };
+        let (attributes, failed) = collect(kind, |visitor| values.record(visitor));
+        cx.span().set_attributes(attributes);
+        if failed {
+            set_failed(cx);
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        if let Some(Exported(cx)) = span.extensions_mut().remove::<Exported>() {
+            cx.span().end();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc};
+
+    use langchain_core::{ToolError, message::Message, state::RegisteredTool};
+    use opentelemetry::trace::{SpanId, TracerProvider};
+    use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider, SpanData};
+    use tracing_subscriber::{Registry, prelude::*};
+
+    use super::*;
+    use crate::{ReactAgent, testing::MockModel};
+
+    fn attribute<'a>(span: &'a SpanData, key: &str) -> Option<&'a OtelValue> {
+        span.attributes
+            .iter()
+            .find(|kv| kv.key.as_str() == key)
+            .map(|kv| &kv.value)
+    }
+
+    #[tokio::test]
+    async fn agent_runs_export_openinference_spans() {
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let subscriber = Registry::default().with(OpenInferenceLayer::new(provider.tracer("test")));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let lookup = RegisteredTool::<ToolError>::new(
+            "lookup".to_owned(),
+            String::new(),
+            serde_json::json!({"type": "object"}),
+            Arc::new(|_| Box::pin(async { Err(ToolError::ExecutionFailed("down".to_owned())) })),
+        );
+        let model = MockModel::new([
+            MockModel::tool_call("lookup", serde_json::json!({"q": "rust"})),
+            Message::assistant("done"),
+        ]);
+        let agent = ReactAgent::builder(model)
+            .with_tools([lookup])
+            .with_content_tracing(true)
+            .build();
+        agent.invoke(Message::user("go"), None).await.unwrap();
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let by_id: HashMap<SpanId, &SpanData> = spans
+            .iter()
+            .map(|span| (span.span_context.span_id(), span))
+            .collect();
+        let kind = |span: &SpanData| attribute(span, "openinference.span.kind").cloned();
+
+        let llm_calls: Vec<_> = spans.iter().filter(|s| s.name == LLM_CALL_SPAN).collect();
+        assert_eq!(llm_calls.len(), 2);
+        let first = llm_calls[0];
+        assert_eq!(kind(first), Some("LLM".into()));
+        assert!(attribute(first, "llm.token_count.total").is_some());
+        assert_eq!(
+            attribute(first, "llm.input_messages.0.message.content"),
+            Some(&"go".into())
+        );
+        assert_eq!(
+            attribute(
+                first,
+                "llm.output_messages.0.message.tool_calls.0.tool_call.function.name"
+            ),
+            Some(&"lookup".into())
+        );
+        let parent = by_id[&first.parent_span_id];
+        assert_eq!(parent.name, NODE_SPAN);
+        assert_eq!(kind(parent), Some("CHAIN".into()));
+
+        let tool = spans.iter().find(|s| s.name == TOOL_CALL_SPAN).unwrap();
+        assert_eq!(kind(tool), Some("TOOL".into()));
+        assert_eq!(attribute(tool, "tool.name"), Some(&"lookup".into()));
+        assert_eq!(
+            attribute(tool, "input.value"),
+            Some(&r#"{"q":"rust"}"#.into())
+        );
+        assert!(matches!(tool.status, Status::Error { .. }));
+    }
+}
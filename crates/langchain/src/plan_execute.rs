@@ -0,0 +1,483 @@
+//! Plan-and-execute agent.
+//!
+//! A planner model first breaks the objective into a list of steps, an executor
+//! (a [`ReactAgent`] with the tools) works through them one at a time, and once
+//! the plan is exhausted a replanner either gives the final answer or appends new
+//! steps. Compared with a bare ReAct loop this keeps long, multi-step tasks on
+//! track because the model commits to a plan up front.
+
+use std::{collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+use langchain_core::{
+    ListParser, OutputParser, ToolError,
+    message::Message,
+    response::Usage,
+    state::{ChatModel, ChatStreamEvent, MessagesState, RegisteredTool},
+};
+use langgraph::{
+    checkpoint::Configuration,
+    label::{GraphLabel, InternedGraphLabel},
+    node::{EventSink, Node, NodeContext},
+    state_graph::{GraphSpec, RunStrategy, StateGraph},
+};
+use serde::{Deserialize, Serialize};
+use smallvec::{SmallVec, smallvec};
+
+use crate::{AgentError, ReactAgent, node::llm::LlmNode};
+
+const PLANNER_PROMPT: &str = "For the given objective, come up with a simple step by step plan. \
+Each step should be an individual task that, if executed correctly, yields part of the answer. \
+Do not add superfluous steps. The result of the final step should be the final answer. \
+Output only the steps, one per line, without any other text.";
+
+const REPLANNER_PROMPT: &str = "You are revising a plan for the given objective. \
+If the completed steps are enough to answer the objective, reply with `FINAL ANSWER:` followed by \
+the answer. Otherwise output only the remaining steps, one per line, without any other text.";
+
+const FINAL_PROMPT: &str = "Using the completed steps, give the final answer to the objective.";
+
+/// Prefix that marks a final answer in the replanner's reply.
+const FINAL_MARKER: &str = "FINAL ANSWER:";
+
+/// Default number of times the replanner may add steps.
+pub const DEFAULT_MAX_REPLANS: usize = 2;
+
+/// Upper bound on graph super-steps for one run.
+const MAX_PLAN_EXECUTE_STEPS: usize = 100;
+
+/// A finished plan step and the executor's answer for it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanStep {
+    pub task: String,
+    pub result: String,
+}
+
+/// State of a plan-and-execute run.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PlanExecuteState {
+    pub objective: String,
+    /// Steps that have not been executed yet.
+    pub plan: Vec<String>,
+    pub past_steps: Vec<PlanStep>,
+    /// How many times the replanner has added steps.
+    pub replans: usize,
+    /// The final answer, set when the run ends.
+    pub response: Option<String>,
+    /// Token usage of the planner, executor and replanner combined.
+    pub usage: Usage,
+}
+
+impl PlanExecuteState {
+    fn progress(&self) -> String {
+        let mut text = format!("Objective: {}\n", self.objective);
+        if !self.past_steps.is_empty() {
+            text.push_str("\nCompleted steps:\n");
+            for (index, step) in self.past_steps.iter().enumerate() {
+                text.push_str(&format!(
+                    "{}. {}\nResult: {}\n",
+                    index + 1,
+                    step.task,
+                    step.result
+                ));
+            }
+        }
+        text
+    }
+}
+
+pub struct PlanExecuteSpec;
+
+impl GraphSpec for PlanExecuteSpec {
+    type State = PlanExecuteState;
+    type Update = PlanExecuteState;
+    type Error = AgentError;
+    type Event = ChatStreamEvent;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, GraphLabel)]
+enum PlanExecuteLabel {
+    Planner,
+    Executor,
+    Replanner,
+}
+
+/// Splits a reply into steps with [`ListParser`], dropping list markers such as
+/// `1.`, `2)` or `-`.
+fn parse_steps(reply: &str) -> Result<Vec<String>, AgentError> {
+    let steps = ListParser::newline_separated()
+        .parse(reply)
+        .map_err(|e| AgentError::StructuredOutput(format!("invalid plan: {e}")))?;
+    Ok(steps
+        .into_iter()
+        .map(|step| {
+            let unnumbered = step.trim_start_matches(|c: char| c.is_ascii_digit());
+            let unnumbered = if unnumbered.len() < step.len() {
+                unnumbered.trim_start_matches(['.', ')'])
+            } else {
+                step.trim_start_matches(['-', '*'])
+            };
+            unnumbered.trim().to_owned()
+        })
+        .filter(|step| !step.is_empty())
+        .collect())
+}
+
+/// Calls the model through an [`LlmNode`] with a system prompt and one user
+/// message, returning the reply text and the usage.
+async fn ask<M>(
+    llm: &LlmNode<M>,
+    system: &str,
+    user: String,
+    context: NodeContext<'_>,
+) -> Result<(String, Usage), AgentError>
+where
+    M: ChatModel + Send + Sync + 'static,
+{
+    let prompt = MessagesState::new(vec![Message::system(system), Message::user(user)]);
+    let delta = llm.run_sync(&prompt, context).await?;
+    let reply = delta
+        .last_assistant()
+        .map(|m| m.content().to_owned())
+        .unwrap_or_default();
+    Ok((reply, delta.usage))
+}
+
+struct PlannerNode<M: ChatModel + 'static> {
+    llm: LlmNode<M>,
+}
+
+struct ExecutorNode {
+    agent: ReactAgent,
+}
+
+struct ReplannerNode<M: ChatModel + 'static> {
+    llm: LlmNode<M>,
+    max_replans: usize,
+}
+
+#[async_trait]
+impl<M> Node<PlanExecuteState, PlanExecuteState, AgentError, ChatStreamEvent> for PlannerNode<M>
+where
+    M: ChatModel + Send + Sync + 'static,
+{
+    async fn run_sync(
+        &self,
+        input: &PlanExecuteState,
+        context: NodeContext<'_>,
+    ) -> Result<PlanExecuteState, AgentError> {
+        let (reply, usage) =
+            ask(&self.llm, PLANNER_PROMPT, input.objective.clone(), context).await?;
+        let mut state = input.clone();
+        state.plan = parse_steps(&reply)?;
+        state.usage += &usage;
+        tracing::debug!("plan: {:?}", state.plan);
+        Ok(state)
+    }
+
+    async fn run_stream(
+        &self,
+        input: &PlanExecuteState,
+        _sink: &dyn EventSink<ChatStreamEvent>,
+        context: NodeContext<'_>,
+    ) -> Result<PlanExecuteState, AgentError> {
+        self.run_sync(input, context).await
+    }
+}
+
+#[async_trait]
+impl Node<PlanExecuteState, PlanExecuteState, AgentError, ChatStreamEvent> for ExecutorNode {
+    async fn run_sync(
+        &self,
+        input: &PlanExecuteState,
+        _context: NodeContext<'_>,
+    ) -> Result<PlanExecuteState, AgentError> {
+        let mut state = input.clone();
+        if state.plan.is_empty() {
+            return Ok(state);
+        }
+        let task = state.plan.remove(0);
+        let message = format!(
+            "{}\nYour task is to execute this step: {task}",
+            state.progress()
+        );
+        let result = self.agent.invoke(Message::user(message), None).await?;
+        state.usage += &result.usage;
+        state.past_steps.push(PlanStep {
+            task,
+            result: result
+                .last_assistant()
+                .map(|m| m.content().to_owned())
+                .unwrap_or_default(),
+        });
+        Ok(state)
+    }
+
+    async fn run_stream(
+        &self,
+        input: &PlanExecuteState,
+        _sink: &dyn EventSink<ChatStreamEvent>,
+        context: NodeContext<'_>,
+    ) -> Result<PlanExecuteState, AgentError> {
+        self.run_sync(input, context).await
+    }
+}
+
+#[async_trait]
+impl<M> Node<PlanExecuteState, PlanExecuteState, AgentError, ChatStreamEvent> for ReplannerNode<M>
+where
+    M: ChatModel + Send + Sync + 'static,
+{
+    async fn run_sync(
+        &self,
+        input: &PlanExecuteState,
+        context: NodeContext<'_>,
+    ) -> Result<PlanExecuteState, AgentError> {
+        let mut state = input.clone();
+        // 没有重新规划的次数时只要求给出最终答案
+        let prompt = if state.replans < self.max_replans {
+            REPLANNER_PROMPT
+        } else {
+            FINAL_PROMPT
+        };
+        let (reply, usage) = ask(&self.llm, prompt, state.progress(), context).await?;
+        state.usage += &usage;
+
+        match reply.split_once(FINAL_MARKER) {
+            Some((_, answer)) => state.response = Some(answer.trim().to_owned()),
+            None if prompt == FINAL_PROMPT => state.response = Some(reply.trim().to_owned()),
+            None => {
+                state.plan = parse_steps(&reply)?;
+                state.replans += 1;
+                tracing::debug!("replanned ({}): {:?}", state.replans, state.plan);
+            }
+        }
+        Ok(state)
+    }
+
+    async fn run_stream(
+        &self,
+        input: &PlanExecuteState,
+        _sink: &dyn EventSink<ChatStreamEvent>,
+        context: NodeContext<'_>,
+    ) -> Result<PlanExecuteState, AgentError> {
+        self.run_sync(input, context).await
+    }
+}
+
+/// Builder for [`PlanExecuteAgent`].
+pub struct PlanExecuteAgentBuilder<M> {
+    model: Arc<M>,
+    tools: Vec<RegisteredTool<ToolError>>,
+    max_replans: usize,
+}
+
+impl<M> PlanExecuteAgentBuilder<M>
+where
+    M: ChatModel + Send + Sync + 'static,
+{
+    pub fn new(model: M) -> Self {
+        Self {
+            model: Arc::new(model),
+            tools: Vec::new(),
+            max_replans: DEFAULT_MAX_REPLANS,
+        }
+    }
+
+    /// Tools available to the executor. The planner and replanner only see
+    /// the objective and step results.
+    pub fn with_tools<I>(mut self, tools: I) -> Self
+    where
+        I: IntoIterator<Item = RegisteredTool<ToolError>>,
+    {
+        self.tools = tools.into_iter().collect();
+        self
+    }
+
+    /// How many times the replanner may add steps once the plan runs out
+    /// (default [`DEFAULT_MAX_REPLANS`]). After that it must give the final
+    /// answer; `0` disables replanning.
+    pub fn with_max_replans(mut self, max_replans: usize) -> Self {
+        self.max_replans = max_replans;
+        self
+    }
+
+    pub fn build(self) -> PlanExecuteAgent {
+        self.try_build().expect("invalid plan-execute graph")
+    }
+
+    pub fn try_build(self) -> Result<PlanExecuteAgent, AgentError> {
+        let mut graph: StateGraph<PlanExecuteSpec> =
+            StateGraph::new(PlanExecuteLabel::Planner, |old, update| *old = update);
+
+        graph.add_node(
+            PlanExecuteLabel::Planner,
+            PlannerNode {
+                llm: LlmNode::new(self.model.clone(), Vec::new()),
+            },
+        );
+        graph.add_node(
+            PlanExecuteLabel::Executor,
+            ExecutorNode {
+                agent: ReactAgent::builder(self.model.clone())
+                    .with_tools(self.tools)
+                    .try_build()?,
+            },
+        );
+        graph.add_node(
+            PlanExecuteLabel::Replanner,
+            ReplannerNode {
+                llm: LlmNode::new(self.model, Vec::new()),
+                max_replans: self.max_replans,
+            },
+        );
+
+        let executor = PlanExecuteLabel::Executor.intern();
+        let replanner = PlanExecuteLabel::Replanner.intern();
+        graph.add_edge(PlanExecuteLabel::Planner, PlanExecuteLabel::Executor);
+        // 执行完所有步骤后交给重新规划节点
+        graph.add_condition_edge(
+            PlanExecuteLabel::Executor,
+            HashMap::from([(executor, executor), (replanner, replanner)]),
+            move |state: &PlanExecuteState| {
+                if state.plan.is_empty() {
+                    smallvec![replanner]
+                } else {
+                    smallvec![executor]
+                }
+            },
+        );
+        // 给出最终答案时结束，否则继续执行新的步骤
+        graph.add_condition_edge(
+            PlanExecuteLabel::Replanner,
+            HashMap::from([(executor, executor)]),
+            move |state: &PlanExecuteState| -> SmallVec<[InternedGraphLabel; 2]> {
+                if state.response.is_some() {
+                    SmallVec::new()
+                } else {
+                    smallvec![executor]
+                }
+            },
+        );
+        graph.validate()?;
+
+        Ok(PlanExecuteAgent { graph })
+    }
+}
+
+/// Agent that plans first and then executes the plan step by step.
+///
+/// ```rust,ignore
+/// let agent = PlanExecuteAgent::builder(model)
+///     .with_tools([search_tool()])
+///     .with_max_replans(1)
+///     .build();
+/// let state = agent.invoke("Who won the 2022 World Cup and how old is their captain?").await?;
+/// println!("{}", state.response.unwrap_or_default());
+/// ```
+pub struct PlanExecuteAgent {
+    pub graph: StateGraph<PlanExecuteSpec>,
+}
+
+impl PlanExecuteAgent {
+    pub fn builder<M>(model: M) -> PlanExecuteAgentBuilder<M>
+    where
+        M: ChatModel + Send + Sync + 'static,
+    {
+        PlanExecuteAgentBuilder::new(model)
+    }
+
+    /// Plans and executes `objective`, returning the final state with the
+    /// answer in [`PlanExecuteState::response`] and every executed step.
+    pub async fn invoke(
+        &self,
+        objective: impl Into<String>,
+    ) -> Result<PlanExecuteState, AgentError> {
+        let state = PlanExecuteState {
+            objective: objective.into(),
+            ..Default::default()
+        };
+        let (state, pending) = self
+            .graph
+            .run(
+                state,
+                &Configuration::default(),
+                MAX_PLAN_EXECUTE_STEPS,
+                RunStrategy::StopAtNonLinear,
+                None,
+            )
+            .await?;
+        if !pending.is_empty() {
+            return Err(AgentError::Agent(format!(
+                "plan-execute run did not finish within {MAX_PLAN_EXECUTE_STEPS} steps"
+            )));
+        }
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockModel;
+
+    /// 按顺序回复 `replies` 的模型
+    fn scripted(replies: &[&str]) -> Arc<MockModel> {
+        Arc::new(MockModel::new(
+            replies.iter().map(|r| Message::assistant(*r)),
+        ))
+    }
+
+    #[test]
+    fn plan_steps_drop_list_markers() {
+        assert_eq!(
+            parse_steps("1. find it\n2) check it\n- report\n\n").unwrap(),
+            ["find it", "check it", "report"]
+        );
+        assert!(parse_steps("  \n").is_err());
+    }
+
+    #[tokio::test]
+    async fn executes_plan_then_replans_until_final_answer() {
+        let model = scripted(&[
+            "1. look up a\n2. look up b", // planner
+            "a is 1",                     // executor: step 1
+            "b is 2",                     // executor: step 2
+            "add them",                   // replanner: one more step
+            "3",                          // executor: step 3
+            "FINAL ANSWER: 3",            // replanner
+        ]);
+
+        let state = PlanExecuteAgent::builder(model.clone())
+            .build()
+            .invoke("a + b?")
+            .await
+            .unwrap();
+
+        assert_eq!(state.response.as_deref(), Some("3"));
+        assert_eq!(state.replans, 1);
+        let tasks: Vec<_> = state.past_steps.iter().map(|s| s.task.as_str()).collect();
+        assert_eq!(tasks, ["look up a", "look up b", "add them"]);
+        assert_eq!(state.past_steps[1].result, "b is 2");
+    }
+
+    #[tokio::test]
+    async fn replanning_stops_at_the_limit() {
+        let model = scripted(&[
+            "only step",
+            "done",
+            "the answer is 42", // 不允许再规划，直接作为最终答案
+        ]);
+
+        let state = PlanExecuteAgent::builder(model.clone())
+            .with_max_replans(0)
+            .build()
+            .invoke("question")
+            .await
+            .unwrap();
+
+        assert_eq!(state.response.as_deref(), Some("the answer is 42"));
+        let last = model.last_request().unwrap();
+        assert_eq!(last.messages[0].content(), FINAL_PROMPT);
+    }
+}
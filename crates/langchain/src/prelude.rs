@@ -0,0 +1,33 @@
+//! 常用类型的统一导出
+//!
+//! ```rust,ignore
+//! use langchain::prelude::*;
+//! ```
+//!
+//! 只包含构建和运行 Agent 最常用的类型，其余类型仍然通过各自的模块路径使用。
+//! 模型实现（如 `langchain_openai::ChatOpenAI`）在各自的 crate 中。
+
+pub use crate::{
+    AgentError, CancellationToken, MAX_STEPS, ReactAgent, ReactAgentBuilder,
+    StructuredOutputStrategy, StructuredStreamEvent,
+    middleware::{
+        BudgetAction, CacheMiddleware, ContentFilterMiddleware, CostTracker,
+        CostTrackingMiddleware, LoggingMiddleware, MemoryMiddleware, MetricsMiddleware,
+        PiiRedactionMiddleware, RateLimitMiddleware, RateLimiter, TokenBudgetMiddleware,
+        TraceMiddleware,
+    },
+    node::middleware::{AgentHook, AgentMiddleware},
+    plan_execute::{PlanExecuteAgent, PlanExecuteState},
+    stepper::{AgentStepper, StepEvent},
+    summarizing::SummarizingCheckpointer,
+    supervisor::Supervisor,
+};
+pub use langchain_core::{
+    error::{ModelError, RetryConfig, ToolError},
+    message::{Content, ContentBlock, ImageDetail, Message, ToolCall},
+    parsers::{JsonParser, KeyValueParser, ListParser, OutputParser, ParseError},
+    state::{ChatModel, JumpTo, MessagesReducer, MessagesState, RegisteredTool, ToolRegistry},
+    store::{BaseStore, InMemoryStore, Namespace},
+    tool,
+};
+pub use langgraph::checkpoint::{Checkpointer, Configuration, MemorySaver};
@@ -0,0 +1,184 @@
+//! Names of the `tracing` spans an agent run opens.
+//!
+//! Every run produces the same span tree, so any `tracing` subscriber (a
+//! formatter, `tracing-opentelemetry`, ...) shows where the time went:
+//!
+//! ```text
+//! agent.step          one super-step of AgentStepper, which invoke is built on
+//! └─ graph.node       one node of the agent graph
+//!    ├─ llm.call      one model request made by the Llm node
+//!    └─ tool.call     one tool call made by the Tool node
+//! ```
+//!
+//! Fields:
+//!
+//! - `agent.step`: `thread_id`, `step`, `duration_ms`
+//! - `graph.node`: `thread_id`, `node`, `step`, `duration_ms`
+//! - `llm.call`: `thread_id`, `stream`, `messages`, `tools`, `prompt_tokens`,
+//!   `completion_tokens`, `total_tokens`, `duration_ms`
+//! - `tool.call`: `thread_id`, `tool_name`, `tool_call_id`, `success`,
+//!   `duration_ms`
+//!
+//! Streaming runs do not go through [`AgentStepper`](crate::AgentStepper), so
+//! their trees start at `graph.node`. `thread_id` is only recorded for runs
+//! that have one. `duration_ms`, the token counts and `success` are recorded
+//! when the span's work finishes. All spans are at the `INFO` level.
+//!
+//! `llm.call` and `tool.call` also declare `input` and `output`: the request and
+//! reply messages as a JSON array, and the tool arguments and result. They are
+//! only recorded when the agent is built with
+//! [`ReactAgentBuilder::with_content_tracing`](crate::ReactAgentBuilder::with_content_tracing).
+
+pub use langgraph::graph::NODE_SPAN;
+
+/// One super-step of an [`AgentStepper`](crate::AgentStepper).
+pub const AGENT_STEP_SPAN: &str = "agent.step";
+
+/// One model request made by the agent's Llm node.
+pub const LLM_CALL_SPAN: &str = "llm.call";
+
+/// One tool call made by the agent's Tool node.
+pub const TOOL_CALL_SPAN: &str = "tool.call";
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    use langchain_core::{ToolError, message::Message, state::RegisteredTool};
+    use langgraph::checkpoint::MemorySaver;
+    use tracing::{
+        Subscriber,
+        field::{Field, Visit},
+        span::{Attributes, Id, Record},
+    };
+    use tracing_subscriber::{Layer, Registry, layer::Context, prelude::*, registry::LookupSpan};
+
+    use super::*;
+    use crate::{ReactAgent, testing::MockModel};
+
+    #[derive(Default)]
+    struct Fields(HashMap<String, String>);
+
+    impl Visit for Fields {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0.insert(
+                field.name().to_owned(),
+                format!("{value:?}").replace('"', ""),
+            );
+        }
+    }
+
+    /// 已关闭的 span：名称、父 span 名称和字段
+    type Closed = (String, Option<String>, HashMap<String, String>);
+
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<Closed>>>);
+
+    impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for Recorder {
+        fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+            let mut fields = Fields::default();
+            attrs.record(&mut fields);
+            ctx.span(id).unwrap().extensions_mut().insert(fields);
+        }
+
+        fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+            let span = ctx.span(id).unwrap();
+            let mut extensions = span.extensions_mut();
+            values.record(extensions.get_mut::<Fields>().unwrap());
+        }
+
+        fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+            let span = ctx.span(&id).unwrap();
+            let fields = span.extensions_mut().remove::<Fields>().unwrap();
+            let parent = span.parent().map(|parent| parent.name().to_owned());
+            self.0
+                .lock()
+                .unwrap()
+                .push((span.name().to_owned(), parent, fields.0));
+        }
+    }
+
+    #[tokio::test]
+    async fn runs_produce_a_span_tree_with_consistent_fields() {
+        let recorder = Recorder::default();
+        let _guard = tracing::subscriber::set_default(Registry::default().with(recorder.clone()));
+
+        let lookup = RegisteredTool::<ToolError>::new(
+            "lookup".to_owned(),
+            String::new(),
+            serde_json::json!({"type": "object"}),
+            Arc::new(|_| Box::pin(async { Ok(serde_json::json!("found")) })),
+        );
+        let model = MockModel::new([
+            MockModel::tool_call("lookup", serde_json::json!({})),
+            Message::assistant("done"),
+        ]);
+        let agent = ReactAgent::builder(model)
+            .with_tools([lookup])
+            .with_checkpointer(Arc::new(MemorySaver::new()))
+            .build();
+        agent
+            .invoke(Message::user("go"), Some("thread-1"))
+            .await
+            .unwrap();
+
+        let spans = recorder.0.lock().unwrap().clone();
+        let named = |name: &str| {
+            spans
+                .iter()
+                .filter(|(span, ..)| span == name)
+                .collect::<Vec<_>>()
+        };
+        let llm_calls = named(LLM_CALL_SPAN);
+        assert_eq!(llm_calls.len(), 2);
+        let tool_calls = named(TOOL_CALL_SPAN);
+        assert_eq!(tool_calls.len(), 1);
+        for (_, parent, fields) in llm_calls.iter().chain(&tool_calls) {
+            assert_eq!(parent.as_deref(), Some(NODE_SPAN));
+            assert_eq!(fields["thread_id"], "thread-1");
+            assert!(fields.contains_key("duration_ms"));
+            // 默认不记录内容
+            assert!(!fields.contains_key("input"));
+        }
+        assert!(llm_calls[0].2.contains_key("total_tokens"));
+        assert_eq!(tool_calls[0].2["tool_name"], "lookup");
+        assert_eq!(tool_calls[0].2["success"], "true");
+        for (_, parent, fields) in named(NODE_SPAN) {
+            assert_eq!(parent.as_deref(), Some(AGENT_STEP_SPAN));
+            assert!(fields.contains_key("node"));
+        }
+        assert!(!named(AGENT_STEP_SPAN).is_empty());
+    }
+
+    #[tokio::test]
+    async fn hidden_reasoning_is_not_traced() {
+        let recorder = Recorder::default();
+        let _guard = tracing::subscriber::set_default(Registry::default().with(recorder.clone()));
+
+        let model = MockModel::from_fn(|_| {
+            Ok(Message::Assistant {
+                content: "It is sunny.".to_owned(),
+                reasoning_content: Some("secret thoughts".to_owned()),
+                tool_calls: None,
+                name: None,
+                metadata: Default::default(),
+            })
+        });
+        let agent = ReactAgent::builder(model)
+            .with_reasoning_hidden(true)
+            .with_content_tracing(true)
+            .build();
+        agent.invoke(Message::user("weather?"), None).await.unwrap();
+
+        let spans = recorder.0.lock().unwrap().clone();
+        let (.., fields) = spans
+            .iter()
+            .find(|(span, ..)| span == LLM_CALL_SPAN)
+            .unwrap();
+        assert!(fields["output"].contains("It is sunny."));
+        assert!(!fields["output"].contains("secret thoughts"));
+    }
+}
@@ -0,0 +1,618 @@
+//! Driving a [`ReactAgent`] run one step at a time.
+//!
+//! [`ReactAgent::invoke`] runs the agent graph to completion. An [`AgentStepper`]
+//! runs the same graph but returns after every super-step, so the caller can
+//! look at (or edit) the state in between: ask a user to confirm the tool calls
+//! the model proposed, narrow the tools visible to the next model call, or stop
+//! the run early.
+//!
+//! ```rust,ignore
+//! let mut stepper = agent.stepper(initial, Some("thread-1"));
+//! loop {
+//!     match stepper.step().await? {
+//!         StepEvent::Step { messages, next, .. } => {
+//!             if next.contains(&ReactAgentLabel::Tool.intern()) && !confirm(&messages) {
+//!                 stepper.stop();
+//!             }
+//!         }
+//!         StepEvent::Finished => break,
+//!     }
+//! }
+//! let state = stepper.into_state();
+//! ```
+
+use std::sync::Arc;
+
+use langchain_core::{message::Message, state::MessagesState};
+use langgraph::{
+    checkpoint::Configuration,
+    label::{BaseGraphLabel, GraphLabel, InternedGraphLabel},
+    label_registry::str_to_label,
+    state_graph::StateGraphRunner,
+};
+use smallvec::SmallVec;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
+
+use crate::{AgentError, MAX_STEPS, ReactAgent, ReactAgentSpec, spans::AGENT_STEP_SPAN};
+
+/// What one call to [`AgentStepper::step`] did.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StepEvent {
+    /// Nodes ran and their updates were merged into the state.
+    Step {
+        /// The nodes that ran, in merge order.
+        nodes: Vec<InternedGraphLabel>,
+        /// Messages the nodes added, e.g. the model's reply or tool results.
+        messages: Vec<Arc<Message>>,
+        /// The nodes the next step will run; empty when the run is over.
+        next: Vec<InternedGraphLabel>,
+    },
+    /// The run paused at an interrupt (see
+    /// [`ReactAgentBuilder::with_interrupt_before`](crate::ReactAgentBuilder::with_interrupt_before)):
+    /// nothing ran. The next [`step`](AgentStepper::step) continues with `next`.
+    Interrupted {
+        /// The nodes the next step will run.
+        next: Vec<InternedGraphLabel>,
+    },
+    /// No nodes are left to run.
+    Finished,
+}
+
+/// A [`ReactAgent`] run that the caller advances with [`step`](Self::step).
+///
+/// Created by [`ReactAgent::stepper`]. Each step honours the agent's deadline,
+/// checkpointer and interrupts the same way [`ReactAgent::invoke`] does, which
+/// is built on this type.
+pub struct AgentStepper<'a> {
+    agent: &'a ReactAgent,
+    runner: StateGraphRunner<'a, ReactAgentSpec>,
+    config: Configuration,
+    started: Instant,
+    max_steps: usize,
+    token_limit: Option<u32>,
+    cancel: Option<CancellationToken>,
+    /// 下一步的节点已经中断过一次，恢复时直接执行
+    resuming: bool,
+    /// 上一步执行了 `interrupt_after` 中的节点，下一次调用先报告中断
+    pause_after: bool,
+}
+
+/// 步骤被中断的原因
+enum Interrupted {
+    Deadline,
+    Cancelled,
+}
+
+impl<'a> AgentStepper<'a> {
+    pub(crate) fn new(
+        agent: &'a ReactAgent,
+        state: MessagesState,
+        config: &Configuration,
+        resume_from: Option<SmallVec<[String; 4]>>,
+    ) -> Self {
+        let started = Instant::now();
+        // 调用方在配置中给出的截止时间优先，例如外层图传入的剩余时间
+        let deadline = config
+            .deadline
+            .or_else(|| agent.deadline.map(|deadline| started + deadline));
+        let config = Configuration {
+            deadline,
+            ..config.clone()
+        };
+
+        let mut runner = StateGraphRunner::new(&agent.graph, state);
+        let restored: Vec<_> = resume_from
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|node| str_to_label(node))
+            .collect();
+        if !restored.is_empty() {
+            runner.current_nodes = restored;
+        }
+
+        Self {
+            agent,
+            runner,
+            config,
+            started,
+            max_steps: MAX_STEPS,
+            token_limit: agent.token_limit,
+            cancel: None,
+            resuming: false,
+            pause_after: false,
+        }
+    }
+
+    /// 从中断处恢复：第一步不再因 `interrupt_before` 暂停
+    pub(crate) fn resuming_interrupt(mut self) -> Self {
+        self.resuming = true;
+        self
+    }
+
+    /// Sets how many steps may run before [`step`](Self::step) fails with
+    /// [`AgentError::MaxStepsExceeded`]. Defaults to [`MAX_STEPS`].
+    pub fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// Sets how many tokens the run may use before [`step`](Self::step) fails
+    /// with [`AgentError::TokenLimitExceeded`], replacing the agent's
+    /// [`with_token_limit`](crate::ReactAgentBuilder::with_token_limit).
+    pub fn with_token_limit(mut self, limit: u32) -> Self {
+        self.token_limit = Some(limit);
+        self
+    }
+
+    /// Stops the run when `cancel` is cancelled: a step in progress is
+    /// abandoned (its model and tool calls are dropped) and [`step`](Self::step)
+    /// fails with [`AgentError::Cancelled`]. If the model had asked for tools
+    /// that never ran, its reply is rolled back (and the thread's checkpoint
+    /// saved without it), so the history never ends with unanswered tool calls.
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// Runs the pending nodes and merges their updates.
+    ///
+    /// Returns [`StepEvent::Finished`] once no nodes are left. Fails with
+    /// [`AgentError::Timeout`] when the agent's deadline passes during the step
+    /// (the state is left as it was before the step) and with
+    /// [`AgentError::MaxStepsExceeded`] when nodes are still pending after the
+    /// step limit. Fails with [`AgentError::Cancelled`] once the token given to
+    /// [`with_cancellation`](Self::with_cancellation) is cancelled, and with
+    /// [`AgentError::TokenLimitExceeded`] when the step's model call took the
+    /// run's usage over the token limit (the step is merged and saved first).
+    ///
+    /// Every error ends the run: the middlewares' `on_run_error` handlers
+    /// are called with it before it is returned.
+    pub async fn step(&mut self) -> Result<StepEvent, AgentError> {
+        let result = self.try_step().await;
+        if let Err(e) = &result {
+            for handler in &self.agent.run_error_handlers {
+                handler(&self.config, e);
+            }
+        }
+        result
+    }
+
+    async fn try_step(&mut self) -> Result<StepEvent, AgentError> {
+        if self.runner.is_finished() {
+            return Ok(StepEvent::Finished);
+        }
+        let graph = &self.agent.graph;
+        let resuming = std::mem::take(&mut self.resuming);
+        if std::mem::take(&mut self.pause_after)
+            || (!resuming
+                && self
+                    .next_nodes()
+                    .iter()
+                    .any(|node| graph.interrupt_before.contains(node)))
+        {
+            // 下一步的节点和状态已经随上一步保存，恢复时从这些节点继续
+            self.resuming = true;
+            tracing::info!("Run interrupted before {:?}", self.next_nodes());
+            return Ok(StepEvent::Interrupted {
+                next: self.next_nodes().to_vec(),
+            });
+        }
+        if self
+            .cancel
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+        {
+            return Err(self.cancelled().await);
+        }
+        if self.runner.steps() >= self.max_steps {
+            return Err(AgentError::MaxStepsExceeded {
+                steps: self.max_steps,
+                state: Box::new(self.runner.state().clone()),
+            });
+        }
+
+        let span = tracing::info_span!(
+            AGENT_STEP_SPAN,
+            thread_id = self.config.thread_id.as_deref(),
+            step = self.runner.steps(),
+            duration_ms = tracing::field::Empty,
+        );
+        let step_started = std::time::Instant::now();
+        let outcome = {
+            let deadline = async {
+                match self.config.deadline {
+                    Some(deadline) => tokio::time::sleep_until(deadline).await,
+                    None => std::future::pending().await,
+                }
+            };
+            let cancelled = async {
+                match &self.cancel {
+                    Some(cancel) => cancel.cancelled().await,
+                    None => std::future::pending().await,
+                }
+            };
+            // 中断时未完成的节点 future 随之释放，其中的模型请求和工具调用一并取消
+            tokio::select! {
+                updates = self.runner.execute(&self.config).instrument(span.clone()) => Ok(updates),
+                () = deadline => Err(Interrupted::Deadline),
+                () = cancelled => Err(Interrupted::Cancelled),
+            }
+        };
+        span.record("duration_ms", step_started.elapsed().as_millis() as u64);
+        let updates = match outcome {
+            Ok(updates) => updates?,
+            Err(Interrupted::Deadline) => {
+                tracing::warn!("Deadline reached before {:?} finished", self.next_nodes());
+                return Err(AgentError::Timeout {
+                    elapsed: self.started.elapsed(),
+                    state: Box::new(self.runner.state().clone()),
+                });
+            }
+            Err(Interrupted::Cancelled) => {
+                tracing::info!("Run cancelled while {:?} were running", self.next_nodes());
+                return Err(self.cancelled().await);
+            }
+        };
+        let nodes: Vec<_> = updates.iter().map(|(label, _)| *label).collect();
+        let messages = updates
+            .iter()
+            .flat_map(|(_, update)| update.messages.iter().cloned())
+            .collect();
+
+        let step = self.runner.steps();
+        let next = self.runner.commit().to_vec();
+        // 与 `StateGraph::run` 一样保存状态和下一步的节点，最后一步保存为最终检查点
+        self.runner.save_checkpoint(&self.config, step).await;
+
+        // 只剩结束节点时没有需要审查后才执行的节点，不再暂停
+        let end = BaseGraphLabel::End.intern();
+        self.pause_after = next.iter().any(|node| *node != end)
+            && nodes
+                .iter()
+                .any(|node| self.agent.graph.interrupt_after.contains(node));
+
+        // 用量只会因模型调用而增加，每步之后检查即覆盖每次模型调用
+        let used = self.runner.state().usage.total_tokens;
+        if let Some(limit) = self.token_limit
+            && used > limit
+        {
+            tracing::warn!("Token limit exceeded: used {used} of {limit} tokens");
+            return Err(AgentError::TokenLimitExceeded {
+                used,
+                limit,
+                state: Box::new(self.runner.state().clone()),
+            });
+        }
+
+        Ok(StepEvent::Step {
+            nodes,
+            messages,
+            next,
+        })
+    }
+
+    /// Steps until the run finishes or pauses at an interrupt and returns the
+    /// state.
+    pub async fn run_to_end(mut self) -> Result<MessagesState, AgentError> {
+        self.run_until_paused().await?;
+        Ok(self.into_state())
+    }
+
+    /// 执行到运行结束或在中断处暂停
+    pub(crate) async fn run_until_paused(&mut self) -> Result<(), AgentError> {
+        while !matches!(
+            self.step().await?,
+            StepEvent::Finished | StepEvent::Interrupted { .. }
+        ) {}
+        Ok(())
+    }
+
+    /// 运行结束后追加一条消息，从入口节点继续同一次运行：截止时间、步数上限和检查点链都沿用
+    pub(crate) fn continue_with(&mut self, message: Message) {
+        self.runner.state.push_message_owned(message);
+        self.runner.current_nodes = vec![self.agent.graph.entry];
+    }
+
+    /// The nodes the next step will run.
+    pub fn next_nodes(&self) -> &[InternedGraphLabel] {
+        self.runner.peek_next()
+    }
+
+    /// Number of steps run so far.
+    pub fn steps(&self) -> usize {
+        self.runner.steps()
+    }
+
+    pub fn state(&self) -> &MessagesState {
+        self.runner.state()
+    }
+
+    /// The state between steps. Changes are seen by the next step, but the
+    /// nodes it runs were already chosen from the state after the last step.
+    pub fn state_mut(&mut self) -> &mut MessagesState {
+        &mut self.runner.state
+    }
+
+    /// Only exposes the named tools to the following steps (see
+    /// [`ReactAgent::invoke_with_tools`]). Every name must be a tool the agent
+    /// was built with; tools cannot be added to a running agent.
+    pub fn set_tools<I, S>(&mut self, tools: I) -> Result<(), AgentError>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let tools: Vec<String> = tools.into_iter().map(Into::into).collect();
+        if let Some(unknown) = tools
+            .iter()
+            .find(|name| !self.agent.tool_names.contains(*name))
+        {
+            return Err(AgentError::Agent(format!("unknown tool: {unknown}")));
+        }
+        self.config.tools = Some(tools);
+        Ok(())
+    }
+
+    /// Ends the run after the current step; the next [`step`](Self::step)
+    /// returns [`StepEvent::Finished`].
+    pub fn stop(&mut self) {
+        self.runner.current_nodes.clear();
+    }
+
+    pub fn into_state(self) -> MessagesState {
+        self.runner.into_state()
+    }
+
+    /// 取消运行。最后一条消息是尚未执行的工具调用时撤回这一轮模型回复，并保存为最终检查点，
+    /// 线程历史中不会留下没有结果的工具调用，下一次调用从新的消息正常开始
+    async fn cancelled(&mut self) -> AgentError {
+        let dangling = matches!(
+            self.runner.state().last_message().map(AsRef::as_ref),
+            Some(Message::Assistant { tool_calls: Some(calls), .. }) if !calls.is_empty()
+        );
+        if dangling {
+            self.runner.state.messages.pop_back();
+            self.runner.current_nodes.clear();
+            let step = self.runner.steps();
+            self.runner.save_checkpoint(&self.config, step).await;
+        }
+        AgentError::Cancelled {
+            state: Box::new(self.runner.state().clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use langchain_core::{ToolError, state::RegisteredTool};
+
+    use super::*;
+    use crate::{ReactAgentLabel, testing::MockModel};
+
+    #[tokio::test]
+    async fn stopping_before_the_tool_step_skips_the_tool() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let tool = RegisteredTool::new(
+            "delete_file".to_owned(),
+            "deletes a file".to_owned(),
+            serde_json::json!({"type": "object", "properties": {}}),
+            Arc::new(move |_| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Box::pin(async { Ok::<_, ToolError>(serde_json::json!("deleted")) })
+            }),
+        );
+        let model = MockModel::new([
+            MockModel::tool_call("delete_file", serde_json::json!({})),
+            Message::assistant("Deleted."),
+        ]);
+        let agent = ReactAgent::builder(model).with_tools([tool]).build();
+
+        let mut initial = MessagesState::default();
+        initial.push_message_owned(Message::user("clean up"));
+        let mut stepper = agent.stepper(initial, None);
+        let mut proposed = Vec::new();
+        while let StepEvent::Step { messages, next, .. } = stepper.step().await.unwrap() {
+            // 模型提出了工具调用，拒绝执行
+            if next.contains(&ReactAgentLabel::Tool.intern()) {
+                proposed = messages;
+                stepper.stop();
+            }
+        }
+
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+        assert_eq!(proposed.len(), 1);
+        assert_eq!(stepper.next_nodes(), []);
+        let state = stepper.into_state();
+        assert_eq!(
+            state.last_tool_calls().unwrap()[0].function_name(),
+            "delete_file"
+        );
+        assert_eq!(state.messages.len(), 2);
+        assert_eq!(state.llm_calls, 1);
+    }
+
+    #[tokio::test]
+    async fn token_limit_stops_the_run_after_the_model_call_that_exceeds_it() {
+        let lookup = RegisteredTool::new(
+            "lookup".to_owned(),
+            String::new(),
+            serde_json::json!({"type": "object", "properties": {}}),
+            Arc::new(|_| Box::pin(async { Ok::<_, ToolError>(serde_json::json!("found")) })),
+        );
+        // 每次调用报告 600 token，并且一直调用工具
+        let usage = langchain_core::response::Usage {
+            prompt_tokens: 500,
+            completion_tokens: 100,
+            total_tokens: 600,
+            completion_tokens_details: None,
+        };
+        let model =
+            MockModel::from_fn(|_| Ok(MockModel::tool_call("lookup", serde_json::json!({}))))
+                .with_usage(usage);
+        let agent = ReactAgent::builder(model)
+            .with_tools([lookup])
+            .with_token_limit(1000)
+            .build();
+
+        let result = agent.invoke(Message::user("go"), None).await;
+        let Err(AgentError::TokenLimitExceeded { used, limit, state }) = result else {
+            panic!("expected TokenLimitExceeded, got {result:?}");
+        };
+        assert_eq!((used, limit), (1200, 1000));
+        // 第二次模型调用的回复已合并，对应的工具调用没有执行
+        assert_eq!(state.llm_calls, 2);
+        assert!(state.last_tool_calls().is_some());
+
+        // 步进器上的设置优先于 Agent 的设置
+        let mut initial = MessagesState::default();
+        initial.push_message_owned(Message::user("go"));
+        let result = agent
+            .stepper(initial, None)
+            .with_token_limit(500)
+            .run_to_end()
+            .await;
+        assert!(matches!(
+            result,
+            Err(AgentError::TokenLimitExceeded { used: 600, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn stepper_checkpoints_under_its_thread_and_saves_a_final_checkpoint() {
+        use langgraph::checkpoint::{CheckpointType, Checkpointer, MemorySaver};
+
+        let checkpointer = Arc::new(MemorySaver::new());
+        let model = MockModel::new([
+            Message::assistant("Hi there."),
+            Message::assistant("Still here."),
+        ]);
+        let agent = ReactAgent::builder(model)
+            .with_checkpointer(checkpointer.clone())
+            .build();
+
+        let mut initial = MessagesState::default();
+        initial.push_message_owned(Message::user("hello"));
+        let state = agent
+            .stepper(initial, Some("t"))
+            .run_to_end()
+            .await
+            .unwrap();
+        assert_eq!(state.messages.len(), 2);
+
+        let checkpoint = Checkpointer::<MessagesState>::get(checkpointer.as_ref(), "t")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(checkpoint.metadata.checkpoint_type, CheckpointType::Final);
+        assert!(checkpoint.next_nodes.is_empty());
+        assert_eq!(checkpoint.state.messages.len(), 2);
+
+        // 之后的 invoke 接着这个线程的对话
+        let state = agent
+            .invoke(Message::user("still there?"), Some("t"))
+            .await
+            .unwrap();
+        assert_eq!(state.messages.len(), 4);
+        assert_eq!(state.messages[0].content(), "hello");
+
+        // 两次运行的检查点依次以前一个检查点为父检查点
+        let saved = Checkpointer::<MessagesState>::list(checkpointer.as_ref(), "t", None)
+            .await
+            .unwrap();
+        assert_eq!(saved[0].parent_id, None);
+        for pair in saved.windows(2) {
+            assert_eq!(pair[1].parent_id.as_ref(), Some(&pair[0].id));
+        }
+    }
+
+    fn counting_tool(calls: Arc<AtomicUsize>) -> RegisteredTool<ToolError> {
+        RegisteredTool::new(
+            "send_mail".to_owned(),
+            "sends a mail".to_owned(),
+            serde_json::json!({"type": "object", "properties": {}}),
+            Arc::new(move |_| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Box::pin(async { Ok::<_, ToolError>(serde_json::json!("sent")) })
+            }),
+        )
+    }
+
+    #[tokio::test]
+    async fn interrupt_after_the_model_pauses_invoke_until_resumed() {
+        use langgraph::checkpoint::MemorySaver;
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let model = MockModel::new([
+            MockModel::tool_call("send_mail", serde_json::json!({})),
+            Message::assistant("Mail sent."),
+        ]);
+        let agent = ReactAgent::builder(model)
+            .with_tools([counting_tool(calls.clone())])
+            .with_checkpointer(Arc::new(MemorySaver::new()))
+            .with_interrupt_after([ReactAgentLabel::Llm])
+            .build();
+
+        let state = agent
+            .invoke(Message::user("mail bob"), Some("t"))
+            .await
+            .unwrap();
+        // 模型提出的工具调用已保存，但工具尚未执行
+        assert_eq!(
+            state.last_tool_calls().unwrap()[0].function_name(),
+            "send_mail"
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+        let pending = agent.pending_nodes("t").await.unwrap();
+        assert!(!pending.is_empty());
+
+        // 恢复后执行工具；第二次模型调用之后再次暂停，但已没有后续节点
+        let state = agent.resume("t").await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(state.last_message().unwrap().content(), "Mail sent.");
+        assert_eq!(
+            agent.pending_nodes("t").await.unwrap(),
+            Vec::<String>::new()
+        );
+    }
+
+    #[tokio::test]
+    async fn stepper_reports_interrupt_before_the_tool_node() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let model = MockModel::new([
+            MockModel::tool_call("send_mail", serde_json::json!({})),
+            Message::assistant("Mail sent."),
+        ]);
+        let agent = ReactAgent::builder(model)
+            .with_tools([counting_tool(calls.clone())])
+            .with_interrupt_before([ReactAgentLabel::Tool])
+            .build();
+
+        let mut initial = MessagesState::default();
+        initial.push_message_owned(Message::user("mail bob"));
+        let mut stepper = agent.stepper(initial, None);
+        let mut interrupted = Vec::new();
+        loop {
+            match stepper.step().await.unwrap() {
+                StepEvent::Interrupted { next } => {
+                    // 暂停时工具尚未执行，模型的工具调用可以在此审查
+                    assert_eq!(calls.load(Ordering::SeqCst), 0);
+                    assert!(stepper.state().last_tool_calls().is_some());
+                    interrupted.push(next);
+                }
+                StepEvent::Step { .. } => {}
+                StepEvent::Finished => break,
+            }
+        }
+
+        assert_eq!(interrupted, [vec![ReactAgentLabel::Tool.intern()]]);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            stepper.into_state().last_message().unwrap().content(),
+            "Mail sent."
+        );
+    }
+}
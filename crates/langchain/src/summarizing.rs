@@ -0,0 +1,551 @@
+//! Keeping long-lived threads cheap to load.
+//!
+//! A [`SummarizingCheckpointer`] wraps any [`Checkpointer`] and changes what
+//! [`get`](Checkpointer::get) returns for a long thread: instead of the full
+//! history, the agent sees
+//!
+//! ```text
+//! [system messages, summary of older turns, recent turns]
+//! ```
+//!
+//! The full history is still what the wrapped checkpointer stores: when the
+//! agent saves a state that starts with the summary, the summary is swapped
+//! back for the messages it covers.
+//!
+//! # When summaries are computed
+//!
+//! A turn starts at a user message. On each load, the last `keep_recent_turns`
+//! turns are always kept as they are. Older turns that the stored summary does
+//! not cover yet are kept as they are too, until there are `summarize_every`
+//! of them; then they are folded into the summary with one model call and the
+//! new summary is written to the [`BaseStore`]. So the model sees at most
+//! `keep_recent_turns + summarize_every` turns plus the summary, and a thread
+//! is re-summarized once every `summarize_every` turns.
+//!
+//! # When summaries are invalidated
+//!
+//! The stored summary records how many messages it covers and a digest of
+//! them. If the thread's history no longer starts with those messages (it was
+//! edited, rewound or deleted and started over) the summary is discarded and
+//! recomputed from the current history. [`Checkpointer::delete`] removes the
+//! thread's summary as well.
+//!
+//! ```rust,ignore
+//! let checkpointer = SummarizingCheckpointer::new(
+//!     Arc::new(MemorySaver::new()),
+//!     Arc::new(InMemoryStore::new()),
+//!     Arc::new(summary_model),
+//! )
+//! .with_keep_recent_turns(10);
+//! let agent = ReactAgent::builder(model)
+//!     .with_checkpointer(Arc::new(checkpointer))
+//!     .build();
+//! ```
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use langchain_core::{
+    message::Message,
+    state::{ChatModel, InvokeOptions, MessagesState},
+    store::{BaseStore, Namespace},
+};
+use langgraph::checkpoint::{
+    Checkpoint, CheckpointError, CheckpointId, CheckpointListResult, CheckpointMetadata,
+    CheckpointQuery, CheckpointStats, Checkpointer, CleanupPolicy,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Turns kept verbatim at the end of the history by default.
+pub const DEFAULT_KEEP_RECENT_TURNS: usize = 10;
+
+/// Older turns collected before they are folded into the summary by default.
+pub const DEFAULT_SUMMARIZE_EVERY: usize = 10;
+
+/// Metadata key marking the summary message; its value is the number of
+/// messages the summary replaces.
+pub const SUMMARY_METADATA_KEY: &str = "summary_of";
+
+const DEFAULT_PROMPT: &str = "Summarize the conversation below so the summary can replace it. \
+Keep facts, decisions, user preferences, tool results that are still relevant and open tasks. \
+If a previous summary is given, merge it with the new messages into one summary. \
+Reply with the summary only.";
+
+const SUMMARY_KEY: &str = "summary";
+
+/// 存储中的摘要：覆盖历史开头（系统消息之后）的 `covered` 条消息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SummaryRecord {
+    summary: String,
+    covered: usize,
+    digest: String,
+}
+
+/// A [`Checkpointer`] that loads long threads as a summary plus the recent
+/// turns. See the [module documentation](self) for when summaries are
+/// computed and invalidated.
+pub struct SummarizingCheckpointer {
+    inner: Arc<dyn Checkpointer<MessagesState>>,
+    store: Arc<dyn BaseStore>,
+    model: Arc<dyn ChatModel>,
+    prompt: String,
+    keep_recent_turns: usize,
+    summarize_every: usize,
+}
+
+impl SummarizingCheckpointer {
+    /// Stores checkpoints in `inner` and summaries in `store`; `model` writes
+    /// the summaries.
+    pub fn new(
+        inner: Arc<dyn Checkpointer<MessagesState>>,
+        store: Arc<dyn BaseStore>,
+        model: Arc<dyn ChatModel>,
+    ) -> Self {
+        Self {
+            inner,
+            store,
+            model,
+            prompt: DEFAULT_PROMPT.to_owned(),
+            keep_recent_turns: DEFAULT_KEEP_RECENT_TURNS,
+            summarize_every: DEFAULT_SUMMARIZE_EVERY,
+        }
+    }
+
+    /// Number of most recent turns always loaded verbatim (at least one).
+    pub fn with_keep_recent_turns(mut self, turns: usize) -> Self {
+        self.keep_recent_turns = turns.max(1);
+        self
+    }
+
+    /// Number of older turns collected before the summary is updated (at
+    /// least one).
+    pub fn with_summarize_every(mut self, turns: usize) -> Self {
+        self.summarize_every = turns.max(1);
+        self
+    }
+
+    /// Replaces the instructions sent to the summary model.
+    pub fn with_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.prompt = prompt.into();
+        self
+    }
+
+    fn namespace(thread_id: &str) -> Namespace {
+        Namespace::new(vec!["summaries".to_owned(), thread_id.to_owned()])
+    }
+
+    async fn load_record(&self, thread_id: &str) -> Option<SummaryRecord> {
+        let bytes = match self
+            .store
+            .get(&Self::namespace(thread_id), SUMMARY_KEY)
+            .await
+        {
+            Ok(bytes) => bytes?,
+            Err(e) => {
+                tracing::warn!("Failed to read the summary of thread {thread_id}: {e}");
+                return None;
+            }
+        };
+        serde_json::from_slice(&bytes).ok()
+    }
+
+    async fn save_record(&self, thread_id: &str, record: &SummaryRecord) {
+        let bytes = serde_json::to_vec(record).expect("summary record serializes");
+        if let Err(e) = self
+            .store
+            .put(&Self::namespace(thread_id), SUMMARY_KEY, bytes)
+            .await
+        {
+            tracing::warn!("Failed to save the summary of thread {thread_id}: {e}");
+        }
+    }
+
+    async fn summarize(
+        &self,
+        previous: Option<&str>,
+        messages: &[Arc<Message>],
+    ) -> Result<String, langchain_core::ModelError> {
+        let transcript = messages
+            .iter()
+            .map(|message| message.render(None))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let input = match previous {
+            Some(summary) => {
+                format!("Previous summary:\n{summary}\n\nNew messages:\n{transcript}")
+            }
+            None => transcript,
+        };
+        let request = [
+            Arc::new(Message::system(self.prompt.clone())),
+            Arc::new(Message::user(input)),
+        ];
+        let completion = self
+            .model
+            .invoke(&request, &InvokeOptions::default())
+            .await?;
+        Ok(completion
+            .messages
+            .last()
+            .map(|message| message.content().trim().to_owned())
+            .unwrap_or_default())
+    }
+
+    /// 把完整的历史压缩为 `[系统消息, 摘要, 未压缩的消息]`，需要时更新摘要
+    async fn compact(&self, thread_id: &str, state: &mut MessagesState) {
+        let messages: Vec<Arc<Message>> = state.messages.iter().cloned().collect();
+        let system = leading_system(&messages);
+        let rest = &messages[system..];
+
+        let turn_starts: Vec<usize> = rest
+            .iter()
+            .enumerate()
+            .filter(|(index, m)| *index == 0 || matches!(m.as_ref(), Message::User { .. }))
+            .map(|(index, _)| index)
+            .collect();
+        if turn_starts.len() <= self.keep_recent_turns {
+            return;
+        }
+        // 最近的若干轮之前的消息都可以被摘要
+        let boundary = turn_starts[turn_starts.len() - self.keep_recent_turns];
+
+        let mut record = self.load_record(thread_id).await.filter(|record| {
+            let valid =
+                record.covered <= boundary && record.digest == digest(&rest[..record.covered]);
+            if !valid {
+                tracing::debug!("Summary of thread {thread_id} no longer matches its history");
+            }
+            valid
+        });
+        let covered = record.as_ref().map_or(0, |record| record.covered);
+        let pending_turns = turn_starts
+            .iter()
+            .filter(|start| (covered..boundary).contains(start))
+            .count();
+        if pending_turns >= self.summarize_every {
+            let previous = record.as_ref().map(|record| record.summary.as_str());
+            match self.summarize(previous, &rest[covered..boundary]).await {
+                Ok(summary) => {
+                    let updated = SummaryRecord {
+                        summary,
+                        covered: boundary,
+                        digest: digest(&rest[..boundary]),
+                    };
+                    self.save_record(thread_id, &updated).await;
+                    record = Some(updated);
+                }
+                Err(e) => tracing::warn!("Failed to summarize thread {thread_id}: {e}"),
+            }
+        }
+
+        let Some(record) = record else {
+            return;
+        };
+        let summary = Message::system(format!(
+            "Summary of the earlier conversation:\n{}",
+            record.summary
+        ))
+        .with_metadata(SUMMARY_METADATA_KEY, serde_json::json!(record.covered));
+        let mut compacted = state.messages.take(system);
+        compacted.push_back(Arc::new(summary));
+        compacted.append(state.messages.skip(system + record.covered));
+        state.messages = compacted;
+    }
+
+    /// 把状态中的摘要换回它覆盖的消息；取不到完整历史时原样保存
+    async fn expand(&self, checkpoint: &Checkpoint<MessagesState>) -> Option<MessagesState> {
+        let state = &checkpoint.state;
+        let (position, covered) = state.messages.iter().enumerate().find_map(|(i, m)| {
+            let covered = m.metadata().get(SUMMARY_METADATA_KEY)?.as_u64()?;
+            Some((i, covered as usize))
+        })?;
+        let thread_id = &checkpoint.metadata.thread_id;
+        let full = match self.inner.get(thread_id).await {
+            Ok(Some(full)) => full.state,
+            _ => {
+                tracing::warn!("Full history of thread {thread_id} not found, saving the summary");
+                return None;
+            }
+        };
+        let system = full
+            .messages
+            .iter()
+            .take_while(|m| is_plain_system(m))
+            .count();
+        if full.messages.len() < system + covered {
+            tracing::warn!("History of thread {thread_id} is shorter than its summary");
+            return None;
+        }
+
+        let mut expanded = state.clone();
+        let mut messages = state.messages.take(position);
+        messages.append(full.messages.clone().slice(system..system + covered));
+        messages.append(state.messages.skip(position + 1));
+        expanded.messages = messages;
+        Some(expanded)
+    }
+}
+
+/// 不是摘要的系统消息
+fn is_plain_system(message: &Arc<Message>) -> bool {
+    matches!(message.as_ref(), Message::System { .. })
+        && !message.metadata().contains_key(SUMMARY_METADATA_KEY)
+}
+
+fn leading_system(messages: &[Arc<Message>]) -> usize {
+    messages.iter().take_while(|m| is_plain_system(m)).count()
+}
+
+fn digest(messages: &[Arc<Message>]) -> String {
+    let mut hasher = Sha256::new();
+    for message in messages {
+        hasher.update(serde_json::to_vec(message.as_ref()).expect("messages serialize"));
+        hasher.update(b"\n");
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+#[async_trait]
+impl Checkpointer<MessagesState> for SummarizingCheckpointer {
+    async fn get(
+        &self,
+        thread_id: &str,
+    ) -> Result<Option<Checkpoint<MessagesState>>, CheckpointError> {
+        let Some(mut checkpoint) = self.inner.get(thread_id).await? else {
+            return Ok(None);
+        };
+        self.compact(thread_id, &mut checkpoint.state).await;
+        Ok(Some(checkpoint))
+    }
+
+    async fn put(&self, checkpoint: &Checkpoint<MessagesState>) -> Result<(), CheckpointError> {
+        match self.expand(checkpoint).await {
+            Some(state) => {
+                let expanded = Checkpoint {
+                    state,
+                    ..checkpoint.clone()
+                };
+                self.inner.put(&expanded).await
+            }
+            None => self.inner.put(checkpoint).await,
+        }
+    }
+
+    async fn delete(&self, thread_id: &str) -> Result<(), CheckpointError> {
+        if let Err(e) = self
+            .store
+            .delete(&Self::namespace(thread_id), SUMMARY_KEY)
+            .await
+        {
+            tracing::warn!("Failed to delete the summary of thread {thread_id}: {e}");
+        }
+        self.inner.delete(thread_id).await
+    }
+
+    async fn delete_checkpoint(&self, checkpoint_id: &CheckpointId) -> Result<(), CheckpointError> {
+        self.inner.delete_checkpoint(checkpoint_id).await
+    }
+
+    async fn list(
+        &self,
+        thread_id: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<CheckpointMetadata>, CheckpointError> {
+        self.inner.list(thread_id, limit).await
+    }
+
+    async fn search(
+        &self,
+        query: CheckpointQuery,
+    ) -> Result<CheckpointListResult, CheckpointError> {
+        self.inner.search(query).await
+    }
+
+    async fn get_by_id(
+        &self,
+        checkpoint_id: &CheckpointId,
+    ) -> Result<Option<Checkpoint<MessagesState>>, CheckpointError> {
+        self.inner.get_by_id(checkpoint_id).await
+    }
+
+    async fn get_metadata(
+        &self,
+        checkpoint_id: &CheckpointId,
+    ) -> Result<Option<CheckpointMetadata>, CheckpointError> {
+        self.inner.get_metadata(checkpoint_id).await
+    }
+
+    async fn get_metadata_parent_id(
+        &self,
+        checkpoint_id: &CheckpointId,
+    ) -> Result<Option<String>, CheckpointError> {
+        self.inner.get_metadata_parent_id(checkpoint_id).await
+    }
+
+    // 以下两个方法的默认实现会调用 `get`，这里直接读取底层的检查点，避免触发摘要
+    async fn get_metadata_id_by_thread_id(&self, thread_id: &str) -> Option<String> {
+        self.inner.get_metadata_id_by_thread_id(thread_id).await
+    }
+
+    async fn get_metadata_parent_id_by_thread_id(&self, thread_id: &str) -> Option<String> {
+        self.inner
+            .get_metadata_parent_id_by_thread_id(thread_id)
+            .await
+    }
+
+    async fn get_history(
+        &self,
+        checkpoint_id: &CheckpointId,
+    ) -> Result<Vec<CheckpointMetadata>, CheckpointError> {
+        self.inner.get_history(checkpoint_id).await
+    }
+
+    async fn get_at_time(
+        &self,
+        thread_id: &str,
+        time: i64,
+    ) -> Result<Option<Checkpoint<MessagesState>>, CheckpointError> {
+        self.inner.get_at_time(thread_id, time).await
+    }
+
+    async fn cleanup(&self, policy: &CleanupPolicy) -> Result<usize, CheckpointError> {
+        self.inner.cleanup(policy).await
+    }
+
+    async fn stats(&self, thread_id: Option<&str>) -> Result<CheckpointStats, CheckpointError> {
+        self.inner.stats(thread_id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use langchain_core::store::InMemoryStore;
+    use langgraph::checkpoint::MemorySaver;
+
+    use super::*;
+    use crate::{ReactAgent, testing::MockModel};
+
+    #[tokio::test]
+    async fn long_threads_load_as_a_summary_plus_recent_turns() {
+        let summarizer = Arc::new(MockModel::from_fn(|request| {
+            Ok(Message::assistant(format!(
+                "summary #{}",
+                request.messages.len()
+            )))
+        }));
+        let model = Arc::new(MockModel::from_fn(|request| {
+            let last = request.messages.last().unwrap().content().to_owned();
+            Ok(Message::assistant(format!("re: {last}")))
+        }));
+        let inner: Arc<dyn Checkpointer<MessagesState>> = Arc::new(MemorySaver::new());
+        let store = Arc::new(InMemoryStore::new());
+        let checkpointer = Arc::new(SummarizingCheckpointer::new(
+            inner.clone(),
+            store.clone(),
+            summarizer.clone(),
+        ));
+        let agent = ReactAgent::builder(model.clone())
+            .with_system_prompt("You are helpful.")
+            .with_checkpointer(checkpointer.clone())
+            .build();
+
+        for turn in 0..100 {
+            agent
+                .invoke(Message::user(format!("turn {turn}")), Some("thread"))
+                .await
+                .unwrap();
+        }
+
+        // 底层始终保存完整的历史
+        let full = inner.get("thread").await.unwrap().unwrap().state;
+        let user_turns = full
+            .messages
+            .iter()
+            .filter(|m| matches!(m.as_ref(), Message::User { .. }))
+            .count();
+        assert_eq!(user_turns, 100);
+        assert_eq!(full.messages.last().unwrap().content(), "re: turn 99");
+        assert!(
+            full.messages
+                .iter()
+                .all(|m| !m.metadata().contains_key(SUMMARY_METADATA_KEY))
+        );
+
+        // 每 10 轮更新一次摘要：加载第 20、30、…、90 轮时
+        let summaries = summarizer.requests();
+        assert_eq!(summaries.len(), 8);
+        assert!(
+            !summaries[0].messages[1]
+                .content()
+                .contains("Previous summary")
+        );
+        assert!(
+            summaries[1].messages[1]
+                .content()
+                .contains("Previous summary")
+        );
+        // 模型看到的历史有上限：系统提示 + 摘要 + 至多 19 轮 + 新消息
+        let requests = model.requests();
+        assert!(requests.iter().all(|request| request.messages.len() <= 42));
+        let last = requests.last().unwrap();
+        assert!(
+            last.messages
+                .iter()
+                .any(|m| m.content().contains("summary #"))
+        );
+        assert!(last.messages.iter().all(|m| m.content() != "turn 0"));
+
+        // 第 100 轮之后加载时又积累了 10 轮；之后摘要已缓存，再次加载不会调用模型
+        checkpointer.get("thread").await.unwrap();
+        assert_eq!(summarizer.requests().len(), 9);
+        let view = checkpointer.get("thread").await.unwrap().unwrap().state;
+        assert_eq!(summarizer.requests().len(), 9);
+        assert_eq!(view.messages.last().unwrap().content(), "re: turn 99");
+        assert!(view.messages.len() < full.messages.len());
+    }
+
+    #[tokio::test]
+    async fn summaries_are_invalidated_when_the_history_changes() {
+        let summarizer = Arc::new(MockModel::from_fn(|_| Ok(Message::assistant("summary"))));
+        let inner: Arc<dyn Checkpointer<MessagesState>> = Arc::new(MemorySaver::new());
+        let store = Arc::new(InMemoryStore::new());
+        let checkpointer =
+            SummarizingCheckpointer::new(inner.clone(), store.clone(), summarizer.clone())
+                .with_keep_recent_turns(2)
+                .with_summarize_every(2);
+        let history = |prefix: &str| {
+            let mut state = MessagesState::default();
+            for turn in 0..6 {
+                state
+                    .messages
+                    .push_back(Arc::new(Message::user(format!("{prefix} {turn}"))));
+                state.messages.push_back(Arc::new(Message::assistant("ok")));
+            }
+            Checkpoint::new_auto(state, "thread".to_owned(), 0, None)
+        };
+
+        inner.put(&history("question")).await.unwrap();
+        let view = checkpointer.get("thread").await.unwrap().unwrap().state;
+        assert_eq!(view.messages.len(), 5);
+        assert_eq!(view.messages[0].metadata()[SUMMARY_METADATA_KEY], 8);
+        assert_eq!(summarizer.requests().len(), 1);
+
+        // 历史被改写后，旧摘要失效并从头重新生成
+        inner.put(&history("edited")).await.unwrap();
+        checkpointer.get("thread").await.unwrap();
+        let requests = summarizer.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].messages[1].content().contains("edited 0"));
+        assert!(
+            !requests[1].messages[1]
+                .content()
+                .contains("Previous summary")
+        );
+
+        // 删除会话时摘要一并删除
+        checkpointer.delete("thread").await.unwrap();
+        let namespace = SummarizingCheckpointer::namespace("thread");
+        assert!(store.get(&namespace, SUMMARY_KEY).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,543 @@
+//! Supervisor agent that routes a conversation between specialized workers.
+//!
+//! The supervisor is an [`LlmNode`] whose only tools are one `transfer_to_<worker>`
+//! handoff per worker. When it calls a handoff, the conversation goes to that
+//! worker's [`ReactAgent`] graph, and the worker's final reply comes back to the
+//! supervisor as the handoff's tool result. The supervisor then hands off again
+//! or answers the user, which ends the run.
+//!
+//! Workers see the user/assistant conversation (without the supervisor's system
+//! prompt and handoff calls) plus the task from the handoff, under their own
+//! system prompt. Their intermediate tool calls stay private; only the final
+//! reply, the usage and the model call count reach the supervisor's state.
+
+use std::collections::{HashMap, HashSet};
+
+use async_trait::async_trait;
+use langchain_core::{
+    message::{Message, ToolCall},
+    request::{ToolFunction, ToolSpec},
+    state::{ChatModel, ChatStreamEvent, MessagesState},
+};
+use langgraph::{
+    checkpoint::Configuration,
+    label::{GraphLabel, InternedGraphLabel, intern_label_name},
+    node::{EventSink, Node, NodeContext},
+    state_graph::{RunStrategy, StateGraph},
+};
+use serde_json::json;
+use smallvec::{SmallVec, smallvec};
+
+use crate::{AgentError, ReactAgent, ReactAgentSpec, node::llm::LlmNode};
+
+/// Prefix of the handoff tool offered for each worker.
+const HANDOFF_PREFIX: &str = "transfer_to_";
+
+/// Default number of handoffs allowed in one run.
+pub const DEFAULT_MAX_HANDOFFS: usize = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, GraphLabel)]
+enum SupervisorLabel {
+    Supervisor,
+}
+
+/// 工作者节点的标签，名称在构建时确定，所以不能用枚举派生
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct WorkerLabel(&'static str);
+
+impl GraphLabel for WorkerLabel {
+    fn dyn_clone(&self) -> Box<dyn GraphLabel> {
+        Box::new(self.clone())
+    }
+
+    fn as_str(&self) -> &'static str {
+        self.0
+    }
+}
+
+struct Worker {
+    name: String,
+    description: String,
+    agent: ReactAgent,
+}
+
+struct WorkerNode {
+    worker: Worker,
+}
+
+impl WorkerNode {
+    /// 交给工作者的对话：去掉系统消息、交接调用及其结果，最后附上本次任务
+    fn worker_input(
+        &self,
+        input: &MessagesState,
+        task: &str,
+        config: &Configuration,
+    ) -> Result<MessagesState, AgentError> {
+        let mut state = MessagesState::default();
+        if let Some(system_prompt) = self.worker.agent.render_system_prompt(config)? {
+            state.push_message_owned(Message::system(system_prompt));
+        }
+        for message in &input.messages {
+            match message.as_ref() {
+                Message::User { .. } => state.push_message(message.clone()),
+                Message::Assistant {
+                    content,
+                    tool_calls,
+                    ..
+                } if tool_calls.is_none() && !content.is_empty() => {
+                    state.push_message(message.clone())
+                }
+                _ => {}
+            }
+        }
+        if !task.is_empty() {
+            state.push_message_owned(Message::user(task));
+        }
+        Ok(state)
+    }
+}
+
+#[async_trait]
+impl Node<MessagesState, MessagesState, AgentError, ChatStreamEvent> for WorkerNode {
+    async fn run_sync(
+        &self,
+        input: &MessagesState,
+        context: NodeContext<'_>,
+    ) -> Result<MessagesState, AgentError> {
+        let handoff = format!("{HANDOFF_PREFIX}{}", self.worker.name);
+        // 工作者沿用本次运行的模板变量和截止时间，但不共享线程和工具范围
+        let config = Configuration {
+            vars: context.config.vars.clone(),
+            deadline: context.config.deadline,
+            ..Configuration::default()
+        };
+        let calls = input.last_tool_calls().unwrap_or_default();
+        let mut delta = MessagesState::default();
+
+        let mut handled = false;
+        for call in calls {
+            // 每次只交给一个工作者，同一轮的其他交接让监督者稍后再调用
+            if handled || call.function_name() != handoff {
+                delta.push_message_owned(Message::tool(
+                    format!(
+                        "Error: `{}` was not run; hand off to one worker at a time",
+                        call.function_name()
+                    ),
+                    call.id().to_owned(),
+                ));
+                continue;
+            }
+            handled = true;
+
+            let task = task_of(call);
+            tracing::debug!("handing off to `{}`: {task}", self.worker.name);
+            let result = self
+                .worker
+                .agent
+                .run_graph(self.worker_input(input, &task, &config)?, &config, None)
+                .await?;
+            let reply = result
+                .last_assistant()
+                .map(|m| m.content().to_owned())
+                .unwrap_or_default();
+            delta.push_message_owned(Message::tool(reply, call.id().to_owned()));
+            delta.usage += &result.usage;
+            delta.llm_calls += result.llm_calls;
+        }
+        Ok(delta)
+    }
+
+    async fn run_stream(
+        &self,
+        input: &MessagesState,
+        _sink: &dyn EventSink<ChatStreamEvent>,
+        context: NodeContext<'_>,
+    ) -> Result<MessagesState, AgentError> {
+        self.run_sync(input, context).await
+    }
+}
+
+/// 交接调用中的任务描述，参数无效时为空
+fn task_of(call: &ToolCall) -> String {
+    call.arguments()
+        .ok()
+        .and_then(|args| args["task"].as_str().map(ToOwned::to_owned))
+        .unwrap_or_default()
+}
+
+fn handoff_spec(worker: &Worker) -> ToolSpec {
+    ToolSpec::Function {
+        function: ToolFunction {
+            name: format!("{HANDOFF_PREFIX}{}", worker.name),
+            description: format!(
+                "Hand the conversation to `{}`: {}",
+                worker.name, worker.description
+            ),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "task": {
+                        "type": "string",
+                        "description": "What the worker should do, with any details it needs"
+                    }
+                },
+                "required": ["task"]
+            }),
+            examples: Vec::new(),
+            output_schema: None,
+        },
+    }
+}
+
+/// Builder for [`Supervisor`].
+pub struct SupervisorBuilder<M> {
+    model: M,
+    workers: Vec<Worker>,
+    system_prompt: Option<String>,
+    max_handoffs: usize,
+}
+
+impl<M> SupervisorBuilder<M>
+where
+    M: ChatModel + Send + Sync + 'static,
+{
+    pub fn new(model: M) -> Self {
+        Self {
+            model,
+            workers: Vec::new(),
+            system_prompt: None,
+            max_handoffs: DEFAULT_MAX_HANDOFFS,
+        }
+    }
+
+    /// Adds a worker the supervisor can hand off to. `description` tells the
+    /// supervisor what the worker is good at; `name` must be unique and is used
+    /// in the handoff tool name, so it should match `[A-Za-z0-9_-]+`.
+    pub fn with_worker(
+        mut self,
+        name: impl Into<String>,
+        description: impl Into<String>,
+        agent: ReactAgent,
+    ) -> Self {
+        self.workers.push(Worker {
+            name: name.into(),
+            description: description.into(),
+            agent,
+        });
+        self
+    }
+
+    /// Replaces the default supervisor instructions. The handoff tools are
+    /// always offered, so the prompt only needs to say when to use which worker.
+    pub fn with_system_prompt<S: Into<String>>(mut self, system_prompt: S) -> Self {
+        self.system_prompt = Some(system_prompt.into());
+        self
+    }
+
+    /// Maximum number of handoffs in one run (default [`DEFAULT_MAX_HANDOFFS`]).
+    /// A run that needs more fails with [`AgentError::MaxStepsExceeded`].
+    pub fn with_max_handoffs(mut self, max_handoffs: usize) -> Self {
+        self.max_handoffs = max_handoffs;
+        self
+    }
+
+    pub fn build(self) -> Supervisor {
+        self.try_build().expect("invalid supervisor graph")
+    }
+
+    pub fn try_build(self) -> Result<Supervisor, AgentError> {
+        if self.workers.is_empty() {
+            return Err(AgentError::Agent(
+                "supervisor needs at least one worker".to_owned(),
+            ));
+        }
+        let mut names = HashSet::new();
+        if let Some(worker) = self.workers.iter().find(|w| !names.insert(&w.name)) {
+            return Err(AgentError::Agent(format!(
+                "duplicate worker name: {}",
+                worker.name
+            )));
+        }
+
+        let system_prompt = self.system_prompt.unwrap_or_else(|| {
+            let mut prompt = "You are a supervisor managing these workers:\n".to_owned();
+            for worker in &self.workers {
+                prompt.push_str(&format!("- {}: {}\n", worker.name, worker.description));
+            }
+            prompt.push_str(
+                "Hand the conversation to the worker best suited for the next step, one at a time. \
+                 When the user's request is fully handled, reply to the user directly.",
+            );
+            prompt
+        });
+
+        let mut graph: StateGraph<ReactAgentSpec> = StateGraph::new(
+            SupervisorLabel::Supervisor,
+            |old: &mut MessagesState, update: MessagesState| old.apply(update),
+        );
+        let specs = self.workers.iter().map(handoff_spec).collect();
+        graph.add_node(SupervisorLabel::Supervisor, LlmNode::new(self.model, specs));
+
+        let supervisor = SupervisorLabel::Supervisor.intern();
+        let mut routes = HashMap::new();
+        for worker in self.workers {
+            let label = WorkerLabel(intern_label_name(&format!("Worker:{}", worker.name))).intern();
+            routes.insert(format!("{HANDOFF_PREFIX}{}", worker.name), label);
+            graph.add_node(label, WorkerNode { worker });
+            graph.add_edge(label, supervisor);
+        }
+
+        // 调用交接工具时转到对应的工作者，直接回复用户时结束
+        let branches = routes.values().map(|&label| (label, label)).collect();
+        graph.add_condition_edge(
+            supervisor,
+            branches,
+            move |state: &MessagesState| -> SmallVec<[InternedGraphLabel; 2]> {
+                state
+                    .last_tool_calls()
+                    .and_then(|calls| calls.first())
+                    .and_then(|call| routes.get(call.function_name()))
+                    .map_or_else(SmallVec::new, |&label| smallvec![label])
+            },
+        );
+        graph.validate()?;
+
+        Ok(Supervisor {
+            graph,
+            system_prompt,
+            max_steps: 2 * self.max_handoffs + 1,
+        })
+    }
+}
+
+/// Multi-agent supervisor that delegates to named [`ReactAgent`] workers.
+///
+/// ```rust,ignore
+/// let researcher = ReactAgent::builder(model.clone()).with_tools([search_tool()]).build();
+/// let mathematician = ReactAgent::builder(model.clone()).with_tools([add_tool()]).build();
+///
+/// let supervisor = Supervisor::builder(model)
+///     .with_worker("researcher", "looks facts up on the web", researcher)
+///     .with_worker("mathematician", "does arithmetic", mathematician)
+///     .build();
+/// let state = supervisor.invoke(Message::user("What is the population of France plus 1000?")).await?;
+/// ```
+pub struct Supervisor {
+    pub graph: StateGraph<ReactAgentSpec>,
+    pub system_prompt: String,
+    max_steps: usize,
+}
+
+impl Supervisor {
+    pub fn builder<M>(model: M) -> SupervisorBuilder<M>
+    where
+        M: ChatModel + Send + Sync + 'static,
+    {
+        SupervisorBuilder::new(model)
+    }
+
+    /// Starts a new conversation with `message` and runs until the supervisor
+    /// replies to the user. The returned state holds the supervisor's view of
+    /// the conversation, with each worker's reply as a handoff tool result.
+    pub async fn invoke(&self, message: Message) -> Result<MessagesState, AgentError> {
+        let state = MessagesState::new(vec![Message::system(self.system_prompt.clone()), message]);
+        self.resume(state).await
+    }
+
+    /// Like [`Supervisor::invoke`], but fills the workers' system prompt
+    /// templates with `vars` as well, see [`ReactAgent::invoke_with_vars`].
+    pub async fn invoke_with_vars<I, K, V>(
+        &self,
+        message: Message,
+        vars: I,
+    ) -> Result<MessagesState, AgentError>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let state = MessagesState::new(vec![Message::system(self.system_prompt.clone()), message]);
+        self.resume_with_vars(state, vars).await
+    }
+
+    /// Continues an earlier conversation, e.g. the state returned by
+    /// [`Supervisor::invoke`] with a new user message appended.
+    pub async fn resume(&self, state: MessagesState) -> Result<MessagesState, AgentError> {
+        self.run(state, &Configuration::default()).await
+    }
+
+    /// Like [`Supervisor::resume`], but fills the workers' system prompt
+    /// templates with `vars` as well.
+    pub async fn resume_with_vars<I, K, V>(
+        &self,
+        state: MessagesState,
+        vars: I,
+    ) -> Result<MessagesState, AgentError>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let config = Configuration {
+            vars: vars
+                .into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+            ..Configuration::default()
+        };
+        self.run(state, &config).await
+    }
+
+    async fn run(
+        &self,
+        state: MessagesState,
+        config: &Configuration,
+    ) -> Result<MessagesState, AgentError> {
+        let (state, pending) = self
+            .graph
+            .run(
+                state,
+                config,
+                self.max_steps,
+                RunStrategy::StopAtNonLinear,
+                None,
+            )
+            .await?;
+        if !pending.is_empty() {
+            return Err(AgentError::MaxStepsExceeded {
+                steps: self.max_steps,
+                state: Box::new(state),
+            });
+        }
+        // 监督者调用了不存在的工作者时图直接结束，留下未回复的工具调用
+        if let Some(Message::Assistant {
+            tool_calls: Some(calls),
+            ..
+        }) = state.last_message().map(AsRef::as_ref)
+            && let Some(call) = calls.first()
+        {
+            return Err(AgentError::Agent(format!(
+                "supervisor called unknown worker tool `{}`",
+                call.function_name()
+            )));
+        }
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockModel;
+    use langchain_core::message::FunctionCall;
+    use std::sync::Arc;
+
+    fn handoff(id: &str, worker: &str, task: &str) -> Message {
+        Message::assistant_with_tool_calls(
+            "",
+            vec![ToolCall {
+                id: id.to_owned(),
+                type_name: "function".to_owned(),
+                function: FunctionCall {
+                    name: format!("{HANDOFF_PREFIX}{worker}"),
+                    arguments: json!({ "task": task }),
+                },
+            }],
+        )
+    }
+
+    #[tokio::test]
+    async fn hands_off_to_workers_and_collects_their_replies() {
+        let researcher = Arc::new(MockModel::new([Message::assistant(
+            "Paris has 2.1M people",
+        )]));
+        let writer = MockModel::new([Message::assistant("Paris: 2.1M residents.")]);
+        let supervisor = Supervisor::builder(MockModel::new([
+            handoff("call_1", "researcher", "find the population of Paris"),
+            handoff("call_2", "writer", "summarize it in one line"),
+            Message::assistant("Paris: 2.1M residents."),
+        ]))
+        .with_worker(
+            "researcher",
+            "finds facts",
+            ReactAgent::builder(researcher.clone())
+                .with_system_prompt("You research.")
+                .build(),
+        )
+        .with_worker("writer", "writes", ReactAgent::builder(writer).build())
+        .build();
+
+        let state = supervisor
+            .invoke(Message::user("How many people live in Paris?"))
+            .await
+            .unwrap();
+
+        let contents: Vec<_> = state.messages.iter().map(|m| m.content()).collect();
+        assert_eq!(
+            contents[3..],
+            [
+                "Paris has 2.1M people",
+                "",
+                "Paris: 2.1M residents.",
+                "Paris: 2.1M residents."
+            ]
+        );
+        // 工作者看到自己的系统提示、用户消息和任务，看不到监督者的提示与交接调用
+        let seen: Vec<_> = researcher.requests()[0]
+            .messages
+            .iter()
+            .map(|m| m.content().to_owned())
+            .collect();
+        assert_eq!(
+            seen,
+            [
+                "You research.",
+                "How many people live in Paris?",
+                "find the population of Paris"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn workers_render_their_prompt_templates_with_the_runs_vars() {
+        let worker = Arc::new(MockModel::new([Message::assistant("done")]));
+        let supervisor = Supervisor::builder(MockModel::new([
+            handoff("call_1", "worker", "do it"),
+            Message::assistant("done"),
+        ]))
+        .with_worker(
+            "worker",
+            "works",
+            ReactAgent::builder(worker.clone())
+                .with_system_prompt_template("You help {user}.", [] as [(&str, &str); 0])
+                .build(),
+        )
+        .build();
+
+        supervisor
+            .invoke_with_vars(Message::user("hi"), [("user", "Ann")])
+            .await
+            .unwrap();
+
+        assert_eq!(worker.requests()[0].messages[0].content(), "You help Ann.");
+    }
+
+    #[tokio::test]
+    async fn endless_handoffs_stop_at_the_limit() {
+        let worker = MockModel::new(vec![Message::assistant("done"); 2]);
+        let supervisor = Supervisor::builder(MockModel::new([
+            handoff("call_1", "worker", "again"),
+            handoff("call_2", "worker", "again"),
+        ]))
+        .with_worker("worker", "works", ReactAgent::builder(worker).build())
+        .with_max_handoffs(1)
+        .build();
+
+        let result = supervisor.invoke(Message::user("loop")).await;
+
+        assert!(matches!(
+            result,
+            Err(AgentError::MaxStepsExceeded { steps: 3, .. })
+        ));
+    }
+}
@@ -0,0 +1,605 @@
+//! Offline test helpers.
+//!
+//! [`MockModel`] implements [`ChatModel`] with scripted replies, so agents,
+//! graphs and middleware can be unit-tested without an API key or network.
+//! [`CassetteModel`] records a real model's replies once and replays them in
+//! later runs, for deterministic integration tests.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use langchain_core::{
+    ModelError,
+    message::{Message, ToolCall},
+    request::{ToolChoice, ToolSpec},
+    response::Usage,
+    state::{ChatCompletion, ChatModel, ChatStreamEvent, InvokeOptions, StandardChatStream},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+
+/// One model call recorded by [`MockModel`].
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    /// The messages sent to the model.
+    pub messages: Vec<Arc<Message>>,
+    /// The tools offered on this call.
+    pub tools: Vec<ToolSpec>,
+    /// The tool choice sent with this call.
+    pub tool_choice: Option<ToolChoice>,
+}
+
+type Responder = dyn Fn(&MockRequest) -> Result<Message, ModelError> + Send + Sync;
+
+enum Script {
+    Queue(Mutex<VecDeque<Result<Message, ModelError>>>),
+    Responder(Box<Responder>),
+}
+
+/// A [`ChatModel`] that replies from a script and records every request.
+///
+/// Replies come either from a queue (consumed in order; an exhausted queue is
+/// a [`ModelError::ResponseError`]) or from a closure over the request.
+/// Streaming yields the same reply as one content chunk (or several, see
+/// [`with_chunk_size`](Self::with_chunk_size)) plus one delta per tool call.
+/// [`with_latency`](Self::with_latency) makes every call take a while, for
+/// deadline and cancellation tests. Share it with an agent through an `Arc` to
+/// inspect the requests later:
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use langchain::{ReactAgent, testing::MockModel};
+/// use langchain_core::message::Message;
+///
+/// # async fn example() {
+/// let model = Arc::new(MockModel::new([
+///     MockModel::tool_call("get_weather", serde_json::json!({"city": "Paris"})),
+///     Message::assistant("It is sunny in Paris."),
+/// ]));
+/// let agent = ReactAgent::builder(model.clone()).build();
+/// let _ = agent.invoke(Message::user("Weather in Paris?"), None).await;
+/// assert_eq!(model.requests().len(), 2);
+/// # }
+/// ```
+pub struct MockModel {
+    script: Script,
+    usage: Usage,
+    latency: Duration,
+    chunk_size: Option<usize>,
+    requests: Mutex<Vec<MockRequest>>,
+}
+
+impl MockModel {
+    /// Replies with `responses` in order, one per call.
+    pub fn new<I>(responses: I) -> Self
+    where
+        I: IntoIterator<Item = Message>,
+    {
+        Self::with_script(Script::Queue(Mutex::new(
+            responses.into_iter().map(Ok).collect(),
+        )))
+    }
+
+    /// Computes each reply from the request, e.g. to echo the last user message.
+    pub fn from_fn<F>(responder: F) -> Self
+    where
+        F: Fn(&MockRequest) -> Result<Message, ModelError> + Send + Sync + 'static,
+    {
+        Self::with_script(Script::Responder(Box::new(responder)))
+    }
+
+    /// Replies with the content of the last message it is sent, e.g. to check
+    /// what middleware did to the prompt.
+    pub fn echo() -> Self {
+        Self::from_fn(|request| {
+            let last = request.messages.last().map(|m| m.content().to_owned());
+            Ok(Message::assistant(last.unwrap_or_default()))
+        })
+    }
+
+    fn with_script(script: Script) -> Self {
+        Self {
+            script,
+            usage: Usage::default(),
+            latency: Duration::ZERO,
+            chunk_size: None,
+            requests: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Queues another reply after the existing ones. Ignored for [`MockModel::from_fn`].
+    pub fn push_response(&self, message: Message) {
+        self.push(Ok(message));
+    }
+
+    /// Queues an error, e.g. to exercise retries or fallbacks.
+    pub fn push_error(&self, error: ModelError) {
+        self.push(Err(error));
+    }
+
+    fn push(&self, reply: Result<Message, ModelError>) {
+        if let Script::Queue(queue) = &self.script {
+            queue.lock().unwrap().push_back(reply);
+        }
+    }
+
+    /// Usage reported with every reply (default zero).
+    pub fn with_usage(mut self, usage: Usage) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    /// Waits `latency` before every reply (and before the first stream event).
+    /// The request is recorded when the call starts. Under a paused tokio clock
+    /// (`#[tokio::test(start_paused = true)]`) the wait costs no real time.
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Streams content and reasoning in chunks of at most `chars` characters
+    /// instead of one chunk each, e.g. to test partial parsing.
+    pub fn with_chunk_size(mut self, chars: usize) -> Self {
+        self.chunk_size = Some(chars.max(1));
+        self
+    }
+
+    /// An assistant message calling one tool, with id `call_0`.
+    pub fn tool_call(name: &str, arguments: serde_json::Value) -> Message {
+        Self::tool_calls([(name, arguments)])
+    }
+
+    /// An assistant message calling several tools at once, with ids `call_<index>`.
+    pub fn tool_calls<'a, I>(calls: I) -> Message
+    where
+        I: IntoIterator<Item = (&'a str, serde_json::Value)>,
+    {
+        let calls = calls
+            .into_iter()
+            .enumerate()
+            .map(|(index, (name, arguments))| {
+                ToolCall::new(format!("call_{index}"), name, arguments)
+            })
+            .collect();
+        Message::assistant_with_tool_calls("", calls)
+    }
+
+    /// Every request received so far, in call order.
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// The most recent request, if any.
+    pub fn last_request(&self) -> Option<MockRequest> {
+        self.requests.lock().unwrap().last().cloned()
+    }
+
+    /// Number of model calls so far.
+    pub fn call_count(&self) -> usize {
+        self.requests.lock().unwrap().len()
+    }
+
+    async fn reply(
+        &self,
+        messages: &[Arc<Message>],
+        options: &InvokeOptions<'_>,
+    ) -> Result<Message, ModelError> {
+        let request = MockRequest {
+            messages: messages.to_vec(),
+            tools: options.tools.map(<[ToolSpec]>::to_vec).unwrap_or_default(),
+            tool_choice: options.tool_choice.clone(),
+        };
+        let reply = match &self.script {
+            Script::Queue(queue) => queue.lock().unwrap().pop_front().unwrap_or_else(|| {
+                Err(ModelError::ResponseError(
+                    "MockModel has no scripted responses left".to_owned(),
+                ))
+            }),
+            Script::Responder(responder) => responder(&request),
+        };
+        self.requests.lock().unwrap().push(request);
+        if !self.latency.is_zero() {
+            tokio::time::sleep(self.latency).await;
+        }
+        reply
+    }
+}
+
+#[async_trait]
+impl ChatModel for MockModel {
+    async fn invoke(
+        &self,
+        messages: &[Arc<Message>],
+        options: &InvokeOptions<'_>,
+    ) -> Result<ChatCompletion, ModelError> {
+        let message = self.reply(messages, options).await?;
+        Ok(ChatCompletion {
+            messages: vec![Arc::new(message)],
+            usage: self.usage.clone(),
+        })
+    }
+
+    async fn stream(
+        &self,
+        messages: &[Arc<Message>],
+        options: &InvokeOptions<'_>,
+    ) -> Result<StandardChatStream, ModelError> {
+        let message = self.reply(messages, options).await?;
+        Ok(message_stream(message, self.usage.clone(), self.chunk_size))
+    }
+}
+
+/// 把一条完整的回复转换为流：思考内容和内容按 `chunk_size` 个字符分片（未设置时各一个分片），
+/// 每个工具调用一个增量
+fn message_stream(message: Message, usage: Usage, chunk_size: Option<usize>) -> StandardChatStream {
+    let mut events = Vec::new();
+    if let Message::Assistant {
+        reasoning_content: Some(reasoning),
+        ..
+    } = &message
+    {
+        events.extend(
+            chunks(reasoning, chunk_size).map(|chunk| Ok(ChatStreamEvent::Reasoning(chunk))),
+        );
+    }
+    events.extend(
+        chunks(message.content(), chunk_size).map(|chunk| Ok(ChatStreamEvent::Content(chunk))),
+    );
+    let mut finish_reason = "stop";
+    if let Message::Assistant {
+        tool_calls: Some(calls),
+        ..
+    } = &message
+    {
+        finish_reason = "tool_calls";
+        for (index, call) in calls.iter().enumerate() {
+            events.push(Ok(ChatStreamEvent::ToolCallDelta {
+                index,
+                id: Some(call.id.clone()),
+                type_name: Some(call.type_name.clone()),
+                name: Some(call.function.name.clone()),
+                arguments: Some(call.function.arguments.to_string()),
+            }));
+        }
+    }
+    events.push(Ok(ChatStreamEvent::Done {
+        finish_reason: Some(finish_reason.to_owned()),
+        usage: Some(usage),
+    }));
+    Box::pin(futures::stream::iter(events))
+}
+
+/// 按字符把 `text` 切成最多 `size` 个字符的分片，空文本没有分片
+fn chunks(text: &str, size: Option<usize>) -> impl Iterator<Item = String> + '_ {
+    let size = size.unwrap_or(usize::MAX);
+    let mut rest = text;
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        let end = rest
+            .char_indices()
+            .nth(size)
+            .map_or(rest.len(), |(index, _)| index);
+        let (chunk, tail) = rest.split_at(end);
+        rest = tail;
+        Some(chunk.to_owned())
+    })
+}
+
+/// Whether a [`CassetteModel`] may call the wrapped model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CassetteMode {
+    /// Replay recorded requests; call the wrapped model for new ones and add
+    /// them to the cassette.
+    Record,
+    /// Only replay. A request missing from the cassette is an error, so tests
+    /// never reach the network.
+    Replay,
+}
+
+/// One recorded model call.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CassetteEntry {
+    /// Hash of the request, used for matching.
+    pub key: String,
+    /// The request in OpenAI format, for readers of the cassette.
+    pub request: Value,
+    pub response: Message,
+    #[serde(default)]
+    pub usage: Usage,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Cassette {
+    interactions: Vec<CassetteEntry>,
+}
+
+/// VCR-style wrapper that records a real model's replies to a JSON file and
+/// replays them on later runs.
+///
+/// Requests are matched by a SHA-256 hash of the messages (in OpenAI format,
+/// so message metadata does not affect matching) and the offered tools.
+/// Identical requests made several times replay their recordings in order.
+/// The cassette is pretty-printed JSON and is rewritten after each new
+/// recording, so it can be reviewed and committed next to the test.
+///
+/// ```rust,ignore
+/// let mode = if env::var("RECORD").is_ok() { CassetteMode::Record } else { CassetteMode::Replay };
+/// let model = CassetteModel::new(ChatOpenAI::new(...), "tests/cassettes/weather.json", mode)?;
+/// let agent = ReactAgent::builder(model).with_tools([langchain_tools::get_weather_tool()]).build();
+/// ```
+pub struct CassetteModel<M> {
+    inner: M,
+    path: PathBuf,
+    mode: CassetteMode,
+    state: Mutex<CassetteState>,
+}
+
+struct CassetteState {
+    cassette: Cassette,
+    /// 每个键已回放的次数
+    replayed: HashMap<String, usize>,
+}
+
+impl<M: ChatModel> CassetteModel<M> {
+    /// Loads the cassette at `path`, or starts an empty one if the file does
+    /// not exist yet.
+    pub fn new(inner: M, path: impl Into<PathBuf>, mode: CassetteMode) -> io::Result<Self> {
+        let path = path.into();
+        let cassette = match fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Cassette::default(),
+            Err(e) => return Err(e),
+        };
+        Ok(Self {
+            inner,
+            path,
+            mode,
+            state: Mutex::new(CassetteState {
+                cassette,
+                replayed: HashMap::new(),
+            }),
+        })
+    }
+
+    /// All recorded interactions.
+    pub fn entries(&self) -> Vec<CassetteEntry> {
+        self.state.lock().unwrap().cassette.interactions.clone()
+    }
+
+    fn request_json(messages: &[Arc<Message>], options: &InvokeOptions<'_>) -> Value {
+        json!({
+            "messages": messages.iter().map(|m| m.to_openai()).collect::<Vec<_>>(),
+            "tools": options.tools.unwrap_or_default(),
+        })
+    }
+
+    fn replay(&self, key: &str) -> Option<CassetteEntry> {
+        let mut state = self.state.lock().unwrap();
+        let CassetteState { cassette, replayed } = &mut *state;
+        let count = replayed.entry(key.to_owned()).or_default();
+        let entry = cassette
+            .interactions
+            .iter()
+            .filter(|entry| entry.key == key)
+            .nth(*count)?
+            .clone();
+        *count += 1;
+        Some(entry)
+    }
+
+    fn record(&self, entry: CassetteEntry) -> Result<(), ModelError> {
+        let mut state = self.state.lock().unwrap();
+        *state.replayed.entry(entry.key.clone()).or_default() += 1;
+        state.cassette.interactions.push(entry);
+        let text = serde_json::to_string_pretty(&state.cassette).map_err(ModelError::ParseError)?;
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(|e| cassette_error(&self.path, e))?;
+        }
+        fs::write(&self.path, text).map_err(|e| cassette_error(&self.path, e))
+    }
+
+    async fn reply(
+        &self,
+        messages: &[Arc<Message>],
+        options: &InvokeOptions<'_>,
+    ) -> Result<(Message, Usage), ModelError> {
+        let request = Self::request_json(messages, options);
+        let key = format!("{:x}", Sha256::digest(request.to_string()));
+        if let Some(entry) = self.replay(&key) {
+            return Ok((entry.response, entry.usage));
+        }
+        if self.mode == CassetteMode::Replay {
+            return Err(ModelError::ResponseError(format!(
+                "request {key} is not recorded in cassette {}",
+                self.path.display()
+            )));
+        }
+
+        let completion = self.inner.invoke(messages, options).await?;
+        let response = completion
+            .messages
+            .last()
+            .map(|m| m.as_ref().clone())
+            .ok_or_else(|| ModelError::ResponseError("model returned no message".to_owned()))?;
+        self.record(CassetteEntry {
+            key,
+            request,
+            response: response.clone(),
+            usage: completion.usage.clone(),
+        })?;
+        Ok((response, completion.usage))
+    }
+}
+
+fn cassette_error(path: &Path, e: io::Error) -> ModelError {
+    ModelError::ResponseError(format!("failed to write cassette {}: {e}", path.display()))
+}
+
+#[async_trait]
+impl<M: ChatModel> ChatModel for CassetteModel<M> {
+    async fn invoke(
+        &self,
+        messages: &[Arc<Message>],
+        options: &InvokeOptions<'_>,
+    ) -> Result<ChatCompletion, ModelError> {
+        let (message, usage) = self.reply(messages, options).await?;
+        Ok(ChatCompletion {
+            messages: vec![Arc::new(message)],
+            usage,
+        })
+    }
+
+    /// 录制时也通过 `invoke` 调用被包装的模型，回放的流由完整回复生成
+    async fn stream(
+        &self,
+        messages: &[Arc<Message>],
+        options: &InvokeOptions<'_>,
+    ) -> Result<StandardChatStream, ModelError> {
+        let (message, usage) = self.reply(messages, options).await?;
+        Ok(message_stream(message, usage, None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ReactAgent;
+    use futures::StreamExt;
+    use langchain_core::{ToolError, state::RegisteredTool};
+
+    fn weather_tool() -> RegisteredTool<ToolError> {
+        RegisteredTool::new(
+            "get_weather".to_owned(),
+            "weather for a city".to_owned(),
+            serde_json::json!({"type": "object", "properties": {"city": {"type": "string"}}}),
+            Arc::new(|args| {
+                Box::pin(async move {
+                    let city = args["city"].as_str().unwrap_or_default().to_owned();
+                    Ok(serde_json::json!(format!("sunny in {city}")))
+                })
+            }),
+        )
+    }
+
+    #[tokio::test]
+    async fn scripted_tool_call_runs_the_tool_and_records_prompts() {
+        let model = Arc::new(MockModel::new([
+            MockModel::tool_call("get_weather", serde_json::json!({"city": "Paris"})),
+            Message::assistant("It is sunny."),
+        ]));
+        let agent = ReactAgent::builder(model.clone())
+            .with_tools([weather_tool()])
+            .build();
+
+        let state = agent.invoke(Message::user("weather?"), None).await.unwrap();
+
+        assert_eq!(state.last_message().unwrap().content(), "It is sunny.");
+        let requests = model.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].tools[0].function_name(), "get_weather");
+        // 第二次调用看到了工具结果
+        assert_eq!(
+            requests[1].messages.last().unwrap().content(),
+            "\"sunny in Paris\""
+        );
+
+        // 脚本用完后返回错误而不是卡住
+        assert!(agent.invoke(Message::user("again"), None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn responder_and_stream_reflect_the_request() {
+        let model = MockModel::from_fn(|request| {
+            Ok(Message::assistant(format!(
+                "{} messages",
+                request.messages.len()
+            )))
+        });
+
+        let events: Vec<_> = model
+            .stream(&[Arc::new(Message::user("hi"))], &InvokeOptions::default())
+            .await
+            .unwrap()
+            .collect()
+            .await;
+
+        assert!(matches!(&events[0], Ok(ChatStreamEvent::Content(text)) if text == "1 messages"));
+        assert!(matches!(
+            events.last(),
+            Some(Ok(ChatStreamEvent::Done { .. }))
+        ));
+        assert_eq!(model.call_count(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn latency_and_chunked_streaming() {
+        let model = MockModel::new([Message::assistant("héllo wörld")])
+            .with_latency(Duration::from_secs(2))
+            .with_chunk_size(4);
+
+        let started = tokio::time::Instant::now();
+        let events: Vec<_> = model
+            .stream(&[Arc::new(Message::user("hi"))], &InvokeOptions::default())
+            .await
+            .unwrap()
+            .collect()
+            .await;
+
+        assert_eq!(started.elapsed(), Duration::from_secs(2));
+        let content: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                Ok(ChatStreamEvent::Content(text)) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+        // 按字符而不是字节分片
+        assert_eq!(content, ["héll", "o wö", "rld"]);
+    }
+
+    #[tokio::test]
+    async fn cassette_records_once_then_replays_offline() {
+        let path = std::env::temp_dir().join(format!("cassette-{}.json", uuid::Uuid::new_v4()));
+        let prompt = [Arc::new(Message::user("hi"))];
+        let options = InvokeOptions::default();
+
+        let recorder = CassetteModel::new(
+            MockModel::new([
+                Message::assistant("hello"),
+                Message::assistant("hello again"),
+            ]),
+            &path,
+            CassetteMode::Record,
+        )
+        .unwrap();
+        for _ in 0..2 {
+            recorder.invoke(&prompt, &options).await.unwrap();
+        }
+        assert_eq!(recorder.entries().len(), 2);
+
+        // 被包装的模型没有任何回复，只能从录制文件回放
+        let replayer = CassetteModel::new(MockModel::new([]), &path, CassetteMode::Replay).unwrap();
+        for expected in ["hello", "hello again"] {
+            let completion = replayer.invoke(&prompt, &options).await.unwrap();
+            assert_eq!(completion.messages[0].content(), expected);
+        }
+        let unrecorded = replayer
+            .invoke(&[Arc::new(Message::user("bye"))], &options)
+            .await;
+        assert!(
+            matches!(unrecorded, Err(ModelError::ResponseError(e)) if e.contains("not recorded"))
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
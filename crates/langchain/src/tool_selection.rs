@@ -0,0 +1,338 @@
+//! Choosing which tool definitions the model sees on each call.
+//!
+//! Every tool definition is sent with every model call. With many tools the
+//! definitions alone can exceed a provider's limits on function definitions
+//! (or just crowd the context), so [`ToolSelection`] can shrink them: either by
+//! compacting each schema, or by sending only the tools most relevant to the
+//! latest user message.
+//!
+//! The tools presented on each call are logged at `debug` level.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use langchain_core::{
+    embeddings::Embedder,
+    message::Message,
+    request::{ToolFunction, ToolSpec},
+    state::MessagesState,
+};
+use serde_json::Value;
+
+/// Enums with more values than this are collapsed by [`compact_tool`].
+pub const MAX_COMPACT_ENUM_VALUES: usize = 8;
+
+/// Schema keywords that only document a value and can be dropped.
+const DOC_KEYWORDS: [&str; 3] = ["description", "title", "examples"];
+
+/// How the agent chooses the tool definitions sent on each model call.
+#[derive(Clone, Default)]
+pub enum ToolSelection {
+    /// Sends every tool as registered.
+    #[default]
+    All,
+    /// Sends every tool, compacted with [`compact_tool`].
+    Compact,
+    /// Sends the `top_k` tools whose name and description are most similar to
+    /// the latest user message. Tool embeddings are computed once and cached;
+    /// if embedding fails, every tool is sent.
+    Relevant {
+        embedder: Arc<dyn Embedder>,
+        top_k: usize,
+    },
+}
+
+impl fmt::Debug for ToolSelection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::All => f.write_str("All"),
+            Self::Compact => f.write_str("Compact"),
+            Self::Relevant { top_k, .. } => f
+                .debug_struct("Relevant")
+                .field("top_k", top_k)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+impl ToolSelection {
+    /// Selects the `top_k` most relevant tools (at least one) with `embedder`.
+    pub fn relevant(embedder: Arc<dyn Embedder>, top_k: usize) -> Self {
+        Self::Relevant {
+            embedder,
+            top_k: top_k.max(1),
+        }
+    }
+}
+
+/// Shrinks a tool definition: keeps only the first sentence of the tool
+/// description, drops `description`, `title` and `examples` from the parameter
+/// schema, and collapses enums with more than [`MAX_COMPACT_ENUM_VALUES`]
+/// values to their plain type.
+pub fn compact_tool(spec: &ToolSpec) -> ToolSpec {
+    let ToolSpec::Function { function } = spec;
+    let description = match function.description.find(". ") {
+        Some(end) => &function.description[..=end],
+        None => &function.description,
+    };
+    let mut parameters = function.parameters.clone();
+    compact_schema(&mut parameters);
+    ToolSpec::Function {
+        function: ToolFunction {
+            name: function.name.clone(),
+            description: description.trim_end().to_owned(),
+            parameters,
+            examples: function.examples.clone(),
+            output_schema: function.output_schema.clone(),
+        },
+    }
+}
+
+fn compact_schema(schema: &mut Value) {
+    match schema {
+        Value::Object(map) => {
+            for keyword in DOC_KEYWORDS {
+                map.remove(keyword);
+            }
+            if map
+                .get("enum")
+                .and_then(Value::as_array)
+                .is_some_and(|values| values.len() > MAX_COMPACT_ENUM_VALUES)
+            {
+                map.remove("enum");
+            }
+            for (key, value) in map.iter_mut() {
+                match key.as_str() {
+                    // 属性名本身不是关键字，只压缩各属性的 schema
+                    "properties" | "definitions" | "$defs" | "patternProperties" => {
+                        if let Value::Object(properties) = value {
+                            properties.values_mut().for_each(compact_schema);
+                        }
+                    }
+                    // 枚举值和默认值是数据，保持原样
+                    "enum" | "const" | "default" | "required" => {}
+                    _ => compact_schema(value),
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(compact_schema),
+        _ => {}
+    }
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let denominator = norm(a) * norm(b);
+    if denominator == 0.0 {
+        0.0
+    } else {
+        dot / denominator
+    }
+}
+
+/// Applies a [`ToolSelection`] before each model call.
+#[derive(Debug, Default)]
+pub(crate) struct ToolSelector {
+    selection: ToolSelection,
+    /// 工具名 → 名称与描述的向量
+    embeddings: Mutex<HashMap<String, Vec<f32>>>,
+}
+
+impl ToolSelector {
+    pub(crate) fn new(selection: ToolSelection) -> Self {
+        Self {
+            selection,
+            embeddings: Mutex::default(),
+        }
+    }
+
+    /// 本次调用发送的工具；`keep` 中的工具（如强制调用的工具）总是保留
+    pub(crate) async fn select(
+        &self,
+        input: &MessagesState,
+        tools: &[ToolSpec],
+        keep: Option<&str>,
+    ) -> Vec<ToolSpec> {
+        let selected = match &self.selection {
+            ToolSelection::All => tools.to_vec(),
+            ToolSelection::Compact => tools.iter().map(compact_tool).collect(),
+            ToolSelection::Relevant { embedder, top_k } if tools.len() > *top_k => {
+                match self
+                    .relevant(embedder.as_ref(), *top_k, input, tools, keep)
+                    .await
+                {
+                    Ok(selected) => selected,
+                    Err(e) => {
+                        tracing::warn!("Tool selection failed, sending every tool: {e}");
+                        tools.to_vec()
+                    }
+                }
+            }
+            ToolSelection::Relevant { .. } => tools.to_vec(),
+        };
+        if tracing::enabled!(tracing::Level::DEBUG) {
+            let names: Vec<_> = selected.iter().map(ToolSpec::function_name).collect();
+            tracing::debug!("Tools presented to the model: {names:?}");
+        }
+        selected
+    }
+
+    async fn relevant(
+        &self,
+        embedder: &dyn Embedder,
+        top_k: usize,
+        input: &MessagesState,
+        tools: &[ToolSpec],
+        keep: Option<&str>,
+    ) -> Result<Vec<ToolSpec>, langchain_core::ModelError> {
+        let Some(query) = input.messages.iter().rev().find_map(|m| match m.as_ref() {
+            Message::User { .. } => Some(m.content().to_owned()),
+            _ => None,
+        }) else {
+            return Ok(tools.to_vec());
+        };
+
+        let missing: Vec<&ToolFunction> = {
+            let cache = self.embeddings.lock().unwrap();
+            tools
+                .iter()
+                .map(|ToolSpec::Function { function }| function)
+                .filter(|function| !cache.contains_key(&function.name))
+                .collect()
+        };
+        if !missing.is_empty() {
+            let texts = missing
+                .iter()
+                .map(|f| format!("{}: {}", f.name, f.description))
+                .collect();
+            let vectors = embedder.embed(texts).await?;
+            let mut cache = self.embeddings.lock().unwrap();
+            for (function, vector) in missing.iter().zip(vectors) {
+                cache.insert(function.name.clone(), vector);
+            }
+        }
+
+        let query = embedder.embed_query(&query).await?;
+        let cache = self.embeddings.lock().unwrap();
+        let mut scored: Vec<(usize, f32)> = tools
+            .iter()
+            .enumerate()
+            .map(|(index, tool)| {
+                let score = if Some(tool.function_name()) == keep {
+                    f32::INFINITY
+                } else {
+                    cache
+                        .get(tool.function_name())
+                        .map_or(f32::NEG_INFINITY, |v| cosine_similarity(&query, v))
+                };
+                (index, score)
+            })
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        let mut chosen: Vec<usize> = scored.into_iter().take(top_k).map(|(i, _)| i).collect();
+        // 保持注册顺序，便于模型端缓存提示词前缀
+        chosen.sort_unstable();
+        Ok(chosen.into_iter().map(|i| tools[i].clone()).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use langchain_core::{ModelError, ToolError, state::RegisteredTool};
+    use serde_json::json;
+
+    use super::*;
+    use crate::{ReactAgent, testing::MockModel};
+
+    /// 按关键词出现与否生成向量
+    struct KeywordEmbedder;
+
+    #[async_trait]
+    impl Embedder for KeywordEmbedder {
+        async fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, ModelError> {
+            Ok(texts
+                .iter()
+                .map(|text| {
+                    let text = text.to_lowercase();
+                    ["weather", "email", "calendar"]
+                        .iter()
+                        .map(|word| if text.contains(word) { 1.0 } else { 0.0 })
+                        .collect()
+                })
+                .collect())
+        }
+    }
+
+    fn tool(name: &str, description: &str) -> RegisteredTool<ToolError> {
+        RegisteredTool::new(
+            name.to_owned(),
+            description.to_owned(),
+            json!({"type": "object"}),
+            Arc::new(|_| Box::pin(async { Ok(json!("ok")) })),
+        )
+    }
+
+    #[test]
+    fn compact_tool_drops_docs_and_collapses_large_enums() {
+        let spec = ToolSpec::Function {
+            function: ToolFunction {
+                name: "convert".to_owned(),
+                description: "Converts units. Supports metric and imperial.".to_owned(),
+                parameters: json!({
+                    "type": "object",
+                    "title": "ConvertArgs",
+                    "properties": {
+                        "description": {"type": "string", "description": "what to convert"},
+                        "unit": {"type": "string", "enum": ["m", "km", "cm", "mm", "in", "ft", "yd", "mi", "nmi"]},
+                        "mode": {"type": "string", "enum": ["exact", "rounded"], "default": "exact"}
+                    },
+                    "required": ["description", "unit"]
+                }),
+                examples: Vec::new(),
+                output_schema: None,
+            },
+        };
+
+        let ToolSpec::Function { function } = compact_tool(&spec);
+        assert_eq!(function.description, "Converts units.");
+        assert_eq!(
+            function.parameters,
+            json!({
+                "type": "object",
+                "properties": {
+                    "description": {"type": "string"},
+                    "unit": {"type": "string"},
+                    "mode": {"type": "string", "enum": ["exact", "rounded"], "default": "exact"}
+                },
+                "required": ["description", "unit"]
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn relevant_selection_sends_the_closest_tools() {
+        let model = Arc::new(MockModel::new([Message::assistant("done")]));
+        let agent = ReactAgent::builder(model.clone())
+            .with_tools([
+                tool("get_weather", "Current weather for a city"),
+                tool("send_email", "Sends an email"),
+                tool("add_event", "Adds a calendar event"),
+            ])
+            .with_tool_selection(ToolSelection::relevant(Arc::new(KeywordEmbedder), 1))
+            .build();
+
+        agent
+            .invoke(Message::user("Send Bob an email"), None)
+            .await
+            .unwrap();
+
+        let tools = model.last_request().unwrap().tools;
+        let names: Vec<_> = tools.iter().map(ToolSpec::function_name).collect();
+        assert_eq!(names, ["send_email"]);
+    }
+}
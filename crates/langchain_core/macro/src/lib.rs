@@ -15,6 +15,40 @@ struct ToolAttrArgs {
     args: ArgsMeta,
     #[darling(default)]
     error: Option<ErrorTypeMeta>,
+    /// `example(args = "{...}", result = "...")`，可以出现多次
+    #[darling(multiple, rename = "example")]
+    examples: Vec<ExampleMeta>,
+    /// 由返回值类型生成输出的 JSON Schema，见 `RegisteredTool::with_output_type`
+    #[darling(default)]
+    output_schema: bool,
+}
+
+/// 一个调用示例，参数和结果都是 JSON 文本
+#[derive(Debug, FromMeta)]
+struct ExampleMeta {
+    args: syn::LitStr,
+    result: syn::LitStr,
+}
+
+impl ExampleMeta {
+    /// 在编译期检查两段 JSON，生成 `.with_example(...)` 调用
+    fn to_tokens(&self) -> darling::Result<proc_macro2::TokenStream> {
+        for lit in [&self.args, &self.result] {
+            if let Err(e) = serde_json::from_str::<serde_json::Value>(&lit.value()) {
+                return Err(
+                    Error::custom(format!("example must be valid JSON: {e}")).with_span(lit)
+                );
+            }
+        }
+        let (args, result) = (&self.args, &self.result);
+        // 已在编译期校验过，这里的 unwrap 不会失败
+        Ok(quote! {
+            .with_example(
+                ::serde_json::from_str(#args).unwrap(),
+                ::serde_json::from_str(#result).unwrap(),
+            )
+        })
+    }
 }
 
 #[derive(Debug, Default)]
@@ -118,6 +152,15 @@ pub fn tool(attr: TokenStream, item: TokenStream) -> TokenStream {
     let description = parsed.description;
     let arg_docs = parsed.args.docs;
     let error_override = parsed.error.map(|v| v.ty);
+    let examples = match parsed
+        .examples
+        .iter()
+        .map(ExampleMeta::to_tokens)
+        .collect::<darling::Result<Vec<_>>>()
+    {
+        Ok(examples) => examples,
+        Err(e) => return e.write_errors().into(),
+    };
 
     // 2. 分析原函数：名字、参数列表、返回类型
     let fn_name = &func.sig.ident;
@@ -247,6 +290,16 @@ pub fn tool(attr: TokenStream, item: TokenStream) -> TokenStream {
         }
     };
 
+    let output_schema = if parsed.output_schema {
+        let ok_ty: syn::Type = match output {
+            syn::ReturnType::Default => parse_quote!(()),
+            syn::ReturnType::Type(_, ty) => result_ok_type(ty).unwrap_or_else(|| (**ty).clone()),
+        };
+        quote! { .with_output_type::<#ok_ty>() }
+    } else {
+        quote! {}
+    };
+
     let tool_name_lit = tool_name;
     let description_lit = description;
 
@@ -275,12 +328,32 @@ pub fn tool(attr: TokenStream, item: TokenStream) -> TokenStream {
                     #call_expr
                 },
             )
+            #(#examples)*
+            #output_schema
         }
     };
 
     expanded.into()
 }
 
+/// `Result<T, E>` 中的 `T`，不是 `Result` 时返回 `None`
+fn result_ok_type(ty: &syn::Type) -> Option<syn::Type> {
+    let syn::Type::Path(tp) = ty else {
+        return None;
+    };
+    let seg = tp.path.segments.last()?;
+    if seg.ident != "Result" {
+        return None;
+    }
+    let syn::PathArguments::AngleBracketed(ab) = &seg.arguments else {
+        return None;
+    };
+    match ab.args.first()? {
+        syn::GenericArgument::Type(ok_ty) => Some(ok_ty.clone()),
+        _ => None,
+    }
+}
+
 // 很简单的 snake_case -> CamelCase 辅助
 fn to_camel_case(name: &str) -> String {
     let mut s = String::new();
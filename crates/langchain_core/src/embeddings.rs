@@ -0,0 +1,29 @@
+//! 文本向量化接口
+//!
+//! [`Embedder`] 将文本转换为向量，是向量记忆与检索增强生成（RAG）的基础。
+
+use async_trait::async_trait;
+
+use crate::error::ModelError;
+
+/// 文本向量化模型
+///
+/// 返回的向量与输入文本一一对应、顺序一致。
+#[async_trait]
+pub trait Embedder: Send + Sync {
+    /// 批量计算文本向量
+    async fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, ModelError>;
+
+    /// 计算单条文本的向量
+    async fn embed_query(&self, text: &str) -> Result<Vec<f32>, ModelError> {
+        self.embed(vec![text.to_owned()])
+            .await?
+            .pop()
+            .ok_or_else(|| ModelError::ResponseError("no embedding in response".to_owned()))
+    }
+
+    /// 向量维度，未知时返回 `None`
+    fn dimensions(&self) -> Option<usize> {
+        None
+    }
+}
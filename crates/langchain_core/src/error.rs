@@ -20,6 +20,15 @@ pub enum ErrorCategory {
     Internal,
     /// 外部服务错误
     External,
+    /// 调用方主动取消（不可重试）
+    Cancelled,
+}
+
+impl ErrorCategory {
+    /// 该类别的错误是否值得重试：只有临时性错误和速率限制会在重试后成功
+    pub fn is_retryable(self) -> bool {
+        matches!(self, ErrorCategory::Transient | ErrorCategory::RateLimit)
+    }
 }
 
 /// 基础错误trait，所有langchain错误都应该实现
@@ -29,10 +38,7 @@ pub trait LangChainError: std::error::Error + Send + Sync + 'static {
 
     /// 判断是否可重试
     fn is_retryable(&self) -> bool {
-        matches!(
-            self.category(),
-            ErrorCategory::Transient | ErrorCategory::RateLimit
-        )
+        self.category().is_retryable()
     }
 
     /// 建议的重试延迟（毫秒）
@@ -47,6 +53,7 @@ pub enum ModelError {
     #[error("API request failed: {0}")]
     RequestFailed(#[source] reqwest::Error),
 
+    /// 速率限制，参数为服务端建议的等待秒数（0 表示未给出）
     #[error("Rate limited: retry after {0} seconds")]
     RateLimited(u32),
 
@@ -65,6 +72,14 @@ pub enum ModelError {
     #[error("Response error: {0}")]
     ResponseError(String),
 
+    /// 服务端错误（HTTP 5xx）
+    #[error("Server error {0}: {1}")]
+    ServerError(u16, String),
+
+    /// 熔断器处于打开状态，参数为距离冷却结束的毫秒数
+    #[error("Circuit breaker is open: retry after {0}ms")]
+    CircuitOpen(u64),
+
     #[error("Other error: {0}")]
     Other(#[source] Box<dyn Error + Send + Sync>),
 }
@@ -79,15 +94,18 @@ impl LangChainError for ModelError {
             ModelError::Timeout(_) => ErrorCategory::Transient,
             ModelError::ParseError(_) => ErrorCategory::Internal,
             ModelError::ResponseError(_) => ErrorCategory::External,
+            ModelError::ServerError(..) => ErrorCategory::Transient,
+            ModelError::CircuitOpen(_) => ErrorCategory::Transient,
             ModelError::Other(_) => ErrorCategory::Internal,
         }
     }
 
     fn retry_delay_ms(&self) -> Option<u64> {
         match self {
-            ModelError::RateLimited(seconds) => Some((*seconds as u64) * 1000),
+            ModelError::RateLimited(seconds) if *seconds > 0 => Some((*seconds as u64) * 1000),
             ModelError::Timeout(_) => Some(1000),
             ModelError::RequestFailed(_) => Some(2000),
+            ModelError::CircuitOpen(ms) => Some(*ms),
             _ => None,
         }
     }
@@ -117,6 +135,9 @@ pub enum ToolError {
 
     #[error("Tool error: {0}")]
     ToolError(String),
+
+    #[error("Tool already registered: {0}")]
+    AlreadyRegistered(String),
 }
 
 impl ToolError {
@@ -138,6 +159,7 @@ impl LangChainError for ToolError {
             ToolError::ExecutionFailed(_) => ErrorCategory::External,
             ToolError::Timeout(_) => ErrorCategory::Transient,
             ToolError::ToolError(_) => ErrorCategory::Internal,
+            ToolError::AlreadyRegistered(_) => ErrorCategory::Validation,
         }
     }
 
@@ -207,6 +229,18 @@ impl LangChainError for ValidationError {
     }
 }
 
+/// 重试延迟的随机抖动策略，避免大量客户端在同一时刻集中重试
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Jitter {
+    /// 不加抖动，严格按指数退避等待
+    #[default]
+    None,
+    /// 在 `[initial_delay_ms, delay]` 内均匀随机
+    Full,
+    /// 保留一半退避时间，另一半随机，即 `[delay / 2, delay]`（不低于 `initial_delay_ms`）
+    Equal,
+}
+
 /// 重试配置
 #[derive(Debug, Clone)]
 pub struct RetryConfig {
@@ -214,6 +248,10 @@ pub struct RetryConfig {
     pub initial_delay_ms: u64,
     pub max_delay_ms: u64,
     pub backoff_multiplier: f32,
+    /// 抖动策略，默认不抖动
+    pub jitter: Jitter,
+    /// 从首次调用开始计算的总耗时上限，超过后不再重试，默认不限制
+    pub max_elapsed_time: Option<std::time::Duration>,
 }
 
 impl Default for RetryConfig {
@@ -223,14 +261,65 @@ impl Default for RetryConfig {
             initial_delay_ms: 1000,
             max_delay_ms: 10000,
             backoff_multiplier: 2.0,
+            jitter: Jitter::None,
+            max_elapsed_time: None,
         }
     }
 }
 
+impl RetryConfig {
+    /// 对指数退避计算出的延迟应用抖动
+    pub fn apply_jitter(&self, delay: std::time::Duration) -> std::time::Duration {
+        let computed = delay.as_millis() as u64;
+        let lower = match self.jitter {
+            Jitter::None => return delay,
+            Jitter::Full => self.initial_delay_ms,
+            Jitter::Equal => (computed / 2).max(self.initial_delay_ms),
+        };
+        if lower >= computed {
+            return delay;
+        }
+        std::time::Duration::from_millis(fastrand::u64(lower..=computed))
+    }
+}
+
 /// 简单的重试逻辑
+#[cfg(feature = "native")]
 pub async fn retry_with_backoff<F, T, E, Fut>(
+    operation: F,
+    error_category: impl Fn(&E) -> ErrorCategory,
+    config: &RetryConfig,
+) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<T, E>>,
+    E: std::fmt::Debug,
+{
+    retry_with_backoff_hint(operation, error_category, |_| None, config).await
+}
+
+/// 按 [`LangChainError`] 自身的类别和建议延迟重试
+///
+/// 临时性错误和速率限制按退避重试，校验、认证等错误立即返回。
+#[cfg(feature = "native")]
+pub async fn retry_on_error<F, T, E, Fut>(operation: F, config: &RetryConfig) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<T, E>>,
+    E: LangChainError + std::fmt::Debug,
+{
+    retry_with_backoff_hint(operation, E::category, E::retry_delay_ms, config).await
+}
+
+/// 支持按错误覆盖延迟的重试逻辑
+///
+/// `retry_delay_ms` 返回 `Some` 时（例如服务端通过 `Retry-After` 指定了等待时间），
+/// 本次重试按该延迟等待；返回 `None` 时使用指数退避。
+#[cfg(feature = "native")]
+pub async fn retry_with_backoff_hint<F, T, E, Fut>(
     mut operation: F,
     error_category: impl Fn(&E) -> ErrorCategory,
+    retry_delay_ms: impl Fn(&E) -> Option<u64>,
     config: &RetryConfig,
 ) -> Result<T, E>
 where
@@ -238,27 +327,29 @@ where
     Fut: std::future::Future<Output = Result<T, E>>,
     E: std::fmt::Debug,
 {
+    let started = std::time::Instant::now();
     let mut delay = std::time::Duration::from_millis(config.initial_delay_ms);
 
     for attempt in 0..=config.max_retries {
         match operation().await {
             Ok(result) => return Ok(result),
             Err(e) => {
-                let category = error_category(&e);
-
-                if !matches!(
-                    category,
-                    ErrorCategory::Transient | ErrorCategory::RateLimit
-                ) {
+                if !error_category(&e).is_retryable() || attempt == config.max_retries {
                     return Err(e);
                 }
 
-                if attempt == config.max_retries {
+                // 使用建议的延迟或指数退避
+                let sleep_duration = retry_delay_ms(&e)
+                    .map(std::time::Duration::from_millis)
+                    .unwrap_or_else(|| config.apply_jitter(delay));
+
+                // 等待后会超出总耗时上限时直接放弃
+                if let Some(max_elapsed) = config.max_elapsed_time
+                    && started.elapsed() + sleep_duration > max_elapsed
+                {
                     return Err(e);
                 }
 
-                // 使用建议的延迟或指数退避
-                let sleep_duration = delay;
                 tokio::time::sleep(sleep_duration).await;
 
                 // 计算下一次延迟
@@ -305,6 +396,159 @@ mod tests {
         assert!(timeout.is_retryable());
     }
 
+    #[test]
+    fn test_server_error_is_retryable() {
+        let server = ModelError::ServerError(503, "overloaded".to_owned());
+        assert_eq!(server.category(), ErrorCategory::Transient);
+        assert!(server.is_retryable());
+
+        // 未给出 Retry-After 时交给指数退避
+        assert_eq!(ModelError::RateLimited(0).retry_delay_ms(), None);
+    }
+
+    #[test]
+    fn test_every_category_classification() {
+        let retryable = [ErrorCategory::Transient, ErrorCategory::RateLimit];
+        for category in [
+            ErrorCategory::Transient,
+            ErrorCategory::Validation,
+            ErrorCategory::Authentication,
+            ErrorCategory::RateLimit,
+            ErrorCategory::Internal,
+            ErrorCategory::External,
+        ] {
+            assert_eq!(category.is_retryable(), retryable.contains(&category));
+        }
+
+        let model_errors = [
+            (
+                ModelError::ModelNotFound("gpt".to_owned()),
+                ErrorCategory::Validation,
+            ),
+            (
+                ModelError::ParseError(serde_json::from_str::<u8>("").unwrap_err()),
+                ErrorCategory::Internal,
+            ),
+            (
+                ModelError::ResponseError("bad".to_owned()),
+                ErrorCategory::External,
+            ),
+            (ModelError::CircuitOpen(100), ErrorCategory::Transient),
+            (ModelError::Other("?".into()), ErrorCategory::Internal),
+        ];
+        for (error, category) in model_errors {
+            assert_eq!(error.category(), category, "{error}");
+            assert_eq!(error.is_retryable(), category.is_retryable());
+        }
+
+        let tool_errors = [
+            (ToolError::ToolCall("x".into()), ErrorCategory::External),
+            (
+                ToolError::InvalidArguments("x".to_owned()),
+                ErrorCategory::Validation,
+            ),
+            (
+                ToolError::ExecutionFailed("x".to_owned()),
+                ErrorCategory::External,
+            ),
+            (
+                ToolError::ToolError("x".to_owned()),
+                ErrorCategory::Internal,
+            ),
+        ];
+        for (error, category) in tool_errors {
+            assert_eq!(error.category(), category, "{error}");
+            assert!(!error.is_retryable());
+        }
+
+        assert_eq!(
+            GraphError::CycleDetected.category(),
+            ErrorCategory::Validation
+        );
+        assert_eq!(
+            GraphError::ExecutionFailed("x".to_owned()).category(),
+            ErrorCategory::Internal
+        );
+        assert!(!GraphError::MaxStepsExceeded.is_retryable());
+
+        let validation = ValidationError::MissingField("query".to_owned());
+        assert_eq!(validation.category(), ErrorCategory::Validation);
+        assert!(!validation.is_retryable());
+    }
+
+    #[cfg(feature = "native")]
+    #[tokio::test]
+    async fn test_retry_on_error_fails_fast_on_validation() {
+        let config = RetryConfig {
+            max_retries: 3,
+            initial_delay_ms: 1,
+            ..Default::default()
+        };
+
+        let mut attempts = 0;
+        let result: Result<(), ToolError> = retry_on_error(
+            || {
+                attempts += 1;
+                async { Err(ToolError::InvalidArguments("a".to_owned())) }
+            },
+            &config,
+        )
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+
+        let mut attempts = 0;
+        let result: Result<u32, ModelError> = retry_on_error(
+            || {
+                attempts += 1;
+                let attempt = attempts;
+                async move {
+                    if attempt < 3 {
+                        Err(ModelError::Timeout(10))
+                    } else {
+                        Ok(attempt)
+                    }
+                }
+            },
+            &config,
+        )
+        .await;
+        assert_eq!(result.unwrap(), 3);
+    }
+
+    #[cfg(feature = "native")]
+    #[tokio::test]
+    async fn test_retry_uses_error_delay_hint() {
+        let config = RetryConfig {
+            max_retries: 2,
+            initial_delay_ms: 60_000,
+            ..Default::default()
+        };
+        let mut attempts = 0;
+        let started = std::time::Instant::now();
+
+        let result: Result<u32, ModelError> = retry_with_backoff_hint(
+            || {
+                attempts += 1;
+                let attempt = attempts;
+                async move {
+                    if attempt < 3 {
+                        Err(ModelError::RateLimited(0))
+                    } else {
+                        Ok(attempt)
+                    }
+                }
+            },
+            |e| e.category(),
+            |_| Some(1),
+            &config,
+        )
+        .await;
+
+        assert_eq!(result.unwrap(), 3);
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+    }
+
     #[test]
     fn test_retry_config_default() {
         let config = RetryConfig::default();
@@ -312,5 +556,52 @@ mod tests {
         assert_eq!(config.initial_delay_ms, 1000);
         assert_eq!(config.max_delay_ms, 10000);
         assert_eq!(config.backoff_multiplier, 2.0);
+        assert_eq!(config.jitter, Jitter::None);
+        assert_eq!(config.max_elapsed_time, None);
+    }
+
+    #[test]
+    fn test_jitter_stays_within_bounds() {
+        let computed = std::time::Duration::from_millis(8000);
+        for jitter in [Jitter::Full, Jitter::Equal] {
+            let config = RetryConfig {
+                jitter,
+                ..Default::default()
+            };
+            for _ in 0..1000 {
+                let delay = config.apply_jitter(computed);
+                assert!(delay >= std::time::Duration::from_millis(config.initial_delay_ms));
+                assert!(delay <= computed);
+            }
+        }
+
+        let config = RetryConfig::default();
+        assert_eq!(config.apply_jitter(computed), computed);
+    }
+
+    #[cfg(feature = "native")]
+    #[tokio::test]
+    async fn test_retry_stops_at_max_elapsed_time() {
+        let config = RetryConfig {
+            max_retries: 100,
+            initial_delay_ms: 20,
+            max_delay_ms: 20,
+            max_elapsed_time: Some(std::time::Duration::from_millis(100)),
+            ..Default::default()
+        };
+        let mut attempts = 0;
+
+        let result: Result<(), ModelError> = retry_with_backoff(
+            || {
+                attempts += 1;
+                async { Err(ModelError::Timeout(0)) }
+            },
+            |e| e.category(),
+            &config,
+        )
+        .await;
+
+        assert!(result.is_err());
+        assert!(attempts < 100);
     }
 }
@@ -0,0 +1,16 @@
+//! 共享的 HTTP 客户端
+//!
+//! 模型提供方和网络工具默认使用同一个 `reqwest::Client`，共享连接池。
+//! 需要代理、自定义 TLS 等配置时，可以通过各自的构建方法传入自己的客户端。
+
+use std::sync::LazyLock;
+
+static SHARED_CLIENT: LazyLock<reqwest::Client> = LazyLock::new(reqwest::Client::new);
+
+/// 默认的 HTTP 客户端，首次使用时创建
+///
+/// 返回的客户端与其他调用者共享连接池（`reqwest::Client` 的克隆只增加引用计数）。
+/// 它没有设置超时，超时由使用方按请求设置。
+pub fn shared_client() -> reqwest::Client {
+    SHARED_CLIENT.clone()
+}
@@ -1,22 +1,54 @@
 //! # langchain_core
 //!
 //! langchain 库的核心模块，包含了所有的基础类型和函数。
+//!
+//! ## WASM
+//!
+//! 关闭默认的 `native` feature 后，消息、解析器、状态等类型可以编译到
+//! `wasm32-unknown-unknown`，用于在浏览器中组装提示词和解析模型输出：
+//!
+//! ```text
+//! cargo build -p langchain_core --no-default-features --target wasm32-unknown-unknown
+//! ```
+//!
+//! 依赖系统计时器的重试（`retry_with_backoff` 等）和熔断器只在 `native` 下提供。
+//! `tests/portable.rs` 只使用这部分可移植的接口，
+//! 可以通过 `cargo test -p langchain_core --no-default-features` 在本机检查。
 
 pub use langchain_core_macro::tool;
 
+pub mod embeddings;
 pub mod error;
+pub mod http;
 pub mod message;
+pub mod model;
 pub mod parsers;
+pub mod prompt;
 pub mod request;
+pub mod reranker;
 pub mod response;
+pub mod schema;
 pub mod state;
 pub mod store;
+#[cfg(feature = "test-util")]
+pub mod testing;
+pub mod tokenizer;
 
+pub use embeddings::Embedder;
 pub use error::{
-    ErrorCategory, GraphError, LangChainError, ModelError, RetryConfig, ToolError, ValidationError,
-    retry_with_backoff,
+    ErrorCategory, GraphError, Jitter, LangChainError, ModelError, RetryConfig, ToolError,
+    ValidationError,
 };
+#[cfg(feature = "native")]
+pub use error::{retry_on_error, retry_with_backoff, retry_with_backoff_hint};
+pub use model::FallbackModel;
+#[cfg(feature = "native")]
+pub use model::{CircuitBreaker, CircuitBreakerConfig, CircuitState};
 pub use parsers::{
     JsonParser, KeyValue, KeyValueParser, ListParser, OrParser, OutputParser, ParseError,
 };
+pub use reranker::{PassthroughReranker, Reranker};
 pub use store::{BaseStore, InMemoryStore, Namespace, StoreError, StoreFilter};
+#[cfg(feature = "tiktoken")]
+pub use tokenizer::TiktokenTokenizer;
+pub use tokenizer::{HeuristicTokenizer, Tokenizer};
@@ -1,9 +1,27 @@
 //! 暂时使用的是OpenAI的标准，后面对接其他标准再进行合并配置
 
+use std::{borrow::Cow, collections::HashMap, fmt, sync::Arc};
+
 use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::error::ValidationError;
+
+/// 消息的附加信息
+pub type Metadata = HashMap<String, serde_json::Value>;
+
+/// 消息的角色
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Role {
+    System,
+    Developer,
+    User,
+    Assistant,
+    Tool,
+}
 
 /// 聊天消息，表示不同角色的消息类型
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "role")]
 pub enum Message {
     /// 用户消息
@@ -14,6 +32,9 @@ pub enum Message {
         /// 可选填的参与者的名称，为模型提供信息以区分相同角色的参与者
         #[serde(skip_serializing_if = "Option::is_none")]
         name: Option<String>,
+        /// 附加信息，见 [`Message::metadata`]
+        #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+        metadata: Metadata,
     },
     /// AI助手消息
     #[serde(rename = "assistant")]
@@ -28,6 +49,9 @@ pub enum Message {
         /// 可选填的消息名称
         #[serde(skip_serializing_if = "Option::is_none")]
         name: Option<String>,
+        /// 附加信息，见 [`Message::metadata`]
+        #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+        metadata: Metadata,
     },
     /// 系统消息
     #[serde(rename = "system")]
@@ -37,6 +61,9 @@ pub enum Message {
         /// 可选填的消息名称
         #[serde(skip_serializing_if = "Option::is_none")]
         name: Option<String>,
+        /// 附加信息，见 [`Message::metadata`]
+        #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+        metadata: Metadata,
     },
     /// 开发者消息
     Developer {
@@ -45,6 +72,9 @@ pub enum Message {
         /// 可选填的消息名称
         #[serde(skip_serializing_if = "Option::is_none")]
         name: Option<String>,
+        /// 附加信息，见 [`Message::metadata`]
+        #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+        metadata: Metadata,
     },
     /// 工具调用消息
     #[serde(rename = "tool")]
@@ -52,6 +82,9 @@ pub enum Message {
         // 必须有 id 来对应
         tool_call_id: String,
         content: String,
+        /// 附加信息，见 [`Message::metadata`]
+        #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+        metadata: Metadata,
     },
 }
 
@@ -65,6 +98,7 @@ impl Message {
         Self::User {
             content: Content::Text(content.into()),
             name: None,
+            metadata: Metadata::new(),
         }
     }
 
@@ -78,6 +112,7 @@ impl Message {
         Self::User {
             content: Content::Text(content.into()),
             name: Some(name.into()),
+            metadata: Metadata::new(),
         }
     }
 
@@ -90,6 +125,20 @@ impl Message {
         Self::User {
             content: Content::Mixed(vec![content]),
             name: None,
+            metadata: Metadata::new(),
+        }
+    }
+
+    /// 创建一个带多个内容块的用户消息，例如一段文字加一张图片
+    /// # Arguments
+    /// * `blocks` - 消息内容块
+    /// # Returns
+    /// * `Message` - 用户消息实例
+    pub fn user_with_content_blocks(blocks: Vec<ContentBlock>) -> Self {
+        Self::User {
+            content: Content::Mixed(blocks),
+            name: None,
+            metadata: Metadata::new(),
         }
     }
 
@@ -104,6 +153,7 @@ impl Message {
             reasoning_content: None,
             tool_calls: None,
             name: None,
+            metadata: Metadata::new(),
         }
     }
 
@@ -119,6 +169,26 @@ impl Message {
             reasoning_content: None,
             tool_calls: None,
             name: Some(name.into()),
+            metadata: Metadata::new(),
+        }
+    }
+
+    /// 创建一个带工具调用的助手消息
+    /// # Arguments
+    /// * `content` - 消息内容，可以为空
+    /// * `tool_calls` - 工具调用列表
+    /// # Returns
+    /// * `Message` - 助手消息实例
+    pub fn assistant_with_tool_calls<S: Into<String>>(
+        content: S,
+        tool_calls: Vec<ToolCall>,
+    ) -> Self {
+        Self::Assistant {
+            content: content.into(),
+            reasoning_content: None,
+            tool_calls: Some(tool_calls),
+            name: None,
+            metadata: Metadata::new(),
         }
     }
 
@@ -131,6 +201,7 @@ impl Message {
         Self::System {
             content: content.into(),
             name: None,
+            metadata: Metadata::new(),
         }
     }
 
@@ -145,6 +216,7 @@ impl Message {
         Self::Developer {
             content: content.into(),
             name: None,
+            metadata: Metadata::new(),
         }
     }
 
@@ -160,6 +232,7 @@ impl Message {
         Self::Developer {
             content: content.into(),
             name: Some(name.into()),
+            metadata: Metadata::new(),
         }
     }
 
@@ -173,6 +246,17 @@ impl Message {
         Self::Tool {
             content: content.into(),
             tool_call_id: tool_call_id.into(),
+            metadata: Metadata::new(),
+        }
+    }
+
+    pub fn role(&self) -> Role {
+        match self {
+            Message::User { .. } => Role::User,
+            Message::Assistant { .. } => Role::Assistant,
+            Message::System { .. } => Role::System,
+            Message::Developer { .. } => Role::Developer,
+            Message::Tool { .. } => Role::Tool,
         }
     }
 
@@ -190,6 +274,171 @@ impl Message {
         }
     }
 
+    /// 消息的附加信息
+    ///
+    /// 用于记录 trace id、引用的文档等调用方自己的数据。附加信息会随 checkpoint 一起保存，
+    /// 但模型提供方在构建请求时会忽略它。
+    pub fn metadata(&self) -> &Metadata {
+        match self {
+            Message::User { metadata, .. }
+            | Message::Assistant { metadata, .. }
+            | Message::System { metadata, .. }
+            | Message::Developer { metadata, .. }
+            | Message::Tool { metadata, .. } => metadata,
+        }
+    }
+
+    pub fn metadata_mut(&mut self) -> &mut Metadata {
+        match self {
+            Message::User { metadata, .. }
+            | Message::Assistant { metadata, .. }
+            | Message::System { metadata, .. }
+            | Message::Developer { metadata, .. }
+            | Message::Tool { metadata, .. } => metadata,
+        }
+    }
+
+    /// 添加一项附加信息
+    pub fn with_metadata<K: Into<String>>(mut self, key: K, value: serde_json::Value) -> Self {
+        self.metadata_mut().insert(key.into(), value);
+        self
+    }
+
+    /// 去掉附加信息后的消息，没有附加信息时不会复制
+    pub fn without_metadata(self: &Arc<Self>) -> Arc<Self> {
+        if self.metadata().is_empty() {
+            return self.clone();
+        }
+        let mut message = self.as_ref().clone();
+        message.metadata_mut().clear();
+        Arc::new(message)
+    }
+
+    /// 对消息中的所有文本应用 `f`，有改动时返回新消息
+    ///
+    /// 包括正文、思考内容、内容块以及工具调用参数中的字符串值。工具调用参数按 JSON
+    /// 结构逐个处理字符串值（参数为 JSON 文本时先解析），因此不会破坏参数的 JSON 结构。
+    /// `f` 返回 `None` 表示该段文本不变。
+    pub fn map_text(&self, mut f: impl FnMut(&str) -> Option<String>) -> Option<Message> {
+        let mut message = self.clone();
+        let changed = match &mut message {
+            Message::User { content, .. } => match content {
+                Content::Text(text) => map_string(text, &mut f),
+                Content::Image { .. } => false,
+                Content::Mixed(blocks) => blocks.iter_mut().fold(false, |changed, block| {
+                    let block_changed = match block {
+                        ContentBlock::Text { text } => map_string(text, &mut f),
+                        ContentBlock::ToolUse { input, .. } => map_json(input, &mut f),
+                        ContentBlock::Reasoning { content } => map_string(content, &mut f),
+                        ContentBlock::ImageUrl { .. } => false,
+                    };
+                    changed | block_changed
+                }),
+            },
+            Message::Assistant {
+                content,
+                reasoning_content,
+                tool_calls,
+                ..
+            } => {
+                let mut changed = map_string(content, &mut f);
+                if let Some(reasoning) = reasoning_content {
+                    changed |= map_string(reasoning, &mut f);
+                }
+                for call in tool_calls.iter_mut().flatten() {
+                    changed |= map_arguments(&mut call.function.arguments, &mut f);
+                }
+                changed
+            }
+            Message::System { content, .. }
+            | Message::Developer { content, .. }
+            | Message::Tool { content, .. } => map_string(content, &mut f),
+        };
+        changed.then_some(message)
+    }
+
+    /// 渲染为带角色前缀的可读文本，每个工具调用单独一行
+    ///
+    /// `max_len` 限制工具结果和工具调用参数的字符数，超出部分被截断，用于日志等紧凑输出；
+    /// `None` 表示完整输出，与 `Display` 相同。
+    pub fn render(&self, max_len: Option<usize>) -> String {
+        let mut out = String::new();
+        match self {
+            Message::User { content, name, .. } => {
+                out.push_str(&role_prefix("User", name.as_deref()));
+                match content {
+                    Content::Text(text) => out.push_str(text),
+                    Content::Image { url } => out.push_str(&format!("[image: {url}]")),
+                    Content::Mixed(blocks) => {
+                        let rendered: Vec<_> = blocks
+                            .iter()
+                            .map(|block| match block {
+                                ContentBlock::Text { text } => text.clone(),
+                                ContentBlock::ToolUse { name, input, .. } => {
+                                    format!(
+                                        "[tool use: {name}({})]",
+                                        truncate(&input.to_string(), max_len)
+                                    )
+                                }
+                                ContentBlock::Reasoning { content } => {
+                                    format!("[reasoning: {content}]")
+                                }
+                                ContentBlock::ImageUrl { image_url } => {
+                                    format!("[image: {}]", image_url.url)
+                                }
+                            })
+                            .collect();
+                        out.push_str(&rendered.join("\n"));
+                    }
+                }
+            }
+            Message::Assistant {
+                content,
+                reasoning_content,
+                tool_calls,
+                name,
+                ..
+            } => {
+                out.push_str(&role_prefix("Assistant", name.as_deref()));
+                out.push_str(content);
+                if let Some(reasoning) = reasoning_content.as_deref().filter(|r| !r.is_empty()) {
+                    out.push_str(&format!("\n  (reasoning) {reasoning}"));
+                }
+                for call in tool_calls.iter().flatten() {
+                    let arguments = match &call.function.arguments {
+                        serde_json::Value::String(raw) => raw.clone(),
+                        value => value.to_string(),
+                    };
+                    out.push_str(&format!(
+                        "\n  -> {}({}) [{}]",
+                        call.function.name,
+                        truncate(&arguments, max_len),
+                        call.id
+                    ));
+                }
+            }
+            Message::System { content, name, .. } => {
+                out.push_str(&role_prefix("System", name.as_deref()));
+                out.push_str(content);
+            }
+            Message::Developer { content, name, .. } => {
+                out.push_str(&role_prefix("Developer", name.as_deref()));
+                out.push_str(content);
+            }
+            Message::Tool {
+                tool_call_id,
+                content,
+                ..
+            } => {
+                out.push_str(&format!(
+                    "Tool [{tool_call_id}]: {}",
+                    truncate(content, max_len)
+                ));
+            }
+        }
+        out
+    }
+
     pub fn to_pretty(&self) -> String {
         match self {
             Message::User { content, .. } => match content {
@@ -229,6 +478,7 @@ impl Message {
             Message::Tool {
                 content,
                 tool_call_id,
+                ..
             } => {
                 format!(
                     "================================ Tool Message =================================\n\nTool {}: {}\n\n",
@@ -239,7 +489,88 @@ impl Message {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// 字符串默认转换为用户消息
+impl From<&str> for Message {
+    fn from(content: &str) -> Self {
+        Self::user(content)
+    }
+}
+
+impl From<String> for Message {
+    fn from(content: String) -> Self {
+        Self::user(content)
+    }
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.render(None))
+    }
+}
+
+fn role_prefix(role: &str, name: Option<&str>) -> String {
+    match name {
+        Some(name) => format!("{role} ({name}): "),
+        None => format!("{role}: "),
+    }
+}
+
+/// 按字符截断，并注明省略了多少字符
+fn truncate(text: &str, max_len: Option<usize>) -> Cow<'_, str> {
+    let Some(max_len) = max_len else {
+        return Cow::Borrowed(text);
+    };
+    match text.char_indices().nth(max_len) {
+        Some((end, _)) => {
+            let omitted = text[end..].chars().count();
+            Cow::Owned(format!("{}… ({omitted} more chars)", &text[..end]))
+        }
+        None => Cow::Borrowed(text),
+    }
+}
+
+fn map_string(text: &mut String, f: &mut impl FnMut(&str) -> Option<String>) -> bool {
+    match f(text) {
+        Some(mapped) => {
+            *text = mapped;
+            true
+        }
+        None => false,
+    }
+}
+
+fn map_json(value: &mut serde_json::Value, f: &mut impl FnMut(&str) -> Option<String>) -> bool {
+    match value {
+        serde_json::Value::String(text) => map_string(text, f),
+        serde_json::Value::Array(items) => items
+            .iter_mut()
+            .fold(false, |changed, item| changed | map_json(item, f)),
+        serde_json::Value::Object(map) => map
+            .values_mut()
+            .fold(false, |changed, item| changed | map_json(item, f)),
+        _ => false,
+    }
+}
+
+/// 工具调用参数可能是 JSON 值，也可能是 JSON 文本
+fn map_arguments(
+    arguments: &mut serde_json::Value,
+    f: &mut impl FnMut(&str) -> Option<String>,
+) -> bool {
+    if let serde_json::Value::String(raw) = arguments
+        && let Ok(mut parsed) = serde_json::from_str::<serde_json::Value>(raw)
+        && !parsed.is_string()
+    {
+        if !map_json(&mut parsed, f) {
+            return false;
+        }
+        *raw = parsed.to_string();
+        return true;
+    }
+    map_json(arguments, f)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct ToolCall {
     pub id: String,
     #[serde(rename = "type")]
@@ -248,6 +579,47 @@ pub struct ToolCall {
 }
 
 impl ToolCall {
+    /// 创建一个 `function` 类型的工具调用
+    pub fn new<I: Into<String>, N: Into<String>>(
+        id: I,
+        name: N,
+        arguments: serde_json::Value,
+    ) -> Self {
+        Self {
+            id: id.into(),
+            type_name: "function".to_owned(),
+            function: FunctionCall {
+                name: name.into(),
+                arguments,
+            },
+        }
+    }
+
+    /// 由模型提供方返回的原始参数创建工具调用
+    ///
+    /// 参数可以是 JSON 值（如 Anthropic 的 `input`），也可以是 JSON 字符串（如 OpenAI 的
+    /// `arguments`）。字符串能解析为 JSON 时保存解析后的值，空字符串视为没有参数，
+    /// 其余字符串原样保留，由 [`ToolCall::arguments`] 报告解析错误。
+    pub fn from_raw<I: Into<String>, N: Into<String>>(
+        id: I,
+        name: N,
+        arguments: Option<&serde_json::Value>,
+    ) -> Self {
+        let arguments = match arguments {
+            Some(Value::String(raw)) if raw.trim().is_empty() => json!({}),
+            Some(Value::String(raw)) => {
+                // `"\"x\""` 这样本身是 JSON 字符串的参数保持原样，导出时才能还原
+                serde_json::from_str::<Value>(raw)
+                    .ok()
+                    .filter(|value| !value.is_string())
+                    .unwrap_or_else(|| json!(raw))
+            }
+            Some(Value::Null) | None => json!({}),
+            Some(value) => value.clone(),
+        };
+        Self::new(id, name, arguments)
+    }
+
     pub fn function_name(&self) -> &str {
         &self.function.name
     }
@@ -263,13 +635,13 @@ impl ToolCall {
         &self.id
     }
 }
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct FunctionCall {
     pub name: String,
     pub arguments: serde_json::Value,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(untagged)]
 pub enum Content {
     Text(String),
@@ -279,7 +651,7 @@ pub enum Content {
     Mixed(Vec<ContentBlock>),
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(tag = "type")]
 pub enum ContentBlock {
     #[serde(rename = "text")]
@@ -293,4 +665,287 @@ pub enum ContentBlock {
     // Claude 或 v1 格式的 reasoning block
     #[serde(rename = "reasoning")]
     Reasoning { content: String },
+    /// 图片，格式与 OpenAI 视觉输入的 `image_url` 内容块相同
+    #[serde(rename = "image_url")]
+    ImageUrl { image_url: ImageUrl },
+}
+
+impl ContentBlock {
+    /// 文本内容块
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::Text { text: text.into() }
+    }
+
+    /// 图片内容块，`url` 可以是网址或 `data:image/...;base64,...` 形式的数据 URL
+    pub fn image(url: impl Into<String>) -> Self {
+        Self::ImageUrl {
+            image_url: ImageUrl {
+                url: url.into(),
+                detail: None,
+            },
+        }
+    }
+
+    /// 指定图片的解析精度，对非图片内容块无效
+    pub fn with_detail(mut self, detail: ImageDetail) -> Self {
+        if let Self::ImageUrl { image_url } = &mut self {
+            image_url.detail = Some(detail);
+        }
+        self
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ImageUrl {
+    pub url: String,
+    /// 解析精度，未设置时由模型（或模型的默认设置）决定
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<ImageDetail>,
+}
+
+/// 图片的解析精度：`low` 更快、消耗更少的 token，`high` 能看清更多细节
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageDetail {
+    Low,
+    High,
+    Auto,
+}
+
+/// OpenAI Chat Completions 格式的导入导出
+///
+/// 与 serde 序列化（用于 checkpoint）的区别：工具调用参数导出为 JSON 字符串、导入时解析回 JSON；
+/// 开发者消息的角色为 `developer`；不导出附加信息（metadata）。
+impl Message {
+    /// 转换为 OpenAI 的消息对象
+    pub fn to_openai(&self) -> Value {
+        let mut object = match self {
+            Message::User { content, name, .. } => {
+                let content = match content {
+                    Content::Text(text) => json!(text),
+                    Content::Image { url } => {
+                        json!([{"type": "image_url", "image_url": {"url": url}}])
+                    }
+                    Content::Mixed(blocks) => Value::Array(
+                        blocks
+                            .iter()
+                            .filter_map(|block| match block {
+                                ContentBlock::Text { text } => {
+                                    Some(json!({"type": "text", "text": text}))
+                                }
+                                ContentBlock::ImageUrl { image_url } => {
+                                    Some(json!({"type": "image_url", "image_url": image_url}))
+                                }
+                                // OpenAI 的用户消息中没有对应的内容块
+                                ContentBlock::ToolUse { .. } | ContentBlock::Reasoning { .. } => {
+                                    None
+                                }
+                            })
+                            .collect(),
+                    ),
+                };
+                with_name(json!({"role": "user", "content": content}), name)
+            }
+            Message::Assistant {
+                content,
+                reasoning_content,
+                tool_calls,
+                name,
+                ..
+            } => {
+                let mut object = json!({"role": "assistant", "content": content});
+                if let Some(reasoning) = reasoning_content {
+                    object["reasoning_content"] = json!(reasoning);
+                }
+                if let Some(calls) = tool_calls {
+                    object["tool_calls"] = calls
+                        .iter()
+                        .map(|call| {
+                            let arguments = match &call.function.arguments {
+                                Value::String(raw) => raw.clone(),
+                                value => value.to_string(),
+                            };
+                            json!({
+                                "id": call.id,
+                                "type": call.type_name,
+                                "function": {"name": call.function.name, "arguments": arguments},
+                            })
+                        })
+                        .collect();
+                }
+                with_name(object, name)
+            }
+            Message::System { content, name, .. } => {
+                with_name(json!({"role": "system", "content": content}), name)
+            }
+            Message::Developer { content, name, .. } => {
+                with_name(json!({"role": "developer", "content": content}), name)
+            }
+            Message::Tool {
+                tool_call_id,
+                content,
+                ..
+            } => json!({"role": "tool", "tool_call_id": tool_call_id, "content": content}),
+        };
+        if let Value::Object(map) = &mut object {
+            map.retain(|_, value| !value.is_null());
+        }
+        object
+    }
+
+    /// 从 OpenAI 的消息对象创建消息
+    ///
+    /// 助手消息的 `content` 可以为 `null`；文本类型的 `content` 数组会拼接为一段文本。
+    /// 不支持已废弃的 `function` 角色。
+    pub fn from_openai(value: &Value) -> Result<Self, ValidationError> {
+        let role = value["role"]
+            .as_str()
+            .ok_or_else(|| ValidationError::MissingField("role".to_owned()))?;
+        let name = value["name"].as_str().map(str::to_owned);
+        let text = |field: &str| -> Result<String, ValidationError> {
+            match &value[field] {
+                Value::String(text) => Ok(text.clone()),
+                Value::Null => Ok(String::new()),
+                Value::Array(parts) => parts
+                    .iter()
+                    .map(
+                        |part| match (part["type"].as_str(), part["text"].as_str()) {
+                            (Some("text"), Some(text)) => Ok(text),
+                            _ => Err(ValidationError::InvalidFormat(format!(
+                                "unsupported {role} content part: {part}"
+                            ))),
+                        },
+                    )
+                    .collect(),
+                other => Err(ValidationError::InvalidFormat(format!(
+                    "invalid {field} for {role} message: {other}"
+                ))),
+            }
+        };
+
+        let message = match role {
+            "user" => {
+                let content = match &value["content"] {
+                    Value::String(text) => Content::Text(text.clone()),
+                    Value::Array(parts) => match parts.as_slice() {
+                        [part] if part["type"] == "image_url" => Content::Image {
+                            url: part["image_url"]["url"]
+                                .as_str()
+                                .ok_or_else(|| {
+                                    ValidationError::MissingField("image_url.url".to_owned())
+                                })?
+                                .to_owned(),
+                        },
+                        _ => Content::Mixed(
+                            parts
+                                .iter()
+                                .map(
+                                    |part| match (part["type"].as_str(), part["text"].as_str()) {
+                                        (Some("text"), Some(text)) => Ok(ContentBlock::Text {
+                                            text: text.to_owned(),
+                                        }),
+                                        (Some("image_url"), _) => {
+                                            serde_json::from_value(part["image_url"].clone())
+                                                .map(|image_url| ContentBlock::ImageUrl {
+                                                    image_url,
+                                                })
+                                                .map_err(|e| {
+                                                    ValidationError::InvalidFormat(format!(
+                                                        "invalid image_url content part: {e}"
+                                                    ))
+                                                })
+                                        }
+                                        _ => Err(ValidationError::InvalidFormat(format!(
+                                            "unsupported user content part: {part}"
+                                        ))),
+                                    },
+                                )
+                                .collect::<Result<_, _>>()?,
+                        ),
+                    },
+                    other => {
+                        return Err(ValidationError::InvalidFormat(format!(
+                            "invalid content for user message: {other}"
+                        )));
+                    }
+                };
+                Message::User {
+                    content,
+                    name,
+                    metadata: Metadata::new(),
+                }
+            }
+            "assistant" => {
+                let tool_calls = match &value["tool_calls"] {
+                    Value::Null => None,
+                    Value::Array(calls) => Some(
+                        calls
+                            .iter()
+                            .map(tool_call_from_openai)
+                            .collect::<Result<_, _>>()?,
+                    ),
+                    other => {
+                        return Err(ValidationError::InvalidFormat(format!(
+                            "tool_calls must be an array, got {other}"
+                        )));
+                    }
+                };
+                Message::Assistant {
+                    content: text("content")?,
+                    reasoning_content: value["reasoning_content"].as_str().map(str::to_owned),
+                    tool_calls,
+                    name,
+                    metadata: Metadata::new(),
+                }
+            }
+            "system" => Message::System {
+                content: text("content")?,
+                name,
+                metadata: Metadata::new(),
+            },
+            "developer" => Message::Developer {
+                content: text("content")?,
+                name,
+                metadata: Metadata::new(),
+            },
+            "tool" => Message::Tool {
+                tool_call_id: value["tool_call_id"]
+                    .as_str()
+                    .ok_or_else(|| ValidationError::MissingField("tool_call_id".to_owned()))?
+                    .to_owned(),
+                content: text("content")?,
+                metadata: Metadata::new(),
+            },
+            other => {
+                return Err(ValidationError::InvalidInput(format!(
+                    "unsupported role `{other}`"
+                )));
+            }
+        };
+        Ok(message)
+    }
+}
+
+fn with_name(mut object: Value, name: &Option<String>) -> Value {
+    if let Some(name) = name {
+        object["name"] = json!(name);
+    }
+    object
+}
+
+fn tool_call_from_openai(call: &Value) -> Result<ToolCall, ValidationError> {
+    let field = |pointer: &str| {
+        call.pointer(pointer)
+            .and_then(Value::as_str)
+            .ok_or_else(|| ValidationError::MissingField(format!("tool_calls{pointer}")))
+    };
+    let mut tool_call = ToolCall::from_raw(
+        field("/id")?,
+        field("/function/name")?,
+        call.pointer("/function/arguments"),
+    );
+    if let Some(type_name) = call["type"].as_str() {
+        type_name.clone_into(&mut tool_call.type_name);
+    }
+    Ok(tool_call)
 }
@@ -0,0 +1,12 @@
+//! 模型包装器
+//!
+//! 这些类型包装任意 [`ChatModel`](crate::state::ChatModel)，并自身实现 `ChatModel`，
+//! 可以直接替换原模型传给 Agent。
+
+#[cfg(feature = "native")]
+pub mod circuit_breaker;
+pub mod fallback;
+
+#[cfg(feature = "native")]
+pub use circuit_breaker::{CircuitBreaker, CircuitBreakerConfig, CircuitState};
+pub use fallback::FallbackModel;
@@ -0,0 +1,409 @@
+//! 熔断器
+//!
+//! 模型服务不可用时快速失败，避免每次调用都等待超时或无休止地重试。
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+
+use crate::{
+    error::{ErrorCategory, LangChainError, ModelError},
+    message::Message,
+    state::{ChatCompletion, ChatModel, InvokeOptions, StandardChatStream},
+};
+
+/// 熔断器状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// 正常放行所有调用
+    Closed,
+    /// 直接拒绝调用，直到冷却结束
+    Open,
+    /// 冷却结束，放行一次探测调用以判断服务是否恢复
+    HalfOpen,
+}
+
+/// 熔断器配置
+#[derive(Debug, Clone)]
+pub struct CircuitBreakerConfig {
+    /// 触发熔断的连续失败次数
+    pub failure_threshold: u32,
+    /// 连续失败需落在该时间窗口内，首次失败超出窗口后重新计数
+    pub window: Duration,
+    /// 熔断打开后的冷却时间
+    pub cooldown: Duration,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            window: Duration::from_secs(60),
+            cooldown: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    state: CircuitState,
+    consecutive_failures: u32,
+    first_failure_at: Option<Instant>,
+    opened_at: Option<Instant>,
+    probe_in_flight: bool,
+}
+
+/// 一次放行的调用
+///
+/// 调用 future 在返回结果前被丢弃（例如调用方超时或取消）时，探测调用不会被
+/// [`CircuitBreaker::record`] 结束，由这里清除 `probe_in_flight`，让下一次调用重新探测。
+struct Permit<'a> {
+    state: &'a Mutex<Inner>,
+    probe: bool,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        if self.probe {
+            self.state.lock().unwrap().probe_in_flight = false;
+        }
+    }
+}
+
+/// 为模型调用加上熔断保护
+///
+/// 只有服务端问题（临时错误、速率限制、外部服务错误）计为失败，
+/// 参数校验、认证等调用方问题不会触发熔断。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// let model = CircuitBreaker::new(ChatOpenAIBuilder::from_base(...).build(), CircuitBreakerConfig::default());
+/// let agent = ReactAgentBuilder::new(model).build();
+/// ```
+pub struct CircuitBreaker<M> {
+    inner: M,
+    config: CircuitBreakerConfig,
+    state: Mutex<Inner>,
+}
+
+impl<M> CircuitBreaker<M> {
+    pub fn new(model: M, config: CircuitBreakerConfig) -> Self {
+        Self {
+            inner: model,
+            config,
+            state: Mutex::new(Inner {
+                state: CircuitState::Closed,
+                consecutive_failures: 0,
+                first_failure_at: None,
+                opened_at: None,
+                probe_in_flight: false,
+            }),
+        }
+    }
+
+    /// 当前状态，冷却已结束的 `Open` 会报告为 `HalfOpen`
+    pub fn state(&self) -> CircuitState {
+        let inner = self.state.lock().unwrap();
+        match inner.state {
+            CircuitState::Open if self.cooldown_remaining(&inner).is_zero() => {
+                CircuitState::HalfOpen
+            }
+            state => state,
+        }
+    }
+
+    /// 当前连续失败次数
+    pub fn consecutive_failures(&self) -> u32 {
+        self.state.lock().unwrap().consecutive_failures
+    }
+
+    fn cooldown_remaining(&self, inner: &Inner) -> Duration {
+        inner
+            .opened_at
+            .map(|at| self.config.cooldown.saturating_sub(at.elapsed()))
+            .unwrap_or_default()
+    }
+
+    /// 调用前检查是否放行
+    fn acquire(&self) -> Result<Permit<'_>, ModelError> {
+        let mut inner = self.state.lock().unwrap();
+        let probe = match inner.state {
+            CircuitState::Closed => false,
+            CircuitState::Open => {
+                let remaining = self.cooldown_remaining(&inner);
+                if !remaining.is_zero() {
+                    return Err(ModelError::CircuitOpen(remaining.as_millis() as u64));
+                }
+                tracing::info!("circuit breaker half-open, probing model");
+                inner.state = CircuitState::HalfOpen;
+                inner.probe_in_flight = true;
+                true
+            }
+            CircuitState::HalfOpen => {
+                if inner.probe_in_flight {
+                    // 探测调用尚未返回，其余调用继续快速失败
+                    return Err(ModelError::CircuitOpen(0));
+                }
+                inner.probe_in_flight = true;
+                true
+            }
+        };
+        Ok(Permit {
+            state: &self.state,
+            probe,
+        })
+    }
+
+    /// 根据调用结果更新状态
+    fn record<T>(&self, mut permit: Permit<'_>, result: &Result<T, ModelError>) {
+        permit.probe = false;
+        let mut inner = self.state.lock().unwrap();
+        inner.probe_in_flight = false;
+
+        let failed = match result {
+            Ok(_) => false,
+            Err(e) => matches!(
+                e.category(),
+                ErrorCategory::Transient | ErrorCategory::RateLimit | ErrorCategory::External
+            ),
+        };
+
+        if !failed {
+            if inner.state != CircuitState::Closed {
+                tracing::info!("circuit breaker closed");
+            }
+            inner.state = CircuitState::Closed;
+            inner.consecutive_failures = 0;
+            inner.first_failure_at = None;
+            inner.opened_at = None;
+            return;
+        }
+
+        let now = Instant::now();
+        match inner.first_failure_at {
+            Some(at) if now.duration_since(at) <= self.config.window => {
+                inner.consecutive_failures += 1;
+            }
+            _ => {
+                inner.first_failure_at = Some(now);
+                inner.consecutive_failures = 1;
+            }
+        }
+
+        if inner.state == CircuitState::HalfOpen
+            || inner.consecutive_failures >= self.config.failure_threshold
+        {
+            tracing::warn!(
+                "circuit breaker opened after {} consecutive failures",
+                inner.consecutive_failures
+            );
+            inner.state = CircuitState::Open;
+            inner.opened_at = Some(now);
+        }
+    }
+}
+
+#[async_trait]
+impl<M: ChatModel> ChatModel for CircuitBreaker<M> {
+    async fn invoke(
+        &self,
+        messages: &[Arc<Message>],
+        options: &InvokeOptions<'_>,
+    ) -> Result<ChatCompletion, ModelError> {
+        let permit = self.acquire()?;
+        let result = self.inner.invoke(messages, options).await;
+        self.record(permit, &result);
+        result
+    }
+
+    /// 流式调用只统计建立连接阶段的结果
+    async fn stream(
+        &self,
+        messages: &[Arc<Message>],
+        options: &InvokeOptions<'_>,
+    ) -> Result<StandardChatStream, ModelError> {
+        let permit = self.acquire()?;
+        let result = self.inner.stream(messages, options).await;
+        self.record(permit, &result);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    struct FlakyModel {
+        failing: AtomicBool,
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl ChatModel for FlakyModel {
+        async fn invoke(
+            &self,
+            _messages: &[Arc<Message>],
+            _options: &InvokeOptions<'_>,
+        ) -> Result<ChatCompletion, ModelError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            if self.failing.load(Ordering::SeqCst) {
+                Err(ModelError::ServerError(503, "unavailable".to_owned()))
+            } else {
+                Ok(ChatCompletion {
+                    messages: vec![Arc::new(Message::assistant("ok"))],
+                    usage: Default::default(),
+                })
+            }
+        }
+
+        async fn stream(
+            &self,
+            _messages: &[Arc<Message>],
+            _options: &InvokeOptions<'_>,
+        ) -> Result<StandardChatStream, ModelError> {
+            unimplemented!()
+        }
+    }
+
+    #[tokio::test]
+    async fn circuit_opens_then_recovers_after_cooldown() {
+        let breaker = CircuitBreaker::new(
+            FlakyModel {
+                failing: AtomicBool::new(true),
+                calls: AtomicUsize::new(0),
+            },
+            CircuitBreakerConfig {
+                failure_threshold: 2,
+                window: Duration::from_secs(60),
+                cooldown: Duration::from_millis(50),
+            },
+        );
+        let options = InvokeOptions::default();
+
+        for _ in 0..2 {
+            assert!(matches!(
+                breaker.invoke(&[], &options).await,
+                Err(ModelError::ServerError(..))
+            ));
+        }
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        // 熔断期间不再调用底层模型
+        assert!(matches!(
+            breaker.invoke(&[], &options).await,
+            Err(ModelError::CircuitOpen(_))
+        ));
+        assert_eq!(breaker.inner.calls.load(Ordering::SeqCst), 2);
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+
+        // 探测失败后重新打开
+        assert!(breaker.invoke(&[], &options).await.is_err());
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        breaker.inner.failing.store(false, Ordering::SeqCst);
+        assert!(breaker.invoke(&[], &options).await.is_ok());
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert_eq!(breaker.consecutive_failures(), 0);
+    }
+
+    #[tokio::test]
+    async fn dropped_probe_releases_half_open_slot() {
+        /// 失败一次，之后的第一次调用永不返回，再之后成功
+        struct HangingModel {
+            calls: AtomicUsize,
+        }
+
+        #[async_trait]
+        impl ChatModel for HangingModel {
+            async fn invoke(
+                &self,
+                _messages: &[Arc<Message>],
+                _options: &InvokeOptions<'_>,
+            ) -> Result<ChatCompletion, ModelError> {
+                match self.calls.fetch_add(1, Ordering::SeqCst) {
+                    0 => Err(ModelError::ServerError(503, "unavailable".to_owned())),
+                    1 => std::future::pending().await,
+                    _ => Ok(ChatCompletion {
+                        messages: vec![Arc::new(Message::assistant("ok"))],
+                        usage: Default::default(),
+                    }),
+                }
+            }
+
+            async fn stream(
+                &self,
+                _messages: &[Arc<Message>],
+                _options: &InvokeOptions<'_>,
+            ) -> Result<StandardChatStream, ModelError> {
+                unimplemented!()
+            }
+        }
+
+        let breaker = CircuitBreaker::new(
+            HangingModel {
+                calls: AtomicUsize::new(0),
+            },
+            CircuitBreakerConfig {
+                failure_threshold: 1,
+                window: Duration::from_secs(60),
+                cooldown: Duration::from_millis(20),
+            },
+        );
+        let options = InvokeOptions::default();
+
+        assert!(breaker.invoke(&[], &options).await.is_err());
+        tokio::time::sleep(Duration::from_millis(30)).await;
+
+        // 探测调用被调用方超时丢弃
+        let probe =
+            tokio::time::timeout(Duration::from_millis(20), breaker.invoke(&[], &options)).await;
+        assert!(probe.is_err());
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+
+        // 下一次调用可以重新探测，而不是永远快速失败
+        assert!(breaker.invoke(&[], &options).await.is_ok());
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[tokio::test]
+    async fn caller_errors_do_not_trip_circuit() {
+        struct InvalidKeyModel;
+
+        #[async_trait]
+        impl ChatModel for InvalidKeyModel {
+            async fn invoke(
+                &self,
+                _messages: &[Arc<Message>],
+                _options: &InvokeOptions<'_>,
+            ) -> Result<ChatCompletion, ModelError> {
+                Err(ModelError::InvalidApiKey)
+            }
+
+            async fn stream(
+                &self,
+                _messages: &[Arc<Message>],
+                _options: &InvokeOptions<'_>,
+            ) -> Result<StandardChatStream, ModelError> {
+                Err(ModelError::InvalidApiKey)
+            }
+        }
+
+        let breaker = CircuitBreaker::new(
+            InvalidKeyModel,
+            CircuitBreakerConfig {
+                failure_threshold: 1,
+                ..Default::default()
+            },
+        );
+        for _ in 0..3 {
+            let _ = breaker.invoke(&[], &InvokeOptions::default()).await;
+        }
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+}
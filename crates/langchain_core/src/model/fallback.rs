@@ -0,0 +1,174 @@
+//! 模型故障转移
+//!
+//! 主模型出现可重试的错误（速率限制、5xx、超时等）时，依次尝试备用模型。
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::{
+    error::{LangChainError, ModelError},
+    message::Message,
+    state::{ChatCompletion, ChatModel, InvokeOptions, StandardChatStream},
+};
+
+/// 按顺序尝试多个模型的 [`ChatModel`]
+///
+/// 只有可重试的错误会触发故障转移，认证失败、参数错误等会直接返回，
+/// 因为换一个模型通常也无法解决。所有模型都失败时返回最后一个错误。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// let model = FallbackModel::new(primary).with_fallback(backup);
+/// let agent = ReactAgentBuilder::new(model).build();
+/// ```
+pub struct FallbackModel {
+    models: Vec<Box<dyn ChatModel>>,
+}
+
+impl FallbackModel {
+    pub fn new<M: ChatModel + 'static>(primary: M) -> Self {
+        Self {
+            models: vec![Box::new(primary)],
+        }
+    }
+
+    /// 追加一个备用模型，按添加顺序尝试
+    pub fn with_fallback<M: ChatModel + 'static>(mut self, model: M) -> Self {
+        self.models.push(Box::new(model));
+        self
+    }
+}
+
+/// 依次调用每个模型，直到成功或遇到不可重试的错误
+macro_rules! try_models {
+    ($self:ident, $method:ident, $messages:ident, $options:ident) => {{
+        let mut last_error = None;
+        for (index, model) in $self.models.iter().enumerate() {
+            match model.$method($messages, $options).await {
+                Ok(result) => {
+                    if index > 0 {
+                        tracing::info!("request served by fallback model #{index}");
+                    } else {
+                        tracing::debug!("request served by primary model");
+                    }
+                    return Ok(result);
+                }
+                Err(e) if e.is_retryable() => {
+                    tracing::warn!("model #{index} failed, trying next: {e}");
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Err(last_error.expect("FallbackModel has at least one model"))
+    }};
+}
+
+#[async_trait]
+impl ChatModel for FallbackModel {
+    async fn invoke(
+        &self,
+        messages: &[Arc<Message>],
+        options: &InvokeOptions<'_>,
+    ) -> Result<ChatCompletion, ModelError> {
+        try_models!(self, invoke, messages, options)
+    }
+
+    /// 只在建立流之前进行故障转移，流开始后的错误直接透传
+    async fn stream(
+        &self,
+        messages: &[Arc<Message>],
+        options: &InvokeOptions<'_>,
+    ) -> Result<StandardChatStream, ModelError> {
+        try_models!(self, stream, messages, options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct StaticModel {
+        result: fn() -> Result<ChatCompletion, ModelError>,
+        calls: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl ChatModel for StaticModel {
+        async fn invoke(
+            &self,
+            _messages: &[Arc<Message>],
+            _options: &InvokeOptions<'_>,
+        ) -> Result<ChatCompletion, ModelError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            (self.result)()
+        }
+
+        async fn stream(
+            &self,
+            _messages: &[Arc<Message>],
+            _options: &InvokeOptions<'_>,
+        ) -> Result<StandardChatStream, ModelError> {
+            unimplemented!()
+        }
+    }
+
+    fn model(
+        result: fn() -> Result<ChatCompletion, ModelError>,
+    ) -> (StaticModel, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        (
+            StaticModel {
+                result,
+                calls: calls.clone(),
+            },
+            calls,
+        )
+    }
+
+    fn ok() -> Result<ChatCompletion, ModelError> {
+        Ok(ChatCompletion {
+            messages: vec![Arc::new(Message::assistant("ok"))],
+            usage: Default::default(),
+        })
+    }
+
+    #[tokio::test]
+    async fn falls_back_on_retryable_error() {
+        let (primary, primary_calls) = model(|| Err(ModelError::RateLimited(1)));
+        let (backup, backup_calls) = model(ok);
+        let fallback = FallbackModel::new(primary).with_fallback(backup);
+
+        let result = fallback.invoke(&[], &InvokeOptions::default()).await;
+
+        assert!(result.is_ok());
+        assert_eq!(primary_calls.load(Ordering::SeqCst), 1);
+        assert_eq!(backup_calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn terminal_error_is_returned_immediately() {
+        let (primary, _) = model(|| Err(ModelError::InvalidApiKey));
+        let (backup, backup_calls) = model(ok);
+        let fallback = FallbackModel::new(primary).with_fallback(backup);
+
+        let result = fallback.invoke(&[], &InvokeOptions::default()).await;
+
+        assert!(matches!(result, Err(ModelError::InvalidApiKey)));
+        assert_eq!(backup_calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn returns_last_error_when_all_fail() {
+        let (primary, _) = model(|| Err(ModelError::RateLimited(1)));
+        let (backup, _) = model(|| Err(ModelError::ServerError(503, "down".to_owned())));
+        let fallback = FallbackModel::new(primary).with_fallback(backup);
+
+        let result = fallback.invoke(&[], &InvokeOptions::default()).await;
+
+        assert!(matches!(result, Err(ModelError::ServerError(503, _))));
+    }
+}
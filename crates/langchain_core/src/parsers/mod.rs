@@ -2,7 +2,8 @@
 //!
 //! 提供从 LLM 文本输出中提取结构化数据的解析器。
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::Value;
 use thiserror::Error;
 
 /// 解析器错误
@@ -19,6 +20,9 @@ pub enum ParseError {
 
     #[error("Empty output")]
     EmptyOutput,
+
+    #[error("Invalid value for field `{field}`: {message}")]
+    InvalidField { field: String, message: String },
 }
 
 /// 输出解析器 trait
@@ -31,16 +35,138 @@ pub trait OutputParser<T>: Send + Sync {
 }
 
 /// JSON 解析器
+///
+/// 默认从模型输出中找出 JSON：优先取代码块中的 JSON，否则取正文中第一个能解析为 `T` 的
+/// 对象或数组，忽略前后的说明文字。[`strict`](Self::strict) 模式要求整个输出就是 JSON。
 pub struct JsonParser<T> {
+    strict: bool,
     phantom: std::marker::PhantomData<T>,
 }
 
 impl<T: for<'de> Deserialize<'de>> JsonParser<T> {
     pub fn new() -> Self {
         Self {
+            strict: false,
             phantom: std::marker::PhantomData,
         }
     }
+
+    /// 要求整个输出（去掉首尾空白后）就是 JSON，不接受代码块和说明文字
+    pub fn strict() -> Self {
+        Self {
+            strict: true,
+            phantom: std::marker::PhantomData,
+        }
+    }
+
+    /// 解析可能尚未输出完整的 JSON，返回目前为止能确定的部分
+    ///
+    /// 未闭合的字符串按已输出的内容截断，未闭合的对象和数组自动补全，
+    /// 不完整的键、还没有值的键和不完整的字面量（如 `tru`）连同之前的逗号一起丢弃。
+    /// 结果只是 JSON 值，不按 `T` 校验；文本中还没有 `{` 或 `[` 时返回 `None`。
+    pub fn parse_partial(&self, text: &str) -> Option<serde_json::Value> {
+        parse_partial_json(text)
+    }
+}
+
+/// 扫描 `text`，返回末尾仍未闭合的容器（`{`/`[`）、是否停在字符串中以及是否停在转义符之后
+fn open_containers(text: &str) -> (Vec<char>, bool, bool) {
+    let mut stack = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+    for c in text.chars() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' | '[' => stack.push(c),
+            '}' | ']' => {
+                stack.pop();
+            }
+            _ => {}
+        }
+    }
+    (stack, in_string, escaped)
+}
+
+/// 补全截断在 `prefix` 处的 JSON 并尝试解析
+fn complete_json(prefix: &str) -> Option<serde_json::Value> {
+    let (stack, in_string, escaped) = open_containers(prefix);
+    let mut text = prefix.to_owned();
+    if escaped {
+        // 末尾未完成的转义无法补全，直接丢弃
+        text.pop();
+    }
+    if in_string {
+        text.push('"');
+    }
+    let trimmed = text.trim_end().len();
+    text.truncate(trimmed);
+    if text.ends_with(',') {
+        text.pop();
+    } else if text.ends_with(':') {
+        // 值还没开始输出的键不计入结果
+        return None;
+    }
+    for open in stack.iter().rev() {
+        text.push(if *open == '{' { '}' } else { ']' });
+    }
+    serde_json::from_str(&text).ok()
+}
+
+fn parse_partial_json(text: &str) -> Option<serde_json::Value> {
+    let start = text.find(['{', '['])?;
+    let text = &text[start..];
+
+    // 根容器闭合后的内容（如代码块结尾的 ```）不属于 JSON；
+    // 同时记录可以安全截断的位置：逗号之前、容器开始之后
+    let mut cuts = Vec::new();
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut end = text.len();
+    for (i, c) in text.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' | '[' => {
+                depth += 1;
+                cuts.push(i + 1);
+            }
+            '}' | ']' => {
+                depth = depth.saturating_sub(1);
+                if depth == 0 {
+                    end = i + 1;
+                    break;
+                }
+            }
+            ',' => cuts.push(i),
+            _ => {}
+        }
+    }
+    let text = &text[..end];
+
+    complete_json(text).or_else(|| {
+        cuts.iter()
+            .rev()
+            .filter(|cut| **cut < text.len())
+            .find_map(|cut| complete_json(&text[..*cut]))
+    })
 }
 
 impl<T: for<'de> Deserialize<'de>> Default for JsonParser<T> {
@@ -51,89 +177,117 @@ impl<T: for<'de> Deserialize<'de>> Default for JsonParser<T> {
 
 impl<T: for<'de> Deserialize<'de> + Send + Sync> OutputParser<T> for JsonParser<T> {
     fn parse(&self, text: &str) -> Result<T, ParseError> {
-        let json_str = extract_json(text)?;
-        serde_json::from_str(&json_str).map_err(ParseError::Json)
+        if self.strict {
+            if text.trim().is_empty() {
+                return Err(ParseError::EmptyOutput);
+            }
+            return serde_json::from_str(text.trim()).map_err(ParseError::Json);
+        }
+        parse_embedded_json(text)
     }
 
     fn get_format_instructions(&self) -> String {
+        if self.strict {
+            return "Output only valid JSON, without code blocks or any other text.".to_owned();
+        }
         "Output must be valid JSON format. Wrap the JSON in ```json ``` code blocks if needed."
             .to_owned()
     }
 }
 
-/// 从文本中提取 JSON
-fn extract_json(text: &str) -> Result<String, ParseError> {
-    let text = text.trim();
-
-    // 1. 查找 ```json 代码块
-    if let Some(start) = text.find("```json") {
-        let json_start = start + 7;
-        if let Some(end) = text[json_start..].find("```") {
-            return Ok(text[json_start..json_start + end].trim().to_owned());
-        }
-    }
-
-    // 2. 查找普通 ``` 代码块
-    if let Some(start) = text.find("```") {
-        let json_start = start + 3;
-        if let Some(end) = text[json_start..].find("```") {
-            let content = text[json_start..json_start + end].trim();
-            // 验证是否是 JSON
-            if content.starts_with('{') || content.starts_with('[') {
-                return Ok(content.to_owned());
-            }
-        }
-    }
-
-    // 3. 查找 JSON 对象（以 { 开始）
-    if let Some(brace_start) = text.find('{') {
-        let mut brace_count = 0;
-        let mut end = brace_start;
-
-        for (i, c) in text[brace_start..].char_indices() {
+/// 文本中所有括号配对的对象或数组候选 `(起始, 结束)`，按起始位置排列，外层在内层之前
+///
+/// 只扫描一遍：括号外的引号属于说明文字，只有在括号内才按 JSON 字符串处理；
+/// 遇到不配对的右括号时丢弃之前未闭合的括号。
+fn json_candidates(text: &str) -> Vec<(usize, usize)> {
+    let mut candidates = Vec::new();
+    let mut stack: Vec<(usize, char)> = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        if in_string {
             match c {
-                '{' => brace_count += 1,
-                '}' => {
-                    brace_count -= 1;
-                    if brace_count == 0 {
-                        end = brace_start + i + 1;
-                        break;
-                    }
-                }
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
                 _ => {}
             }
+            continue;
         }
-
-        if brace_count == 0 {
-            return Ok(text[brace_start..end].to_owned());
+        match c {
+            '"' if !stack.is_empty() => in_string = true,
+            '{' => stack.push((i, '}')),
+            '[' => stack.push((i, ']')),
+            '}' | ']' => match stack.pop() {
+                Some((start, close)) if close == c => candidates.push((start, i + 1)),
+                _ => stack.clear(),
+            },
+            _ => {}
         }
     }
+    candidates.sort_unstable_by_key(|&(start, _)| start);
+    candidates
+}
 
-    // 4. 查找 JSON 数组（以 [ 开始）
-    if let Some(bracket_start) = text.find('[') {
-        let mut bracket_count = 0;
-        let mut end = bracket_start;
-
-        for (i, c) in text[bracket_start..].char_indices() {
-            match c {
-                '[' => bracket_count += 1,
-                ']' => {
-                    bracket_count -= 1;
-                    if bracket_count == 0 {
-                        end = bracket_start + i + 1;
-                        break;
-                    }
+/// 按顺序尝试文本中的候选，返回第一个能反序列化为 `T` 的值
+///
+/// 前言中可能也有括号（如 `[1 result]`、`Found [1] match:`），不是合法 JSON 或类型不符时继续
+/// 查找后面的候选；合法但类型不符的值不再尝试其内部的候选。失败时记录最有参考价值的错误：
+/// 类型不符优先于语法错误。
+fn first_json_value<T: DeserializeOwned>(
+    text: &str,
+    error: &mut Option<serde_json::Error>,
+) -> Option<T> {
+    let mut skip_until = 0;
+    for (start, end) in json_candidates(text) {
+        if start < skip_until {
+            continue;
+        }
+        match serde_json::from_str(&text[start..end]) {
+            Ok(value) => return Some(value),
+            Err(e) if e.is_data() => {
+                skip_until = end;
+                if error.as_ref().is_none_or(|previous| !previous.is_data()) {
+                    *error = Some(e);
                 }
-                _ => {}
+            }
+            Err(e) => {
+                error.get_or_insert(e);
             }
         }
+    }
+    None
+}
 
-        if bracket_count == 0 {
-            return Ok(text[bracket_start..end].to_owned());
+/// 从文本中提取并解析 JSON
+///
+/// 模型常在 JSON 前后加上说明（"Here is the JSON:"、"Let me know if..."）或用代码块包裹。
+/// 优先使用代码块中的 JSON，否则取全文中第一个能解析为 `T` 的对象或数组。
+fn parse_embedded_json<T: DeserializeOwned>(text: &str) -> Result<T, ParseError> {
+    let text = text.trim();
+    let mut error = None;
+
+    // 1. 查找 ``` 代码块（带或不带语言标记）
+    let mut rest = text;
+    while let Some(start) = rest.find("```") {
+        let body = &rest[start + 3..];
+        let Some(end) = body.find("```") else {
+            break;
+        };
+        if let Some(value) = first_json_value(&body[..end], &mut error) {
+            return Ok(value);
         }
+        rest = &body[end + 3..];
     }
 
-    Err(ParseError::PatternNotFound("No JSON found".to_owned()))
+    // 2. 查找正文中的 JSON 对象或数组；都不符合时返回具体的 JSON 错误
+    if let Some(value) = first_json_value(text, &mut error) {
+        return Ok(value);
+    }
+    Err(error.map_or_else(
+        || ParseError::PatternNotFound("No JSON found".to_owned()),
+        ParseError::Json,
+    ))
 }
 
 /// 列表解析器
@@ -211,6 +365,95 @@ impl KeyValueParser {
     pub fn csv_style() -> Self {
         Self::new(",", "=")
     }
+
+    /// 把键值对组成 JSON 对象后反序列化为 `T`
+    ///
+    /// 看起来像数字、布尔值或 `null` 的值按对应的 JSON 类型处理，`T` 中对应字段
+    /// 需要字符串时（如 `zip: 02134`）退回字符串；其余值都是字符串。
+    /// 同名的键以最后一个为准。值无法转换为字段类型时返回 [`ParseError::InvalidField`]。
+    pub fn into_typed<T: DeserializeOwned>(self) -> TypedKeyValueParser<T> {
+        TypedKeyValueParser {
+            inner: self,
+            phantom: std::marker::PhantomData,
+        }
+    }
+}
+
+/// 按 `T` 反序列化的键值对解析器，见 [`KeyValueParser::into_typed`]
+pub struct TypedKeyValueParser<T> {
+    inner: KeyValueParser,
+    phantom: std::marker::PhantomData<T>,
+}
+
+/// 键值对组成的 JSON 对象文本，以及每个字段在文本中的范围（字节偏移）
+fn key_value_object(fields: &[(String, Value)]) -> (String, Vec<std::ops::Range<usize>>) {
+    let mut json = String::from("{");
+    let mut spans = Vec::with_capacity(fields.len());
+    for (i, (key, value)) in fields.iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        let start = json.len();
+        json.push_str(&Value::String(key.clone()).to_string());
+        json.push(':');
+        json.push_str(&value.to_string());
+        spans.push(start..json.len());
+    }
+    json.push('}');
+    (json, spans)
+}
+
+impl<T: DeserializeOwned + Send + Sync> OutputParser<T> for TypedKeyValueParser<T> {
+    fn parse(&self, text: &str) -> Result<T, ParseError> {
+        let mut fields: Vec<(String, Value)> = Vec::new();
+        for KeyValue { key, value } in self.inner.parse(text)? {
+            let value = match serde_json::from_str::<Value>(&value) {
+                Ok(v @ (Value::Number(_) | Value::Bool(_) | Value::Null)) => v,
+                _ => Value::String(value),
+            };
+            match fields.iter_mut().find(|(k, _)| *k == key) {
+                Some(field) => field.1 = value,
+                None => fields.push((key, value)),
+            }
+        }
+
+        // 退回字符串的字段按原类型转换时的错误，退回后仍失败时报告这个错误
+        let mut first_errors: Vec<Option<String>> = vec![None; fields.len()];
+        loop {
+            let (json, spans) = key_value_object(&fields);
+            let error = match serde_json::from_str::<T>(&json) {
+                Ok(value) => return Ok(value),
+                Err(e) => e,
+            };
+            // 错误位置（第 1 行的列号，从 1 开始）落在哪个字段的范围内
+            let at = error.column().saturating_sub(1);
+            let Some(index) = spans
+                .iter()
+                .position(|span| span.start <= at && at <= span.end)
+            else {
+                return Err(ParseError::Json(error));
+            };
+            // 去掉 serde_json 附加的位置信息，那是拼接后文本中的位置，对调用方没有意义
+            let message = error.to_string();
+            let message = match message.rfind(" at line ") {
+                Some(end) => message[..end].to_owned(),
+                None => message,
+            };
+            let (key, value) = &mut fields[index];
+            if value.is_string() {
+                return Err(ParseError::InvalidField {
+                    field: key.clone(),
+                    message: first_errors[index].take().unwrap_or(message),
+                });
+            }
+            first_errors[index] = Some(message);
+            *value = Value::String(value.to_string());
+        }
+    }
+
+    fn get_format_instructions(&self) -> String {
+        self.inner.get_format_instructions()
+    }
 }
 
 impl OutputParser<Vec<KeyValue>> for KeyValueParser {
@@ -375,6 +618,41 @@ Here's the data:
         assert_eq!(result.value, 42);
     }
 
+    #[test]
+    fn test_json_parser_partial() {
+        let parser = JsonParser::<TestData>::new();
+        let partial = |text| parser.parse_partial(text);
+
+        assert_eq!(partial("Sure, "), None);
+        assert_eq!(partial("```json\n{"), Some(serde_json::json!({})));
+        assert_eq!(partial(r#"{"na"#), Some(serde_json::json!({})));
+        assert_eq!(partial(r#"{"name": "#), Some(serde_json::json!({})));
+        assert_eq!(
+            partial(r#"{"name": "te"#),
+            Some(serde_json::json!({ "name": "te" }))
+        );
+        assert_eq!(
+            partial(r#"{"name": "test", "value": 4"#),
+            Some(serde_json::json!({ "name": "test", "value": 4 }))
+        );
+        assert_eq!(
+            partial(r#"{"name": "test", "ok": tr"#),
+            Some(serde_json::json!({ "name": "test" }))
+        );
+        assert_eq!(
+            partial(r#"{"name": "a\"#),
+            Some(serde_json::json!({ "name": "a" }))
+        );
+        assert_eq!(
+            partial(r#"{"tags": ["a", "b"#),
+            Some(serde_json::json!({ "tags": ["a", "b"] }))
+        );
+        assert_eq!(
+            partial("```json\n{\"name\": \"test\", \"value\": 42}\n```"),
+            Some(serde_json::json!({ "name": "test", "value": 42 }))
+        );
+    }
+
     #[test]
     fn test_list_parser_comma() {
         let parser = ListParser::comma_separated();
@@ -404,6 +682,43 @@ Here's the data:
         assert_eq!(result[0].value, "John");
     }
 
+    #[test]
+    fn test_key_value_parser_typed() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Person {
+            name: String,
+            age: u8,
+            zip: String,
+            admin: bool,
+        }
+
+        let parser = KeyValueParser::standard().into_typed::<Person>();
+        let person = parser
+            .parse("name: Ali\nage: 30\nzip: 02134\nadmin: true")
+            .unwrap();
+        assert_eq!(
+            person,
+            Person {
+                name: "Ali".to_owned(),
+                age: 30,
+                zip: "02134".to_owned(),
+                admin: true,
+            }
+        );
+
+        match parser.parse("name: Ali\nage: 300\nzip: 1\nadmin: no") {
+            Err(ParseError::InvalidField { field, message }) => {
+                assert_eq!(field, "age");
+                assert_eq!(message, "invalid value: integer `300`, expected u8");
+            }
+            other => panic!("{other:?}"),
+        }
+        match parser.parse("name: Ali\nage: thirty\nzip: 1\nadmin: true") {
+            Err(ParseError::InvalidField { field, .. }) => assert_eq!(field, "age"),
+            other => panic!("{other:?}"),
+        }
+    }
+
     #[test]
     fn test_key_value_parser_csv() {
         let parser = KeyValueParser::csv_style();
@@ -444,7 +759,7 @@ Here's the data:
 
     #[test]
     fn test_json_extract_from_code_block() {
-        let json_str = extract_json(
+        let parsed: TestData = parse_embedded_json(
             r#"
 Here's the result:
 ```json
@@ -458,16 +773,117 @@ Thanks!
         )
         .unwrap();
 
-        let parsed: TestData = serde_json::from_str(&json_str).unwrap();
         assert_eq!(parsed.name, "test");
         assert_eq!(parsed.value, 42);
     }
 
     #[test]
-    fn test_json_extract_plain() {
-        let json_str = extract_json(r#"{"name": "test", "value": 42}"#).unwrap();
+    fn test_json_parser_ignores_surrounding_prose() {
+        let parser = JsonParser::<TestData>::new();
+        let parse = |text: &str| {
+            let data = parser.parse(text).unwrap();
+            (data.name, data.value)
+        };
+
+        // 前言和结尾的说明
+        assert_eq!(
+            parse(
+                "Here is the JSON:\n{\"name\": \"a\", \"value\": 1}\nLet me know if you need more."
+            ),
+            ("a".to_owned(), 1)
+        );
+        // 字符串中的括号和转义的引号
+        assert_eq!(
+            parse(r#"Sure! {"name": "}{ \"[x]\" {", "value": 2} Hope this helps {:"#),
+            ("}{ \"[x]\" {".to_owned(), 2)
+        );
+        // 前言中不是 JSON 的括号
+        assert_eq!(
+            parse(r#"Result [1 of 1] {see below}: {"name": "b", "value": 3}"#),
+            ("b".to_owned(), 3)
+        );
+        // 代码块之后还有说明，说明中也有括号
+        assert_eq!(
+            parse(
+                "Here you go:\n```json\n{\"name\": \"c\", \"value\": 4}\n```\nNote: use {value} carefully."
+            ),
+            ("c".to_owned(), 4)
+        );
+        // 第一个代码块不是 JSON
+        assert_eq!(
+            parse(
+                "```text\nno json here\n```\nand then:\n```\n{\"name\": \"d\", \"value\": 5}\n```"
+            ),
+            ("d".to_owned(), 5)
+        );
+
+        // 前言中合法但类型不符的 JSON
+        assert_eq!(
+            parse(r#"Found [1] match: {"name": "e", "value": 6}"#),
+            ("e".to_owned(), 6)
+        );
+        // 说明文字中未配对的引号和括号
+        assert_eq!(
+            parse(r#"A 5" screen (see [note: {"name": "f", "value": 7}"#),
+            ("f".to_owned(), 7)
+        );
+
+        let values = JsonParser::<Vec<u32>>::new()
+            .parse("The values are [1, 2, 3]. Anything else?")
+            .unwrap();
+        assert_eq!(values, [1, 2, 3]);
+        let values = JsonParser::<Vec<u32>>::new()
+            .parse(r#"For {"query": "x"} the values are [4, 5]."#)
+            .unwrap();
+        assert_eq!(values, [4, 5]);
+    }
 
-        let parsed: TestData = serde_json::from_str(&json_str).unwrap();
+    #[test]
+    fn test_json_parser_reports_type_mismatch() {
+        let parser = JsonParser::<TestData>::new();
+        let error = parser
+            .parse(r#"Result [1 of 1]: {"name": "a", "value": "not a number"}"#)
+            .unwrap_err();
+        let ParseError::Json(error) = error else {
+            panic!("expected a JSON error, got {error:?}");
+        };
+        assert!(error.is_data());
+        assert!(matches!(
+            parser.parse("no json at all"),
+            Err(ParseError::PatternNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_json_candidates_are_linear() {
+        // 大量未闭合的括号不会让扫描退化为平方复杂度
+        let text = format!("{}{{\"name\": \"x\", \"value\": 1}}", "[{".repeat(200_000));
+        let started = std::time::Instant::now();
+        let data = JsonParser::<TestData>::new().parse(&text).unwrap();
+        assert_eq!(data.value, 1);
+        assert!(started.elapsed() < std::time::Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_json_parser_strict() {
+        let parser = JsonParser::<TestData>::strict();
+
+        let data = parser.parse(" {\"name\": \"a\", \"value\": 1}\n").unwrap();
+        assert_eq!((data.name.as_str(), data.value), ("a", 1));
+        assert!(matches!(
+            parser.parse("Here is the JSON: {\"name\": \"a\", \"value\": 1}"),
+            Err(ParseError::Json(_))
+        ));
+        assert!(matches!(
+            parser.parse("```json\n{\"name\": \"a\", \"value\": 1}\n```"),
+            Err(ParseError::Json(_))
+        ));
+        assert!(matches!(parser.parse("  "), Err(ParseError::EmptyOutput)));
+    }
+
+    #[test]
+    fn test_json_extract_plain() {
+        let parsed: TestData = parse_embedded_json(r#"{"name": "test", "value": 42}"#).unwrap();
         assert_eq!(parsed.name, "test");
         assert_eq!(parsed.value, 42);
     }
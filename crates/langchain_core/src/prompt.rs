@@ -0,0 +1,164 @@
+//! 提示词模板
+//!
+//! 模板中的 `{name}` 在渲染时替换为同名变量的值，`{{` 和 `}}` 分别表示字面的 `{` 和 `}`。
+//! 变量名只能包含字母、数字和下划线。缺少变量时渲染失败，不会把占位符原样留在提示词中。
+//!
+//! ```
+//! use std::collections::HashMap;
+//! use langchain_core::prompt::PromptTemplate;
+//!
+//! let template = PromptTemplate::new("Hello {user}, today is {date}.").unwrap();
+//! let vars = HashMap::from([
+//!     ("user".to_owned(), "Ann".to_owned()),
+//!     ("date".to_owned(), "2026-10-16".to_owned()),
+//! ]);
+//! assert_eq!(template.render(&vars).unwrap(), "Hello Ann, today is 2026-10-16.");
+//! ```
+
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+/// 提示词模板错误
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum PromptError {
+    /// 模板语法错误，`position` 为出错位置的字节偏移
+    #[error("invalid prompt template at byte {position}: {message}")]
+    Syntax { position: usize, message: String },
+
+    #[error("missing prompt variable `{0}`")]
+    MissingVariable(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Text(String),
+    Variable(String),
+}
+
+/// 解析后的提示词模板
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptTemplate {
+    segments: Vec<Segment>,
+}
+
+impl PromptTemplate {
+    /// 解析模板，占位符未闭合、为空或含有非法字符，以及出现未转义的 `}` 时返回
+    /// [`PromptError::Syntax`]
+    pub fn new(template: &str) -> Result<Self, PromptError> {
+        let syntax = |position: usize, message: &str| PromptError::Syntax {
+            position,
+            message: message.to_owned(),
+        };
+
+        let mut segments = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.char_indices().peekable();
+        while let Some((position, c)) = chars.next() {
+            match c {
+                '{' if chars.next_if(|(_, c)| *c == '{').is_some() => text.push('{'),
+                '}' if chars.next_if(|(_, c)| *c == '}').is_some() => text.push('}'),
+                '}' => {
+                    return Err(syntax(
+                        position,
+                        "unmatched `}`, write `}}` for a literal brace",
+                    ));
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some((_, '}')) => break,
+                            Some((_, c)) if c.is_ascii_alphanumeric() || c == '_' => name.push(c),
+                            Some((at, _)) => {
+                                return Err(syntax(
+                                    at,
+                                    "placeholder names may only contain letters, digits and `_`, write `{{` for a literal brace",
+                                ));
+                            }
+                            None => return Err(syntax(position, "unclosed placeholder")),
+                        }
+                    }
+                    if name.is_empty() {
+                        return Err(syntax(position, "empty placeholder"));
+                    }
+                    if !text.is_empty() {
+                        segments.push(Segment::Text(std::mem::take(&mut text)));
+                    }
+                    segments.push(Segment::Variable(name));
+                }
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            segments.push(Segment::Text(text));
+        }
+        Ok(Self { segments })
+    }
+
+    /// 模板中用到的变量名，按首次出现的顺序排列
+    pub fn variables(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = Vec::new();
+        for segment in &self.segments {
+            if let Segment::Variable(name) = segment
+                && !names.contains(&name.as_str())
+            {
+                names.push(name);
+            }
+        }
+        names
+    }
+
+    /// 用 `vars` 替换占位符，缺少变量时返回 [`PromptError::MissingVariable`]
+    pub fn render(&self, vars: &HashMap<String, String>) -> Result<String, PromptError> {
+        let mut output = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Text(text) => output.push_str(text),
+                Segment::Variable(name) => output.push_str(
+                    vars.get(name)
+                        .ok_or_else(|| PromptError::MissingVariable(name.clone()))?,
+                ),
+            }
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_placeholders_and_escaped_braces() {
+        let template =
+            PromptTemplate::new("{{\"user\": \"{user}\"}} uses {tools}; {user}").unwrap();
+        assert_eq!(template.variables(), ["user", "tools"]);
+
+        let vars = HashMap::from([
+            ("user".to_owned(), "Ann".to_owned()),
+            ("tools".to_owned(), "search".to_owned()),
+        ]);
+        assert_eq!(
+            template.render(&vars).unwrap(),
+            "{\"user\": \"Ann\"} uses search; Ann"
+        );
+
+        let err = template
+            .render(&HashMap::from([("user".to_owned(), "Ann".to_owned())]))
+            .unwrap_err();
+        assert_eq!(err, PromptError::MissingVariable("tools".to_owned()));
+    }
+
+    #[test]
+    fn rejects_malformed_templates() {
+        for (template, position) in [("Hi {user", 3), ("a } b", 2), ("{}", 0), ("{a b}", 2)] {
+            match PromptTemplate::new(template) {
+                Err(PromptError::Syntax { position: at, .. }) => {
+                    assert_eq!(at, position, "{template}");
+                }
+                other => panic!("{template}: {other:?}"),
+            }
+        }
+    }
+}
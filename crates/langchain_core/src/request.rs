@@ -46,12 +46,14 @@ pub struct RequestBody {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tools: Option<Vec<ToolSpec>>,
 
-    /// 控制模型调用 tool 的行为。
-    /// - `none` 意味着模型不会调用任何 tool，而是生成一条消息。
-    /// - `auto` 意味着模型可以选择生成一条消息或调用一个或多个 tool。
-    /// - `required` 意味着模型必须调用一个或多个 tool。
+    /// 控制模型调用 tool 的行为，见 [`ToolChoice`]
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub tool_choice: Option<String>,
+    pub tool_choice: Option<ToolChoice>,
+
+    /// 是否允许模型在一次回复中并行调用多个 tool，默认由服务端决定（OpenAI 为 true）。
+    /// 只有设置了 `tools` 时才能发送。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parallel_tool_calls: Option<bool>,
 
     /// 是否返回所输出 token 的对数概率。
     /// 如果为 true，则在 `message` 的 `content` 中返回每个输出 token 的对数概率。
@@ -70,8 +72,10 @@ pub struct RequestBody {
     /// 停止生成的标记序列
     /// - OpenAI: 最多 4 个序列，API 将停止生成后续标记。返回的文本将不包含停止序列。
     /// - DeepSeek: 一个 string 或最多包含 16 个 string 的 list，在遇到这些词时，API 将停止生成更多的 token。
+    ///
+    /// 统一以 list 形式发送，两种实现都支持。
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub stop: Option<String>,
+    pub stop: Option<Vec<String>>,
 
     /// 频率惩罚参数，范围为-2.0到2.0，默认值为0.0
     /// 如果该值为正，那么新 token 会根据其在已有文本中的出现频率受到相应的惩罚，降低模型重复相同内容的可能性。
@@ -83,6 +87,10 @@ pub struct RequestBody {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub presence_penalty: Option<f32>,
 
+    /// 随机种子，相同的种子和参数会尽量（不保证）返回相同的结果
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+
     /// 额外参数，当本结构体中没有包含特定的参数时，使用此参数传递额外的参数。
     #[serde(flatten)]
     pub extra: HashMap<String, Value>,
@@ -275,6 +283,17 @@ pub struct ToolFunction {
     pub name: String,
     pub description: String,
     pub parameters: Value,
+    /// 调用示例：(参数, 结果)
+    ///
+    /// 模型接口没有对应的字段，不随工具定义发送；由 Agent 附加到系统提示中，
+    /// 帮助较弱的模型写出正确的参数。
+    #[serde(skip)]
+    pub examples: Vec<(Value, Value)>,
+    /// 工具结果的 JSON Schema，可选
+    ///
+    /// 同样不随工具定义发送；用于说明工具的返回格式，以及由 Agent 校验工具结果。
+    #[serde(skip)]
+    pub output_schema: Option<Value>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -290,6 +309,85 @@ impl ToolSpec {
             ToolSpec::Function { function } => &function.name,
         }
     }
+
+    /// 工具的调用示例，见 [`ToolFunction::examples`]
+    pub fn examples(&self) -> &[(Value, Value)] {
+        match self {
+            ToolSpec::Function { function } => &function.examples,
+        }
+    }
+}
+
+/// 把工具的调用示例整理成一段提示词，没有任何示例时返回 `None`
+pub fn tool_examples_prompt(tools: &[ToolSpec]) -> Option<String> {
+    let mut lines = Vec::new();
+    for tool in tools {
+        for (args, result) in tool.examples() {
+            lines.push(format!("- {}({args}) -> {result}", tool.function_name()));
+        }
+    }
+    if lines.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "Examples of correct tool calls and their results:\n{}",
+        lines.join("\n")
+    ))
+}
+
+/// 控制模型调用 tool 的行为
+///
+/// 序列化为 OpenAI 的格式：`"auto"`、`"none"`、`"required"`，或
+/// `{"type": "function", "function": {"name": ...}}`。
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(into = "Value", try_from = "Value")]
+pub enum ToolChoice {
+    /// 模型自行选择生成消息或调用 tool
+    Auto,
+    /// 模型不调用 tool，直接生成消息
+    None,
+    /// 模型必须调用一个或多个 tool
+    Required,
+    /// 模型必须调用指定名称的 tool
+    Function(String),
+}
+
+impl ToolChoice {
+    pub fn function(name: impl Into<String>) -> Self {
+        Self::Function(name.into())
+    }
+}
+
+impl From<ToolChoice> for Value {
+    fn from(choice: ToolChoice) -> Self {
+        match choice {
+            ToolChoice::Auto => Value::from("auto"),
+            ToolChoice::None => Value::from("none"),
+            ToolChoice::Required => Value::from("required"),
+            ToolChoice::Function(name) => {
+                serde_json::json!({"type": "function", "function": {"name": name}})
+            }
+        }
+    }
+}
+
+impl TryFrom<Value> for ToolChoice {
+    type Error = String;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match &value {
+            Value::String(mode) => match mode.as_str() {
+                "auto" => Ok(Self::Auto),
+                "none" => Ok(Self::None),
+                "required" => Ok(Self::Required),
+                _ => Err(format!("unknown tool_choice `{mode}`")),
+            },
+            _ => value["function"]["name"]
+                .as_str()
+                .map(Self::function)
+                .ok_or_else(|| format!("invalid tool_choice: {value}")),
+        }
+    }
 }
 
 fn is_false(value: &bool) -> bool {
@@ -309,4 +407,23 @@ mod test {
         println!("{:?}", parsed);
         assert_eq!(parsed.get("xxxxx").and_then(|v| v.as_i64()), Some(42));
     }
+
+    #[test]
+    fn tool_choice_round_trips_in_openai_format() {
+        use super::*;
+        let cases = [
+            (ToolChoice::Auto, serde_json::json!("auto")),
+            (ToolChoice::None, serde_json::json!("none")),
+            (ToolChoice::Required, serde_json::json!("required")),
+            (
+                ToolChoice::function("search"),
+                serde_json::json!({"type": "function", "function": {"name": "search"}}),
+            ),
+        ];
+        for (choice, json) in cases {
+            assert_eq!(serde_json::to_value(&choice).unwrap(), json);
+            assert_eq!(serde_json::from_value::<ToolChoice>(json).unwrap(), choice);
+        }
+        assert!(serde_json::from_value::<ToolChoice>(serde_json::json!("always")).is_err());
+    }
 }
@@ -0,0 +1,64 @@
+//! 文档重排序接口
+//!
+//! [`Reranker`] 按与查询的相关性为检索到的文档打分并重新排序，通常放在向量检索之后，
+//! 与 [`Embedder`](crate::embeddings::Embedder) 一起组成检索增强生成（RAG）的流程。
+
+use async_trait::async_trait;
+
+use crate::error::ModelError;
+
+/// 文档重排序模型
+///
+/// 返回 `(原始下标, 相关性分数)`，按分数从高到低排列。调用方用下标取回自己的文档对象，
+/// 不需要把元数据传给重排序模型。返回的条目数可以少于输入（例如只保留前 N 条）。
+#[async_trait]
+pub trait Reranker: Send + Sync {
+    async fn rerank(
+        &self,
+        query: &str,
+        documents: &[String],
+    ) -> Result<Vec<(usize, f32)>, ModelError>;
+}
+
+/// 不调用任何模型，保持原始顺序
+///
+/// 分数从 1.0 开始按名次递减，用于测试或关闭重排序。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PassthroughReranker;
+
+#[async_trait]
+impl Reranker for PassthroughReranker {
+    async fn rerank(
+        &self,
+        _query: &str,
+        documents: &[String],
+    ) -> Result<Vec<(usize, f32)>, ModelError> {
+        let count = documents.len() as f32;
+        Ok((0..documents.len())
+            .map(|index| (index, 1.0 - index as f32 / count))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn passthrough_keeps_order_with_descending_scores() {
+        let documents = vec!["a".to_owned(), "b".to_owned(), "c".to_owned()];
+
+        let ranked = PassthroughReranker.rerank("q", &documents).await.unwrap();
+
+        let indices: Vec<_> = ranked.iter().map(|(index, _)| *index).collect();
+        assert_eq!(indices, [0, 1, 2]);
+        assert!(ranked.windows(2).all(|pair| pair[0].1 > pair[1].1));
+        assert!(
+            PassthroughReranker
+                .rerank("q", &[])
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -1,8 +1,20 @@
-//! 暂时使用的是OpenAI的标准，后面对接其他标准再进行合并配置
+//! 模型响应的结构与规范化
+//!
+//! [`ResponseBody`] 是 OpenAI 标准的响应体。不同提供方表示工具调用的方式不同（OpenAI 的
+//! `tool_calls`、Anthropic 的 `tool_use` 内容块等），[`ResponseNormalizer`] 把它们的原始响应
+//! 统一转换为 `Message::Assistant { tool_calls, .. }`，`ToolNode` 和路由因此不需要关心提供方。
+//! 接入新的提供方时实现该 trait 即可。
 
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-use crate::message::Message;
+use crate::{
+    error::ValidationError,
+    message::{Message, Metadata, ToolCall},
+};
+
+/// 附加信息中保存提供方原始响应的键，见 [`NormalizedResponse::with_raw`]
+pub const RAW_RESPONSE_METADATA_KEY: &str = "raw_response";
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ResponseBody {
@@ -12,6 +24,9 @@ pub struct ResponseBody {
     pub model: String,
     pub choices: Vec<Choice>,
     pub usage: Usage,
+    /// 服务端配置的指纹，变化时即使种子相同结果也可能不同
+    #[serde(default)]
+    pub system_fingerprint: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -29,7 +44,227 @@ pub struct Usage {
     pub completion_tokens_details: Option<TokensDetails>,
 }
 
+impl std::ops::AddAssign<&Usage> for Usage {
+    fn add_assign(&mut self, other: &Usage) {
+        self.prompt_tokens = self.prompt_tokens.saturating_add(other.prompt_tokens);
+        self.completion_tokens = self
+            .completion_tokens
+            .saturating_add(other.completion_tokens);
+        self.total_tokens = self.total_tokens.saturating_add(other.total_tokens);
+        if let Some(other) = &other.completion_tokens_details {
+            let details = self.completion_tokens_details.get_or_insert_default();
+            details.reasoning_tokens = details
+                .reasoning_tokens
+                .saturating_add(other.reasoning_tokens);
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct TokensDetails {
     pub reasoning_tokens: u32,
 }
+
+/// 规范化后的响应：每个候选对应一条助手消息
+#[derive(Debug, Clone, Default)]
+pub struct NormalizedResponse {
+    pub messages: Vec<Message>,
+    pub usage: Usage,
+    /// 结束原因，按提供方的原始取值保存
+    pub finish_reason: Option<String>,
+}
+
+impl NormalizedResponse {
+    /// 把原始响应体写入每条消息的附加信息（键为 [`RAW_RESPONSE_METADATA_KEY`]），
+    /// 用于调试或读取规范格式中没有的字段
+    pub fn with_raw(mut self, body: &Value) -> Self {
+        for message in &mut self.messages {
+            message
+                .metadata_mut()
+                .insert(RAW_RESPONSE_METADATA_KEY.to_owned(), body.clone());
+        }
+        self
+    }
+}
+
+/// 把某个提供方的原始响应体转换为规范的消息
+pub trait ResponseNormalizer: Send + Sync {
+    fn normalize(&self, body: &Value) -> Result<NormalizedResponse, ValidationError>;
+}
+
+/// OpenAI Chat Completions 格式：`choices[].message`，工具调用在 `tool_calls` 中，参数为 JSON 字符串
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OpenAINormalizer;
+
+impl ResponseNormalizer for OpenAINormalizer {
+    fn normalize(&self, body: &Value) -> Result<NormalizedResponse, ValidationError> {
+        let choices = body["choices"]
+            .as_array()
+            .ok_or_else(|| ValidationError::MissingField("choices".to_owned()))?;
+        let messages = choices
+            .iter()
+            .map(|choice| {
+                let mut message = choice["message"].clone();
+                // 部分兼容接口省略了 role
+                if message.is_object() && message.get("role").is_none() {
+                    message["role"] = Value::from("assistant");
+                }
+                Message::from_openai(&message)
+            })
+            .collect::<Result<_, _>>()?;
+        let usage = match &body["usage"] {
+            Value::Null => Usage::default(),
+            usage => serde_json::from_value(usage.clone()).map_err(|e| {
+                ValidationError::InvalidFormat(format!("invalid usage in response: {e}"))
+            })?,
+        };
+        Ok(NormalizedResponse {
+            messages,
+            usage,
+            finish_reason: choices
+                .first()
+                .and_then(|choice| choice["finish_reason"].as_str())
+                .map(str::to_owned),
+        })
+    }
+}
+
+/// Anthropic Messages 格式：`content` 为内容块数组，工具调用是 `tool_use` 块，参数为 JSON 对象；
+/// `thinking` 块作为思考内容
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AnthropicNormalizer;
+
+impl ResponseNormalizer for AnthropicNormalizer {
+    fn normalize(&self, body: &Value) -> Result<NormalizedResponse, ValidationError> {
+        let blocks = body["content"]
+            .as_array()
+            .ok_or_else(|| ValidationError::MissingField("content".to_owned()))?;
+        let mut content = String::new();
+        let mut reasoning: Option<String> = None;
+        let mut tool_calls = Vec::new();
+        for block in blocks {
+            match block["type"].as_str() {
+                Some("text") => content.push_str(block["text"].as_str().unwrap_or_default()),
+                Some("thinking") => reasoning
+                    .get_or_insert_default()
+                    .push_str(block["thinking"].as_str().unwrap_or_default()),
+                Some("tool_use") => {
+                    let field = |name: &str| {
+                        block[name].as_str().ok_or_else(|| {
+                            ValidationError::MissingField(format!("tool_use.{name}"))
+                        })
+                    };
+                    tool_calls.push(ToolCall::from_raw(
+                        field("id")?,
+                        field("name")?,
+                        block.get("input"),
+                    ));
+                }
+                // 其余内容块（如 redacted_thinking）没有对应的规范字段
+                _ => {}
+            }
+        }
+        let usage = &body["usage"];
+        let prompt_tokens = usage["input_tokens"].as_u64().unwrap_or_default() as u32;
+        let completion_tokens = usage["output_tokens"].as_u64().unwrap_or_default() as u32;
+        Ok(NormalizedResponse {
+            messages: vec![Message::Assistant {
+                content,
+                reasoning_content: reasoning,
+                tool_calls: (!tool_calls.is_empty()).then_some(tool_calls),
+                name: None,
+                metadata: Metadata::new(),
+            }],
+            usage: Usage {
+                prompt_tokens,
+                completion_tokens,
+                total_tokens: prompt_tokens.saturating_add(completion_tokens),
+                completion_tokens_details: None,
+            },
+            finish_reason: body["stop_reason"].as_str().map(str::to_owned),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn providers_normalize_to_the_same_tool_calls() {
+        let openai = json!({
+            "choices": [{
+                "index": 0,
+                "message": {
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [{
+                        "id": "call_1",
+                        "type": "function",
+                        "function": {"name": "get_weather", "arguments": "{\"city\":\"Paris\"}"}
+                    }]
+                },
+                "finish_reason": "tool_calls"
+            }],
+            "usage": {"prompt_tokens": 10, "completion_tokens": 5, "total_tokens": 15}
+        });
+        let anthropic = json!({
+            "role": "assistant",
+            "content": [
+                {"type": "thinking", "thinking": "Need the weather."},
+                {"type": "tool_use", "id": "call_1", "name": "get_weather", "input": {"city": "Paris"}}
+            ],
+            "stop_reason": "tool_use",
+            "usage": {"input_tokens": 10, "output_tokens": 5}
+        });
+
+        let openai = OpenAINormalizer.normalize(&openai).unwrap();
+        let anthropic = AnthropicNormalizer.normalize(&anthropic).unwrap();
+        let (Message::Assistant { tool_calls: a, .. }, Message::Assistant { tool_calls: b, .. }) =
+            (&openai.messages[0], &anthropic.messages[0])
+        else {
+            panic!("expected assistant messages");
+        };
+        assert_eq!(a, b);
+        assert_eq!(
+            b.as_ref().unwrap()[0].arguments().unwrap(),
+            json!({"city": "Paris"})
+        );
+        assert_eq!(openai.usage.total_tokens, anthropic.usage.total_tokens);
+        assert_eq!(openai.finish_reason.as_deref(), Some("tool_calls"));
+        assert_eq!(anthropic.finish_reason.as_deref(), Some("tool_use"));
+        let Message::Assistant {
+            reasoning_content, ..
+        } = &anthropic.messages[0]
+        else {
+            unreachable!()
+        };
+        assert_eq!(reasoning_content.as_deref(), Some("Need the weather."));
+    }
+
+    #[test]
+    fn raw_payload_is_kept_only_on_request() {
+        let body = json!({
+            "content": [{"type": "text", "text": "Hi"}, {"type": "tool_use", "id": "t", "name": "ping", "input": {}}]
+        });
+        let response = AnthropicNormalizer.normalize(&body).unwrap();
+        assert!(response.messages[0].metadata().is_empty());
+        assert_eq!(response.messages[0].content(), "Hi");
+
+        let response = response.with_raw(&body);
+        assert_eq!(
+            response.messages[0].metadata()[RAW_RESPONSE_METADATA_KEY],
+            body
+        );
+    }
+
+    #[test]
+    fn empty_string_arguments_mean_no_arguments() {
+        let call = ToolCall::from_raw("call_1", "now", Some(&json!("")));
+        assert_eq!(call.arguments().unwrap(), json!({}));
+        let call = ToolCall::from_raw("call_1", "echo", Some(&json!("not json")));
+        assert!(call.arguments().is_err());
+    }
+}
@@ -0,0 +1,494 @@
+//! 工具参数的 JSON Schema 校验
+//!
+//! 只实现工具参数中常用的一部分关键字：`type`、`enum`、`const`、`properties`、`required`、
+//! `additionalProperties`、`items`、长度与数值范围、`anyOf`/`oneOf`/`allOf`，以及指向
+//! 同一文档内 `definitions`/`$defs` 的 `$ref`（schemars 生成的 schema 会用到）。
+//! 不认识的关键字直接忽略，因此校验只会比完整实现更宽松。
+//!
+//! [`coerce`] 按同一份 schema 把模型给出的宽松类型（`"5"`、`"true"`）转换为期望的类型。
+
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+/// 展开 `$ref` 的最大深度，防止循环引用
+const MAX_DEPTH: usize = 32;
+
+/// 第一个不满足 schema 的位置
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("`{path}`: {message}")]
+pub struct SchemaViolation {
+    /// 出错的位置，例如 `$.items[0].name`
+    pub path: String,
+    pub message: String,
+}
+
+/// 校验 `value` 是否满足 `schema`，返回遇到的第一个错误
+pub fn validate(schema: &Value, value: &Value) -> Result<(), SchemaViolation> {
+    Validator { root: schema }.check(schema, value, "$", 0)
+}
+
+/// 按 schema 把字符串形式的数字、布尔值和 JSON 数组/对象转换为期望的类型
+///
+/// 只在值的类型不满足 schema 且转换后满足时才修改，返回被转换的位置（例如 `$.days`）。
+/// 无法转换的值保持原样，交给 [`validate`] 或工具自身报错。
+pub fn coerce(schema: &Value, value: &mut Value) -> Vec<String> {
+    let mut coerced = Vec::new();
+    Validator { root: schema }.coerce(schema, value, "$", 0, &mut coerced);
+    coerced
+}
+
+struct Validator<'a> {
+    root: &'a Value,
+}
+
+fn violation(path: &str, message: impl Into<String>) -> SchemaViolation {
+    SchemaViolation {
+        path: path.to_owned(),
+        message: message.into(),
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn matches_type(expected: &str, value: &Value) -> bool {
+    match expected {
+        "number" => value.is_number(),
+        // 2.0 这样没有小数部分的浮点数也算整数
+        "integer" => value.as_f64().is_some_and(|n| n.fract() == 0.0),
+        other => type_name(value) == other,
+    }
+}
+
+impl Validator<'_> {
+    fn coerce(
+        &self,
+        schema: &Value,
+        value: &mut Value,
+        path: &str,
+        depth: usize,
+        coerced: &mut Vec<String>,
+    ) {
+        let Some(object) = schema.as_object() else {
+            return;
+        };
+        if depth > MAX_DEPTH {
+            return;
+        }
+        if let Some(target) = object
+            .get("$ref")
+            .and_then(Value::as_str)
+            .and_then(|reference| reference.strip_prefix('#'))
+            .and_then(|pointer| self.root.pointer(pointer))
+        {
+            self.coerce(target, value, path, depth + 1, coerced);
+        }
+
+        let expected: Vec<&str> = match object.get("type") {
+            Some(Value::String(t)) => vec![t.as_str()],
+            Some(Value::Array(types)) => types.iter().filter_map(Value::as_str).collect(),
+            _ => vec![],
+        };
+        // 字符串只满足 `string` 类型，允许字符串时不转换
+        if let Value::String(s) = &*value
+            && !expected.is_empty()
+            && !expected.contains(&"string")
+            && let Some(converted) = expected.iter().find_map(|t| coerce_string(t, s))
+        {
+            *value = converted;
+            coerced.push(path.to_owned());
+        }
+
+        match value {
+            Value::Object(fields) => {
+                let properties = object.get("properties").and_then(Value::as_object);
+                for (key, field) in fields.iter_mut() {
+                    if let Some(sub) = properties.and_then(|p| p.get(key)) {
+                        self.coerce(sub, field, &format!("{path}.{key}"), depth + 1, coerced);
+                    }
+                }
+            }
+            Value::Array(items) => {
+                if let Some(item_schema) = object.get("items") {
+                    for (index, item) in items.iter_mut().enumerate() {
+                        let item_path = format!("{path}[{index}]");
+                        self.coerce(item_schema, item, &item_path, depth + 1, coerced);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn check(
+        &self,
+        schema: &Value,
+        value: &Value,
+        path: &str,
+        depth: usize,
+    ) -> Result<(), SchemaViolation> {
+        let Some(schema) = schema.as_object() else {
+            // `true` 或空 schema 接受任何值，`false` 拒绝任何值
+            return match schema {
+                Value::Bool(false) => Err(violation(path, "no value is allowed here")),
+                _ => Ok(()),
+            };
+        };
+        if depth > MAX_DEPTH {
+            return Ok(());
+        }
+
+        if let Some(target) = schema
+            .get("$ref")
+            .and_then(Value::as_str)
+            .and_then(|reference| reference.strip_prefix('#'))
+            .and_then(|pointer| self.root.pointer(pointer))
+        {
+            self.check(target, value, path, depth + 1)?;
+        }
+
+        self.check_type(schema, value, path)?;
+        self.check_combinators(schema, value, path, depth)?;
+
+        match value {
+            Value::Object(object) => self.check_object(schema, object, path, depth),
+            Value::Array(items) => self.check_array(schema, items, path, depth),
+            Value::String(s) => check_string(schema, s, path),
+            Value::Number(_) => check_number(schema, value, path),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_type(
+        &self,
+        schema: &Map<String, Value>,
+        value: &Value,
+        path: &str,
+    ) -> Result<(), SchemaViolation> {
+        let expected: Vec<&str> = match schema.get("type") {
+            Some(Value::String(t)) => vec![t.as_str()],
+            Some(Value::Array(types)) => types.iter().filter_map(Value::as_str).collect(),
+            _ => vec![],
+        };
+        if !expected.is_empty() && !expected.iter().any(|t| matches_type(t, value)) {
+            return Err(violation(
+                path,
+                format!(
+                    "expected {}, got {}",
+                    expected.join(" or "),
+                    type_name(value)
+                ),
+            ));
+        }
+        if let Some(allowed) = schema.get("enum").and_then(Value::as_array)
+            && !allowed.contains(value)
+        {
+            let allowed: Vec<String> = allowed.iter().map(Value::to_string).collect();
+            return Err(violation(
+                path,
+                format!("expected one of {}, got {value}", allowed.join(", ")),
+            ));
+        }
+        if let Some(constant) = schema.get("const")
+            && constant != value
+        {
+            return Err(violation(path, format!("expected {constant}, got {value}")));
+        }
+        Ok(())
+    }
+
+    fn check_combinators(
+        &self,
+        schema: &Map<String, Value>,
+        value: &Value,
+        path: &str,
+        depth: usize,
+    ) -> Result<(), SchemaViolation> {
+        for sub in schema
+            .get("allOf")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            self.check(sub, value, path, depth + 1)?;
+        }
+        for keyword in ["anyOf", "oneOf"] {
+            let Some(options) = schema.get(keyword).and_then(Value::as_array) else {
+                continue;
+            };
+            let mut first_error = None;
+            let passed = options
+                .iter()
+                .any(|sub| match self.check(sub, value, path, depth + 1) {
+                    Ok(()) => true,
+                    Err(e) => {
+                        first_error.get_or_insert(e);
+                        false
+                    }
+                });
+            if !passed && let Some(e) = first_error {
+                return Err(violation(
+                    path,
+                    format!("does not match any allowed schema ({})", e.message),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn check_object(
+        &self,
+        schema: &Map<String, Value>,
+        object: &Map<String, Value>,
+        path: &str,
+        depth: usize,
+    ) -> Result<(), SchemaViolation> {
+        for field in schema
+            .get("required")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+        {
+            if !object.contains_key(field) {
+                return Err(violation(
+                    &format!("{path}.{field}"),
+                    "required property is missing",
+                ));
+            }
+        }
+
+        let properties = schema.get("properties").and_then(Value::as_object);
+        for (key, value) in object {
+            let field_path = format!("{path}.{key}");
+            match properties.and_then(|p| p.get(key)) {
+                Some(sub) => self.check(sub, value, &field_path, depth + 1)?,
+                None => match schema.get("additionalProperties") {
+                    Some(Value::Bool(false)) => {
+                        return Err(violation(&field_path, "unknown property"));
+                    }
+                    Some(sub @ Value::Object(_)) => {
+                        self.check(sub, value, &field_path, depth + 1)?;
+                    }
+                    _ => {}
+                },
+            }
+        }
+        Ok(())
+    }
+
+    fn check_array(
+        &self,
+        schema: &Map<String, Value>,
+        items: &[Value],
+        path: &str,
+        depth: usize,
+    ) -> Result<(), SchemaViolation> {
+        if let Some(min) = schema.get("minItems").and_then(Value::as_u64)
+            && (items.len() as u64) < min
+        {
+            return Err(violation(path, format!("expected at least {min} items")));
+        }
+        if let Some(max) = schema.get("maxItems").and_then(Value::as_u64)
+            && (items.len() as u64) > max
+        {
+            return Err(violation(path, format!("expected at most {max} items")));
+        }
+        if let Some(item_schema) = schema.get("items") {
+            for (index, item) in items.iter().enumerate() {
+                self.check(item_schema, item, &format!("{path}[{index}]"), depth + 1)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 把字符串转换为 `expected` 类型的值，不能转换时返回 `None`
+fn coerce_string(expected: &str, s: &str) -> Option<Value> {
+    let trimmed = s.trim();
+    match expected {
+        "integer" => trimmed
+            .parse::<i64>()
+            .ok()
+            .map(Value::from)
+            .or_else(|| trimmed.parse::<u64>().ok().map(Value::from)),
+        "number" => trimmed.parse::<i64>().ok().map(Value::from).or_else(|| {
+            trimmed
+                .parse::<f64>()
+                .ok()
+                .filter(|n| n.is_finite())
+                .map(Value::from)
+        }),
+        "boolean" => match trimmed.to_ascii_lowercase().as_str() {
+            "true" => Some(Value::Bool(true)),
+            "false" => Some(Value::Bool(false)),
+            _ => None,
+        },
+        "array" | "object" => serde_json::from_str::<Value>(trimmed)
+            .ok()
+            .filter(|parsed| type_name(parsed) == expected),
+        "null" if trimmed == "null" => Some(Value::Null),
+        _ => None,
+    }
+}
+
+fn check_string(schema: &Map<String, Value>, s: &str, path: &str) -> Result<(), SchemaViolation> {
+    let len = s.chars().count() as u64;
+    if let Some(min) = schema.get("minLength").and_then(Value::as_u64)
+        && len < min
+    {
+        return Err(violation(
+            path,
+            format!("expected at least {min} characters"),
+        ));
+    }
+    if let Some(max) = schema.get("maxLength").and_then(Value::as_u64)
+        && len > max
+    {
+        return Err(violation(
+            path,
+            format!("expected at most {max} characters"),
+        ));
+    }
+    Ok(())
+}
+
+fn check_number(
+    schema: &Map<String, Value>,
+    value: &Value,
+    path: &str,
+) -> Result<(), SchemaViolation> {
+    let Some(n) = value.as_f64() else {
+        return Ok(());
+    };
+    let bound = |key: &str| schema.get(key).and_then(Value::as_f64);
+    if let Some(min) = bound("minimum")
+        && n < min
+    {
+        return Err(violation(
+            path,
+            format!("expected a value >= {min}, got {value}"),
+        ));
+    }
+    if let Some(max) = bound("maximum")
+        && n > max
+    {
+        return Err(violation(
+            path,
+            format!("expected a value <= {max}, got {value}"),
+        ));
+    }
+    if let Some(min) = bound("exclusiveMinimum")
+        && n <= min
+    {
+        return Err(violation(
+            path,
+            format!("expected a value > {min}, got {value}"),
+        ));
+    }
+    if let Some(max) = bound("exclusiveMaximum")
+        && n >= max
+    {
+        return Err(violation(
+            path,
+            format!("expected a value < {max}, got {value}"),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn weather_schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "city": {"type": "string", "minLength": 1},
+                "days": {"type": "integer", "minimum": 1, "maximum": 7},
+                "unit": {"$ref": "#/definitions/Unit"},
+                "tags": {"type": "array", "items": {"type": "string"}},
+                "note": {"type": ["string", "null"]}
+            },
+            "required": ["city"],
+            "additionalProperties": false,
+            "definitions": {"Unit": {"type": "string", "enum": ["c", "f"]}}
+        })
+    }
+
+    #[test]
+    fn accepts_valid_arguments() {
+        let schema = weather_schema();
+
+        assert!(validate(&schema, &json!({"city": "Paris"})).is_ok());
+        assert!(
+            validate(
+                &schema,
+                &json!({"city": "Paris", "days": 3.0, "unit": "c", "tags": ["a"], "note": null})
+            )
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn reports_the_violated_field() {
+        let schema = weather_schema();
+        let path = |args: Value| validate(&schema, &args).unwrap_err().path;
+
+        assert_eq!(path(json!({})), "$.city");
+        assert_eq!(path(json!({"city": 1})), "$.city");
+        assert_eq!(path(json!({"city": "P", "days": 9})), "$.days");
+        assert_eq!(path(json!({"city": "P", "days": 1.5})), "$.days");
+        assert_eq!(path(json!({"city": "P", "unit": "k"})), "$.unit");
+        assert_eq!(path(json!({"city": "P", "tags": ["a", 2]})), "$.tags[1]");
+        assert_eq!(path(json!({"city": "P", "extra": 1})), "$.extra");
+        assert_eq!(path(json!("Paris")), "$");
+    }
+
+    #[test]
+    fn coerces_loosely_typed_arguments() {
+        let schema = weather_schema();
+        let mut args = json!({
+            "city": "42",
+            "days": " 3 ",
+            "tags": "[\"a\", \"b\"]",
+            "note": "null"
+        });
+
+        let coerced = coerce(&schema, &mut args);
+
+        // 允许字符串的字段保持原样
+        assert_eq!(coerced, ["$.days", "$.tags"]);
+        assert_eq!(
+            args,
+            json!({"city": "42", "days": 3, "tags": ["a", "b"], "note": "null"})
+        );
+        assert!(validate(&schema, &args).is_ok());
+
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "flag": {"type": "boolean"},
+                "ratio": {"type": "number"},
+                "ids": {"type": "array", "items": {"type": "integer"}}
+            }
+        });
+        let mut args = json!({"flag": "TRUE", "ratio": "0.5", "ids": ["1", 2, "x"]});
+        coerce(&schema, &mut args);
+        assert_eq!(
+            args,
+            json!({"flag": true, "ratio": 0.5, "ids": [1, 2, "x"]})
+        );
+    }
+}
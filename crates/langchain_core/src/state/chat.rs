@@ -1,14 +1,20 @@
 use async_trait::async_trait;
 use futures_core::Stream;
 use im::Vector;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
 use std::pin::Pin;
 use std::sync::Arc;
 
 use crate::{
-    error::ModelError,
-    message::{Message, ToolCall},
-    request::{ResponseFormat, ToolSpec},
+    error::{ModelError, ValidationError},
+    message::{Content, Message, Role, ToolCall},
+    parsers::ParseError,
+    request::{ResponseFormat, ToolChoice, ToolSpec},
     response::Usage,
+    tokenizer::{HeuristicTokenizer, Tokenizer},
 };
 
 /// LLM 调用选项
@@ -24,16 +30,30 @@ pub struct InvokeOptions<'a> {
     pub top_p: Option<f32>,
     /// 停止序列
     pub stop: Option<&'a [String]>,
+    /// 频率惩罚
+    pub frequency_penalty: Option<f32>,
+    /// 存在惩罚
+    pub presence_penalty: Option<f32>,
     /// 响应格式
     pub response_format: Option<&'a ResponseFormat>,
-    /// 工具选择 (e.g. "auto", "none", "required", or specific function name)
-    pub tool_choice: Option<String>,
+    /// 工具选择；不支持该参数的模型实现忽略它
+    pub tool_choice: Option<ToolChoice>,
 }
 
-#[derive(Debug, Clone, Default)]
+/// 结构化输出的运行结果
+///
+/// 解析失败不会丢弃运行结果：`raw` 保留模型的原始输出，`parse_error` 说明失败原因，
+/// 便于排查模型实际返回了什么。
+#[derive(Debug, Default)]
 pub struct AgentState<State, Output> {
+    /// 运行结束时的完整状态
     pub state: State,
+    /// 解析成功时的结构化输出
     pub struct_output: Option<Output>,
+    /// 用于解析的原始文本（最后一条助手消息的内容）
+    pub raw: String,
+    /// 解析失败的原因
+    pub parse_error: Option<ParseError>,
 }
 
 impl<State, Output> AgentState<State, Output> {
@@ -41,14 +61,114 @@ impl<State, Output> AgentState<State, Output> {
         Self {
             state,
             struct_output,
+            raw: String::new(),
+            parse_error: None,
+        }
+    }
+
+    /// 解析 `raw` 并记录结果，空文本视为 [`ParseError::EmptyOutput`]
+    pub fn parse(
+        state: State,
+        raw: String,
+        parse: impl FnOnce(&str) -> Result<Output, ParseError>,
+    ) -> Self {
+        let result = if raw.trim().is_empty() {
+            Err(ParseError::EmptyOutput)
+        } else {
+            parse(&raw)
+        };
+        let (struct_output, parse_error) = match result {
+            Ok(output) => (Some(output), None),
+            Err(e) => (None, Some(e)),
+        };
+        Self {
+            state,
+            struct_output,
+            raw,
+            parse_error,
+        }
+    }
+
+    /// 取出结构化输出，解析失败时返回解析错误
+    pub fn into_output(self) -> Result<Output, ParseError> {
+        match (self.struct_output, self.parse_error) {
+            (Some(output), _) => Ok(output),
+            (None, Some(e)) => Err(e),
+            (None, None) => Err(ParseError::EmptyOutput),
         }
     }
 }
 
+/// 对话状态
+///
+/// 消息保存在持久化的 `im::Vector` 中，克隆时各快照共享消息，不会逐条复制，
+/// 因此图运行时每步的状态克隆（例如保存 checkpoint）开销与历史长度基本无关。
 #[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
 pub struct MessagesState {
     pub messages: Vector<Arc<Message>>,
     pub llm_calls: u32,
+    /// 对已有消息的替换：(下标, 新消息)
+    ///
+    /// 只在节点返回的更新中使用，由 [`MessagesState::apply`] 合并，不会持久化。
+    #[serde(skip)]
+    pub replacements: Vec<(usize, Arc<Message>)>,
+    /// 本次运行累计的 token 用量，不持久化
+    #[serde(skip)]
+    pub usage: Usage,
+    /// 中间件钩子请求的跳转，只对紧接着的路由生效，不持久化
+    #[serde(skip)]
+    pub jump_to: Option<JumpTo>,
+}
+
+/// 把节点返回的更新合并到状态中的方式，用作图的 reducer
+///
+/// ```rust,ignore
+/// let reducer = MessagesReducer::Window(20);
+/// let graph = StateGraph::new(entry, move |state, update| reducer.reduce(state, update));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MessagesReducer {
+    /// 追加新消息，见 [`MessagesState::apply`]
+    #[default]
+    Append,
+    /// 更新中有新消息时，用它们替换状态中的最后一条消息，例如只保留最新的一版草稿
+    ReplaceLast,
+    /// 追加后只保留开头的系统消息和最近的若干条消息，见 [`MessagesState::keep_window`]
+    Window(usize),
+}
+
+impl MessagesReducer {
+    pub fn reduce(self, state: &mut MessagesState, update: MessagesState) {
+        match self {
+            Self::Append => state.apply(update),
+            Self::ReplaceLast => {
+                let last = state.messages.len().checked_sub(1);
+                let replaces = !update.messages.is_empty();
+                state.apply(update);
+                if let Some(last) = last.filter(|_| replaces) {
+                    state.messages.remove(last);
+                }
+            }
+            Self::Window(window) => {
+                state.apply(update);
+                state.keep_window(window);
+            }
+        }
+    }
+}
+
+/// 中间件钩子可以请求的跳转
+///
+/// 钩子在返回的更新中调用 [`MessagesState::jump`] 即可改变后续流程，
+/// 例如缓存命中时直接给出回复而不调用模型，或者拒绝请求并结束运行。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JumpTo {
+    /// 跳过剩余的模型调用前钩子和模型调用，视为模型已经给出了最后一条消息：
+    /// 继续执行模型调用后的钩子，再根据是否有工具调用决定执行工具或结束。
+    /// 只对 `before_agent` / `before_model` 钩子有效
+    SkipModel,
+    /// 跳过剩余流程，直接执行 `after_agent` 钩子并结束运行
+    End,
 }
 
 impl MessagesState {
@@ -56,9 +176,35 @@ impl MessagesState {
         Self {
             messages: messages.into_iter().map(Arc::new).collect(),
             llm_calls: 0,
+            replacements: Vec::new(),
+            usage: Usage::default(),
+            jump_to: None,
         }
     }
 
+    /// 导出为 OpenAI Chat Completions 格式的 `messages` 数组，见 [`Message::to_openai`]
+    pub fn to_openai_messages(&self) -> serde_json::Value {
+        serde_json::Value::Array(self.messages.iter().map(|m| m.to_openai()).collect())
+    }
+
+    /// 从 OpenAI Chat Completions 格式的 `messages` 数组创建状态，见 [`Message::from_openai`]
+    ///
+    /// 出错时的错误信息包含出错消息的下标。
+    pub fn from_openai_messages(messages: &serde_json::Value) -> Result<Self, ValidationError> {
+        let items = messages.as_array().ok_or_else(|| {
+            ValidationError::InvalidFormat("expected an array of messages".to_owned())
+        })?;
+        let messages = items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| {
+                Message::from_openai(item)
+                    .map_err(|e| ValidationError::InvalidInput(format!("message #{index}: {e}")))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self::new(messages))
+    }
+
     pub fn push_message(&mut self, message: Arc<Message>) {
         self.messages.push_back(message);
     }
@@ -77,6 +223,104 @@ impl MessagesState {
         self.messages.extend(messages.into_iter().map(Arc::new));
     }
 
+    /// 在更新中记录一次替换，合并时用 `message` 替换下标为 `index` 的消息
+    pub fn replace_message(&mut self, index: usize, message: Message) {
+        self.replacements.push((index, Arc::new(message)));
+    }
+
+    /// 合并节点返回的更新
+    ///
+    /// 先按下标替换已有消息（越界的替换会被忽略），再追加新消息，并累加模型调用次数和 token 用量。
+    /// 跳转请求只保留最新一次更新中的值。
+    pub fn apply(&mut self, update: MessagesState) {
+        for (index, message) in update.replacements {
+            if index < self.messages.len() {
+                self.messages.set(index, message);
+            } else {
+                tracing::warn!("ignoring replacement of missing message #{index}");
+            }
+        }
+        if !update.messages.is_empty() {
+            self.append_messages(update.messages);
+        }
+        self.llm_calls += update.llm_calls;
+        self.usage += &update.usage;
+        self.jump_to = update.jump_to;
+    }
+
+    /// 只保留开头的系统消息和最近的 `window` 条消息
+    ///
+    /// 窗口不以工具结果开头：被截断的工具调用对应的结果一并丢弃，避免留下孤立的工具消息。
+    pub fn keep_window(&mut self, window: usize) {
+        let system = self
+            .messages
+            .iter()
+            .take_while(|m| matches!(m.as_ref(), Message::System { .. }))
+            .count();
+        let len = self.messages.len();
+        if len - system <= window {
+            return;
+        }
+        let mut start = len - window;
+        while start < len && matches!(self.messages[start].as_ref(), Message::Tool { .. }) {
+            start += 1;
+        }
+        let mut kept = self.messages.take(system);
+        kept.append(self.messages.skip(start));
+        self.messages = kept;
+    }
+
+    /// 整理消息，使其满足多数模型服务的格式要求
+    ///
+    /// - 所有系统消息合并为一条并移到最前面
+    /// - 相邻且完全相同的消息只保留一条
+    /// - 相邻的同角色纯文本消息（名称相同、不含工具调用）合并为一条，内容以空行分隔
+    ///
+    /// 工具调用与工具结果不会被合并或重排。未改动的消息仍共享原来的 `Arc`。
+    pub fn normalize(&mut self) {
+        let mut system: Option<Arc<Message>> = None;
+        let mut rest: Vec<Arc<Message>> = Vec::with_capacity(self.messages.len());
+
+        for message in &self.messages {
+            if let Message::System { content, .. } = message.as_ref() {
+                match &mut system {
+                    Some(merged) if merged.content() != content => {
+                        let merged = Arc::make_mut(merged);
+                        if let Message::System {
+                            content: text,
+                            metadata,
+                            ..
+                        } = merged
+                        {
+                            text.push_str("\n\n");
+                            text.push_str(content);
+                            metadata.extend(message.metadata().clone());
+                        }
+                    }
+                    Some(_) => {}
+                    None => system = Some(message.clone()),
+                }
+                continue;
+            }
+
+            match rest.last_mut() {
+                Some(last) if last == message => {}
+                Some(last) => match merge_text(last, message) {
+                    Some(merged) => *last = Arc::new(merged),
+                    None => rest.push(message.clone()),
+                },
+                None => rest.push(message.clone()),
+            }
+        }
+
+        self.messages = system.into_iter().chain(rest).collect();
+    }
+
+    /// 在更新中请求跳转，见 [`JumpTo`]
+    pub fn jump(&mut self, to: JumpTo) {
+        self.jump_to = Some(to);
+    }
+
     pub fn increment_llm_calls(&mut self) {
         self.llm_calls = self.llm_calls.saturating_add(1);
     }
@@ -109,8 +353,248 @@ impl MessagesState {
             None => None,
         }
     }
+
+    /// 最近的 `n` 条消息，不足 `n` 条时返回全部
+    ///
+    /// 以下几个方法都直接借用 `messages`，不复制消息。
+    pub fn last_n(&self, n: usize) -> MessageRange<'_> {
+        let len = self.messages.len();
+        self.range(len.saturating_sub(n), len)
+    }
+
+    /// 从最后一条 `role` 角色的消息开始到末尾的消息，没有该角色的消息时为空
+    ///
+    /// 例如 `since_role(Role::User)` 是最近一次用户输入及之后模型和工具产生的消息。
+    pub fn since_role(&self, role: Role) -> MessageRange<'_> {
+        let len = self.messages.len();
+        let start = self
+            .messages
+            .iter()
+            .rposition(|m| m.role() == role)
+            .unwrap_or(len);
+        self.range(start, len)
+    }
+
+    /// 按轮次遍历对话
+    ///
+    /// 每轮从一条用户消息开始，包含之后的助手回复、工具调用和工具结果，直到下一条用户消息。
+    /// 第一条用户消息之前的消息（通常是系统提示词）单独作为第一轮。
+    pub fn iter_turns(&self) -> impl Iterator<Item = MessageRange<'_>> {
+        let starts: Vec<usize> = self
+            .messages
+            .iter()
+            .enumerate()
+            .filter(|(index, m)| *index == 0 || m.role() == Role::User)
+            .map(|(index, _)| index)
+            .collect();
+        let ends: Vec<usize> = starts
+            .iter()
+            .skip(1)
+            .copied()
+            .chain(std::iter::once(self.messages.len()))
+            .collect();
+        starts
+            .into_iter()
+            .zip(ends)
+            .map(|(start, end)| self.range(start, end))
+    }
+
+    // `Focus::narrow` 不接受空区间，这里用 skip/take 借用一段消息
+    fn range(&self, start: usize, end: usize) -> MessageRange<'_> {
+        self.messages.iter().skip(start).take(end - start)
+    }
+
+    /// 把整段对话渲染为可读文本，每条消息之间空一行，见 [`Message::render`]
+    pub fn transcript(&self) -> String {
+        self.render_transcript(None)
+    }
+
+    /// 与 [`transcript`](Self::transcript) 相同，但工具结果和调用参数最多保留 `max_len` 个字符
+    pub fn transcript_compact(&self, max_len: usize) -> String {
+        self.render_transcript(Some(max_len))
+    }
+
+    fn render_transcript(&self, max_len: Option<usize>) -> String {
+        self.messages
+            .iter()
+            .map(|message| message.render(max_len))
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+
+    /// 把所有消息追加写入 JSONL 文件，每行一条消息，文件不存在时创建
+    ///
+    /// 每条消息使用与 checkpoint 相同的 JSON 格式（包括附加信息），便于 grep 和整理评测数据。
+    /// 与 checkpointer 不同，这里只记录消息，不能用于恢复运行。
+    pub fn append_to_jsonl(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut buffer = Vec::new();
+        for message in &self.messages {
+            serde_json::to_writer(&mut buffer, message.as_ref())?;
+            buffer.push(b'\n');
+        }
+        // 一次写入，多个进程同时追加时各自的消息不会交错
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?
+            .write_all(&buffer)
+    }
+
+    /// 读取 [`append_to_jsonl`](Self::append_to_jsonl) 写入的文件
+    ///
+    /// 空行会被忽略；无法解析的行（例如写入中断留下的半行）跳过并输出警告。
+    pub fn from_jsonl(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let path = path.as_ref();
+        let reader = BufReader::new(File::open(path)?);
+        let mut messages = Vec::new();
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<Message>(&line) {
+                Ok(message) => messages.push(message),
+                Err(e) => tracing::warn!(
+                    "skipping invalid line {} in {}: {e}",
+                    index + 1,
+                    path.display()
+                ),
+            }
+        }
+        Ok(Self::new(messages))
+    }
+
+    /// 按执行顺序列出所有工具调用及其结果
+    ///
+    /// 助手消息中的 `tool_calls` 与之后的 `Message::Tool` 按 tool call id 配对，
+    /// 因此同一轮中的并行调用即使结果乱序返回也能正确对应。尚未返回结果的调用
+    /// `result` 为 `None`。
+    pub fn tool_steps(&self) -> Vec<ToolStep> {
+        collect_tool_steps(self.messages.iter())
+    }
+
+    /// 最后一条用户消息之后的工具调用及其结果
+    ///
+    /// 可用于判断最终回答是否基于失败的工具调用：
+    /// `state.last_turn_tool_steps().iter().any(|step| step.failed)`。
+    pub fn last_turn_tool_steps(&self) -> Vec<ToolStep> {
+        collect_tool_steps(self.since_role(Role::User))
+    }
+}
+
+fn collect_tool_steps<'a>(messages: impl Iterator<Item = &'a Arc<Message>>) -> Vec<ToolStep> {
+    let mut steps = Vec::new();
+    let mut pending: HashMap<&str, usize> = HashMap::new();
+
+    for message in messages {
+        match message.as_ref() {
+            Message::Assistant {
+                tool_calls: Some(calls),
+                ..
+            } => {
+                for call in calls {
+                    pending.insert(call.id.as_str(), steps.len());
+                    steps.push(ToolStep {
+                        id: call.id.clone(),
+                        name: call.function.name.clone(),
+                        arguments: call.function.arguments.clone(),
+                        result: None,
+                        failed: false,
+                    });
+                }
+            }
+            Message::Tool {
+                content,
+                tool_call_id,
+                metadata,
+            } => {
+                if let Some(index) = pending.remove(tool_call_id.as_str()) {
+                    steps[index].result = Some(content.clone());
+                    steps[index].failed = metadata
+                        .get(TOOL_FAILED_METADATA)
+                        .and_then(serde_json::Value::as_bool)
+                        .unwrap_or(false);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    steps
+}
+
+/// 借用 [`MessagesState::messages`] 中连续的一段消息，见 [`MessagesState::last_n`]
+pub type MessageRange<'a> = std::iter::Take<std::iter::Skip<im::vector::Iter<'a, Arc<Message>>>>;
+
+/// 合并两条相邻的同角色纯文本消息，不能合并时返回 `None`
+fn merge_text(first: &Message, second: &Message) -> Option<Message> {
+    let mergeable = match (first, second) {
+        (
+            Message::User {
+                content: Content::Text(_),
+                name: n1,
+                ..
+            },
+            Message::User {
+                content: Content::Text(_),
+                name: n2,
+                ..
+            },
+        ) => n1 == n2,
+        (
+            Message::Assistant {
+                reasoning_content: None,
+                tool_calls: None,
+                name: n1,
+                ..
+            },
+            Message::Assistant {
+                reasoning_content: None,
+                tool_calls: None,
+                name: n2,
+                ..
+            },
+        )
+        | (Message::Developer { name: n1, .. }, Message::Developer { name: n2, .. }) => n1 == n2,
+        _ => false,
+    };
+    if !mergeable {
+        return None;
+    }
+
+    let text = format!("{}\n\n{}", first.content(), second.content());
+    let mut merged = first.clone();
+    match &mut merged {
+        Message::User { content, .. } => *content = Content::Text(text),
+        Message::Assistant { content, .. } | Message::Developer { content, .. } => *content = text,
+        _ => {}
+    }
+    merged.metadata_mut().extend(second.metadata().clone());
+    Some(merged)
 }
 
+/// 一次工具调用及其结果，见 [`MessagesState::tool_steps`]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ToolStep {
+    /// tool call id
+    pub id: String,
+    /// 工具名称
+    pub name: String,
+    /// 调用参数
+    pub arguments: serde_json::Value,
+    /// 工具返回的内容，尚未返回时为 `None`
+    pub result: Option<String>,
+    /// 工具执行失败，`result` 是返回给模型的错误，见 [`TOOL_FAILED_METADATA`]
+    #[serde(default)]
+    pub failed: bool,
+}
+
+/// 工具结果消息的附加信息键：工具执行失败（未知工具、参数错误、工具返回错误等）时为 `true`
+///
+/// 模型看到的错误内容与成功的结果一样是普通文本，调用方可以据此区分，例如判断
+/// 最终回答是否基于部分失败的工具结果，见 [`MessagesState::last_turn_tool_steps`]。
+pub const TOOL_FAILED_METADATA: &str = "tool_failed";
+
 #[derive(Debug, Clone)]
 pub struct ChatCompletion {
     pub messages: Vec<Arc<Message>>,
@@ -120,7 +604,9 @@ pub struct ChatCompletion {
 #[derive(Debug, Clone)]
 pub enum ChatStreamEvent {
     Content(String),
-    ReasoningContent(String),
+    /// 推理模型（o1、DeepSeek-R1 等）单独输出的思考内容，汇总后写入助手消息的
+    /// `reasoning_content`，不参与工具调用的判断
+    Reasoning(String),
     ToolCallDelta {
         index: usize,
         id: Option<String>,
@@ -132,6 +618,17 @@ pub enum ChatStreamEvent {
         finish_reason: Option<String>,
         usage: Option<Usage>,
     },
+    /// 工具执行过程中推送的增量输出，`id` 为对应的工具调用 id
+    ToolChunk {
+        id: String,
+        name: String,
+        chunk: String,
+    },
+    /// 一轮工具调用全部结束，按调用顺序列出成功和失败的工具调用 id
+    ToolResults {
+        succeeded: Vec<String>,
+        failed: Vec<String>,
+    },
 }
 
 pub type ChatStream<E> = Pin<Box<dyn Stream<Item = Result<ChatStreamEvent, E>> + Send>>;
@@ -153,4 +650,290 @@ pub trait ChatModel: Send + Sync {
         messages: &[Arc<Message>],
         options: &InvokeOptions<'_>,
     ) -> Result<StandardChatStream, ModelError>;
+
+    /// 与模型一致的 token 计数器，默认按字符数估算
+    fn tokenizer(&self) -> Arc<dyn Tokenizer> {
+        Arc::new(HeuristicTokenizer)
+    }
+}
+
+/// 共享同一个模型实例，例如让多个节点或 Agent 使用同一个客户端
+#[async_trait]
+impl<M: ChatModel + ?Sized> ChatModel for Arc<M> {
+    async fn invoke(
+        &self,
+        messages: &[Arc<Message>],
+        options: &InvokeOptions<'_>,
+    ) -> Result<ChatCompletion, ModelError> {
+        (**self).invoke(messages, options).await
+    }
+
+    async fn stream(
+        &self,
+        messages: &[Arc<Message>],
+        options: &InvokeOptions<'_>,
+    ) -> Result<StandardChatStream, ModelError> {
+        (**self).stream(messages, options).await
+    }
+
+    fn tokenizer(&self) -> Arc<dyn Tokenizer> {
+        (**self).tokenizer()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(id: &str, name: &str) -> ToolCall {
+        ToolCall::new(id, name, serde_json::json!({ "q": id }))
+    }
+
+    fn contents(state: &MessagesState) -> Vec<&str> {
+        state.messages.iter().map(|m| m.content()).collect()
+    }
+
+    #[test]
+    fn reducers_append_replace_last_or_keep_a_window() {
+        let initial = MessagesState::new(vec![
+            Message::system("sys"),
+            Message::user("q1"),
+            Message::assistant("a1"),
+        ]);
+        let update = || MessagesState::new(vec![Message::user("q2"), Message::assistant("a2")]);
+
+        let mut state = initial.clone();
+        MessagesReducer::Append.reduce(&mut state, update());
+        assert_eq!(contents(&state), ["sys", "q1", "a1", "q2", "a2"]);
+
+        let mut state = initial.clone();
+        MessagesReducer::ReplaceLast.reduce(&mut state, update());
+        assert_eq!(contents(&state), ["sys", "q1", "q2", "a2"]);
+
+        let mut state = initial.clone();
+        MessagesReducer::Window(2).reduce(&mut state, update());
+        assert_eq!(contents(&state), ["sys", "q2", "a2"]);
+
+        // 窗口不从工具结果开始
+        let mut state = initial;
+        let mut update = MessagesState::new(vec![Message::assistant_with_tool_calls(
+            "",
+            vec![call("c1", "search")],
+        )]);
+        update.extend_messages_owned(vec![Message::tool("r", "c1"), Message::assistant("a2")]);
+        MessagesReducer::Window(2).reduce(&mut state, update);
+        assert_eq!(contents(&state), ["sys", "a2"]);
+    }
+
+    #[test]
+    fn window_accessors_slice_history_by_count_role_and_turn() {
+        let state = MessagesState::new(vec![
+            Message::system("sys"),
+            Message::user("weather?"),
+            Message::assistant_with_tool_calls("", vec![call("a", "weather"), call("b", "time")]),
+            Message::tool("sunny", "a"),
+            Message::tool("noon", "b"),
+            Message::assistant("sunny at noon"),
+            Message::user("thanks"),
+            Message::assistant("welcome"),
+        ]);
+
+        let last: Vec<_> = state.last_n(2).map(|m| m.content()).collect();
+        assert_eq!(last, ["thanks", "welcome"]);
+        assert_eq!(state.last_n(100).len(), 8);
+
+        let tools: Vec<_> = state.since_role(Role::Tool).map(|m| m.content()).collect();
+        assert_eq!(tools, ["noon", "sunny at noon", "thanks", "welcome"]);
+        assert_eq!(state.since_role(Role::Developer).len(), 0);
+
+        let turns: Vec<Vec<_>> = state
+            .iter_turns()
+            .map(|turn| turn.map(|m| m.role()).collect())
+            .collect();
+        assert_eq!(
+            turns,
+            [
+                vec![Role::System],
+                vec![
+                    Role::User,
+                    Role::Assistant,
+                    Role::Tool,
+                    Role::Tool,
+                    Role::Assistant
+                ],
+                vec![Role::User, Role::Assistant],
+            ]
+        );
+        assert_eq!(MessagesState::default().iter_turns().count(), 0);
+    }
+
+    #[test]
+    fn tool_steps_pairs_parallel_calls_by_id() {
+        let mut state = MessagesState::new(vec![
+            Message::user("weather?"),
+            Message::Assistant {
+                content: String::new(),
+                reasoning_content: None,
+                tool_calls: Some(vec![call("a", "weather"), call("b", "time")]),
+                name: None,
+                metadata: Default::default(),
+            },
+            // 结果乱序返回
+            Message::tool("12:00", "b"),
+            Message::tool("sunny", "a"),
+            Message::Assistant {
+                content: String::new(),
+                reasoning_content: None,
+                tool_calls: Some(vec![call("c", "weather")]),
+                name: None,
+                metadata: Default::default(),
+            },
+        ]);
+        state.push_message_owned(Message::assistant("done"));
+
+        let steps = state.tool_steps();
+
+        assert_eq!(steps.len(), 3);
+        assert_eq!(
+            (steps[0].name.as_str(), steps[0].result.as_deref()),
+            ("weather", Some("sunny"))
+        );
+        assert_eq!(
+            (steps[1].name.as_str(), steps[1].result.as_deref()),
+            ("time", Some("12:00"))
+        );
+        assert_eq!(steps[1].arguments, serde_json::json!({ "q": "b" }));
+        assert_eq!(steps[2].result, None);
+    }
+
+    #[test]
+    fn transcript_renders_roles_calls_and_results() {
+        let state = MessagesState::new(vec![
+            Message::system("be brief"),
+            Message::user("weather?"),
+            Message::assistant_with_tool_calls("", vec![call("a", "weather")]),
+            Message::tool("sunny and warm", "a"),
+            Message::assistant("It is sunny."),
+        ]);
+
+        assert_eq!(
+            state.transcript(),
+            "System: be brief\n\n\
+             User: weather?\n\n\
+             Assistant: \n  -> weather({\"q\":\"a\"}) [a]\n\n\
+             Tool [a]: sunny and warm\n\n\
+             Assistant: It is sunny."
+        );
+        assert!(
+            state
+                .transcript_compact(5)
+                .contains("Tool [a]: sunny… (9 more chars)")
+        );
+        assert_eq!(state.messages[1].to_string(), "User: weather?");
+    }
+
+    #[test]
+    fn strings_convert_to_user_messages() {
+        let message: Message = "hello".into();
+        assert!(matches!(message, Message::User { .. }));
+        assert_eq!(message.content(), "hello");
+        assert_eq!(Message::from(String::from("hi")).content(), "hi");
+    }
+
+    #[test]
+    fn normalize_hoists_system_and_merges_same_role_text() {
+        let mut state = MessagesState::new(vec![
+            Message::user("hi"),
+            Message::system("be brief"),
+            Message::user("hi"),
+            Message::user("what's the weather?"),
+            Message::system("answer in English"),
+            Message::assistant_with_tool_calls("", vec![call("a", "weather")]),
+            Message::tool("sunny", "a"),
+            Message::assistant("It is sunny."),
+            Message::assistant("Anything else?"),
+        ]);
+
+        state.normalize();
+
+        let roles: Vec<_> = state
+            .messages
+            .iter()
+            .map(|m| m.to_string().split([':', ' ']).next().unwrap().to_owned())
+            .collect();
+        assert_eq!(roles, ["System", "User", "Assistant", "Tool", "Assistant"]);
+        assert_eq!(state.messages[0].content(), "be brief\n\nanswer in English");
+        // 完全相同的相邻消息只保留一条，不同的合并
+        assert_eq!(state.messages[1].content(), "hi\n\nwhat's the weather?");
+        assert_eq!(
+            state.messages[4].content(),
+            "It is sunny.\n\nAnything else?"
+        );
+        assert_eq!(state.tool_steps()[0].result.as_deref(), Some("sunny"));
+    }
+
+    #[test]
+    fn normalize_keeps_well_formed_history_untouched() {
+        let mut state = MessagesState::new(vec![
+            Message::system("be brief"),
+            Message::user("hi"),
+            Message::assistant("hello"),
+            Message::user("hi"),
+        ]);
+        let before = state.messages.clone();
+
+        state.normalize();
+
+        assert_eq!(state.messages.len(), 4);
+        assert!(
+            before
+                .iter()
+                .zip(&state.messages)
+                .all(|(a, b)| Arc::ptr_eq(a, b))
+        );
+    }
+
+    #[test]
+    fn metadata_round_trips_and_is_omitted_when_empty() {
+        let plain = serde_json::to_value(Message::user("hi")).unwrap();
+        assert_eq!(plain, serde_json::json!({"role": "user", "content": "hi"}));
+
+        // 没有 metadata 字段的旧数据仍可反序列化
+        let old: Message = serde_json::from_value(plain).unwrap();
+        assert!(old.metadata().is_empty());
+
+        let cited =
+            Message::assistant("see [1]").with_metadata("sources", serde_json::json!(["doc-1"]));
+        let json = serde_json::to_string(&cited).unwrap();
+        let restored: Message = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, cited);
+        assert_eq!(restored.metadata()["sources"][0], "doc-1");
+    }
+
+    #[test]
+    fn jsonl_round_trip_skips_corrupt_lines() {
+        let path =
+            std::env::temp_dir().join(format!("langchain-transcript-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let first = MessagesState::new(vec![
+            Message::user("weather?"),
+            Message::assistant_with_tool_calls("", vec![call("1", "weather")]),
+        ]);
+        let second = MessagesState::new(vec![Message::tool("sunny", "1")]);
+
+        first.append_to_jsonl(&path).unwrap();
+        second.append_to_jsonl(&path).unwrap();
+        // 模拟写到一半中断的最后一行
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"{\"role\":\"assistant\",\"cont")
+            .unwrap();
+        let loaded = MessagesState::from_jsonl(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let expected: Vec<_> = first.messages.iter().chain(&second.messages).collect();
+        assert_eq!(loaded.messages.iter().collect::<Vec<_>>(), expected);
+    }
 }
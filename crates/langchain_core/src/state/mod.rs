@@ -1,5 +1,7 @@
 mod chat;
+mod registry;
 mod tool;
 
 pub use chat::*;
+pub use registry::*;
 pub use tool::*;
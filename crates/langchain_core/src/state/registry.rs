@@ -0,0 +1,124 @@
+use std::collections::BTreeMap;
+
+use crate::{error::ToolError, request::ToolSpec, state::RegisteredTool};
+
+/// 按名称管理的工具集合
+///
+/// 适合从插件等来源动态加载大量工具的应用：在一处注册、查找和移除工具，
+/// 再整体交给 Agent（见 `ReactAgentBuilder::with_registry`）。工具按名称排序。
+pub struct ToolRegistry<E> {
+    tools: BTreeMap<String, RegisteredTool<E>>,
+}
+
+impl<E> Default for ToolRegistry<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E> ToolRegistry<E> {
+    pub fn new() -> Self {
+        Self {
+            tools: BTreeMap::new(),
+        }
+    }
+
+    /// 注册工具，已有同名工具时返回 [`ToolError::AlreadyRegistered`]，不替换原有的工具
+    pub fn register(&mut self, tool: RegisteredTool<E>) -> Result<(), ToolError> {
+        let name = tool.function.name.clone();
+        if self.tools.contains_key(&name) {
+            return Err(ToolError::AlreadyRegistered(name));
+        }
+        self.tools.insert(name, tool);
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&RegisteredTool<E>> {
+        self.tools.get(name)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<RegisteredTool<E>> {
+        self.tools.remove(name)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.tools.contains_key(name)
+    }
+
+    /// 所有工具的名称
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.tools.keys().map(String::as_str)
+    }
+
+    /// 所有工具的定义
+    pub fn list_specs(&self) -> Vec<ToolSpec> {
+        self.tools
+            .values()
+            .map(|tool| ToolSpec::Function {
+                function: tool.function.clone(),
+            })
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tools.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
+}
+
+impl<E> IntoIterator for ToolRegistry<E> {
+    type Item = RegisteredTool<E>;
+    type IntoIter = std::collections::btree_map::IntoValues<String, RegisteredTool<E>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.tools.into_values()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use serde_json::json;
+
+    use super::*;
+
+    fn tool(name: &str, description: &str) -> RegisteredTool<ToolError> {
+        RegisteredTool::new(
+            name.to_owned(),
+            description.to_owned(),
+            json!({"type": "object"}),
+            Arc::new(|args| Box::pin(async move { Ok(args) })),
+        )
+    }
+
+    #[test]
+    fn rejects_duplicate_names_and_keeps_the_first_tool() {
+        let mut registry = ToolRegistry::new();
+        registry.register(tool("search", "web search")).unwrap();
+        registry.register(tool("calc", "calculator")).unwrap();
+
+        let err = registry
+            .register(tool("search", "another search"))
+            .unwrap_err();
+        assert!(matches!(err, ToolError::AlreadyRegistered(ref name) if name == "search"));
+        assert_eq!(
+            registry.get("search").unwrap().function.description,
+            "web search"
+        );
+
+        let names: Vec<_> = registry
+            .list_specs()
+            .iter()
+            .map(|spec| spec.function_name().to_owned())
+            .collect();
+        assert_eq!(names, ["calc", "search"]);
+
+        assert!(registry.remove("calc").is_some());
+        assert!(registry.remove("calc").is_none());
+        assert_eq!(registry.names().collect::<Vec<_>>(), ["search"]);
+    }
+}
@@ -9,9 +9,22 @@ pub type ToolFuture<E> = Pin<Box<dyn Future<Output = Result<Value, E>> + Send>>;
 
 pub type ToolFn<E> = dyn Fn(Value) -> ToolFuture<E> + Send + Sync;
 
+/// 接收工具的增量输出
+pub type ToolChunkFn = dyn Fn(String) + Send + Sync;
+
+/// 支持增量输出的工具函数：执行过程中通过第二个参数推送输出片段，
+/// 最终结果仍由返回的 future 给出
+pub type ToolStreamFn<E> = dyn Fn(Value, Arc<ToolChunkFn>) -> ToolFuture<E> + Send + Sync;
+
 pub struct RegisteredTool<E> {
     pub function: ToolFunction,
     pub handler: Arc<ToolFn<E>>,
+    /// 增量输出的工具函数，见 [`RegisteredTool::streaming`]
+    pub stream_handler: Option<Arc<ToolStreamFn<E>>>,
+    /// 成功调用后结束 Agent 运行，不再把结果交回模型，见 [`RegisteredTool::terminal`]
+    pub terminal: bool,
+    /// 返回给模型的结果的最大字符数，覆盖 Agent 的全局设置，见 [`RegisteredTool::with_max_output_chars`]
+    pub max_output_chars: Option<usize>,
 }
 
 impl<E> RegisteredTool<E> {
@@ -25,8 +38,69 @@ impl<E> RegisteredTool<E> {
             name,
             description,
             parameters,
+            examples: Vec::new(),
+            output_schema: None,
         };
-        Self { function, handler }
+        Self {
+            function,
+            handler,
+            stream_handler: None,
+            terminal: false,
+            max_output_chars: None,
+        }
+    }
+
+    /// 注册支持增量输出的工具（长时间运行的命令、流式 HTTP 响应等）
+    ///
+    /// 流式运行 Agent 时，推送的片段作为 `ChatStreamEvent::ToolChunk` 事件实时发出；
+    /// 非流式运行时片段被丢弃，只使用最终结果。
+    pub fn streaming(
+        name: String,
+        description: String,
+        parameters: Value,
+        handler: Arc<ToolStreamFn<E>>,
+    ) -> Self
+    where
+        E: 'static,
+    {
+        let stream_handler = handler.clone();
+        let discard: Arc<ToolChunkFn> = Arc::new(|_| {});
+        let buffered: Arc<ToolFn<E>> = Arc::new(move |args| (handler)(args, discard.clone()));
+        Self {
+            stream_handler: Some(stream_handler),
+            ..Self::new(name, description, parameters, buffered)
+        }
+    }
+
+    /// 添加一个调用示例：模型以 `args` 调用时工具返回 `result`，见 [`ToolFunction::examples`]
+    pub fn with_example(mut self, args: Value, result: Value) -> Self {
+        self.function.examples.push((args, result));
+        self
+    }
+
+    /// 声明工具结果的 JSON Schema，见 [`ToolFunction::output_schema`]
+    pub fn with_output_schema(mut self, schema: Value) -> Self {
+        self.function.output_schema = Some(schema);
+        self
+    }
+
+    /// 由 `T` 生成工具结果的 JSON Schema，`T` 通常是工具函数的返回类型
+    pub fn with_output_type<T: JsonSchema>(self) -> Self {
+        self.with_output_schema(schema_value::<T>())
+    }
+
+    /// 结果超过 `limit` 个字符时截断后再交给模型（如读取整个文件的工具），
+    /// 可以为单个工具放宽或收紧 Agent 的全局上限
+    pub fn with_max_output_chars(mut self, limit: usize) -> Self {
+        self.max_output_chars = Some(limit);
+        self
+    }
+
+    /// 标记为终止工具（例如 `submit_final_answer`）：调用成功后运行直接结束，
+    /// 工具结果就是最后一条消息。调用失败时照常把错误交回模型，让它重试
+    pub fn terminal(mut self) -> Self {
+        self.terminal = true;
+        self
     }
 }
 
@@ -150,11 +224,7 @@ where
         Args: DeserializeOwned + JsonSchema + Send + 'static,
         Output: Serialize + Send + 'static,
     {
-        let schema = schemars::schema_for!(Args);
-        let mut parameters = serde_json::to_value(schema.schema).unwrap();
-        if let Value::Object(map) = &mut parameters {
-            map.remove("title");
-        }
+        let parameters = schema_value::<Args>();
         let f = Arc::new(f);
         let handler: Arc<ToolFn<E>> = Arc::new(move |value: Value| {
             let f = f.clone();
@@ -169,6 +239,16 @@ where
     }
 }
 
+/// `T` 的 JSON Schema，去掉类型名生成的 `title`
+fn schema_value<T: JsonSchema>() -> Value {
+    let schema = schemars::schema_for!(T);
+    let mut value = serde_json::to_value(schema.schema).unwrap();
+    if let Value::Object(map) = &mut value {
+        map.remove("title");
+    }
+    value
+}
+
 #[cfg(test)]
 mod tests {
     extern crate self as langchain_core;
@@ -395,4 +475,64 @@ mod tests {
             panic!("parameters must be object");
         }
     }
+
+    #[tool(
+        description = "查询城市天气",
+        args(city = "城市名"),
+        example(args = r#"{"city": "Paris"}"#, result = r#""sunny""#),
+        example(args = r#"{"city": "Oslo"}"#, result = r#""snow""#)
+    )]
+    async fn weather_with_examples(city: String) -> String {
+        city
+    }
+
+    #[test]
+    fn tool_attribute_collects_examples() {
+        let tool: RegisteredTool<langchain_core::ToolError> = weather_with_examples_tool();
+        assert_eq!(
+            tool.function.examples,
+            [
+                (
+                    serde_json::json!({"city": "Paris"}),
+                    serde_json::json!("sunny")
+                ),
+                (
+                    serde_json::json!({"city": "Oslo"}),
+                    serde_json::json!("snow")
+                ),
+            ]
+        );
+    }
+
+    #[derive(serde::Serialize, JsonSchema)]
+    struct Forecast {
+        city: String,
+        celsius: f64,
+    }
+
+    #[tool(description = "查询城市预报", output_schema)]
+    async fn forecast(city: String) -> Result<Forecast, std::io::Error> {
+        Ok(Forecast {
+            city,
+            celsius: 21.5,
+        })
+    }
+
+    #[test]
+    fn tool_attribute_declares_output_schema() {
+        let tool = forecast_tool();
+        let schema = tool.function.output_schema.as_ref().expect("output schema");
+        assert_eq!(schema["properties"]["celsius"]["type"], "number");
+        assert!(schema.get("title").is_none());
+        // 输出 schema 不随工具定义发送给模型
+        let spec = serde_json::to_value(&tool.function).unwrap();
+        assert!(spec.get("output_schema").is_none());
+
+        assert!(
+            weather_with_examples_tool()
+                .function
+                .output_schema
+                .is_none()
+        );
+    }
 }
@@ -4,21 +4,24 @@
 
 use crate::store::{BaseStore, Namespace, StoreError, StoreFilter};
 use async_trait::async_trait;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
 /// 存储条目
 #[derive(Debug, Clone)]
 struct StoreEntry {
-    /// 字节数组值
-    value: Vec<u8>,
+    /// 字节数组值，`list` 在锁内只复制指针
+    value: Arc<Vec<u8>>,
     /// 创建时间戳（Unix 时间戳，秒）
     created_at: u64,
     /// 更新时间戳（Unix 时间戳，秒）
     _updated_at: u64,
 }
 
+/// 单个命名空间内的条目，按键排序，前缀和范围查询只访问命中的区间
+type Bucket = BTreeMap<String, StoreEntry>;
+
 /// 内存存储实现
 ///
 /// 这是一个使用 HashMap 和 RwLock 实现的简单内存存储。
@@ -27,6 +30,10 @@ struct StoreEntry {
 /// - 单进程应用
 /// - 缓存场景
 ///
+/// 条目按命名空间分桶、桶内按键排序。`list` 只在锁内定位命中的区间并
+/// 复制值的指针，复制字节在释放锁之后进行，因此大范围扫描不会长时间阻塞
+/// 并发写入。`list` 的结果按键升序返回。
+///
 /// 对于生产环境，建议使用持久化存储（如数据库）。
 ///
 /// # 示例
@@ -51,8 +58,8 @@ struct StoreEntry {
 /// ```
 #[derive(Debug, Default, Clone)]
 pub struct InMemoryStore {
-    /// 存储结构: (namespace_string, key) -> StoreEntry
-    storage: Arc<RwLock<HashMap<(String, String), StoreEntry>>>,
+    /// 存储结构: namespace_string -> (key -> StoreEntry)
+    storage: Arc<RwLock<HashMap<String, Bucket>>>,
 }
 
 impl InMemoryStore {
@@ -65,6 +72,39 @@ impl InMemoryStore {
     fn namespace_to_string(ns: &Namespace) -> String {
         ns.to_string()
     }
+
+    /// 在桶内定位满足过滤条件的条目，最多取 `limit` 个
+    ///
+    /// 只克隆键和值的指针，调用方持有读锁的时间与命中数量成正比，而不是与
+    /// 存储大小成正比。
+    fn matching(
+        bucket: &Bucket,
+        filter: &StoreFilter,
+        limit: usize,
+    ) -> Vec<(String, Arc<Vec<u8>>)> {
+        let pick = |(key, entry): (&String, &StoreEntry)| (key.clone(), Arc::clone(&entry.value));
+        match filter {
+            StoreFilter::Prefix(prefix) => bucket
+                .range::<String, _>(prefix..)
+                .take_while(|(key, _)| key.starts_with(prefix.as_str()))
+                .take(limit)
+                .map(pick)
+                .collect(),
+            StoreFilter::Exact(exact) => bucket
+                .get_key_value(exact.as_str())
+                .into_iter()
+                .take(limit)
+                .map(pick)
+                .collect(),
+            // BTreeMap::range 在 start > end 时会 panic
+            StoreFilter::Range { start, end } if start < end => bucket
+                .range::<String, _>(start..end)
+                .take(limit)
+                .map(pick)
+                .collect(),
+            StoreFilter::Range { .. } => Vec::new(),
+        }
+    }
 }
 
 #[async_trait]
@@ -81,17 +121,15 @@ impl BaseStore for InMemoryStore {
             .as_secs();
 
         let ns_key = Self::namespace_to_string(namespace);
+        let value = Arc::new(value);
         let mut storage = self.storage.write().await;
+        let bucket = storage.entry(ns_key).or_default();
 
         // 检查是否已存在，如果存在则保留 created_at
-        let created_at = if let Some(existing) = storage.get(&(ns_key.clone(), key.to_owned())) {
-            existing.created_at
-        } else {
-            now
-        };
+        let created_at = bucket.get(key).map_or(now, |existing| existing.created_at);
 
-        storage.insert(
-            (ns_key, key.to_owned()),
+        bucket.insert(
+            key.to_owned(),
             StoreEntry {
                 value,
                 created_at,
@@ -104,19 +142,29 @@ impl BaseStore for InMemoryStore {
 
     async fn get(&self, namespace: &Namespace, key: &str) -> Result<Option<Vec<u8>>, StoreError> {
         let ns_key = Self::namespace_to_string(namespace);
-        let storage = self.storage.read().await;
+        let value = {
+            let storage = self.storage.read().await;
+            storage
+                .get(&ns_key)
+                .and_then(|bucket| bucket.get(key))
+                .map(|entry| Arc::clone(&entry.value))
+        };
 
-        match storage.get(&(ns_key, key.to_owned())) {
-            Some(entry) => Ok(Some(entry.value.clone())),
-            None => Ok(None),
-        }
+        Ok(value.map(|value| value.as_ref().clone()))
     }
 
     async fn delete(&self, namespace: &Namespace, key: &str) -> Result<bool, StoreError> {
         let ns_key = Self::namespace_to_string(namespace);
         let mut storage = self.storage.write().await;
 
-        Ok(storage.remove(&(ns_key, key.to_owned())).is_some())
+        let Some(bucket) = storage.get_mut(&ns_key) else {
+            return Ok(false);
+        };
+        let removed = bucket.remove(key).is_some();
+        if bucket.is_empty() {
+            storage.remove(&ns_key);
+        }
+        Ok(removed)
     }
 
     async fn list(
@@ -126,41 +174,29 @@ impl BaseStore for InMemoryStore {
         limit: Option<usize>,
     ) -> Result<Vec<(String, Vec<u8>)>, StoreError> {
         let ns_key = Self::namespace_to_string(namespace);
-        let storage = self.storage.read().await;
-
-        let mut results = Vec::new();
-
-        for ((ns, key), entry) in storage.iter() {
-            // 过滤命名空间
-            if ns != &ns_key {
-                continue;
-            }
-
-            // 应用过滤条件
-            match filter {
-                StoreFilter::Prefix(prefix) if !key.starts_with(prefix) => continue,
-                StoreFilter::Exact(exact) if key != exact => continue,
-                StoreFilter::Range { start, end } if !(key >= start && key < end) => continue,
-                _ => {}
+        let limit = limit.unwrap_or(usize::MAX);
+
+        // 锁内只做快照，值的复制放到锁外
+        let snapshot = {
+            let storage = self.storage.read().await;
+            match storage.get(&ns_key) {
+                Some(bucket) => Self::matching(bucket, filter, limit),
+                None => return Ok(Vec::new()),
             }
+        };
 
-            results.push((key.clone(), entry.value.clone()));
-
-            // 检查限制
-            if let Some(limit) = limit
-                && results.len() >= limit
-            {
-                break;
-            }
-        }
-
-        Ok(results)
+        Ok(snapshot
+            .into_iter()
+            .map(|(key, value)| (key, value.as_ref().clone()))
+            .collect())
     }
 
     async fn exists(&self, namespace: &Namespace, key: &str) -> Result<bool, StoreError> {
         let ns_key = Self::namespace_to_string(namespace);
         let storage = self.storage.read().await;
-        Ok(storage.contains_key(&(ns_key, key.to_owned())))
+        Ok(storage
+            .get(&ns_key)
+            .is_some_and(|bucket| bucket.contains_key(key)))
     }
 }
 
@@ -338,7 +374,7 @@ mod tests {
             .await
             .unwrap();
 
-        // HashMap 迭代顺序不确定，需要排序
+        // 结果按键排序
         results.sort_by(|a, b| a.0.cmp(&b.0));
 
         assert_eq!(results.len(), 2);
@@ -376,10 +412,7 @@ mod tests {
 
         // 获取创建时间
         let storage = store.storage.read().await;
-        let created_at = storage
-            .get(&(namespace.to_string(), "key1".to_owned()))
-            .unwrap()
-            .created_at;
+        let created_at = storage[&namespace.to_string()]["key1"].created_at;
         drop(storage);
 
         // 等待确保时间戳变化
@@ -393,9 +426,7 @@ mod tests {
 
         // 验证创建时间不变
         let storage = store.storage.read().await;
-        let entry = storage
-            .get(&(namespace.to_string(), "key1".to_owned()))
-            .unwrap();
+        let entry = &storage[&namespace.to_string()]["key1"];
         assert_eq!(entry.created_at, created_at);
         assert!(entry._updated_at >= created_at);
     }
@@ -420,4 +451,94 @@ mod tests {
         let items: Vec<String> = serde_json::from_slice(&retrieved.unwrap()).unwrap();
         assert_eq!(items, vec!["item1", "item2", "item3"]);
     }
+
+    #[tokio::test]
+    async fn test_store_list_is_sorted_and_limited_to_namespace() {
+        let store = InMemoryStore::new();
+        let ns1 = Namespace::from_str("a").unwrap();
+        let ns2 = Namespace::from_str("b").unwrap();
+
+        for key in ["doc_3", "doc_1", "other", "doc_2"] {
+            store.put(&ns1, key, key.as_bytes().to_vec()).await.unwrap();
+        }
+        store.put(&ns2, "doc_0", b"x".to_vec()).await.unwrap();
+
+        let keys = |results: Vec<(String, Vec<u8>)>| {
+            results.into_iter().map(|(key, _)| key).collect::<Vec<_>>()
+        };
+        let prefix = StoreFilter::Prefix("doc_".to_owned());
+        assert_eq!(
+            keys(store.list(&ns1, &prefix, None).await.unwrap()),
+            ["doc_1", "doc_2", "doc_3"]
+        );
+        assert_eq!(
+            keys(store.list(&ns1, &prefix, Some(2)).await.unwrap()),
+            ["doc_1", "doc_2"]
+        );
+
+        // 反向区间不匹配任何键
+        let reversed = StoreFilter::Range {
+            start: "z".to_owned(),
+            end: "a".to_owned(),
+        };
+        assert!(store.list(&ns1, &reversed, None).await.unwrap().is_empty());
+
+        // 删除最后一个键后命名空间被移除
+        assert!(store.delete(&ns2, "doc_0").await.unwrap());
+        assert!(!store.storage.read().await.contains_key("b"));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_store_concurrent_readers_and_writers() {
+        const WRITERS: usize = 8;
+        const READERS: usize = 8;
+        const KEYS: usize = 500;
+
+        let store = InMemoryStore::new();
+        let namespace = Namespace::from_str("stress").unwrap();
+        // 预先写入较大的值，让扫描的复制成本明显
+        for i in 0..KEYS {
+            store
+                .put(&namespace, &format!("seed_{i:04}"), vec![0; 4096])
+                .await
+                .unwrap();
+        }
+
+        let mut tasks = Vec::new();
+        for writer in 0..WRITERS {
+            let (store, namespace) = (store.clone(), namespace.clone());
+            tasks.push(tokio::spawn(async move {
+                for i in 0..KEYS {
+                    let key = format!("w{writer}_{i:04}");
+                    store.put(&namespace, &key, vec![1; 64]).await.unwrap();
+                    if i % 10 == 0 {
+                        assert!(store.delete(&namespace, &key).await.unwrap());
+                    }
+                }
+            }));
+        }
+        for _ in 0..READERS {
+            let (store, namespace) = (store.clone(), namespace.clone());
+            tasks.push(tokio::spawn(async move {
+                let filter = StoreFilter::Prefix("seed_".to_owned());
+                for _ in 0..50 {
+                    let results = store.list(&namespace, &filter, None).await.unwrap();
+                    assert_eq!(results.len(), KEYS);
+                    tokio::task::yield_now().await;
+                }
+            }));
+        }
+
+        let all = futures::future::try_join_all(tasks);
+        tokio::time::timeout(std::time::Duration::from_secs(30), all)
+            .await
+            .expect("concurrent store operations should not deadlock")
+            .unwrap();
+
+        for writer in 0..WRITERS {
+            let filter = StoreFilter::Prefix(format!("w{writer}_"));
+            let written = store.list(&namespace, &filter, None).await.unwrap();
+            assert_eq!(written.len(), KEYS - KEYS / 10);
+        }
+    }
 }
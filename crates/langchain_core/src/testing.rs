@@ -0,0 +1,130 @@
+//! 测试工具：本地模拟 HTTP 服务
+//!
+//! 模型提供方、网络工具和追踪导出的测试需要一个返回固定响应的 HTTP 服务。
+//! [`MockHttpServer`] 监听本地随机端口，按顺序用给定的原始响应回复每个连接，并记录收到的请求。
+//! 需要启用 `test-util` feature，通常只在 `[dev-dependencies]` 中启用。
+//!
+//! ```no_run
+//! use langchain_core::testing::{MockHttpServer, json_response};
+//!
+//! # async fn example() {
+//! let server = MockHttpServer::start(vec![json_response(r#"{"ok":true}"#)]).await;
+//! // 把 server.url() 作为被测客户端的 base_url
+//! let request = server.request(0).await;
+//! assert!(request.starts_with("GET /"));
+//! # }
+//! ```
+
+use std::sync::{Arc, Mutex};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+    sync::Notify,
+};
+
+/// 按顺序回复固定响应的本地 HTTP 服务
+///
+/// 每个连接只处理一个请求：读完请求头和请求体（按 `content-length`）后写出响应并关闭连接。
+/// 响应用完后服务不再接受连接。
+pub struct MockHttpServer {
+    url: String,
+    requests: Arc<Mutex<Vec<String>>>,
+    received: Arc<Notify>,
+}
+
+impl MockHttpServer {
+    /// 启动服务，`responses` 是完整的原始 HTTP 响应，见 [`response`] 和 [`json_response`]
+    pub async fn start(responses: Vec<String>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let received = Arc::new(Notify::new());
+        let (recorded, notify) = (requests.clone(), received.clone());
+        tokio::spawn(async move {
+            for response in responses {
+                let Ok((mut socket, _)) = listener.accept().await else {
+                    return;
+                };
+                let request = read_request(&mut socket).await;
+                recorded.lock().unwrap().push(request);
+                notify.notify_waiters();
+                // 客户端可能已经放弃请求（例如超时测试），忽略写入错误
+                let _ = socket.write_all(response.as_bytes()).await;
+                let _ = socket.shutdown().await;
+            }
+        });
+        Self {
+            url,
+            requests,
+            received,
+        }
+    }
+
+    /// 服务地址，形如 `http://127.0.0.1:12345`，不带结尾的 `/`
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// 目前收到的所有原始请求（请求行、请求头和请求体）
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// 等待并返回第 `index` 个请求（从 0 开始）
+    ///
+    /// 适合请求在后台发出的场景，例如批量导出。
+    pub async fn request(&self, index: usize) -> String {
+        loop {
+            let received = self.received.notified();
+            if let Some(request) = self.requests.lock().unwrap().get(index) {
+                return request.clone();
+            }
+            received.await;
+        }
+    }
+}
+
+/// 读完请求头和 `content-length` 指定长度的请求体
+async fn read_request(socket: &mut tokio::net::TcpStream) -> String {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 4096];
+    loop {
+        let n = socket.read(&mut buf).await.unwrap_or(0);
+        request.extend_from_slice(&buf[..n]);
+        let text = String::from_utf8_lossy(&request);
+        if let Some(end) = text.find("\r\n\r\n") {
+            let content_length = text[..end]
+                .lines()
+                .find_map(|l| {
+                    l.to_ascii_lowercase()
+                        .strip_prefix("content-length:")
+                        .and_then(|v| v.trim().parse::<usize>().ok())
+                })
+                .unwrap_or(0);
+            if request.len() >= end + 4 + content_length {
+                break;
+            }
+        }
+        if n == 0 {
+            break;
+        }
+    }
+    String::from_utf8_lossy(&request).into_owned()
+}
+
+/// 构造原始 HTTP 响应
+///
+/// `status` 是状态码和原因短语，例如 `429 Too Many Requests`；
+/// 需要额外的响应头时可以跟在后面，例如 `"429 Too Many Requests\r\nretry-after: 2"`。
+pub fn response(status: &str, content_type: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {status}\r\ncontent-type: {content_type}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+/// 状态为 `200 OK` 的 JSON 响应
+pub fn json_response(body: &str) -> String {
+    response("200 OK", "application/json", body)
+}
@@ -0,0 +1,158 @@
+//! Token 计数
+//!
+//! [`Tokenizer`] 为上下文裁剪、预算控制和费用估算提供统一的 token 计数接口。
+//! 默认的 [`HeuristicTokenizer`] 按字符数估算；启用 `tiktoken` feature 后可以使用
+//! [`TiktokenTokenizer`] 得到 OpenAI 模型的精确计数。
+
+use std::sync::Arc;
+
+use crate::message::Message;
+
+/// 每条消息在正文之外的固定开销（角色、分隔符等），取自 OpenAI 的计数规则
+const MESSAGE_OVERHEAD: usize = 4;
+
+/// Token 计数器
+pub trait Tokenizer: Send + Sync {
+    /// 计算一段文本的 token 数
+    fn count_tokens(&self, text: &str) -> usize;
+
+    /// 计算一组消息发送给模型时大约占用的 token 数
+    ///
+    /// 包括正文、思考内容、工具调用的名称与参数，以及每条消息的固定开销。
+    fn count_messages(&self, messages: &[Arc<Message>]) -> usize {
+        messages
+            .iter()
+            .map(|message| {
+                let mut tokens = MESSAGE_OVERHEAD + self.count_tokens(message.content());
+                if let Message::Assistant {
+                    reasoning_content,
+                    tool_calls,
+                    ..
+                } = message.as_ref()
+                {
+                    if let Some(reasoning) = reasoning_content {
+                        tokens += self.count_tokens(reasoning);
+                    }
+                    for call in tool_calls.iter().flatten() {
+                        let arguments = match &call.function.arguments {
+                            serde_json::Value::String(raw) => raw.clone(),
+                            value => value.to_string(),
+                        };
+                        tokens += self.count_tokens(&call.function.name);
+                        tokens += self.count_tokens(&arguments);
+                    }
+                }
+                tokens
+            })
+            .sum()
+    }
+}
+
+/// 按字符数估算：约 4 个字符一个 token，向上取整
+///
+/// 对英文文本比较接近真实值，对中文等会明显偏低，需要精确计数时使用模型自己的分词器。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HeuristicTokenizer;
+
+impl Tokenizer for HeuristicTokenizer {
+    fn count_tokens(&self, text: &str) -> usize {
+        text.chars().count().div_ceil(4)
+    }
+}
+
+/// 基于 `tiktoken-rs` 的精确计数，与 OpenAI 模型的分词一致
+#[cfg(feature = "tiktoken")]
+pub struct TiktokenTokenizer {
+    bpe: tiktoken_rs::CoreBPE,
+}
+
+#[cfg(feature = "tiktoken")]
+impl TiktokenTokenizer {
+    /// `cl100k_base` 编码（GPT-4、GPT-3.5）
+    pub fn cl100k() -> Self {
+        Self {
+            bpe: tiktoken_rs::cl100k_base().expect("cl100k_base is bundled"),
+        }
+    }
+
+    /// `o200k_base` 编码（GPT-4o 及之后的模型）
+    pub fn o200k() -> Self {
+        Self {
+            bpe: tiktoken_rs::o200k_base().expect("o200k_base is bundled"),
+        }
+    }
+
+    /// 按模型名选择编码，未知模型返回 `None`
+    pub fn for_model(model: &str) -> Option<Self> {
+        tiktoken_rs::get_bpe_from_model(model)
+            .ok()
+            .map(|bpe| Self { bpe })
+    }
+}
+
+#[cfg(feature = "tiktoken")]
+impl std::fmt::Debug for TiktokenTokenizer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TiktokenTokenizer").finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "tiktoken")]
+impl Tokenizer for TiktokenTokenizer {
+    fn count_tokens(&self, text: &str) -> usize {
+        self.bpe.encode_with_special_tokens(text).len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::ToolCall;
+
+    #[test]
+    fn heuristic_counts_chars_over_four() {
+        let tokenizer = HeuristicTokenizer;
+        assert_eq!(tokenizer.count_tokens(""), 0);
+        assert_eq!(tokenizer.count_tokens("hello world"), 3);
+        // 按字符而不是字节计数
+        assert_eq!(tokenizer.count_tokens("你好世界"), 1);
+    }
+
+    #[test]
+    fn message_count_includes_overhead_and_tool_calls() {
+        let tokenizer = HeuristicTokenizer;
+        let messages = vec![
+            Arc::new(Message::user("hello world")),
+            Arc::new(Message::assistant_with_tool_calls(
+                "",
+                vec![ToolCall::new("1", "search", serde_json::json!({"q": "x"}))],
+            )),
+        ];
+
+        // user: 4 + 3；assistant: 4 + 0 + "search"(2) + {"q":"x"}(3)
+        assert_eq!(tokenizer.count_messages(&messages), 16);
+    }
+
+    #[cfg(feature = "tiktoken")]
+    #[test]
+    fn heuristic_is_close_to_tiktoken_on_english() {
+        let exact = TiktokenTokenizer::cl100k();
+        assert_eq!(exact.count_tokens("hello world"), 2);
+
+        for sample in [
+            "hello world",
+            "The quick brown fox jumps over the lazy dog.",
+            "fn main() { println!(\"{}\", 1 + 2); }",
+        ] {
+            let heuristic = HeuristicTokenizer.count_tokens(sample) as f64;
+            let exact = exact.count_tokens(sample) as f64;
+            let ratio = heuristic / exact;
+            assert!(
+                (0.5..=2.0).contains(&ratio),
+                "{sample}: {heuristic} vs {exact}"
+            );
+        }
+        assert!(TiktokenTokenizer::for_model("gpt-4o").is_some());
+        assert!(TiktokenTokenizer::for_model("not-a-model").is_none());
+    }
+}
@@ -0,0 +1,140 @@
+//! OpenAI 消息格式导入导出的往返测试
+
+use langchain_core::{
+    message::{Content, ContentBlock, Message, ToolCall},
+    state::MessagesState,
+};
+use proptest::prelude::*;
+use serde_json::{Value, json};
+
+fn text() -> impl Strategy<Value = String> {
+    prop_oneof![Just(String::new()), "[a-zA-Z0-9 \"{}\\[\\]\n]{1,20}"]
+}
+
+fn name() -> impl Strategy<Value = Option<String>> {
+    proptest::option::of("[a-z_]{1,8}")
+}
+
+/// 参数为对象的工具调用：导入时参数字符串会被解析回对象
+fn tool_call() -> impl Strategy<Value = ToolCall> {
+    (
+        "call_[a-z0-9]{1,8}",
+        "[a-z_]{1,12}",
+        proptest::collection::btree_map(
+            "[a-z]{1,6}",
+            prop_oneof![
+                any::<i32>().prop_map(Value::from),
+                any::<bool>().prop_map(Value::from),
+                text().prop_map(Value::from),
+            ],
+            0..4,
+        ),
+    )
+        .prop_map(|(id, name, args)| {
+            ToolCall::new(id, name, Value::Object(args.into_iter().collect()))
+        })
+}
+
+fn message() -> impl Strategy<Value = Message> {
+    prop_oneof![
+        (text(), name()).prop_map(|(content, name)| Message::User {
+            content: Content::Text(content),
+            name,
+            metadata: Default::default(),
+        }),
+        "https://[a-z]{1,8}\\.png".prop_map(|url| Message::User {
+            content: Content::Image { url },
+            name: None,
+            metadata: Default::default(),
+        }),
+        proptest::collection::vec(text(), 2..4).prop_map(|parts| Message::User {
+            content: Content::Mixed(
+                parts
+                    .into_iter()
+                    .map(|text| ContentBlock::Text { text })
+                    .collect()
+            ),
+            name: None,
+            metadata: Default::default(),
+        }),
+        (
+            text(),
+            proptest::option::of(text()),
+            proptest::option::of(proptest::collection::vec(tool_call(), 1..3)),
+            name(),
+        )
+            .prop_map(|(content, reasoning_content, tool_calls, name)| {
+                Message::Assistant {
+                    content,
+                    reasoning_content,
+                    tool_calls,
+                    name,
+                    metadata: Default::default(),
+                }
+            }),
+        (text(), name()).prop_map(|(content, name)| Message::System {
+            content,
+            name,
+            metadata: Default::default(),
+        }),
+        (text(), name()).prop_map(|(content, name)| Message::Developer {
+            content,
+            name,
+            metadata: Default::default(),
+        }),
+        ("call_[a-z0-9]{1,8}", text()).prop_map(|(id, content)| Message::tool(content, id)),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn messages_round_trip(messages in proptest::collection::vec(message(), 0..8)) {
+        let state = MessagesState::new(messages.clone());
+
+        let exported = state.to_openai_messages();
+        let imported = MessagesState::from_openai_messages(&exported).unwrap();
+
+        let imported: Vec<Message> = imported.messages.iter().map(|m| m.as_ref().clone()).collect();
+        prop_assert_eq!(imported, messages);
+    }
+
+    #[test]
+    fn export_uses_string_arguments(call in tool_call()) {
+        let exported = Message::assistant_with_tool_calls("", vec![call.clone()]).to_openai();
+        let raw = exported["tool_calls"][0]["function"]["arguments"].as_str().unwrap();
+
+        prop_assert_eq!(serde_json::from_str::<Value>(raw).unwrap(), call.function.arguments);
+    }
+}
+
+#[test]
+fn imports_logged_openai_conversation() {
+    let logged = json!([
+        {"role": "system", "content": "be brief"},
+        {"role": "user", "content": [{"type": "text", "text": "weather?"}]},
+        {"role": "assistant", "content": null, "tool_calls": [{
+            "id": "call_1",
+            "type": "function",
+            "function": {"name": "get_weather", "arguments": "{\"city\":\"Paris\"}"}
+        }]},
+        {"role": "tool", "tool_call_id": "call_1", "content": "sunny"},
+        {"role": "assistant", "content": "It is sunny."}
+    ]);
+
+    let state = MessagesState::from_openai_messages(&logged).unwrap();
+
+    assert_eq!(state.messages.len(), 5);
+    let Message::Assistant {
+        tool_calls: Some(calls),
+        content,
+        ..
+    } = state.messages[2].as_ref()
+    else {
+        panic!("expected tool calls");
+    };
+    assert!(content.is_empty());
+    assert_eq!(calls[0].arguments().unwrap(), json!({"city": "Paris"}));
+
+    let error = MessagesState::from_openai_messages(&json!([{"role": "function"}])).unwrap_err();
+    assert!(error.to_string().contains("message #0"));
+}
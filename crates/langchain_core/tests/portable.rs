@@ -0,0 +1,57 @@
+//! 只使用可移植接口（消息、解析器、状态、token 计数）的编译与行为检查
+//!
+//! 不依赖异步运行时和系统计时器，与 wasm32 构建使用的接口一致。配合
+//! `cargo test -p langchain_core --no-default-features` 运行即可在本机确认这部分接口
+//! 不依赖 `native` feature；它不检查 wasm32 目标本身，真正的 wasm32 编译检查见 `tests/wasm32.rs`。
+
+use langchain_core::{
+    HeuristicTokenizer, JsonParser, ListParser, OutputParser, Tokenizer,
+    message::{Message, ToolCall},
+    state::MessagesState,
+};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Person {
+    name: String,
+    age: u32,
+}
+
+#[test]
+fn assemble_prompt_and_parse_reply_without_runtime() {
+    let mut state = MessagesState::new(vec![
+        Message::system("extract the person as JSON"),
+        Message::user("Ann is 30"),
+    ]);
+    state.push_message_owned(Message::assistant_with_tool_calls(
+        "",
+        vec![ToolCall::new(
+            "1",
+            "lookup",
+            serde_json::json!({"name": "Ann"}),
+        )],
+    ));
+    state.push_message_owned(Message::tool("found", "1"));
+
+    let request = serde_json::to_value(&state.messages).unwrap();
+    assert_eq!(request[1]["role"], "user");
+    assert!(state.transcript().contains("lookup"));
+    assert!(
+        HeuristicTokenizer.count_messages(&state.messages.iter().cloned().collect::<Vec<_>>()) > 0
+    );
+
+    let person: Person = JsonParser::new()
+        .parse(r#"{"name": "Ann", "age": 30}"#)
+        .unwrap();
+    assert_eq!(
+        person,
+        Person {
+            name: "Ann".to_owned(),
+            age: 30
+        }
+    );
+    assert_eq!(
+        ListParser::new(",").parse("a, b").unwrap(),
+        ["a".to_owned(), "b".to_owned()]
+    );
+}
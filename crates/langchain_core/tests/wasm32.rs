@@ -0,0 +1,32 @@
+//! 在 `wasm32-unknown-unknown` 上编译关闭 `native` 的 `langchain_core`
+//!
+//! 需要先安装目标：`rustup target add wasm32-unknown-unknown`，然后运行
+//! `cargo test -p langchain_core --features wasm-check --test wasm32`。
+//! 编译产物放在独立的目录中，不会与当前测试构建争用 target 目录的锁。
+#![cfg(feature = "wasm-check")]
+
+use std::process::Command;
+
+#[test]
+fn compiles_for_wasm32_without_native() {
+    let output = Command::new(env!("CARGO"))
+        .args([
+            "check",
+            "--package",
+            "langchain_core",
+            "--no-default-features",
+            "--target",
+            "wasm32-unknown-unknown",
+            "--target-dir",
+        ])
+        .arg(std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("wasm32"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("failed to run cargo");
+
+    assert!(
+        output.status.success(),
+        "langchain_core does not compile for wasm32-unknown-unknown:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
@@ -0,0 +1,243 @@
+//! OpenAI 标准的 `/embeddings` 接口实现
+
+use std::time::Duration;
+
+use langchain_core::{
+    embeddings::Embedder,
+    error::{ModelError, RetryConfig, ValidationError},
+};
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{HttpClient, error::OpenAIError, post_json};
+
+pub const EMBEDDINGS: &str = "/embeddings";
+
+/// OpenAI 单次请求最多允许 2048 条输入
+const DEFAULT_BATCH_SIZE: usize = 2048;
+
+pub struct OpenAIEmbeddings {
+    client: HttpClient,
+    base_url: String,
+    model: String,
+    api_key: String,
+    dimensions: Option<u32>,
+    batch_size: usize,
+    retry: Option<RetryConfig>,
+}
+
+#[derive(Serialize)]
+struct EmbeddingRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dimensions: Option<u32>,
+    encoding_format: &'static str,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingData {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+impl OpenAIEmbeddings {
+    async fn embed_batch(&self, batch: &[String]) -> Result<Vec<Vec<f32>>, OpenAIError> {
+        let request = EmbeddingRequest {
+            model: &self.model,
+            input: batch,
+            dimensions: self.dimensions,
+            encoding_format: "float",
+        };
+
+        let response = post_json(
+            &self.client,
+            &format!("{}{EMBEDDINGS}", self.base_url),
+            &self.api_key,
+            &request,
+            self.retry.as_ref(),
+        )
+        .await?;
+
+        let mut response = response
+            .json::<EmbeddingResponse>()
+            .await
+            .map_err(OpenAIError::ResponseBodyParse)?;
+
+        if response.data.len() != batch.len() {
+            return Err(OpenAIError::Other(format!(
+                "expected {} embeddings, got {}",
+                batch.len(),
+                response.data.len()
+            )));
+        }
+
+        // 服务端不保证返回顺序，按 index 还原
+        response.data.sort_by_key(|d| d.index);
+        Ok(response.data.into_iter().map(|d| d.embedding).collect())
+    }
+}
+
+#[async_trait::async_trait]
+impl Embedder for OpenAIEmbeddings {
+    async fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, ModelError> {
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(self.batch_size) {
+            embeddings.extend(self.embed_batch(batch).await?);
+        }
+        Ok(embeddings)
+    }
+
+    fn dimensions(&self) -> Option<usize> {
+        self.dimensions.map(|d| d as usize)
+    }
+}
+
+pub struct OpenAIEmbeddingsBuilder {
+    base_url: String,
+    model: String,
+    api_key: String,
+    dimensions: Option<u32>,
+    batch_size: usize,
+    http_client: Option<reqwest::Client>,
+    timeout: Option<Duration>,
+    retry: Option<RetryConfig>,
+}
+
+impl OpenAIEmbeddingsBuilder {
+    pub fn from_base<T: Into<String>>(model: T, base_url: T, api_key: T) -> Self {
+        Self {
+            base_url: base_url.into(),
+            model: model.into(),
+            api_key: api_key.into(),
+            dimensions: None,
+            batch_size: DEFAULT_BATCH_SIZE,
+            http_client: None,
+            timeout: None,
+            retry: None,
+        }
+    }
+
+    pub fn base_url(mut self, base_url: String) -> Self {
+        self.base_url = base_url;
+        self
+    }
+
+    pub fn model(mut self, model: String) -> Self {
+        self.model = model;
+        self
+    }
+
+    /// 输出向量维度，仅 `text-embedding-3` 及之后的模型支持。未设置时使用模型默认维度。
+    pub fn dimensions(mut self, dimensions: u32) -> Self {
+        self.dimensions = Some(dimensions);
+        self
+    }
+
+    /// 单次请求包含的最大文本数，默认 2048。输入更多时自动拆分为多次请求。
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// 使用预先配置好的 HTTP 客户端（代理、连接池、自定义 TLS 等），多个实例可以共享同一个客户端。
+    ///
+    /// 未设置时使用 [`langchain_core::http::shared_client`]。传入客户端后，只有调用了
+    /// `timeout` 时才按请求覆盖客户端自身的超时。
+    pub fn http_client(mut self, client: reqwest::Client) -> Self {
+        self.http_client = Some(client);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// 请求失败时的重试策略，默认不重试。
+    pub fn retry(mut self, config: RetryConfig) -> Self {
+        self.retry = Some(config);
+        self
+    }
+
+    /// 校验参数并构建 [`OpenAIEmbeddings`]。
+    pub fn try_build(self) -> Result<OpenAIEmbeddings, ValidationError> {
+        if self.batch_size == 0 {
+            return Err(ValidationError::OutOfRange(
+                "batch_size must be greater than 0".to_owned(),
+            ));
+        }
+        if self.dimensions == Some(0) {
+            return Err(ValidationError::OutOfRange(
+                "dimensions must be greater than 0".to_owned(),
+            ));
+        }
+
+        Ok(OpenAIEmbeddings {
+            client: HttpClient::new(self.http_client, self.timeout, HeaderMap::new()),
+            base_url: self.base_url,
+            model: self.model,
+            api_key: self.api_key,
+            dimensions: self.dimensions,
+            batch_size: self.batch_size,
+            retry: self.retry,
+        })
+    }
+
+    /// 构建 [`OpenAIEmbeddings`]。
+    ///
+    /// # Panics
+    ///
+    /// 参数校验失败时 panic，需要处理错误时请使用 [`OpenAIEmbeddingsBuilder::try_build`]。
+    pub fn build(self) -> OpenAIEmbeddings {
+        self.try_build()
+            .expect("invalid OpenAIEmbeddings configuration")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{json_response, mock_server};
+
+    #[tokio::test]
+    async fn embed_splits_input_into_batches() {
+        let base_url = mock_server(vec![
+            json_response(
+                r#"{"data":[{"index":1,"embedding":[0.2,0.2]},{"index":0,"embedding":[0.1,0.1]}]}"#,
+            ),
+            json_response(r#"{"data":[{"index":0,"embedding":[0.3,0.3]}]}"#),
+        ])
+        .await;
+        let embedder = OpenAIEmbeddingsBuilder::from_base(
+            "text-embedding-3-small".to_owned(),
+            base_url,
+            "sk-test".to_owned(),
+        )
+        .dimensions(2)
+        .batch_size(2)
+        .build();
+
+        let texts = vec!["a".to_owned(), "b".to_owned(), "c".to_owned()];
+        let embeddings = embedder.embed(texts).await.unwrap();
+
+        assert_eq!(
+            embeddings,
+            vec![vec![0.1, 0.1], vec![0.2, 0.2], vec![0.3, 0.3]]
+        );
+        assert_eq!(embedder.dimensions(), Some(2));
+    }
+
+    #[test]
+    fn try_build_rejects_zero_batch_size() {
+        let result = OpenAIEmbeddingsBuilder::from_base("m", "http://localhost", "k")
+            .batch_size(0)
+            .try_build();
+        assert!(matches!(result, Err(ValidationError::OutOfRange(_))));
+    }
+}
@@ -1,4 +1,4 @@
-use langchain_core::error::ModelError;
+use langchain_core::error::{ErrorCategory, LangChainError, ModelError};
 use thiserror::Error;
 
 /// OpenAI API 错误
@@ -22,11 +22,42 @@ pub enum OpenAIError {
     /// 响应体解析错误
     #[error("响应体解析错误")]
     ResponseBodyParse(reqwest::Error),
+    /// 触发速率限制（HTTP 429），携带服务端 `Retry-After` 建议的等待秒数
+    #[error("触发速率限制，建议等待: {retry_after_secs:?} 秒")]
+    RateLimited { retry_after_secs: Option<u64> },
+    /// 服务端错误（HTTP 5xx）
+    #[error("服务端错误: status: {status}, body: {body}")]
+    Server { status: u16, body: String },
     /// 其他错误
     #[error("其他错误: {0}")]
     Other(String),
 }
 
+impl LangChainError for OpenAIError {
+    fn category(&self) -> ErrorCategory {
+        match self {
+            OpenAIError::InvalidApiKey => ErrorCategory::Authentication,
+            OpenAIError::InvalidHeaderValue(_) => ErrorCategory::Validation,
+            OpenAIError::ModelNotFound => ErrorCategory::Validation,
+            OpenAIError::Timeout => ErrorCategory::Transient,
+            OpenAIError::Http(_) => ErrorCategory::Transient,
+            OpenAIError::ResponseBodyParse(_) => ErrorCategory::Internal,
+            OpenAIError::RateLimited { .. } => ErrorCategory::RateLimit,
+            OpenAIError::Server { .. } => ErrorCategory::Transient,
+            OpenAIError::Other(_) => ErrorCategory::External,
+        }
+    }
+
+    fn retry_delay_ms(&self) -> Option<u64> {
+        match self {
+            OpenAIError::RateLimited { retry_after_secs } => {
+                retry_after_secs.map(|secs| secs.saturating_mul(1000))
+            }
+            _ => None,
+        }
+    }
+}
+
 impl From<OpenAIError> for ModelError {
     fn from(e: OpenAIError) -> Self {
         match e {
@@ -38,6 +69,12 @@ impl From<OpenAIError> for ModelError {
             OpenAIError::InvalidHeaderValue(s) => {
                 ModelError::Other(Box::new(OpenAIError::InvalidHeaderValue(s)))
             }
+            OpenAIError::RateLimited { retry_after_secs } => ModelError::RateLimited(
+                retry_after_secs
+                    .map(|secs| secs.min(u32::MAX as u64) as u32)
+                    .unwrap_or(0),
+            ),
+            OpenAIError::Server { status, body } => ModelError::ServerError(status, body),
             OpenAIError::Other(s) => ModelError::ResponseError(s),
         }
     }
@@ -1,138 +1,305 @@
 //! # OpenAI 标准实现
 //! 该模块实现了 OpenAI 标准的模型和工具调用，提供了与 OpenAI API 交互的功能。
 
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, OnceLock};
 use std::time::Duration;
 
 use futures_util::StreamExt;
 use langchain_core::{
-    error::ModelError,
-    message::Message,
+    error::{LangChainError, ModelError, RetryConfig, ValidationError, retry_with_backoff_hint},
+    http::shared_client,
+    message::{Content, ContentBlock, ImageDetail, Message},
     request::RequestBody,
-    response::ResponseBody,
-    response::Usage,
+    response::{OpenAINormalizer, ResponseNormalizer, Usage},
     state::{ChatCompletion, ChatModel, ChatStreamEvent, InvokeOptions, StandardChatStream},
+    tokenizer::{HeuristicTokenizer, Tokenizer},
 };
-use reqwest::header::{AUTHORIZATION, CONTENT_TYPE, HeaderMap, HeaderValue};
+use reqwest::header::{
+    AUTHORIZATION, CONTENT_TYPE, HeaderMap, HeaderName, HeaderValue, RETRY_AFTER,
+};
+use serde::Serialize;
 
 use crate::error::OpenAIError;
 
+mod embeddings;
 mod error;
+mod rerank;
+
+pub use embeddings::{OpenAIEmbeddings, OpenAIEmbeddingsBuilder};
+pub use rerank::{OpenAIReranker, OpenAIRerankerBuilder};
 
 pub const CHAT_COMPLETIONS: &str = "/chat/completions";
 
-pub struct ChatOpenAI {
+/// 没有设置超时时每个请求的超时
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// 发送请求使用的客户端，以及每个请求附带的超时和请求头
+#[derive(Clone)]
+pub(crate) struct HttpClient {
     client: reqwest::Client,
+    timeout: Option<Duration>,
+    headers: HeaderMap,
+}
+
+impl HttpClient {
+    /// 没有传入客户端时使用共享的客户端，超时默认 10 分钟；
+    /// 传入的客户端只在设置了 `timeout` 时按请求覆盖超时
+    pub(crate) fn new(
+        client: Option<reqwest::Client>,
+        timeout: Option<Duration>,
+        headers: HeaderMap,
+    ) -> Self {
+        let timeout = match client {
+            Some(_) => timeout,
+            None => Some(timeout.unwrap_or(DEFAULT_TIMEOUT)),
+        };
+        Self {
+            client: client.unwrap_or_else(shared_client),
+            timeout,
+            headers,
+        }
+    }
+}
+
+pub struct ChatOpenAI {
+    client: HttpClient,
     base_url: String,
     model: String,
     api_key: String,
     default_temperature: Option<f32>,
     default_max_tokens: Option<u32>,
     default_top_p: Option<f32>,
+    default_stop: Option<Vec<String>>,
+    default_frequency_penalty: Option<f32>,
+    default_presence_penalty: Option<f32>,
+    image_detail: Option<ImageDetail>,
+    seed: Option<u64>,
+    parallel_tool_calls: Option<bool>,
+    include_raw_response: bool,
+    /// 最近一次响应中的 `system_fingerprint`
+    system_fingerprint: Arc<Mutex<Option<String>>>,
+    retry: Option<RetryConfig>,
+    /// 第一次计数时按模型名创建，之后复用，避免每次都重新构建 BPE 词表
+    tokenizer: OnceLock<Arc<dyn Tokenizer>>,
 }
 
-#[async_trait::async_trait]
-impl ChatModel for ChatOpenAI {
-    async fn invoke(
-        &self,
-        messages: &[Arc<Message>],
-        options: &InvokeOptions<'_>,
-    ) -> Result<ChatCompletion, ModelError> {
+impl ChatOpenAI {
+    /// 最近一次响应返回的 `system_fingerprint`，服务端没有返回时为 `None`
+    ///
+    /// 使用 [`ChatOpenAIBuilder::seed`] 比较多次运行的结果时，指纹不同说明服务端配置发生了变化，
+    /// 结果不同是预期的。
+    pub fn system_fingerprint(&self) -> Option<String> {
+        self.system_fingerprint.lock().unwrap().clone()
+    }
+
+    /// 构建请求体，调用时传入的选项优先于构建时设置的默认值
+    fn build_request(&self, messages: &[Arc<Message>], options: &InvokeOptions<'_>) -> RequestBody {
         let tools = options.tools.unwrap_or(&[]).to_vec();
 
-        let mut request = RequestBody::from_model(&self.model).with_messages(messages.to_vec());
+        // 附加信息只在本地使用，不发送给接口
+        let messages = messages
+            .iter()
+            .map(|message| vision_content(message.without_metadata(), self.image_detail))
+            .collect();
+        let mut request = RequestBody::from_model(&self.model).with_messages(messages);
 
         // 应用配置选项
-        if let Some(temperature) = options.temperature.or(self.default_temperature) {
-            request.temperature = Some(temperature);
-        }
-        if let Some(max_tokens) = options.max_tokens.or(self.default_max_tokens) {
-            request.max_tokens = Some(max_tokens);
-        }
-        if let Some(top_p) = options.top_p.or(self.default_top_p) {
-            request.top_p = Some(top_p);
-        }
-        // OpenAI API 的 stop 参数是 String，我们取第一个或合并
-        if let Some(stop) = options.stop
-            && stop.is_empty()
-        {
-            request.stop = Some(stop.join(", "));
-        }
+        request.temperature = options.temperature.or(self.default_temperature);
+        request.max_tokens = options.max_tokens.or(self.default_max_tokens);
+        request.top_p = options.top_p.or(self.default_top_p);
+        request.frequency_penalty = options.frequency_penalty.or(self.default_frequency_penalty);
+        request.presence_penalty = options.presence_penalty.or(self.default_presence_penalty);
+        request.seed = self.seed;
+        request.stop = match options.stop {
+            Some(stop) if !stop.is_empty() => Some(stop.to_vec()),
+            _ => self.default_stop.clone(),
+        };
 
         if let Some(format) = options.response_format {
             request.response_format = Some(format.clone());
         }
 
+        // 工具选择与并行调用开关只能与 tools 一起发送
         if !tools.is_empty() {
             request = request.with_tools(tools);
+            request.tool_choice = options.tool_choice.clone();
+            request.parallel_tool_calls = self.parallel_tool_calls;
         }
 
-        if let Some(tool_choice) = &options.tool_choice {
-            request.tool_choice = Some(tool_choice.clone());
+        request
+    }
+
+    /// 发送请求并检查状态码，配置了重试策略时对可重试的错误自动重试
+    async fn send(&self, request: &RequestBody) -> Result<reqwest::Response, OpenAIError> {
+        post_json(
+            &self.client,
+            &format!("{}{CHAT_COMPLETIONS}", self.base_url),
+            &self.api_key,
+            request,
+            self.retry.as_ref(),
+        )
+        .await
+    }
+}
+
+/// 记录响应中的 `system_fingerprint`，响应中没有时保留之前的值
+fn record_fingerprint(last: &Mutex<Option<String>>, fingerprint: Option<String>) {
+    if fingerprint.is_some() {
+        *last.lock().unwrap() = fingerprint;
+    }
+}
+
+/// 把用户消息中的图片转换为视觉输入的 `image_url` 内容块，未指定精度的图片使用 `detail`
+fn vision_content(message: Arc<Message>, detail: Option<ImageDetail>) -> Arc<Message> {
+    let Message::User { content, .. } = message.as_ref() else {
+        return message;
+    };
+    let needs_detail = |block: &ContentBlock| matches!(block, ContentBlock::ImageUrl { image_url } if image_url.detail.is_none());
+    match content {
+        Content::Image { .. } => {}
+        Content::Mixed(blocks) if detail.is_some() && blocks.iter().any(needs_detail) => {}
+        _ => return message,
+    }
+
+    let mut message = message.as_ref().clone();
+    if let Message::User { content, .. } = &mut message {
+        if let Content::Image { url } = content {
+            *content = Content::Mixed(vec![ContentBlock::image(std::mem::take(url))]);
+        }
+        if let (Content::Mixed(blocks), Some(detail)) = (content, detail) {
+            for block in blocks {
+                if let ContentBlock::ImageUrl { image_url } = block {
+                    image_url.detail.get_or_insert(detail);
+                }
+            }
+        }
+    }
+    Arc::new(message)
+}
+
+/// 以 JSON 请求体 POST 到 `url` 并检查状态码
+///
+/// 传入 `retry` 时对可重试的错误（429、5xx、网络错误）按 [`RetryConfig`] 重试，
+/// 429 优先使用 `Retry-After` 指定的等待时间。
+pub(crate) async fn post_json<B: Serialize>(
+    client: &HttpClient,
+    url: &str,
+    api_key: &str,
+    body: &B,
+    retry: Option<&RetryConfig>,
+) -> Result<reqwest::Response, OpenAIError> {
+    match retry {
+        Some(config) => {
+            retry_with_backoff_hint(
+                || post_json_once(client, url, api_key, body),
+                LangChainError::category,
+                LangChainError::retry_delay_ms,
+                config,
+            )
+            .await
         }
+        None => post_json_once(client, url, api_key, body).await,
+    }
+}
+
+async fn post_json_once<B: Serialize>(
+    client: &HttpClient,
+    url: &str,
+    api_key: &str,
+    body: &B,
+) -> Result<reqwest::Response, OpenAIError> {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {api_key}"))
+            .map_err(|e| OpenAIError::InvalidHeaderValue(e.to_string()))?,
+    );
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+    let mut request = client
+        .client
+        .post(url)
+        .headers(client.headers.clone())
+        .headers(headers)
+        .json(body);
+    if let Some(timeout) = client.timeout {
+        request = request.timeout(timeout);
+    }
+    let response = request.send().await.map_err(|e| {
+        if e.is_timeout() {
+            OpenAIError::Timeout
+        } else {
+            OpenAIError::Http(e)
+        }
+    })?;
+
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let retry_after_secs = parse_retry_after(response.headers());
+    let body = response
+        .text()
+        .await
+        .unwrap_or_else(|e| format!("failed to read error body: {e}"));
+    tracing::error!("OpenAI API error: status = {status}, body = {body}");
+    Err(match status.as_u16() {
+        401 => OpenAIError::InvalidApiKey,
+        404 => OpenAIError::ModelNotFound,
+        429 => OpenAIError::RateLimited { retry_after_secs },
+        code if status.is_server_error() => OpenAIError::Server { status: code, body },
+        _ => OpenAIError::Other(format!("status: {status}, body: {body}")),
+    })
+}
+
+/// 解析 `Retry-After` 响应头，仅支持秒数形式
+fn parse_retry_after(headers: &HeaderMap) -> Option<u64> {
+    headers
+        .get(RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok())
+}
+
+#[async_trait::async_trait]
+impl ChatModel for ChatOpenAI {
+    async fn invoke(
+        &self,
+        messages: &[Arc<Message>],
+        options: &InvokeOptions<'_>,
+    ) -> Result<ChatCompletion, ModelError> {
+        let request = self.build_request(messages, options);
 
         tracing::debug!(
             "OpenAI API request: {}",
             serde_json::to_string_pretty(&request).unwrap()
         );
 
-        let mut headers = HeaderMap::new();
-        headers.insert(
-            AUTHORIZATION,
-            HeaderValue::from_str(&format!("Bearer {}", self.api_key))
-                .map_err(|e| OpenAIError::InvalidHeaderValue(e.to_string()))?,
-        );
-        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-
-        let response = self
-            .client
-            .post(format!("{}{CHAT_COMPLETIONS}", self.base_url))
-            .headers(headers)
-            .json(&request)
-            .send()
-            .await
-            .map_err(|e| {
-                if e.is_timeout() {
-                    OpenAIError::Timeout
-                } else {
-                    OpenAIError::Http(e)
-                }
-            })?;
-
-        let status = response.status();
-        if !status.is_success() {
-            let body = response
-                .text()
-                .await
-                .unwrap_or_else(|e| format!("failed to read error body: {e}"));
-            tracing::error!("OpenAI API error: status = {status}, body = {body}");
-            let error = match status.as_u16() {
-                401 => OpenAIError::InvalidApiKey,
-                404 => OpenAIError::ModelNotFound,
-                _ => OpenAIError::Other(format!("status: {status}, body: {body}")),
-            };
-            return Err(error.into());
-        }
+        let response = self.send(&request).await?;
 
-        let response: ResponseBody = response
-            .json::<ResponseBody>()
+        let body: serde_json::Value = response
+            .json()
             .await
             .map_err(OpenAIError::ResponseBodyParse)?;
 
-        tracing::debug!("OpenAI API response: {:?}", response);
-
-        let messages = response
-            .choices
-            .iter()
-            .map(|c| Arc::new(c.message.clone()))
-            .collect::<Vec<_>>();
+        tracing::debug!("OpenAI API response: {:?}", body);
+        record_fingerprint(
+            &self.system_fingerprint,
+            body["system_fingerprint"].as_str().map(str::to_owned),
+        );
 
-        if messages.is_empty() {
+        let mut response = OpenAINormalizer
+            .normalize(&body)
+            .map_err(|e| ModelError::ResponseError(e.to_string()))?;
+        if self.include_raw_response {
+            response = response.with_raw(&body);
+        }
+        if response.messages.is_empty() {
             return Err(OpenAIError::Other("no choices in response".to_owned()).into());
         }
 
         Ok(ChatCompletion {
-            messages,
+            messages: response.messages.into_iter().map(Arc::new).collect(),
             usage: response.usage,
         })
     }
@@ -142,31 +309,7 @@ impl ChatModel for ChatOpenAI {
         messages: &[Arc<Message>],
         options: &InvokeOptions<'_>,
     ) -> Result<StandardChatStream, ModelError> {
-        let tools = options.tools.unwrap_or(&[]).to_vec();
-
-        let mut request = RequestBody::from_model(&self.model).with_messages(messages.to_vec());
-
-        // 应用配置选项
-        if let Some(temperature) = options.temperature.or(self.default_temperature) {
-            request.temperature = Some(temperature);
-        }
-        if let Some(max_tokens) = options.max_tokens.or(self.default_max_tokens) {
-            request.max_tokens = Some(max_tokens);
-        }
-        if let Some(top_p) = options.top_p.or(self.default_top_p) {
-            request.top_p = Some(top_p);
-        }
-        // OpenAI API 的 stop 参数是 String，我们取第一个或合并
-        if let Some(stop) = options.stop
-            && stop.is_empty()
-        {
-            request.stop = Some(stop.join(", "));
-        }
-
-        if !tools.is_empty() {
-            request = request.with_tools(tools);
-        }
-
+        let mut request = self.build_request(messages, options);
         request.stream = true;
 
         tracing::debug!(
@@ -174,43 +317,8 @@ impl ChatModel for ChatOpenAI {
             serde_json::to_string_pretty(&request).unwrap()
         );
 
-        let mut headers = HeaderMap::new();
-        headers.insert(
-            AUTHORIZATION,
-            HeaderValue::from_str(&format!("Bearer {}", self.api_key))
-                .map_err(|e| OpenAIError::InvalidHeaderValue(e.to_string()))?,
-        );
-        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-
-        let response = self
-            .client
-            .post(format!("{}{CHAT_COMPLETIONS}", self.base_url))
-            .headers(headers)
-            .json(&request)
-            .send()
-            .await
-            .map_err(|e| {
-                if e.is_timeout() {
-                    OpenAIError::Timeout
-                } else {
-                    OpenAIError::Http(e)
-                }
-            })?;
-
-        let status = response.status();
-        if !status.is_success() {
-            let body = response
-                .text()
-                .await
-                .unwrap_or_else(|e| format!("failed to read error body: {e}"));
-            tracing::error!("OpenAI API error: status = {status}, body = {body}");
-            let error = match status.as_u16() {
-                401 => OpenAIError::InvalidApiKey,
-                404 => OpenAIError::ModelNotFound,
-                _ => OpenAIError::Other(format!("status: {status}, body: {body}")),
-            };
-            return Err(error.into());
-        }
+        let response = self.send(&request).await?;
+        let fingerprint = self.system_fingerprint.clone();
 
         let stream = async_stream::try_stream! {
             let mut buffer = String::new();
@@ -236,6 +344,10 @@ impl ChatModel for ChatOpenAI {
 
                     let value: serde_json::Value =
                         serde_json::from_str(&data).map_err(|e| OpenAIError::Other(e.to_string()))?;
+                    record_fingerprint(
+                        &fingerprint,
+                        value.get("system_fingerprint").and_then(|f| f.as_str()).map(str::to_owned),
+                    );
 
                     let usage = value
                         .get("usage")
@@ -274,7 +386,7 @@ impl ChatModel for ChatOpenAI {
                             if let Some(reasoning_content) = delta.get("reasoning_content").and_then(|c| c.as_str())
                                 && !reasoning_content.is_empty()
                             {
-                                yield ChatStreamEvent::ReasoningContent(reasoning_content.to_owned());
+                                yield ChatStreamEvent::Reasoning(reasoning_content.to_owned());
                             }
 
                             if let Some(tool_calls) = delta.get("tool_calls").and_then(|t| t.as_array()) {
@@ -323,6 +435,21 @@ impl ChatModel for ChatOpenAI {
 
         Ok(Box::pin(stream))
     }
+
+    /// 启用 `tiktoken` feature 时按模型名使用精确的分词器，未知模型退回到估算
+    fn tokenizer(&self) -> Arc<dyn Tokenizer> {
+        self.tokenizer
+            .get_or_init(|| {
+                #[cfg(feature = "tiktoken")]
+                if let Some(tokenizer) =
+                    langchain_core::tokenizer::TiktokenTokenizer::for_model(&self.model)
+                {
+                    return Arc::new(tokenizer);
+                }
+                Arc::new(HeuristicTokenizer)
+            })
+            .clone()
+    }
 }
 
 fn split_sse_event(buffer: &str) -> Option<(String, String)> {
@@ -354,7 +481,17 @@ pub struct ChatOpenAIBuilder {
     temperature: Option<f32>,
     max_tokens: Option<u32>,
     top_p: Option<f32>,
+    stop: Option<Vec<String>>,
+    frequency_penalty: Option<f32>,
+    presence_penalty: Option<f32>,
+    image_detail: Option<ImageDetail>,
+    seed: Option<u64>,
+    parallel_tool_calls: Option<bool>,
+    include_raw_response: bool,
+    headers: Vec<(String, String)>,
+    http_client: Option<reqwest::Client>,
     timeout: Option<Duration>,
+    retry: Option<RetryConfig>,
 }
 
 impl ChatOpenAIBuilder {
@@ -366,7 +503,17 @@ impl ChatOpenAIBuilder {
             temperature: None,
             max_tokens: None,
             top_p: None,
+            stop: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            image_detail: None,
+            seed: None,
+            parallel_tool_calls: None,
+            include_raw_response: false,
+            headers: Vec::new(),
+            http_client: None,
             timeout: None,
+            retry: None,
         }
     }
 
@@ -380,41 +527,183 @@ impl ChatOpenAIBuilder {
         self
     }
 
+    /// 采样温度，取值范围 `[0, 2]`。未设置时由服务端决定（通常为 1）。
     pub fn temperature(mut self, temperature: f32) -> Self {
         self.temperature = Some(temperature);
         self
     }
 
+    /// 单次生成的最大 token 数，必须大于 0。未设置时由服务端决定。
     pub fn max_tokens(mut self, max_tokens: u32) -> Self {
         self.max_tokens = Some(max_tokens);
         self
     }
 
+    /// 核采样概率，取值范围 `[0, 1]`。
     pub fn top_p(mut self, top_p: f32) -> Self {
         self.top_p = Some(top_p);
         self
     }
 
+    /// 停止序列，遇到任一序列时停止生成。
+    pub fn stop<I, S>(mut self, stop: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.stop = Some(stop.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// 频率惩罚，取值范围 `[-2, 2]`。
+    pub fn frequency_penalty(mut self, frequency_penalty: f32) -> Self {
+        self.frequency_penalty = Some(frequency_penalty);
+        self
+    }
+
+    /// 存在惩罚，取值范围 `[-2, 2]`。
+    pub fn presence_penalty(mut self, presence_penalty: f32) -> Self {
+        self.presence_penalty = Some(presence_penalty);
+        self
+    }
+
+    /// 随机种子。相同的种子、参数和输入会尽量返回相同的结果，配合 `temperature(0.0)`
+    /// 可以让评测更稳定。
+    ///
+    /// 确定性只是尽力而为（API 不作保证）：服务端配置变化时结果仍可能不同，
+    /// 可以通过 [`ChatOpenAI::system_fingerprint`] 判断。
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// 是否允许模型在一次回复中并行调用多个工具，未设置时由服务端决定（默认允许）。
+    ///
+    /// 设为 `false` 时模型每轮至多调用一个工具，适合需要严格按顺序使用工具的流程。
+    /// 只在请求带有工具时发送。
+    pub fn parallel_tool_calls(mut self, enabled: bool) -> Self {
+        self.parallel_tool_calls = Some(enabled);
+        self
+    }
+
+    /// 在 `invoke` 返回的消息的附加信息中保留完整的原始响应体，键为
+    /// [`RAW_RESPONSE_METADATA_KEY`](langchain_core::response::RAW_RESPONSE_METADATA_KEY)
+    pub fn include_raw_response(mut self, enabled: bool) -> Self {
+        self.include_raw_response = enabled;
+        self
+    }
+
+    /// 图片的默认解析精度，只作用于没有单独指定精度的图片。未设置时由服务端决定（`auto`）。
+    pub fn image_detail(mut self, detail: ImageDetail) -> Self {
+        self.image_detail = Some(detail);
+        self
+    }
+
+    /// 发送 `OpenAI-Organization` 请求头，用于属于多个组织的账号指定计费组织。
+    pub fn organization(self, organization: impl Into<String>) -> Self {
+        self.header("OpenAI-Organization", organization)
+    }
+
+    /// 每个请求都附带的请求头，例如网关或代理要求的鉴权头。同名的请求头以最后一次设置为准。
+    ///
+    /// 名称或值不合法时 [`ChatOpenAIBuilder::try_build`] 返回错误。
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// 使用预先配置好的 HTTP 客户端（代理、连接池、自定义 TLS 等），多个实例可以共享同一个客户端。
+    ///
+    /// 未设置时使用 [`langchain_core::http::shared_client`]。传入客户端后，只有调用了
+    /// `timeout` 时才按请求覆盖客户端自身的超时。
+    pub fn http_client(mut self, client: reqwest::Client) -> Self {
+        self.http_client = Some(client);
+        self
+    }
+
     pub fn timeout(mut self, timeout: Duration) -> Self {
         self.timeout = Some(timeout);
         self
     }
 
-    pub fn build(self) -> ChatOpenAI {
-        let timeout = self.timeout.unwrap_or_else(|| Duration::from_secs(600));
-        let client = reqwest::Client::builder()
-            .timeout(timeout)
-            .build()
-            .expect("failed to build reqwest client");
-        ChatOpenAI {
-            client,
+    /// 请求失败时的重试策略，默认不重试。
+    ///
+    /// 429 与 5xx 会被重试，其中 429 优先按 `Retry-After` 指定的时间等待；
+    /// 其余 4xx 错误直接返回。流式调用只在建立连接阶段重试。
+    pub fn retry(mut self, config: RetryConfig) -> Self {
+        self.retry = Some(config);
+        self
+    }
+
+    /// 校验生成参数并构建 [`ChatOpenAI`]。
+    ///
+    /// 参数超出 OpenAI API 允许的范围时返回 [`ValidationError`]。
+    pub fn try_build(self) -> Result<ChatOpenAI, ValidationError> {
+        check_range("temperature", self.temperature, 0.0, 2.0)?;
+        check_range("top_p", self.top_p, 0.0, 1.0)?;
+        check_range("frequency_penalty", self.frequency_penalty, -2.0, 2.0)?;
+        check_range("presence_penalty", self.presence_penalty, -2.0, 2.0)?;
+        if self.max_tokens == Some(0) {
+            return Err(ValidationError::OutOfRange(
+                "max_tokens must be greater than 0".to_owned(),
+            ));
+        }
+        if let Some(stop) = &self.stop
+            && stop.iter().any(|s| s.is_empty())
+        {
+            return Err(ValidationError::InvalidInput(
+                "stop sequences must not be empty".to_owned(),
+            ));
+        }
+
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            let name = HeaderName::from_bytes(name.as_bytes()).map_err(|e| {
+                ValidationError::InvalidInput(format!("invalid header name {name:?}: {e}"))
+            })?;
+            let value = HeaderValue::from_str(value).map_err(|e| {
+                ValidationError::InvalidInput(format!("invalid value for header {name}: {e}"))
+            })?;
+            headers.insert(name, value);
+        }
+
+        Ok(ChatOpenAI {
+            client: HttpClient::new(self.http_client, self.timeout, headers),
             base_url: self.base_url,
             model: self.model,
             api_key: self.api_key,
             default_temperature: self.temperature,
             default_max_tokens: self.max_tokens,
             default_top_p: self.top_p,
-        }
+            default_stop: self.stop.filter(|stop| !stop.is_empty()),
+            default_frequency_penalty: self.frequency_penalty,
+            default_presence_penalty: self.presence_penalty,
+            image_detail: self.image_detail,
+            seed: self.seed,
+            parallel_tool_calls: self.parallel_tool_calls,
+            include_raw_response: self.include_raw_response,
+            system_fingerprint: Arc::default(),
+            retry: self.retry,
+            tokenizer: OnceLock::new(),
+        })
+    }
+
+    /// 构建 [`ChatOpenAI`]。
+    ///
+    /// # Panics
+    ///
+    /// 参数校验失败时 panic，需要处理错误时请使用 [`ChatOpenAIBuilder::try_build`]。
+    pub fn build(self) -> ChatOpenAI {
+        self.try_build().expect("invalid ChatOpenAI configuration")
+    }
+}
+
+fn check_range(name: &str, value: Option<f32>, min: f32, max: f32) -> Result<(), ValidationError> {
+    match value {
+        Some(v) if !(min..=max).contains(&v) => Err(ValidationError::OutOfRange(format!(
+            "{name} must be between {min} and {max}, got {v}"
+        ))),
+        _ => Ok(()),
     }
 }
 
@@ -422,9 +711,362 @@ impl ChatOpenAIBuilder {
 mod tests {
     use super::*;
     use langchain_core::message::Message;
+    use langchain_core::request::{ToolFunction, ToolSpec};
     use langchain_core::state::InvokeOptions;
+    use langchain_core::testing::MockHttpServer;
+    pub(crate) use langchain_core::testing::json_response;
     use std::sync::Arc;
 
+    fn builder() -> ChatOpenAIBuilder {
+        ChatOpenAIBuilder::from_base("gpt-4o-mini", "http://localhost", "sk-test")
+    }
+
+    #[test]
+    fn try_build_rejects_out_of_range_parameters() {
+        assert!(matches!(
+            builder().temperature(2.5).try_build(),
+            Err(ValidationError::OutOfRange(_))
+        ));
+        assert!(matches!(
+            builder().top_p(-0.1).try_build(),
+            Err(ValidationError::OutOfRange(_))
+        ));
+        assert!(matches!(
+            builder().presence_penalty(3.0).try_build(),
+            Err(ValidationError::OutOfRange(_))
+        ));
+        assert!(matches!(
+            builder().max_tokens(0).try_build(),
+            Err(ValidationError::OutOfRange(_))
+        ));
+        assert!(matches!(
+            builder().stop([""]).try_build(),
+            Err(ValidationError::InvalidInput(_))
+        ));
+        assert!(builder().temperature(f32::NAN).try_build().is_err());
+    }
+
+    #[test]
+    fn tokenizer_is_built_once() {
+        let model = builder().build();
+        let first = model.tokenizer();
+        assert!(Arc::ptr_eq(&first, &model.tokenizer()));
+        assert!(first.count_tokens("hello world") > 0);
+    }
+
+    #[test]
+    fn build_request_applies_defaults_and_overrides() {
+        let client = builder()
+            .temperature(0.0)
+            .top_p(0.9)
+            .max_tokens(256)
+            .stop(["\n\n"])
+            .frequency_penalty(0.5)
+            .presence_penalty(-0.5)
+            .try_build()
+            .unwrap();
+        let messages = vec![Arc::new(Message::user("hello"))];
+
+        let request = client.build_request(&messages, &InvokeOptions::default());
+        assert_eq!(request.temperature, Some(0.0));
+        assert_eq!(request.top_p, Some(0.9));
+        assert_eq!(request.max_tokens, Some(256));
+        assert_eq!(request.stop, Some(vec!["\n\n".to_owned()]));
+        assert_eq!(request.frequency_penalty, Some(0.5));
+        assert_eq!(request.presence_penalty, Some(-0.5));
+
+        let stop = vec!["END".to_owned(), "STOP".to_owned()];
+        let options = InvokeOptions {
+            temperature: Some(1.2),
+            stop: Some(&stop),
+            ..Default::default()
+        };
+        let request = client.build_request(&messages, &options);
+        assert_eq!(request.temperature, Some(1.2));
+        assert_eq!(request.stop, Some(stop));
+    }
+
+    #[test]
+    fn parallel_tool_calls_is_sent_only_with_tools() {
+        let client = builder().parallel_tool_calls(false).try_build().unwrap();
+        let messages = vec![Arc::new(Message::user("hello"))];
+
+        let body = serde_json::to_value(client.build_request(&messages, &InvokeOptions::default()))
+            .unwrap();
+        assert!(body.get("parallel_tool_calls").is_none());
+
+        let tools = vec![ToolSpec::Function {
+            function: ToolFunction {
+                name: "add".to_owned(),
+                description: String::new(),
+                parameters: serde_json::json!({"type": "object"}),
+                examples: Vec::new(),
+                output_schema: None,
+            },
+        }];
+        let options = InvokeOptions {
+            tools: Some(&tools),
+            ..Default::default()
+        };
+        let body = serde_json::to_value(client.build_request(&messages, &options)).unwrap();
+        assert_eq!(body["parallel_tool_calls"], false);
+
+        // 未设置时不发送，由服务端决定
+        let body =
+            serde_json::to_value(builder().build().build_request(&messages, &options)).unwrap();
+        assert!(body.get("parallel_tool_calls").is_none());
+    }
+
+    #[test]
+    fn build_request_drops_message_metadata() {
+        let client = builder().try_build().unwrap();
+        let messages = vec![Arc::new(
+            Message::user("hello").with_metadata("trace_id", serde_json::json!("t-1")),
+        )];
+
+        let request = client.build_request(&messages, &InvokeOptions::default());
+        let body = serde_json::to_value(&request).unwrap();
+
+        assert_eq!(
+            body["messages"][0],
+            serde_json::json!({"role": "user", "content": "hello"})
+        );
+        // 调用方持有的消息保留附加信息
+        assert_eq!(messages[0].metadata()["trace_id"], "t-1");
+    }
+
+    #[test]
+    fn build_request_serializes_images_as_vision_content() {
+        let client = builder()
+            .image_detail(ImageDetail::Low)
+            .try_build()
+            .unwrap();
+        let messages = vec![
+            Arc::new(Message::user_with_content_blocks(vec![
+                ContentBlock::text("What is in this image?"),
+                ContentBlock::image("https://example.com/a.png"),
+                ContentBlock::image("https://example.com/b.png").with_detail(ImageDetail::High),
+            ])),
+            Arc::new(Message::User {
+                content: Content::Image {
+                    url: "https://example.com/c.png".to_owned(),
+                },
+                name: None,
+                metadata: Default::default(),
+            }),
+        ];
+
+        let request = client.build_request(&messages, &InvokeOptions::default());
+        let body = serde_json::to_value(&request).unwrap();
+
+        assert_eq!(
+            body["messages"][0]["content"],
+            serde_json::json!([
+                {"type": "text", "text": "What is in this image?"},
+                {"type": "image_url", "image_url": {"url": "https://example.com/a.png", "detail": "low"}},
+                {"type": "image_url", "image_url": {"url": "https://example.com/b.png", "detail": "high"}},
+            ])
+        );
+        assert_eq!(
+            body["messages"][1]["content"],
+            serde_json::json!([
+                {"type": "image_url", "image_url": {"url": "https://example.com/c.png", "detail": "low"}},
+            ])
+        );
+    }
+
+    /// 启动一个按顺序返回给定原始 HTTP 响应的本地服务，返回其 base_url
+    pub(crate) async fn mock_server(responses: Vec<String>) -> String {
+        MockHttpServer::start(responses).await.url().to_owned()
+    }
+
+    const RATE_LIMITED: &str = "HTTP/1.1 429 Too Many Requests\r\nRetry-After: 2\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+
+    fn completion_response() -> String {
+        json_response(
+            r#"{"id":"1","object":"chat.completion","created":0,"model":"gpt-4o-mini","choices":[{"index":0,"message":{"role":"assistant","content":"hi"},"finish_reason":"stop"}],"usage":{"prompt_tokens":1,"completion_tokens":1,"total_tokens":2}}"#,
+        )
+    }
+
+    #[tokio::test]
+    async fn rate_limited_response_carries_retry_after() {
+        let base_url = mock_server(vec![RATE_LIMITED.to_owned()]).await;
+        let client = builder().base_url(base_url).build();
+        let request = RequestBody::from_model("gpt-4o-mini");
+
+        let err = client.send(&request).await.unwrap_err();
+        assert!(matches!(
+            err,
+            OpenAIError::RateLimited {
+                retry_after_secs: Some(2)
+            }
+        ));
+        assert!(err.is_retryable());
+        assert_eq!(err.retry_delay_ms(), Some(2000));
+        assert!(matches!(ModelError::from(err), ModelError::RateLimited(2)));
+    }
+
+    #[tokio::test]
+    async fn invoke_waits_for_retry_after_before_retrying() {
+        let base_url = mock_server(vec![RATE_LIMITED.to_owned(), completion_response()]).await;
+        let client = builder()
+            .base_url(base_url)
+            .retry(RetryConfig {
+                max_retries: 1,
+                initial_delay_ms: 0,
+                ..Default::default()
+            })
+            .build();
+        let messages = vec![Arc::new(Message::user("hello"))];
+
+        let started = std::time::Instant::now();
+        let completion = client
+            .invoke(&messages, &InvokeOptions::default())
+            .await
+            .unwrap();
+
+        assert!(started.elapsed() >= Duration::from_secs(2));
+        assert_eq!(completion.messages.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn organization_and_custom_headers_are_sent_with_every_request() {
+        let server = MockHttpServer::start(vec![completion_response()]).await;
+        let client = builder()
+            .base_url(server.url().to_owned())
+            .organization("org-123")
+            .header("X-Gateway-Key", "secret")
+            .build();
+        let messages = vec![Arc::new(Message::user("hello"))];
+
+        client
+            .invoke(&messages, &InvokeOptions::default())
+            .await
+            .unwrap();
+
+        let request = server.requests()[0].to_ascii_lowercase();
+        assert!(request.contains("openai-organization: org-123"));
+        assert!(request.contains("x-gateway-key: secret"));
+        assert!(request.contains("authorization: bearer sk-test"));
+
+        assert!(matches!(
+            builder().header("bad header", "x").try_build(),
+            Err(ValidationError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            builder().header("X-Token", "line\nbreak").try_build(),
+            Err(ValidationError::InvalidInput(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn injected_http_client_is_used_with_builder_headers() {
+        let server = MockHttpServer::start(vec![completion_response()]).await;
+        let mut default_headers = HeaderMap::new();
+        default_headers.insert("x-proxy-tag", HeaderValue::from_static("shared"));
+        let http = reqwest::Client::builder()
+            .default_headers(default_headers)
+            .build()
+            .unwrap();
+        let client = builder()
+            .base_url(server.url().to_owned())
+            .http_client(http)
+            .header("X-Gateway-Key", "secret")
+            .build();
+
+        client
+            .invoke(
+                &[Arc::new(Message::user("hello"))],
+                &InvokeOptions::default(),
+            )
+            .await
+            .unwrap();
+
+        let request = server.requests()[0].to_ascii_lowercase();
+        assert!(request.contains("x-proxy-tag: shared"));
+        assert!(request.contains("x-gateway-key: secret"));
+        assert!(request.contains("authorization: bearer sk-test"));
+    }
+
+    #[tokio::test]
+    async fn seed_is_sent_and_fingerprint_is_recorded() {
+        let server = MockHttpServer::start(vec![json_response(
+            r#"{"id":"1","object":"chat.completion","created":0,"model":"gpt-4o-mini","system_fingerprint":"fp_44709d6fcb","choices":[{"index":0,"message":{"role":"assistant","content":"hi"},"finish_reason":"stop"}],"usage":{"prompt_tokens":1,"completion_tokens":1,"total_tokens":2}}"#,
+        )])
+        .await;
+        let client = builder().base_url(server.url().to_owned()).seed(42).build();
+        assert_eq!(client.system_fingerprint(), None);
+
+        let messages = vec![Arc::new(Message::user("hello"))];
+        client
+            .invoke(&messages, &InvokeOptions::default())
+            .await
+            .unwrap();
+
+        assert!(server.requests()[0].contains(r#""seed":42"#));
+        assert_eq!(
+            client.system_fingerprint().as_deref(),
+            Some("fp_44709d6fcb")
+        );
+    }
+
+    #[tokio::test]
+    async fn tool_calls_are_normalized_and_raw_response_is_optional() {
+        let body = r#"{"id":"1","object":"chat.completion","created":0,"model":"gpt-4o-mini","choices":[{"index":0,"message":{"role":"assistant","content":null,"tool_calls":[{"id":"call_1","type":"function","function":{"name":"get_weather","arguments":"{\"city\":\"Paris\"}"}}]},"finish_reason":"tool_calls"}],"usage":{"prompt_tokens":1,"completion_tokens":1,"total_tokens":2}}"#;
+        let base_url = mock_server(vec![json_response(body), json_response(body)]).await;
+        let messages = vec![Arc::new(Message::user("weather?"))];
+
+        let client = builder().base_url(base_url.clone()).build();
+        let completion = client
+            .invoke(&messages, &InvokeOptions::default())
+            .await
+            .unwrap();
+        let Message::Assistant {
+            content,
+            tool_calls: Some(calls),
+            metadata,
+            ..
+        } = completion.messages[0].as_ref()
+        else {
+            panic!("expected tool calls");
+        };
+        assert!(content.is_empty());
+        assert_eq!(
+            calls[0].arguments().unwrap(),
+            serde_json::json!({"city": "Paris"})
+        );
+        assert!(metadata.is_empty());
+
+        let client = builder()
+            .base_url(base_url)
+            .include_raw_response(true)
+            .build();
+        let completion = client
+            .invoke(&messages, &InvokeOptions::default())
+            .await
+            .unwrap();
+        let raw =
+            &completion.messages[0].metadata()[langchain_core::response::RAW_RESPONSE_METADATA_KEY];
+        assert_eq!(raw["id"], "1");
+    }
+
+    #[tokio::test]
+    async fn client_errors_are_not_retried() {
+        let base_url = mock_server(vec![
+            "HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_owned(),
+        ])
+        .await;
+        let client = builder()
+            .base_url(base_url)
+            .retry(RetryConfig::default())
+            .build();
+        let request = RequestBody::from_model("gpt-4o-mini");
+
+        let err = client.send(&request).await.unwrap_err();
+        assert!(matches!(err, OpenAIError::Other(_)));
+        assert!(!err.is_retryable());
+    }
+
     #[tokio::test]
     #[ignore]
     async fn invoke_with_real_openai() {
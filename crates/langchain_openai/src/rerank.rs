@@ -0,0 +1,228 @@
+//! Cohere 风格的 `/rerank` 接口实现
+//!
+//! OpenAI 本身没有重排序接口，Cohere、Jina、SiliconFlow、vLLM 等服务使用相同的请求格式：
+//! `{model, query, documents, top_n}`，返回 `results: [{index, relevance_score}]`。
+
+use std::time::Duration;
+
+use langchain_core::{
+    error::{ModelError, RetryConfig, ValidationError},
+    reranker::Reranker,
+};
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{HttpClient, error::OpenAIError, post_json};
+
+pub const RERANK: &str = "/rerank";
+
+pub struct OpenAIReranker {
+    client: HttpClient,
+    base_url: String,
+    model: String,
+    api_key: String,
+    top_n: Option<usize>,
+    retry: Option<RetryConfig>,
+}
+
+#[derive(Serialize)]
+struct RerankRequest<'a> {
+    model: &'a str,
+    query: &'a str,
+    documents: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_n: Option<usize>,
+    return_documents: bool,
+}
+
+#[derive(Deserialize)]
+struct RerankResponse {
+    results: Vec<RerankResult>,
+}
+
+#[derive(Deserialize)]
+struct RerankResult {
+    index: usize,
+    relevance_score: f32,
+}
+
+#[async_trait::async_trait]
+impl Reranker for OpenAIReranker {
+    async fn rerank(
+        &self,
+        query: &str,
+        documents: &[String],
+    ) -> Result<Vec<(usize, f32)>, ModelError> {
+        if documents.is_empty() {
+            return Ok(Vec::new());
+        }
+        let request = RerankRequest {
+            model: &self.model,
+            query,
+            documents,
+            top_n: self.top_n,
+            return_documents: false,
+        };
+
+        let response = post_json(
+            &self.client,
+            &format!("{}{RERANK}", self.base_url),
+            &self.api_key,
+            &request,
+            self.retry.as_ref(),
+        )
+        .await?;
+
+        let response = response
+            .json::<RerankResponse>()
+            .await
+            .map_err(OpenAIError::ResponseBodyParse)?;
+
+        if let Some(result) = response.results.iter().find(|r| r.index >= documents.len()) {
+            return Err(OpenAIError::Other(format!(
+                "rerank result index {} out of range for {} documents",
+                result.index,
+                documents.len()
+            ))
+            .into());
+        }
+
+        // 服务端通常已经按分数排序，这里不依赖这一点
+        let mut ranked: Vec<_> = response
+            .results
+            .into_iter()
+            .map(|r| (r.index, r.relevance_score))
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        Ok(ranked)
+    }
+}
+
+pub struct OpenAIRerankerBuilder {
+    base_url: String,
+    model: String,
+    api_key: String,
+    top_n: Option<usize>,
+    http_client: Option<reqwest::Client>,
+    timeout: Option<Duration>,
+    retry: Option<RetryConfig>,
+}
+
+impl OpenAIRerankerBuilder {
+    pub fn from_base<T: Into<String>>(model: T, base_url: T, api_key: T) -> Self {
+        Self {
+            base_url: base_url.into(),
+            model: model.into(),
+            api_key: api_key.into(),
+            top_n: None,
+            http_client: None,
+            timeout: None,
+            retry: None,
+        }
+    }
+
+    pub fn base_url(mut self, base_url: String) -> Self {
+        self.base_url = base_url;
+        self
+    }
+
+    pub fn model(mut self, model: String) -> Self {
+        self.model = model;
+        self
+    }
+
+    /// 只返回分数最高的 `top_n` 条，未设置时返回全部文档
+    pub fn top_n(mut self, top_n: usize) -> Self {
+        self.top_n = Some(top_n);
+        self
+    }
+
+    /// 使用预先配置好的 HTTP 客户端（代理、连接池、自定义 TLS 等），多个实例可以共享同一个客户端。
+    ///
+    /// 未设置时使用 [`langchain_core::http::shared_client`]。传入客户端后，只有调用了
+    /// `timeout` 时才按请求覆盖客户端自身的超时。
+    pub fn http_client(mut self, client: reqwest::Client) -> Self {
+        self.http_client = Some(client);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// 请求失败时的重试策略，默认不重试。
+    pub fn retry(mut self, config: RetryConfig) -> Self {
+        self.retry = Some(config);
+        self
+    }
+
+    /// 校验参数并构建 [`OpenAIReranker`]。
+    pub fn try_build(self) -> Result<OpenAIReranker, ValidationError> {
+        if self.top_n == Some(0) {
+            return Err(ValidationError::OutOfRange(
+                "top_n must be greater than 0".to_owned(),
+            ));
+        }
+
+        Ok(OpenAIReranker {
+            client: HttpClient::new(self.http_client, self.timeout, HeaderMap::new()),
+            base_url: self.base_url,
+            model: self.model,
+            api_key: self.api_key,
+            top_n: self.top_n,
+            retry: self.retry,
+        })
+    }
+
+    /// 构建 [`OpenAIReranker`]。
+    ///
+    /// # Panics
+    ///
+    /// 参数校验失败时 panic，需要处理错误时请使用 [`OpenAIRerankerBuilder::try_build`]。
+    pub fn build(self) -> OpenAIReranker {
+        self.try_build()
+            .expect("invalid OpenAIReranker configuration")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{json_response, mock_server};
+
+    fn reranker(base_url: String) -> OpenAIReranker {
+        OpenAIRerankerBuilder::from_base(
+            "bge-reranker-v2-m3".to_owned(),
+            base_url,
+            "sk-test".to_owned(),
+        )
+        .top_n(2)
+        .build()
+    }
+
+    #[tokio::test]
+    async fn rerank_returns_indices_sorted_by_score() {
+        let base_url = mock_server(vec![json_response(
+            r#"{"results":[{"index":0,"relevance_score":0.1},{"index":2,"relevance_score":0.9}]}"#,
+        )])
+        .await;
+        let documents = vec!["a".to_owned(), "b".to_owned(), "c".to_owned()];
+
+        let ranked = reranker(base_url).rerank("q", &documents).await.unwrap();
+
+        assert_eq!(ranked, vec![(2, 0.9), (0, 0.1)]);
+    }
+
+    #[tokio::test]
+    async fn out_of_range_index_is_an_error() {
+        let base_url = mock_server(vec![json_response(
+            r#"{"results":[{"index":5,"relevance_score":0.5}]}"#,
+        )])
+        .await;
+
+        let result = reranker(base_url).rerank("q", &["a".to_owned()]).await;
+
+        assert!(result.is_err());
+    }
+}
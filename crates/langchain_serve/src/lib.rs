@@ -0,0 +1,341 @@
+//! 以 HTTP 服务的形式部署 [`ReactAgent`]
+//!
+//! 独立于 `langchain` 的可选 crate，只有需要部署服务时才引入 axum 等依赖。提供两个接口：
+//!
+//! - `POST /invoke`：请求 `{"input": "...", "thread_id": "..."}`，返回最终回复与完整消息
+//! - `POST /stream`：请求同上，以 SSE 返回 `content`、`reasoning`、`tool_call`、`done` 事件
+//!
+//! Agent 配置了 checkpointer 时，请求中的 `thread_id` 用于延续对话；没有传入时服务会生成一个
+//! 新的 `thread_id`，`/invoke` 在响应体中、`/stream` 在 `thread` 事件中返回给调用方。
+//!
+//! ```no_run
+//! use langchain::ReactAgent;
+//!
+//! # async fn example(agent: ReactAgent) -> std::io::Result<()> {
+//! langchain_serve::serve(agent, "0.0.0.0:8000").await
+//! # }
+//! ```
+
+use std::{convert::Infallible, sync::Arc};
+
+use axum::{
+    Json, Router,
+    extract::State,
+    http::StatusCode,
+    response::{
+        IntoResponse, Response,
+        sse::{Event, Sse},
+    },
+    routing::post,
+};
+use futures::{Stream, StreamExt};
+use langchain::{AgentError, ReactAgent};
+use langchain_core::{
+    ErrorCategory, LangChainError,
+    message::Message,
+    response::Usage,
+    state::{ChatStreamEvent, MessagesState},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::net::{TcpListener, ToSocketAddrs};
+
+/// 单次输入的最大字符数
+pub const MAX_INPUT_CHARS: usize = 100_000;
+
+/// `thread_id` 的最大长度
+const MAX_THREAD_ID_LEN: usize = 128;
+
+/// `/invoke` 与 `/stream` 的请求体
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InvokeRequest {
+    /// 用户输入
+    pub input: String,
+    /// 会话 id，只在 Agent 配置了 checkpointer 时有意义
+    #[serde(default)]
+    pub thread_id: Option<String>,
+}
+
+/// `/invoke` 的响应体
+#[derive(Debug, Clone, Serialize)]
+pub struct InvokeResponse {
+    /// 最后一条助手消息的内容
+    pub output: String,
+    pub thread_id: Option<String>,
+    pub messages: Vec<Message>,
+    pub usage: Usage,
+}
+
+impl InvokeResponse {
+    fn new(state: &MessagesState, thread_id: Option<String>) -> Self {
+        Self {
+            output: state
+                .last_assistant()
+                .map(|m| m.content().to_owned())
+                .unwrap_or_default(),
+            thread_id,
+            messages: state.messages.iter().map(|m| m.as_ref().clone()).collect(),
+            usage: state.usage.clone(),
+        }
+    }
+}
+
+/// 服务返回的错误，响应体为 `{"error": "..."}`
+#[derive(Debug)]
+pub struct ServeError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ServeError {
+    fn bad_request(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::UNPROCESSABLE_ENTITY,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<AgentError> for ServeError {
+    fn from(e: AgentError) -> Self {
+        let status = match e.category() {
+            ErrorCategory::Validation => StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorCategory::RateLimit => StatusCode::TOO_MANY_REQUESTS,
+            ErrorCategory::Transient | ErrorCategory::External | ErrorCategory::Authentication => {
+                StatusCode::BAD_GATEWAY
+            }
+            ErrorCategory::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+            // 与 nginx 一样用 499 表示请求方已放弃
+            ErrorCategory::Cancelled => StatusCode::from_u16(499).expect("499 is a valid status"),
+        };
+        tracing::warn!("agent run failed: {e}");
+        Self {
+            status,
+            message: e.to_string(),
+        }
+    }
+}
+
+impl IntoResponse for ServeError {
+    fn into_response(self) -> Response {
+        (self.status, Json(json!({ "error": self.message }))).into_response()
+    }
+}
+
+/// 校验请求，返回本次运行使用的 `thread_id`
+fn validate(agent: &ReactAgent, request: &InvokeRequest) -> Result<Option<String>, ServeError> {
+    if request.input.trim().is_empty() {
+        return Err(ServeError::bad_request("`input` must not be empty"));
+    }
+    if request.input.chars().count() > MAX_INPUT_CHARS {
+        return Err(ServeError::bad_request(format!(
+            "`input` exceeds {MAX_INPUT_CHARS} characters"
+        )));
+    }
+    match &request.thread_id {
+        Some(thread_id)
+            if thread_id.is_empty()
+                || thread_id.len() > MAX_THREAD_ID_LEN
+                || !thread_id
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':')) =>
+        {
+            Err(ServeError::bad_request(format!(
+                "`thread_id` must be 1-{MAX_THREAD_ID_LEN} characters of [A-Za-z0-9-_.:]"
+            )))
+        }
+        Some(thread_id) => Ok(Some(thread_id.clone())),
+        // 有 checkpointer 时为新会话分配 id，调用方用它继续对话
+        None => Ok(agent
+            .graph
+            .checkpointer
+            .is_some()
+            .then(|| agent.id_generator().next_id())),
+    }
+}
+
+async fn invoke(
+    State(agent): State<Arc<ReactAgent>>,
+    Json(request): Json<InvokeRequest>,
+) -> Result<Json<InvokeResponse>, ServeError> {
+    let thread_id = validate(&agent, &request)?;
+    let state = agent
+        .invoke(Message::user(request.input), thread_id.as_deref())
+        .await?;
+    Ok(Json(InvokeResponse::new(&state, thread_id)))
+}
+
+/// 把流式事件转换为 SSE 事件
+fn sse_event(event: ChatStreamEvent) -> Event {
+    match event {
+        ChatStreamEvent::Content(text) => Event::default().event("content").data(text),
+        ChatStreamEvent::Reasoning(text) => Event::default().event("reasoning").data(text),
+        ChatStreamEvent::ToolCallDelta {
+            index,
+            id,
+            name,
+            arguments,
+            ..
+        } => Event::default().event("tool_call").data(
+            json!({"index": index, "id": id, "name": name, "arguments": arguments}).to_string(),
+        ),
+        ChatStreamEvent::Done {
+            finish_reason,
+            usage,
+        } => Event::default()
+            .event("done")
+            .data(json!({"finish_reason": finish_reason, "usage": usage}).to_string()),
+        ChatStreamEvent::ToolChunk { id, name, chunk } => Event::default()
+            .event("tool_chunk")
+            .data(json!({"id": id, "name": name, "chunk": chunk}).to_string()),
+        ChatStreamEvent::ToolResults { succeeded, failed } => Event::default()
+            .event("tool_results")
+            .data(json!({"succeeded": succeeded, "failed": failed}).to_string()),
+    }
+}
+
+async fn stream(
+    State(agent): State<Arc<ReactAgent>>,
+    Json(request): Json<InvokeRequest>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ServeError> {
+    let thread_id = validate(&agent, &request)?;
+    let events = async_stream::stream! {
+        if let Some(thread_id) = &thread_id {
+            yield Ok(Event::default().event("thread").data(thread_id.clone()));
+        }
+        match agent.stream(Message::user(request.input), thread_id.as_deref()).await {
+            Ok(inner) => {
+                let mut inner = std::pin::pin!(inner);
+                while let Some(event) = inner.next().await {
+                    yield Ok(sse_event(event));
+                }
+            }
+            Err(e) => {
+                tracing::warn!("agent stream failed: {e}");
+                yield Ok(Event::default()
+                    .event("error")
+                    .data(json!({ "error": e.to_string() }).to_string()));
+            }
+        }
+    };
+    Ok(Sse::new(events))
+}
+
+/// 挂载 `/invoke` 和 `/stream` 的路由，可以合并到已有的 axum 应用中
+pub fn router(agent: Arc<ReactAgent>) -> Router {
+    Router::new()
+        .route("/invoke", post(invoke))
+        .route("/stream", post(stream))
+        .with_state(agent)
+}
+
+/// 在 `addr` 上启动服务，直到进程退出
+pub async fn serve(agent: ReactAgent, addr: impl ToSocketAddrs) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    tracing::info!("serving agent on {}", listener.local_addr()?);
+    axum::serve(listener, router(Arc::new(agent))).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use langchain::{ReactAgentBuilder, testing::MockModel};
+    use langgraph::checkpoint::MemorySaver;
+
+    async fn spawn(agent: ReactAgent) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router(Arc::new(agent))).await });
+        url
+    }
+
+    fn agent() -> ReactAgent {
+        // 回复收到的用户消息数量，流式输出时每 5 个字符一块
+        let model = MockModel::from_fn(|request| {
+            let users = request
+                .messages
+                .iter()
+                .filter(|m| matches!(m.as_ref(), Message::User { .. }))
+                .count();
+            Ok(Message::assistant(format!("{users} messages")))
+        })
+        .with_chunk_size(5);
+        ReactAgentBuilder::new(model)
+            .with_checkpointer(Arc::new(MemorySaver::new()))
+            .build()
+    }
+
+    #[tokio::test]
+    async fn invoke_continues_conversation_by_thread_id() {
+        let url = spawn(agent()).await;
+        let client = reqwest::Client::new();
+
+        let first: serde_json::Value = client
+            .post(format!("{url}/invoke"))
+            .json(&json!({"input": "hi"}))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let thread_id = first["thread_id"].as_str().unwrap();
+        let second: serde_json::Value = client
+            .post(format!("{url}/invoke"))
+            .json(&json!({"input": "again", "thread_id": thread_id}))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+
+        assert_eq!(first["output"], "1 messages");
+        assert_eq!(second["output"], "2 messages");
+        assert_eq!(second["thread_id"], thread_id);
+    }
+
+    #[tokio::test]
+    async fn invalid_requests_are_rejected() {
+        let url = spawn(agent()).await;
+        let client = reqwest::Client::new();
+
+        for body in [
+            json!({"input": "  "}),
+            json!({"input": "hi", "thread_id": "../etc"}),
+            json!({"input": "hi", "extra": true}),
+        ] {
+            let response = client
+                .post(format!("{url}/invoke"))
+                .json(&body)
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(
+                response.status(),
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "{body}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn stream_sends_server_sent_events() {
+        let url = spawn(agent()).await;
+
+        let body = reqwest::Client::new()
+            .post(format!("{url}/stream"))
+            .json(&json!({"input": "hi", "thread_id": "t1"}))
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+
+        assert!(body.starts_with("event: thread\ndata: t1\n\n"));
+        assert!(body.contains("event: content\ndata: 1 mes\n\nevent: content\ndata: sages\n\n"));
+        assert!(body.contains("event: done\ndata: {\"finish_reason\":\"stop\""));
+    }
+}
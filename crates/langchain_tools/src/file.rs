@@ -1,8 +1,30 @@
 //! 文件操作工具
 //!
 //! 提供文件读取、写入和目录列表功能。
-
-use langchain_core::tool;
+//!
+//! 文件工具只能通过 [`FileToolset`] 注册给 Agent：所有路径都相对于 [`FileToolsConfig`] 的根目录解析，
+//! 不能通过 `..`、绝对路径或符号链接访问根目录之外的文件，还可以限制为只读或只允许部分扩展名。
+//! 不限制路径的工具需要显式使用 [`FileToolsConfig::unconfined`]。
+//! 本模块中的 [`read_file`] 等函数供程序直接调用，同样不限制路径。
+//!
+//! ```no_run
+//! use langchain_tools::file::{FileToolsConfig, FileToolset};
+//!
+//! let tools = FileToolset::new(
+//!     FileToolsConfig::new("./workspace")
+//!         .with_read_only(true)
+//!         .with_allowed_extensions(["md", "txt"]),
+//! )
+//! .tools();
+//! ```
+
+use std::{
+    collections::HashSet,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+
+use langchain_core::{ToolError, state::RegisteredTool, tool};
 use thiserror::Error;
 
 /// 文件操作错误
@@ -19,6 +41,25 @@ pub enum FileToolError {
 
     #[error("Invalid path: {0}")]
     InvalidPath(String),
+
+    /// 违反 [`FileToolsConfig`] 的限制：路径在根目录之外、只读模式下写入或扩展名不被允许
+    #[error("Access denied: {0}")]
+    AccessDenied(String),
+}
+
+/// 访问被拒绝和路径无效作为参数错误返回，让模型换一个路径重试
+impl From<FileToolError> for ToolError {
+    fn from(e: FileToolError) -> Self {
+        match e {
+            FileToolError::AccessDenied(_) | FileToolError::InvalidPath(_) => {
+                ToolError::InvalidArguments(e.to_string())
+            }
+            FileToolError::Io(e) => ToolError::tool_call(e),
+            FileToolError::PathNotFound(_) | FileToolError::PermissionDenied(_) => {
+                ToolError::ExecutionFailed(e.to_string())
+            }
+        }
+    }
 }
 
 /// 文件信息
@@ -29,45 +70,129 @@ pub struct FileInfo {
     pub size: Option<u64>,
 }
 
-/// 读取文件内容
+/// 不限制路径的文件操作，供程序直接调用
+fn unconfined() -> FileToolset {
+    FileToolset::new(FileToolsConfig::unconfined())
+}
+
+/// 读取文件内容，不限制路径
+pub async fn read_file(path: String) -> Result<String, FileToolError> {
+    unconfined().read_file(&path).await
+}
+
+/// 写入内容到文件，不限制路径
+pub async fn write_file(path: String, content: String) -> Result<String, FileToolError> {
+    unconfined().write_file(&path, &content).await
+}
+
+/// 列出目录内容，不限制路径
+pub async fn list_directory(path: String) -> Result<Vec<FileInfo>, FileToolError> {
+    unconfined().list_directory(&path).await
+}
+
+/// 删除文件，不限制路径
+pub async fn delete_file(path: String) -> Result<String, FileToolError> {
+    unconfined().delete_file(&path).await
+}
+
+/// 创建目录，不限制路径
+pub async fn create_directory(
+    path: String,
+    recursive: Option<bool>,
+) -> Result<String, FileToolError> {
+    unconfined()
+        .create_directory(&path, recursive.unwrap_or(false))
+        .await
+}
+
+// 以下函数只提供工具的名称、描述和参数，由 [`FileToolset::tools`] 按配置执行，不单独导出
+
 #[tool(
+    name = "read_file",
     description = "Read the contents of a text file",
     args(path = "File path to read")
 )]
-pub async fn read_file(path: String) -> Result<String, FileToolError> {
-    tracing::debug!("Reading file: {}", path);
-
-    let content = tokio::fs::read_to_string(&path).await?;
-
-    Ok(content)
+async fn read_file_spec(path: String) -> Result<String, FileToolError> {
+    read_file(path).await
 }
 
-/// 写入内容到文件
 #[tool(
+    name = "write_file",
     description = "Write content to a file",
     args(path = "File path to write", content = "Content to write")
 )]
-pub async fn write_file(path: String, content: String) -> Result<String, FileToolError> {
-    tracing::debug!("Writing to file: {} ({} bytes)", path, content.len());
-
-    tokio::fs::write(&path, &content).await?;
-
-    Ok(format!(
-        "Successfully wrote {} bytes to {}",
-        content.len(),
-        path
-    ))
+async fn write_file_spec(path: String, content: String) -> Result<String, FileToolError> {
+    write_file(path, content).await
 }
 
-/// 列出目录内容
 #[tool(
+    name = "list_directory",
     description = "List files in a directory",
     args(path = "Directory path")
 )]
-pub async fn list_directory(path: String) -> Result<Vec<FileInfo>, FileToolError> {
-    tracing::debug!("Listing directory: {}", path);
+async fn list_directory_spec(path: String) -> Result<Vec<FileInfo>, FileToolError> {
+    list_directory(path).await
+}
 
-    let mut entries = tokio::fs::read_dir(&path).await?;
+#[tool(
+    name = "delete_file",
+    description = "Delete a file",
+    args(path = "File path to delete")
+)]
+async fn delete_file_spec(path: String) -> Result<String, FileToolError> {
+    delete_file(path).await
+}
+
+#[tool(
+    name = "create_directory",
+    description = "Create a directory",
+    args(
+        path = "Directory path to create",
+        recursive = "Create parent directories if needed"
+    )
+)]
+async fn create_directory_spec(
+    path: String,
+    recursive: Option<bool>,
+) -> Result<String, FileToolError> {
+    create_directory(path, recursive).await
+}
+
+async fn read(path: &Path) -> Result<String, FileToolError> {
+    tracing::debug!("Reading file: {}", path.display());
+
+    let content = tokio::fs::read_to_string(path).await?;
+
+    Ok(content)
+}
+
+/// `no_follow` 时不跟随最后一个路径组件上的符号链接（unix 上使用 `O_NOFOLLOW`），
+/// 防止检查之后该位置被替换为指向根目录之外的链接
+async fn write(path: &Path, content: &str, no_follow: bool) -> Result<(), FileToolError> {
+    tracing::debug!(
+        "Writing to file: {} ({} bytes)",
+        path.display(),
+        content.len()
+    );
+
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    if no_follow {
+        options.custom_flags(libc::O_NOFOLLOW);
+    }
+    #[cfg(not(unix))]
+    let _ = no_follow;
+    let mut file = options.open(path).await?;
+    tokio::io::AsyncWriteExt::write_all(&mut file, content.as_bytes()).await?;
+
+    Ok(())
+}
+
+async fn list(path: &Path) -> Result<Vec<FileInfo>, FileToolError> {
+    tracing::debug!("Listing directory: {}", path.display());
+
+    let mut entries = tokio::fs::read_dir(path).await?;
     let mut result = Vec::new();
 
     while let Some(entry) = entries.next_entry().await? {
@@ -94,37 +219,350 @@ pub async fn list_directory(path: String) -> Result<Vec<FileInfo>, FileToolError
     Ok(result)
 }
 
-/// 删除文件
-#[tool(description = "Delete a file", args(path = "File path to delete"))]
-pub async fn delete_file(path: String) -> Result<String, FileToolError> {
-    tracing::debug!("Deleting file: {}", path);
+async fn delete(path: &Path) -> Result<(), FileToolError> {
+    tracing::debug!("Deleting file: {}", path.display());
 
-    tokio::fs::remove_file(&path).await?;
+    tokio::fs::remove_file(path).await?;
 
-    Ok(format!("Successfully deleted: {}", path))
+    Ok(())
 }
 
-/// 创建目录
-#[tool(
-    description = "Create a directory",
-    args(
-        path = "Directory path to create",
-        recursive = "Create parent directories if needed"
-    )
-)]
-pub async fn create_directory(
-    path: String,
-    recursive: Option<bool>,
-) -> Result<String, FileToolError> {
-    tracing::debug!("Creating directory: {}", path);
+async fn create_dir(path: &Path, recursive: bool) -> Result<(), FileToolError> {
+    tracing::debug!("Creating directory: {}", path.display());
 
-    if recursive.unwrap_or(false) {
-        tokio::fs::create_dir_all(&path).await?;
+    if recursive {
+        tokio::fs::create_dir_all(path).await?;
     } else {
-        tokio::fs::create_dir(&path).await?;
+        tokio::fs::create_dir(path).await?;
+    }
+
+    Ok(())
+}
+
+/// 对路径的操作，决定适用哪些限制
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Access {
+    ReadFile,
+    ReadDir,
+    WriteFile,
+    WriteDir,
+    /// 删除文件：最后一个组件是符号链接时删除链接本身，不跟随
+    Delete,
+}
+
+impl Access {
+    fn writes(self) -> bool {
+        matches!(self, Self::WriteFile | Self::WriteDir | Self::Delete)
     }
 
-    Ok(format!("Successfully created directory: {}", path))
+    fn is_file(self) -> bool {
+        matches!(self, Self::ReadFile | Self::WriteFile | Self::Delete)
+    }
+}
+
+/// [`FileToolset`] 的访问限制
+#[derive(Debug, Clone)]
+pub struct FileToolsConfig {
+    /// 根目录，`None` 表示不限制路径
+    root: Option<PathBuf>,
+    read_only: bool,
+    /// 允许访问的文件扩展名（小写、不带点），`None` 表示不限制；不影响目录
+    allowed_extensions: Option<HashSet<String>>,
+}
+
+impl FileToolsConfig {
+    /// 把所有路径限制在 `root` 之内
+    ///
+    /// 模型传入的路径相对于 `root` 解析，绝对路径也视为相对于 `root`（类似 chroot），
+    /// 例如 `/notes/a.md` 对应 `<root>/notes/a.md`。跳出根目录的 `..` 和指向根目录之外的
+    /// 符号链接都会被拒绝。`root` 需要在调用工具时已经存在。
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: Some(root.into()),
+            read_only: false,
+            allowed_extensions: None,
+        }
+    }
+
+    /// 不限制路径，可以访问进程有权限访问的任何文件，只适合本地开发
+    pub fn unconfined() -> Self {
+        Self {
+            root: None,
+            read_only: false,
+            allowed_extensions: None,
+        }
+    }
+
+    /// 只读模式：拒绝写入、删除文件和创建目录
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// 只允许访问这些扩展名的文件（不区分大小写，可以带或不带 `.`），没有扩展名的文件会被拒绝
+    pub fn with_allowed_extensions<I, S>(mut self, extensions: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.allowed_extensions = Some(
+            extensions
+                .into_iter()
+                .map(|e| e.as_ref().trim_start_matches('.').to_ascii_lowercase())
+                .collect(),
+        );
+        self
+    }
+
+    pub fn root(&self) -> Option<&Path> {
+        self.root.as_deref()
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// 检查 `path` 是否允许以 `access` 方式访问，返回实际访问的路径
+    ///
+    /// 限制在根目录内时，返回的路径中已存在的部分都已解析掉符号链接（[`Access::Delete`]
+    /// 的最后一个组件除外），之后的访问不会再经过链接离开根目录。
+    async fn resolve(&self, path: &str, access: Access) -> Result<PathBuf, FileToolError> {
+        if path.contains('\0') {
+            return Err(FileToolError::InvalidPath(path.to_owned()));
+        }
+        if self.read_only && access.writes() {
+            return Err(FileToolError::AccessDenied(format!(
+                "{path}: file tools are read-only"
+            )));
+        }
+        self.check_extension(path, Path::new(path), access)?;
+        let resolved = match &self.root {
+            Some(root) => confine_real(&tokio::fs::canonicalize(root).await?, path, access).await?,
+            None => PathBuf::from(path),
+        };
+        // 链接 `a.md -> key.pem` 访问的是目标文件，按目标的扩展名再检查一次
+        if access != Access::Delete
+            && let Ok(target) = tokio::fs::canonicalize(&resolved).await
+        {
+            self.check_extension(path, &target, access)?;
+        }
+        Ok(resolved)
+    }
+
+    fn check_extension(
+        &self,
+        path: &str,
+        file: &Path,
+        access: Access,
+    ) -> Result<(), FileToolError> {
+        let Some(allowed) = &self.allowed_extensions else {
+            return Ok(());
+        };
+        if !access.is_file() {
+            return Ok(());
+        }
+        let extension = file
+            .extension()
+            .map(|e| e.to_string_lossy().to_ascii_lowercase());
+        if extension.is_some_and(|e| allowed.contains(&e)) {
+            Ok(())
+        } else {
+            Err(FileToolError::AccessDenied(format!(
+                "{path}: file extension is not allowed"
+            )))
+        }
+    }
+}
+
+fn outside(path: &str) -> FileToolError {
+    FileToolError::AccessDenied(format!("{path}: outside of the allowed directory"))
+}
+
+/// 把 `path` 限制在 `root`（已规范化）之内，逐个检查已存在的路径组件
+///
+/// 符号链接必须指向根目录之内已存在的位置：悬空的链接会在写入时在根目录之外创建文件，
+/// 同样拒绝。不存在的组件原样拼接，之后由调用方创建。
+async fn confine_real(root: &Path, path: &str, access: Access) -> Result<PathBuf, FileToolError> {
+    let lexical = confine(root, path)?;
+    let relative = lexical.strip_prefix(root).map_err(|_| outside(path))?;
+    let parts: Vec<_> = relative.components().collect();
+
+    let mut real = root.to_path_buf();
+    for (i, part) in parts.iter().enumerate() {
+        let next = real.join(part);
+        let is_last = i + 1 == parts.len();
+        match tokio::fs::symlink_metadata(&next).await {
+            Ok(meta) if meta.file_type().is_symlink() => {
+                if is_last && access == Access::Delete {
+                    return Ok(next);
+                }
+                let target = tokio::fs::canonicalize(&next).await.map_err(|_| {
+                    FileToolError::AccessDenied(format!("{path}: broken symbolic link"))
+                })?;
+                if !target.starts_with(root) {
+                    return Err(outside(path));
+                }
+                real = target;
+            }
+            Ok(_) => real = next,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                real = next;
+                real.extend(&parts[i + 1..]);
+                return Ok(real);
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(real)
+}
+
+/// 按路径组件把 `path` 拼接到 `root` 下，`..` 越过根目录时拒绝
+fn confine(root: &Path, path: &str) -> Result<PathBuf, FileToolError> {
+    let mut resolved = root.to_path_buf();
+    let mut depth = 0usize;
+    for component in Path::new(path).components() {
+        match component {
+            // 绝对路径视为相对于根目录
+            Component::Prefix(_) | Component::RootDir | Component::CurDir => {}
+            Component::ParentDir => {
+                if depth == 0 {
+                    return Err(FileToolError::AccessDenied(format!(
+                        "{path}: outside of the allowed directory"
+                    )));
+                }
+                resolved.pop();
+                depth -= 1;
+            }
+            Component::Normal(part) => {
+                resolved.push(part);
+                depth += 1;
+            }
+        }
+    }
+    Ok(resolved)
+}
+
+/// 按 [`FileToolsConfig`] 限制访问范围的文件工具
+///
+/// [`tools`](Self::tools) 返回与本模块中的函数同名、同参数的工具，但每次调用前先检查路径。
+/// 违反限制时返回 [`FileToolError::AccessDenied`]，作为参数错误交给模型。
+#[derive(Debug, Clone)]
+pub struct FileToolset {
+    config: Arc<FileToolsConfig>,
+}
+
+impl FileToolset {
+    pub fn new(config: FileToolsConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+        }
+    }
+
+    pub fn config(&self) -> &FileToolsConfig {
+        &self.config
+    }
+
+    pub async fn read_file(&self, path: &str) -> Result<String, FileToolError> {
+        read(&self.config.resolve(path, Access::ReadFile).await?).await
+    }
+
+    pub async fn write_file(&self, path: &str, content: &str) -> Result<String, FileToolError> {
+        write(
+            &self.config.resolve(path, Access::WriteFile).await?,
+            content,
+            self.config.root.is_some(),
+        )
+        .await?;
+        Ok(format!(
+            "Successfully wrote {} bytes to {path}",
+            content.len()
+        ))
+    }
+
+    pub async fn list_directory(&self, path: &str) -> Result<Vec<FileInfo>, FileToolError> {
+        list(&self.config.resolve(path, Access::ReadDir).await?).await
+    }
+
+    pub async fn delete_file(&self, path: &str) -> Result<String, FileToolError> {
+        delete(&self.config.resolve(path, Access::Delete).await?).await?;
+        Ok(format!("Successfully deleted: {path}"))
+    }
+
+    pub async fn create_directory(
+        &self,
+        path: &str,
+        recursive: bool,
+    ) -> Result<String, FileToolError> {
+        create_dir(
+            &self.config.resolve(path, Access::WriteDir).await?,
+            recursive,
+        )
+        .await?;
+        Ok(format!("Successfully created directory: {path}"))
+    }
+
+    /// 可以注册到 Agent 的文件工具；只读模式下不包含写入、删除和创建目录的工具
+    ///
+    /// 需要不限制路径的工具时，显式使用 `FileToolset::new(FileToolsConfig::unconfined())`。
+    pub fn tools(&self) -> Vec<RegisteredTool<ToolError>> {
+        let mut tools = vec![
+            self.typed(
+                read_file_spec_tool(),
+                |toolset, args: ReadFileArgs| async move { toolset.read_file(&args.path).await },
+            ),
+            self.typed(
+                list_directory_spec_tool(),
+                |toolset, args: ListDirectoryArgs| async move {
+                    toolset.list_directory(&args.path).await
+                },
+            ),
+        ];
+        if !self.config.read_only {
+            tools.push(self.typed(
+                write_file_spec_tool(),
+                |toolset, args: WriteFileArgs| async move {
+                    toolset.write_file(&args.path, &args.content).await
+                },
+            ));
+            tools.push(
+                self.typed(delete_file_spec_tool(), |toolset, args: DeleteFileArgs| async move {
+                    toolset.delete_file(&args.path).await
+                }),
+            );
+            tools.push(self.typed(
+                create_directory_spec_tool(),
+                |toolset, args: CreateDirectoryArgs| async move {
+                    toolset
+                        .create_directory(&args.path, args.recursive.unwrap_or(false))
+                        .await
+                },
+            ));
+        }
+        tools
+    }
+
+    /// 沿用 `tool` 的名称和描述，由本实例执行
+    fn typed<Args, Output, F, Fut>(
+        &self,
+        tool: RegisteredTool<ToolError>,
+        f: F,
+    ) -> RegisteredTool<ToolError>
+    where
+        Args: serde::de::DeserializeOwned + schemars::JsonSchema + Send + 'static,
+        Output: serde::Serialize + Send + 'static,
+        F: Fn(FileToolset, Args) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Output, FileToolError>> + Send + 'static,
+    {
+        let toolset = self.clone();
+        RegisteredTool::from_typed(
+            tool.function.name,
+            tool.function.description,
+            move |args: Args| {
+                let result = f(toolset.clone(), args);
+                async move { result.await.map_err(ToolError::from) }
+            },
+        )
+    }
 }
 
 #[cfg(test)]
@@ -161,4 +599,133 @@ mod tests {
 
         Ok(())
     }
+
+    /// 每个测试使用独立的根目录
+    async fn sandbox(name: &str) -> PathBuf {
+        let root =
+            std::env::temp_dir().join(format!("langchain_tools_{name}_{}", std::process::id()));
+        let _ = tokio::fs::remove_dir_all(&root).await;
+        tokio::fs::create_dir_all(root.join("notes")).await.unwrap();
+        root
+    }
+
+    fn denied<T: std::fmt::Debug>(result: Result<T, FileToolError>) -> bool {
+        matches!(result, Err(FileToolError::AccessDenied(_)))
+    }
+
+    #[tokio::test]
+    async fn paths_are_confined_to_the_root() -> anyhow::Result<()> {
+        let root = sandbox("confined").await;
+        let files = FileToolset::new(FileToolsConfig::new(&root));
+
+        files.write_file("notes/a.md", "hello").await?;
+        assert_eq!(files.read_file("./notes/../notes/a.md").await?, "hello");
+        // 绝对路径相对于根目录解析
+        assert_eq!(files.read_file("/notes/a.md").await?, "hello");
+        assert!(denied(files.read_file("../../etc/passwd").await));
+        assert!(denied(files.read_file("notes/../../outside.txt").await));
+        assert!(files.read_file("/etc/passwd").await.is_err());
+
+        files.create_directory("/a/b", true).await?;
+        assert!(root.join("a/b").is_dir());
+        let entries = files.list_directory("/").await?;
+        assert_eq!(entries[0].name, "a");
+
+        tokio::fs::remove_dir_all(root).await?;
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn symlinks_cannot_escape_the_root() -> anyhow::Result<()> {
+        let root = sandbox("symlink").await;
+        let outside = sandbox("symlink_outside").await;
+        tokio::fs::write(outside.join("secret.txt"), "secret").await?;
+        tokio::fs::symlink(&outside, root.join("link")).await?;
+        let files = FileToolset::new(FileToolsConfig::new(&root));
+
+        assert!(denied(files.read_file("link/secret.txt").await));
+        assert!(denied(files.write_file("link/new.txt", "x").await));
+        assert!(!outside.join("new.txt").exists());
+
+        tokio::fs::remove_dir_all(root).await?;
+        tokio::fs::remove_dir_all(outside).await?;
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn dangling_symlinks_and_linked_extensions_are_denied() -> anyhow::Result<()> {
+        let root = sandbox("dangling").await;
+        let outside = sandbox("dangling_outside").await;
+        let target = outside.join("x.md");
+        // 指向根目录之外尚不存在的文件
+        tokio::fs::symlink(&target, root.join("evil.md")).await?;
+        tokio::fs::symlink(outside.join("dir"), root.join("evil_dir")).await?;
+        tokio::fs::write(root.join("key.pem"), "key").await?;
+        tokio::fs::symlink(root.join("key.pem"), root.join("a.md")).await?;
+        let files = FileToolset::new(FileToolsConfig::new(&root).with_allowed_extensions(["md"]));
+
+        assert!(denied(files.write_file("evil.md", "x").await));
+        assert!(denied(files.write_file("evil_dir/new.md", "x").await));
+        assert!(denied(files.create_directory("evil_dir", true).await));
+        assert!(!target.exists());
+        assert!(!outside.join("dir").exists());
+        // 扩展名按链接目标检查
+        assert!(denied(files.read_file("a.md").await));
+        // 删除链接本身，不影响目标
+        tokio::fs::symlink(outside.join("keep.md"), root.join("link.md")).await?;
+        tokio::fs::write(outside.join("keep.md"), "keep").await?;
+        files.delete_file("link.md").await?;
+        assert!(outside.join("keep.md").exists());
+
+        tokio::fs::remove_dir_all(root).await?;
+        tokio::fs::remove_dir_all(outside).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn read_only_and_extension_policies() -> anyhow::Result<()> {
+        let root = sandbox("policies").await;
+        tokio::fs::write(root.join("notes/a.md"), "# a").await?;
+        tokio::fs::write(root.join("notes/key.pem"), "key").await?;
+        let config = FileToolsConfig::new(&root)
+            .with_read_only(true)
+            .with_allowed_extensions([".MD", "txt"]);
+        let files = FileToolset::new(config);
+
+        assert_eq!(files.read_file("notes/a.md").await?, "# a");
+        assert!(denied(files.read_file("notes/key.pem").await));
+        assert!(denied(files.write_file("notes/b.md", "b").await));
+        assert!(denied(files.delete_file("notes/a.md").await));
+        assert!(denied(files.create_directory("more", false).await));
+        assert_eq!(files.list_directory("notes").await?.len(), 2);
+
+        let names: Vec<_> = files
+            .tools()
+            .into_iter()
+            .map(|tool| tool.function.name)
+            .collect();
+        assert_eq!(names, ["read_file", "list_directory"]);
+
+        tokio::fs::remove_dir_all(root).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn violations_are_returned_to_the_model_as_invalid_arguments() {
+        let root = sandbox("tool_errors").await;
+        let tools = FileToolset::new(FileToolsConfig::new(&root)).tools();
+        let read = tools
+            .iter()
+            .find(|t| t.function.name == "read_file")
+            .unwrap();
+
+        let error = (read.handler)(serde_json::json!({"path": "../x.txt"}))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, ToolError::InvalidArguments(_)));
+
+        tokio::fs::remove_dir_all(root).await.unwrap();
+    }
 }
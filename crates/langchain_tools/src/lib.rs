@@ -6,7 +6,11 @@
 //!
 //! - ✅ Web 搜索（DuckDuckGo）
 //! - ✅ 文件操作（读、写、列目录）
-//! - ✅ 实用工具（日期、计算等）
+//! - ✅ 实用工具（日期、计算、单位换算、统计、Base64、哈希等）
+//! - ✅ MCP 服务器工具接入（stdio / HTTP）
+//! - ✅ 根据 OpenAPI 3 文档生成 REST API 工具
+//! - ✅ 只读 SQL 查询（SQLite / PostgreSQL，需要 `sql` feature）
+//! - ✅ 天气查询（Open-Meteo，无需 API key）
 //! - ✅ 类型安全的工具定义
 //! - ✅ 自动 JSON Schema 生成
 //! - ✅ 异步 API
@@ -28,12 +32,26 @@
 //! ```
 
 pub mod file;
+pub mod mcp;
+pub mod openapi;
+#[cfg(feature = "sql")]
+pub mod sql;
 pub mod util;
+pub mod weather;
 pub mod web;
 
 // 重新导出常用工具和类型
 pub use file::{
-    FileInfo, FileToolError, create_directory, delete_file, list_directory, read_file, write_file,
+    FileInfo, FileToolError, FileToolsConfig, FileToolset, create_directory, delete_file,
+    list_directory, read_file, write_file,
 };
-pub use util::{UtilError, calculate, eval_expression, get_current_time};
-pub use web::{SearchResult, WebSearchError, search_web};
+pub use mcp::{HttpTransport, McpError, McpTool, McpToolset, McpTransport, StdioTransport};
+pub use openapi::{OpenApiAuth, OpenApiError, OpenApiToolset};
+#[cfg(feature = "sql")]
+pub use sql::{SqlConnection, SqlToolError, SqlToolset};
+pub use util::{
+    UtilError, base64_decode, base64_encode, calculate, convert_units, eval_expression,
+    get_current_time, hash, statistics,
+};
+pub use weather::{OpenMeteo, Units, WeatherError, WeatherReport, get_weather, get_weather_tool};
+pub use web::{SearchBackend, SearchResult, WebSearch, WebSearchError, search_web};
@@ -0,0 +1,602 @@
+//! MCP（Model Context Protocol）工具适配
+//!
+//! 连接 MCP 服务器，把它提供的工具转换为 [`RegisteredTool`]，可以直接传给
+//! `ReactAgentBuilder::with_tools`。支持 stdio（启动子进程）和 HTTP（Streamable HTTP）两种传输。
+//!
+//! ```no_run
+//! use langchain_tools::mcp::McpToolset;
+//!
+//! # async fn example() -> Result<(), langchain_tools::mcp::McpError> {
+//! let toolset = McpToolset::connect_stdio("npx", &["-y", "@modelcontextprotocol/server-everything"]).await?;
+//! let tools = toolset.tools();
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use async_trait::async_trait;
+use langchain_core::{
+    error::ToolError,
+    http::shared_client,
+    request::{ToolFunction, ToolSpec},
+    state::RegisteredTool,
+};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use thiserror::Error;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    process::{Child, ChildStdin, Command},
+    sync::oneshot,
+};
+
+/// 客户端声明支持的协议版本
+pub const PROTOCOL_VERSION: &str = "2025-03-26";
+
+/// MCP 错误
+#[derive(Debug, Error)]
+pub enum McpError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    /// 服务器返回的 JSON-RPC 错误
+    #[error("MCP server error {code}: {message}")]
+    Server { code: i64, message: String },
+
+    /// 不符合协议的响应
+    #[error("MCP protocol error: {0}")]
+    Protocol(String),
+
+    /// 连接已关闭（例如子进程退出）
+    #[error("MCP connection closed")]
+    Closed,
+}
+
+impl From<McpError> for ToolError {
+    fn from(e: McpError) -> Self {
+        ToolError::tool_call(e)
+    }
+}
+
+/// JSON-RPC 传输层
+#[async_trait]
+pub trait McpTransport: Send + Sync {
+    /// 发送请求并等待对应的响应，返回 `result` 字段
+    async fn request(&self, method: &str, params: Value) -> Result<Value, McpError>;
+
+    /// 发送不需要响应的通知
+    async fn notify(&self, method: &str, params: Value) -> Result<(), McpError>;
+}
+
+/// 把 JSON-RPC 响应转换为 `result` 或错误
+fn into_result(mut message: Value) -> Result<Value, McpError> {
+    if let Some(error) = message.get("error") {
+        return Err(McpError::Server {
+            code: error["code"].as_i64().unwrap_or_default(),
+            message: error["message"].as_str().unwrap_or_default().to_owned(),
+        });
+    }
+    match message.get_mut("result") {
+        Some(result) => Ok(result.take()),
+        None => Err(McpError::Protocol(format!(
+            "response has neither result nor error: {message}"
+        ))),
+    }
+}
+
+type Pending = Arc<Mutex<HashMap<u64, oneshot::Sender<Result<Value, McpError>>>>>;
+
+/// 通过子进程的标准输入输出通信，每行一条 JSON-RPC 消息
+///
+/// 子进程在传输层被丢弃时结束，标准错误输出原样继承，便于查看服务器日志。
+pub struct StdioTransport {
+    stdin: tokio::sync::Mutex<ChildStdin>,
+    pending: Pending,
+    next_id: AtomicU64,
+    _child: Child,
+}
+
+impl StdioTransport {
+    /// 启动 MCP 服务器子进程
+    pub fn spawn(program: &str, args: &[&str]) -> Result<Self, McpError> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| McpError::Protocol("child stdin unavailable".to_owned()))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| McpError::Protocol("child stdout unavailable".to_owned()))?;
+
+        let pending: Pending = Arc::default();
+        let reader_pending = pending.clone();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stdout).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let Ok(message) = serde_json::from_str::<Value>(&line) else {
+                    tracing::warn!("ignoring non-JSON line from MCP server: {line}");
+                    continue;
+                };
+                // 服务器发来的请求和通知不需要处理
+                let Some(id) = message.get("id").and_then(Value::as_u64) else {
+                    continue;
+                };
+                if message.get("method").is_some() {
+                    continue;
+                }
+                if let Some(sender) = reader_pending.lock().unwrap().remove(&id) {
+                    let _ = sender.send(into_result(message));
+                }
+            }
+            for (_, sender) in reader_pending.lock().unwrap().drain() {
+                let _ = sender.send(Err(McpError::Closed));
+            }
+        });
+
+        Ok(Self {
+            stdin: tokio::sync::Mutex::new(stdin),
+            pending,
+            next_id: AtomicU64::new(1),
+            _child: child,
+        })
+    }
+
+    async fn send(&self, message: &Value) -> Result<(), McpError> {
+        let mut line = serde_json::to_vec(message)?;
+        line.push(b'\n');
+        let mut stdin = self.stdin.lock().await;
+        stdin.write_all(&line).await?;
+        stdin.flush().await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl McpTransport for StdioTransport {
+    async fn request(&self, method: &str, params: Value) -> Result<Value, McpError> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id, sender);
+
+        let message = json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params});
+        if let Err(e) = self.send(&message).await {
+            self.pending.lock().unwrap().remove(&id);
+            return Err(e);
+        }
+        receiver.await.map_err(|_| McpError::Closed)?
+    }
+
+    async fn notify(&self, method: &str, params: Value) -> Result<(), McpError> {
+        self.send(&json!({"jsonrpc": "2.0", "method": method, "params": params}))
+            .await
+    }
+}
+
+/// Streamable HTTP 传输：每条消息一次 POST，响应可以是 JSON 或 SSE
+pub struct HttpTransport {
+    client: reqwest::Client,
+    url: String,
+    bearer_token: Option<String>,
+    session_id: Mutex<Option<String>>,
+    next_id: AtomicU64,
+}
+
+impl HttpTransport {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            client: shared_client(),
+            url: url.into(),
+            bearer_token: None,
+            session_id: Mutex::new(None),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    /// 使用自定义的 HTTP 客户端（超时、代理等）
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// 以 `Authorization: Bearer` 头发送的令牌
+    pub fn with_bearer_token(mut self, token: impl Into<String>) -> Self {
+        self.bearer_token = Some(token.into());
+        self
+    }
+
+    async fn post(&self, message: &Value) -> Result<reqwest::Response, McpError> {
+        let mut request = self
+            .client
+            .post(&self.url)
+            .header("Accept", "application/json, text/event-stream")
+            .json(message);
+        if let Some(token) = &self.bearer_token {
+            request = request.bearer_auth(token);
+        }
+        let session_id = self.session_id.lock().unwrap().clone();
+        if let Some(session_id) = session_id {
+            request = request.header("Mcp-Session-Id", session_id);
+        }
+
+        let response = request.send().await?.error_for_status()?;
+        if let Some(session_id) = response
+            .headers()
+            .get("Mcp-Session-Id")
+            .and_then(|v| v.to_str().ok())
+        {
+            *self.session_id.lock().unwrap() = Some(session_id.to_owned());
+        }
+        Ok(response)
+    }
+}
+
+/// 从 SSE 响应体中找出 id 匹配的 JSON-RPC 响应
+fn find_sse_response(body: &str, id: u64) -> Result<Value, McpError> {
+    body.split("\n\n")
+        .filter_map(|event| {
+            let data: Vec<_> = event
+                .lines()
+                .filter_map(|line| line.strip_prefix("data:"))
+                .map(str::trim_start)
+                .collect();
+            serde_json::from_str::<Value>(&data.join("\n")).ok()
+        })
+        .find(|message| message.get("id").and_then(Value::as_u64) == Some(id))
+        .ok_or_else(|| McpError::Protocol(format!("no response with id {id} in event stream")))
+}
+
+#[async_trait]
+impl McpTransport for HttpTransport {
+    async fn request(&self, method: &str, params: Value) -> Result<Value, McpError> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let message = json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params});
+        let response = self.post(&message).await?;
+
+        let is_sse = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("text/event-stream"));
+        let message = if is_sse {
+            find_sse_response(&response.text().await?, id)?
+        } else {
+            response.json::<Value>().await?
+        };
+        into_result(message)
+    }
+
+    async fn notify(&self, method: &str, params: Value) -> Result<(), McpError> {
+        self.post(&json!({"jsonrpc": "2.0", "method": method, "params": params}))
+            .await?;
+        Ok(())
+    }
+}
+
+/// 服务器通过 `tools/list` 声明的工具
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpTool {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub input_schema: Value,
+}
+
+impl McpTool {
+    /// 转换为模型使用的参数 schema：保证顶层是带 `properties` 的 object
+    fn parameters(&self) -> Value {
+        let mut schema = match &self.input_schema {
+            Value::Object(_) => self.input_schema.clone(),
+            _ => json!({}),
+        };
+        schema["type"] = json!("object");
+        if !schema.get("properties").is_some_and(Value::is_object) {
+            schema["properties"] = json!({});
+        }
+        schema
+    }
+
+    /// 检查参数是对象且包含所有必填字段
+    fn validate(&self, arguments: &Value) -> Result<(), ToolError> {
+        let Some(object) = arguments.as_object() else {
+            return Err(ToolError::InvalidArguments(format!(
+                "{} expects a JSON object, got {arguments}",
+                self.name
+            )));
+        };
+        let missing: Vec<_> = self.input_schema["required"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .filter(|field| !object.contains_key(*field))
+            .collect();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(ToolError::InvalidArguments(format!(
+                "{} is missing required arguments: {}",
+                self.name,
+                missing.join(", ")
+            )))
+        }
+    }
+}
+
+/// 把 `tools/call` 的结果转换为工具输出
+///
+/// 优先使用结构化结果 `structuredContent`，否则拼接所有文本内容；
+/// `isError` 为真时转换为 [`ToolError::ExecutionFailed`]。
+fn call_output(result: Value) -> Result<Value, ToolError> {
+    let text = result["content"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|block| match block["type"].as_str() {
+            Some("text") => block["text"].as_str().map(str::to_owned),
+            Some(other) => Some(format!("[{other} content]")),
+            None => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    if result["isError"].as_bool() == Some(true) {
+        return Err(ToolError::ExecutionFailed(text));
+    }
+    match result.get("structuredContent") {
+        Some(structured) if !structured.is_null() => Ok(structured.clone()),
+        _ => Ok(Value::String(text)),
+    }
+}
+
+/// 一个 MCP 服务器提供的工具集合
+#[derive(Clone)]
+pub struct McpToolset {
+    transport: Arc<dyn McpTransport>,
+    tools: Vec<McpTool>,
+}
+
+impl std::fmt::Debug for McpToolset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("McpToolset")
+            .field("tools", &self.tools)
+            .finish_non_exhaustive()
+    }
+}
+
+impl McpToolset {
+    /// 完成初始化握手并获取工具列表
+    pub async fn connect(transport: impl McpTransport + 'static) -> Result<Self, McpError> {
+        let transport: Arc<dyn McpTransport> = Arc::new(transport);
+        transport
+            .request(
+                "initialize",
+                json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "clientInfo": {"name": "langchain-rs", "version": env!("CARGO_PKG_VERSION")},
+                }),
+            )
+            .await?;
+        transport
+            .notify("notifications/initialized", json!({}))
+            .await?;
+
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor {
+                Some(cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+            let page = transport.request("tools/list", params).await?;
+            let listed: Vec<McpTool> = serde_json::from_value(page["tools"].clone())?;
+            tools.extend(listed);
+            cursor = page["nextCursor"].as_str().map(str::to_owned);
+            if cursor.is_none() {
+                break;
+            }
+        }
+        tracing::debug!("connected to MCP server with {} tools", tools.len());
+
+        Ok(Self { transport, tools })
+    }
+
+    /// 启动子进程并通过 stdio 连接
+    pub async fn connect_stdio(program: &str, args: &[&str]) -> Result<Self, McpError> {
+        Self::connect(StdioTransport::spawn(program, args)?).await
+    }
+
+    /// 通过 Streamable HTTP 连接
+    pub async fn connect_http(url: impl Into<String>) -> Result<Self, McpError> {
+        Self::connect(HttpTransport::new(url)).await
+    }
+
+    /// 服务器声明的原始工具信息
+    pub fn mcp_tools(&self) -> &[McpTool] {
+        &self.tools
+    }
+
+    /// 工具定义，参数 schema 直接取自服务器
+    pub fn specs(&self) -> Vec<ToolSpec> {
+        self.tools
+            .iter()
+            .map(|tool| ToolSpec::Function {
+                function: ToolFunction {
+                    name: tool.name.clone(),
+                    description: tool.description.clone().unwrap_or_default(),
+                    parameters: tool.parameters(),
+                    examples: Vec::new(),
+                    output_schema: None,
+                },
+            })
+            .collect()
+    }
+
+    /// 可以注册到 Agent 的工具，调用时转发给服务器的 `tools/call`
+    pub fn tools(&self) -> Vec<RegisteredTool<ToolError>> {
+        self.tools
+            .iter()
+            .map(|tool| {
+                let transport = self.transport.clone();
+                let mcp_tool = Arc::new(tool.clone());
+                RegisteredTool::new(
+                    tool.name.clone(),
+                    tool.description.clone().unwrap_or_default(),
+                    tool.parameters(),
+                    Arc::new(move |arguments: Value| {
+                        let transport = transport.clone();
+                        let tool = mcp_tool.clone();
+                        Box::pin(async move {
+                            tool.validate(&arguments)?;
+                            let result = transport
+                                .request(
+                                    "tools/call",
+                                    json!({"name": tool.name, "arguments": arguments}),
+                                )
+                                .await?;
+                            call_output(result)
+                        })
+                    }),
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 在内存中模拟一个 MCP 服务器，记录收到的方法
+    #[derive(Default)]
+    struct FakeServer {
+        calls: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl McpTransport for Arc<FakeServer> {
+        async fn request(&self, method: &str, params: Value) -> Result<Value, McpError> {
+            self.calls.lock().unwrap().push(method.to_owned());
+            match method {
+                "initialize" => Ok(json!({"protocolVersion": PROTOCOL_VERSION})),
+                "tools/list" if params.get("cursor").is_none() => Ok(json!({
+                    "tools": [{
+                        "name": "add",
+                        "description": "add two numbers",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {"a": {"type": "number"}, "b": {"type": "number"}},
+                            "required": ["a", "b"]
+                        }
+                    }],
+                    "nextCursor": "page2"
+                })),
+                "tools/list" => Ok(json!({"tools": [{"name": "fail"}]})),
+                "tools/call" => match params["name"].as_str() {
+                    Some("add") => {
+                        let sum = params["arguments"]["a"].as_f64().unwrap()
+                            + params["arguments"]["b"].as_f64().unwrap();
+                        Ok(json!({"content": [{"type": "text", "text": sum.to_string()}]}))
+                    }
+                    _ => Ok(json!({
+                        "content": [{"type": "text", "text": "boom"}],
+                        "isError": true
+                    })),
+                },
+                _ => Err(McpError::Server {
+                    code: -32601,
+                    message: "method not found".to_owned(),
+                }),
+            }
+        }
+
+        async fn notify(&self, method: &str, _params: Value) -> Result<(), McpError> {
+            self.calls.lock().unwrap().push(method.to_owned());
+            Ok(())
+        }
+    }
+
+    async fn toolset() -> (McpToolset, Arc<FakeServer>) {
+        let server = Arc::new(FakeServer::default());
+        let toolset = McpToolset::connect(server.clone()).await.unwrap();
+        (toolset, server)
+    }
+
+    #[tokio::test]
+    async fn connect_handshakes_and_lists_all_pages() {
+        let (toolset, server) = toolset().await;
+
+        assert_eq!(
+            *server.calls.lock().unwrap(),
+            [
+                "initialize",
+                "notifications/initialized",
+                "tools/list",
+                "tools/list"
+            ]
+        );
+        let specs = toolset.specs();
+        assert_eq!(specs.len(), 2);
+        assert_eq!(specs[0].function_name(), "add");
+        // 没有 inputSchema 的工具得到空的 object schema
+        let ToolSpec::Function { function } = &specs[1];
+        assert_eq!(
+            function.parameters,
+            json!({"type": "object", "properties": {}})
+        );
+    }
+
+    #[tokio::test]
+    async fn tools_proxy_calls_and_map_errors() {
+        let (toolset, _) = toolset().await;
+        let tools = toolset.tools();
+        let add = &tools[0].handler;
+        let fail = &tools[1].handler;
+
+        assert_eq!(add(json!({"a": 1, "b": 2})).await.unwrap(), json!("3"));
+        assert!(matches!(
+            add(json!({"a": 1})).await,
+            Err(ToolError::InvalidArguments(message)) if message.contains("b")
+        ));
+        assert!(matches!(
+            add(json!("1 + 2")).await,
+            Err(ToolError::InvalidArguments(_))
+        ));
+        assert!(matches!(
+            fail(json!({})).await,
+            Err(ToolError::ExecutionFailed(message)) if message == "boom"
+        ));
+    }
+
+    #[test]
+    fn sse_body_is_searched_for_matching_id() {
+        let body = "event: message\ndata: {\"jsonrpc\":\"2.0\",\"method\":\"notifications/progress\"}\n\n\
+                    event: message\ndata: {\"jsonrpc\":\"2.0\",\"id\":7,\"result\":{\"ok\":true}}\n\n";
+
+        let message = find_sse_response(body, 7).unwrap();
+
+        assert_eq!(into_result(message).unwrap(), json!({"ok": true}));
+        assert!(find_sse_response(body, 8).is_err());
+    }
+}
@@ -0,0 +1,618 @@
+//! 根据 OpenAPI 3 文档生成工具
+//!
+//! 读取 JSON 格式的 OpenAPI 3 文档，把选中的每个操作（operation）转换为一个 [`RegisteredTool`]：
+//! 路径、查询、请求头参数和 JSON 请求体合并为工具参数，调用时拼装 HTTP 请求并返回响应。
+//!
+//! ```no_run
+//! use langchain_tools::openapi::{OpenApiAuth, OpenApiToolset};
+//!
+//! # fn example(spec: &str) -> Result<(), langchain_tools::openapi::OpenApiError> {
+//! let toolset = OpenApiToolset::from_json(spec)?
+//!     .with_tags(["pets"])
+//!     .with_base_url("https://petstore.example.com/v1")
+//!     .with_auth(OpenApiAuth::Bearer("token".to_owned()));
+//! let tools = toolset.tools();
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{collections::HashSet, sync::Arc};
+
+use langchain_core::{
+    error::ToolError,
+    http::shared_client,
+    request::{ToolFunction, ToolSpec},
+    state::RegisteredTool,
+};
+use serde_json::{Map, Value, json};
+use thiserror::Error;
+
+/// 展开 `$ref` 的最大深度，超过后保留原始引用，避免循环引用无限展开
+const MAX_REF_DEPTH: usize = 8;
+
+/// 错误响应体在错误信息中保留的最大字符数
+const MAX_ERROR_BODY: usize = 2000;
+
+/// 请求体在工具参数中的字段名
+const BODY_FIELD: &str = "body";
+
+const METHODS: [&str; 7] = ["get", "post", "put", "patch", "delete", "head", "options"];
+
+/// OpenAPI 文档解析错误
+#[derive(Debug, Error)]
+pub enum OpenApiError {
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("invalid OpenAPI document: {0}")]
+    InvalidSpec(String),
+}
+
+/// 请求鉴权方式
+#[derive(Debug, Clone)]
+pub enum OpenApiAuth {
+    /// `Authorization: Bearer <token>`
+    Bearer(String),
+    /// 自定义请求头，例如 `X-API-Key`
+    Header { name: String, value: String },
+    /// 查询参数，例如 `?api_key=...`
+    Query { name: String, value: String },
+}
+
+/// 参数所在位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParameterLocation {
+    Path,
+    Query,
+    Header,
+}
+
+/// 操作的一个参数
+#[derive(Debug, Clone)]
+pub struct OperationParameter {
+    pub name: String,
+    pub location: ParameterLocation,
+    pub required: bool,
+    pub description: Option<String>,
+    pub schema: Value,
+}
+
+/// 文档中的一个操作
+#[derive(Debug, Clone)]
+pub struct Operation {
+    /// 工具名，取 `operationId`，没有时由方法和路径生成
+    pub name: String,
+    pub method: reqwest::Method,
+    pub path: String,
+    pub description: String,
+    pub tags: Vec<String>,
+    pub parameters: Vec<OperationParameter>,
+    /// `application/json` 请求体的 schema
+    pub body: Option<Value>,
+    pub body_required: bool,
+}
+
+impl Operation {
+    /// 工具参数 schema：每个参数一个字段，请求体放在 `body` 字段
+    pub fn parameters_schema(&self) -> Value {
+        let mut properties = Map::new();
+        let mut required = Vec::new();
+        for parameter in &self.parameters {
+            let mut schema = parameter.schema.clone();
+            if let (Some(description), Value::Object(object)) =
+                (&parameter.description, &mut schema)
+            {
+                object
+                    .entry("description")
+                    .or_insert_with(|| json!(description));
+            }
+            properties.insert(parameter.name.clone(), schema);
+            if parameter.required {
+                required.push(json!(parameter.name));
+            }
+        }
+        if let Some(body) = &self.body {
+            properties.insert(BODY_FIELD.to_owned(), body.clone());
+            if self.body_required {
+                required.push(json!(BODY_FIELD));
+            }
+        }
+        json!({"type": "object", "properties": properties, "required": required})
+    }
+
+    /// 按参数拼装并发送请求
+    async fn call(
+        &self,
+        client: &reqwest::Client,
+        base_url: &str,
+        auth: Option<&OpenApiAuth>,
+        arguments: Value,
+    ) -> Result<Value, ToolError> {
+        let Value::Object(mut arguments) = arguments else {
+            return Err(ToolError::InvalidArguments(format!(
+                "{} expects a JSON object",
+                self.name
+            )));
+        };
+
+        let mut path = self.path.clone();
+        let mut query = Vec::new();
+        let mut headers = Vec::new();
+        for parameter in &self.parameters {
+            let Some(value) = arguments.remove(&parameter.name) else {
+                if parameter.required {
+                    return Err(ToolError::InvalidArguments(format!(
+                        "{} is missing required parameter {}",
+                        self.name, parameter.name
+                    )));
+                }
+                continue;
+            };
+            let value = match value {
+                Value::String(s) => s,
+                other => other.to_string(),
+            };
+            match parameter.location {
+                ParameterLocation::Path => {
+                    path = path.replace(
+                        &format!("{{{}}}", parameter.name),
+                        &urlencoding::encode(&value),
+                    );
+                }
+                ParameterLocation::Query => query.push((parameter.name.clone(), value)),
+                ParameterLocation::Header => headers.push((parameter.name.clone(), value)),
+            }
+        }
+        let body = arguments.remove(BODY_FIELD);
+        if body.is_none() && self.body_required {
+            return Err(ToolError::InvalidArguments(format!(
+                "{} requires a request body in `{BODY_FIELD}`",
+                self.name
+            )));
+        }
+
+        let url = format!("{}{path}", base_url.trim_end_matches('/'));
+        let mut request = client.request(self.method.clone(), &url).query(&query);
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        match auth {
+            Some(OpenApiAuth::Bearer(token)) => request = request.bearer_auth(token),
+            Some(OpenApiAuth::Header { name, value }) => {
+                request = request.header(name.as_str(), value.as_str());
+            }
+            Some(OpenApiAuth::Query { name, value }) => {
+                request = request.query(&[(name, value)]);
+            }
+            None => {}
+        }
+        if let (Some(_), Some(body)) = (&self.body, &body) {
+            request = request.json(body);
+        }
+
+        let response = request.send().await.map_err(ToolError::tool_call)?;
+        let status = response.status();
+        let text = response.text().await.map_err(ToolError::tool_call)?;
+        if !status.is_success() {
+            let body: String = text.chars().take(MAX_ERROR_BODY).collect();
+            return Err(ToolError::ExecutionFailed(format!(
+                "{} {url} failed with status {status}: {body}",
+                self.method
+            )));
+        }
+        Ok(serde_json::from_str(&text).unwrap_or(Value::String(text)))
+    }
+}
+
+/// 由 OpenAPI 文档生成的工具集合
+#[derive(Debug, Clone)]
+pub struct OpenApiToolset {
+    operations: Vec<Operation>,
+    base_url: String,
+    auth: Option<OpenApiAuth>,
+    client: reqwest::Client,
+}
+
+impl OpenApiToolset {
+    /// 解析 JSON 格式的文档
+    pub fn from_json(spec: &str) -> Result<Self, OpenApiError> {
+        Self::from_value(serde_json::from_str(spec)?)
+    }
+
+    pub fn from_value(spec: Value) -> Result<Self, OpenApiError> {
+        let version = spec["openapi"].as_str().unwrap_or_default();
+        if !version.starts_with("3.") {
+            return Err(OpenApiError::InvalidSpec(format!(
+                "expected an OpenAPI 3 document, got version {version:?}"
+            )));
+        }
+        let paths = spec["paths"]
+            .as_object()
+            .ok_or_else(|| OpenApiError::InvalidSpec("missing `paths`".to_owned()))?;
+
+        let mut operations = Vec::new();
+        let mut names = HashSet::new();
+        for (path, item) in paths {
+            let shared = item["parameters"].as_array().cloned().unwrap_or_default();
+            for method in METHODS {
+                let Some(operation) = item.get(method) else {
+                    continue;
+                };
+                let operation = parse_operation(&spec, method, path, operation, &shared)?;
+                if !names.insert(operation.name.clone()) {
+                    return Err(OpenApiError::InvalidSpec(format!(
+                        "duplicate operation name {}",
+                        operation.name
+                    )));
+                }
+                operations.push(operation);
+            }
+        }
+
+        Ok(Self {
+            operations,
+            base_url: spec["servers"][0]["url"]
+                .as_str()
+                .unwrap_or_default()
+                .to_owned(),
+            auth: None,
+            client: shared_client(),
+        })
+    }
+
+    /// 覆盖文档 `servers` 中的地址
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    pub fn with_auth(mut self, auth: OpenApiAuth) -> Self {
+        self.auth = Some(auth);
+        self
+    }
+
+    /// 使用自定义的 HTTP 客户端（超时、代理等）
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// 只保留带有任一给定标签的操作
+    pub fn with_tags<I, S>(mut self, tags: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let tags: Vec<S> = tags.into_iter().collect();
+        self.operations.retain(|operation| {
+            tags.iter()
+                .any(|tag| operation.tags.iter().any(|t| t == tag.as_ref()))
+        });
+        self
+    }
+
+    /// 只保留给定名称（`operationId`）的操作
+    pub fn with_operations<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let names: Vec<S> = names.into_iter().collect();
+        self.operations
+            .retain(|operation| names.iter().any(|name| operation.name == name.as_ref()));
+        self
+    }
+
+    pub fn operations(&self) -> &[Operation] {
+        &self.operations
+    }
+
+    pub fn specs(&self) -> Vec<ToolSpec> {
+        self.operations
+            .iter()
+            .map(|operation| ToolSpec::Function {
+                function: ToolFunction {
+                    name: operation.name.clone(),
+                    description: operation.description.clone(),
+                    parameters: operation.parameters_schema(),
+                    examples: Vec::new(),
+                    output_schema: None,
+                },
+            })
+            .collect()
+    }
+
+    /// 可以注册到 Agent 的工具
+    ///
+    /// 调用失败（非 2xx 状态）时返回 [`ToolError::ExecutionFailed`]，信息中包含状态码和响应体。
+    pub fn tools(&self) -> Vec<RegisteredTool<ToolError>> {
+        let base_url: Arc<str> = Arc::from(self.base_url.as_str());
+        let auth = Arc::new(self.auth.clone());
+        self.operations
+            .iter()
+            .map(|operation| {
+                let parameters = operation.parameters_schema();
+                let description = operation.description.clone();
+                let operation = Arc::new(operation.clone());
+                let client = self.client.clone();
+                let base_url = base_url.clone();
+                let auth = auth.clone();
+                RegisteredTool::new(
+                    operation.name.clone(),
+                    description,
+                    parameters,
+                    Arc::new(move |arguments: Value| {
+                        let operation = operation.clone();
+                        let client = client.clone();
+                        let base_url = base_url.clone();
+                        let auth = auth.clone();
+                        Box::pin(async move {
+                            operation
+                                .call(&client, &base_url, auth.as_ref().as_ref(), arguments)
+                                .await
+                        })
+                    }),
+                )
+            })
+            .collect()
+    }
+}
+
+fn parse_operation(
+    spec: &Value,
+    method: &str,
+    path: &str,
+    operation: &Value,
+    shared: &[Value],
+) -> Result<Operation, OpenApiError> {
+    let name = match operation["operationId"].as_str() {
+        Some(id) => sanitize_name(id),
+        None => sanitize_name(&format!("{method}_{path}")),
+    };
+    let description = [&operation["summary"], &operation["description"]]
+        .into_iter()
+        .filter_map(Value::as_str)
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    // 操作级参数覆盖路径级同名参数
+    let mut parameters: Vec<OperationParameter> = Vec::new();
+    for raw in shared
+        .iter()
+        .chain(operation["parameters"].as_array().into_iter().flatten())
+    {
+        let raw = resolve(spec, raw, 0);
+        let location = match raw["in"].as_str() {
+            Some("path") => ParameterLocation::Path,
+            Some("query") => ParameterLocation::Query,
+            Some("header") => ParameterLocation::Header,
+            // cookie 参数不支持
+            _ => continue,
+        };
+        let name = raw["name"]
+            .as_str()
+            .ok_or_else(|| OpenApiError::InvalidSpec(format!("parameter without name in {path}")))?
+            .to_owned();
+        let parameter = OperationParameter {
+            required: location == ParameterLocation::Path
+                || raw["required"].as_bool().unwrap_or(false),
+            description: raw["description"].as_str().map(str::to_owned),
+            schema: match raw.get("schema") {
+                Some(schema) => resolve(spec, schema, 0),
+                None => json!({"type": "string"}),
+            },
+            name,
+            location,
+        };
+        parameters.retain(|p| p.name != parameter.name || p.location != parameter.location);
+        parameters.push(parameter);
+    }
+
+    let request_body = resolve(spec, &operation["requestBody"], 0);
+    let body = request_body["content"]["application/json"]["schema"]
+        .as_object()
+        .map(|_| {
+            resolve(
+                spec,
+                &request_body["content"]["application/json"]["schema"],
+                0,
+            )
+        });
+
+    Ok(Operation {
+        name,
+        method: method
+            .to_uppercase()
+            .parse()
+            .map_err(|_| OpenApiError::InvalidSpec(format!("invalid method {method}")))?,
+        path: path.to_owned(),
+        description,
+        tags: operation["tags"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .map(str::to_owned)
+            .collect(),
+        parameters,
+        body_required: body.is_some() && request_body["required"].as_bool().unwrap_or(false),
+        body,
+    })
+}
+
+/// 递归展开文档内部的 `$ref`（`#/components/...`）
+fn resolve(spec: &Value, value: &Value, depth: usize) -> Value {
+    match value {
+        Value::Object(object) => {
+            if let Some(reference) = object.get("$ref").and_then(Value::as_str) {
+                let target = reference
+                    .strip_prefix('#')
+                    .and_then(|pointer| spec.pointer(pointer));
+                return match target {
+                    Some(target) if depth < MAX_REF_DEPTH => resolve(spec, target, depth + 1),
+                    _ => value.clone(),
+                };
+            }
+            Value::Object(
+                object
+                    .iter()
+                    .map(|(key, value)| (key.clone(), resolve(spec, value, depth)))
+                    .collect(),
+            )
+        }
+        Value::Array(items) => Value::Array(
+            items
+                .iter()
+                .map(|item| resolve(spec, item, depth))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// 工具名只能包含字母、数字、`_` 和 `-`，最长 64 个字符
+fn sanitize_name(raw: &str) -> String {
+    let mut name = String::with_capacity(raw.len());
+    for c in raw.chars() {
+        if c.is_ascii_alphanumeric() || c == '-' {
+            name.push(c);
+        } else if !name.is_empty() && !name.ends_with('_') {
+            // 连续的分隔符合并为一个 `_`
+            name.push('_');
+        }
+    }
+    name.trim_end_matches('_').chars().take(64).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use langchain_core::testing::{MockHttpServer, response};
+
+    fn petstore() -> Value {
+        json!({
+            "openapi": "3.0.3",
+            "servers": [{"url": "https://petstore.example.com/v1"}],
+            "paths": {
+                "/pets/{petId}": {
+                    "parameters": [{"name": "petId", "in": "path", "schema": {"type": "integer"}}],
+                    "get": {
+                        "operationId": "getPet",
+                        "summary": "Get a pet",
+                        "tags": ["pets"],
+                        "parameters": [
+                            {"$ref": "#/components/parameters/Verbose"},
+                            {"name": "X-Trace", "in": "header", "schema": {"type": "string"}}
+                        ]
+                    }
+                },
+                "/pets": {
+                    "post": {
+                        "operationId": "createPet",
+                        "tags": ["pets"],
+                        "requestBody": {
+                            "required": true,
+                            "content": {"application/json": {"schema": {"$ref": "#/components/schemas/Pet"}}}
+                        }
+                    }
+                },
+                "/health": {"get": {"tags": ["ops"]}}
+            },
+            "components": {
+                "parameters": {
+                    "Verbose": {"name": "verbose", "in": "query", "description": "more detail", "schema": {"type": "boolean"}}
+                },
+                "schemas": {
+                    "Pet": {
+                        "type": "object",
+                        "properties": {"name": {"type": "string"}},
+                        "required": ["name"]
+                    }
+                }
+            }
+        })
+    }
+
+    /// 用给定的状态和响应体回复一个请求
+    async fn mock_server(status: &str, body: &str) -> MockHttpServer {
+        MockHttpServer::start(vec![response(status, "application/json", body)]).await
+    }
+
+    #[test]
+    fn maps_operations_to_tool_specs_and_filters() {
+        let toolset = OpenApiToolset::from_value(petstore()).unwrap();
+        let names: Vec<_> = toolset
+            .operations()
+            .iter()
+            .map(|o| o.name.as_str())
+            .collect();
+        // 按路径排序
+        assert_eq!(names, ["get_health", "createPet", "getPet"]);
+
+        let get_pet = &toolset.operations()[2];
+        let schema = get_pet.parameters_schema();
+        assert_eq!(schema["properties"]["petId"]["type"], "integer");
+        assert_eq!(
+            schema["properties"]["verbose"]["description"],
+            "more detail"
+        );
+        assert_eq!(schema["required"], json!(["petId"]));
+
+        let create = toolset.operations()[1].parameters_schema();
+        assert_eq!(create["properties"]["body"]["required"], json!(["name"]));
+        assert_eq!(create["required"], json!(["body"]));
+
+        let pets = toolset
+            .clone()
+            .with_tags(["pets"])
+            .with_operations(["createPet"]);
+        assert_eq!(pets.specs().len(), 1);
+        assert_eq!(pets.specs()[0].function_name(), "createPet");
+    }
+
+    #[tokio::test]
+    async fn calls_endpoint_with_parameters_and_auth() {
+        let server = mock_server("200 OK", r#"{"id":7,"name":"Rex"}"#).await;
+        let toolset = OpenApiToolset::from_value(petstore())
+            .unwrap()
+            .with_base_url(server.url())
+            .with_auth(OpenApiAuth::Bearer("secret".to_owned()))
+            .with_operations(["getPet"]);
+        let tool = &toolset.tools()[0];
+
+        let output = (tool.handler)(json!({"petId": 7, "verbose": true, "X-Trace": "abc"}))
+            .await
+            .unwrap();
+        let request = server.request(0).await.to_lowercase();
+
+        assert_eq!(output, json!({"id": 7, "name": "Rex"}));
+        assert!(request.starts_with("get /pets/7?verbose=true http/1.1"));
+        assert!(request.contains("authorization: bearer secret"));
+        assert!(request.contains("x-trace: abc"));
+    }
+
+    #[tokio::test]
+    async fn failed_calls_report_status_and_body() {
+        let server = mock_server("422 Unprocessable Entity", r#"{"error":"name taken"}"#).await;
+        let toolset = OpenApiToolset::from_value(petstore())
+            .unwrap()
+            .with_base_url(server.url())
+            .with_operations(["createPet"]);
+        let tool = &toolset.tools()[0];
+
+        assert!(matches!(
+            (tool.handler)(json!({})).await,
+            Err(ToolError::InvalidArguments(_))
+        ));
+        let error = (tool.handler)(json!({"body": {"name": "Rex"}}))
+            .await
+            .unwrap_err();
+
+        assert!(server.request(0).await.ends_with(r#"{"name":"Rex"}"#));
+        let ToolError::ExecutionFailed(message) = error else {
+            panic!("unexpected error: {error}");
+        };
+        assert!(message.contains("422"));
+        assert!(message.contains("name taken"));
+    }
+}
@@ -0,0 +1,541 @@
+//! SQL 查询工具（需要启用 `sql` feature）
+//!
+//! 把预先注册的 SQLite / PostgreSQL 连接暴露为一个 `sql_query` 工具：模型只能按别名选择连接，
+//! 不能自己给出连接串。默认只允许只读查询，结果按行数上限截断后以 JSON 返回。
+//!
+//! ```no_run
+//! use langchain_tools::sql::SqlToolset;
+//!
+//! # async fn example() -> Result<(), langchain_tools::sql::SqlToolError> {
+//! let toolset = SqlToolset::new()
+//!     .connect("analytics", "sqlite://analytics.db")
+//!     .await?
+//!     .with_max_rows(50);
+//! let tool = toolset.tool();
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use futures::TryStreamExt;
+use langchain_core::{
+    error::ToolError,
+    request::{ToolFunction, ToolSpec},
+    state::RegisteredTool,
+};
+use serde_json::{Map, Value, json};
+use sqlx::{
+    Column, Database, Executor, IntoArguments, Row, TypeInfo, ValueRef,
+    postgres::{PgPool, PgRow},
+    sqlite::{SqlitePool, SqliteRow},
+};
+use thiserror::Error;
+
+/// 工具名称
+pub const SQL_QUERY_TOOL: &str = "sql_query";
+
+/// 单次查询默认返回的最大行数
+pub const DEFAULT_MAX_ROWS: usize = 100;
+
+/// 只读查询允许的第一个关键字
+const READ_KEYWORDS: [&str; 5] = ["select", "with", "explain", "show", "values"];
+
+/// 只读查询中不允许作为语句出现的关键字，例如 PostgreSQL 中修改数据的 CTE
+const WRITE_KEYWORDS: [&str; 18] = [
+    "insert", "update", "delete", "merge", "upsert", "replace", "create", "drop", "alter",
+    "truncate", "grant", "revoke", "attach", "detach", "vacuum", "reindex", "copy", "pragma",
+];
+
+/// SQL 工具错误
+#[derive(Debug, Error)]
+pub enum SqlToolError {
+    #[error("unknown connection `{0}`")]
+    UnknownConnection(String),
+
+    #[error("write queries are not allowed: {0}")]
+    WriteNotAllowed(String),
+
+    #[error("unsupported connection url: {0}")]
+    UnsupportedUrl(String),
+
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl From<SqlToolError> for ToolError {
+    fn from(e: SqlToolError) -> Self {
+        match e {
+            SqlToolError::UnknownConnection(_) | SqlToolError::WriteNotAllowed(_) => {
+                ToolError::InvalidArguments(e.to_string())
+            }
+            other => ToolError::ExecutionFailed(other.to_string()),
+        }
+    }
+}
+
+/// 预先注册的数据库连接
+#[derive(Debug, Clone)]
+pub enum SqlConnection {
+    Sqlite(SqlitePool),
+    Postgres(PgPool),
+}
+
+/// 通过 `sql_query` 工具查询已注册连接的工具集
+#[derive(Debug, Clone)]
+pub struct SqlToolset {
+    connections: BTreeMap<String, SqlConnection>,
+    allow_writes: bool,
+    max_rows: usize,
+}
+
+impl Default for SqlToolset {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SqlToolset {
+    pub fn new() -> Self {
+        Self {
+            connections: BTreeMap::new(),
+            allow_writes: false,
+            max_rows: DEFAULT_MAX_ROWS,
+        }
+    }
+
+    /// 以 `alias` 注册一个连接，同名连接会被替换
+    pub fn with_connection(mut self, alias: impl Into<String>, connection: SqlConnection) -> Self {
+        self.connections.insert(alias.into(), connection);
+        self
+    }
+
+    pub fn with_sqlite(self, alias: impl Into<String>, pool: SqlitePool) -> Self {
+        self.with_connection(alias, SqlConnection::Sqlite(pool))
+    }
+
+    pub fn with_postgres(self, alias: impl Into<String>, pool: PgPool) -> Self {
+        self.with_connection(alias, SqlConnection::Postgres(pool))
+    }
+
+    /// 按连接串的协议（`sqlite:` 或 `postgres:` / `postgresql:`）建立连接并注册
+    pub async fn connect(self, alias: impl Into<String>, url: &str) -> Result<Self, SqlToolError> {
+        let connection = if url.starts_with("sqlite:") {
+            SqlConnection::Sqlite(SqlitePool::connect(url).await?)
+        } else if url.starts_with("postgres:") || url.starts_with("postgresql:") {
+            SqlConnection::Postgres(PgPool::connect(url).await?)
+        } else {
+            // 连接串可能包含密码，只保留协议部分
+            let scheme = url.split(':').next().unwrap_or_default();
+            return Err(SqlToolError::UnsupportedUrl(scheme.to_owned()));
+        };
+        Ok(self.with_connection(alias, connection))
+    }
+
+    /// 允许执行写操作（`INSERT`、`UPDATE`、DDL 等），默认只允许只读查询
+    pub fn with_writes(mut self, allow: bool) -> Self {
+        self.allow_writes = allow;
+        self
+    }
+
+    /// 单次查询返回的最大行数，超出部分被截断
+    pub fn with_max_rows(mut self, max_rows: usize) -> Self {
+        self.max_rows = max_rows;
+        self
+    }
+
+    /// 已注册的连接别名
+    pub fn aliases(&self) -> impl Iterator<Item = &str> {
+        self.connections.keys().map(String::as_str)
+    }
+
+    /// 在 `alias` 对应的连接上执行查询
+    ///
+    /// 读查询返回 `{"columns": [...], "rows": [{...}], "truncated": bool}`；
+    /// 允许写操作时，写语句返回 `{"rows_affected": n}`。
+    /// 只读模式下除了检查语句本身，还在从不提交的事务中执行（PostgreSQL 另外设为只读事务），
+    /// 绕过检查的修改会被回滚；连接回到连接池时不会带着只读等设置。
+    pub async fn query(&self, alias: &str, query: &str) -> Result<Value, SqlToolError> {
+        let connection = self
+            .connections
+            .get(alias)
+            .ok_or_else(|| SqlToolError::UnknownConnection(alias.to_owned()))?;
+        let read_only = is_read_only(query);
+        if !read_only && !self.allow_writes {
+            return Err(SqlToolError::WriteNotAllowed(
+                "only a single SELECT/WITH/EXPLAIN/SHOW/VALUES statement is allowed".to_owned(),
+            ));
+        }
+
+        match connection {
+            SqlConnection::Sqlite(pool) if read_only => {
+                // 事务在离开作用域时回滚，查询失败或被取消时也是如此
+                let mut tx = pool.begin().await?;
+                Ok(fetch_rows(&mut *tx, query, self.max_rows, sqlite_value).await?)
+            }
+            SqlConnection::Postgres(pool) if read_only => {
+                // 事务在离开作用域时回滚
+                let mut tx = pool.begin().await?;
+                sqlx::query("SET TRANSACTION READ ONLY")
+                    .execute(&mut *tx)
+                    .await?;
+                Ok(fetch_rows(&mut *tx, query, self.max_rows, postgres_value).await?)
+            }
+            SqlConnection::Sqlite(pool) => {
+                let result = sqlx::raw_sql(query).execute(pool).await?;
+                Ok(json!({"rows_affected": result.rows_affected()}))
+            }
+            SqlConnection::Postgres(pool) => {
+                let result = sqlx::raw_sql(query).execute(pool).await?;
+                Ok(json!({"rows_affected": result.rows_affected()}))
+            }
+        }
+    }
+
+    pub fn spec(&self) -> ToolSpec {
+        ToolSpec::Function {
+            function: ToolFunction {
+                name: SQL_QUERY_TOOL.to_owned(),
+                description: self.description(),
+                parameters: self.parameters(),
+                examples: Vec::new(),
+                output_schema: None,
+            },
+        }
+    }
+
+    /// 可以注册到 Agent 的 `sql_query` 工具
+    pub fn tool(&self) -> RegisteredTool<ToolError> {
+        let toolset = Arc::new(self.clone());
+        RegisteredTool::new(
+            SQL_QUERY_TOOL.to_owned(),
+            self.description(),
+            self.parameters(),
+            Arc::new(move |arguments: Value| {
+                let toolset = toolset.clone();
+                Box::pin(async move {
+                    let (Some(alias), Some(query)) = (
+                        arguments["connection_alias"].as_str(),
+                        arguments["query"].as_str(),
+                    ) else {
+                        return Err(ToolError::InvalidArguments(
+                            "`connection_alias` and `query` must be strings".to_owned(),
+                        ));
+                    };
+                    Ok(toolset.query(alias, query).await?)
+                })
+            }),
+        )
+    }
+
+    fn description(&self) -> String {
+        let mode = if self.allow_writes {
+            "Run a SQL statement"
+        } else {
+            "Run a read-only SQL query"
+        };
+        format!(
+            "{mode} against one of the registered databases and return the rows as JSON \
+             (at most {} rows). Available connections: {}.",
+            self.max_rows,
+            self.connections
+                .iter()
+                .map(|(alias, connection)| match connection {
+                    SqlConnection::Sqlite(_) => format!("{alias} (SQLite)"),
+                    SqlConnection::Postgres(_) => format!("{alias} (PostgreSQL)"),
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "connection_alias": {
+                    "type": "string",
+                    "enum": self.aliases().collect::<Vec<_>>(),
+                    "description": "Name of the database connection to query"
+                },
+                "query": {
+                    "type": "string",
+                    "description": "SQL query to run"
+                }
+            },
+            "required": ["connection_alias", "query"]
+        })
+    }
+}
+
+/// 语句是否为单条只读查询
+///
+/// 忽略字符串、引号标识符和注释后按关键字判断，不是完整的 SQL 解析；紧跟 `(` 的词是函数调用
+/// （例如 `replace(name, 'a', 'b')`），不当作语句关键字。实际执行时还会在数据库层面限制为只读。
+fn is_read_only(query: &str) -> bool {
+    // 词及其后是否紧跟 `(`
+    let mut words: Vec<(String, bool)> = Vec::new();
+    let mut word = String::new();
+    let mut after_word = false;
+    let mut statements = 0;
+    let mut in_statement = false;
+    let mut chars = query.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if !c.is_alphanumeric() && c != '_' && !word.is_empty() {
+            words.push((std::mem::take(&mut word), false));
+            after_word = true;
+        }
+        match c {
+            '\'' | '"' | '`' => {
+                // 跳过字符串和引号标识符，连续两个引号表示转义
+                while let Some(next) = chars.next() {
+                    if next == c && chars.next_if_eq(&c).is_none() {
+                        break;
+                    }
+                }
+            }
+            '-' if chars.next_if_eq(&'-').is_some() => {
+                for next in chars.by_ref() {
+                    if next == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.next_if_eq(&'*').is_some() => {
+                let mut prev = ' ';
+                for next in chars.by_ref() {
+                    if prev == '*' && next == '/' {
+                        break;
+                    }
+                    prev = next;
+                }
+            }
+            ';' => in_statement = false,
+            c if c.is_alphanumeric() || c == '_' => {
+                if !in_statement {
+                    in_statement = true;
+                    statements += 1;
+                }
+                word.extend(c.to_lowercase());
+                continue;
+            }
+            c if c.is_whitespace() => continue,
+            '(' if after_word => {
+                if let Some((_, call)) = words.last_mut() {
+                    *call = true;
+                }
+            }
+            _ => {
+                if !in_statement {
+                    in_statement = true;
+                    statements += 1;
+                }
+            }
+        }
+        after_word = false;
+    }
+    if !word.is_empty() {
+        words.push((word, false));
+    }
+
+    statements == 1
+        && words
+            .first()
+            .is_some_and(|(first, _)| READ_KEYWORDS.contains(&first.as_str()))
+        && !words
+            .iter()
+            .any(|(word, call)| !call && WRITE_KEYWORDS.contains(&word.as_str()))
+}
+
+/// 逐行读取结果，超过 `max_rows` 时停止读取并标记截断
+async fn fetch_rows<'c, DB, E>(
+    executor: E,
+    query: &str,
+    max_rows: usize,
+    decode: fn(&DB::Row, usize) -> Result<Value, sqlx::Error>,
+) -> Result<Value, sqlx::Error>
+where
+    DB: Database,
+    E: Executor<'c, Database = DB>,
+    for<'q> DB::Arguments<'q>: IntoArguments<'q, DB>,
+{
+    let mut columns = Vec::new();
+    let mut rows = Vec::new();
+    let mut truncated = false;
+    let mut stream = sqlx::query::<DB>(query).fetch(executor);
+    while let Some(row) = stream.try_next().await? {
+        if rows.len() == max_rows {
+            truncated = true;
+            break;
+        }
+        if columns.is_empty() {
+            columns = row
+                .columns()
+                .iter()
+                .map(|column| column.name().to_owned())
+                .collect();
+        }
+        let mut object = Map::new();
+        for (index, name) in columns.iter().enumerate() {
+            object.insert(name.clone(), decode(&row, index)?);
+        }
+        rows.push(Value::Object(object));
+    }
+
+    Ok(json!({"columns": columns, "rows": rows, "truncated": truncated}))
+}
+
+/// 按值的实际存储类型转换为 JSON
+fn sqlite_value(row: &SqliteRow, index: usize) -> Result<Value, sqlx::Error> {
+    let raw = row.try_get_raw(index)?;
+    if raw.is_null() {
+        return Ok(Value::Null);
+    }
+    let type_name = raw.type_info().name().to_owned();
+    Ok(match type_name.as_str() {
+        "INTEGER" | "BOOLEAN" => json!(row.try_get_unchecked::<i64, _>(index)?),
+        "REAL" | "NUMERIC" => json!(row.try_get_unchecked::<f64, _>(index)?),
+        "BLOB" => json!(BASE64.encode(row.try_get_unchecked::<Vec<u8>, _>(index)?)),
+        _ => json!(row.try_get_unchecked::<String, _>(index)?),
+    })
+}
+
+/// 按列类型转换为 JSON；无法转换的类型需要在查询中转换为文本（`::text`）
+fn postgres_value(row: &PgRow, index: usize) -> Result<Value, sqlx::Error> {
+    let raw = row.try_get_raw(index)?;
+    if raw.is_null() {
+        return Ok(Value::Null);
+    }
+    let type_name = raw.type_info().name().to_owned();
+    Ok(match type_name.as_str() {
+        "BOOL" => json!(row.try_get::<bool, _>(index)?),
+        "INT2" => json!(row.try_get::<i16, _>(index)?),
+        "INT4" => json!(row.try_get::<i32, _>(index)?),
+        "INT8" => json!(row.try_get::<i64, _>(index)?),
+        "FLOAT4" => json!(row.try_get::<f32, _>(index)?),
+        "FLOAT8" => json!(row.try_get::<f64, _>(index)?),
+        "TEXT" | "VARCHAR" | "BPCHAR" | "CHAR" | "NAME" => json!(row.try_get::<String, _>(index)?),
+        "JSON" | "JSONB" => row.try_get::<Value, _>(index)?,
+        "UUID" => json!(row.try_get::<sqlx::types::Uuid, _>(index)?.to_string()),
+        "TIMESTAMPTZ" => json!(
+            row.try_get::<chrono::DateTime<chrono::Utc>, _>(index)?
+                .to_rfc3339()
+        ),
+        "TIMESTAMP" => json!(row.try_get::<chrono::NaiveDateTime, _>(index)?.to_string()),
+        "DATE" => json!(row.try_get::<chrono::NaiveDate, _>(index)?.to_string()),
+        "BYTEA" => json!(BASE64.encode(row.try_get::<Vec<u8>, _>(index)?)),
+        other => json!(format!("<unsupported type {other}, cast it to text>")),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+    use std::time::Duration;
+
+    async fn toolset() -> SqlToolset {
+        // 内存数据库按连接隔离，只使用一个连接
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::raw_sql(
+            "CREATE TABLE sales (region TEXT, amount REAL, units INTEGER);
+             INSERT INTO sales VALUES ('north', 10.5, 3), ('south', 4.0, 1), ('east', NULL, 2);",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        SqlToolset::new().with_sqlite("shop", pool)
+    }
+
+    #[test]
+    fn read_only_detection() {
+        assert!(is_read_only("SELECT * FROM t"));
+        assert!(is_read_only("  with x as (select 1) select * from x;"));
+        assert!(is_read_only("select 'drop table t; --' as note"));
+        assert!(is_read_only("select update_time from t -- delete later"));
+        assert!(is_read_only("SELECT replace(name, 'a', 'b') FROM t"));
+        assert!(is_read_only("select insert ('abc', 1, 1, 'x')"));
+
+        assert!(!is_read_only("DELETE FROM t"));
+        assert!(!is_read_only("select 1; drop table t"));
+        assert!(!is_read_only(
+            "with gone as (delete from t returning *) select * from gone"
+        ));
+        assert!(!is_read_only(
+            "with x as (select 1) replace into t select * from x"
+        ));
+        assert!(!is_read_only(""));
+    }
+
+    #[tokio::test]
+    async fn queries_return_rows_up_to_the_cap() {
+        let toolset = toolset().await.with_max_rows(2);
+
+        let result = toolset
+            .query(
+                "shop",
+                "SELECT region, amount, units FROM sales ORDER BY units DESC",
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result["columns"], json!(["region", "amount", "units"]));
+        assert_eq!(
+            result["rows"],
+            json!([
+                {"region": "north", "amount": 10.5, "units": 3},
+                {"region": "east", "amount": null, "units": 2}
+            ])
+        );
+        assert_eq!(result["truncated"], json!(true));
+    }
+
+    #[tokio::test]
+    async fn writes_are_blocked_unless_enabled() {
+        let toolset = toolset().await;
+
+        let tool = toolset.tool();
+        let blocked = (tool.handler)(json!({
+            "connection_alias": "shop",
+            "query": "DELETE FROM sales"
+        }))
+        .await;
+        assert!(matches!(blocked, Err(ToolError::InvalidArguments(_))));
+        assert!(matches!(
+            toolset.query("other", "SELECT 1").await,
+            Err(SqlToolError::UnknownConnection(alias)) if alias == "other"
+        ));
+
+        let toolset = toolset.with_writes(true);
+        let result = toolset
+            .query("shop", "DELETE FROM sales WHERE units < 2")
+            .await
+            .unwrap();
+        assert_eq!(result, json!({"rows_affected": 1}));
+    }
+
+    #[tokio::test]
+    async fn cancelled_reads_leave_the_connection_writable() {
+        let toolset = toolset().await;
+
+        // 查询执行中被取消，连接回到连接池
+        let slow = "WITH RECURSIVE n(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM n WHERE x < 5000000) \
+                    SELECT count(*) FROM n";
+        let cancelled =
+            tokio::time::timeout(Duration::from_millis(20), toolset.query("shop", slow)).await;
+        assert!(cancelled.is_err());
+
+        let result = toolset
+            .with_writes(true)
+            .query("shop", "DELETE FROM sales WHERE units < 2")
+            .await
+            .unwrap();
+        assert_eq!(result, json!({"rows_affected": 1}));
+    }
+}
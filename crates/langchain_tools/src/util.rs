@@ -1,8 +1,11 @@
 //! 实用工具
 //!
-//! 提供常用的实用工具，如获取当前时间、计算器等。
+//! 提供常用的实用工具，如获取当前时间、计算器、单位换算、统计、编码和摘要等。
 
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
 use langchain_core::tool;
+use md5::Md5;
+use sha2::{Digest, Sha256};
 use thiserror::Error;
 
 /// 实用工具错误
@@ -13,6 +16,18 @@ pub enum UtilError {
 
     #[error("Parse error: {0}")]
     Parse(String),
+
+    #[error("Unknown unit: {0}")]
+    UnknownUnit(String),
+
+    #[error("Cannot convert {from} to {to}: incompatible units")]
+    IncompatibleUnits { from: String, to: String },
+
+    #[error("Invalid base64: {0}")]
+    InvalidBase64(String),
+
+    #[error("Unknown hash algorithm: {0}")]
+    UnknownAlgorithm(String),
 }
 
 /// 获取当前时间
@@ -59,6 +74,212 @@ pub async fn calculate(a: f64, op: String, b: f64) -> Result<f64, UtilError> {
     Ok(result)
 }
 
+/// 单位所属的物理量，只有同一物理量的单位之间可以换算
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Dimension {
+    Length,
+    Mass,
+    Temperature,
+    Time,
+}
+
+/// 单位表：(名称, 物理量, 换算到基准单位的倍数, 偏移量)
+///
+/// 基准值 = 数值 × 倍数 + 偏移量；基准单位分别为米、千克、开尔文和秒。
+/// 只有温度需要偏移量。
+const UNITS: &[(&str, Dimension, f64, f64)] = &[
+    ("mm", Dimension::Length, 0.001, 0.0),
+    ("cm", Dimension::Length, 0.01, 0.0),
+    ("m", Dimension::Length, 1.0, 0.0),
+    ("km", Dimension::Length, 1000.0, 0.0),
+    ("in", Dimension::Length, 0.0254, 0.0),
+    ("ft", Dimension::Length, 0.3048, 0.0),
+    ("yd", Dimension::Length, 0.9144, 0.0),
+    ("mi", Dimension::Length, 1609.344, 0.0),
+    ("mg", Dimension::Mass, 1e-6, 0.0),
+    ("g", Dimension::Mass, 0.001, 0.0),
+    ("kg", Dimension::Mass, 1.0, 0.0),
+    ("t", Dimension::Mass, 1000.0, 0.0),
+    ("oz", Dimension::Mass, 0.028_349_523_125, 0.0),
+    ("lb", Dimension::Mass, 0.453_592_37, 0.0),
+    ("c", Dimension::Temperature, 1.0, 273.15),
+    (
+        "f",
+        Dimension::Temperature,
+        5.0 / 9.0,
+        273.15 - 32.0 * 5.0 / 9.0,
+    ),
+    ("k", Dimension::Temperature, 1.0, 0.0),
+    ("ms", Dimension::Time, 0.001, 0.0),
+    ("s", Dimension::Time, 1.0, 0.0),
+    ("min", Dimension::Time, 60.0, 0.0),
+    ("h", Dimension::Time, 3600.0, 0.0),
+    ("d", Dimension::Time, 86400.0, 0.0),
+    ("week", Dimension::Time, 604_800.0, 0.0),
+];
+
+/// 单位的常见别名
+const UNIT_ALIASES: &[(&str, &str)] = &[
+    ("meter", "m"),
+    ("meters", "m"),
+    ("kilometer", "km"),
+    ("kilometers", "km"),
+    ("centimeter", "cm"),
+    ("centimeters", "cm"),
+    ("millimeter", "mm"),
+    ("millimeters", "mm"),
+    ("inch", "in"),
+    ("inches", "in"),
+    ("foot", "ft"),
+    ("feet", "ft"),
+    ("yard", "yd"),
+    ("yards", "yd"),
+    ("mile", "mi"),
+    ("miles", "mi"),
+    ("gram", "g"),
+    ("grams", "g"),
+    ("kilogram", "kg"),
+    ("kilograms", "kg"),
+    ("milligram", "mg"),
+    ("milligrams", "mg"),
+    ("tonne", "t"),
+    ("tonnes", "t"),
+    ("ounce", "oz"),
+    ("ounces", "oz"),
+    ("pound", "lb"),
+    ("pounds", "lb"),
+    ("lbs", "lb"),
+    ("celsius", "c"),
+    ("°c", "c"),
+    ("fahrenheit", "f"),
+    ("°f", "f"),
+    ("kelvin", "k"),
+    ("millisecond", "ms"),
+    ("milliseconds", "ms"),
+    ("second", "s"),
+    ("seconds", "s"),
+    ("sec", "s"),
+    ("minute", "min"),
+    ("minutes", "min"),
+    ("hour", "h"),
+    ("hours", "h"),
+    ("day", "d"),
+    ("days", "d"),
+    ("weeks", "week"),
+];
+
+/// 按名称或别名查找单位（不区分大小写）
+fn lookup_unit(name: &str) -> Result<(Dimension, f64, f64), UtilError> {
+    let lower = name.trim().to_lowercase();
+    let canonical = UNIT_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == lower)
+        .map_or(lower.as_str(), |(_, unit)| *unit);
+    UNITS
+        .iter()
+        .find(|(unit, ..)| *unit == canonical)
+        .map(|&(_, dimension, scale, offset)| (dimension, scale, offset))
+        .ok_or_else(|| UtilError::UnknownUnit(name.to_owned()))
+}
+
+/// 单位换算
+#[tool(
+    description = "Convert a value between units of length (mm, cm, m, km, in, ft, yd, mi), mass (mg, g, kg, t, oz, lb), temperature (c, f, k) or time (ms, s, min, h, d, week)",
+    args(
+        value = "Value to convert",
+        from = "Unit of the value",
+        to = "Target unit"
+    )
+)]
+pub async fn convert_units(value: f64, from: String, to: String) -> Result<f64, UtilError> {
+    let (from_dimension, from_scale, from_offset) = lookup_unit(&from)?;
+    let (to_dimension, to_scale, to_offset) = lookup_unit(&to)?;
+    if from_dimension != to_dimension {
+        return Err(UtilError::IncompatibleUnits { from, to });
+    }
+
+    let base = value * from_scale + from_offset;
+    Ok((base - to_offset) / to_scale)
+}
+
+/// 统计运算
+#[tool(
+    description = "Compute a statistic over a list of numbers: mean, median, stddev (population), min, max or sum",
+    args(numbers = "Numbers to summarize", op = "Statistic to compute")
+)]
+pub async fn statistics(numbers: Vec<f64>, op: String) -> Result<f64, UtilError> {
+    if numbers.is_empty() {
+        return Err(UtilError::Calculation(
+            "Cannot compute statistics of an empty list".to_owned(),
+        ));
+    }
+
+    let count = numbers.len() as f64;
+    let mean = numbers.iter().sum::<f64>() / count;
+    let result = match op.to_lowercase().as_str() {
+        "mean" | "average" | "avg" => mean,
+        "median" => {
+            let mut sorted = numbers;
+            sorted.sort_by(f64::total_cmp);
+            let mid = sorted.len() / 2;
+            if sorted.len().is_multiple_of(2) {
+                (sorted[mid - 1] + sorted[mid]) / 2.0
+            } else {
+                sorted[mid]
+            }
+        }
+        "stddev" | "std" => {
+            let variance = numbers.iter().map(|n| (n - mean).powi(2)).sum::<f64>() / count;
+            variance.sqrt()
+        }
+        "min" => numbers.iter().copied().fold(f64::INFINITY, f64::min),
+        "max" => numbers.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        "sum" => numbers.iter().sum(),
+        _ => {
+            return Err(UtilError::Calculation(format!("Unknown statistic: {}", op)));
+        }
+    };
+
+    Ok(result)
+}
+
+/// Base64 编码
+#[tool(
+    description = "Encode text as standard base64",
+    args(text = "Text to encode")
+)]
+pub async fn base64_encode(text: String) -> String {
+    BASE64.encode(text)
+}
+
+/// Base64 解码，解码结果必须是 UTF-8 文本
+#[tool(
+    description = "Decode standard base64 into text",
+    args(encoded = "Base64 string to decode")
+)]
+pub async fn base64_decode(encoded: String) -> Result<String, UtilError> {
+    let bytes = BASE64
+        .decode(encoded.trim())
+        .map_err(|e| UtilError::InvalidBase64(e.to_string()))?;
+    String::from_utf8(bytes)
+        .map_err(|_| UtilError::InvalidBase64("decoded bytes are not valid UTF-8".to_owned()))
+}
+
+/// 计算文本摘要，返回十六进制字符串
+#[tool(
+    description = "Compute the hex digest of text with sha256 or md5",
+    args(text = "Text to hash", algo = "Hash algorithm: sha256 or md5")
+)]
+pub async fn hash(text: String, algo: String) -> Result<String, UtilError> {
+    let digest = match algo.to_lowercase().replace('-', "").as_str() {
+        "sha256" => format!("{:x}", Sha256::digest(text)),
+        "md5" => format!("{:x}", Md5::digest(text)),
+        _ => return Err(UtilError::UnknownAlgorithm(algo)),
+    };
+
+    Ok(digest)
+}
+
 /// 计算表达式（简单的四则运算）
 #[tool(
     description = "Evaluate a mathematical expression (supports +, -, *, /, parentheses)",
@@ -208,6 +429,87 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_convert_units() {
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+
+        assert!(close(
+            convert_units(5.0, "km".to_owned(), "mi".to_owned())
+                .await
+                .unwrap(),
+            3.106_855_961_185_987
+        ));
+        assert!(close(
+            convert_units(100.0, "Celsius".to_owned(), "F".to_owned())
+                .await
+                .unwrap(),
+            212.0
+        ));
+        assert!(close(
+            convert_units(-40.0, "f".to_owned(), "c".to_owned())
+                .await
+                .unwrap(),
+            -40.0
+        ));
+        assert!(close(
+            convert_units(90.0, "minutes".to_owned(), "h".to_owned())
+                .await
+                .unwrap(),
+            1.5
+        ));
+
+        assert!(matches!(
+            convert_units(1.0, "kg".to_owned(), "m".to_owned()).await,
+            Err(UtilError::IncompatibleUnits { .. })
+        ));
+        assert!(matches!(
+            convert_units(1.0, "parsec".to_owned(), "m".to_owned()).await,
+            Err(UtilError::UnknownUnit(unit)) if unit == "parsec"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_statistics() {
+        let numbers = vec![2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0];
+        let stat = |op: &str| statistics(numbers.clone(), op.to_owned());
+
+        assert_eq!(stat("mean").await.unwrap(), 5.0);
+        assert_eq!(stat("median").await.unwrap(), 4.5);
+        assert_eq!(stat("stddev").await.unwrap(), 2.0);
+        assert_eq!(stat("min").await.unwrap(), 2.0);
+        assert_eq!(stat("max").await.unwrap(), 9.0);
+        assert!(stat("mode").await.is_err());
+        assert!(statistics(Vec::new(), "mean".to_owned()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_base64_round_trip() {
+        let encoded = base64_encode("hello, 世界".to_owned()).await;
+        assert_eq!(encoded, "aGVsbG8sIOS4lueVjA==");
+        assert_eq!(base64_decode(encoded).await.unwrap(), "hello, 世界");
+
+        assert!(matches!(
+            base64_decode("not base64!".to_owned()).await,
+            Err(UtilError::InvalidBase64(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_hash() {
+        assert_eq!(
+            hash("abc".to_owned(), "sha256".to_owned()).await.unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            hash("abc".to_owned(), "MD5".to_owned()).await.unwrap(),
+            "900150983cd24fb0d6963f7d28e17f72"
+        );
+        assert!(matches!(
+            hash("abc".to_owned(), "sha1".to_owned()).await,
+            Err(UtilError::UnknownAlgorithm(algo)) if algo == "sha1"
+        ));
+    }
+
     #[tokio::test]
     async fn test_get_current_time() {
         // 测试获取当前时间
@@ -0,0 +1,498 @@
+//! 天气查询工具
+//!
+//! 默认使用 [Open-Meteo](https://open-meteo.com)（无需 API key）：先用地理编码接口把地名解析为经纬度，
+//! 再查询当前天气和未来几天的预报。
+//!
+//! ```no_run
+//! use langchain_tools::weather::{OpenMeteo, Units};
+//!
+//! # async fn example() -> Result<(), langchain_tools::weather::WeatherError> {
+//! let report = OpenMeteo::new().weather("Paris, France", Units::Metric).await?;
+//! println!("{}: {}", report.location, report.current.conditions);
+//!
+//! // 注册到 Agent 的工具，名称为 `get_weather`
+//! let tool = OpenMeteo::new().with_forecast_days(5).tool();
+//! # Ok(())
+//! # }
+//! ```
+
+use langchain_core::{error::ToolError, http::shared_client, state::RegisteredTool};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Open-Meteo 地理编码接口地址
+pub const OPEN_METEO_GEOCODING_URL: &str = "https://geocoding-api.open-meteo.com/v1";
+
+/// Open-Meteo 天气预报接口地址
+pub const OPEN_METEO_FORECAST_URL: &str = "https://api.open-meteo.com/v1";
+
+/// 默认的预报天数（包括今天）
+pub const DEFAULT_FORECAST_DAYS: u8 = 3;
+
+/// 地名带有限定部分（如 `Paris, France`）时查询的候选地点数
+const GEOCODING_CANDIDATES: usize = 10;
+
+/// 天气查询错误
+#[derive(Debug, Error)]
+pub enum WeatherError {
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("unknown location: {0}")]
+    UnknownLocation(String),
+
+    #[error("unexpected response: {0}")]
+    Parse(String),
+}
+
+/// 未知地点作为参数错误返回，让模型换一个地名重试
+impl From<WeatherError> for ToolError {
+    fn from(e: WeatherError) -> Self {
+        match e {
+            WeatherError::UnknownLocation(_) => ToolError::InvalidArguments(e.to_string()),
+            WeatherError::Http(e) => ToolError::tool_call(e),
+            WeatherError::Parse(_) => ToolError::ExecutionFailed(e.to_string()),
+        }
+    }
+}
+
+/// 单位制
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Units {
+    /// 摄氏度、km/h、毫米
+    #[default]
+    Metric,
+    /// 华氏度、mph、英寸
+    Imperial,
+}
+
+/// 当前天气
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CurrentWeather {
+    pub temperature: f64,
+    pub apparent_temperature: f64,
+    /// 相对湿度（%）
+    pub humidity: f64,
+    pub wind_speed: f64,
+    /// 天气状况的文字描述，见 [`describe_weather_code`]
+    pub conditions: String,
+}
+
+/// 某一天的预报
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DailyForecast {
+    /// 当地日期，`YYYY-MM-DD`
+    pub date: String,
+    pub temperature_max: f64,
+    pub temperature_min: f64,
+    pub precipitation: f64,
+    pub conditions: String,
+}
+
+/// 一次查询的结果
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WeatherReport {
+    /// 解析后的地点，例如 `Paris, Île-de-France, France`
+    pub location: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub units: Units,
+    pub current: CurrentWeather,
+    pub forecast: Vec<DailyForecast>,
+}
+
+/// 把 WMO 天气代码转换为简短的英文描述
+pub fn describe_weather_code(code: u8) -> &'static str {
+    match code {
+        0 => "clear sky",
+        1 => "mainly clear",
+        2 => "partly cloudy",
+        3 => "overcast",
+        45 | 48 => "fog",
+        51 | 53 | 55 => "drizzle",
+        56 | 57 => "freezing drizzle",
+        61 | 63 | 65 => "rain",
+        66 | 67 => "freezing rain",
+        71 | 73 | 75 | 77 => "snow",
+        80..=82 => "rain showers",
+        85 | 86 => "snow showers",
+        95 => "thunderstorm",
+        96 | 99 => "thunderstorm with hail",
+        _ => "unknown",
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct GeocodingResponse {
+    #[serde(default)]
+    results: Vec<Place>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Place {
+    name: String,
+    latitude: f64,
+    longitude: f64,
+    #[serde(default)]
+    country: Option<String>,
+    #[serde(default)]
+    country_code: Option<String>,
+    #[serde(default)]
+    admin1: Option<String>,
+}
+
+impl Place {
+    fn display_name(&self) -> String {
+        [
+            Some(&self.name),
+            self.admin1.as_ref(),
+            self.country.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(", ")
+    }
+
+    /// 国家、国家代码或一级行政区与 `qualifier` 相符
+    fn matches(&self, qualifier: &str) -> bool {
+        [&self.country, &self.country_code, &self.admin1]
+            .into_iter()
+            .flatten()
+            .any(|part| part.eq_ignore_ascii_case(qualifier))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ForecastResponse {
+    current: CurrentResponse,
+    daily: DailyResponse,
+}
+
+#[derive(Debug, Deserialize)]
+struct CurrentResponse {
+    temperature_2m: f64,
+    apparent_temperature: f64,
+    relative_humidity_2m: f64,
+    wind_speed_10m: f64,
+    weather_code: u8,
+}
+
+#[derive(Debug, Deserialize)]
+struct DailyResponse {
+    time: Vec<String>,
+    weather_code: Vec<u8>,
+    temperature_2m_max: Vec<f64>,
+    temperature_2m_min: Vec<f64>,
+    precipitation_sum: Vec<f64>,
+}
+
+/// `get_weather` 工具的参数
+#[derive(Debug, Deserialize, JsonSchema)]
+struct GetWeatherArgs {
+    /// City or place name, optionally qualified with a region or country, e.g. "Paris, France"
+    location: String,
+    /// "metric" (default) or "imperial"
+    units: Option<Units>,
+}
+
+/// Open-Meteo 天气查询
+#[derive(Debug, Clone)]
+pub struct OpenMeteo {
+    client: reqwest::Client,
+    geocoding_url: String,
+    forecast_url: String,
+    forecast_days: u8,
+}
+
+impl Default for OpenMeteo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OpenMeteo {
+    pub fn new() -> Self {
+        Self {
+            client: shared_client(),
+            geocoding_url: OPEN_METEO_GEOCODING_URL.to_owned(),
+            forecast_url: OPEN_METEO_FORECAST_URL.to_owned(),
+            forecast_days: DEFAULT_FORECAST_DAYS,
+        }
+    }
+
+    /// 使用自建或商业版的 Open-Meteo 接口地址
+    pub fn with_base_urls(
+        mut self,
+        geocoding_url: impl Into<String>,
+        forecast_url: impl Into<String>,
+    ) -> Self {
+        self.geocoding_url = geocoding_url.into();
+        self.forecast_url = forecast_url.into();
+        self
+    }
+
+    /// 使用自定义的 HTTP 客户端（超时、代理等）
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// 预报天数（包括今天），小于 1 的值按 1 处理
+    pub fn with_forecast_days(mut self, days: u8) -> Self {
+        self.forecast_days = days.max(1);
+        self
+    }
+
+    /// 把地名解析为地点；`Paris, France` 这样的地名按逗号后的部分筛选候选地点，没有相符的候选时视为未知地点
+    async fn geocode(&self, location: &str) -> Result<Place, WeatherError> {
+        let (name, qualifier) = match location.split_once(',') {
+            Some((name, qualifier)) => (name.trim(), Some(qualifier.trim())),
+            None => (location.trim(), None),
+        };
+        let count = if qualifier.is_some() {
+            GEOCODING_CANDIDATES
+        } else {
+            1
+        };
+        let response: GeocodingResponse = self
+            .client
+            .get(format!("{}/search", self.geocoding_url))
+            .query(&[
+                ("name", name),
+                ("count", &count.to_string()),
+                ("language", "en"),
+                ("format", "json"),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let mut places = response.results;
+        // 限定部分与所有候选地点都不相符时不退回第一个候选，避免查到同名的其他地点
+        let index = match qualifier {
+            Some(qualifier) => places.iter().position(|p| p.matches(qualifier)),
+            None => (!places.is_empty()).then_some(0),
+        };
+        match index {
+            Some(index) => Ok(places.swap_remove(index)),
+            None => Err(WeatherError::UnknownLocation(location.to_owned())),
+        }
+    }
+
+    /// 查询 `location` 的当前天气和预报
+    pub async fn weather(
+        &self,
+        location: &str,
+        units: Units,
+    ) -> Result<WeatherReport, WeatherError> {
+        let place = self.geocode(location).await?;
+        tracing::debug!("Resolved `{location}` to {}", place.display_name());
+
+        let mut query = vec![
+            ("latitude", place.latitude.to_string()),
+            ("longitude", place.longitude.to_string()),
+            (
+                "current",
+                "temperature_2m,apparent_temperature,relative_humidity_2m,wind_speed_10m,weather_code"
+                    .to_owned(),
+            ),
+            (
+                "daily",
+                "weather_code,temperature_2m_max,temperature_2m_min,precipitation_sum".to_owned(),
+            ),
+            ("timezone", "auto".to_owned()),
+            ("forecast_days", self.forecast_days.to_string()),
+        ];
+        if units == Units::Imperial {
+            query.extend([
+                ("temperature_unit", "fahrenheit".to_owned()),
+                ("wind_speed_unit", "mph".to_owned()),
+                ("precipitation_unit", "inch".to_owned()),
+            ]);
+        }
+        let response: ForecastResponse = self
+            .client
+            .get(format!("{}/forecast", self.forecast_url))
+            .query(&query)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let daily = response.daily;
+        let days = daily.time.len();
+        if [
+            daily.weather_code.len(),
+            daily.temperature_2m_max.len(),
+            daily.temperature_2m_min.len(),
+            daily.precipitation_sum.len(),
+        ]
+        .iter()
+        .any(|len| *len != days)
+        {
+            return Err(WeatherError::Parse(
+                "daily forecast columns have different lengths".to_owned(),
+            ));
+        }
+        let forecast = (0..days)
+            .map(|i| DailyForecast {
+                date: daily.time[i].clone(),
+                temperature_max: daily.temperature_2m_max[i],
+                temperature_min: daily.temperature_2m_min[i],
+                precipitation: daily.precipitation_sum[i],
+                conditions: describe_weather_code(daily.weather_code[i]).to_owned(),
+            })
+            .collect();
+
+        let current = response.current;
+        Ok(WeatherReport {
+            location: place.display_name(),
+            latitude: place.latitude,
+            longitude: place.longitude,
+            units,
+            current: CurrentWeather {
+                temperature: current.temperature_2m,
+                apparent_temperature: current.apparent_temperature,
+                humidity: current.relative_humidity_2m,
+                wind_speed: current.wind_speed_10m,
+                conditions: describe_weather_code(current.weather_code).to_owned(),
+            },
+            forecast,
+        })
+    }
+
+    /// 可以注册到 Agent 的 `get_weather` 工具
+    ///
+    /// 找不到地点时返回 [`ToolError::InvalidArguments`]，让模型换一个地名重试。
+    pub fn tool(&self) -> RegisteredTool<ToolError> {
+        let provider = self.clone();
+        RegisteredTool::from_typed(
+            "get_weather".to_owned(),
+            "Get the current weather and a short daily forecast for a location".to_owned(),
+            move |args: GetWeatherArgs| {
+                let provider = provider.clone();
+                async move {
+                    provider
+                        .weather(&args.location, args.units.unwrap_or_default())
+                        .await
+                        .map_err(ToolError::from)
+                }
+            },
+        )
+    }
+}
+
+/// 使用默认配置的 Open-Meteo 查询天气
+pub async fn get_weather(location: &str, units: Units) -> Result<WeatherReport, WeatherError> {
+    OpenMeteo::new().weather(location, units).await
+}
+
+/// 使用默认配置的 Open-Meteo 的 `get_weather` 工具，见 [`OpenMeteo::tool`]
+pub fn get_weather_tool() -> RegisteredTool<ToolError> {
+    OpenMeteo::new().tool()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use langchain_core::testing::{MockHttpServer, json_response};
+    use serde_json::{Value, json};
+
+    async fn mock_server(responses: Vec<Value>) -> MockHttpServer {
+        MockHttpServer::start(
+            responses
+                .iter()
+                .map(|body| json_response(&body.to_string()))
+                .collect(),
+        )
+        .await
+    }
+
+    fn forecast() -> Value {
+        json!({
+            "current": {
+                "temperature_2m": 55.4,
+                "apparent_temperature": 52.1,
+                "relative_humidity_2m": 81,
+                "wind_speed_10m": 7.2,
+                "weather_code": 3
+            },
+            "daily": {
+                "time": ["2026-10-16", "2026-10-17"],
+                "weather_code": [3, 61],
+                "temperature_2m_max": [58.1, 54.0],
+                "temperature_2m_min": [47.3, 45.9],
+                "precipitation_sum": [0.0, 0.31]
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn geocodes_then_fetches_current_weather_and_forecast() {
+        let geocoding = json!({"results": [
+            {"name": "Paris", "latitude": 33.66, "longitude": -95.55, "country": "United States", "country_code": "US", "admin1": "Texas"},
+            {"name": "Paris", "latitude": 48.85, "longitude": 2.35, "country": "France", "country_code": "FR", "admin1": "Île-de-France"}
+        ]});
+        let server = mock_server(vec![geocoding, forecast()]).await;
+        let provider = OpenMeteo::new().with_base_urls(server.url(), server.url());
+
+        let report = provider
+            .weather("Paris, France", Units::Imperial)
+            .await
+            .unwrap();
+
+        assert_eq!(report.location, "Paris, Île-de-France, France");
+        assert_eq!((report.latitude, report.longitude), (48.85, 2.35));
+        assert_eq!(report.current.temperature, 55.4);
+        assert_eq!(report.current.conditions, "overcast");
+        assert_eq!(report.forecast.len(), 2);
+        assert_eq!(report.forecast[1].conditions, "rain");
+        assert_eq!(report.forecast[1].precipitation, 0.31);
+
+        let requests = server.requests();
+        assert!(requests[0].starts_with("GET /search?name=Paris&count=10"));
+        assert!(requests[1].starts_with("GET /forecast?latitude=48.85&longitude=2.35"));
+        assert!(requests[1].contains("temperature_unit=fahrenheit"));
+    }
+
+    #[tokio::test]
+    async fn unknown_location_is_an_invalid_argument() {
+        let server = mock_server(vec![json!({"generationtime_ms": 0.5})]).await;
+        let tool = OpenMeteo::new()
+            .with_base_urls(server.url(), server.url())
+            .tool();
+
+        let result = (tool.handler)(json!({"location": "Atlantis"})).await;
+
+        assert!(matches!(
+            result,
+            Err(ToolError::InvalidArguments(message)) if message == "unknown location: Atlantis"
+        ));
+    }
+
+    #[tokio::test]
+    async fn unmatched_qualifier_is_an_unknown_location() {
+        let geocoding = json!({"results": [
+            {"name": "Paris", "latitude": 33.66, "longitude": -95.55, "country": "United States", "country_code": "US", "admin1": "Texas"},
+            {"name": "Paris", "latitude": 48.85, "longitude": 2.35, "country": "France", "country_code": "FR", "admin1": "Île-de-France"}
+        ]});
+        let server = mock_server(vec![geocoding]).await;
+        let provider = OpenMeteo::new().with_base_urls(server.url(), server.url());
+
+        let result = provider.weather("Paris, Germany", Units::Metric).await;
+
+        assert!(matches!(
+            result,
+            Err(WeatherError::UnknownLocation(location)) if location == "Paris, Germany"
+        ));
+        // 没有继续查询天气
+        assert_eq!(server.requests().len(), 1);
+    }
+}
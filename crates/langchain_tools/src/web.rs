@@ -1,10 +1,32 @@
 //! Web 搜索工具
 //!
-//! 使用 DuckDuckGo API 进行 Web 搜索。
+//! 使用 DuckDuckGo 进行 Web 搜索。默认依次尝试 HTML 版、Lite 版页面和 Instant Answer API：
+//! 某个后端被限流、页面结构无法识别或没有结果时换下一个后端。临时网络错误会先按
+//! [`RetryConfig`] 退避重试；被限流时不等待，直接换下一个后端。
 
-use langchain_core::tool;
+use std::{sync::LazyLock, time::Duration};
+
+use langchain_core::{
+    ToolError,
+    error::{ErrorCategory, Jitter, LangChainError, RetryConfig, retry_with_backoff_hint},
+    http::shared_client,
+    state::RegisteredTool,
+    tool,
+};
+use regex::Regex;
 use thiserror::Error;
 
+const USER_AGENT: &str = "Mozilla/5.0 (compatible; LangChainBot/1.0)";
+
+/// DuckDuckGo HTML 版搜索页面地址
+pub const DUCKDUCKGO_HTML_URL: &str = "https://html.duckduckgo.com/html/";
+
+/// DuckDuckGo Lite 版搜索页面地址
+pub const DUCKDUCKGO_LITE_URL: &str = "https://lite.duckduckgo.com/lite/";
+
+/// DuckDuckGo Instant Answer API 地址
+pub const DUCKDUCKGO_API_URL: &str = "https://api.duckduckgo.com/";
+
 /// Web 搜索错误
 #[derive(Debug, Error)]
 pub enum WebSearchError {
@@ -16,6 +38,35 @@ pub enum WebSearchError {
 
     #[error("Parse error: {0}")]
     Parse(String),
+
+    /// 被 DuckDuckGo 限流（429 或人机验证页面），附带服务端建议的等待秒数
+    #[error("Rate limited by DuckDuckGo")]
+    RateLimited(Option<u64>),
+}
+
+impl LangChainError for WebSearchError {
+    fn category(&self) -> ErrorCategory {
+        match self {
+            WebSearchError::Http(e)
+                if e.is_timeout()
+                    || e.is_connect()
+                    || e.status().is_some_and(|s| s.is_server_error()) =>
+            {
+                ErrorCategory::Transient
+            }
+            WebSearchError::Http(_) => ErrorCategory::External,
+            WebSearchError::NoResults => ErrorCategory::External,
+            WebSearchError::Parse(_) => ErrorCategory::External,
+            WebSearchError::RateLimited(_) => ErrorCategory::RateLimit,
+        }
+    }
+
+    fn retry_delay_ms(&self) -> Option<u64> {
+        match self {
+            WebSearchError::RateLimited(seconds) => seconds.map(|s| s.saturating_mul(1000)),
+            _ => None,
+        }
+    }
 }
 
 /// Web 搜索结果
@@ -26,68 +77,622 @@ pub struct SearchResult {
     pub url: String,
 }
 
-/// 使用 DuckDuckGo 进行 Web 搜索
-#[tool(
-    description = "Search the web for information using DuckDuckGo",
-    args(
-        query = "Search query",
-        max_results = "Maximum number of results (default: 5)"
+/// DuckDuckGo 的搜索后端
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchBackend {
+    /// HTML 版搜索页面，结果最完整
+    Html,
+    /// Lite 版搜索页面，结构最简单
+    Lite,
+    /// Instant Answer API，只返回百科类的相关主题，很多查询没有结果
+    InstantAnswer,
+}
+
+impl SearchBackend {
+    pub fn default_url(self) -> &'static str {
+        match self {
+            SearchBackend::Html => DUCKDUCKGO_HTML_URL,
+            SearchBackend::Lite => DUCKDUCKGO_LITE_URL,
+            SearchBackend::InstantAnswer => DUCKDUCKGO_API_URL,
+        }
+    }
+}
+
+/// DuckDuckGo 搜索，可以指定使用的 HTTP 客户端、后端和重试策略
+///
+/// [`search_web`] 使用共享的默认客户端。需要代理或自定义超时时：
+///
+/// ```rust,ignore
+/// let client = reqwest::Client::builder().proxy(proxy).build()?;
+/// let tool = WebSearch::new().with_client(client).tool();
+/// ```
+#[derive(Debug, Clone)]
+pub struct WebSearch {
+    client: reqwest::Client,
+    backends: Vec<(SearchBackend, String)>,
+    retry: RetryConfig,
+}
+
+impl Default for WebSearch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WebSearch {
+    /// 依次使用 HTML 版、Lite 版和 Instant Answer API，每个后端最多重试 2 次，
+    /// 单个后端的重试总耗时不超过 20 秒
+    pub fn new() -> Self {
+        Self {
+            client: shared_client(),
+            backends: Vec::new(),
+            retry: RetryConfig {
+                max_retries: 2,
+                initial_delay_ms: 1000,
+                max_delay_ms: 8000,
+                backoff_multiplier: 2.0,
+                jitter: Jitter::Equal,
+                max_elapsed_time: Some(Duration::from_secs(20)),
+            },
+        }
+        .with_backends([
+            SearchBackend::Html,
+            SearchBackend::Lite,
+            SearchBackend::InstantAnswer,
+        ])
+    }
+
+    /// 使用自定义的 HTTP 客户端（超时、代理等）
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// 按顺序尝试的后端，使用各自的默认地址
+    pub fn with_backends(mut self, backends: impl IntoIterator<Item = SearchBackend>) -> Self {
+        self.backends = backends
+            .into_iter()
+            .map(|backend| (backend, backend.default_url().to_owned()))
+            .collect();
+        self
+    }
+
+    /// 修改某个后端的地址（镜像、代理等），不在后端列表中时忽略
+    pub fn with_backend_url(mut self, backend: SearchBackend, url: impl Into<String>) -> Self {
+        let url = url.into();
+        for (b, u) in &mut self.backends {
+            if *b == backend {
+                u.clone_from(&url);
+            }
+        }
+        self
+    }
+
+    /// 每个后端遇到临时网络错误时的重试策略，`max_delay_ms` 同时是返回的限流等待时间的上限
+    pub fn with_retry(mut self, retry: RetryConfig) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// 依次尝试各个后端，返回第一个有结果的后端的结果
+    ///
+    /// 全部失败时，只要有后端被限流就返回 [`WebSearchError::RateLimited`]（建议的等待时间不超过
+    /// 重试策略的 `max_delay_ms`），否则返回第一个后端的错误。
+    pub async fn search(
+        &self,
+        query: &str,
+        max_results: Option<usize>,
+    ) -> Result<Vec<SearchResult>, WebSearchError> {
+        let max_results = max_results.unwrap_or(5);
+        let mut first_error = None;
+        let mut rate_limited = None;
+        for (backend, url) in &self.backends {
+            // 被限流时不在同一个后端上等待，换下一个后端
+            let result = retry_with_backoff_hint(
+                || self.search_backend(*backend, url, query),
+                |e: &WebSearchError| match e {
+                    WebSearchError::RateLimited(_) => ErrorCategory::External,
+                    e => e.category(),
+                },
+                |_| None,
+                &self.retry,
+            )
+            .await
+            .and_then(|results| {
+                if results.is_empty() {
+                    Err(WebSearchError::NoResults)
+                } else {
+                    Ok(results)
+                }
+            });
+            match result {
+                Ok(mut results) => {
+                    results.truncate(max_results);
+                    return Ok(results);
+                }
+                Err(e) => {
+                    tracing::warn!("DuckDuckGo {backend:?} search failed: {e}");
+                    match e {
+                        WebSearchError::RateLimited(seconds) => {
+                            let max_seconds = self.retry.max_delay_ms / 1000;
+                            rate_limited = Some(WebSearchError::RateLimited(
+                                seconds.map(|s| s.min(max_seconds)),
+                            ));
+                        }
+                        e => {
+                            first_error.get_or_insert(e);
+                        }
+                    }
+                }
+            }
+        }
+        Err(rate_limited
+            .or(first_error)
+            .unwrap_or(WebSearchError::NoResults))
+    }
+
+    async fn search_backend(
+        &self,
+        backend: SearchBackend,
+        url: &str,
+        query: &str,
+    ) -> Result<Vec<SearchResult>, WebSearchError> {
+        tracing::debug!("Searching DuckDuckGo {backend:?}: {url}");
+
+        let mut request = self
+            .client
+            .get(url)
+            .header(reqwest::header::USER_AGENT, USER_AGENT)
+            .query(&[("q", query)]);
+        if backend == SearchBackend::InstantAnswer {
+            request = request.query(&[("format", "json"), ("no_html", "1")]);
+        }
+        let response = request.send().await?;
+
+        // 限流时 DuckDuckGo 返回 429，或者以 202 返回人机验证页面
+        let status = response.status();
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS
+            || status == reqwest::StatusCode::ACCEPTED
+        {
+            let retry_after = response
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.trim().parse().ok());
+            return Err(WebSearchError::RateLimited(retry_after));
+        }
+        let body = response.error_for_status()?.text().await?;
+
+        match backend {
+            SearchBackend::Html | SearchBackend::Lite => parse_html_results(&body),
+            SearchBackend::InstantAnswer => parse_instant_answer(&body),
+        }
+    }
+
+    /// 与 [`search_web_tool`] 相同的 `search_web` 工具，使用本实例的客户端
+    pub fn tool(&self) -> RegisteredTool<ToolError> {
+        let search = self.clone();
+        let tool = search_web_tool();
+        RegisteredTool::from_typed(
+            tool.function.name,
+            tool.function.description,
+            move |args: SearchWebArgs| {
+                let search = search.clone();
+                async move {
+                    search
+                        .search(&args.query, args.max_results)
+                        .await
+                        .map_err(ToolError::tool_call)
+                }
+            },
+        )
+    }
+}
+
+static ANCHOR: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?is)<a\s([^>]*)>(.*?)</a>").unwrap());
+static SNIPPET: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"(?is)<(?:td|div|span)\s[^>]*class\s*=\s*["'][^"']*snippet[^"']*["'][^>]*>(.*?)</(?:td|div|span)>"#,
     )
-)]
-pub async fn search_web(
-    query: String,
-    max_results: Option<usize>,
-) -> Result<Vec<SearchResult>, WebSearchError> {
-    let client = reqwest::Client::builder()
-        .user_agent("Mozilla/5.0 (compatible; LangChainBot/1.0)")
-        .build()?;
+    .unwrap()
+});
+static ATTRIBUTE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?is)([a-z-]+)\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap());
+static TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?s)<[^>]*>").unwrap());
+
+/// 人机验证页面的特征
+const CHALLENGE_MARKERS: &[&str] = &[
+    "anomaly-modal",
+    "anomaly.js",
+    "challenge-form",
+    "bots use DuckDuckGo too",
+];
+
+/// 明确没有结果的页面的特征
+const NO_RESULTS_MARKERS: &[&str] = &["class=\"no-results\"", "No results."];
+
+/// 页面中按出现顺序排列的结果片段
+enum Fragment {
+    /// 结果链接：链接地址和标题，`explicit` 表示通过已知的 class 识别
+    Link {
+        url: String,
+        text: String,
+        explicit: bool,
+    },
+    Snippet(String),
+}
+
+/// 解析 HTML 版和 Lite 版搜索页面
+///
+/// 优先按已知的 class（`result__a`、`result-link`、`*snippet*`）识别结果；页面改版导致
+/// 找不到这些 class 时，退回到识别指向 DuckDuckGo 跳转地址（`/l/?uddg=`）的链接。
+/// 既没有结果也不是明确的无结果页面时返回 [`WebSearchError::Parse`]，而不是静默返回空列表。
+fn parse_html_results(body: &str) -> Result<Vec<SearchResult>, WebSearchError> {
+    if CHALLENGE_MARKERS.iter().any(|marker| body.contains(marker)) {
+        return Err(WebSearchError::RateLimited(None));
+    }
+
+    let mut fragments = Vec::new();
+    for anchor in ANCHOR.captures_iter(body) {
+        let position = anchor.get(0).unwrap().start();
+        let attributes = &anchor[1];
+        let class = attribute(attributes, "class").unwrap_or_default();
+        let text = clean_text(&anchor[2]);
+        if class.contains("snippet") {
+            fragments.push((position, Fragment::Snippet(text)));
+            continue;
+        }
+        let Some(url) = attribute(attributes, "href").and_then(|href| result_url(&href)) else {
+            continue;
+        };
+        let explicit = class.contains("result__a") || class.contains("result-link");
+        // 显示网址的链接不是标题
+        if !explicit && class.contains("url") {
+            continue;
+        }
+        fragments.push((
+            position,
+            Fragment::Link {
+                url,
+                text,
+                explicit,
+            },
+        ));
+    }
+    for snippet in SNIPPET.captures_iter(body) {
+        let position = snippet.get(0).unwrap().start();
+        fragments.push((position, Fragment::Snippet(clean_text(&snippet[1]))));
+    }
+    fragments.sort_by_key(|(position, _)| *position);
+
+    let has_explicit = fragments
+        .iter()
+        .any(|(_, f)| matches!(f, Fragment::Link { explicit: true, .. }));
+    let mut results: Vec<SearchResult> = Vec::new();
+    // 当前结果的摘要是否来自摘要元素，来自摘要元素时不再被同一网址的其他链接覆盖
+    let mut snippet_found = false;
+    for (_, fragment) in fragments {
+        match fragment {
+            Fragment::Link {
+                url,
+                text,
+                explicit,
+            } => {
+                if has_explicit && !explicit {
+                    continue;
+                }
+                match results.last_mut() {
+                    // 同一结果中指向同一网址的其他链接，取最长的文本作为摘要
+                    Some(last) if last.url == url => {
+                        if !snippet_found && text.len() > last.snippet.len() {
+                            last.snippet = text;
+                        }
+                    }
+                    _ if text.is_empty() => {}
+                    _ => {
+                        results.push(SearchResult {
+                            title: text,
+                            snippet: String::new(),
+                            url,
+                        });
+                        snippet_found = false;
+                    }
+                }
+            }
+            Fragment::Snippet(text) => {
+                if let Some(last) = results.last_mut()
+                    && !snippet_found
+                {
+                    last.snippet = text;
+                    snippet_found = true;
+                }
+            }
+        }
+    }
+
+    if results.is_empty()
+        && !NO_RESULTS_MARKERS
+            .iter()
+            .any(|marker| body.contains(marker))
+    {
+        return Err(WebSearchError::Parse(
+            "unrecognized DuckDuckGo result page".to_owned(),
+        ));
+    }
+    Ok(results)
+}
 
-    let url = format!(
-        "https://api.duckduckgo.com/?q={}&format=json",
-        urlencoding::encode(&query)
-    );
+/// 取出标签属性的值（已解码 HTML 实体）
+fn attribute(attributes: &str, name: &str) -> Option<String> {
+    ATTRIBUTE.captures_iter(attributes).find_map(|captures| {
+        if !captures[1].eq_ignore_ascii_case(name) {
+            return None;
+        }
+        let value = captures.get(2).or_else(|| captures.get(3))?.as_str();
+        Some(decode_entities(value))
+    })
+}
 
-    tracing::debug!("Searching DuckDuckGo: {}", url);
+/// 结果链接的目标网址：解开 DuckDuckGo 的跳转地址，过滤广告和站内链接
+fn result_url(href: &str) -> Option<String> {
+    if let Some((_, query)) = href.split_once("uddg=") {
+        let encoded = query.split('&').next().unwrap_or_default();
+        return urlencoding::decode(encoded)
+            .ok()
+            .map(|url| url.into_owned());
+    }
+    let is_external = href.starts_with("http://") || href.starts_with("https://");
+    (is_external && !href.contains("duckduckgo.com")).then(|| href.to_owned())
+}
 
-    let response = client.get(&url).send().await?;
-    let json: serde_json::Value = response.json().await?;
+/// 去掉标签、解码 HTML 实体并合并空白
+fn clean_text(html: &str) -> String {
+    let text = decode_entities(&TAG.replace_all(html, ""));
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
 
-    let results: Vec<SearchResult> = json["RelatedTopics"]
+fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_owned();
+    }
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..]
+            .find(';')
+            .filter(|&end| end <= 8)
+            .map(|end| &rest[1..=end]);
+        let character = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => {
+                let code = entity.strip_prefix('#')?;
+                let code = match code.strip_prefix(['x', 'X']) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                    None => code.parse().ok()?,
+                };
+                char::from_u32(code)
+            }
+        });
+        match (entity, character) {
+            (Some(entity), Some(character)) => {
+                decoded.push(character);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// 解析 Instant Answer API 的相关主题，包括按类别分组的主题
+fn parse_instant_answer(body: &str) -> Result<Vec<SearchResult>, WebSearchError> {
+    let json: serde_json::Value =
+        serde_json::from_str(body).map_err(|e| WebSearchError::Parse(e.to_string()))?;
+    let topics = json["RelatedTopics"]
         .as_array()
-        .ok_or(WebSearchError::NoResults)?
+        .ok_or_else(|| WebSearchError::Parse("missing RelatedTopics".to_owned()))?;
+
+    let results = topics
         .iter()
+        .flat_map(|topic| match topic["Topics"].as_array() {
+            Some(group) => group.iter().collect(),
+            None => vec![topic],
+        })
         .filter_map(|topic| {
             let text = topic["Text"].as_str()?;
             let first_url = topic["FirstURL"].as_str()?;
 
             // 解析标题和摘要
-            let parts: Vec<&str> = text.splitn(2, " - ").collect();
-            let (title, snippet) = if parts.len() == 2 {
-                (parts[0].to_string(), parts[1].to_string())
-            } else {
-                (text.to_string(), String::new())
+            let (title, snippet) = match text.split_once(" - ") {
+                Some((title, snippet)) => (title.to_owned(), snippet.to_owned()),
+                None => (text.to_owned(), String::new()),
             };
 
             Some(SearchResult {
                 title,
                 snippet,
-                url: first_url.to_string(),
+                url: first_url.to_owned(),
             })
         })
-        .take(max_results.unwrap_or(5))
         .collect();
-
-    if results.is_empty() {
-        return Err(WebSearchError::NoResults);
-    }
-
     Ok(results)
 }
 
+/// 使用 DuckDuckGo 进行 Web 搜索
+#[tool(
+    description = "Search the web for information using DuckDuckGo",
+    args(
+        query = "Search query",
+        max_results = "Maximum number of results (default: 5)"
+    )
+)]
+pub async fn search_web(
+    query: String,
+    max_results: Option<usize>,
+) -> Result<Vec<SearchResult>, WebSearchError> {
+    WebSearch::new().search(&query, max_results).await
+}
+
 #[cfg(test)]
 mod tests {
+    use langchain_core::testing::{MockHttpServer, response};
+    use serde_json::Value;
+
     use super::*;
 
+    /// 按保存的页面解析，与对应的 JSON 快照比较
+    fn assert_snapshot(page: &str, snapshot: &str) {
+        let results = parse_html_results(page).unwrap();
+        let expected: Value = serde_json::from_str(snapshot).unwrap();
+        assert_eq!(serde_json::to_value(&results).unwrap(), expected);
+    }
+
+    #[test]
+    fn parses_html_page() {
+        assert_snapshot(
+            include_str!("../tests/fixtures/duckduckgo/html.html"),
+            include_str!("../tests/fixtures/duckduckgo/html.json"),
+        );
+    }
+
+    #[test]
+    fn parses_lite_page() {
+        assert_snapshot(
+            include_str!("../tests/fixtures/duckduckgo/lite.html"),
+            include_str!("../tests/fixtures/duckduckgo/lite.json"),
+        );
+    }
+
+    #[test]
+    fn falls_back_to_redirect_links_when_classes_change() {
+        assert_snapshot(
+            include_str!("../tests/fixtures/duckduckgo/html_restyled.html"),
+            include_str!("../tests/fixtures/duckduckgo/html.json"),
+        );
+    }
+
+    #[test]
+    fn distinguishes_empty_challenge_and_unknown_pages() {
+        let empty = parse_html_results(include_str!(
+            "../tests/fixtures/duckduckgo/html_no_results.html"
+        ));
+        assert!(empty.unwrap().is_empty());
+        let challenge =
+            parse_html_results(include_str!("../tests/fixtures/duckduckgo/anomaly.html"));
+        assert!(matches!(challenge, Err(WebSearchError::RateLimited(None))));
+        let unknown = parse_html_results("<html><body><p>Something new</p></body></html>");
+        assert!(matches!(unknown, Err(WebSearchError::Parse(_))));
+    }
+
+    #[test]
+    fn parses_grouped_instant_answer_topics() {
+        let body = serde_json::json!({
+            "RelatedTopics": [
+                {"Text": "Rust - A language", "FirstURL": "https://duckduckgo.com/Rust"},
+                {"Name": "See also", "Topics": [
+                    {"Text": "Cargo - Package manager", "FirstURL": "https://duckduckgo.com/Cargo"}
+                ]}
+            ]
+        });
+        let results = parse_instant_answer(&body.to_string()).unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[1].title, "Cargo");
+        assert_eq!(results[1].snippet, "Package manager");
+    }
+
+    /// 依次用 `responses`（状态行和页面）回复每个连接
+    async fn mock_server(responses: Vec<(&'static str, String)>) -> MockHttpServer {
+        MockHttpServer::start(
+            responses
+                .iter()
+                .map(|(status, body)| response(status, "text/html", body))
+                .collect(),
+        )
+        .await
+    }
+
+    fn fast_retry() -> RetryConfig {
+        RetryConfig {
+            max_retries: 1,
+            initial_delay_ms: 1,
+            max_delay_ms: 8000,
+            ..RetryConfig::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn rate_limits_fall_back_to_the_next_backend_without_waiting() {
+        let lite = include_str!("../tests/fixtures/duckduckgo/lite.html").to_owned();
+        let server = mock_server(vec![
+            (
+                "429 Too Many Requests\r\nretry-after: 18446744073709551615",
+                String::new(),
+            ),
+            ("503 Service Unavailable", String::new()),
+            ("200 OK", lite),
+        ])
+        .await;
+        let url = server.url();
+        let search = WebSearch::new()
+            .with_backends([SearchBackend::Html, SearchBackend::Lite])
+            .with_backend_url(SearchBackend::Html, format!("{url}/html/"))
+            .with_backend_url(SearchBackend::Lite, format!("{url}/lite/"))
+            .with_retry(fast_retry());
+
+        let started = std::time::Instant::now();
+        let results = search.search("rust lang", Some(2)).await.unwrap();
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].url, "https://www.rust-lang.org/");
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        assert!(requests[0].starts_with("GET /html/?q=rust+lang"));
+        // Lite 版的临时错误仍然重试
+        assert!(requests[1].starts_with("GET /lite/"));
+        assert!(requests[2].starts_with("GET /lite/"));
+    }
+
+    #[tokio::test]
+    async fn reports_rate_limits_when_every_backend_is_limited() {
+        let anomaly = include_str!("../tests/fixtures/duckduckgo/anomaly.html").to_owned();
+        // HTML 版以 202 返回验证页面，Lite 版以 429 要求等待很久
+        let server = mock_server(vec![
+            ("202 Accepted", anomaly),
+            ("429 Too Many Requests\r\nretry-after: 86400", String::new()),
+        ])
+        .await;
+        let url = server.url();
+        let search = WebSearch::new()
+            .with_backends([SearchBackend::Html, SearchBackend::Lite])
+            .with_backend_url(SearchBackend::Html, format!("{url}/html/"))
+            .with_backend_url(SearchBackend::Lite, format!("{url}/lite/"))
+            .with_retry(fast_retry());
+
+        let error = search.search("rust", None).await.unwrap_err();
+        assert!(matches!(error, WebSearchError::RateLimited(Some(8))));
+        assert_eq!(error.retry_delay_ms(), Some(8000));
+        assert!(error.is_retryable());
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[test]
+    fn huge_retry_after_does_not_overflow() {
+        let error = WebSearchError::RateLimited(Some(u64::MAX));
+        assert_eq!(error.retry_delay_ms(), Some(u64::MAX));
+    }
+
     #[tokio::test]
     #[ignore] // 需要网络连接
     async fn test_search_web() {
@@ -0,0 +1,132 @@
+//! 用 `#[derive(GraphState)]` 定义非对话的图状态
+//!
+//! `Produce` 节点每步生成一个条目，`Count` 节点累加计数，直到收集到足够的条目。
+//!
+//! ```sh
+//! cargo run -p langgraph --example graph_state
+//! ```
+
+use std::{collections::HashMap, convert::Infallible};
+
+use async_trait::async_trait;
+use langgraph::{
+    GraphLabel, GraphState,
+    checkpoint::Configuration,
+    node::{EventSink, Node, NodeContext},
+    state_graph::{GraphSpec, RunStrategy, StateGraph},
+};
+use serde::{Deserialize, Serialize};
+
+const TARGET: usize = 3;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, GraphState)]
+struct CounterState {
+    /// 每次更新累加
+    #[reducer(add)]
+    count: u32,
+    /// 每次更新追加到末尾
+    #[reducer(append)]
+    items: Vec<String>,
+    /// 默认为替换：更新中为 `Some` 时覆盖
+    last_node: String,
+}
+
+struct CounterSpec;
+
+impl GraphSpec for CounterSpec {
+    type State = CounterState;
+    type Update = CounterStateDiff;
+    type Error = Infallible;
+    type Event = ();
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, GraphLabel)]
+enum Step {
+    Produce,
+    Count,
+}
+
+struct Produce;
+
+#[async_trait]
+impl Node<CounterState, CounterStateDiff, Infallible, ()> for Produce {
+    async fn run_sync(
+        &self,
+        input: &CounterState,
+        _context: NodeContext<'_>,
+    ) -> Result<CounterStateDiff, Infallible> {
+        Ok(CounterStateDiff {
+            items: vec![format!("item-{}", input.items.len())],
+            last_node: Some("Produce".to_owned()),
+            ..Default::default()
+        })
+    }
+
+    async fn run_stream(
+        &self,
+        input: &CounterState,
+        _sink: &dyn EventSink<()>,
+        context: NodeContext<'_>,
+    ) -> Result<CounterStateDiff, Infallible> {
+        self.run_sync(input, context).await
+    }
+}
+
+struct Count;
+
+#[async_trait]
+impl Node<CounterState, CounterStateDiff, Infallible, ()> for Count {
+    async fn run_sync(
+        &self,
+        _input: &CounterState,
+        _context: NodeContext<'_>,
+    ) -> Result<CounterStateDiff, Infallible> {
+        Ok(CounterStateDiff {
+            count: 1,
+            last_node: Some("Count".to_owned()),
+            ..Default::default()
+        })
+    }
+
+    async fn run_stream(
+        &self,
+        input: &CounterState,
+        _sink: &dyn EventSink<()>,
+        context: NodeContext<'_>,
+    ) -> Result<CounterStateDiff, Infallible> {
+        self.run_sync(input, context).await
+    }
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let mut graph: StateGraph<CounterSpec> = StateGraph::from_state(Step::Produce);
+    graph.add_node(Step::Produce, Produce);
+    graph.add_node(Step::Count, Count);
+    graph.add_edge(Step::Produce, Step::Count);
+    graph.add_condition_edge(
+        Step::Count,
+        HashMap::from([(Step::Produce.intern(), Step::Produce.intern())]),
+        // 条目足够时不选择任何分支，运行结束
+        |state: &CounterState| {
+            if state.items.len() < TARGET {
+                smallvec::smallvec![Step::Produce.intern()]
+            } else {
+                smallvec::smallvec![]
+            }
+        },
+    );
+
+    let (state, _) = graph
+        .run(
+            CounterState::default(),
+            &Configuration::default(),
+            20,
+            RunStrategy::StopAtNonLinear,
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(state.count, TARGET as u32);
+    println!("{state:#?}");
+}
@@ -0,0 +1,98 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{Data, DeriveInput, Fields, spanned::Spanned};
+
+/// 字段的合并方式，由 `#[reducer(...)]` 指定
+enum Reducer {
+    Replace,
+    Append,
+    Add,
+}
+
+fn field_reducer(field: &syn::Field) -> syn::Result<Reducer> {
+    let mut reducer = Reducer::Replace;
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("reducer")) {
+        attr.parse_nested_meta(|meta| {
+            reducer = if meta.path.is_ident("replace") {
+                Reducer::Replace
+            } else if meta.path.is_ident("append") {
+                Reducer::Append
+            } else if meta.path.is_ident("add") {
+                Reducer::Add
+            } else {
+                return Err(meta.error("expected `replace`, `append` or `add`"));
+            };
+            Ok(())
+        })?;
+    }
+    Ok(reducer)
+}
+
+pub fn derive_graph_state_impl(input: DeriveInput) -> syn::Result<TokenStream> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new(
+            input.span(),
+            "GraphState can only be derived for structs with named fields",
+        ));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(syn::Error::new(
+            data.fields.span(),
+            "GraphState can only be derived for structs with named fields",
+        ));
+    };
+
+    let ident = &input.ident;
+    let vis = &input.vis;
+    let diff = format_ident!("{}Diff", ident);
+    let generics = &input.generics;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let mut diff_fields = Vec::new();
+    let mut applies = Vec::new();
+    for field in &fields.named {
+        let name = field.ident.as_ref().expect("named field");
+        let field_vis = &field.vis;
+        let ty = &field.ty;
+        match field_reducer(field)? {
+            Reducer::Replace => {
+                diff_fields.push(quote! { #field_vis #name: ::core::option::Option<#ty> });
+                applies.push(quote! {
+                    if let ::core::option::Option::Some(value) = diff.#name {
+                        self.#name = value;
+                    }
+                });
+            }
+            Reducer::Append => {
+                diff_fields.push(quote! { #field_vis #name: #ty });
+                applies.push(quote! {
+                    ::core::iter::Extend::extend(&mut self.#name, diff.#name);
+                });
+            }
+            Reducer::Add => {
+                diff_fields.push(quote! { #field_vis #name: #ty });
+                applies.push(quote! {
+                    self.#name += diff.#name;
+                });
+            }
+        }
+    }
+
+    let langgraph = crate::langgraph_path();
+    let doc = format!("Update type of [`{ident}`] generated by `#[derive(GraphState)]`.");
+    Ok(quote! {
+        #[doc = #doc]
+        #[derive(Default)]
+        #vis struct #diff #generics #where_clause {
+            #(#diff_fields,)*
+        }
+
+        impl #impl_generics #langgraph::graph_state::GraphState for #ident #ty_generics #where_clause {
+            type Diff = #diff #ty_generics;
+
+            fn apply(&mut self, diff: Self::Diff) {
+                #(#applies)*
+            }
+        }
+    })
+}
@@ -1,7 +1,38 @@
 use proc_macro::TokenStream;
-use quote::{quote, quote_spanned};
+use proc_macro_crate::{FoundCrate, crate_name};
+use quote::{format_ident, quote, quote_spanned};
 use syn::{DeriveInput, parse_macro_input, spanned::Spanned};
 
+mod graph_state;
+
+/// 生成代码中引用 langgraph 的路径
+///
+/// 依赖方可能在 `Cargo.toml` 中重命名了 langgraph；在 langgraph 的库代码内部使用 `crate`，
+/// 它自己的示例和集成测试则按依赖的名称引用。
+pub(crate) fn langgraph_path() -> proc_macro2::TokenStream {
+    match crate_name("langgraph") {
+        Ok(FoundCrate::Itself)
+            if std::env::var("CARGO_CRATE_NAME").as_deref() == Ok("langgraph") =>
+        {
+            quote!(crate)
+        }
+        Ok(FoundCrate::Name(name)) => {
+            let name = format_ident!("{name}");
+            quote!(::#name)
+        }
+        _ => quote!(::langgraph),
+    }
+}
+
+#[proc_macro_derive(GraphState, attributes(reducer))]
+pub fn graph_state_derive(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    graph_state::derive_graph_state_impl(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
 #[proc_macro_derive(GraphLabel)]
 pub fn graph_label_derive(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
@@ -66,6 +97,56 @@ fn derive_graph_label_impl(input: DeriveInput) -> TokenStream {
         _ => quote! {}, // union 已经提前返回
     };
 
+    // --- intern() 实现 ---
+    // 单元结构体和全部为单元变体的枚举每个值对应一个静态缓存；
+    // 泛型类型的静态变量会被所有实例化共享，带字段的值也无法按值缓存，仍使用默认实现
+    let langgraph = langgraph_path();
+    let intern_impl = if !input.generics.params.is_empty() {
+        quote! {}
+    } else {
+        match &input.data {
+            syn::Data::Enum(data_enum)
+                if !data_enum.variants.is_empty()
+                    && data_enum
+                        .variants
+                        .iter()
+                        .all(|variant| matches!(variant.fields, syn::Fields::Unit)) =>
+            {
+                let count = data_enum.variants.len();
+                let arms = data_enum
+                    .variants
+                    .iter()
+                    .enumerate()
+                    .map(|(index, variant)| {
+                        let v_ident = &variant.ident;
+                        quote! {
+                            #ident::#v_ident => #index,
+                        }
+                    });
+                quote! {
+                    fn intern(&self) -> #langgraph::label::InternedGraphLabel {
+                        static CACHE: [#langgraph::label::LabelCache; #count] =
+                            [const { #langgraph::label::LabelCache::new() }; #count];
+                        let index = match self {
+                            #(#arms)*
+                        };
+                        CACHE[index].get_or_intern(self)
+                    }
+                }
+            }
+            syn::Data::Struct(data_struct) if matches!(data_struct.fields, syn::Fields::Unit) => {
+                quote! {
+                    fn intern(&self) -> #langgraph::label::InternedGraphLabel {
+                        static CACHE: #langgraph::label::LabelCache =
+                            #langgraph::label::LabelCache::new();
+                        CACHE.get_or_intern(self)
+                    }
+                }
+            }
+            _ => quote! {},
+        }
+    };
+
     quote! {
         // To ensure alloc is available, but also prevent its name from clashing, we place the implementation inside an anonymous constant
         // 把 extern crate alloc 和 impl 块包裹在一个局部作用域中，避免在用户模块顶层直接引入名为 alloc 的 crate，和用户自己的 use alloc 等名字起冲突。
@@ -78,6 +159,8 @@ fn derive_graph_label_impl(input: DeriveInput) -> TokenStream {
                 }
 
                 #as_str_impl
+
+                #intern_impl
             }
         };
     }
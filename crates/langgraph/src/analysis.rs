@@ -0,0 +1,227 @@
+//! 图结构分析
+//!
+//! 只根据静态可知的边（普通边与条件边的全部分支）分析图结构，帮助判断 `max_steps`
+//! 是否够用，例如排查 "agent hit max_steps"：
+//! - 有环的图（如 ReAct 的模型 ↔ 工具循环）运行步数取决于运行时的路由，只报告不拒绝
+//! - 无环路径的最大长度是不重复经过任何节点时一次运行需要的 super-step 数
+//!
+//! 扇出边的目标在运行时才能确定，不计入分析。
+
+use std::collections::HashSet;
+
+use crate::{edge::Edge, graph::Graph, label::InternedGraphLabel};
+
+impl<S: Clone + Default, I, O, E: std::fmt::Debug, Ev: std::fmt::Debug> Graph<S, I, O, E, Ev> {
+    /// 节点静态可知的后继，按名称排序并去重
+    fn successors(&self, label: InternedGraphLabel) -> Vec<InternedGraphLabel> {
+        let Some(node_state) = self.nodes.get(&label) else {
+            return Vec::new();
+        };
+        let mut next = Vec::new();
+        for edge in &node_state.edges {
+            match edge {
+                Edge::NodeEdge(to) => next.push(*to),
+                Edge::ConditionalEdge { next_nodes, .. } => {
+                    next.extend(next_nodes.iter().map(|(_, to)| *to));
+                }
+                Edge::FanOutEdge { .. } => {}
+            }
+        }
+        let mut seen = HashSet::new();
+        next.retain(|l| seen.insert(*l));
+        next.sort_by_key(|l| l.as_str());
+        next
+    }
+
+    /// 图中是否存在环（包括指向自身的边）
+    pub fn has_cycles(&self) -> bool {
+        // 深度优先搜索，遇到仍在当前路径上的节点即说明有环
+        fn visit<S: Clone + Default, I, O, E: std::fmt::Debug, Ev: std::fmt::Debug>(
+            graph: &Graph<S, I, O, E, Ev>,
+            label: InternedGraphLabel,
+            on_path: &mut HashSet<InternedGraphLabel>,
+            done: &mut HashSet<InternedGraphLabel>,
+        ) -> bool {
+            if done.contains(&label) {
+                return false;
+            }
+            if !on_path.insert(label) {
+                return true;
+            }
+            let cyclic = graph
+                .successors(label)
+                .into_iter()
+                .any(|next| visit(graph, next, on_path, done));
+            on_path.remove(&label);
+            done.insert(label);
+            cyclic
+        }
+
+        let mut on_path = HashSet::new();
+        let mut done = HashSet::new();
+        self.nodes
+            .keys()
+            .any(|label| visit(self, *label, &mut on_path, &mut done))
+    }
+
+    /// 从 `start` 出发可以到达的节点（包括 `start` 本身），按名称排序
+    pub fn reachable_from(&self, start: InternedGraphLabel) -> Vec<InternedGraphLabel> {
+        let mut reachable = HashSet::from([start]);
+        let mut queue = vec![start];
+        while let Some(label) = queue.pop() {
+            for next in self.successors(label) {
+                if reachable.insert(next) {
+                    queue.push(next);
+                }
+            }
+        }
+        let mut reachable: Vec<_> = reachable.into_iter().collect();
+        reachable.sort_by_key(|l| l.as_str());
+        reachable
+    }
+
+    /// 从 `start` 出发、不重复经过任何节点的最长路径（包括 `start` 本身）
+    ///
+    /// 路径长度即沿这条路径运行一次需要的 super-step 数；长度相同时取按名称先遍历到的一条。
+    /// 穷举所有简单路径，适用于节点数不多的图。
+    pub fn longest_acyclic_path(&self, start: InternedGraphLabel) -> Vec<InternedGraphLabel> {
+        fn extend<S: Clone + Default, I, O, E: std::fmt::Debug, Ev: std::fmt::Debug>(
+            graph: &Graph<S, I, O, E, Ev>,
+            path: &mut Vec<InternedGraphLabel>,
+            longest: &mut Vec<InternedGraphLabel>,
+        ) {
+            if path.len() > longest.len() {
+                longest.clone_from(path);
+            }
+            let Some(last) = path.last().copied() else {
+                return;
+            };
+            for next in graph.successors(last) {
+                if !path.contains(&next) {
+                    path.push(next);
+                    extend(graph, path, longest);
+                    path.pop();
+                }
+            }
+        }
+
+        let mut longest = Vec::new();
+        extend(self, &mut vec![start], &mut longest);
+        longest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, convert::Infallible};
+
+    use async_trait::async_trait;
+
+    use crate::{
+        label::GraphLabel,
+        node::{EventSink, Node, NodeContext},
+        state_graph::{GraphSpec, StateGraph},
+    };
+
+    struct TestSpec;
+    impl GraphSpec for TestSpec {
+        type State = i32;
+        type Update = i32;
+        type Error = Infallible;
+        type Event = ();
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, Hash, GraphLabel)]
+    enum TestLabel {
+        Start,
+        CallModel,
+        ToolExecutor,
+        End,
+        Orphan,
+    }
+
+    struct Noop;
+
+    #[async_trait]
+    impl Node<i32, i32, Infallible, ()> for Noop {
+        async fn run_sync(&self, input: &i32, _: NodeContext<'_>) -> Result<i32, Infallible> {
+            Ok(*input)
+        }
+
+        async fn run_stream(
+            &self,
+            input: &i32,
+            _: &dyn EventSink<()>,
+            _: NodeContext<'_>,
+        ) -> Result<i32, Infallible> {
+            Ok(*input)
+        }
+    }
+
+    fn graph() -> StateGraph<TestSpec> {
+        let mut sg: StateGraph<TestSpec> = StateGraph::new(TestLabel::Start, |s, u| *s = u);
+        for label in [
+            TestLabel::Start,
+            TestLabel::CallModel,
+            TestLabel::ToolExecutor,
+            TestLabel::End,
+            TestLabel::Orphan,
+        ] {
+            sg.add_node(label, Noop);
+        }
+        sg.add_edge(TestLabel::Start, TestLabel::CallModel);
+        sg.add_condition_edge(
+            TestLabel::CallModel,
+            HashMap::from([
+                (
+                    TestLabel::ToolExecutor.intern(),
+                    TestLabel::ToolExecutor.intern(),
+                ),
+                (TestLabel::End.intern(), TestLabel::End.intern()),
+            ]),
+            |_| smallvec::smallvec![TestLabel::End.intern()],
+        );
+        sg.add_edge(TestLabel::ToolExecutor, TestLabel::End);
+        sg
+    }
+
+    #[test]
+    fn acyclic_graph_reports_its_longest_path() {
+        let sg = graph();
+
+        assert!(!sg.has_cycles());
+        let path: Vec<_> = sg
+            .longest_acyclic_path()
+            .iter()
+            .map(|l| l.as_str())
+            .collect();
+        assert_eq!(path, ["Start", "CallModel", "ToolExecutor", "End"]);
+        let reachable: Vec<_> = sg
+            .reachable_from(TestLabel::Start)
+            .iter()
+            .map(|l| l.as_str())
+            .collect();
+        assert_eq!(reachable, ["CallModel", "End", "Start", "ToolExecutor"]);
+    }
+
+    #[test]
+    fn react_loop_is_reported_as_a_cycle() {
+        let mut sg = graph();
+        sg.add_edge(TestLabel::ToolExecutor, TestLabel::CallModel);
+
+        assert!(sg.has_cycles());
+        // 回到模型节点的边不计入最长路径，每个节点只经过一次
+        let path: Vec<_> = sg
+            .longest_acyclic_path()
+            .iter()
+            .map(|l| l.as_str())
+            .collect();
+        assert_eq!(path, ["Start", "CallModel", "ToolExecutor", "End"]);
+        let from_tools: Vec<_> = sg
+            .reachable_from(TestLabel::ToolExecutor)
+            .iter()
+            .map(|l| l.as_str())
+            .collect();
+        assert_eq!(from_tools, ["CallModel", "End", "ToolExecutor"]);
+    }
+}
@@ -185,10 +185,10 @@ where
         // 排序
         match query.order {
             CheckpointOrder::Desc => {
-                results.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+                results.sort_by_key(|b| std::cmp::Reverse(b.created_at));
             }
             CheckpointOrder::Asc => {
-                results.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+                results.sort_by_key(|a| a.created_at);
             }
         }
 
@@ -251,6 +251,15 @@ where
         Ok(None)
     }
 
+    async fn get_metadata_id_by_thread_id(&self, thread_id: &str) -> Option<String> {
+        // 只读元数据索引，不反序列化检查点
+        let index = self.metadata_index.read().await;
+        index
+            .get(thread_id)
+            .and_then(|metadatas| metadatas.last())
+            .map(|metadata| metadata.id.clone())
+    }
+
     async fn get_history(
         &self,
         checkpoint_id: &CheckpointId,
@@ -705,10 +705,10 @@ where
         // 排序
         match query.order {
             CheckpointOrder::Desc => {
-                results.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+                results.sort_by_key(|b| std::cmp::Reverse(b.created_at));
             }
             CheckpointOrder::Asc => {
-                results.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+                results.sort_by_key(|a| a.created_at);
             }
         }
 
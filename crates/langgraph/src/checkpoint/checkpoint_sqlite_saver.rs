@@ -1198,7 +1198,8 @@ mod tests {
             .unwrap();
         assert_eq!(deleted, 0);
 
-        // 清理 0 天前的（应删除所有）
+        // 清理 0 天前的（应删除所有）；截止时刻不含本身，等过了写入的那一毫秒再清理
+        tokio::time::sleep(tokio::time::Duration::from_millis(2)).await;
         let policy_zero = CleanupPolicy::KeepDays(0);
         let deleted_zero = Checkpointer::<TestState>::cleanup(&saver, &policy_zero)
             .await
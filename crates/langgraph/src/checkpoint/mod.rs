@@ -36,6 +36,12 @@ pub struct Configuration {
     pub thread_id: Option<String>,
     /// 响应格式
     pub response_format: Option<ResponseFormat>,
+    /// 本次运行可用的工具名称，`None` 表示使用构建时注册的全部工具
+    pub tools: Option<Vec<String>>,
+    /// 整次运行的截止时间；到达时正在执行的节点被取消，运行停在该 super-step 之前
+    pub deadline: Option<tokio::time::Instant>,
+    /// 系统提示词模板的变量，运行开始新对话时用于渲染提示词；子图和工作者 Agent 沿用同一组变量
+    pub vars: HashMap<String, String>,
 }
 
 /// 检查点 ID（唯一标识-uuidv7）
@@ -4,6 +4,9 @@ use smallvec::SmallVec;
 /// 条件边的条件函数，输入为节点输入，输出为后继节点标签列表
 pub type EdgeCondition<S> = Box<dyn Fn(&S) -> SmallVec<[InternedGraphLabel; 2]> + Send + Sync>;
 
+/// 扇出边的路由函数，输入为当前状态，直接输出运行时计算出的后继节点标签列表
+pub type EdgeRouter<S> = Box<dyn Fn(&S) -> Vec<InternedGraphLabel> + Send + Sync>;
+
 pub enum Edge<S> {
     /// 普通边，直接连接两个节点
     NodeEdge(InternedGraphLabel),
@@ -16,4 +19,6 @@ pub enum Edge<S> {
         next_nodes: SmallVec<[(InternedGraphLabel, InternedGraphLabel); 4]>,
         condition: EdgeCondition<S>,
     },
+    /// 扇出边，目标节点在运行时由路由函数给出，可同时激活多个节点
+    FanOutEdge { router: EdgeRouter<S> },
 }
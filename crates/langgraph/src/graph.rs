@@ -1,17 +1,36 @@
 use async_stream::stream;
 use smallvec::SmallVec;
 use std::fmt::Debug;
+use std::time::Instant;
 use std::{collections::HashMap, marker::PhantomData};
 use thiserror::Error;
 use tokio::sync::mpsc;
+use tracing::{Instrument, Span, field};
 
 use crate::{
-    edge::{Edge, EdgeCondition},
+    edge::{Edge, EdgeCondition, EdgeRouter},
     event::GraphEvent,
     label::{GraphLabel, InternedGraphLabel, IntoGraphNodeArray},
     node::{EventStream, Node, NodeContext, NodeState},
 };
 
+/// 每次节点执行所在的 span 名称
+///
+/// 字段：`node`（节点标签）、`step`（super-step 序号）、`thread_id`（有时记录）、
+/// `duration_ms`（节点结束时记录）。
+pub const NODE_SPAN: &str = "graph.node";
+
+/// 节点执行的 span，见 [`NODE_SPAN`]
+fn node_span(label: InternedGraphLabel, context: &NodeContext<'_>) -> Span {
+    tracing::info_span!(
+        NODE_SPAN,
+        node = label.as_str(),
+        step = context.step,
+        thread_id = context.config.thread_id.as_deref(),
+        duration_ms = field::Empty,
+    )
+}
+
 #[derive(Default)]
 pub struct Graph<S: Clone + Default, I, O, E, Ev: std::fmt::Debug> {
     pub nodes: HashMap<InternedGraphLabel, NodeState<S, I, O, E, Ev>>,
@@ -107,6 +126,31 @@ impl<S: Clone + Default, I, O, E: std::fmt::Debug, Ev: std::fmt::Debug> Graph<S,
         Ok(())
     }
 
+    /// 添加一个扇出边到图中，目标节点在运行时由 `router` 根据状态给出
+    ///
+    /// 与条件边不同，扇出边的目标无需预先声明，适合 map-reduce 等需要按数据动态
+    /// 激活多个节点的场景。返回的节点会在同一个 super-step 中并行执行。
+    pub fn try_add_node_fan_out_edge<F>(
+        &mut self,
+        pred_node: impl GraphLabel,
+        router: F,
+    ) -> Result<(), GraphError<E>>
+    where
+        F: Fn(&S) -> Vec<InternedGraphLabel> + Send + Sync + 'static,
+    {
+        let router: EdgeRouter<S> = Box::new(router);
+        let pred_node_state = self.get_node_state_mut(pred_node)?;
+        pred_node_state.edges.push(Edge::FanOutEdge { router });
+        Ok(())
+    }
+
+    pub fn add_node_fan_out_edge<F>(&mut self, pred_node: impl GraphLabel, router: F)
+    where
+        F: Fn(&S) -> Vec<InternedGraphLabel> + Send + Sync + 'static,
+    {
+        self.try_add_node_fan_out_edge(pred_node, router).unwrap();
+    }
+
     /// 添加一个边到图中，保证 `pred_node` 是 `next_node` 的前继
     pub fn add_node_edge(&mut self, pred_node: impl GraphLabel, next_node: impl GraphLabel) {
         self.try_add_node_edge(pred_node, next_node).unwrap();
@@ -150,11 +194,20 @@ impl<S: Clone + Default, I, O, E: std::fmt::Debug, Ev: std::fmt::Debug> Graph<S,
             .get(&current)
             .ok_or_else(|| GraphError::InvalidNode(current))?;
 
+        let step = context.step;
+        let span = node_span(current, &context);
+        let started = Instant::now();
         let output = state
             .node
             .run_sync(input, context)
-            .await
-            .map_err(GraphError::NodeRunError)?;
+            .instrument(span.clone())
+            .await;
+        span.record("duration_ms", started.elapsed().as_millis() as u64);
+        let output = output.map_err(|error| GraphError::NodeRunError {
+            node: current,
+            step,
+            error,
+        })?;
 
         // let next_nodes = self.get_next_nodes(state, &output);
 
@@ -183,6 +236,8 @@ impl<S: Clone + Default, I, O, E: std::fmt::Debug, Ev: std::fmt::Debug> Graph<S,
             .ok_or_else(|| GraphError::InvalidNode(current))?;
 
         let label = node_state.label;
+        let step = context.step;
+        let span = node_span(label, &context);
 
         struct ChannelSink<Ev> {
             tx: mpsc::Sender<Ev>,
@@ -201,7 +256,11 @@ impl<S: Clone + Default, I, O, E: std::fmt::Debug, Ev: std::fmt::Debug> Graph<S,
             let (tx, mut rx) = mpsc::channel(100);
             let sink = ChannelSink { tx };
 
-            let mut run_future = node_state.node.run_stream(input, &sink, context);
+            let started = Instant::now();
+            let mut run_future = node_state
+                .node
+                .run_stream(input, &sink, context)
+                .instrument(span.clone());
 
             let output_result;
 
@@ -217,6 +276,7 @@ impl<S: Clone + Default, I, O, E: std::fmt::Debug, Ev: std::fmt::Debug> Graph<S,
                 }
             }
 
+            span.record("duration_ms", started.elapsed().as_millis() as u64);
             // Drop future and sink to close the channel
             drop(run_future);
             drop(sink);
@@ -234,7 +294,7 @@ impl<S: Clone + Default, I, O, E: std::fmt::Debug, Ev: std::fmt::Debug> Graph<S,
                     }
                     Err(e) => {
                         tracing::error!("node {:?} error", label);
-                        yield Err(GraphError::NodeRunError(e));
+                        yield Err(GraphError::NodeRunError { node: label, step, error: e });
                     }
                 }
             }
@@ -243,6 +303,88 @@ impl<S: Clone + Default, I, O, E: std::fmt::Debug, Ev: std::fmt::Debug> Graph<S,
         Ok(EventStream::new(stream))
     }
 
+    /// 在运行前检查图结构
+    ///
+    /// - 入口节点必须已注册
+    /// - 普通边与条件边分支的目标必须已注册
+    /// - 所有节点都必须能从入口到达
+    /// - 至少存在一条能结束的路径：可到达的节点中有没有出边的节点，或有条件边/扇出边（可能返回空）
+    ///
+    /// 扇出边的目标在运行时才能确定，存在可到达的扇出边时跳过可达性检查。
+    pub fn validate(&self, entry: InternedGraphLabel) -> Result<(), GraphError<E>> {
+        if !self.nodes.contains_key(&entry) {
+            return Err(GraphError::MissingEntry(entry));
+        }
+
+        // 按名称排序，保证多个错误时报告的是同一个
+        let mut labels: Vec<_> = self.nodes.keys().copied().collect();
+        labels.sort_by_key(|l| l.as_str());
+
+        for from in &labels {
+            for edge in &self.nodes[from].edges {
+                match edge {
+                    Edge::NodeEdge(to) if !self.nodes.contains_key(to) => {
+                        return Err(GraphError::DanglingEdge {
+                            from: *from,
+                            to: *to,
+                        });
+                    }
+                    Edge::ConditionalEdge { next_nodes, .. } => {
+                        if let Some((branch, to)) = next_nodes
+                            .iter()
+                            .find(|(_, to)| !self.nodes.contains_key(to))
+                        {
+                            return Err(GraphError::InvalidBranchTarget {
+                                from: *from,
+                                branch: *branch,
+                                to: *to,
+                            });
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let mut reachable = std::collections::HashSet::from([entry]);
+        let mut queue = vec![entry];
+        let mut has_fan_out = false;
+        let mut has_exit = false;
+        while let Some(label) = queue.pop() {
+            let edges = &self.nodes[&label].edges;
+            if edges.is_empty() {
+                has_exit = true;
+            }
+            for edge in edges {
+                let targets: Vec<_> = match edge {
+                    Edge::NodeEdge(to) => vec![*to],
+                    Edge::ConditionalEdge { next_nodes, .. } => {
+                        has_exit = true;
+                        next_nodes.iter().map(|(_, to)| *to).collect()
+                    }
+                    Edge::FanOutEdge { .. } => {
+                        has_exit = true;
+                        has_fan_out = true;
+                        Vec::new()
+                    }
+                };
+                for to in targets {
+                    if reachable.insert(to) {
+                        queue.push(to);
+                    }
+                }
+            }
+        }
+
+        if !has_fan_out && let Some(label) = labels.iter().find(|l| !reachable.contains(l)) {
+            return Err(GraphError::UnreachableNode(*label));
+        }
+        if !has_exit {
+            return Err(GraphError::NoEnd);
+        }
+        Ok(())
+    }
+
     pub fn get_next_nodes(
         &self,
         node_state: &NodeState<S, I, O, E, Ev>,
@@ -264,6 +406,7 @@ impl<S: Clone + Default, I, O, E: std::fmt::Debug, Ev: std::fmt::Debug> Graph<S,
                         }
                     }
                 }
+                Edge::FanOutEdge { router } => next_nodes.extend((router)(state)),
             }
         }
         next_nodes
@@ -280,13 +423,52 @@ pub enum GraphError<E> {
     #[error("edge {0:?} already exists")]
     EdgeAlreadyExists(InternedGraphLabel),
 
-    /// Node run Error
-    #[error("node {0:?} run error")]
-    NodeRunError(E),
+    /// 节点执行失败，`node` 为失败的节点，`step` 为失败时的 super-step 序号（从 0 开始）
+    #[error("node {node:?} failed at step {step}: {error:?}")]
+    NodeRunError {
+        node: InternedGraphLabel,
+        step: usize,
+        error: E,
+    },
 
     /// 事件不存在
     #[error("no event")]
     NoEvent,
+
+    /// 入口节点未注册
+    #[error("entry node {0:?} does not exist")]
+    MissingEntry(InternedGraphLabel),
+
+    /// 边指向未注册的节点
+    #[error("edge {from:?} -> {to:?} points at a node that does not exist")]
+    DanglingEdge {
+        from: InternedGraphLabel,
+        to: InternedGraphLabel,
+    },
+
+    /// 条件边的分支指向未注册的节点
+    #[error("branch {branch:?} of conditional edge from {from:?} points at missing node {to:?}")]
+    InvalidBranchTarget {
+        from: InternedGraphLabel,
+        branch: InternedGraphLabel,
+        to: InternedGraphLabel,
+    },
+
+    /// 节点无法从入口到达
+    #[error("node {0:?} is unreachable from the entry")]
+    UnreachableNode(InternedGraphLabel),
+
+    /// 从入口出发的所有路径都无法结束
+    #[error("graph has no reachable end node, every path loops forever")]
+    NoEnd,
+
+    /// 子图没有运行到结束（达到步数上限、截止时间，或遇到多个后继节点），
+    /// `pending` 为尚未执行的节点
+    #[error("subgraph `{name}` stopped before {pending:?} without finishing")]
+    SubgraphUnfinished {
+        name: String,
+        pending: Vec<InternedGraphLabel>,
+    },
 }
 
 #[cfg(test)]
@@ -608,4 +790,59 @@ mod tests {
         assert_eq!(output, 1);
         assert_eq!(next, vec![TestLabel::B.intern()]);
     }
+
+    fn new_graph() -> Graph<i32, i32, i32, Infallible, ()> {
+        let mut graph = Graph {
+            nodes: HashMap::new(),
+            marker: PhantomData,
+        };
+        graph.add_node(TestLabel::A, IncNode);
+        graph.add_node(TestLabel::B, IncNode);
+        graph
+    }
+
+    #[test]
+    fn validate_accepts_well_formed_graph() {
+        let mut graph = new_graph();
+        graph.add_node_edge(TestLabel::A, TestLabel::B);
+        assert_eq!(graph.validate(TestLabel::A.intern()), Ok(()));
+    }
+
+    #[test]
+    fn validate_reports_structural_errors() {
+        let a = TestLabel::A.intern();
+        let b = TestLabel::B.intern();
+        let c = TestLabel::C.intern();
+
+        let graph = new_graph();
+        assert_eq!(graph.validate(c), Err(GraphError::MissingEntry(c)));
+        assert_eq!(graph.validate(a), Err(GraphError::UnreachableNode(b)));
+
+        let mut graph = new_graph();
+        graph.add_node_edge(TestLabel::A, TestLabel::C);
+        assert_eq!(
+            graph.validate(a),
+            Err(GraphError::DanglingEdge { from: a, to: c })
+        );
+
+        let mut graph = new_graph();
+        graph.add_node_condition_edge(
+            TestLabel::A,
+            HashMap::from([(b, c)]),
+            |_| smallvec::smallvec![],
+        );
+        assert_eq!(
+            graph.validate(a),
+            Err(GraphError::InvalidBranchTarget {
+                from: a,
+                branch: b,
+                to: c
+            })
+        );
+
+        let mut graph = new_graph();
+        graph.add_node_edge(TestLabel::A, TestLabel::B);
+        graph.add_node_edge(TestLabel::B, TestLabel::A);
+        assert_eq!(graph.validate(a), Err(GraphError::NoEnd));
+    }
 }
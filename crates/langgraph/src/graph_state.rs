@@ -0,0 +1,73 @@
+//! 自定义图状态
+//!
+//! [`MessagesState`](langchain_core::state::MessagesState) 手写了更新的合并逻辑；
+//! 其他状态结构体可以用 `#[derive(GraphState)]` 按字段生成：
+//!
+//! - `#[reducer(replace)]`（默认）：更新中的字段为 `Option<T>`，有值时替换原值
+//! - `#[reducer(append)]`：更新中的字段与原字段同类型，合并时 `extend` 到原值末尾
+//! - `#[reducer(add)]`：更新中的字段与原字段同类型，合并时 `+=` 到原值上
+//!
+//! 派生宏同时生成名为 `{状态名}Diff` 的更新类型，所有字段都有默认值，
+//! 节点只需填写要修改的字段：
+//!
+//! ```rust,ignore
+//! #[derive(Debug, Clone, Default, Serialize, Deserialize, GraphState)]
+//! struct CounterState {
+//!     #[reducer(add)]
+//!     count: u32,
+//!     #[reducer(append)]
+//!     items: Vec<String>,
+//!     last: String,
+//! }
+//!
+//! let diff = CounterStateDiff { count: 1, ..Default::default() };
+//! ```
+
+pub use langgraph_macro::GraphState;
+
+/// 可以作为图状态的类型：定义节点返回的更新类型以及如何合并
+pub trait GraphState {
+    /// 节点返回的更新，默认值表示不做修改
+    type Diff: Default;
+
+    /// 把更新合并到状态中
+    fn apply(&mut self, diff: Self::Diff);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Default, PartialEq, GraphState)]
+    struct TestState {
+        #[reducer(add)]
+        count: u32,
+        #[reducer(append)]
+        items: Vec<String>,
+        last: String,
+    }
+
+    #[test]
+    fn derived_apply_merges_fields_by_reducer() {
+        let mut state = TestState {
+            count: 1,
+            items: vec!["a".to_owned()],
+            last: "a".to_owned(),
+        };
+
+        state.apply(TestStateDiff {
+            count: 2,
+            items: vec!["b".to_owned(), "c".to_owned()],
+            last: Some("c".to_owned()),
+        });
+        assert_eq!(state.count, 3);
+        assert_eq!(state.items, ["a", "b", "c"]);
+        assert_eq!(state.last, "c");
+
+        // 默认的更新不修改任何字段
+        state.apply(TestStateDiff::default());
+        assert_eq!(state.count, 3);
+        assert_eq!(state.items.len(), 3);
+        assert_eq!(state.last, "c");
+    }
+}
@@ -122,6 +122,7 @@ impl Node<MessagesState, MessagesState, InterruptError, ()> for HumanInTheLoopNo
                     reasoning_content: None,
                     tool_calls: None,
                     name: None,
+                    metadata: Default::default(),
                 });
             }
             InterruptResponse::Input { value } => {
@@ -129,6 +130,7 @@ impl Node<MessagesState, MessagesState, InterruptError, ()> for HumanInTheLoopNo
                 delta.push_message_owned(Message::User {
                     content: Content::Text(value),
                     name: None,
+                    metadata: Default::default(),
                 });
             }
             InterruptResponse::Approve => {
@@ -138,6 +140,7 @@ impl Node<MessagesState, MessagesState, InterruptError, ()> for HumanInTheLoopNo
                     reasoning_content: None,
                     tool_calls: None,
                     name: None,
+                    metadata: Default::default(),
                 });
             }
             InterruptResponse::Reject { reason } => {
@@ -147,6 +150,7 @@ impl Node<MessagesState, MessagesState, InterruptError, ()> for HumanInTheLoopNo
                     reasoning_content: None,
                     tool_calls: None,
                     name: None,
+                    metadata: Default::default(),
                 });
             }
             InterruptResponse::Cancel => {
@@ -0,0 +1,78 @@
+//! ID 生成：线程 id、工具调用 id 的来源
+//!
+//! 默认使用 UUID v4。需要 ULID、自增 id，或者在测试中需要可复现的 id（例如对完整对话做快照测试）时，
+//! 实现 [`IdGenerator`] 并注入到 Agent 中。检查点 id 不经过生成器：检查点存储依赖按时间排序的
+//! UUID v7 来查找线程的最新检查点。
+
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// ID 生成器
+///
+/// 实现必须保证同一个生成器返回的 id 互不相同：持久化的线程 id 重复会让两次运行共享历史；
+/// 同一条助手消息中重复的工具调用 id 会让工具结果无法与调用对应。生成器会被多个并发运行共享，因此需要 `Send + Sync`。
+pub trait IdGenerator: Debug + Send + Sync {
+    /// 生成一个新的 id
+    fn next_id(&self) -> String;
+}
+
+/// 生成 UUID v4，默认的 [`IdGenerator`]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct UuidGenerator;
+
+impl IdGenerator for UuidGenerator {
+    fn next_id(&self) -> String {
+        uuid::Uuid::new_v4().to_string()
+    }
+}
+
+/// 按顺序生成 `{prefix}{n}`（`n` 从 1 开始）的 [`IdGenerator`]
+///
+/// id 只在同一个生成器内唯一，进程重启后会从头开始，适合测试和快照；
+/// 与持久化的检查点存储一起使用时，重启后生成的线程 id 会与已有线程重复。
+#[derive(Debug, Default)]
+pub struct SequentialIdGenerator {
+    prefix: String,
+    next: AtomicU64,
+}
+
+impl SequentialIdGenerator {
+    pub fn new(prefix: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+            next: AtomicU64::new(0),
+        }
+    }
+}
+
+impl IdGenerator for SequentialIdGenerator {
+    fn next_id(&self) -> String {
+        let n = self.next.fetch_add(1, Ordering::Relaxed) + 1;
+        format!("{}{n}", self.prefix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sequential_ids_are_unique_across_threads() {
+        let ids = std::sync::Arc::new(SequentialIdGenerator::new("id-"));
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let ids = ids.clone();
+                std::thread::spawn(move || (0..100).map(|_| ids.next_id()).collect::<Vec<_>>())
+            })
+            .collect();
+        let mut all: Vec<String> = handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect();
+        all.sort();
+        all.dedup();
+        assert_eq!(all.len(), 400);
+        assert!(all.contains(&"id-1".to_owned()));
+        assert!(all.contains(&"id-400".to_owned()));
+    }
+}
@@ -7,7 +7,7 @@ use std::{
     fmt::Debug,
     hash::{Hash, Hasher},
     ptr,
-    sync::LazyLock,
+    sync::{LazyLock, OnceLock},
 };
 
 // 从当前 crate 引入驻留相关的工具类型和 trait
@@ -29,6 +29,41 @@ static GRAPH_LABEL_INTERNER: LazyLock<Interner<dyn GraphLabel>> = LazyLock::new(
 
 pub type InternedGraphLabel = Interned<dyn GraphLabel>;
 
+// 运行时确定的标签名称的驻留器
+// Interner for label names that are only known at runtime
+static LABEL_NAME_INTERNER: LazyLock<Interner<str>> = LazyLock::new(Interner::new);
+
+/// 驻留运行时才确定的标签名称，返回 [`GraphLabel::as_str`] 需要的 `&'static str`
+///
+/// 同一个名称只分配一次，重复构建同一张图（例如每次请求都构建一次）不会不断泄漏内存。
+pub fn intern_label_name(name: &str) -> &'static str {
+    LABEL_NAME_INTERNER.intern(name).0
+}
+
+/// 单个标签值的驻留结果缓存
+///
+/// `#[derive(GraphLabel)]` 为单元结构体和无字段的枚举变体各生成一个静态缓存，
+/// 首次调用 `intern()` 时经过全局驻留器，之后直接返回缓存的结果，
+/// 路由等热路径不再需要加锁并哈希标签。
+#[doc(hidden)]
+pub struct LabelCache(OnceLock<InternedGraphLabel>);
+
+impl LabelCache {
+    pub const fn new() -> Self {
+        Self(OnceLock::new())
+    }
+
+    pub fn get_or_intern(&self, label: &dyn GraphLabel) -> InternedGraphLabel {
+        *self.0.get_or_init(|| GRAPH_LABEL_INTERNER.intern(label))
+    }
+}
+
+impl Default for LabelCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 // const N: 常量泛型，参数化是编译期常量值而不是类型参数
 // const N: constant generic parameter, not a type parameter
 pub trait IntoGraphNodeArray<const N: usize> {
@@ -228,6 +263,31 @@ mod tests {
         assert_eq!(enum_label3.as_str(), "B");
     }
 
+    #[test]
+    fn runtime_label_names_are_allocated_once() {
+        let name = format!("Worker:{}", "researcher");
+        let first = intern_label_name(&name);
+        let second = intern_label_name("Worker:researcher");
+        assert_eq!(first, "Worker:researcher");
+        assert!(std::ptr::eq(first, second));
+    }
+
+    #[test]
+    fn cached_intern_matches_the_global_interner() {
+        // 派生宏生成的缓存与直接经过全局驻留器得到的必须是同一个实例
+        let cached = TestEnumLabel::B.intern();
+        let direct = GRAPH_LABEL_INTERNER.intern(&TestEnumLabel::B as &dyn GraphLabel);
+        assert_eq!(cached, direct);
+        assert_eq!(
+            TestLabel.intern(),
+            GRAPH_LABEL_INTERNER.intern(&TestLabel as &dyn GraphLabel)
+        );
+
+        // 带字段的标签仍然按值驻留
+        assert_eq!(NonZstLabel(7).intern(), NonZstLabel(7).intern());
+        assert_ne!(NonZstLabel(7).intern(), NonZstLabel(8).intern());
+    }
+
     #[test]
     fn test_dyn_eq_across_types() {
         let a_val: i32 = 1;
@@ -1,20 +1,27 @@
 #![cfg_attr(any(docsrs, docsrs_dep), feature(rustdoc_internals))]
 
+pub mod analysis;
 pub mod checkpoint;
 pub mod edge;
 pub mod event;
 pub mod execution_mode;
 pub mod executor;
 pub mod graph;
+pub mod graph_state;
 pub mod hitl_node;
+pub mod id;
 mod intern;
 pub mod interrupt;
 pub mod label;
 pub mod label_registry;
 pub mod node;
 pub mod state_graph;
+pub mod subgraph;
+pub mod visualize;
 
+pub use graph_state::GraphState;
 pub use hitl_node::HumanInTheLoopNode;
+pub use id::{IdGenerator, SequentialIdGenerator, UuidGenerator};
 pub use interrupt::{
     InMemoryInterruptManager, InputType, Interrupt, InterruptError, InterruptManager,
     InterruptReason, InterruptResponse,
@@ -24,3 +31,4 @@ pub use label_registry::{
     clear_registry, contains_label, label_to_str, register_label, register_labels,
     registered_count, str_to_label,
 };
+pub use subgraph::SubgraphNode;
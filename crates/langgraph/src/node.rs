@@ -5,7 +5,10 @@ use std::sync::Arc;
 use async_trait::async_trait;
 use downcast_rs::{Downcast, impl_downcast};
 use futures::Stream;
-use langchain_core::store::BaseStore;
+use langchain_core::{
+    error::{LangChainError, RetryConfig, retry_on_error},
+    store::BaseStore,
+};
 
 use crate::{checkpoint::Configuration, edge::Edge, label::InternedGraphLabel};
 
@@ -15,6 +18,8 @@ pub struct NodeContext<'a> {
     pub store: Option<Arc<dyn BaseStore>>,
     /// 运行时配置
     pub config: &'a Configuration,
+    /// 当前 super-step 的序号（从 0 开始）
+    pub step: usize,
 }
 
 impl<'a> NodeContext<'a> {
@@ -22,10 +27,21 @@ impl<'a> NodeContext<'a> {
         Self {
             store: None,
             config,
+            step: 0,
         }
     }
     pub fn new(store: Option<Arc<dyn BaseStore>>, config: &'a Configuration) -> Self {
-        Self { store, config }
+        Self {
+            store,
+            config,
+            step: 0,
+        }
+    }
+
+    /// 设置当前 super-step 的序号
+    pub fn with_step(mut self, step: usize) -> Self {
+        self.step = step;
+        self
     }
 }
 
@@ -80,6 +96,73 @@ pub trait Node<I, O, E, Ev>: Downcast + Send + Sync + 'static {
 
 impl_downcast!(Node<I, O, E, Ev>);
 
+/// 为节点加上失败重试的包装节点
+///
+/// 节点返回可重试的错误（[`ErrorCategory::Transient`]、[`ErrorCategory::RateLimit`]）时
+/// 按 [`RetryConfig`] 退避后重新执行，其余错误直接返回。
+/// 流式执行时，失败尝试中已经发出的事件不会撤回。
+///
+/// [`ErrorCategory::Transient`]: langchain_core::error::ErrorCategory::Transient
+/// [`ErrorCategory::RateLimit`]: langchain_core::error::ErrorCategory::RateLimit
+pub struct RetryNode<N> {
+    pub inner: N,
+    pub config: RetryConfig,
+}
+
+impl<N> RetryNode<N> {
+    pub fn new(inner: N, config: RetryConfig) -> Self {
+        Self { inner, config }
+    }
+}
+
+#[async_trait]
+impl<I, O, E, Ev, N> Node<I, O, E, Ev> for RetryNode<N>
+where
+    I: Sync + 'static,
+    O: Send + 'static,
+    E: LangChainError + Debug,
+    Ev: 'static,
+    N: Node<I, O, E, Ev>,
+{
+    async fn run_sync(&self, input: &I, context: NodeContext<'_>) -> Result<O, E> {
+        let store = context.store;
+        let config = context.config;
+        let step = context.step;
+        retry_on_error(
+            || {
+                self.inner.run_sync(
+                    input,
+                    NodeContext::new(store.clone(), config).with_step(step),
+                )
+            },
+            &self.config,
+        )
+        .await
+    }
+
+    async fn run_stream(
+        &self,
+        input: &I,
+        sink: &dyn EventSink<Ev>,
+        context: NodeContext<'_>,
+    ) -> Result<O, E> {
+        let store = context.store;
+        let config = context.config;
+        let step = context.step;
+        retry_on_error(
+            || {
+                self.inner.run_stream(
+                    input,
+                    sink,
+                    NodeContext::new(store.clone(), config).with_step(step),
+                )
+            },
+            &self.config,
+        )
+        .await
+    }
+}
+
 /// 节点状态结构体，包含节点的标签、类型名称等元数据和节点实例
 ///
 /// # 类型参数
@@ -4,17 +4,25 @@ use crate::{
     },
     event::GraphEvent,
     graph::{Graph, GraphError},
+    graph_state::GraphState,
     label::{GraphLabel, InternedGraphLabel},
     label_registry::register_label,
-    node::{EventStream, Node, NodeContext},
+    node::{EventStream, Node, NodeContext, RetryNode},
 };
 use futures::future::join_all;
-use langchain_core::store::BaseStore;
+use langchain_core::{
+    error::{LangChainError, RetryConfig},
+    store::BaseStore,
+};
 use serde::{Serialize, de::DeserializeOwned};
 use smallvec::{SmallVec, smallvec};
 use std::fmt::Debug;
+use std::mem;
 use std::sync::Arc;
-use std::{collections::HashMap, marker::PhantomData};
+use std::{
+    collections::{HashMap, HashSet},
+    marker::PhantomData,
+};
 
 /// Reducer 函数类型：接收当前状态的可变引用和更新，原地修改状态
 /// (&mut Current State, Update) -> ()
@@ -74,6 +82,14 @@ impl<Spec: GraphSpec> StateGraph<Spec> {
         }
     }
 
+    /// 从入口节点创建 StateGraph，使用 [`GraphState::apply`] 合并更新
+    pub fn from_state(entry: impl GraphLabel) -> Self
+    where
+        Spec::State: GraphState<Diff = Spec::Update>,
+    {
+        Self::new(entry, |state: &mut Spec::State, update| state.apply(update))
+    }
+
     /// 设置入口节点
     pub fn set_entry(&mut self, entry: impl GraphLabel) {
         self.entry = entry.intern();
@@ -132,6 +148,18 @@ impl<Spec: GraphSpec> StateGraph<Spec> {
         self.graph.add_node(label, node);
     }
 
+    /// 添加带重试策略的节点
+    ///
+    /// 节点返回可重试的错误时按 `config` 退避重试，重试耗尽后才让整个运行失败，
+    /// 见 [`RetryNode`]。
+    pub fn add_node_with_retry<T>(&mut self, label: impl GraphLabel, node: T, config: RetryConfig)
+    where
+        T: Node<Spec::State, Spec::Update, Spec::Error, Spec::Event>,
+        Spec::Error: LangChainError,
+    {
+        self.add_node(label, RetryNode::new(node, config));
+    }
+
     /// 添加边
     pub fn add_edge(&mut self, pred: impl GraphLabel, next: impl GraphLabel) {
         self.graph.add_node_edge(pred, next);
@@ -150,13 +178,123 @@ impl<Spec: GraphSpec> StateGraph<Spec> {
         self.graph
             .add_node_condition_edge(pred, branches, condition);
     }
+
+    /// 检查图结构是否完整，见 [`Graph::validate`]
+    pub fn validate(&self) -> Result<(), GraphError<Spec::Error>> {
+        self.graph.validate(self.entry)
+    }
+
+    /// 图中是否存在环，见 [`Graph::has_cycles`]
+    pub fn has_cycles(&self) -> bool {
+        self.graph.has_cycles()
+    }
+
+    /// 从 `start` 出发可以到达的节点，见 [`Graph::reachable_from`]
+    pub fn reachable_from(&self, start: impl GraphLabel) -> Vec<InternedGraphLabel> {
+        self.graph.reachable_from(start.intern())
+    }
+
+    /// 从入口出发、不重复经过任何节点的最长路径，见 [`Graph::longest_acyclic_path`]
+    pub fn longest_acyclic_path(&self) -> Vec<InternedGraphLabel> {
+        self.graph.longest_acyclic_path(self.entry)
+    }
+
+    /// 导出为 Mermaid flowchart，入口节点以圆角框标出
+    pub fn to_mermaid(&self) -> String {
+        self.graph.to_mermaid(Some(self.entry))
+    }
+
+    /// 导出为 Graphviz DOT，入口节点以双边框标出
+    pub fn to_dot(&self) -> String {
+        self.graph.to_dot(Some(self.entry))
+    }
+
+    /// 添加扇出边
+    /// 路由函数根据当前状态直接返回要激活的节点列表，这些节点会在下一个 super-step 中并行执行。
+    /// 需要配合 [`RunStrategy::Parallel`] 使用，其他策略只会保留其中一个分支。
+    pub fn add_fan_out_edge<F>(&mut self, pred: impl GraphLabel, router: F)
+    where
+        F: Fn(&Spec::State) -> Vec<InternedGraphLabel> + Send + Sync + 'static,
+    {
+        self.graph.add_node_fan_out_edge(pred, router);
+    }
+}
+
+/// 按首次出现的顺序去重
+///
+/// 后继节点的顺序即下一个 super-step 中合并更新的顺序：先按前驱节点的顺序，
+/// 再按边的添加顺序（条件边、扇出边按返回顺序）。
+fn dedup_in_order(nodes: &mut SmallVec<[InternedGraphLabel; 4]>) {
+    let mut seen = HashSet::with_capacity(nodes.len());
+    nodes.retain(|node| seen.insert(*node));
 }
 
 impl<Spec: GraphSpec> StateGraph<Spec>
 where
     Spec::State: Serialize + DeserializeOwned,
 {
+    /// 把一个 super-step 之后的状态保存到 `config.thread_id` 下
+    ///
+    /// `next_nodes` 为空时运行已结束，保存为 [`CheckpointType::Final`](crate::checkpoint::CheckpointType::Final)；
+    /// 否则记录下一步的节点，恢复时从这些节点继续。没有线程 id 或 checkpointer 时什么也不做，
+    /// 保存失败只记录日志。父检查点是线程当前最新的检查点；逐步驱动图时使用
+    /// [`StateGraphRunner::save_checkpoint`]，由运行器记录父检查点。
+    pub async fn save_checkpoint(
+        &self,
+        config: &Configuration,
+        state: &Spec::State,
+        step: usize,
+        next_nodes: &[InternedGraphLabel],
+    ) {
+        self.save_run_checkpoint(config, state, step, next_nodes, &mut None)
+            .await;
+    }
+
+    /// 运行中保存检查点：`parent` 是本次运行上一次保存的检查点 id，保存成功后更新为新检查点的 id
+    ///
+    /// 只有运行中第一次保存（`parent` 为 `None`）时才向 checkpointer 查询线程最新的检查点，
+    /// 之后每步不必再读取上一个检查点。
+    async fn save_run_checkpoint(
+        &self,
+        config: &Configuration,
+        state: &Spec::State,
+        step: usize,
+        next_nodes: &[InternedGraphLabel],
+        parent: &mut Option<String>,
+    ) {
+        let (Some(thread_id), Some(checkpointer)) = (&config.thread_id, &self.checkpointer) else {
+            return;
+        };
+        let parent_id = match parent {
+            Some(id) => Some(id.clone()),
+            None => checkpointer.get_metadata_id_by_thread_id(thread_id).await,
+        };
+        let checkpoint = if next_nodes.is_empty() {
+            Checkpoint::new_final(state.clone(), thread_id.clone(), step, parent_id)
+        } else {
+            Checkpoint::new_auto_with_next_nodes(
+                state.clone(),
+                thread_id.clone(),
+                step,
+                next_nodes.iter().map(|n| n.as_str().to_owned()).collect(),
+                parent_id,
+            )
+        };
+        match checkpointer.put(&checkpoint).await {
+            Ok(()) => *parent = Some(checkpoint.metadata.id),
+            Err(e) => tracing::error!("Failed to save checkpoint: {:?}", e),
+        }
+    }
+
     /// 同步执行
+    ///
+    /// 每个 super-step 中的活跃节点并发执行，全部完成后：
+    /// 1. 按活跃节点的顺序依次把各节点的更新交给 reducer 合并；
+    /// 2. 所有更新合并完成后，再基于合并后的状态计算各节点的后继节点。
+    ///
+    /// 任一节点失败时整个 super-step 失败并返回错误。
+    /// 设置了 [`Configuration::deadline`] 时，到达截止时间的 super-step 被取消，
+    /// 与达到步数上限一样返回停止时的状态和未执行完的节点。
     pub async fn run(
         &self,
         mut state: Spec::State,
@@ -166,6 +304,7 @@ where
         resume_from: Option<SmallVec<[String; 4]>>,
     ) -> Result<(Spec::State, Vec<InternedGraphLabel>), GraphError<Spec::Error>> {
         let mut current_nodes: SmallVec<[InternedGraphLabel; 4]> = smallvec![self.entry];
+        let mut parent = None;
 
         // 优先使用显式传入的恢复点
         if let Some(nodes) = resume_from {
@@ -211,65 +350,53 @@ where
         for step in 0..max_steps {
             // 如果当前没有活跃节点，图执行结束
             if current_nodes.is_empty() {
-                // Save checkpoint at end
-                if let Some(thread_id) = &config.thread_id
-                    && let Some(checkpointer) = &self.checkpointer
-                {
-                    let parent_id = checkpointer.get_metadata_id_by_thread_id(thread_id).await;
-                    let checkpoint =
-                        Checkpoint::new_final(state.clone(), thread_id.clone(), step, parent_id);
-                    if let Err(e) = checkpointer.put(&checkpoint).await {
-                        tracing::error!("Failed to save checkpoint: {:?}", e);
-                    }
-                }
+                self.save_run_checkpoint(config, &state, step, &[], &mut parent)
+                    .await;
                 return Ok((state, current_nodes.into_vec()));
             }
 
             // 1. 并行执行当前步骤的所有活跃节点
             // 这是一个 "Super-step"：所有节点并行运行，然后统一同步
             let futures = current_nodes.iter().map(|&node| {
-                let context = NodeContext::new(self.store.clone(), config);
+                let context = NodeContext::new(self.store.clone(), config).with_step(step);
                 self.graph.run_once(node, &state, context)
             });
 
-            let results = join_all(futures).await;
+            // 到达截止时间时丢弃本 super-step 的执行，把这些节点作为待执行节点返回
+            let results = match config.deadline {
+                Some(deadline) => {
+                    match tokio::time::timeout_at(deadline, join_all(futures)).await {
+                        Ok(results) => results,
+                        Err(_) => {
+                            tracing::warn!("Deadline reached before {:?} finished", current_nodes);
+                            return Ok((state, current_nodes.into_vec()));
+                        }
+                    }
+                }
+                None => join_all(futures).await,
+            };
 
             // 2. 收集结果并应用 Reducer
             // 注意：虽然执行是并行的，但 Reducer 的应用是顺序的（按节点顺序）
             // 这保证了确定性。如果用户需要特定的合并逻辑，应该在 reducer 内部处理。
-            let mut all_next_nodes: SmallVec<[InternedGraphLabel; 4]> = SmallVec::new();
-
+            let mut finished = Vec::with_capacity(results.len());
             for result in results {
                 let (update, node_state) = result?;
                 (self.reducer)(&mut state, update);
-                let next = self.graph.get_next_nodes(node_state, &state);
-                all_next_nodes.extend(next);
+                finished.push(node_state);
             }
 
             // 3. 决定下一轮的活跃节点
+            // 所有更新合并后再计算，保证条件边看到的是本轮完整的状态
+            let mut all_next_nodes: SmallVec<[InternedGraphLabel; 4]> = SmallVec::new();
+            for node_state in finished {
+                all_next_nodes.extend(self.graph.get_next_nodes(node_state, &state));
+            }
             // 去重，防止同一节点被多次触发
-            all_next_nodes.sort_unstable();
-            all_next_nodes.dedup();
+            dedup_in_order(&mut all_next_nodes);
 
-            if let Some(thread_id) = &config.thread_id
-                && let Some(checkpointer) = &self.checkpointer
-            {
-                let next_node_strs = all_next_nodes
-                    .iter()
-                    .map(|n| n.as_str().to_owned())
-                    .collect();
-                let parent_id = checkpointer.get_metadata_id_by_thread_id(thread_id).await;
-                let checkpoint = Checkpoint::new_auto_with_next_nodes(
-                    state.clone(),
-                    thread_id.clone(),
-                    step,
-                    next_node_strs,
-                    parent_id,
-                );
-                if let Err(e) = checkpointer.put(&checkpoint).await {
-                    tracing::error!("Failed to save checkpoint: {:?}", e);
-                }
-            }
+            self.save_run_checkpoint(config, &state, step, &all_next_nodes, &mut parent)
+                .await;
 
             if all_next_nodes.is_empty() {
                 return Ok((state, Vec::new()));
@@ -309,6 +436,7 @@ where
         use futures::StreamExt;
 
         let mut current_nodes: SmallVec<[InternedGraphLabel; 4]> = smallvec![self.entry];
+        let mut parent = None;
         let graph = &self.graph;
         let reducer = &self.reducer;
         let checkpointer = &self.checkpointer;
@@ -354,16 +482,7 @@ where
             for step in 0..max_steps {
                 if current_nodes.is_empty() {
                     // End of graph, save final state
-                    if let Some(thread_id) = &config.thread_id && let Some(checkpointer) = checkpointer {
-                        let parent_id = checkpointer
-                            .get_metadata_id_by_thread_id(thread_id)
-                            .await;
-                        let checkpoint =
-                            Checkpoint::new_final(state.clone(), thread_id.clone(), step, parent_id);
-                        if let Err(e) = checkpointer.put(&checkpoint).await {
-                            tracing::error!("Failed to save checkpoint: {:?}", e);
-                        }
-                        }
+                    self.save_run_checkpoint(config, &state, step, &[], &mut parent).await;
                     break;
                 }
 
@@ -371,22 +490,7 @@ where
                 let should_interrupt = current_nodes.iter().any(|n| self.interrupt_before.contains(n));
                 if should_interrupt {
                     tracing::info!("Interrupting before nodes: {:?}", current_nodes);
-                    if let Some(thread_id) = &config.thread_id && let Some(checkpointer) = checkpointer {
-                        let next_node_strs = current_nodes.iter().map(|n| n.as_str().to_owned()).collect();
-                        let parent_id = checkpointer
-                            .get_metadata_id_by_thread_id(thread_id)
-                            .await;
-                        let checkpoint = Checkpoint::new_auto_with_next_nodes(
-                            state.clone(),
-                            thread_id.clone(),
-                            step,
-                            next_node_strs,
-                            parent_id,
-                        );
-                        if let Err(e) = checkpointer.put(&checkpoint).await {
-                            tracing::error!("Failed to save checkpoint: {:?}", e);
-                        }
-                    }
+                    self.save_run_checkpoint(config, &state, step, &current_nodes, &mut parent).await;
                     break;
                 }
 
@@ -396,7 +500,7 @@ where
 
                 let mut streams = Vec::new();
                 for &node in &current_nodes {
-                    let context = NodeContext::new(store.clone(), config);
+                    let context = NodeContext::new(store.clone(), config).with_step(step);
                     match graph.run_stream(node, &state, context).await {
                         Ok(s) => streams.push(s),
                         Err(e) => {
@@ -416,10 +520,11 @@ where
                     match event_result {
                         Ok(event) => match event {
                             GraphEvent::NodeEnd {
+                                label,
                                 output,
                                 ..
                             } => {
-                                updates.push(output);
+                                updates.push((label, output));
                             }
                             GraphEvent::Streaming { event, .. } => {
                                 yield event;
@@ -436,8 +541,9 @@ where
                 // 必须显式 drop combined_stream，因为它持有 state 的借用
                 drop(combined_stream);
 
-                // 2. 本轮结束，应用所有 updates
-                for update in updates {
+                // 2. 本轮结束，按活跃节点的顺序（而不是完成顺序）应用所有 updates，与 run 保持一致
+                updates.sort_by_key(|(label, _)| current_nodes.iter().position(|n| n == label));
+                for (_, update) in updates {
                     (reducer)(&mut state, update);
                 }
 
@@ -452,47 +558,15 @@ where
                     }
                 }
 
-                all_next_nodes.sort_unstable();
-                all_next_nodes.dedup();
+                dedup_in_order(&mut all_next_nodes);
 
                 // Save Checkpoint
-                if let Some(thread_id) = &config.thread_id && let Some(checkpointer) = checkpointer {
-                        let next_node_strs = all_next_nodes.iter().map(|n| n.as_str().to_owned()).collect();
-                        let parent_id = checkpointer
-                            .get_metadata_id_by_thread_id(thread_id)
-                            .await;
-                        let checkpoint = Checkpoint::new_auto_with_next_nodes(
-                            state.clone(),
-                            thread_id.clone(),
-                            step,
-                            next_node_strs,
-                            parent_id,
-                        );
-                        if let Err(e) = checkpointer.put(&checkpoint).await {
-                            tracing::error!("Failed to save checkpoint: {:?}", e);
-                        }
-                    }
+                self.save_run_checkpoint(config, &state, step, &all_next_nodes, &mut parent).await;
 
                 // check interrupt_after
                 let should_interrupt_after = current_nodes.iter().any(|n| self.interrupt_after.contains(n));
                 if should_interrupt_after {
                     tracing::info!("Interrupting after nodes: {:?}", current_nodes);
-                    if let Some(thread_id) = &config.thread_id && let Some(checkpointer) = checkpointer {
-                        let next_node_strs = all_next_nodes.iter().map(|n| n.as_str().to_owned()).collect();
-                        let parent_id = checkpointer
-                            .get_metadata_id_by_thread_id(thread_id)
-                            .await;
-                        let checkpoint = Checkpoint::new_auto_with_next_nodes(
-                            state.clone(),
-                            thread_id.clone(),
-                            step,
-                            next_node_strs,
-                            parent_id,
-                        );
-                        if let Err(e) = checkpointer.put(&checkpoint).await {
-                            tracing::error!("Failed to save checkpoint: {:?}", e);
-                        }
-                    }
                     break;
                 }
 
@@ -525,10 +599,38 @@ where
 }
 
 /// StateGraph 运行器（用于逐步执行）
+///
+/// 面向调试器、检查器等工具：每个 super-step 拆成 [`execute`](Self::execute) 与
+/// [`commit`](Self::commit) 两个阶段，两者之间可以通过引用同时查看执行前的状态
+/// 和待合并的更新，合并后再查看新状态，整个过程不需要克隆状态。
+///
+/// 后继节点的选择等价于 [`RunStrategy::Parallel`]；运行器不读写 checkpoint。
+/// [`run_until_interrupt`](Self::run_until_interrupt) 按图上配置的中断点暂停。
+///
+/// ```rust,ignore
+/// let mut runner = StateGraphRunner::new(&graph, initial);
+/// while !runner.is_finished() {
+///     println!("next: {:?}", runner.peek_next());
+///     let before = format!("{:?}", runner.state());
+///     for (label, update) in runner.execute(&config).await? {
+///         println!("{label:?} -> {update:?}");
+///     }
+///     runner.commit();
+///     println!("{before} => {:?}", runner.state());
+/// }
+/// ```
 pub struct StateGraphRunner<'g, Spec: GraphSpec> {
     pub state_graph: &'g StateGraph<Spec>,
     pub current_nodes: Vec<InternedGraphLabel>,
     pub state: Spec::State,
+    /// 已执行、尚未合并的更新，按活跃节点的顺序排列
+    pending: Vec<(InternedGraphLabel, Spec::Update)>,
+    /// 已完成的 super-step 数
+    steps: usize,
+    /// 上次因 `interrupt_before` 暂停，恢复时不再对同一批节点中断
+    interrupted_before: bool,
+    /// 本次运行最后保存的检查点 id，作为下一个检查点的父检查点
+    checkpoint_id: Option<String>,
 }
 
 impl<'g, Spec: GraphSpec> StateGraphRunner<'g, Spec> {
@@ -538,10 +640,202 @@ impl<'g, Spec: GraphSpec> StateGraphRunner<'g, Spec> {
             state_graph,
             current_nodes: vec![state_graph.entry],
             state: initial_state,
+            pending: Vec::new(),
+            steps: 0,
+            interrupted_before: false,
+            checkpoint_id: None,
+        }
+    }
+
+    /// 下一个 super-step 将要执行的节点
+    pub fn peek_next(&self) -> &[InternedGraphLabel] {
+        &self.current_nodes
+    }
+
+    /// 当前状态；在 `execute` 与 `commit` 之间为合并前的状态
+    pub fn state(&self) -> &Spec::State {
+        &self.state
+    }
+
+    /// 已执行、尚未合并的更新
+    pub fn pending_updates(&self) -> &[(InternedGraphLabel, Spec::Update)] {
+        &self.pending
+    }
+
+    /// 修改尚未合并的更新，例如在 `interrupt_after` 中断时拒绝模型提出的工具调用
+    pub fn pending_updates_mut(&mut self) -> &mut [(InternedGraphLabel, Spec::Update)] {
+        &mut self.pending
+    }
+
+    /// 已完成的 super-step 数
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// 没有活跃节点，也没有待合并的更新时，运行结束
+    pub fn is_finished(&self) -> bool {
+        self.current_nodes.is_empty() && self.pending.is_empty()
+    }
+
+    /// 取出最终状态
+    pub fn into_state(self) -> Spec::State {
+        self.state
+    }
+
+    /// 并发执行当前活跃节点，返回各节点的更新，但不合并到状态中
+    ///
+    /// 上一次执行的更新尚未 `commit` 时不会重复执行，直接返回这些更新。
+    /// 任一节点失败时返回错误，状态与活跃节点保持不变，可以重新执行。
+    pub async fn execute(
+        &mut self,
+        config: &Configuration,
+    ) -> Result<&[(InternedGraphLabel, Spec::Update)], GraphError<Spec::Error>> {
+        if self.pending.is_empty() && !self.current_nodes.is_empty() {
+            let graph = &self.state_graph.graph;
+            let state = &self.state;
+            let futures = self.current_nodes.iter().map(|&node| {
+                let context =
+                    NodeContext::new(self.state_graph.store.clone(), config).with_step(self.steps);
+                graph.run_once(node, state, context)
+            });
+
+            let mut pending = Vec::with_capacity(self.current_nodes.len());
+            for result in join_all(futures).await {
+                let (update, node_state) = result?;
+                pending.push((node_state.label, update));
+            }
+            self.pending = pending;
+        }
+        Ok(&self.pending)
+    }
+
+    /// 按活跃节点的顺序合并待处理的更新，并计算下一步的活跃节点
+    ///
+    /// 返回新的活跃节点，为空表示图执行结束。
+    pub fn commit(&mut self) -> &[InternedGraphLabel] {
+        if self.pending.is_empty() {
+            return &self.current_nodes;
+        }
+
+        let graph = &self.state_graph.graph;
+        let mut finished = Vec::with_capacity(self.pending.len());
+        for (label, update) in self.pending.drain(..) {
+            (self.state_graph.reducer)(&mut self.state, update);
+            finished.push(label);
+        }
+
+        let mut next_nodes: SmallVec<[InternedGraphLabel; 4]> = SmallVec::new();
+        for label in finished {
+            if let Some(node_state) = graph.nodes.get(&label) {
+                next_nodes.extend(graph.get_next_nodes(node_state, &self.state));
+            }
+        }
+        dedup_in_order(&mut next_nodes);
+
+        self.current_nodes = next_nodes.into_vec();
+        self.steps += 1;
+        &self.current_nodes
+    }
+
+    /// 执行并合并一个 super-step，返回新的活跃节点
+    pub async fn step(
+        &mut self,
+        config: &Configuration,
+    ) -> Result<&[InternedGraphLabel], GraphError<Spec::Error>> {
+        self.execute(config).await?;
+        Ok(self.commit())
+    }
+
+    /// 逐步执行直到图结束、遇到中断或执行了 `max_steps` 个 super-step
+    ///
+    /// - 活跃节点中有 [`StateGraph::interrupt_before`] 里的节点时，不执行，返回
+    ///   [`RunnerStatus::InterruptedBefore`]；
+    /// - 执行的节点中有 [`StateGraph::interrupt_after`] 里的节点时，更新保留在
+    ///   [`pending_updates`](Self::pending_updates) 中不合并，返回
+    ///   [`RunnerStatus::InterruptedAfter`]，调用方可以在此审查（或通过
+    ///   [`pending_updates_mut`](Self::pending_updates_mut) 修改）节点的输出。
+    ///
+    /// 中断后再次调用即可恢复：先合并保留的更新，或执行中断前未执行的节点，再继续运行。
+    pub async fn run_until_interrupt(
+        &mut self,
+        config: &Configuration,
+        max_steps: usize,
+    ) -> Result<RunnerStatus, GraphError<Spec::Error>> {
+        let graph = self.state_graph;
+        let mut resuming_before = mem::take(&mut self.interrupted_before);
+        if !self.pending.is_empty() {
+            self.commit();
+        }
+
+        for _ in 0..max_steps {
+            if self.current_nodes.is_empty() {
+                return Ok(RunnerStatus::Finished);
+            }
+            if !mem::take(&mut resuming_before)
+                && self
+                    .current_nodes
+                    .iter()
+                    .any(|n| graph.interrupt_before.contains(n))
+            {
+                self.interrupted_before = true;
+                return Ok(RunnerStatus::InterruptedBefore(self.current_nodes.clone()));
+            }
+
+            self.execute(config).await?;
+            let stopped: Vec<_> = self
+                .pending
+                .iter()
+                .map(|(label, _)| *label)
+                .filter(|label| graph.interrupt_after.contains(label))
+                .collect();
+            if !stopped.is_empty() {
+                return Ok(RunnerStatus::InterruptedAfter(stopped));
+            }
+            self.commit();
         }
+
+        Ok(if self.current_nodes.is_empty() {
+            RunnerStatus::Finished
+        } else {
+            RunnerStatus::StepLimit
+        })
     }
 }
 
+impl<Spec: GraphSpec> StateGraphRunner<'_, Spec>
+where
+    Spec::State: Serialize + DeserializeOwned,
+{
+    /// 把当前状态保存为第 `step` 步之后的检查点，当前活跃节点作为下一步的节点
+    ///
+    /// 与 [`StateGraph::save_checkpoint`] 相同，但由运行器记录本次运行上一次保存的检查点 id
+    /// 作为父检查点，只在第一次保存时查询 checkpointer。
+    pub async fn save_checkpoint(&mut self, config: &Configuration, step: usize) {
+        self.state_graph
+            .save_run_checkpoint(
+                config,
+                &self.state,
+                step,
+                &self.current_nodes,
+                &mut self.checkpoint_id,
+            )
+            .await;
+    }
+}
+
+/// [`StateGraphRunner::run_until_interrupt`] 返回时运行器所处的状态
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RunnerStatus {
+    /// 图执行结束
+    Finished,
+    /// 即将执行这些节点，它们在 `interrupt_before` 中，尚未执行
+    InterruptedBefore(Vec<InternedGraphLabel>),
+    /// 这些节点在 `interrupt_after` 中，已经执行，更新尚未合并
+    InterruptedAfter(Vec<InternedGraphLabel>),
+    /// 达到步数上限，仍有待执行的节点
+    StepLimit,
+}
+
 #[cfg(test)]
 mod tests {
     use async_trait::async_trait;
@@ -786,6 +1080,409 @@ mod tests {
         assert_eq!(final_state, 1);
     }
 
+    #[tokio::test]
+    async fn add_node_with_retry_retries_transient_errors_only() {
+        use langchain_core::error::ModelError;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        struct ModelSpec;
+        impl GraphSpec for ModelSpec {
+            type State = i32;
+            type Update = i32;
+            type Error = ModelError;
+            type Event = ();
+        }
+
+        /// 前 `failures` 次调用返回 `error`，之后成功
+        struct FlakyNode {
+            failures: usize,
+            error: fn() -> ModelError,
+            calls: Arc<AtomicUsize>,
+        }
+
+        #[async_trait]
+        impl Node<i32, i32, ModelError, ()> for FlakyNode {
+            async fn run_sync(
+                &self,
+                input: &i32,
+                _context: NodeContext<'_>,
+            ) -> Result<i32, ModelError> {
+                if self.calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+                    return Err((self.error)());
+                }
+                Ok(*input + 1)
+            }
+
+            async fn run_stream(
+                &self,
+                input: &i32,
+                _sink: &dyn EventSink<()>,
+                context: NodeContext<'_>,
+            ) -> Result<i32, ModelError> {
+                self.run_sync(input, context).await
+            }
+        }
+
+        let retry = RetryConfig {
+            max_retries: 3,
+            initial_delay_ms: 1,
+            max_delay_ms: 1,
+            ..Default::default()
+        };
+        let config = Configuration::default();
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut sg: StateGraph<ModelSpec> =
+            StateGraph::new(TestLabel::A, |state, update| *state = update);
+        sg.add_node_with_retry(
+            TestLabel::A,
+            FlakyNode {
+                failures: 2,
+                error: || ModelError::ServerError(503, "unavailable".to_owned()),
+                calls: calls.clone(),
+            },
+            retry.clone(),
+        );
+        let (final_state, _) = sg
+            .run(0, &config, 1, RunStrategy::PickFirst, None)
+            .await
+            .unwrap();
+        assert_eq!(final_state, 1);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        // 不可重试的错误直接让运行失败
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut sg: StateGraph<ModelSpec> =
+            StateGraph::new(TestLabel::A, |state, update| *state = update);
+        sg.add_node_with_retry(
+            TestLabel::A,
+            FlakyNode {
+                failures: 2,
+                error: || ModelError::InvalidApiKey,
+                calls: calls.clone(),
+            },
+            retry,
+        );
+        let result = sg.run(0, &config, 1, RunStrategy::PickFirst, None).await;
+        assert!(matches!(
+            result,
+            Err(GraphError::NodeRunError {
+                error: ModelError::InvalidApiKey,
+                ..
+            })
+        ));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn node_error_reports_failing_node_and_step() {
+        use langchain_core::error::ModelError;
+
+        struct ModelSpec;
+        impl GraphSpec for ModelSpec {
+            type State = i32;
+            type Update = i32;
+            type Error = ModelError;
+            type Event = ();
+        }
+
+        struct Fail;
+
+        #[async_trait]
+        impl Node<i32, i32, ModelError, ()> for Fail {
+            async fn run_sync(
+                &self,
+                _input: &i32,
+                _context: NodeContext<'_>,
+            ) -> Result<i32, ModelError> {
+                Err(ModelError::InvalidApiKey)
+            }
+
+            async fn run_stream(
+                &self,
+                input: &i32,
+                _sink: &dyn EventSink<()>,
+                context: NodeContext<'_>,
+            ) -> Result<i32, ModelError> {
+                self.run_sync(input, context).await
+            }
+        }
+
+        struct Inc;
+
+        #[async_trait]
+        impl Node<i32, i32, ModelError, ()> for Inc {
+            async fn run_sync(
+                &self,
+                input: &i32,
+                _context: NodeContext<'_>,
+            ) -> Result<i32, ModelError> {
+                Ok(*input + 1)
+            }
+
+            async fn run_stream(
+                &self,
+                input: &i32,
+                _sink: &dyn EventSink<()>,
+                context: NodeContext<'_>,
+            ) -> Result<i32, ModelError> {
+                self.run_sync(input, context).await
+            }
+        }
+
+        let mut sg: StateGraph<ModelSpec> =
+            StateGraph::new(TestLabel::A, |state, update| *state = update);
+        sg.add_node(TestLabel::A, Inc);
+        sg.add_node(TestLabel::B, Inc);
+        sg.add_node(TestLabel::C, Fail);
+        sg.add_edge(TestLabel::A, TestLabel::B);
+        sg.add_edge(TestLabel::B, TestLabel::C);
+        let config = Configuration::default();
+
+        let err = sg
+            .run(0, &config, 10, RunStrategy::PickFirst, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            GraphError::NodeRunError {
+                node,
+                step: 2,
+                error: ModelError::InvalidApiKey,
+            } if node == TestLabel::C.intern()
+        ));
+
+        // 逐步执行时报告的是运行器的步数
+        let mut runner = StateGraphRunner::new(&sg, 0);
+        runner.step(&config).await.unwrap();
+        runner.step(&config).await.unwrap();
+        let err = runner.step(&config).await.unwrap_err();
+        assert!(matches!(
+            err,
+            GraphError::NodeRunError { node, step: 2, .. } if node == TestLabel::C.intern()
+        ));
+    }
+
+    #[tokio::test]
+    async fn runner_exposes_state_and_updates_between_phases() {
+        let mut sg: StateGraph<TestSpec> =
+            StateGraph::new(TestLabel::A, |state, update| *state += update);
+        sg.add_node(TestLabel::A, AddOne);
+        sg.add_node(TestLabel::B, AddOne);
+        sg.add_edge(TestLabel::A, TestLabel::B);
+
+        let config = Configuration::default();
+        let mut runner = StateGraphRunner::new(&sg, 1);
+        assert_eq!(runner.peek_next(), &[TestLabel::A.intern()]);
+
+        let updates = runner.execute(&config).await.unwrap();
+        assert_eq!(updates, &[(TestLabel::A.intern(), 2)]);
+        // 合并前状态不变
+        assert_eq!(*runner.state(), 1);
+
+        assert_eq!(runner.commit(), &[TestLabel::B.intern()]);
+        assert_eq!(*runner.state(), 3);
+        assert!(runner.pending_updates().is_empty());
+
+        assert!(runner.step(&config).await.unwrap().is_empty());
+        assert!(runner.is_finished());
+        assert_eq!(runner.steps(), 2);
+        assert_eq!(runner.into_state(), 7);
+    }
+
+    #[tokio::test]
+    async fn runner_clones_state_only_for_checkpoint_snapshots() {
+        use crate::checkpoint::MemorySaver;
+        use std::cell::Cell;
+
+        thread_local! {
+            static CLONES: Cell<usize> = const { Cell::new(0) };
+        }
+
+        /// 克隆时计数的状态
+        #[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+        struct Counted(i32);
+
+        impl Clone for Counted {
+            fn clone(&self) -> Self {
+                CLONES.with(|clones| clones.set(clones.get() + 1));
+                Self(self.0)
+            }
+        }
+
+        struct CountedSpec;
+        impl GraphSpec for CountedSpec {
+            type State = Counted;
+            type Update = i32;
+            type Error = Infallible;
+            type Event = ();
+        }
+
+        struct Increment;
+
+        #[async_trait]
+        impl Node<Counted, i32, Infallible, ()> for Increment {
+            async fn run_sync(
+                &self,
+                _input: &Counted,
+                _context: NodeContext<'_>,
+            ) -> Result<i32, Infallible> {
+                Ok(1)
+            }
+
+            async fn run_stream(
+                &self,
+                _input: &Counted,
+                _sink: &dyn EventSink<()>,
+                _context: NodeContext<'_>,
+            ) -> Result<i32, Infallible> {
+                Ok(1)
+            }
+        }
+
+        let mut sg: StateGraph<CountedSpec> =
+            StateGraph::new(TestLabel::A, |state: &mut Counted, update| {
+                state.0 += update
+            })
+            .with_checkpointer(MemorySaver::new());
+        sg.add_node(TestLabel::A, Increment);
+        sg.add_node(TestLabel::B, Increment);
+        sg.add_node(TestLabel::C, Increment);
+        sg.add_edge(TestLabel::A, TestLabel::B);
+        sg.add_edge(TestLabel::B, TestLabel::C);
+
+        let run = |config: Configuration| {
+            let sg = &sg;
+            async move {
+                let mut runner = StateGraphRunner::new(sg, Counted(0));
+                while !runner.is_finished() {
+                    let step = runner.steps();
+                    runner.step(&config).await.unwrap();
+                    runner.save_checkpoint(&config, step).await;
+                }
+                runner.into_state()
+            }
+        };
+
+        // 更新原地合并，不保存检查点时状态从不克隆
+        let state = run(Configuration::default()).await;
+        assert_eq!(state.0, 3);
+        assert_eq!(CLONES.with(Cell::get), 0);
+
+        // 只有每步的检查点快照克隆一次，父检查点由运行器依次记录
+        let config = Configuration {
+            thread_id: Some("t".to_owned()),
+            ..Configuration::default()
+        };
+        run(config).await;
+        assert_eq!(CLONES.with(Cell::get), 3);
+        let checkpointer = sg.checkpointer.as_ref().unwrap();
+        let saved = checkpointer.list("t", None).await.unwrap();
+        assert_eq!(saved.len(), 3);
+        assert_eq!(saved[0].parent_id, None);
+        for pair in saved.windows(2) {
+            assert_eq!(pair[1].parent_id.as_ref(), Some(&pair[0].id));
+        }
+    }
+
+    #[tokio::test]
+    async fn runner_interrupt_after_holds_model_output_until_resumed() {
+        use langchain_core::{
+            message::{FunctionCall, Message, ToolCall},
+            state::MessagesState,
+        };
+
+        struct AgentSpec;
+        impl GraphSpec for AgentSpec {
+            type State = MessagesState;
+            type Update = MessagesState;
+            type Error = Infallible;
+            type Event = ();
+        }
+
+        #[derive(Debug, Clone, PartialEq, Eq, Hash, GraphLabel)]
+        enum AgentLabel {
+            CallModel,
+            Tools,
+        }
+
+        /// 返回固定的一条消息
+        struct Reply(fn() -> Message);
+
+        #[async_trait]
+        impl Node<MessagesState, MessagesState, Infallible, ()> for Reply {
+            async fn run_sync(
+                &self,
+                _input: &MessagesState,
+                _context: NodeContext<'_>,
+            ) -> Result<MessagesState, Infallible> {
+                Ok(MessagesState::new(vec![(self.0)()]))
+            }
+
+            async fn run_stream(
+                &self,
+                input: &MessagesState,
+                _sink: &dyn EventSink<()>,
+                context: NodeContext<'_>,
+            ) -> Result<MessagesState, Infallible> {
+                self.run_sync(input, context).await
+            }
+        }
+
+        let mut sg: StateGraph<AgentSpec> = StateGraph::new(
+            AgentLabel::CallModel,
+            |state: &mut MessagesState, update| state.apply(update),
+        )
+        .with_interrupt_after(vec![AgentLabel::CallModel]);
+        sg.add_node(
+            AgentLabel::CallModel,
+            Reply(|| {
+                Message::assistant_with_tool_calls(
+                    "",
+                    vec![ToolCall {
+                        id: "call_1".to_owned(),
+                        type_name: "function".to_owned(),
+                        function: FunctionCall {
+                            name: "delete_files".to_owned(),
+                            arguments: serde_json::json!({}),
+                        },
+                    }],
+                )
+            }),
+        );
+        sg.add_node(
+            AgentLabel::Tools,
+            Reply(|| Message::tool("deleted", "call_1")),
+        );
+        sg.add_edge(AgentLabel::CallModel, AgentLabel::Tools);
+
+        let config = Configuration::default();
+        let mut runner = StateGraphRunner::new(&sg, MessagesState::new(vec![Message::user("hi")]));
+        let status = runner.run_until_interrupt(&config, 10).await.unwrap();
+
+        assert_eq!(
+            status,
+            RunnerStatus::InterruptedAfter(vec![AgentLabel::CallModel.intern()])
+        );
+        // 模型提出的工具调用可供审查，尚未合并到状态中，工具也没有执行
+        let (_, update) = &runner.pending_updates()[0];
+        assert_eq!(
+            update.last_tool_calls().unwrap()[0].function_name(),
+            "delete_files"
+        );
+        assert_eq!(runner.state().messages.len(), 1);
+
+        let status = runner.run_until_interrupt(&config, 10).await.unwrap();
+        assert_eq!(status, RunnerStatus::Finished);
+        let contents: Vec<_> = runner
+            .state()
+            .messages
+            .iter()
+            .map(|m| m.content())
+            .collect();
+        assert_eq!(contents, ["hi", "", "deleted"]);
+    }
+
     #[tokio::test]
     async fn state_graph_run_strategy_parallel() {
         let mut sg: StateGraph<TestSpec> =
@@ -894,4 +1591,80 @@ mod tests {
 
         assert_eq!(final_state, vec!["A", "B", "C", "D", "E"]);
     }
+
+    #[tokio::test]
+    async fn state_graph_fan_out_edge_runs_targets_concurrently_in_order() {
+        #[derive(Debug, Clone, PartialEq, Eq, Hash, GraphLabel)]
+        enum Label {
+            Plan,
+            Worker1,
+            Worker2,
+            Worker3,
+        }
+
+        struct VecSpec;
+        impl GraphSpec for VecSpec {
+            type State = Vec<String>;
+            type Update = String;
+            type Error = Infallible;
+            type Event = ();
+        }
+
+        /// 所有 worker 都到达屏障后才能返回，串行执行会超时
+        struct BarrierNode(&'static str, Arc<tokio::sync::Barrier>);
+        #[async_trait]
+        impl Node<Vec<String>, String, Infallible, ()> for BarrierNode {
+            async fn run_sync(
+                &self,
+                _: &Vec<String>,
+                _context: NodeContext<'_>,
+            ) -> Result<String, Infallible> {
+                self.1.wait().await;
+                Ok(self.0.to_owned())
+            }
+            async fn run_stream(
+                &self,
+                input: &Vec<String>,
+                _: &dyn EventSink<()>,
+                context: NodeContext<'_>,
+            ) -> Result<String, Infallible> {
+                self.run_sync(input, context).await
+            }
+        }
+
+        let barrier = Arc::new(tokio::sync::Barrier::new(3));
+        let mut sg: StateGraph<VecSpec> =
+            StateGraph::new(Label::Plan, |state: &mut Vec<String>, update: String| {
+                if !update.is_empty() {
+                    state.push(update);
+                }
+            });
+        sg.add_node(
+            Label::Plan,
+            BarrierNode("", Arc::new(tokio::sync::Barrier::new(1))),
+        );
+        sg.add_node(Label::Worker1, BarrierNode("w1", barrier.clone()));
+        sg.add_node(Label::Worker2, BarrierNode("w2", barrier.clone()));
+        sg.add_node(Label::Worker3, BarrierNode("w3", barrier));
+
+        sg.add_fan_out_edge(Label::Plan, |_state: &Vec<String>| {
+            vec![
+                Label::Worker3.intern(),
+                Label::Worker1.intern(),
+                Label::Worker2.intern(),
+            ]
+        });
+
+        let config = Configuration::default();
+        let (final_state, _) = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            sg.run(Vec::new(), &config, 10, RunStrategy::Parallel, None),
+        )
+        .await
+        .expect("fan-out targets should run concurrently")
+        .unwrap();
+
+        // 更新按路由函数返回的顺序合并
+        assert_eq!(final_state, vec!["w3", "w1", "w2"]);
+    }
 }
@@ -0,0 +1,296 @@
+//! 子图节点：把一个 [`StateGraph`] 作为另一个图中的节点使用
+//!
+//! 子图以外层节点收到的状态为输入运行到结束，它在这期间新增、替换的消息作为外层节点的
+//! 更新返回，再由外层的 reducer 合并。子图没有运行到结束时节点失败，不返回部分结果。
+//! 监督者、计划执行等组合模式都可以建立在它之上。
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use langchain_core::state::MessagesState;
+
+use crate::{
+    checkpoint::Configuration,
+    graph::GraphError,
+    node::{EventSink, Node, NodeContext},
+    state_graph::{GraphSpec, RunStrategy, StateGraph},
+};
+
+/// 子图单次运行默认的最大 super-step 数
+pub const DEFAULT_SUBGRAPH_MAX_STEPS: usize = 25;
+
+/// 以 [`MessagesState`] 为状态的子图节点
+///
+/// 运行配置会传入子图，其中的 `thread_id` 加上 `:{name}` 后缀，
+/// 这样子图即使与外层图共用同一个 checkpointer，也不会覆盖外层的检查点；
+/// 子图上次在该线程中没有运行完时，再次进入会从它停下的节点继续。
+///
+/// ```rust,ignore
+/// outer.add_node(Label::Research, SubgraphNode::new("research", research_graph));
+/// ```
+pub struct SubgraphNode<Spec: GraphSpec> {
+    pub name: String,
+    pub graph: StateGraph<Spec>,
+    pub max_steps: usize,
+}
+
+impl<Spec: GraphSpec> SubgraphNode<Spec> {
+    pub fn new(name: impl Into<String>, graph: StateGraph<Spec>) -> Self {
+        Self {
+            name: name.into(),
+            graph,
+            max_steps: DEFAULT_SUBGRAPH_MAX_STEPS,
+        }
+    }
+
+    /// 设置子图单次运行的最大 super-step 数，超出时节点以
+    /// [`GraphError::SubgraphUnfinished`] 失败
+    pub fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// 子图使用的运行配置
+    fn config(&self, outer: &Configuration) -> Configuration {
+        Configuration {
+            thread_id: outer
+                .thread_id
+                .as_ref()
+                .map(|thread_id| format!("{thread_id}:{}", self.name)),
+            ..outer.clone()
+        }
+    }
+}
+
+/// 子图最终状态相对输入的差异：新增的消息、被替换的消息、用量与模型调用次数
+fn diff(input: &MessagesState, output: MessagesState) -> MessagesState {
+    let mut delta = MessagesState::default();
+    if output.messages.len() < input.messages.len() {
+        tracing::warn!(
+            "subgraph removed {} messages, which cannot be expressed as an update",
+            input.messages.len() - output.messages.len()
+        );
+    }
+    for (index, message) in output.messages.iter().enumerate() {
+        match input.messages.get(index) {
+            Some(old) if Arc::ptr_eq(old, message) || old == message => {}
+            Some(_) => delta.replacements.push((index, message.clone())),
+            None => delta.push_message(message.clone()),
+        }
+    }
+    delta.usage = output.usage;
+    delta.llm_calls = output.llm_calls;
+    delta
+}
+
+#[async_trait]
+impl<Spec> Node<MessagesState, MessagesState, Spec::Error, Spec::Event> for SubgraphNode<Spec>
+where
+    Spec: GraphSpec<State = MessagesState, Update = MessagesState> + Send + Sync + 'static,
+    Spec::Error: From<GraphError<Spec::Error>>,
+{
+    async fn run_sync(
+        &self,
+        input: &MessagesState,
+        context: NodeContext<'_>,
+    ) -> Result<MessagesState, Spec::Error> {
+        // 用量和调用次数从零开始统计，结束后整体作为更新返回
+        let state = MessagesState {
+            messages: input.messages.clone(),
+            ..Default::default()
+        };
+        let config = self.config(context.config);
+        let (output, pending) = self
+            .graph
+            .run(
+                state,
+                &config,
+                self.max_steps,
+                RunStrategy::StopAtNonLinear,
+                None,
+            )
+            .await?;
+        if !pending.is_empty() {
+            // 子图的进度已保存在它自己的线程下，外层重试该节点时从停下的节点继续
+            tracing::warn!("subgraph `{}` stopped before {:?}", self.name, pending);
+            return Err(GraphError::SubgraphUnfinished {
+                name: self.name.clone(),
+                pending,
+            }
+            .into());
+        }
+        Ok(diff(input, output))
+    }
+
+    async fn run_stream(
+        &self,
+        input: &MessagesState,
+        _sink: &dyn EventSink<Spec::Event>,
+        context: NodeContext<'_>,
+    ) -> Result<MessagesState, Spec::Error> {
+        self.run_sync(input, context).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        checkpoint::{Checkpointer, MemorySaver},
+        label::GraphLabel,
+    };
+    use langchain_core::message::Message;
+
+    #[derive(Debug, PartialEq)]
+    struct TestError(String);
+
+    impl From<GraphError<TestError>> for TestError {
+        fn from(e: GraphError<TestError>) -> Self {
+            match e {
+                GraphError::NodeRunError { error, .. } => error,
+                other => Self(other.to_string()),
+            }
+        }
+    }
+
+    struct TestSpec;
+
+    impl GraphSpec for TestSpec {
+        type State = MessagesState;
+        type Update = MessagesState;
+        type Error = TestError;
+        type Event = ();
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, Hash, GraphLabel)]
+    enum TestLabel {
+        Draft,
+        Review,
+        Ask,
+        Inner,
+        Answer,
+    }
+
+    /// 追加一条固定内容的助手消息
+    struct Say(&'static str);
+
+    #[async_trait]
+    impl Node<MessagesState, MessagesState, TestError, ()> for Say {
+        async fn run_sync(
+            &self,
+            _input: &MessagesState,
+            _context: NodeContext<'_>,
+        ) -> Result<MessagesState, TestError> {
+            let mut delta = MessagesState::new(vec![Message::assistant(self.0)]);
+            delta.llm_calls = 1;
+            Ok(delta)
+        }
+
+        async fn run_stream(
+            &self,
+            input: &MessagesState,
+            _sink: &dyn EventSink<()>,
+            context: NodeContext<'_>,
+        ) -> Result<MessagesState, TestError> {
+            self.run_sync(input, context).await
+        }
+    }
+
+    fn graph(entry: TestLabel) -> StateGraph<TestSpec> {
+        StateGraph::new(entry, |old: &mut MessagesState, update| old.apply(update))
+    }
+
+    #[tokio::test]
+    async fn nested_graph_runs_as_a_single_node() {
+        let saver = Arc::new(MemorySaver::new());
+
+        let mut inner = graph(TestLabel::Draft).with_shared_checkpointer(saver.clone());
+        inner.add_node(TestLabel::Draft, Say("draft"));
+        inner.add_node(TestLabel::Review, Say("reviewed"));
+        inner.add_edge(TestLabel::Draft, TestLabel::Review);
+
+        let mut outer = graph(TestLabel::Ask).with_shared_checkpointer(saver.clone());
+        outer.add_node(TestLabel::Ask, Say("asked"));
+        outer.add_node(TestLabel::Inner, SubgraphNode::new("writer", inner));
+        outer.add_node(TestLabel::Answer, Say("answer"));
+        outer.add_edge(TestLabel::Ask, TestLabel::Inner);
+        outer.add_edge(TestLabel::Inner, TestLabel::Answer);
+        outer.validate().unwrap();
+
+        let config = Configuration {
+            thread_id: Some("t1".to_owned()),
+            ..Default::default()
+        };
+        let (state, pending) = outer
+            .run(
+                MessagesState::new(vec![Message::user("hi")]),
+                &config,
+                10,
+                RunStrategy::StopAtNonLinear,
+                None,
+            )
+            .await
+            .unwrap();
+
+        assert!(pending.is_empty());
+        let contents: Vec<_> = state.messages.iter().map(|m| m.content()).collect();
+        assert_eq!(contents, ["hi", "asked", "draft", "reviewed", "answer"]);
+        assert_eq!(state.llm_calls, 4);
+
+        // 子图的检查点保存在带后缀的线程下，不覆盖外层的检查点
+        let inner_checkpoint: MessagesState =
+            Checkpointer::<MessagesState>::get(&*saver, "t1:writer")
+                .await
+                .unwrap()
+                .unwrap()
+                .state;
+        assert_eq!(inner_checkpoint.messages.len(), 4);
+        let outer_checkpoint = Checkpointer::<MessagesState>::get(&*saver, "t1")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(outer_checkpoint.state.messages.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn hitting_the_inner_step_limit_fails_the_node() {
+        // 内层图在 Draft 和 Review 之间循环，永远不会结束
+        let mut inner = graph(TestLabel::Draft);
+        inner.add_node(TestLabel::Draft, Say("draft"));
+        inner.add_node(TestLabel::Review, Say("reviewed"));
+        inner.add_edge(TestLabel::Draft, TestLabel::Review);
+        inner.add_edge(TestLabel::Review, TestLabel::Draft);
+
+        let mut outer = graph(TestLabel::Inner);
+        outer.add_node(
+            TestLabel::Inner,
+            SubgraphNode::new("writer", inner).with_max_steps(3),
+        );
+        outer.add_node(TestLabel::Answer, Say("answer"));
+        outer.add_edge(TestLabel::Inner, TestLabel::Answer);
+
+        let result = outer
+            .run(
+                MessagesState::new(vec![Message::user("hi")]),
+                &Configuration::default(),
+                10,
+                RunStrategy::StopAtNonLinear,
+                None,
+            )
+            .await;
+        let Err(GraphError::NodeRunError { node, error, .. }) = result else {
+            panic!("expected the subgraph node to fail, got {result:?}");
+        };
+        assert_eq!(node, TestLabel::Inner.intern());
+        assert_eq!(
+            error,
+            TestError(
+                GraphError::<TestError>::SubgraphUnfinished {
+                    name: "writer".to_owned(),
+                    pending: vec![TestLabel::Review.intern()],
+                }
+                .to_string()
+            )
+        );
+    }
+}
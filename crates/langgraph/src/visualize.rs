@@ -0,0 +1,244 @@
+//! 图结构可视化
+//!
+//! 将图导出为 Mermaid 或 Graphviz DOT 文本，粘贴到对应的渲染器即可查看：
+//! - 普通边为实线
+//! - 条件边为带分支名的虚线
+//! - 扇出边的目标在运行时才能确定，渲染为一个标注为 "conditional" 的菱形
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write;
+
+use crate::{edge::Edge, graph::Graph, label::InternedGraphLabel};
+
+/// 与输出格式无关的图结构快照，节点与边的顺序固定，保证输出稳定
+struct Layout {
+    /// (节点 id, 显示名, 是否为入口)
+    nodes: Vec<(String, &'static str, bool)>,
+    /// (起点 id, 终点 id, 分支名)，分支名为 `None` 表示普通边
+    edges: Vec<(String, String, Option<&'static str>)>,
+    /// 扇出边：(起点 id, 菱形节点 id)
+    fan_outs: Vec<(String, String)>,
+}
+
+impl<S: Clone + Default, I, O, E: std::fmt::Debug, Ev: std::fmt::Debug> Graph<S, I, O, E, Ev> {
+    fn layout(&self, entry: Option<InternedGraphLabel>) -> Layout {
+        // 收集所有出现过的标签（包括只作为边目标出现的），按名称排序后分配 id。
+        // 不同枚举可能有同名变体，因此用序号而不是名称作为 id。
+        let mut labels: Vec<InternedGraphLabel> = self.nodes.keys().copied().collect();
+        for node_state in self.nodes.values() {
+            for edge in &node_state.edges {
+                match edge {
+                    Edge::NodeEdge(next) => labels.push(*next),
+                    Edge::ConditionalEdge { next_nodes, .. } => {
+                        labels.extend(next_nodes.iter().map(|(_, next)| *next));
+                    }
+                    Edge::FanOutEdge { .. } => {}
+                }
+            }
+        }
+        let mut seen = HashSet::new();
+        labels.retain(|l| seen.insert(*l));
+        labels.sort_by_key(|l| l.as_str());
+
+        let ids: BTreeMap<_, _> = labels
+            .iter()
+            .enumerate()
+            .map(|(i, label)| (*label, format!("n{i}")))
+            .collect();
+
+        let nodes = labels
+            .iter()
+            .map(|label| (ids[label].clone(), label.as_str(), Some(*label) == entry))
+            .collect();
+
+        let mut edges = Vec::new();
+        let mut fan_outs = Vec::new();
+        for label in &labels {
+            let Some(node_state) = self.nodes.get(label) else {
+                continue;
+            };
+            let from = &ids[label];
+            for (i, edge) in node_state.edges.iter().enumerate() {
+                match edge {
+                    Edge::NodeEdge(next) => edges.push((from.clone(), ids[next].clone(), None)),
+                    Edge::ConditionalEdge { next_nodes, .. } => {
+                        let mut branches: Vec<_> = next_nodes.iter().collect();
+                        branches.sort_by_key(|(branch, next)| (next.as_str(), branch.as_str()));
+                        for (branch, next) in branches {
+                            edges.push((from.clone(), ids[next].clone(), Some(branch.as_str())));
+                        }
+                    }
+                    Edge::FanOutEdge { .. } => {
+                        fan_outs.push((from.clone(), format!("{from}_fan_out{i}")));
+                    }
+                }
+            }
+        }
+
+        Layout {
+            nodes,
+            edges,
+            fan_outs,
+        }
+    }
+
+    /// 导出为 Mermaid flowchart
+    pub fn to_mermaid(&self, entry: Option<InternedGraphLabel>) -> String {
+        let layout = self.layout(entry);
+        let mut out = String::from("flowchart TD\n");
+        for (id, name, is_entry) in &layout.nodes {
+            if *is_entry {
+                let _ = writeln!(out, "    {id}([\"{name}\"])");
+            } else {
+                let _ = writeln!(out, "    {id}[\"{name}\"]");
+            }
+        }
+        for (from, diamond) in &layout.fan_outs {
+            let _ = writeln!(out, "    {diamond}{{\"conditional\"}}");
+            let _ = writeln!(out, "    {from} -.-> {diamond}");
+        }
+        for (from, to, branch) in &layout.edges {
+            match branch {
+                Some(branch) => {
+                    let _ = writeln!(out, "    {from} -.->|{branch}| {to}");
+                }
+                None => {
+                    let _ = writeln!(out, "    {from} --> {to}");
+                }
+            }
+        }
+        out
+    }
+
+    /// 导出为 Graphviz DOT
+    pub fn to_dot(&self, entry: Option<InternedGraphLabel>) -> String {
+        let layout = self.layout(entry);
+        let mut out = String::from("digraph StateGraph {\n");
+        for (id, name, is_entry) in &layout.nodes {
+            if *is_entry {
+                let _ = writeln!(out, "    {id} [label=\"{name}\", peripheries=2];");
+            } else {
+                let _ = writeln!(out, "    {id} [label=\"{name}\"];");
+            }
+        }
+        for (from, diamond) in &layout.fan_outs {
+            let _ = writeln!(out, "    {diamond} [label=\"conditional\", shape=diamond];");
+            let _ = writeln!(out, "    {from} -> {diamond} [style=dashed];");
+        }
+        for (from, to, branch) in &layout.edges {
+            match branch {
+                Some(branch) => {
+                    let _ = writeln!(
+                        out,
+                        "    {from} -> {to} [style=dashed, label=\"{branch}\"];"
+                    );
+                }
+                None => {
+                    let _ = writeln!(out, "    {from} -> {to};");
+                }
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, convert::Infallible};
+
+    use async_trait::async_trait;
+
+    use crate::{
+        label::GraphLabel,
+        node::{EventSink, Node, NodeContext},
+        state_graph::{GraphSpec, StateGraph},
+    };
+
+    struct TestSpec;
+    impl GraphSpec for TestSpec {
+        type State = i32;
+        type Update = i32;
+        type Error = Infallible;
+        type Event = ();
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, Hash, GraphLabel)]
+    enum TestLabel {
+        Start,
+        CallModel,
+        ToolExecutor,
+        End,
+        Fan,
+    }
+
+    #[derive(Debug)]
+    struct Noop;
+
+    #[async_trait]
+    impl Node<i32, i32, Infallible, ()> for Noop {
+        async fn run_sync(&self, input: &i32, _: NodeContext<'_>) -> Result<i32, Infallible> {
+            Ok(*input)
+        }
+
+        async fn run_stream(
+            &self,
+            input: &i32,
+            _: &dyn EventSink<()>,
+            _: NodeContext<'_>,
+        ) -> Result<i32, Infallible> {
+            Ok(*input)
+        }
+    }
+
+    fn react_like_graph() -> StateGraph<TestSpec> {
+        let mut sg: StateGraph<TestSpec> = StateGraph::new(TestLabel::Start, |s, u| *s = u);
+        sg.add_node(TestLabel::Start, Noop);
+        sg.add_node(TestLabel::CallModel, Noop);
+        sg.add_node(TestLabel::ToolExecutor, Noop);
+        sg.add_node(TestLabel::End, Noop);
+        sg.add_node(TestLabel::Fan, Noop);
+
+        sg.add_edge(TestLabel::Start, TestLabel::CallModel);
+        let mut branches = HashMap::new();
+        branches.insert(
+            TestLabel::ToolExecutor.intern(),
+            TestLabel::ToolExecutor.intern(),
+        );
+        branches.insert(TestLabel::End.intern(), TestLabel::End.intern());
+        sg.add_condition_edge(TestLabel::CallModel, branches, |_| {
+            smallvec::smallvec![TestLabel::End.intern()]
+        });
+        sg.add_edge(TestLabel::ToolExecutor, TestLabel::CallModel);
+        sg.add_fan_out_edge(TestLabel::Fan, |_| Vec::new());
+        sg
+    }
+
+    #[test]
+    fn mermaid_contains_nodes_and_edges() {
+        let sg = react_like_graph();
+        let mermaid = sg.to_mermaid();
+
+        assert!(mermaid.starts_with("flowchart TD\n"));
+        for name in ["Start", "CallModel", "ToolExecutor", "End", "Fan"] {
+            assert!(mermaid.contains(&format!("\"{name}\"")), "{mermaid}");
+        }
+        assert!(mermaid.contains("([\"Start\"])"));
+        assert!(mermaid.contains("-.->|ToolExecutor|"));
+        assert!(mermaid.contains("{\"conditional\"}"));
+        // 输出稳定
+        assert_eq!(mermaid, sg.to_mermaid());
+    }
+
+    #[test]
+    fn dot_contains_nodes_and_edges() {
+        let dot = react_like_graph().to_dot();
+
+        assert!(dot.starts_with("digraph StateGraph {\n"));
+        for name in ["Start", "CallModel", "ToolExecutor", "End", "Fan"] {
+            assert!(dot.contains(&format!("label=\"{name}\"")), "{dot}");
+        }
+        assert!(dot.contains("[style=dashed, label=\"End\"]"));
+        assert!(dot.contains("shape=diamond"));
+    }
+}